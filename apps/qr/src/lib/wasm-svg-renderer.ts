@@ -21,6 +21,7 @@ export interface RenderConfig {
     bodyShape?: 'square' | 'dots' | 'rounded' | string;
     eyeFrameShape?: string; // Not implemented in Rust yet
     eyeBallShape?: string; // Not implemented in Rust yet
+    fullMatrix?: boolean; // Draw finder patterns from the matrix instead of injecting custom eyes
 
     // Logo
     logo?: string; // Data URL or URL
@@ -124,7 +125,10 @@ export class WasmSvgRenderer {
         // Generate raw SVG path from Rust
         const ecc = config?.ecc || 'M';
         const mask = (config?.mask === undefined || config?.mask === null) ? -1 : config.mask;
-        let svgString = wasm.generate_svg(content, shapeId, ecc, mask);
+        const fullMatrix = config?.fullMatrix ?? false;
+        let svgString = fullMatrix
+            ? wasm.generate_svg_full(content, shapeId, ecc, mask)
+            : wasm.generate_svg(content, shapeId, ecc, mask);
 
         // 2. Determine Filter Usage
         const useLiquidFilter = config?.effectLiquid ?? false;
@@ -234,8 +238,8 @@ export class WasmSvgRenderer {
         }
 
         // --- 5. Eye Injection (Hybrid Mode) ---
-        // Rust skips finders, we inject them here for max customization
-        const vbMatch = svgString.match(/viewBox="0 0 (\d+) (\d+)"/);
+        // Rust skips finders (unless fullMatrix), we inject them here for max customization
+        const vbMatch = fullMatrix ? null : svgString.match(/viewBox="0 0 (\d+) (\d+)"/);
         if (vbMatch) {
             const size = parseInt(vbMatch[1]);
             // Use shared finder pattern renderer (margin=0 for Rust SVG)
@@ -8,7 +8,7 @@ import { ChatManager, type ChatEvent } from '../p2p/chat';
 import { dmKeyBytes } from './dm';
 import { debugLog, debugWarn, redact } from '../debug';
 
-export type DmState = 'idle' | 'connecting' | 'connected' | 'reconnecting' | 'error';
+export type DmState = 'idle' | 'connecting' | 'connected' | 'reconnecting' | 'left' | 'error';
 
 export type DmEvent =
     | { type: 'status_change'; state: DmState; message?: string }
@@ -57,6 +57,9 @@ export class DmManager {
 
     public destroy() {
         this.clearReconnectTimer();
+        // Tell the friend we left on purpose so they don't keep reconnecting.
+        void this.chatManager?.leave('user-left');
+        this.chatManager = null;
         this.teardownChat();
     }
 
@@ -190,7 +193,7 @@ export class DmManager {
 
         this.chatUnsub = this.chatManager.on((e) => {
             if (e.type === 'disconnected') {
-                this.handleDisconnect();
+                this.handleDisconnect(e.reconnect);
             }
             this.emit({ type: 'chat_event', event: e });
         });
@@ -199,11 +202,13 @@ export class DmManager {
         this.reconnectAttempt = 0;
     }
 
-    private handleDisconnect() {
+    private handleDisconnect(reconnect: boolean) {
         debugLog('[DmManager] handleDisconnect() called');
         this.teardownChat();
 
-        if (this.reconnectAttempt < this.maxReconnectAttempts) {
+        if (!reconnect) {
+            this.emit({ type: 'status_change', state: 'left', message: 'Friend left the chat' });
+        } else if (this.reconnectAttempt < this.maxReconnectAttempts) {
             this.scheduleReconnect('Disconnected. Reconnecting...', { immediate: true });
         } else {
             this.emit({ type: 'status_change', state: 'error', message: 'Connection lost' });
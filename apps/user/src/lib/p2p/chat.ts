@@ -14,15 +14,36 @@ import initWasmP2p, {
     decode_frame_type_v1,
     encode_chat_text_v1,
     encode_file_chunk_v1,
+    encode_file_chunk_compressed_v1,
+    should_compress_file,
+    check_received_file,
+    sanitize_filename,
     encode_file_end_v1,
     encode_file_offer_v1,
     decode_file_accept_id_v1,
     decode_file_reject_v1,
+    decode_goodbye_v1,
     encode_file_accept_v1,
     encode_file_reject_v1,
+    encode_goodbye_v1,
     decrypt_envelope_v1,
-    encrypt_envelope_v1,
+    encrypt_envelope_with_suite_v1,
+    encode_heartbeat_ping_v1,
+    encode_heartbeat_pong_v1,
+    encode_hello_v1,
+    negotiate_hello_v1,
+    negotiate_cipher_suite_v1,
+    seal_file_offer_v1,
+    decode_file_offer_sealed_id_v1,
+    decode_file_reveal_id_v1,
+    encode_file_reveal_v1,
+    open_file_offer_v1,
+    FrameScheduler,
+    PeerClock,
+    type TransferLimiter,
 } from '@holi/wasm-p2p';
+import { ProjectKey, type Outbox } from '@holi/wasm-core';
+import { CryptoPool } from './crypto-pool';
 
 export type ChatMessage =
     | { type: 'text'; id: string; senderId: string; content: string; timestamp: number }
@@ -33,46 +54,178 @@ export type ChatMessage =
 export type ChatEvent =
     | { type: 'message'; message: ChatMessage }
     | { type: 'file_progress'; fileId: string; progress: number }
-    | { type: 'file_received'; fileId: string; filename: string; blob: Blob }
+    | {
+          type: 'file_received';
+          fileId: string;
+          /** Sanitized; the name the file was saved under. */
+          filename: string;
+          /** Typed `application/octet-stream` when `suspicious`. */
+          blob: Blob;
+          /** The data doesn't match the offered name or type, or can run code when opened. */
+          suspicious: boolean;
+      }
     | { type: 'encryption_error'; message: string }
-    | { type: 'disconnected' };
+    | {
+          type: 'disconnected';
+          /** `peer-left` if the peer sent a Goodbye first, else `connection-lost`. */
+          end: SessionEnd;
+          reason?: GoodbyeReason;
+          /** Worth reconnecting: the connection was lost or the peer is restarting. */
+          reconnect: boolean;
+          /** Opaque token from the peer's Goodbye, to present when reconnecting. */
+          resumeTicket?: Uint8Array;
+      };
+
+export type SessionEnd = 'peer-left' | 'connection-lost';
+
+/** Why a peer ended the session, as sent in its Goodbye frame. */
+export type GoodbyeReason =
+    | 'unspecified'
+    | 'user-left'
+    | 'app-closing'
+    | 'idle'
+    | 'protocol-violation'
+    | 'restarting';
 
 export type IncomingFileOffer = {
     id: string;
+    /** Sanitized, safe to show and save under. */
     filename: string;
     mimeType: string;
     size: number;
+    /** Sent as a sealed offer; the receiver already agreed to see its metadata. */
+    sealed?: boolean;
 };
 
+/** FileReject codes, so senders can react without matching reason text. */
+export type FileRejectCode =
+    | 'unspecified'
+    | 'too-large'
+    | 'unsupported-type'
+    | 'user-declined'
+    | 'quota-exceeded'
+    | 'busy';
+
 export type IncomingFileDecision =
     | 'accept'
     | 'reject'
-    | { decision: 'reject'; reason: string };
+    | { decision: 'reject'; reason: string; code?: FileRejectCode; detail?: number };
+
+/** Error an outgoing file offer fails with when the peer rejects it. */
+export type FileRejectedError = Error & {
+    code: FileRejectCode;
+    /** Size limit (bytes), ms until the quota resets, or transfer limit; 0 if none. */
+    detail: number;
+};
 
 export type ChatManagerOptions = {
     maxAutoAcceptBytes?: number;
     onIncomingFileOffer?: (offer: IncomingFileOffer) => Promise<IncomingFileDecision>;
+    /**
+     * Decide on a sealed offer before its filename, type and size are known.
+     * On accept the sender reveals them and `onIncomingFileOffer` decides as usual,
+     * so UIs can ask "accept before seeing the filename" here or only after the reveal.
+     * Defaults to accepting the reveal.
+     */
+    onIncomingSealedFileOffer?: (offer: { id: string }) => Promise<IncomingFileDecision>;
+    /**
+     * Send file offers sealed: only the transfer id is visible until the peer accepts.
+     * Can be overridden per `sendFile` call.
+     */
+    sealFileOffers?: boolean;
     /**
      * Optional session key (32 bytes). If set, all protocol frames are sent as EncryptedEnvelope (0x50).
      * Key negotiation (PAKE/handshake) is intentionally out of scope here.
      */
     sessionKeyBytes?: Uint8Array;
+    /**
+     * Chunk size and in-flight window for file sends, e.g. from `calibrate_transfer()`.
+     * Defaults to 16KB chunks with 256KB buffered.
+     */
+    transferTuning?: { chunkSize: number; window: number };
+    /**
+     * Encrypt outgoing file chunks on this many workers instead of the main thread.
+     * Only used with a session key and when SharedArrayBuffer is available (cross-origin isolated).
+     */
+    cryptoWorkers?: number;
+    /**
+     * Byte share of chat vs file chunks on the channel while both are queued.
+     * Control frames always go first. Defaults to 4:1.
+     */
+    outboundWeights?: { chat: number; bulk: number };
+    /**
+     * Store-and-forward for this peer (wasm-core, initialized by the caller). Texts sent while
+     * the channel is down are queued sealed under the session key, and sent first once a
+     * ChatManager for the same `peerId` opens. Keep it across restarts with `box.sealed(key)`
+     * and `Outbox.open`. Needs `sessionKeyBytes`.
+     */
+    outbox?: { box: Outbox; peerId: string };
+    /**
+     * Abuse limits on files from this peer (wasm-p2p, initialized by the caller). Share one limiter
+     * across sessions so per-peer quotas hold over reconnects. Offers over a limit are rejected,
+     * and a transfer that exceeds one mid-way is dropped with a FileReject.
+     */
+    transferLimits?: { limiter: TransferLimiter; peerId: string };
+    /**
+     * Advertise AES-256-GCM in our Hello; set it only where AES is hardware-accelerated.
+     * Envelopes switch to it once the peer advertises it too. Crypto pool frames stay XChaCha20-Poly1305.
+     */
+    preferAesGcm?: boolean;
 };
 
 type EventHandler = (event: ChatEvent) => void;
 
 const CHUNK_SIZE = 16 * 1024; // 16KB chunks
+// Bytes of a received file checked against its offer (holi_p2p::sniff::SNIFF_LEN).
+const SNIFF_LEN = 512;
+// Largest FileChunk data accepted, stated in our Hello.
+const CHUNK_SIZE_LIMIT = 64 * 1024;
+const MAX_BUFFERED_BYTES = 256 * 1024;
+// Bytes handed to the channel at a time; the rest wait in the outbound
+// scheduler, where chat can still overtake file chunks.
+const CHANNEL_BUFFER_BYTES = 64 * 1024;
+
+// Must match holi_p2p::outbound::Priority
+const PRIORITY_CONTROL = 0;
+const PRIORITY_BULK = 2;
 
 // Heartbeat frames (not part of wasm protocol; simple lightweight app-level control messages).
 // These never surface to UI as chat messages.
+// ping = [0x01, t0], pong = [0x02, t1, t0] (u64 BE ms); legacy pongs are [0x02, t1].
 const HEARTBEAT_PING = 0x01;
 const HEARTBEAT_PONG = 0x02;
+const HEARTBEAT_PING_LEN = 9;
+const HEARTBEAT_PONG_LEN = 17;
+
+// Hello capabilities (holi_p2p::frame::HELLO_CAP_*)
+const HELLO_CAP_AES_256_GCM = 1 << 0;
+const HELLO_CAP_CLOCK_SYNC = 1 << 4;
+const HELLO_CAP_COMPRESSED_CHUNKS = 1 << 6;
+// What this build advertises in its Hello.
+const LOCAL_CAPABILITIES = HELLO_CAP_CLOCK_SYNC | HELLO_CAP_COMPRESSED_CHUNKS;
+
+// Must match holi_p2p::frame::CipherSuite
+const CIPHER_SUITE_XCHACHA20_POLY1305 = 1;
+
+/** What both sides advertised in their Hello frames. */
+type SessionHello = { capabilities: number; maxChunkSize: number | null };
 
 export class ChatManager {
     private channel: RTCDataChannel;
     private projectId: string;
     private options: Required<Pick<ChatManagerOptions, 'maxAutoAcceptBytes'>> &
-        Pick<ChatManagerOptions, 'onIncomingFileOffer'>;
+        Pick<
+            ChatManagerOptions,
+            | 'onIncomingFileOffer'
+            | 'onIncomingSealedFileOffer'
+            | 'sealFileOffers'
+            | 'transferTuning'
+            | 'cryptoWorkers'
+            | 'outboundWeights'
+            | 'outbox'
+            | 'transferLimits'
+            | 'preferAesGcm'
+        >;
     private sessionKeyBytes: Uint8Array | null = null;
     private listeners: EventHandler[] = [];
 
@@ -80,14 +233,31 @@ export class ChatManager {
 
     private heartbeatTimer: number | null = null;
     private lastPongAt = 0;
+    private peerClock: PeerClock | null = null;
+    // Negotiated from the peer's Hello; until it arrives (or from older peers) nothing optional is used.
+    private hello: SessionHello = { capabilities: 0, maxChunkSize: null };
+    // Envelope cipher suite, from both Hello frames.
+    private cipherSuite = CIPHER_SUITE_XCHACHA20_POLY1305;
+    // Set when the peer says goodbye; the channel closing afterwards is deliberate.
+    private peerGoodbye: { reason: GoodbyeReason; reconnect: boolean; resumeTicket: Uint8Array | null } | null = null;
+    // Received messages, read one after another; the close handler runs at its end.
+    private inbound: Promise<void> = Promise.resolve();
 
     private wasmReady: Promise<void> | null = null;
+    private cryptoPool: CryptoPool | null = null;
+
+    private outbound: FrameScheduler | null = null;
+    private pumpScheduled = false;
+    private drainWaiters: (() => void)[] = [];
 
     private pendingOutgoingFileAccept = new Map<
         string,
         { resolve: () => void; reject: (reason: unknown) => void }
     >();
 
+    // Accepted sealed offers awaiting their FileReveal, by transfer id.
+    private sealedIncomingOffers = new Map<string, Uint8Array>();
+
     // File assembly buffers
     private incomingFiles = new Map<string, {
         metadata: { filename: string; mimeType: string; size: number };
@@ -101,6 +271,14 @@ export class ChatManager {
         this.options = {
             maxAutoAcceptBytes: options?.maxAutoAcceptBytes ?? 50 * 1024 * 1024,
             onIncomingFileOffer: options?.onIncomingFileOffer,
+            onIncomingSealedFileOffer: options?.onIncomingSealedFileOffer,
+            sealFileOffers: options?.sealFileOffers,
+            transferTuning: options?.transferTuning,
+            cryptoWorkers: options?.cryptoWorkers,
+            outboundWeights: options?.outboundWeights,
+            outbox: options?.outbox,
+            transferLimits: options?.transferLimits,
+            preferAesGcm: options?.preferAesGcm,
         };
 
         if (options?.sessionKeyBytes) {
@@ -113,8 +291,33 @@ export class ChatManager {
         this.setupChannel();
     }
 
+    /**
+     * Send a Goodbye, then close, so the peer shows "left" instead of retrying.
+     * Pass `restarting` (with an optional resume ticket) when coming back shortly.
+     */
+    async leave(reason: GoodbyeReason = 'user-left', resumeTicket?: Uint8Array) {
+        try {
+            await this.ensureWasmReady();
+            // Straight to the channel: queued frames are dropped by close() anyway.
+            const bytes = await this.maybeEncrypt(new Uint8Array(encode_goodbye_v1(reason, resumeTicket)));
+            if (this.channel.readyState === 'open') this.channel.send(bytes);
+        } catch {
+            // The peer will see a lost connection instead.
+        }
+        this.close();
+    }
+
     close() {
         this.stopHeartbeat();
+        this.releaseTransferLimits();
+        this.peerClock?.free();
+        this.peerClock = null;
+        this.cryptoPool?.close();
+        this.cryptoPool = null;
+        this.outbound?.free();
+        this.outbound = null;
+        this.drainWaiters.splice(0).forEach((wake) => wake());
+        this.sealedIncomingOffers.clear();
         try {
             // Detach handlers to avoid late events firing into a disposed instance.
             this.channel.onmessage = null;
@@ -132,7 +335,7 @@ export class ChatManager {
     private async maybeEncrypt(frameBytes: Uint8Array): Promise<Uint8Array> {
         if (!this.sessionKeyBytes) return frameBytes;
         await this.ensureWasmReady();
-        const wrapped = await encrypt_envelope_v1(this.sessionKeyBytes, frameBytes);
+        const wrapped = await encrypt_envelope_with_suite_v1(this.cipherSuite, this.sessionKeyBytes, frameBytes);
         return new Uint8Array(wrapped);
     }
 
@@ -151,6 +354,9 @@ export class ChatManager {
         }
 
         try {
+            // Open on the crypto pool when there is one, so large chunks don't block the UI.
+            const pool = this.getCryptoPool(CHUNK_SIZE_LIMIT);
+            if (pool?.fits(frameBytes)) return await pool.open(frameBytes);
             const inner = await decrypt_envelope_v1(this.sessionKeyBytes, frameBytes);
             return new Uint8Array(inner);
         } catch (e) {
@@ -167,19 +373,83 @@ export class ChatManager {
         this.emit({ type: 'encryption_error', message });
     }
 
+    /**
+     * Worker pool for envelope sealing and opening, if enabled and supported.
+     * Slots fit the larger of `chunkSize` and the chunks we accept, so one pool serves both directions.
+     */
+    private getCryptoPool(chunkSize: number): CryptoPool | null {
+        const workers = this.options.cryptoWorkers ?? 0;
+        if (!this.sessionKeyBytes || workers < 1 || !CryptoPool.isSupported()) return null;
+        this.cryptoPool ??= new CryptoPool(this.sessionKeyBytes, {
+            workers,
+            chunkSize: Math.max(chunkSize, CHUNK_SIZE_LIMIT),
+        });
+        return this.cryptoPool;
+    }
+
     private async sendFrame(frameBytes: Uint8Array) {
+        await this.ensureWasmReady();
+        // Classify before sealing; the envelope hides the frame type.
+        let priority = PRIORITY_CONTROL;
+        try {
+            priority = FrameScheduler.classify(frameBytes);
+        } catch {
+            // Not a protocol frame; send it ahead of everything else.
+        }
         const bytes = await this.maybeEncrypt(frameBytes);
-        this.channel.send(bytes as any);
+        this.enqueueOutbound(priority, bytes);
     }
 
-    private async sendHeartbeat(kind: number) {
-        const payload = new Uint8Array(1 + 8);
-        payload[0] = kind;
-        const now = BigInt(Date.now());
-        for (let i = 0; i < 8; i++) {
-            payload[1 + i] = Number((now >> BigInt(8 * (7 - i))) & BigInt(0xff));
+    /** Queue an encoded (and, if needed, sealed) frame for the channel. */
+    private enqueueOutbound(priority: number, bytes: Uint8Array) {
+        if (!this.outbound) {
+            const weights = this.options.outboundWeights ?? { chat: 4, bulk: 1 };
+            this.outbound = new FrameScheduler(weights.chat, weights.bulk);
         }
-        await this.sendFrame(payload);
+        this.outbound.enqueue(priority, bytes);
+        this.pumpOutbound();
+    }
+
+    /** Move queued frames to the channel while its buffer has room. */
+    private pumpOutbound() {
+        const outbound = this.outbound;
+        if (!outbound || this.channel.readyState !== 'open') return;
+        while (outbound.len > 0 && this.channel.bufferedAmount < CHANNEL_BUFFER_BYTES) {
+            const batch = outbound.next_batch(CHANNEL_BUFFER_BYTES - this.channel.bufferedAmount) as Uint8Array[];
+            batch.forEach((frame) => this.channel.send(frame as any));
+        }
+        if (outbound.len > 0) this.schedulePump();
+        this.drainWaiters.splice(0).forEach((wake) => wake());
+    }
+
+    /** Pump again once the channel buffer drops to `threshold` bytes. */
+    private schedulePump(threshold = CHANNEL_BUFFER_BYTES / 2) {
+        if (this.pumpScheduled) return;
+        this.pumpScheduled = true;
+        // Event-based backpressure: avoid busy-waiting.
+        this.channel.bufferedAmountLowThreshold = threshold;
+        // RTCDataChannel is an EventTarget in browsers; TS types are inconsistent.
+        (this.channel as any).addEventListener('bufferedamountlow', () => {
+            this.pumpScheduled = false;
+            this.pumpOutbound();
+        }, { once: true });
+    }
+
+    /** Wait until queued plus in-channel file bytes drop to `targetBytes`. */
+    private async waitForBulkBelow(targetBytes: number) {
+        while (this.outbound) {
+            const queued = this.outbound.pending_bytes(PRIORITY_BULK) + this.channel.bufferedAmount;
+            if (queued <= targetBytes) return;
+            await new Promise<void>((resolve) => {
+                this.drainWaiters.push(resolve);
+                this.schedulePump(Math.min(targetBytes, CHANNEL_BUFFER_BYTES / 2));
+            });
+        }
+    }
+
+    private async sendHeartbeatPing() {
+        await this.ensureWasmReady();
+        await this.sendFrame(new Uint8Array(encode_heartbeat_ping_v1(Date.now())));
     }
 
     startHeartbeat(opts?: { intervalMs?: number; timeoutMs?: number }) {
@@ -200,18 +470,18 @@ export class ChatManager {
                 } catch {
                     // ignore
                 }
-                this.emit({ type: 'disconnected' });
+                this.emitDisconnected();
                 return;
             }
 
-            void this.sendHeartbeat(HEARTBEAT_PING).catch(() => {
+            void this.sendHeartbeatPing().catch(() => {
                 // If sending fails, close and let reconnect logic restart.
                 try {
                     this.channel.close();
                 } catch {
                     // ignore
                 }
-                this.emit({ type: 'disconnected' });
+                this.emitDisconnected();
             });
         }, intervalMs);
     }
@@ -232,193 +502,405 @@ export class ChatManager {
 
     private async handleHeartbeat(bytes: Uint8Array) {
         const type = bytes[0];
+        this.lastPongAt = Date.now();
+        await this.ensureWasmReady();
         if (type === HEARTBEAT_PING) {
-            this.lastPongAt = Date.now();
-            await this.sendHeartbeat(HEARTBEAT_PONG);
+            const clockSync = this.peerSupports(HELLO_CAP_CLOCK_SYNC);
+            await this.sendFrame(new Uint8Array(encode_heartbeat_pong_v1(bytes, Date.now(), clockSync)));
         } else if (type === HEARTBEAT_PONG) {
-            this.lastPongAt = Date.now();
+            this.peerClock ??= new PeerClock();
+            this.peerClock.record_pong(bytes, Date.now());
         }
     }
 
-    private setupChannel() {
-        this.channel.binaryType = 'arraybuffer';
-        this.channel.onmessage = async (event) => {
-            let bytes: Uint8Array;
-            if (event.data instanceof ArrayBuffer) {
-                bytes = new Uint8Array(event.data);
-            } else if (event.data instanceof Blob) {
-                bytes = new Uint8Array(await event.data.arrayBuffer());
-            } else {
-                console.warn('[Chat] Unknown message data type:', typeof event.data);
+    /** What our Hello advertises. */
+    private get localCapabilities(): number {
+        return LOCAL_CAPABILITIES | (this.options.preferAesGcm ? HELLO_CAP_AES_256_GCM : 0);
+    }
+
+    /** Whether the peer's Hello advertised every bit of `capability`. */
+    private peerSupports(capability: number): boolean {
+        return (this.hello.capabilities & capability) === capability;
+    }
+
+    /**
+     * Send what was queued for this peer while it was offline, before any new traffic.
+     * A frame leaves the outbox only once the channel took it; the rest wait for the next reconnect.
+     */
+    private flushOutbox() {
+        const outbox = this.options.outbox;
+        if (!outbox) return;
+        let frame: Uint8Array | undefined;
+        while ((frame = outbox.box.next(outbox.peerId))) {
+            try {
+                this.channel.send(frame);
+            } catch (e) {
+                debugWarn('[Chat] Outbox flush stopped', e);
                 return;
             }
+            outbox.box.ack(outbox.peerId, frame);
+        }
+    }
 
-            try {
-                // Heartbeat check (Plaintext)
-                // If we are unencrypted, heartbeats are raw bytes [0x01/0x02, ...timestamp]
-                if (!this.sessionKeyBytes && bytes.length === 9 && (bytes[0] === 0x01 || bytes[0] === 0x02)) {
-                    this.handleHeartbeat(bytes);
-                    return;
-                }
+    /** Queue a frame in the outbox, sealed under the session key the peer will reconnect with. */
+    private queueOffline(frameBytes: Uint8Array) {
+        const outbox = this.options.outbox!;
+        const key = ProjectKey.from_bytes(this.sessionKeyBytes!);
+        try {
+            outbox.box.queue(outbox.peerId, key, frameBytes);
+        } finally {
+            key.free();
+        }
+    }
 
-                await this.ensureWasmReady();
+    /** Tell the peer what this build supports, once the channel is open. */
+    private async sendHello() {
+        await this.ensureWasmReady();
+        await this.sendFrame(new Uint8Array(encode_hello_v1(this.localCapabilities, CHUNK_SIZE_LIMIT)));
+    }
 
-                // If we are not configured for encryption but peer sends encrypted envelopes, tell the UI clearly.
-                if (!this.sessionKeyBytes) {
-                    // Safety check: verify magic header manually to avoid "BadMagic" throwing obscurely
-                    if (bytes.length < 2 || bytes[0] !== 72 || bytes[1] !== 79) { // 'H', 'O'
-                        // Ignore garbage or unknown protocols
-                        return;
-                    }
-                    const t = await decode_frame_type_v1(bytes);
-                    if (t === 0x50) {
-                        this.reportEncryptionIssueOnce(
-                            'Received encrypted data but no session password is set. Enable Encryption and ensure both sides use the same password.'
-                        );
-                        return;
-                    }
-                }
+    /**
+     * Peer clock minus local clock (ms), estimated from heartbeat round trips.
+     * Subtract it from a peer timestamp to show it in local time. Null until a pong arrives.
+     */
+    estimatedPeerClockOffsetMs(): number | null {
+        return this.peerClock?.estimated_peer_clock_offset_ms() ?? null;
+    }
 
-                const decodedBytes = await this.maybeDecrypt(bytes);
-                const frameType = await decode_frame_type_v1(decodedBytes);
+    private setupChannel() {
+        this.channel.binaryType = 'arraybuffer';
+        const hello = () => {
+            this.flushOutbox();
+            void this.sendHello().catch((e) => debugWarn('[Chat] Failed to send Hello', e));
+        };
+        if (this.channel.readyState === 'open') {
+            hello();
+        } else {
+            (this.channel as any).addEventListener('open', hello, { once: true });
+        }
+        this.channel.onmessage = (event) => {
+            // Frames are read one at a time in arrival order, so a Hello or Goodbye is taken in
+            // before the frames and the close that follow it. Handling the rest may wait on the
+            // user, so it runs off that chain.
+            const read = this.inbound.then(() => this.readFrame(event));
+            this.inbound = read.then(
+                () => undefined,
+                () => undefined,
+            );
+            void read
+                .then((frame) => frame && this.handleFrame(frame.decodedBytes, frame.frameType))
+                .catch((e) => this.reportFrameError(e));
+        };
 
+        // Handle disconnect
+        this.channel.onclose = () => {
+            debugLog('[Chat] Data channel closed');
+            this.stopHeartbeat();
+            this.releaseTransferLimits();
+            // After the frames already received, which may hold the peer's Goodbye.
+            void this.inbound.then(() => this.emitDisconnected());
+        };
+    }
 
-                // 0x10 = ChatText (Binary Wire Format v1)
-                if (frameType === 0x10) {
-                    const content = await decode_chat_text_payload_v1(decodedBytes);
-                    const msg: ChatMessage = {
-                        type: 'text',
-                        id: crypto.randomUUID(),
-                        senderId: 'peer',
-                        content,
-                        timestamp: Date.now(),
-                    };
-                    await this.handleIncoming(msg);
-                    return;
-                }
+    /**
+     * Decrypt a received message and take in session-level frames. Returns the frame to handle,
+     * or null if there is nothing more to do with it.
+     */
+    private async readFrame(event: MessageEvent): Promise<{ decodedBytes: Uint8Array; frameType: number } | null> {
+        let bytes: Uint8Array;
+        if (event.data instanceof ArrayBuffer) {
+            bytes = new Uint8Array(event.data);
+        } else if (event.data instanceof Blob) {
+            bytes = new Uint8Array(await event.data.arrayBuffer());
+        } else {
+            console.warn('[Chat] Unknown message data type:', typeof event.data);
+            return null;
+        }
 
-                // 0x20 = FileOffer
-                if (frameType === 0x20) {
-                    const offer = (await decode_file_offer_v1(decodedBytes)) as any;
+        // Heartbeat check (Plaintext)
+        // If we are unencrypted, heartbeats are raw bytes [0x01/0x02, ...timestamps]
+        const isHeartbeat =
+            (bytes.length === HEARTBEAT_PING_LEN && (bytes[0] === HEARTBEAT_PING || bytes[0] === HEARTBEAT_PONG)) ||
+            (bytes.length === HEARTBEAT_PONG_LEN && bytes[0] === HEARTBEAT_PONG);
+        if (!this.sessionKeyBytes && isHeartbeat) {
+            this.handleHeartbeat(bytes);
+            return null;
+        }
 
-                    const incomingOffer: IncomingFileOffer = {
-                        id: String(offer.id),
-                        filename: String(offer.filename),
-                        mimeType: String(offer.mimeType),
-                        size: Number(offer.size),
-                    };
+        await this.ensureWasmReady();
 
-                    let decision: IncomingFileDecision;
-                    if (this.options.onIncomingFileOffer) {
-                        decision = await this.options.onIncomingFileOffer(incomingOffer);
-                    } else {
-                        decision =
-                            incomingOffer.size <= this.options.maxAutoAcceptBytes
-                                ? 'accept'
-                                : { decision: 'reject', reason: 'File too large' };
-                    }
+        // If we are not configured for encryption but peer sends encrypted envelopes, tell the UI clearly.
+        if (!this.sessionKeyBytes) {
+            // Safety check: verify magic header manually to avoid "BadMagic" throwing obscurely
+            if (bytes.length < 2 || bytes[0] !== 72 || bytes[1] !== 79) { // 'H', 'O'
+                // Ignore garbage or unknown protocols
+                return null;
+            }
+            const t = await decode_frame_type_v1(bytes);
+            if (t === 0x50) {
+                this.reportEncryptionIssueOnce(
+                    'Received encrypted data but no session password is set. Enable Encryption and ensure both sides use the same password.'
+                );
+                return null;
+            }
+        }
 
-                    const rejectReason =
-                        decision === 'reject'
-                            ? 'rejected'
-                            : typeof decision === 'object' && decision.decision === 'reject'
-                                ? decision.reason
-                                : null;
-
-                    if (rejectReason) {
-                        const rejectBytes = encode_file_reject_v1(incomingOffer.id, rejectReason);
-                        await this.sendFrame(new Uint8Array(rejectBytes));
-                        this.emit({
-                            type: 'message',
-                            message: {
-                                type: 'text',
-                                id: crypto.randomUUID(),
-                                senderId: 'system',
-                                content: `Rejected ${incomingOffer.filename}: ${rejectReason}`,
-                                timestamp: Date.now(),
-                            },
-                        });
-                        return;
-                    }
+        const decodedBytes = await this.maybeDecrypt(bytes);
+        const frameType = await decode_frame_type_v1(decodedBytes);
 
-                    const acceptBytes = encode_file_accept_v1(incomingOffer.id);
-                    await this.sendFrame(new Uint8Array(acceptBytes));
-
-                    const msg: ChatMessage = {
-                        type: 'file-start',
-                        id: incomingOffer.id,
-                        senderId: 'peer',
-                        filename: incomingOffer.filename,
-                        mimeType: incomingOffer.mimeType,
-                        size: incomingOffer.size,
-                        timestamp: Date.now(),
-                    };
-                    await this.handleIncoming(msg);
-                    return;
-                }
+        // 0x03 = Hello: what the peer supports; older peers never send one.
+        if (frameType === 0x03) {
+            this.hello = negotiate_hello_v1(this.localCapabilities, CHUNK_SIZE_LIMIT, decodedBytes) as SessionHello;
+            this.cipherSuite = negotiate_cipher_suite_v1(this.localCapabilities, this.hello.capabilities);
+            return null;
+        }
 
-                // 0x21 = FileAccept
-                if (frameType === 0x21) {
-                    const id = await decode_file_accept_id_v1(decodedBytes);
-                    const pending = this.pendingOutgoingFileAccept.get(id);
-                    if (pending) {
-                        pending.resolve();
-                        this.pendingOutgoingFileAccept.delete(id);
-                    }
-                    return;
-                }
+        // 0x06 = Goodbye: the peer is about to close the channel on purpose.
+        if (frameType === 0x06) {
+            const goodbye = (await decode_goodbye_v1(decodedBytes)) as any;
+            this.peerGoodbye = {
+                reason: goodbye.reason as GoodbyeReason,
+                reconnect: Boolean(goodbye.reconnect),
+                resumeTicket: goodbye.resumeTicket instanceof Uint8Array ? goodbye.resumeTicket : null,
+            };
+            return null;
+        }
 
-                // 0x22 = FileReject
-                if (frameType === 0x22) {
-                    const rej = (await decode_file_reject_v1(decodedBytes)) as any;
-                    const id = String(rej.id);
-                    const pending = this.pendingOutgoingFileAccept.get(id);
-                    if (pending) {
-                        pending.reject(new Error(String(rej.reason || 'rejected')));
-                        this.pendingOutgoingFileAccept.delete(id);
-                    }
-                    return;
-                }
+        return { decodedBytes, frameType };
+    }
 
-                // 0x23 = FileChunk
-                if (frameType === 0x23) {
-                    const chunk = (await decode_file_chunk_v1(decodedBytes)) as any;
-                    const data = chunk.data as Uint8Array;
-                    const chunkBuf = data.buffer.slice(data.byteOffset, data.byteOffset + data.byteLength);
-
-                    const msg: ChatMessage = {
-                        type: 'file-chunk',
-                        id: String(chunk.id),
-                        chunkIndex: Number(chunk.chunkIndex),
-                        data: chunkBuf,
-                    };
-                    await this.handleIncoming(msg);
-                    return;
-                }
+    private async handleFrame(decodedBytes: Uint8Array, frameType: number) {
+        // 0x10 = ChatText (Binary Wire Format v1)
+        if (frameType === 0x10) {
+            const content = await decode_chat_text_payload_v1(decodedBytes);
+            const msg: ChatMessage = {
+                type: 'text',
+                id: crypto.randomUUID(),
+                senderId: 'peer',
+                content,
+                timestamp: Date.now(),
+            };
+            await this.handleIncoming(msg);
+            return;
+        }
 
-                // 0x24 = FileEnd
-                if (frameType === 0x24) {
-                    const id = await decode_file_end_id_v1(decodedBytes);
-                    const msg: ChatMessage = { type: 'file-end', id };
-                    await this.handleIncoming(msg);
-                    return;
-                }
+        // 0x20 = FileOffer
+        if (frameType === 0x20) {
+            const offer = (await decode_file_offer_v1(decodedBytes)) as any;
+            if (await this.refusedByLimits(decodedBytes, String(offer.filename))) return;
+            await this.answerFileOffer({
+                id: String(offer.id),
+                filename: String(offer.filename),
+                mimeType: String(offer.mimeType),
+                size: Number(offer.size),
+            });
+            return;
+        }
 
-                console.warn('[Chat] Unhandled binary frame type:', frameType);
-            } catch (e) {
-                // Avoid spamming errors during encryption mismatch; the UI already gets a clear message.
-                if (!this.reportedEncryptionIssue) {
-                    console.error('[Chat] Failed to decode binary frame:', e);
-                }
+        // 0x25 = FileOfferSealed: only the id is readable until we accept and the sender reveals.
+        if (frameType === 0x25) {
+            const id = await decode_file_offer_sealed_id_v1(decodedBytes);
+            const decision = this.options.onIncomingSealedFileOffer
+                ? await this.options.onIncomingSealedFileOffer({ id })
+                : 'accept';
+            const rejection = rejectionOf(decision);
+            if (rejection) {
+                await this.sendFrame(
+                    new Uint8Array(
+                        encode_file_reject_v1(id, rejection.reason, rejection.code, rejection.detail),
+                    ),
+                );
+                this.emitSystemMessage(`Rejected a sealed file offer: ${rejection.reason}`);
+                return;
             }
-        };
+            this.sealedIncomingOffers.set(id, decodedBytes);
+            await this.sendFrame(new Uint8Array(encode_file_accept_v1(id)));
+            return;
+        }
 
-        // Handle disconnect
-        this.channel.onclose = () => {
-            debugLog('[Chat] Data channel closed');
-            this.stopHeartbeat();
-            this.emit({ type: 'disconnected' });
+        // 0x26 = FileReveal: open the sealed offer, then decide as for a plain one.
+        if (frameType === 0x26) {
+            const id = await decode_file_reveal_id_v1(decodedBytes);
+            const sealed = this.sealedIncomingOffers.get(id);
+            if (!sealed) return;
+            this.sealedIncomingOffers.delete(id);
+            const offer = (await open_file_offer_v1(sealed, decodedBytes)) as any;
+            const plain = encode_file_offer_v1(String(offer.id), String(offer.filename), String(offer.mimeType), BigInt(offer.size));
+            if (await this.refusedByLimits(new Uint8Array(plain), String(offer.filename))) return;
+            await this.answerFileOffer({
+                id: String(offer.id),
+                filename: String(offer.filename),
+                mimeType: String(offer.mimeType),
+                size: Number(offer.size),
+                sealed: true,
+            });
+            return;
+        }
+
+        // 0x21 = FileAccept
+        if (frameType === 0x21) {
+            const id = await decode_file_accept_id_v1(decodedBytes);
+            const pending = this.pendingOutgoingFileAccept.get(id);
+            if (pending) {
+                pending.resolve();
+                this.pendingOutgoingFileAccept.delete(id);
+            }
+            return;
+        }
+
+        // 0x22 = FileReject
+        if (frameType === 0x22) {
+            const rej = (await decode_file_reject_v1(decodedBytes)) as any;
+            const id = String(rej.id);
+            const pending = this.pendingOutgoingFileAccept.get(id);
+            if (pending) {
+                const error = new Error(String(rej.reason || rej.code)) as FileRejectedError;
+                error.code = rej.code as FileRejectCode;
+                error.detail = Number(rej.detail);
+                pending.reject(error);
+                this.pendingOutgoingFileAccept.delete(id);
+            }
+            return;
+        }
+
+        // 0x23 = FileChunk
+        if (frameType === 0x23) {
+            const limits = this.options.transferLimits;
+            const refused = limits?.limiter.record_chunk(limits.peerId, decodedBytes);
+            const chunk = (await decode_file_chunk_v1(decodedBytes)) as any;
+            if (refused) {
+                await this.sendFrame(new Uint8Array(refused));
+                this.incomingFiles.delete(String(chunk.id));
+                this.emitSystemMessage('Stopped receiving a file: it went over the transfer limits');
+                return;
+            }
+            const data = chunk.data as Uint8Array;
+            const chunkBuf = data.buffer.slice(data.byteOffset, data.byteOffset + data.byteLength);
+
+            const msg: ChatMessage = {
+                type: 'file-chunk',
+                id: String(chunk.id),
+                chunkIndex: Number(chunk.chunkIndex),
+                data: chunkBuf,
+            };
+            await this.handleIncoming(msg);
+            return;
+        }
+
+        // 0x24 = FileEnd
+        if (frameType === 0x24) {
+            const id = await decode_file_end_id_v1(decodedBytes);
+            const limits = this.options.transferLimits;
+            limits?.limiter.record_end(limits.peerId, decodedBytes);
+            const msg: ChatMessage = { type: 'file-end', id };
+            await this.handleIncoming(msg);
+            return;
+        }
+
+        console.warn('[Chat] Unhandled binary frame type:', frameType);
+    }
+
+    private reportFrameError(e: unknown) {
+        // Avoid spamming errors during encryption mismatch; the UI already gets a clear message.
+        if (!this.reportedEncryptionIssue) {
+            console.error('[Chat] Failed to decode binary frame:', e);
+        }
+    }
+
+    private emitDisconnected() {
+        const goodbye = this.peerGoodbye;
+        this.emit(
+            goodbye
+                ? {
+                      type: 'disconnected',
+                      end: 'peer-left',
+                      reason: goodbye.reason,
+                      reconnect: goodbye.reconnect,
+                      resumeTicket: goodbye.resumeTicket ?? undefined,
+                  }
+                : { type: 'disconnected', end: 'connection-lost', reconnect: true },
+        );
+    }
+
+    /**
+     * Check a FileOffer frame against `transferLimits`. If it is over a limit, send the FileReject
+     * and return true; the offer is then not shown to the user.
+     */
+    private async refusedByLimits(offerFrame: Uint8Array, filename: string): Promise<boolean> {
+        const limits = this.options.transferLimits;
+        const refused = limits?.limiter.admit_offer(limits.peerId, offerFrame, Date.now());
+        if (!refused) return false;
+        await this.sendFrame(new Uint8Array(refused));
+        this.emitSystemMessage(`Rejected ${sanitize_filename(filename)}: over the transfer limits`);
+        return true;
+    }
+
+    /** Running transfers from this peer stop counting once the channel is gone. */
+    private releaseTransferLimits() {
+        const limits = this.options.transferLimits;
+        limits?.limiter.finish_peer(limits.peerId);
+    }
+
+    /** Accept or reject an offer (plain, or sealed after its reveal) and start receiving. */
+    private async answerFileOffer(offer: IncomingFileOffer) {
+        // Never show or save the name as sent: it may carry paths or disguised extensions.
+        const incomingOffer = { ...offer, filename: sanitize_filename(offer.filename) };
+        let decision: IncomingFileDecision;
+        if (this.options.onIncomingFileOffer) {
+            decision = await this.options.onIncomingFileOffer(incomingOffer);
+        } else {
+            decision =
+                incomingOffer.size <= this.options.maxAutoAcceptBytes
+                    ? 'accept'
+                    : {
+                          decision: 'reject',
+                          reason: 'File too large',
+                          code: 'too-large',
+                          detail: this.options.maxAutoAcceptBytes,
+                      };
+        }
+
+        const rejection = rejectionOf(decision);
+        if (rejection) {
+            const rejectBytes = encode_file_reject_v1(
+                incomingOffer.id,
+                rejection.reason,
+                rejection.code,
+                rejection.detail,
+            );
+            await this.sendFrame(new Uint8Array(rejectBytes));
+            const limits = this.options.transferLimits;
+            limits?.limiter.finish(limits.peerId, incomingOffer.id);
+            this.emitSystemMessage(`Rejected ${incomingOffer.filename}: ${rejection.reason}`);
+            return;
+        }
+
+        const acceptBytes = encode_file_accept_v1(incomingOffer.id);
+        await this.sendFrame(new Uint8Array(acceptBytes));
+
+        const msg: ChatMessage = {
+            type: 'file-start',
+            id: incomingOffer.id,
+            senderId: 'peer',
+            filename: incomingOffer.filename,
+            mimeType: incomingOffer.mimeType,
+            size: incomingOffer.size,
+            timestamp: Date.now(),
         };
+        await this.handleIncoming(msg);
+    }
+
+    private emitSystemMessage(content: string) {
+        this.emit({
+            type: 'message',
+            message: {
+                type: 'text',
+                id: crypto.randomUUID(),
+                senderId: 'system',
+                content,
+                timestamp: Date.now(),
+            },
+        });
     }
 
     get isConnected(): boolean {
@@ -464,14 +946,26 @@ export class ChatManager {
                 debugLog('[Chat] File transfer complete', { fileId: redact(msg.id) });
                 const completedFile = this.incomingFiles.get(msg.id);
                 if (completedFile) {
+                    // Check the data against the offer before the browser or disk sees it.
+                    const { filename, mimeType } = completedFile.metadata;
+                    const sample = new Uint8Array(await new Blob(completedFile.chunks).slice(0, SNIFF_LEN).arrayBuffer());
+                    const check = check_received_file(filename, mimeType, sample) as {
+                        filename: string;
+                        suspicious: boolean;
+                        blobType: string;
+                    };
+                    if (check.suspicious) {
+                        debugWarn('[Chat] Received file does not match its offer', { fileId: redact(msg.id) });
+                    }
+
                     // Reassemble Blob
-                    const blob = new Blob(completedFile.chunks, { type: completedFile.metadata.mimeType });
+                    const blob = new Blob(completedFile.chunks, { type: check.blobType });
                     debugLog('[Chat] Reassembled blob', { bytes: blob.size });
 
                     // Save to Local Folder
                     try {
                         if (getActiveHandle()) {
-                            await fsdb.saveProjectFile(this.projectId, blob, `chat/${completedFile.metadata.filename}`);
+                            await fsdb.saveProjectFile(this.projectId, blob, `chat/${check.filename}`);
                             debugLog('[Chat] File saved to local folder');
                         } else {
                             console.warn(`[Chat] No active workspace - file NOT saved to disk!`);
@@ -483,8 +977,9 @@ export class ChatManager {
                     this.emit({
                         type: 'file_received',
                         fileId: msg.id,
-                        filename: completedFile.metadata.filename,
-                        blob
+                        filename: check.filename,
+                        blob,
+                        suspicious: check.suspicious,
                     });
 
                     this.incomingFiles.delete(msg.id);
@@ -499,7 +994,8 @@ export class ChatManager {
     // === Sending ===
 
     sendText(content: string) {
-        if (!this.isConnected) {
+        const offline = !this.isConnected;
+        if (offline && !(this.options.outbox && this.sessionKeyBytes)) {
             console.warn('[Chat] Cannot send - channel is not open!');
             throw new Error('Connection lost. Please reconnect.');
         }
@@ -512,6 +1008,13 @@ export class ChatManager {
             timestamp: Date.now()
         };
 
+        if (offline) {
+            void this.ensureWasmReady()
+                .then(() => this.queueOffline(new Uint8Array(encode_chat_text_v1(content))))
+                .catch((e) => debugWarn('[Chat] Could not queue message for offline peer', e));
+            return msg;
+        }
+
         // Binary fast-path for chat text.
         // Note: this is intentionally minimal for MVP1; richer metadata moves into the protocol layer.
         void this.ensureWasmReady().then(() => {
@@ -522,19 +1025,24 @@ export class ChatManager {
         return msg;
     }
 
-    private async waitForBufferedAmountBelow(targetBytes: number) {
-        if (this.channel.bufferedAmount <= targetBytes) return;
+    /** Resolves when the peer accepts offer `id`, rejects on FileReject or timeout. */
+    private async waitForFileAccept(id: string) {
+        const accepted = new Promise<void>((resolve, reject) => {
+            this.pendingOutgoingFileAccept.set(id, { resolve, reject });
+        });
 
-        // Event-based backpressure: avoid busy-waiting.
-        this.channel.bufferedAmountLowThreshold = targetBytes;
-        await new Promise<void>((resolve) => {
-            const handler = () => resolve();
-            // RTCDataChannel is an EventTarget in browsers; TS types are inconsistent.
-            (this.channel as any).addEventListener('bufferedamountlow', handler, { once: true });
+        const ACCEPT_TIMEOUT_MS = 10_000;
+        await Promise.race([
+            accepted,
+            new Promise<void>((_, reject) =>
+                setTimeout(() => reject(new Error('File offer timed out')), ACCEPT_TIMEOUT_MS)
+            ),
+        ]).finally(() => {
+            this.pendingOutgoingFileAccept.delete(id);
         });
     }
 
-    async sendFile(file: File) {
+    async sendFile(file: File, options?: { sealOffer?: boolean }) {
         if (!this.isConnected) {
             console.warn('[Chat] Cannot send file - channel is not open!');
             throw new Error('Connection lost. Please reconnect.');
@@ -547,22 +1055,18 @@ export class ChatManager {
         // 1. Send Offer (binary)
         // WASM expects u64 for file size, so we must pass a BigInt.
         const offerBytes = encode_file_offer_v1(id, file.name, file.type || 'application/octet-stream', BigInt(file.size));
-        await this.sendFrame(new Uint8Array(offerBytes));
-
-        const accepted = new Promise<void>((resolve, reject) => {
-            this.pendingOutgoingFileAccept.set(id, { resolve, reject });
-        });
+        if (options?.sealOffer ?? this.options.sealFileOffers ?? false) {
+            // Sealed: the peer accepts blind, then sees the metadata and accepts or rejects again.
+            const { frame, key } = seal_file_offer_v1(offerBytes) as { frame: Uint8Array; key: Uint8Array };
+            await this.sendFrame(frame);
+            await this.waitForFileAccept(id);
+            await this.sendFrame(new Uint8Array(encode_file_reveal_v1(id, key)));
+        } else {
+            await this.sendFrame(new Uint8Array(offerBytes));
+        }
 
         // Wait for receiver to accept/reject before streaming chunks.
-        const ACCEPT_TIMEOUT_MS = 10_000;
-        await Promise.race([
-            accepted,
-            new Promise<void>((_, reject) =>
-                setTimeout(() => reject(new Error('File offer timed out')), ACCEPT_TIMEOUT_MS)
-            ),
-        ]).finally(() => {
-            this.pendingOutgoingFileAccept.delete(id);
-        });
+        await this.waitForFileAccept(id);
 
         const startMsg: ChatMessage = {
             type: 'file-start',
@@ -575,28 +1079,53 @@ export class ChatManager {
         };
 
         // 2. Stream Chunks
+        const chunkSize = Math.min(
+            this.options.transferTuning?.chunkSize ?? CHUNK_SIZE,
+            this.hello.maxChunkSize ?? Infinity,
+        );
+        const maxBuffered = this.options.transferTuning
+            ? this.options.transferTuning.window * chunkSize
+            : MAX_BUFFERED_BYTES;
         const buffer = await file.arrayBuffer();
         let offset = 0;
         let chunkIndex = 0;
+        // Deflate chunks only for peers that inflate them, and only for files that shrink.
+        const compress = this.peerSupports(HELLO_CAP_COMPRESSED_CHUNKS)
+            && should_compress_file(file.type || 'application/octet-stream', new Uint8Array(buffer.slice(0, 4096)));
+
+        // With a crypto pool, chunks are sealed on workers and sent in order as they finish.
+        const pool = this.getCryptoPool(chunkSize);
+        const sealing: Promise<Uint8Array>[] = [];
+        const sendSealed = async () => {
+            const sealed = await sealing.shift()!;
+            await this.waitForBulkBelow(maxBuffered);
+            this.enqueueOutbound(PRIORITY_BULK, sealed);
+        };
 
         while (offset < buffer.byteLength) {
-            const chunk = buffer.slice(offset, offset + CHUNK_SIZE);
-
-            await this.waitForBufferedAmountBelow(256 * 1024);
-
-            const chunkBytes = encode_file_chunk_v1(id, chunkIndex++, new Uint8Array(chunk));
-            await this.sendFrame(new Uint8Array(chunkBytes));
+            const chunk = buffer.slice(offset, offset + chunkSize);
+
+            const chunkBytes = compress
+                ? encode_file_chunk_compressed_v1(id, chunkIndex++, new Uint8Array(), new Uint8Array(chunk))
+                : encode_file_chunk_v1(id, chunkIndex++, new Uint8Array(chunk));
+            if (pool) {
+                sealing.push(pool.seal(new Uint8Array(chunkBytes)));
+                if (sealing.length >= pool.capacity) await sendSealed();
+            } else {
+                await this.waitForBulkBelow(maxBuffered);
+                await this.sendFrame(new Uint8Array(chunkBytes));
+            }
 
             if (chunkIndex % 25 === 0) debugLog('[Chat] Sent chunks', { sent: chunkIndex });
 
-            offset += CHUNK_SIZE;
+            offset += chunkSize;
 
             // Yield to main thread
             await new Promise(r => setTimeout(r, 0));
         }
+        while (sealing.length > 0) await sendSealed();
 
-        // 3. Send End (binary)
-        await this.waitForBufferedAmountBelow(256 * 1024);
+        // 3. Send End (binary); queued behind the chunks
         const endBytes = encode_file_end_v1(id);
         await this.sendFrame(new Uint8Array(endBytes));
         debugLog('[Chat] Finished sending file', { chunks: chunkIndex });
@@ -615,3 +1144,13 @@ export class ChatManager {
     }
 
 }
+
+type Rejection = { reason: string; code: FileRejectCode; detail?: number };
+
+function rejectionOf(decision: IncomingFileDecision): Rejection | null {
+    if (decision === 'reject') return { reason: 'rejected', code: 'user-declined' };
+    if (typeof decision === 'object' && decision.decision === 'reject') {
+        return { reason: decision.reason, code: decision.code ?? 'user-declined', detail: decision.detail };
+    }
+    return null;
+}
@@ -0,0 +1,152 @@
+/**
+ * Worker pool for EncryptedEnvelope seal/open.
+ * Frames go through a SharedArrayBuffer split into per-worker slots; the slot
+ * scheduler (CryptoOffload, from holi-p2p) returns results in submission
+ * order, so callers can send them as they resolve.
+ * Requires cross-origin isolation (COOP/COEP, set in middleware).
+ */
+
+import { CryptoOffload } from '@holi/wasm-p2p';
+import CryptoWorker from './crypto-worker?worker';
+import type { CryptoWorkerInit } from './crypto-worker';
+
+// Must match holi_p2p::offload
+const SLOT_FREE = 0;
+const SLOT_SUBMITTED = 1;
+const SLOT_FAILED = 3;
+const OP_SEAL = 1;
+const OP_OPEN = 2;
+
+type Job = { seq: number; worker: number; slot: number };
+
+export type CryptoPoolOptions = {
+    workers: number;
+    /** Frames queued per worker before `seal`/`open` wait for a free slot. */
+    slotsPerWorker?: number;
+    /** Largest chunk payload that will be sealed. */
+    chunkSize: number;
+};
+
+export class CryptoPool {
+    private offload: CryptoOffload;
+    private buffer: SharedArrayBuffer;
+    private workers: Worker[] = [];
+    private ready: Promise<void>;
+    private waiters = new Map<number, { resolve: (bytes: Uint8Array) => void; reject: (e: Error) => void }>();
+    private slotWaiters: (() => void)[] = [];
+
+    static isSupported(): boolean {
+        return typeof SharedArrayBuffer !== 'undefined' && globalThis.crossOriginIsolated === true;
+    }
+
+    /** Callers must have initialized @holi/wasm-p2p on this thread. */
+    constructor(keyBytes: Uint8Array, options: CryptoPoolOptions) {
+        this.offload = new CryptoOffload(options.workers, options.slotsPerWorker ?? 2, options.chunkSize);
+        this.buffer = new SharedArrayBuffer(this.offload.buffer_len);
+
+        const started: Promise<void>[] = [];
+        for (let w = 0; w < this.offload.workers; w++) {
+            const worker = new CryptoWorker();
+            started.push(new Promise((resolve) => {
+                worker.onmessage = (event: MessageEvent<{ ready?: boolean; slot?: number }>) => {
+                    if (event.data.ready) resolve();
+                    if (event.data.slot !== undefined) this.onSlotDone(event.data.slot);
+                };
+            }));
+            const first = w * this.offload.slots_per_worker;
+            const init: CryptoWorkerInit = {
+                buffer: this.buffer,
+                keyBytes,
+                doorbellOffset: this.offload.doorbell_offset(w),
+                slots: Array.from({ length: this.offload.slots_per_worker }, (_, i) => ({
+                    index: first + i,
+                    headerOffset: this.offload.header_offset(first + i),
+                    dataOffset: this.offload.data_offset(first + i),
+                    capacity: this.offload.slot_capacity,
+                })),
+            };
+            worker.postMessage(init);
+            this.workers.push(worker);
+        }
+        this.ready = Promise.all(started).then(() => undefined);
+    }
+
+    /** Frames that can be in flight before `seal`/`open` start waiting. */
+    get capacity(): number {
+        return this.offload.workers * this.offload.slots_per_worker;
+    }
+
+    /** Whether `bytes` fits a slot; larger frames make `seal`/`open` throw. */
+    fits(bytes: Uint8Array): boolean {
+        return bytes.byteLength <= this.offload.slot_capacity;
+    }
+
+    /** Encrypt an encoded frame into an EncryptedEnvelope. */
+    seal(frame: Uint8Array): Promise<Uint8Array> {
+        return this.run(OP_SEAL, frame);
+    }
+
+    /** Decrypt an EncryptedEnvelope into its inner frame. */
+    open(envelope: Uint8Array): Promise<Uint8Array> {
+        return this.run(OP_OPEN, envelope);
+    }
+
+    close() {
+        this.workers.forEach((w) => w.terminate());
+        this.workers = [];
+        this.offload.free();
+        const error = new Error('Crypto pool closed');
+        this.waiters.forEach((w) => w.reject(error));
+        this.waiters.clear();
+    }
+
+    private async run(op: number, bytes: Uint8Array): Promise<Uint8Array> {
+        if (!this.fits(bytes)) {
+            throw new Error('Frame too large for crypto pool slot');
+        }
+        await this.ready;
+        let job = this.offload.submit() as Job | null;
+        while (!job) {
+            await new Promise<void>((resolve) => this.slotWaiters.push(resolve));
+            job = this.offload.submit() as Job | null;
+        }
+
+        const header = new Int32Array(this.buffer, this.offload.header_offset(job.slot), 4);
+        new Uint8Array(this.buffer, this.offload.data_offset(job.slot), bytes.byteLength).set(bytes);
+        header[1] = op;
+        header[2] = bytes.byteLength;
+        const seq = job.seq;
+        const result = new Promise<Uint8Array>((resolve, reject) => {
+            this.waiters.set(seq, { resolve, reject });
+        });
+        Atomics.store(header, 0, SLOT_SUBMITTED);
+
+        const doorbell = new Int32Array(this.buffer, this.offload.doorbell_offset(job.worker), 1);
+        Atomics.add(doorbell, 0, 1);
+        Atomics.notify(doorbell, 0);
+        return result;
+    }
+
+    private onSlotDone(slot: number) {
+        this.offload.complete(slot);
+        let job: Job | null;
+        let freed = 0;
+        while ((job = this.offload.next_ready() as Job | null)) {
+            const header = new Int32Array(this.buffer, this.offload.header_offset(job.slot), 4);
+            const failed = Atomics.load(header, 0) === SLOT_FAILED;
+            // Copy out: the slot is reused and RTCDataChannel can't send shared memory.
+            const out = failed
+                ? null
+                : new Uint8Array(this.buffer, this.offload.data_offset(job.slot), header[2]).slice();
+            Atomics.store(header, 0, SLOT_FREE);
+            freed++;
+
+            const waiter = this.waiters.get(job.seq);
+            this.waiters.delete(job.seq);
+            if (!waiter) continue;
+            if (out) waiter.resolve(out);
+            else waiter.reject(new Error('Crypto worker failed to process frame'));
+        }
+        this.slotWaiters.splice(0, freed).forEach((wake) => wake());
+    }
+}
@@ -0,0 +1,58 @@
+/**
+ * Crypto worker for CryptoPool.
+ * Seals/opens frames in place in its slots of the shared buffer and posts
+ * `{ slot }` when one is finished. Layout: holi_p2p::offload.
+ */
+
+import initWasmP2p, { open_slot, seal_slot } from '@holi/wasm-p2p';
+
+// Must match holi_p2p::offload
+const SLOT_SUBMITTED = 1;
+const SLOT_DONE = 2;
+const SLOT_FAILED = 3;
+const OP_SEAL = 1;
+
+export type CryptoWorkerInit = {
+    buffer: SharedArrayBuffer;
+    keyBytes: Uint8Array;
+    doorbellOffset: number;
+    slots: { index: number; headerOffset: number; dataOffset: number; capacity: number }[];
+};
+
+const ctx = self as unknown as DedicatedWorkerGlobalScope;
+
+ctx.onmessage = async (event: MessageEvent<CryptoWorkerInit>) => {
+    const { buffer, keyBytes, doorbellOffset, slots } = event.data;
+    await initWasmP2p();
+
+    const doorbell = new Int32Array(buffer, doorbellOffset, 1);
+    const views = slots.map((s) => ({
+        index: s.index,
+        header: new Int32Array(buffer, s.headerOffset, 4),
+        data: new Uint8Array(buffer, s.dataOffset, s.capacity),
+    }));
+
+    ctx.postMessage({ ready: true });
+
+    // Blocking is fine here: this worker does nothing else, and the owner
+    // terminates it when the pool closes.
+    for (;;) {
+        const seen = Atomics.load(doorbell, 0);
+        for (const slot of views) {
+            if (Atomics.load(slot.header, 0) !== SLOT_SUBMITTED) continue;
+            const op = slot.header[1];
+            const len = slot.header[2];
+            let state = SLOT_DONE;
+            try {
+                slot.header[2] = op === OP_SEAL
+                    ? seal_slot(keyBytes, slot.data, len)
+                    : open_slot(keyBytes, slot.data, len);
+            } catch {
+                state = SLOT_FAILED;
+            }
+            Atomics.store(slot.header, 0, state);
+            ctx.postMessage({ slot: slot.index });
+        }
+        Atomics.wait(doorbell, 0, seen);
+    }
+};
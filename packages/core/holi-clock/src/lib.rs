@@ -0,0 +1,109 @@
+//! # Holi Clock
+//!
+//! Wall-clock abstraction so core logic (identities, ACL expiry, renderer
+//! timing) never calls `js_sys::Date::now` directly.
+//!
+//! - `JsClock`: `Date.now()`, browser builds (`wasm32-unknown-unknown`)
+//! - `SystemClock`: `std::time::SystemTime`, native and WASI builds
+//! - `MockClock`: manually driven, for tests
+//!
+//! `DefaultClock` resolves to whichever real clock the target supports.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Source of the current time in milliseconds since the Unix epoch.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now_ms(&self) -> u64;
+}
+
+/// `Date.now()` from the JS host.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsClock;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+impl Clock for JsClock {
+    fn now_ms(&self) -> u64 {
+        js_sys::Date::now() as u64
+    }
+}
+
+/// `std::time::SystemTime`. Not available in the browser, where it panics.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub type DefaultClock = JsClock;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub type DefaultClock = SystemClock;
+
+/// Real clock for the current target.
+pub fn default_clock() -> DefaultClock {
+    DefaultClock::default()
+}
+
+/// Clock that only moves when told to.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new(start_ms: u64) -> Self {
+        MockClock {
+            now: AtomicU64::new(start_ms),
+        }
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now.store(now_ms, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, delta_ms: u64) {
+        self.now.fetch_add(delta_ms, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for std::sync::Arc<C> {
+    fn now_ms(&self) -> u64 {
+        (**self).now_ms()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock_is_manual() {
+        let clock = MockClock::new(1_000);
+        assert_eq!(clock.now_ms(), 1_000);
+        clock.advance(500);
+        assert_eq!(clock.now_ms(), 1_500);
+        clock.set(42);
+        assert_eq!(clock.now_ms(), 42);
+    }
+
+    #[test]
+    fn system_clock_is_after_2020() {
+        assert!(default_clock().now_ms() > 1_577_836_800_000);
+    }
+}
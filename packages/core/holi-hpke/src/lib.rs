@@ -0,0 +1,240 @@
+//! # Holi HPKE
+//!
+//! Hybrid Public-Key Encryption sealed boxes, RFC 9180 base mode with the
+//! suite DHKEM(X25519, HKDF-SHA256) + HKDF-SHA256 + ChaCha20Poly1305.
+//!
+//! Lets a sender encrypt to a recipient's X25519 public key without an
+//! interactive handshake (offline share tokens, project invites, wrapping
+//! group envelope keys). Shared by `wasm-crypto` (JS bindings) and
+//! `wasm-core` (vault invites) so both speak the same format.
+//!
+//! Sealed box layout: `enc (32 bytes) || ciphertext || tag (16 bytes)`.
+//!
+//! This crate draws no randomness: callers pass the ephemeral secret, so
+//! each WASM crate keeps using its own RNG.
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::fmt;
+use x25519_dalek::{x25519, X25519_BASEPOINT_BYTES};
+
+/// Length of the encapsulated key (ephemeral X25519 public key).
+pub const HPKE_ENC_LEN: usize = 32;
+/// Length of the Poly1305 authentication tag.
+pub const HPKE_TAG_LEN: usize = 16;
+
+const KEM_ID: u16 = 0x0020; // DHKEM(X25519, HKDF-SHA256)
+const KDF_ID: u16 = 0x0001; // HKDF-SHA256
+const AEAD_ID: u16 = 0x0003; // ChaCha20Poly1305
+const MODE_BASE: u8 = 0x00;
+
+const N_SECRET: usize = 32;
+const N_K: usize = 32;
+const N_N: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HpkeError {
+    /// The X25519 exchange produced the all-zero secret (low-order key).
+    InvalidPublicKey,
+    /// Shorter than `enc` plus the tag.
+    TooShort,
+    SealFailed,
+    /// Wrong recipient, `info` or `aad`, or the box was altered.
+    OpenFailed,
+}
+
+impl fmt::Display for HpkeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HpkeError::InvalidPublicKey => write!(f, "HPKE: invalid public key"),
+            HpkeError::TooShort => write!(f, "Sealed box too short"),
+            HpkeError::SealFailed => write!(f, "HPKE seal failed"),
+            HpkeError::OpenFailed => write!(f, "HPKE open failed"),
+        }
+    }
+}
+
+impl std::error::Error for HpkeError {}
+
+fn kem_suite_id() -> Vec<u8> {
+    let mut id = b"KEM".to_vec();
+    id.extend_from_slice(&KEM_ID.to_be_bytes());
+    id
+}
+
+fn hpke_suite_id() -> Vec<u8> {
+    let mut id = b"HPKE".to_vec();
+    id.extend_from_slice(&KEM_ID.to_be_bytes());
+    id.extend_from_slice(&KDF_ID.to_be_bytes());
+    id.extend_from_slice(&AEAD_ID.to_be_bytes());
+    id
+}
+
+fn labeled_extract(suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> [u8; 32] {
+    let mut labeled_ikm = b"HPKE-v1".to_vec();
+    labeled_ikm.extend_from_slice(suite_id);
+    labeled_ikm.extend_from_slice(label);
+    labeled_ikm.extend_from_slice(ikm);
+    let (prk, _) = Hkdf::<Sha256>::extract(Some(salt), &labeled_ikm);
+    prk.into()
+}
+
+fn labeled_expand(suite_id: &[u8], prk: &[u8; 32], label: &[u8], info: &[u8], out: &mut [u8]) {
+    let mut labeled_info = (out.len() as u16).to_be_bytes().to_vec();
+    labeled_info.extend_from_slice(b"HPKE-v1");
+    labeled_info.extend_from_slice(suite_id);
+    labeled_info.extend_from_slice(label);
+    labeled_info.extend_from_slice(info);
+    // A 32-byte PRK is always valid and every output here is far below 255 * 32 bytes.
+    Hkdf::<Sha256>::from_prk(prk)
+        .expect("32-byte PRK")
+        .expand(&labeled_info, out)
+        .expect("HKDF output length");
+}
+
+/// X25519 with the all-zero output check required by RFC 9180 §7.1.4.
+fn dh(secret: &[u8; 32], public: &[u8; 32]) -> Result<[u8; 32], HpkeError> {
+    let shared = x25519(*secret, *public);
+    if shared == [0u8; 32] {
+        return Err(HpkeError::InvalidPublicKey);
+    }
+    Ok(shared)
+}
+
+fn extract_and_expand(dh: &[u8; 32], kem_context: &[u8]) -> [u8; N_SECRET] {
+    let suite_id = kem_suite_id();
+    let eae_prk = labeled_extract(&suite_id, b"", b"eae_prk", dh);
+    let mut shared_secret = [0u8; N_SECRET];
+    labeled_expand(&suite_id, &eae_prk, b"shared_secret", kem_context, &mut shared_secret);
+    shared_secret
+}
+
+/// Derive the AEAD key and base nonce for a single-shot base-mode context.
+fn key_schedule(shared_secret: &[u8; N_SECRET], info: &[u8]) -> ([u8; N_K], [u8; N_N]) {
+    let suite_id = hpke_suite_id();
+    let psk_id_hash = labeled_extract(&suite_id, b"", b"psk_id_hash", b"");
+    let info_hash = labeled_extract(&suite_id, b"", b"info_hash", info);
+
+    let mut context = vec![MODE_BASE];
+    context.extend_from_slice(&psk_id_hash);
+    context.extend_from_slice(&info_hash);
+
+    let secret = labeled_extract(&suite_id, shared_secret, b"secret", b"");
+
+    let mut key = [0u8; N_K];
+    let mut base_nonce = [0u8; N_N];
+    labeled_expand(&suite_id, &secret, b"key", &context, &mut key);
+    labeled_expand(&suite_id, &secret, b"base_nonce", &context, &mut base_nonce);
+    (key, base_nonce)
+}
+
+/// X25519 public key of `secret`.
+pub fn public_key(secret: &[u8; 32]) -> [u8; 32] {
+    x25519(*secret, X25519_BASEPOINT_BYTES)
+}
+
+/// Seal `plaintext` to `recipient_public_key`.
+///
+/// `ephemeral_secret` must be 32 fresh random bytes per call. `info` binds
+/// the application context (e.g. `b"holi.share-token.v1"`) and must match on
+/// open. `aad` is authenticated but not encrypted.
+pub fn seal(
+    ephemeral_secret: &[u8; 32],
+    recipient_public_key: &[u8; 32],
+    info: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, HpkeError> {
+    let enc = public_key(ephemeral_secret);
+    let dh = dh(ephemeral_secret, recipient_public_key)?;
+
+    let mut kem_context = enc.to_vec();
+    kem_context.extend_from_slice(recipient_public_key);
+    let shared_secret = extract_and_expand(&dh, &kem_context);
+
+    let (key, base_nonce) = key_schedule(&shared_secret, info);
+    let cipher = ChaCha20Poly1305::new(&key.into());
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&base_nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| HpkeError::SealFailed)?;
+
+    let mut sealed = Vec::with_capacity(HPKE_ENC_LEN + ciphertext.len());
+    sealed.extend_from_slice(&enc);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Open a box produced by `seal` for the keypair of `secret`.
+pub fn open(secret: &[u8; 32], info: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, HpkeError> {
+    if sealed.len() < HPKE_ENC_LEN + HPKE_TAG_LEN {
+        return Err(HpkeError::TooShort);
+    }
+    let (enc, ciphertext) = sealed.split_at(HPKE_ENC_LEN);
+    let enc: [u8; HPKE_ENC_LEN] = enc.try_into().unwrap();
+
+    let dh = dh(secret, &enc)?;
+    let mut kem_context = enc.to_vec();
+    kem_context.extend_from_slice(&public_key(secret));
+    let shared_secret = extract_and_expand(&dh, &kem_context);
+
+    let (key, base_nonce) = key_schedule(&shared_secret, info);
+    let cipher = ChaCha20Poly1305::new(&key.into());
+    cipher
+        .decrypt(Nonce::from_slice(&base_nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| HpkeError::OpenFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes32(hex_str: &str) -> [u8; 32] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let recipient = [7u8; 32];
+        let info = b"holi.test.v1";
+        let sealed = seal(&[9u8; 32], &public_key(&recipient), info, b"aad", b"Project Key").unwrap();
+        assert_eq!(sealed.len(), HPKE_ENC_LEN + 11 + HPKE_TAG_LEN);
+        assert_eq!(open(&recipient, info, b"aad", &sealed).unwrap(), b"Project Key");
+    }
+
+    #[test]
+    fn test_open_wrong_recipient_or_context() {
+        let recipient = [1u8; 32];
+        let sealed = seal(&[2u8; 32], &public_key(&recipient), b"info", b"", b"secret").unwrap();
+
+        assert_eq!(open(&[3u8; 32], b"info", b"", &sealed), Err(HpkeError::OpenFailed));
+        assert_eq!(open(&recipient, b"other-info", b"", &sealed), Err(HpkeError::OpenFailed));
+        assert_eq!(open(&recipient, b"info", b"aad", &sealed), Err(HpkeError::OpenFailed));
+        assert_eq!(open(&recipient, b"info", b"", &sealed[..40]), Err(HpkeError::TooShort));
+        assert_eq!(seal(&[2u8; 32], &[0u8; 32], b"", b"", b""), Err(HpkeError::InvalidPublicKey));
+    }
+
+    #[test]
+    fn test_rfc9180_base_vector_a2() {
+        // RFC 9180 Appendix A.2.1 (DHKEM(X25519) / HKDF-SHA256 / ChaCha20Poly1305), seq 0
+        let sk_e = bytes32("f4ec9b33b792c372c1d2c2063507b684ef925b8c75a42dbcbf57d63ccd381600");
+        let sk_r = bytes32("8057991eef8f1f1af18f4a9491d16a1ce333f695d4db8e38da75975c4478e0fb");
+        let info = hex::decode("4f6465206f6e2061204772656369616e2055726e").unwrap();
+        let aad = hex::decode("436f756e742d30").unwrap();
+        let pt = hex::decode("4265617574792069732074727574682c20747275746820626561757479").unwrap();
+
+        let sealed = seal(&sk_e, &public_key(&sk_r), &info, &aad, &pt).unwrap();
+        assert_eq!(
+            hex::encode(&sealed[..HPKE_ENC_LEN]),
+            "1afa08d3dec047a643885163f1180476fa7ddb54c6a8029ea33f95796bf2ac4a"
+        );
+        assert_eq!(
+            hex::encode(&sealed[HPKE_ENC_LEN..]),
+            "1c5250d8034ec2b784ba2cfd69dbdb8af406cfe3ff938e131f0def8c8b60b4db21993c62ce81883d2dd1b51a28"
+        );
+        assert_eq!(open(&sk_r, &info, &aad, &sealed).unwrap(), pt);
+    }
+}
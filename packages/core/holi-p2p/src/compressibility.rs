@@ -0,0 +1,188 @@
+//! Whether file data is worth compressing before transfer.
+//!
+//! Media and archives are already compressed; running them through a
+//! compressor again burns CPU (and battery on phones) for no gain. The
+//! decision uses, in order: the offer's MIME type, magic bytes at the start
+//! of the file, and the byte entropy of a sample.
+//!
+//! Chunks of files that pass are sent DEFLATE-compressed with
+//! `frame::encode_file_chunk_compressed_v1`.
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
+/// Sample bytes looked at by `should_compress`.
+pub const SAMPLE_LEN: usize = 4096;
+/// Byte entropy (bits per byte) above which data is treated as compressed
+/// or encrypted. Text sits around 4.5-5.5; deflate/zstd output is above 7.9.
+pub const ENTROPY_THRESHOLD: f64 = 7.5;
+
+/// MIME types that are compressed by format.
+pub fn is_precompressed_mime(mime_type: &str) -> bool {
+	let mime = mime_type
+		.split(';')
+		.next()
+		.unwrap_or_default()
+		.trim()
+		.to_ascii_lowercase();
+	if mime == "image/svg+xml" || mime == "image/bmp" || mime == "image/x-ms-bmp" {
+		return false;
+	}
+	if mime.starts_with("image/") || mime.starts_with("video/") || mime.starts_with("audio/") {
+		return true;
+	}
+	matches!(
+		mime.as_str(),
+		"application/zip"
+			| "application/gzip"
+			| "application/x-gzip"
+			| "application/zstd"
+			| "application/x-7z-compressed"
+			| "application/x-rar-compressed"
+			| "application/vnd.rar"
+			| "application/x-bzip2"
+			| "application/x-xz"
+			| "application/pdf"
+			| "application/epub+zip"
+			| "application/java-archive"
+			| "application/vnd.android.package-archive"
+			| "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+			| "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+			| "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+			| "font/woff"
+			| "font/woff2"
+	)
+}
+
+/// Signatures of compressed container and media formats.
+const MAGIC: &[&[u8]] = &[
+	b"PK\x03\x04",            // zip, docx/xlsx, apk, jar, epub
+	b"\x1f\x8b",              // gzip
+	b"\x28\xb5\x2f\xfd",      // zstd
+	b"7z\xbc\xaf\x27\x1c",    // 7z
+	b"Rar!\x1a\x07",          // rar
+	b"BZh",                   // bzip2
+	b"\xfd7zXZ\x00",          // xz
+	b"\xff\xd8\xff",          // jpeg
+	b"\x89PNG\r\n\x1a\n",     // png
+	b"GIF8",                  // gif
+	b"OggS",                  // ogg
+	b"fLaC",                  // flac
+	b"ID3",                   // mp3
+	b"\x1a\x45\xdf\xa3",      // webm / mkv
+	b"wOFF",                  // woff
+	b"wOF2",                  // woff2
+];
+
+/// Whether `data` starts with the signature of a compressed format.
+pub fn has_compressed_magic(data: &[u8]) -> bool {
+	if MAGIC.iter().any(|m| data.starts_with(m)) {
+		return true;
+	}
+	// RIFF containers (webp, avi, wav): only webp is compressed.
+	if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+		return true;
+	}
+	// ISO BMFF (mp4, mov, heic, avif): "ftyp" box at offset 4.
+	data.len() >= 8 && &data[4..8] == b"ftyp"
+}
+
+/// Shannon entropy of `data` in bits per byte (0.0 to 8.0).
+pub fn byte_entropy(data: &[u8]) -> f64 {
+	if data.is_empty() {
+		return 0.0;
+	}
+	let mut counts = [0u32; 256];
+	for &b in data {
+		counts[b as usize] += 1;
+	}
+	let len = data.len() as f64;
+	counts
+		.iter()
+		.filter(|&&c| c > 0)
+		.map(|&c| {
+			let p = c as f64 / len;
+			-p * p.log2()
+		})
+		.sum()
+}
+
+/// Whether a file is worth compressing. `sample` is the start of the file;
+/// only the first `SAMPLE_LEN` bytes are used.
+pub fn should_compress(mime_type: &str, sample: &[u8]) -> bool {
+	if is_precompressed_mime(mime_type) {
+		return false;
+	}
+	let sample = &sample[..sample.len().min(SAMPLE_LEN)];
+	!has_compressed_magic(sample) && byte_entropy(sample) < ENTROPY_THRESHOLD
+}
+
+/// Raw DEFLATE of `data`, or `None` if that is not smaller.
+pub fn deflate(data: &[u8]) -> Option<Vec<u8>> {
+	// Level 1: transfers favour speed, most of the gain on text comes early
+	let compressed = compress_to_vec(data, 1);
+	(compressed.len() < data.len()).then_some(compressed)
+}
+
+/// Inverse of `deflate`; `None` if `data` is not valid DEFLATE or inflates
+/// past `max_len` bytes.
+pub fn inflate(data: &[u8], max_len: usize) -> Option<Vec<u8>> {
+	decompress_to_vec_with_limit(data, max_len).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn mime_types() {
+		assert!(is_precompressed_mime("video/mp4"));
+		assert!(is_precompressed_mime("image/JPEG"));
+		assert!(is_precompressed_mime("application/zip; charset=binary"));
+		assert!(!is_precompressed_mime("image/svg+xml"));
+		assert!(!is_precompressed_mime("text/plain"));
+		assert!(!is_precompressed_mime("application/json"));
+	}
+
+	#[test]
+	fn magic_bytes_override_generic_mime() {
+		assert!(!should_compress("application/octet-stream", b"\x89PNG\r\n\x1a\n rest"));
+		assert!(!should_compress("application/octet-stream", b"\x00\x00\x00\x20ftypisom"));
+		assert!(!should_compress("", b"RIFF\x00\x00\x00\x00WEBPVP8 "));
+		assert!(should_compress("", b"RIFF\x00\x00\x00\x00WAVEfmt "));
+	}
+
+	#[test]
+	fn entropy_separates_text_from_random() {
+		let text = "The quick brown fox jumps over the lazy dog. ".repeat(100);
+		assert!(byte_entropy(text.as_bytes()) < 5.0);
+		assert!(should_compress("application/octet-stream", text.as_bytes()));
+
+		// xorshift noise stands in for encrypted or compressed bytes
+		let mut x = 0x2545_f491u32;
+		let noise: Vec<u8> = (0..SAMPLE_LEN)
+			.map(|_| {
+				x ^= x << 13;
+				x ^= x >> 17;
+				x ^= x << 5;
+				x as u8
+			})
+			.collect();
+		assert!(byte_entropy(&noise) > 7.9);
+		assert!(!should_compress("application/octet-stream", &noise));
+
+		assert_eq!(byte_entropy(&[]), 0.0);
+		assert_eq!(byte_entropy(&[7; 100]), 0.0);
+	}
+
+	#[test]
+	fn deflate_roundtrip() {
+		let text = "The quick brown fox jumps over the lazy dog. ".repeat(100);
+		let compressed = deflate(text.as_bytes()).unwrap();
+		assert!(compressed.len() < text.len() / 4);
+		assert_eq!(inflate(&compressed, text.len()).unwrap(), text.as_bytes());
+		assert_eq!(inflate(&compressed, text.len() - 1), None);
+		assert_eq!(inflate(b"not deflate", 1024), None);
+		assert_eq!(deflate(&[1, 2, 3]), None);
+	}
+}
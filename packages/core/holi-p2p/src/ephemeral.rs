@@ -0,0 +1,272 @@
+//! Burn-after-reading chat.
+//!
+//! Each direction of an ephemeral session has its own chain key, derived
+//! from the session key with HKDF-SHA256 and `chain_info`. Every message
+//! moves the chain forward:
+//!
+//!   message key || next chain key = HKDF-Expand(chain key, EPHEMERAL_RATCHET_INFO, 64)
+//!
+//! and the old chain key is wiped, so the current state can't rebuild the
+//! key of an earlier message. Each message is sealed with a random nonce
+//! too, since reusing a session key starts the same chains again. A
+//! message's text and key are wiped `ttl_ms` after it was sent or received
+//! (the shorter of both sides' TTLs). Messages that arrive ahead of order
+//! leave keys behind for the ones they skipped; those expire the same way.
+//!
+//! As with `rekey`, the crypto is the caller's: `ReceiveWindow` and
+//! `BurnLedger` hold the keys and texts and decide when they go. Dropping
+//! them must wipe them, e.g. by storing `zeroize::Zeroizing` values.
+
+use std::collections::BTreeMap;
+
+/// Wipe messages this long after they were sent or received.
+pub const DEFAULT_EPHEMERAL_TTL_MS: u64 = 5 * 60 * 1000;
+/// Most keys kept for messages that haven't arrived while later ones have.
+pub const MAX_SKIPPED_KEYS: usize = 256;
+/// HKDF info prefix for a direction's first chain key; see `chain_info`.
+pub const EPHEMERAL_CHAIN_INFO: &[u8] = b"holi-ephemeral-chain-v1";
+/// HKDF info for one step of a chain.
+pub const EPHEMERAL_RATCHET_INFO: &[u8] = b"holi-ephemeral-ratchet-v1";
+
+/// HKDF info for the first chain key of the messages the session's
+/// initiator sends (`from_initiator`) or receives.
+pub fn chain_info(from_initiator: bool) -> Vec<u8> {
+	let mut info = EPHEMERAL_CHAIN_INFO.to_vec();
+	info.push(if from_initiator { 1 } else { 2 });
+	info
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EphemeralError {
+	/// The message's key was used or wiped already: a replay, or a message
+	/// that arrived after its TTL.
+	KeyGone { index: u64 },
+	/// Further ahead of the next expected message than `MAX_SKIPPED_KEYS`
+	/// allows.
+	TooFarAhead { index: u64, next: u64 },
+}
+
+/// Keys of incoming messages, in chain order.
+///
+/// `take` moves the window on before the caller knows whether the message
+/// is genuine; work on a clone and keep it only once the message opens.
+#[derive(Debug, Clone)]
+pub struct ReceiveWindow<K> {
+	next: u64,
+	/// Keys of skipped messages and when they expire.
+	skipped: BTreeMap<u64, (K, u64)>,
+}
+
+impl<K> Default for ReceiveWindow<K> {
+	fn default() -> Self {
+		ReceiveWindow { next: 0, skipped: BTreeMap::new() }
+	}
+}
+
+impl<K> ReceiveWindow<K> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Index the chain is at: the next message expected in order.
+	pub fn next_index(&self) -> u64 {
+		self.next
+	}
+
+	/// Keys held for messages that haven't arrived yet.
+	pub fn skipped_keys(&self) -> usize {
+		self.skipped.len()
+	}
+
+	/// Key of message `index`. `step` moves the chain one message forward
+	/// and returns that message's key; it runs as often as needed to reach
+	/// `index`, and keys for the messages passed on the way are kept until
+	/// `expires_at`.
+	pub fn take(&mut self, index: u64, expires_at: u64, mut step: impl FnMut() -> K) -> Result<K, EphemeralError> {
+		if index < self.next {
+			return self
+				.skipped
+				.remove(&index)
+				.map(|(key, _)| key)
+				.ok_or(EphemeralError::KeyGone { index });
+		}
+		let gap = index - self.next;
+		if gap > (MAX_SKIPPED_KEYS - self.skipped.len()) as u64 {
+			return Err(EphemeralError::TooFarAhead { index, next: self.next });
+		}
+		while self.next < index {
+			self.skipped.insert(self.next, (step(), expires_at));
+			self.next += 1;
+		}
+		self.next += 1;
+		Ok(step())
+	}
+
+	/// Drop the skipped keys that expired by `now`; returns how many.
+	pub fn expire(&mut self, now: u64) -> usize {
+		let before = self.skipped.len();
+		self.skipped.retain(|_, (_, expires_at)| *expires_at > now);
+		before - self.skipped.len()
+	}
+
+	/// Drop every skipped key.
+	pub fn clear(&mut self) {
+		self.skipped.clear();
+	}
+}
+
+/// Messages of a session that are still readable, and when each is wiped.
+#[derive(Debug)]
+pub struct BurnLedger<T> {
+	next_id: u32,
+	live: BTreeMap<u32, (T, u64)>,
+	burned: u64,
+}
+
+impl<T> Default for BurnLedger<T> {
+	fn default() -> Self {
+		BurnLedger { next_id: 0, live: BTreeMap::new(), burned: 0 }
+	}
+}
+
+impl<T> BurnLedger<T> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Keep `value` until `expires_at`; returns its id.
+	pub fn insert(&mut self, value: T, expires_at: u64) -> u32 {
+		let id = self.next_id;
+		self.next_id = self.next_id.wrapping_add(1);
+		self.live.insert(id, (value, expires_at));
+		id
+	}
+
+	pub fn get(&self, id: u32) -> Option<&T> {
+		self.live.get(&id).map(|(value, _)| value)
+	}
+
+	pub fn expires_at(&self, id: u32) -> Option<u64> {
+		self.live.get(&id).map(|&(_, expires_at)| expires_at)
+	}
+
+	/// Messages not wiped yet.
+	pub fn live(&self) -> usize {
+		self.live.len()
+	}
+
+	/// Messages wiped so far.
+	pub fn burned(&self) -> u64 {
+		self.burned
+	}
+
+	/// Earliest expiry among live messages, to schedule the next `sweep`.
+	pub fn next_expiry(&self) -> Option<u64> {
+		self.live.values().map(|&(_, expires_at)| expires_at).min()
+	}
+
+	/// Drop the messages that expired by `now`, returning their ids.
+	pub fn sweep(&mut self, now: u64) -> Vec<u32> {
+		let expired: Vec<u32> = self
+			.live
+			.iter()
+			.filter(|(_, (_, expires_at))| *expires_at <= now)
+			.map(|(&id, _)| id)
+			.collect();
+		for id in &expired {
+			self.live.remove(id);
+		}
+		self.burned += expired.len() as u64;
+		expired
+	}
+
+	/// Drop every message, returning their ids.
+	pub fn burn_all(&mut self) -> Vec<u32> {
+		let ids: Vec<u32> = self.live.keys().copied().collect();
+		self.live.clear();
+		self.burned += ids.len() as u64;
+		ids
+	}
+}
+
+/// What an ephemeral session still holds, to show the user that burned
+/// history is gone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BurnReport {
+	/// Texts still readable.
+	pub live_messages: usize,
+	pub burned_messages: u64,
+	/// Keys held for messages that haven't arrived.
+	pub skipped_keys: usize,
+	/// Position of the sending chain: messages sent so far.
+	pub send_index: u64,
+	/// Position of the receiving chain.
+	pub receive_index: u64,
+	/// The chain keys were wiped too; the session can't send or receive.
+	pub destroyed: bool,
+}
+
+impl BurnReport {
+	/// No text or message key of an earlier message is left. The chain keys
+	/// only lead to keys of messages not sent yet.
+	pub fn history_unrecoverable(&self) -> bool {
+		self.live_messages == 0 && self.skipped_keys == 0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A chain whose "keys" are their own indices.
+	fn counter() -> impl FnMut() -> u64 {
+		let mut next = 0;
+		move || {
+			next += 1;
+			next - 1
+		}
+	}
+
+	#[test]
+	fn takes_keys_in_and_out_of_order() {
+		let mut window = ReceiveWindow::new();
+		let mut chain = counter();
+		assert_eq!(window.take(0, 100, &mut chain), Ok(0));
+		assert_eq!(window.take(3, 100, &mut chain), Ok(3));
+		assert_eq!(window.skipped_keys(), 2);
+		assert_eq!(window.take(1, 100, &mut chain), Ok(1));
+		assert_eq!(window.take(1, 100, &mut chain), Err(EphemeralError::KeyGone { index: 1 }));
+		assert_eq!(window.take(0, 100, &mut chain), Err(EphemeralError::KeyGone { index: 0 }));
+		assert_eq!(window.next_index(), 4);
+
+		// The key skipped for message 2 expires like a message would
+		assert_eq!(window.expire(99), 0);
+		assert_eq!(window.expire(100), 1);
+		assert_eq!(window.take(2, 200, &mut chain), Err(EphemeralError::KeyGone { index: 2 }));
+
+		let far = 4 + MAX_SKIPPED_KEYS as u64 + 1;
+		assert_eq!(window.take(far, 200, &mut chain), Err(EphemeralError::TooFarAhead { index: far, next: 4 }));
+		assert_eq!(window.take(u64::MAX, 200, &mut chain), Err(EphemeralError::TooFarAhead { index: u64::MAX, next: 4 }));
+		assert_eq!(window.take(far - 1, 200, &mut chain), Ok(far - 1));
+		assert_eq!(window.skipped_keys(), MAX_SKIPPED_KEYS);
+	}
+
+	#[test]
+	fn ledger_burns_on_time() {
+		let mut ledger = BurnLedger::new();
+		let a = ledger.insert("a", 1_000);
+		let b = ledger.insert("b", 2_000);
+		assert_eq!(ledger.next_expiry(), Some(1_000));
+		assert_eq!(ledger.sweep(999), Vec::<u32>::new());
+		assert_eq!(ledger.sweep(1_000), vec![a]);
+		assert_eq!(ledger.get(a), None);
+		assert_eq!(ledger.get(b), Some(&"b"));
+		ledger.insert("c", 3_000);
+		assert_eq!(ledger.burn_all().len(), 2);
+		assert_eq!((ledger.live(), ledger.burned()), (0, 3));
+
+		let report = BurnReport { burned_messages: 3, send_index: 2, ..Default::default() };
+		assert!(report.history_unrecoverable());
+		assert!(!BurnReport { skipped_keys: 1, ..report }.history_unrecoverable());
+	}
+}
@@ -1,39 +1,152 @@
+use std::ops::Range;
+
+use crate::merkle::{MerkleHash, MAX_PROOF_LEN, MERKLE_HASH_LEN};
+use crate::compressibility;
+use crate::metrics::Metrics;
 use crate::varint::{
-	decode_u32_varint, decode_u64_varint, encode_u32_varint, encode_u64_varint, VarintError,
+	decode_u32_varint, decode_u64_varint, encode_u32_varint, encode_u32_varint_into,
+	encode_u64_varint, varint_len, VarintError, MAX_U32_VARINT_LEN, MAX_U64_VARINT_LEN,
 };
 
 pub const MAGIC: [u8; 2] = [b'H', b'O'];
 pub const VERSION_V1: u8 = 1;
+/// Longest v1 header: magic, version, type, flags and a 5-byte length.
+pub const MAX_HEADER_LEN_V1: usize = 5 + MAX_U32_VARINT_LEN;
 pub const ENVELOPE_NONCE_LEN: usize = 24;
+/// Nonce length of AES-256-GCM envelopes.
+pub const ENVELOPE_AES_GCM_NONCE_LEN: usize = 12;
+/// EncryptedEnvelope flag: the payload starts with a `CipherSuite` byte.
+/// Unflagged envelopes are XChaCha20-Poly1305.
+pub const ENVELOPE_FLAG_CIPHER_SUITE: u8 = 0x01;
+/// Hello capability: the peer can open AES-256-GCM envelopes and prefers them.
+pub const HELLO_CAP_AES_256_GCM: u32 = 1 << 0;
+/// Hello capability: the peer reads FileOffer thumbnails. Older decoders take
+/// the placeholder root written before a thumbnail for a real one, so only
+/// send thumbnails to peers that set this.
+pub const HELLO_CAP_OFFER_THUMBNAIL: u32 = 1 << 1;
+/// Hello capability: the peer can receive animated (multi-frame) QR codes.
+pub const HELLO_CAP_ANIMATED_QR: u32 = 1 << 2;
+/// Hello capability: the peer can decode fountain-coded QR frame sequences.
+pub const HELLO_CAP_FOUNTAIN_CODES: u32 = 1 << 3;
+/// Hello capability: the peer reads 17-byte heartbeat pongs that echo the
+/// ping's timestamp (see `keepalive`). Older peers drop anything but 9 bytes.
+pub const HELLO_CAP_CLOCK_SYNC: u32 = 1 << 4;
+/// Hello capability: the peer reads Merkle proofs in FileChunk frames
+/// (`FILE_CHUNK_FLAG_PROOF`). Older decoders take the proof for chunk data,
+/// so only send proofs when both sides set this.
+pub const HELLO_CAP_MERKLE_PROOFS: u32 = 1 << 5;
+/// Hello capability: the peer inflates FileChunk data sent with
+/// `FILE_CHUNK_FLAG_COMPRESSED`.
+pub const HELLO_CAP_COMPRESSED_CHUNKS: u32 = 1 << 6;
+/// Hello capability: the peer checks ChatText signatures bound to a recipient
+/// and channel (`CHAT_TEXT_FLAG_BOUND`). Older peers check the unbound bytes,
+/// so only bind signatures for peers that set this.
+pub const HELLO_CAP_BOUND_CHAT: u32 = 1 << 7;
+/// Most recipients one MultiEnvelope can carry (the count is one byte).
+pub const MULTI_ENVELOPE_MAX_RECIPIENTS: usize = 255;
+/// X25519 public key carried by RekeyRequest/RekeyResponse.
+pub const REKEY_PUBLIC_KEY_LEN: usize = 32;
+/// Per-offer key that opens a FileOfferSealed, sent in FileReveal.
+pub const OFFER_KEY_LEN: usize = 32;
+pub const ACL_AUTHOR_KEY_LEN: usize = 32;
+pub const ACL_SIGNATURE_LEN: usize = 64;
+/// HPKE sealed box of a 32-byte project key: enc, ciphertext and tag.
+pub const PROJECT_INVITE_SEALED_KEY_LEN: usize = 32 + 32 + 16;
+/// FileChunk flag: a Merkle proof precedes the chunk data.
+pub const FILE_CHUNK_FLAG_PROOF: u8 = 0x01;
+/// FileChunk flag: the chunk data is raw DEFLATE.
+pub const FILE_CHUNK_FLAG_COMPRESSED: u8 = 0x02;
+/// Largest chunk a compressed FileChunk may inflate to.
+pub const MAX_INFLATED_CHUNK_LEN: usize = 1 << 20;
+pub const CHAT_SENDER_KEY_LEN: usize = 32;
+pub const CHAT_SIGNATURE_LEN: usize = 64;
+/// ChatText flag: sender attribution precedes the text.
+pub const CHAT_TEXT_FLAG_ATTRIBUTED: u8 = 0x01;
+/// ChatText flag, with `CHAT_TEXT_FLAG_ATTRIBUTED`: the signature covers
+/// `bound_chat_text_signing_bytes`.
+pub const CHAT_TEXT_FLAG_BOUND: u8 = 0x02;
+
+/// Domain separator prepended to AclUpdate bytes before signing.
+const ACL_SIGNING_CONTEXT: &[u8] = b"holi-acl-v1";
+/// Domain separator prepended to ProjectInvite bytes before signing.
+const INVITE_SIGNING_CONTEXT: &[u8] = b"holi-invite-v1";
+/// Domain separator prepended to ChatText bytes before signing.
+const CHAT_SIGNING_CONTEXT: &[u8] = b"holi-chat-v1";
+/// Domain separator of ChatText bytes bound to a recipient and channel.
+const BOUND_CHAT_SIGNING_CONTEXT: &[u8] = b"holi-chat-v2";
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FrameType {
 	Ping = 0x01,
 	Pong = 0x02,
+	Hello = 0x03,
+	RekeyRequest = 0x04,
+	RekeyResponse = 0x05,
+	Goodbye = 0x06,
 	ChatText = 0x10,
 	FileOffer = 0x20,
 	FileAccept = 0x21,
 	FileReject = 0x22,
 	FileChunk = 0x23,
 	FileEnd = 0x24,
+	FileOfferSealed = 0x25,
+	FileReveal = 0x26,
+	AclUpdate = 0x30,
+	ProjectInvite = 0x31,
 	ProtocolError = 0x7F,
 	EncryptedEnvelope = 0x50,
+	MultiEnvelope = 0x51,
+	EphemeralEnvelope = 0x52,
 }
 
 impl FrameType {
+	/// Every frame type, in wire-value order.
+	pub const ALL: [FrameType; 20] = [
+		Self::Ping,
+		Self::Pong,
+		Self::Hello,
+		Self::RekeyRequest,
+		Self::RekeyResponse,
+		Self::Goodbye,
+		Self::ChatText,
+		Self::FileOffer,
+		Self::FileAccept,
+		Self::FileReject,
+		Self::FileChunk,
+		Self::FileEnd,
+		Self::FileOfferSealed,
+		Self::FileReveal,
+		Self::AclUpdate,
+		Self::ProjectInvite,
+		Self::EncryptedEnvelope,
+		Self::MultiEnvelope,
+		Self::EphemeralEnvelope,
+		Self::ProtocolError,
+	];
+
 	pub fn from_u8(value: u8) -> Option<Self> {
 		Some(match value {
 			0x01 => Self::Ping,
 			0x02 => Self::Pong,
+			0x03 => Self::Hello,
+			0x04 => Self::RekeyRequest,
+			0x05 => Self::RekeyResponse,
+			0x06 => Self::Goodbye,
 			0x10 => Self::ChatText,
 			0x20 => Self::FileOffer,
 			0x21 => Self::FileAccept,
 			0x22 => Self::FileReject,
 			0x23 => Self::FileChunk,
 			0x24 => Self::FileEnd,
+			0x25 => Self::FileOfferSealed,
+			0x26 => Self::FileReveal,
+			0x30 => Self::AclUpdate,
+			0x31 => Self::ProjectInvite,
 			0x7F => Self::ProtocolError,
 			0x50 => Self::EncryptedEnvelope,
+			0x51 => Self::MultiEnvelope,
+			0x52 => Self::EphemeralEnvelope,
 			_ => return None,
 		})
 	}
@@ -52,21 +165,213 @@ pub struct FileOffer {
 	pub filename: String,
 	pub mime_type: String,
 	pub size: u64,
+	/// Merkle root of the file; lets the receiver verify each chunk on arrival.
+	pub merkle: Option<MerkleRoot>,
+	/// Preview image, encrypted under the session key.
+	pub thumbnail: Option<OfferThumbnail>,
+}
+
+/// Everything in a FileOffer except the id; the plaintext of a FileOfferSealed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OfferMetadata {
+	pub filename: String,
+	pub mime_type: String,
+	pub size: u64,
+	pub merkle: Option<MerkleRoot>,
+	pub thumbnail: Option<OfferThumbnail>,
+}
+
+/// A small encoded image of the offered file. Opaque here: the sender
+/// encrypts it with a key both peers hold, and the offer id as associated
+/// data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OfferThumbnail {
+	pub nonce: [u8; ENVELOPE_NONCE_LEN],
+	pub ciphertext: Vec<u8>,
+}
+
+impl FileOffer {
+	pub fn metadata(&self) -> OfferMetadata {
+		OfferMetadata {
+			filename: self.filename.clone(),
+			mime_type: self.mime_type.clone(),
+			size: self.size,
+			merkle: self.merkle,
+			thumbnail: self.thumbnail.clone(),
+		}
+	}
+
+	pub fn from_metadata(id: String, metadata: OfferMetadata) -> Self {
+		Self {
+			id,
+			filename: metadata.filename,
+			mime_type: metadata.mime_type,
+			size: metadata.size,
+			merkle: metadata.merkle,
+			thumbnail: metadata.thumbnail,
+		}
+	}
+}
+
+/// A FileOffer whose metadata is encrypted under a per-offer key. Only the
+/// id is readable until the receiver accepts and the sender reveals the key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedFileOffer {
+	pub id: String,
+	pub nonce: [u8; ENVELOPE_NONCE_LEN],
+	/// XChaCha20-Poly1305 ciphertext of `encode_offer_metadata`, with the id
+	/// as associated data.
+	pub ciphertext: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReveal {
+	pub id: String,
+	pub key: [u8; OFFER_KEY_LEN],
+}
+
+/// Root of a file's Merkle tree (see `crate::merkle`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MerkleRoot {
+	/// Leaf size in bytes; equal to the chunk size when chunks carry proofs.
+	pub leaf_size: u32,
+	pub root: MerkleHash,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FileChunk {
 	pub id: String,
 	pub chunk_index: u32,
+	/// Sibling hashes for `chunk_index`, empty if the sender sent none.
+	pub proof: Vec<MerkleHash>,
 	pub data: Vec<u8>,
 }
 
+/// Why a transfer was refused, so the sender can react without matching
+/// (localized) text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RejectCode {
+	/// No code: rejects from older peers, or codes this version doesn't know.
+	Unspecified = 0,
+	TooLarge = 1,
+	UnsupportedType = 2,
+	UserDeclined = 3,
+	QuotaExceeded = 4,
+	/// Too many transfers running; worth retrying later.
+	Busy = 5,
+}
+
+impl RejectCode {
+	pub const ALL: [RejectCode; 6] = [
+		Self::Unspecified,
+		Self::TooLarge,
+		Self::UnsupportedType,
+		Self::UserDeclined,
+		Self::QuotaExceeded,
+		Self::Busy,
+	];
+
+	pub fn from_u8(value: u8) -> Option<Self> {
+		Self::ALL.into_iter().find(|c| *c as u8 == value)
+	}
+
+	/// Name used by the wasm bindings, e.g. `"too-large"`.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Unspecified => "unspecified",
+			Self::TooLarge => "too-large",
+			Self::UnsupportedType => "unsupported-type",
+			Self::UserDeclined => "user-declined",
+			Self::QuotaExceeded => "quota-exceeded",
+			Self::Busy => "busy",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|c| c.name() == name)
+	}
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FileReject {
 	pub id: String,
+	pub code: RejectCode,
+	/// Limit behind `code`: the size limit in bytes for `TooLarge`, ms until
+	/// the quota resets for `QuotaExceeded`, the transfer limit for `Busy`.
+	/// 0 if none.
+	pub detail: u64,
+	/// Optional human-readable text; empty if none.
 	pub reason: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclAction {
+	/// Grant `role` (0 = owner, 1 = editor, 2 = viewer), optionally expiring at `expires_at` (ms).
+	Grant { role: u8, expires_at: Option<u64> },
+	Revoke,
+}
+
+/// Signed permission change for one user on one project.
+///
+/// The signature covers `acl_update_signing_bytes`, i.e. every field except
+/// the signature itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AclUpdate {
+	pub project_id: String,
+	pub user_id: String,
+	pub action: AclAction,
+	/// Author clock in ms, used for last-writer-wins ordering.
+	pub timestamp: u64,
+	pub author: [u8; ACL_AUTHOR_KEY_LEN],
+	pub signature: [u8; ACL_SIGNATURE_LEN],
+}
+
+/// Invitation to a project, carrying its key sealed to the invitee.
+///
+/// The signature covers `project_invite_signing_bytes`, i.e. every field
+/// except the signature itself. The matching ACL grant travels separately
+/// as an AclUpdate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectInvite {
+	pub project_id: String,
+	/// Role offered (0 = owner, 1 = editor, 2 = viewer), as in AclAction::Grant.
+	pub role: u8,
+	/// Inviter clock in ms.
+	pub timestamp: u64,
+	/// Ed25519 public key of the inviting member.
+	pub inviter: [u8; ACL_AUTHOR_KEY_LEN],
+	/// Ed25519 public key of the invited peer.
+	pub invitee: [u8; ACL_AUTHOR_KEY_LEN],
+	/// Project key sealed (HPKE) to the invitee's X25519 key.
+	pub sealed_key: [u8; PROJECT_INVITE_SEALED_KEY_LEN],
+	pub signature: [u8; ACL_SIGNATURE_LEN],
+}
+
+/// Who sent a ChatText, signed so it survives relays and offline storage.
+///
+/// The signature covers `chat_text_signing_bytes`, i.e. the sender, sequence,
+/// timestamp and text, or if `bound` `bound_chat_text_signing_bytes`, which
+/// add the recipient and channel so the message can't be replayed to others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatAttribution {
+	/// Ed25519 public key of the sender.
+	pub sender: [u8; CHAT_SENDER_KEY_LEN],
+	/// Per-sender message counter, for ordering and replay detection.
+	pub sequence: u64,
+	/// Sender clock in ms.
+	pub timestamp: u64,
+	pub signature: [u8; CHAT_SIGNATURE_LEN],
+	/// Sent with `CHAT_TEXT_FLAG_BOUND`.
+	pub bound: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatText {
+	pub text: String,
+	pub attribution: Option<ChatAttribution>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DecodeError {
 	UnexpectedEof,
@@ -77,6 +382,74 @@ pub enum DecodeError {
 	LengthTooLarge { length: u32, max: u32 },
 	InvalidUtf8,
 	BadEnvelope,
+	UnknownCipherSuite { suite: u8 },
+	BadAclUpdate,
+	BadProjectInvite,
+	BadRekey,
+	BadMultiEnvelope,
+	BadMerkleProof,
+	BadCompressedChunk,
+	BadHello,
+}
+
+impl DecodeError {
+	/// Every `name()`, indexed by `index()`.
+	pub const NAMES: [&'static str; 16] = [
+		"unexpected-eof",
+		"bad-magic",
+		"unsupported-version",
+		"unknown-frame-type",
+		"varint",
+		"length-too-large",
+		"invalid-utf8",
+		"bad-envelope",
+		"unknown-cipher-suite",
+		"bad-acl-update",
+		"bad-project-invite",
+		"bad-rekey",
+		"bad-multi-envelope",
+		"bad-merkle-proof",
+		"bad-compressed-chunk",
+		"bad-hello",
+	];
+
+	/// Position of the error category in `NAMES`, for counters kept in an
+	/// array of `NAMES.len()`.
+	pub fn index(&self) -> usize {
+		match self {
+			Self::UnexpectedEof => 0,
+			Self::BadMagic => 1,
+			Self::UnsupportedVersion { .. } => 2,
+			Self::UnknownFrameType { .. } => 3,
+			Self::Varint(_) => 4,
+			Self::LengthTooLarge { .. } => 5,
+			Self::InvalidUtf8 => 6,
+			Self::BadEnvelope => 7,
+			Self::UnknownCipherSuite { .. } => 8,
+			Self::BadAclUpdate => 9,
+			Self::BadProjectInvite => 10,
+			Self::BadRekey => 11,
+			Self::BadMultiEnvelope => 12,
+			Self::BadMerkleProof => 13,
+			Self::BadCompressedChunk => 14,
+			Self::BadHello => 15,
+		}
+	}
+
+	/// Error category without its details, e.g. `"bad-magic"`; used as the
+	/// metrics key.
+	pub fn name(&self) -> &'static str {
+		Self::NAMES[self.index()]
+	}
+}
+
+/// Why a frame could not be encoded from the given parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+	/// More sibling hashes than `MAX_PROOF_LEN`.
+	ProofTooLong { len: usize, max: usize },
+	/// Nonce of the wrong length for the cipher suite.
+	NonceLength { len: usize, expected: usize },
 }
 
 impl From<VarintError> for DecodeError {
@@ -85,16 +458,81 @@ impl From<VarintError> for DecodeError {
 	}
 }
 
+/// Write a v1 header for a payload of `payload_len` bytes at the start of
+/// `out`, returning the bytes written. Panics if `out` is shorter than
+/// `MAX_HEADER_LEN_V1` and the header doesn't fit. Counts the frame in
+/// `Metrics::global()`.
+pub fn encode_header_v1_into(
+	frame_type: FrameType,
+	flags: u8,
+	payload_len: u32,
+	out: &mut [u8],
+) -> usize {
+	out[..2].copy_from_slice(&MAGIC);
+	out[2] = VERSION_V1;
+	out[3] = frame_type as u8;
+	out[4] = flags;
+	let header_len = 5 + encode_u32_varint_into(payload_len, &mut out[5..]);
+	Metrics::global().record_encoded(frame_type, header_len + payload_len as usize);
+	header_len
+}
+
 pub fn encode_v1(frame: &Frame, out: &mut Vec<u8>) {
-	out.extend_from_slice(&MAGIC);
-	out.push(VERSION_V1);
-	out.push(frame.frame_type as u8);
-	out.push(frame.flags);
-	encode_u32_varint(frame.payload.len() as u32, out);
+	let mut header = [0u8; MAX_HEADER_LEN_V1];
+	let n = encode_header_v1_into(
+		frame.frame_type,
+		frame.flags,
+		frame.payload.len() as u32,
+		&mut header,
+	);
+	out.extend_from_slice(&header[..n]);
 	out.extend_from_slice(&frame.payload);
 }
 
-pub fn decode_v1(input: &[u8], max_payload_len: u32) -> Result<(Frame, usize), DecodeError> {
+/// Header of a decoded frame whose payload stays in the input buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameRef {
+	pub frame_type: FrameType,
+	pub flags: u8,
+	/// Where the payload sits in the buffer passed to `decode_v1_ref`.
+	pub payload_range: Range<usize>,
+}
+
+impl FrameRef {
+	/// The payload, borrowed from the buffer the frame was decoded from.
+	pub fn payload<'a>(&self, input: &'a [u8]) -> &'a [u8] {
+		&input[self.payload_range.clone()]
+	}
+
+	pub fn to_frame(&self, input: &[u8]) -> Frame {
+		Frame {
+			frame_type: self.frame_type,
+			flags: self.flags,
+			payload: self.payload(input).to_vec(),
+		}
+	}
+}
+
+/// Like `decode_v1` but without copying the payload: slice `input` with
+/// `payload_range` (or take a subarray on the JS side) instead. Counts the
+/// frame, or the error, in `Metrics::global()`.
+pub fn decode_v1_ref(input: &[u8], max_payload_len: u32) -> Result<(FrameRef, usize), DecodeError> {
+	match peek_v1_ref(input, max_payload_len) {
+		Ok((frame, used)) => {
+			Metrics::global().record_decoded(frame.frame_type, used);
+			Ok((frame, used))
+		}
+		Err(e) => {
+			Metrics::global().record_decode_error(&e);
+			Err(e)
+		}
+	}
+}
+
+/// `decode_v1_ref` without touching the metrics, for looking at a frame
+/// that gets decoded for real elsewhere (e.g. peeking at its type before
+/// handing it to a typed decoder).
+pub fn peek_v1_ref(input: &[u8], max_payload_len: u32) -> Result<(FrameRef, usize), DecodeError> {
 	if input.len() < 5 {
 		return Err(DecodeError::UnexpectedEof);
 	}
@@ -122,17 +560,21 @@ pub fn decode_v1(input: &[u8], max_payload_len: u32) -> Result<(Frame, usize), D
 	if input.len() < total_len {
 		return Err(DecodeError::UnexpectedEof);
 	}
-	let payload = input[header_len..total_len].to_vec();
 	Ok((
-		Frame {
+		FrameRef {
 			frame_type,
 			flags,
-			payload,
+			payload_range: header_len..total_len,
 		},
 		total_len,
 	))
 }
 
+pub fn decode_v1(input: &[u8], max_payload_len: u32) -> Result<(Frame, usize), DecodeError> {
+	let (frame, used) = decode_v1_ref(input, max_payload_len)?;
+	Ok((frame.to_frame(input), used))
+}
+
 pub fn encode_chat_text_v1(text: &str) -> Vec<u8> {
 	let frame = Frame {
 		frame_type: FrameType::ChatText,
@@ -144,8 +586,99 @@ pub fn encode_chat_text_v1(text: &str) -> Vec<u8> {
 	out
 }
 
+/// Canonical bytes a sender signs for an attributed ChatText.
+pub fn chat_text_signing_bytes(
+	text: &str,
+	sender: &[u8; CHAT_SENDER_KEY_LEN],
+	sequence: u64,
+	timestamp: u64,
+) -> Vec<u8> {
+	let mut out = CHAT_SIGNING_CONTEXT.to_vec();
+	out.extend_from_slice(sender);
+	encode_u64_varint(sequence, &mut out);
+	encode_u64_varint(timestamp, &mut out);
+	out.extend_from_slice(text.as_bytes());
+	out
+}
+
+/// Canonical bytes a sender signs for an attributed ChatText meant only for
+/// `recipient` (its Ed25519 public key) in `channel` (a room or session id).
+pub fn bound_chat_text_signing_bytes(
+	text: &str,
+	sender: &[u8; CHAT_SENDER_KEY_LEN],
+	recipient: &[u8; CHAT_SENDER_KEY_LEN],
+	channel: &[u8],
+	sequence: u64,
+	timestamp: u64,
+) -> Vec<u8> {
+	let mut out = BOUND_CHAT_SIGNING_CONTEXT.to_vec();
+	out.extend_from_slice(sender);
+	out.extend_from_slice(recipient);
+	encode_u32_varint(channel.len() as u32, &mut out);
+	out.extend_from_slice(channel);
+	encode_u64_varint(sequence, &mut out);
+	encode_u64_varint(timestamp, &mut out);
+	out.extend_from_slice(text.as_bytes());
+	out
+}
+
+/// ChatText carrying `attribution`; peers that only read the payload as text
+/// should use `decode_chat_text_frame_v1` instead.
+pub fn encode_attributed_chat_text_v1(text: &str, attribution: &ChatAttribution) -> Vec<u8> {
+	let mut payload = Vec::with_capacity(CHAT_SENDER_KEY_LEN + CHAT_SIGNATURE_LEN + 20 + text.len());
+	payload.extend_from_slice(&attribution.sender);
+	encode_u64_varint(attribution.sequence, &mut payload);
+	encode_u64_varint(attribution.timestamp, &mut payload);
+	payload.extend_from_slice(&attribution.signature);
+	payload.extend_from_slice(text.as_bytes());
+	let bound = if attribution.bound { CHAT_TEXT_FLAG_BOUND } else { 0 };
+	let frame = Frame {
+		frame_type: FrameType::ChatText,
+		flags: CHAT_TEXT_FLAG_ATTRIBUTED | bound,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+/// Decode a ChatText frame, including its attribution if flagged.
+///
+/// The signature is not checked here.
+pub fn decode_chat_text_frame_v1(frame: &Frame) -> Result<ChatText, DecodeError> {
+	let payload = &frame.payload[..];
+	let mut attribution = None;
+	let mut text_start = 0;
+	if frame.flags & CHAT_TEXT_FLAG_ATTRIBUTED != 0 {
+		let mut sender = [0u8; CHAT_SENDER_KEY_LEN];
+		sender.copy_from_slice(payload.get(..CHAT_SENDER_KEY_LEN).ok_or(DecodeError::UnexpectedEof)?);
+		let mut i = CHAT_SENDER_KEY_LEN;
+		let (sequence, n) = decode_u64_varint(&payload[i..])?;
+		i += n;
+		let (timestamp, n) = decode_u64_varint(&payload[i..])?;
+		i += n;
+		let mut signature = [0u8; CHAT_SIGNATURE_LEN];
+		signature.copy_from_slice(
+			payload.get(i..i + CHAT_SIGNATURE_LEN).ok_or(DecodeError::UnexpectedEof)?,
+		);
+		text_start = i + CHAT_SIGNATURE_LEN;
+		attribution = Some(ChatAttribution {
+			sender,
+			sequence,
+			timestamp,
+			signature,
+			bound: frame.flags & CHAT_TEXT_FLAG_BOUND != 0,
+		});
+	}
+	let text = std::str::from_utf8(&payload[text_start..]).map_err(|_| DecodeError::InvalidUtf8)?;
+	Ok(ChatText {
+		text: text.to_string(),
+		attribution,
+	})
+}
+
 fn encode_string(out: &mut Vec<u8>, value: &str) {
-	encode_u32_varint(value.as_bytes().len() as u32, out);
+	encode_u32_varint(value.len() as u32, out);
 	out.extend_from_slice(value.as_bytes());
 }
 
@@ -162,12 +695,36 @@ fn decode_string(input: &[u8]) -> Result<(String, usize), DecodeError> {
 	Ok((s, end))
 }
 
+/// Offer fields after the id, as laid out in FileOffer.
+pub fn encode_offer_metadata(metadata: &OfferMetadata, out: &mut Vec<u8>) {
+	encode_string(out, &metadata.filename);
+	encode_string(out, &metadata.mime_type);
+	encode_u64_varint(metadata.size, out);
+	// Trailing and optional, so older decoders still read the offer.
+	match (&metadata.merkle, &metadata.thumbnail) {
+		(Some(merkle), _) => {
+			encode_u32_varint(merkle.leaf_size, out);
+			out.extend_from_slice(&merkle.root);
+		}
+		// Leaf size 0 with a zero root stands in for "no root", keeping the
+		// thumbnail at a fixed place
+		(None, Some(_)) => {
+			encode_u32_varint(0, out);
+			out.extend_from_slice(&[0u8; MERKLE_HASH_LEN]);
+		}
+		(None, None) => {}
+	}
+	if let Some(thumbnail) = &metadata.thumbnail {
+		out.extend_from_slice(&thumbnail.nonce);
+		encode_u32_varint(thumbnail.ciphertext.len() as u32, out);
+		out.extend_from_slice(&thumbnail.ciphertext);
+	}
+}
+
 pub fn encode_file_offer_v1(offer: &FileOffer) -> Vec<u8> {
 	let mut payload = Vec::new();
 	encode_string(&mut payload, &offer.id);
-	encode_string(&mut payload, &offer.filename);
-	encode_string(&mut payload, &offer.mime_type);
-	encode_u64_varint(offer.size, &mut payload);
+	encode_offer_metadata(&offer.metadata(), &mut payload);
 
 	let frame = Frame {
 		frame_type: FrameType::FileOffer,
@@ -197,10 +754,13 @@ pub fn decode_file_accept_payload_v1(payload: &[u8]) -> Result<String, DecodeErr
 	Ok(id)
 }
 
-pub fn encode_file_reject_v1(id: &str, reason: &str) -> Vec<u8> {
+pub fn encode_file_reject_v1(reject: &FileReject) -> Vec<u8> {
 	let mut payload = Vec::new();
-	encode_string(&mut payload, id);
-	encode_string(&mut payload, reason);
+	encode_string(&mut payload, &reject.id);
+	encode_string(&mut payload, &reject.reason);
+	// Trailing, so older decoders still read the id and text
+	payload.push(reject.code as u8);
+	encode_u64_varint(reject.detail, &mut payload);
 	let frame = Frame {
 		frame_type: FrameType::FileReject,
 		flags: 0,
@@ -237,25 +797,401 @@ pub fn decode_encrypted_envelope_payload_v1(
 	Ok((nonce, ciphertext))
 }
 
+/// AEAD used for an EncryptedEnvelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CipherSuite {
+	XChaCha20Poly1305 = 0x01,
+	Aes256Gcm = 0x02,
+}
+
+impl CipherSuite {
+	pub fn from_u8(value: u8) -> Option<Self> {
+		Some(match value {
+			0x01 => Self::XChaCha20Poly1305,
+			0x02 => Self::Aes256Gcm,
+			_ => return None,
+		})
+	}
+
+	pub fn nonce_len(self) -> usize {
+		match self {
+			Self::XChaCha20Poly1305 => ENVELOPE_NONCE_LEN,
+			Self::Aes256Gcm => ENVELOPE_AES_GCM_NONCE_LEN,
+		}
+	}
+
+	/// Suite to seal with once both Hello capability sets are known:
+	/// AES-256-GCM only if both sides advertise it, XChaCha20-Poly1305 otherwise.
+	pub fn negotiate(local_capabilities: u32, remote_capabilities: u32) -> Self {
+		if local_capabilities & remote_capabilities & HELLO_CAP_AES_256_GCM != 0 {
+			Self::Aes256Gcm
+		} else {
+			Self::XChaCha20Poly1305
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedEnvelope {
+	pub suite: CipherSuite,
+	/// `suite.nonce_len()` bytes.
+	pub nonce: Vec<u8>,
+	pub ciphertext: Vec<u8>,
+}
+
+/// EncryptedEnvelope sealed with `suite`. XChaCha20-Poly1305 envelopes keep
+/// the unflagged layout so peers that predate cipher suites can open them.
+/// `nonce` must be `suite.nonce_len()` bytes.
+pub fn encode_encrypted_envelope_with_suite_v1(
+	suite: CipherSuite,
+	nonce: &[u8],
+	ciphertext: &[u8],
+) -> Result<Vec<u8>, EncodeError> {
+	let wrong_length = EncodeError::NonceLength { len: nonce.len(), expected: suite.nonce_len() };
+	if suite == CipherSuite::XChaCha20Poly1305 {
+		let nonce = nonce.try_into().map_err(|_| wrong_length)?;
+		return Ok(encode_encrypted_envelope_v1(nonce, ciphertext));
+	}
+	if nonce.len() != suite.nonce_len() {
+		return Err(wrong_length);
+	}
+	let mut payload = Vec::with_capacity(1 + nonce.len() + ciphertext.len());
+	payload.push(suite as u8);
+	payload.extend_from_slice(nonce);
+	payload.extend_from_slice(ciphertext);
+	let frame = Frame {
+		frame_type: FrameType::EncryptedEnvelope,
+		flags: ENVELOPE_FLAG_CIPHER_SUITE,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	Ok(out)
+}
+
+/// Decode an EncryptedEnvelope of any cipher suite. Unlike
+/// `decode_encrypted_envelope_payload_v1`, this reads the header flags.
+pub fn decode_encrypted_envelope_frame_v1(frame: &Frame) -> Result<EncryptedEnvelope, DecodeError> {
+	let (suite, rest) = if frame.flags & ENVELOPE_FLAG_CIPHER_SUITE != 0 {
+		let (&raw, rest) = frame.payload.split_first().ok_or(DecodeError::BadEnvelope)?;
+		let suite = CipherSuite::from_u8(raw).ok_or(DecodeError::UnknownCipherSuite { suite: raw })?;
+		(suite, rest)
+	} else {
+		(CipherSuite::XChaCha20Poly1305, frame.payload.as_slice())
+	};
+	if rest.len() < suite.nonce_len() {
+		return Err(DecodeError::BadEnvelope);
+	}
+	let (nonce, ciphertext) = rest.split_at(suite.nonce_len());
+	Ok(EncryptedEnvelope {
+		suite,
+		nonce: nonce.to_vec(),
+		ciphertext: ciphertext.to_vec(),
+	})
+}
+
+/// Hello extension: largest FileChunk data the peer accepts, as a varint.
+pub const HELLO_EXT_MAX_CHUNK_SIZE: u8 = 0x01;
+
+/// Opening message of a session: what this peer supports (`HELLO_CAP_*`).
+/// After the capabilities come extensions, each a tag byte, a length byte
+/// and that many value bytes (`HELLO_EXT_*`). Unknown capability bits,
+/// unknown extensions and trailing bytes that don't make up a whole
+/// extension are ignored so later versions can extend it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Hello {
+	pub capabilities: u32,
+	/// Largest FileChunk data the peer accepts, in bytes; `None` if it
+	/// states no limit (and from older peers). Never `Some(0)`: decoding
+	/// rejects it.
+	pub max_chunk_size: Option<u32>,
+}
+
+impl Hello {
+	/// What both sides of a session can use: the capabilities both
+	/// advertise and the smaller stated chunk size. Send only what this
+	/// allows, so neither side gets frames its build can't parse.
+	pub fn intersect(&self, remote: &Hello) -> Hello {
+		let max_chunk_size = match (self.max_chunk_size, remote.max_chunk_size) {
+			(Some(a), Some(b)) => Some(a.min(b)),
+			(a, b) => a.or(b),
+		};
+		Hello {
+			capabilities: self.capabilities & remote.capabilities,
+			max_chunk_size,
+		}
+	}
+
+	/// Whether every bit of `capability` (`HELLO_CAP_*`) is set.
+	pub fn supports(&self, capability: u32) -> bool {
+		self.capabilities & capability == capability
+	}
+}
+
+pub fn encode_hello_v1(hello: &Hello) -> Vec<u8> {
+	let mut payload = Vec::with_capacity(2 * MAX_U32_VARINT_LEN + 2);
+	encode_u32_varint(hello.capabilities, &mut payload);
+	if let Some(max_chunk_size) = hello.max_chunk_size {
+		let mut value = Vec::with_capacity(MAX_U32_VARINT_LEN);
+		encode_u32_varint(max_chunk_size, &mut value);
+		payload.extend_from_slice(&[HELLO_EXT_MAX_CHUNK_SIZE, value.len() as u8]);
+		payload.extend_from_slice(&value);
+	}
+	let frame = Frame {
+		frame_type: FrameType::Hello,
+		flags: 0,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+pub fn decode_hello_payload_v1(payload: &[u8]) -> Result<Hello, DecodeError> {
+	let (capabilities, used) = decode_u32_varint(payload)?;
+	let mut rest = &payload[used..];
+	let mut max_chunk_size = None;
+	while let [tag, len, tail @ ..] = rest {
+		let Some((value, tail)) = tail.split_at_checked(*len as usize) else {
+			break;
+		};
+		if *tag == HELLO_EXT_MAX_CHUNK_SIZE {
+			let size = match decode_u32_varint(value) {
+				Ok((size, used)) if used == value.len() && size > 0 => size,
+				_ => return Err(DecodeError::BadHello),
+			};
+			max_chunk_size = Some(size);
+		}
+		rest = tail;
+	}
+	Ok(Hello {
+		capabilities,
+		max_chunk_size,
+	})
+}
+
+/// Why a peer is ending the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum GoodbyeReason {
+	/// No reason given, or one this version doesn't know.
+	#[default]
+	Unspecified = 0,
+	/// The user closed the chat or left the project.
+	UserLeft = 1,
+	/// The tab or app is shutting down.
+	AppClosing = 2,
+	/// Closed after a period without activity.
+	Idle = 3,
+	/// The other side sent something this peer won't accept.
+	ProtocolViolation = 4,
+	/// Reloading or switching networks; expect the peer back shortly.
+	Restarting = 5,
+}
+
+impl GoodbyeReason {
+	pub const ALL: [GoodbyeReason; 6] = [
+		Self::Unspecified,
+		Self::UserLeft,
+		Self::AppClosing,
+		Self::Idle,
+		Self::ProtocolViolation,
+		Self::Restarting,
+	];
+
+	pub fn from_u8(value: u8) -> Option<Self> {
+		Self::ALL.into_iter().find(|r| *r as u8 == value)
+	}
+
+	/// Name used by the wasm bindings, e.g. `"user-left"`.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Unspecified => "unspecified",
+			Self::UserLeft => "user-left",
+			Self::AppClosing => "app-closing",
+			Self::Idle => "idle",
+			Self::ProtocolViolation => "protocol-violation",
+			Self::Restarting => "restarting",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|r| r.name() == name)
+	}
+}
+
+/// Last frame of a deliberately closed session, sent just before the
+/// channel closes so the peer can tell leaving from a lost connection
+/// (see `session::SessionEnd`).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Goodbye {
+	pub reason: GoodbyeReason,
+	/// Opaque token to present when reconnecting, typically with
+	/// `Restarting`; empty if none.
+	pub resume_ticket: Vec<u8>,
+}
+
+pub fn encode_goodbye_v1(goodbye: &Goodbye) -> Vec<u8> {
+	let mut payload = Vec::with_capacity(1 + MAX_U32_VARINT_LEN + goodbye.resume_ticket.len());
+	payload.push(goodbye.reason as u8);
+	if !goodbye.resume_ticket.is_empty() {
+		encode_u32_varint(goodbye.resume_ticket.len() as u32, &mut payload);
+		payload.extend_from_slice(&goodbye.resume_ticket);
+	}
+	let frame = Frame {
+		frame_type: FrameType::Goodbye,
+		flags: 0,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+/// Unknown reasons read as `Unspecified`; the peer is leaving either way.
+pub fn decode_goodbye_payload_v1(payload: &[u8]) -> Result<Goodbye, DecodeError> {
+	let (&reason, rest) = payload.split_first().ok_or(DecodeError::UnexpectedEof)?;
+	let resume_ticket = if rest.is_empty() {
+		Vec::new()
+	} else {
+		let (len, used) = decode_u32_varint(rest)?;
+		used.checked_add(len as usize)
+			.and_then(|end| rest.get(used..end))
+			.ok_or(DecodeError::UnexpectedEof)?
+			.to_vec()
+	};
+	Ok(Goodbye {
+		reason: GoodbyeReason::from_u8(reason).unwrap_or_default(),
+		resume_ticket,
+	})
+}
+
 pub fn decode_file_reject_payload_v1(payload: &[u8]) -> Result<FileReject, DecodeError> {
 	let (id, i1) = decode_string(payload)?;
-	let (reason, _i2) = decode_string(&payload[i1..])?;
-	Ok(FileReject { id, reason })
+	let (reason, i2) = decode_string(&payload[i1..])?;
+	let rest = &payload[i1 + i2..];
+	let (code, detail) = match rest.split_first() {
+		Some((&code, detail)) => {
+			let code = RejectCode::from_u8(code).unwrap_or(RejectCode::Unspecified);
+			let detail = if detail.is_empty() { 0 } else { decode_u64_varint(detail)?.0 };
+			(code, detail)
+		}
+		None => (RejectCode::Unspecified, 0),
+	};
+	Ok(FileReject {
+		id,
+		code,
+		detail,
+		reason,
+	})
 }
 
 pub fn decode_file_offer_payload_v1(payload: &[u8]) -> Result<FileOffer, DecodeError> {
 	let (id, i1) = decode_string(payload)?;
-	let (filename, i2) = decode_string(&payload[i1..])?;
-	let (mime_type, i3) = decode_string(&payload[i1 + i2..])?;
-	let (size, _i4) = decode_u64_varint(&payload[i1 + i2 + i3..])?;
-	Ok(FileOffer {
-		id,
+	let metadata = decode_offer_metadata(&payload[i1..])?;
+	Ok(FileOffer::from_metadata(id, metadata))
+}
+
+pub fn decode_offer_metadata(input: &[u8]) -> Result<OfferMetadata, DecodeError> {
+	let (filename, i1) = decode_string(input)?;
+	let (mime_type, i2) = decode_string(&input[i1..])?;
+	let (size, i3) = decode_u64_varint(&input[i1 + i2..])?;
+	let mut rest = &input[i1 + i2 + i3..];
+	let merkle = if rest.is_empty() {
+		None
+	} else {
+		let (leaf_size, n) = decode_u32_varint(rest)?;
+		let root = rest
+			.get(n..n + MERKLE_HASH_LEN)
+			.ok_or(DecodeError::UnexpectedEof)?;
+		let mut merkle = MerkleRoot {
+			leaf_size,
+			root: [0u8; MERKLE_HASH_LEN],
+		};
+		merkle.root.copy_from_slice(root);
+		rest = &rest[n + MERKLE_HASH_LEN..];
+		(leaf_size != 0).then_some(merkle)
+	};
+	let thumbnail = if rest.is_empty() {
+		None
+	} else {
+		let nonce_bytes = rest.get(..ENVELOPE_NONCE_LEN).ok_or(DecodeError::UnexpectedEof)?;
+		let mut nonce = [0u8; ENVELOPE_NONCE_LEN];
+		nonce.copy_from_slice(nonce_bytes);
+		let (len, n) = decode_u32_varint(&rest[ENVELOPE_NONCE_LEN..])?;
+		let start = ENVELOPE_NONCE_LEN + n;
+		let ciphertext = start
+			.checked_add(len as usize)
+			.and_then(|end| rest.get(start..end))
+			.ok_or(DecodeError::UnexpectedEof)?;
+		Some(OfferThumbnail {
+			nonce,
+			ciphertext: ciphertext.to_vec(),
+		})
+	};
+	Ok(OfferMetadata {
 		filename,
 		mime_type,
 		size,
+		merkle,
+		thumbnail,
+	})
+}
+
+pub fn encode_file_offer_sealed_v1(offer: &SealedFileOffer) -> Vec<u8> {
+	let mut payload = Vec::with_capacity(offer.id.len() + ENVELOPE_NONCE_LEN + offer.ciphertext.len() + 5);
+	encode_string(&mut payload, &offer.id);
+	payload.extend_from_slice(&offer.nonce);
+	payload.extend_from_slice(&offer.ciphertext);
+	let frame = Frame {
+		frame_type: FrameType::FileOfferSealed,
+		flags: 0,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+pub fn decode_file_offer_sealed_payload_v1(payload: &[u8]) -> Result<SealedFileOffer, DecodeError> {
+	let (id, i1) = decode_string(payload)?;
+	let nonce_bytes = payload
+		.get(i1..i1 + ENVELOPE_NONCE_LEN)
+		.ok_or(DecodeError::UnexpectedEof)?;
+	let mut nonce = [0u8; ENVELOPE_NONCE_LEN];
+	nonce.copy_from_slice(nonce_bytes);
+	Ok(SealedFileOffer {
+		id,
+		nonce,
+		ciphertext: payload[i1 + ENVELOPE_NONCE_LEN..].to_vec(),
 	})
 }
 
+pub fn encode_file_reveal_v1(reveal: &FileReveal) -> Vec<u8> {
+	let mut payload = Vec::new();
+	encode_string(&mut payload, &reveal.id);
+	payload.extend_from_slice(&reveal.key);
+	let frame = Frame {
+		frame_type: FrameType::FileReveal,
+		flags: 0,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+pub fn decode_file_reveal_payload_v1(payload: &[u8]) -> Result<FileReveal, DecodeError> {
+	let (id, i1) = decode_string(payload)?;
+	if payload.len() != i1 + OFFER_KEY_LEN {
+		return Err(DecodeError::UnexpectedEof);
+	}
+	let mut key = [0u8; OFFER_KEY_LEN];
+	key.copy_from_slice(&payload[i1..]);
+	Ok(FileReveal { id, key })
+}
+
 pub fn encode_file_chunk_v1(id: &str, chunk_index: u32, data: &[u8]) -> Vec<u8> {
 	let mut payload = Vec::with_capacity(id.len() + data.len() + 16);
 	encode_string(&mut payload, id);
@@ -272,17 +1208,144 @@ pub fn encode_file_chunk_v1(id: &str, chunk_index: u32, data: &[u8]) -> Vec<u8>
 	out
 }
 
+/// Encoded length of `encode_file_chunk_v1(id, chunk_index, data)` with
+/// `data_len` bytes of data, for sizing pooled buffers.
+pub fn file_chunk_v1_len(id: &str, chunk_index: u32, data_len: usize) -> usize {
+	let payload_len =
+		varint_len(id.len() as u64) + id.len() + varint_len(chunk_index as u64) + data_len;
+	5 + varint_len(payload_len as u64) + payload_len
+}
+
+/// `encode_file_chunk_v1` into a caller-provided buffer, without allocating.
+/// Returns the bytes written. Panics if `out` is shorter than
+/// `file_chunk_v1_len(id, chunk_index, data.len())`.
+pub fn encode_file_chunk_v1_into(id: &str, chunk_index: u32, data: &[u8], out: &mut [u8]) -> usize {
+	let payload_len =
+		varint_len(id.len() as u64) + id.len() + varint_len(chunk_index as u64) + data.len();
+	let mut i = encode_header_v1_into(FrameType::FileChunk, 0, payload_len as u32, out);
+	i += encode_u32_varint_into(id.len() as u32, &mut out[i..]);
+	out[i..i + id.len()].copy_from_slice(id.as_bytes());
+	i += id.len();
+	i += encode_u32_varint_into(chunk_index, &mut out[i..]);
+	out[i..i + data.len()].copy_from_slice(data);
+	i + data.len()
+}
+
+/// FileChunk carrying the Merkle proof for `chunk_index`. Only for peers
+/// whose Hello sets `HELLO_CAP_MERKLE_PROOFS`.
+pub fn encode_file_chunk_with_proof_v1(
+	id: &str,
+	chunk_index: u32,
+	proof: &[MerkleHash],
+	data: &[u8],
+) -> Result<Vec<u8>, EncodeError> {
+	encode_file_chunk_flagged(id, chunk_index, Some(proof), data, FILE_CHUNK_FLAG_PROOF)
+}
+
+/// FileChunk with `data` DEFLATE-compressed (`FILE_CHUNK_FLAG_COMPRESSED`),
+/// plus the Merkle proof unless `proof` is `None`. Data that does not shrink
+/// goes out uncompressed. Only for peers whose Hello sets
+/// `HELLO_CAP_COMPRESSED_CHUNKS`, and for files `compressibility::should_compress`
+/// accepts; the proof still covers the uncompressed data.
+pub fn encode_file_chunk_compressed_v1(
+	id: &str,
+	chunk_index: u32,
+	proof: Option<&[MerkleHash]>,
+	data: &[u8],
+) -> Result<Vec<u8>, EncodeError> {
+	let proof_flag = if proof.is_some() { FILE_CHUNK_FLAG_PROOF } else { 0 };
+	match compressibility::deflate(data) {
+		Some(compressed) => encode_file_chunk_flagged(
+			id,
+			chunk_index,
+			proof,
+			&compressed,
+			proof_flag | FILE_CHUNK_FLAG_COMPRESSED,
+		),
+		None => encode_file_chunk_flagged(id, chunk_index, proof, data, proof_flag),
+	}
+}
+
+fn encode_file_chunk_flagged(
+	id: &str,
+	chunk_index: u32,
+	proof: Option<&[MerkleHash]>,
+	data: &[u8],
+	flags: u8,
+) -> Result<Vec<u8>, EncodeError> {
+	let proof = proof.unwrap_or_default();
+	if proof.len() > MAX_PROOF_LEN {
+		return Err(EncodeError::ProofTooLong { len: proof.len(), max: MAX_PROOF_LEN });
+	}
+	let mut payload =
+		Vec::with_capacity(id.len() + 1 + proof.len() * MERKLE_HASH_LEN + data.len() + 16);
+	encode_string(&mut payload, id);
+	encode_u32_varint(chunk_index, &mut payload);
+	if flags & FILE_CHUNK_FLAG_PROOF != 0 {
+		payload.push(proof.len() as u8);
+		for hash in proof {
+			payload.extend_from_slice(hash);
+		}
+	}
+	payload.extend_from_slice(data);
+
+	let frame = Frame {
+		frame_type: FrameType::FileChunk,
+		flags,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	Ok(out)
+}
+
+/// Decode a FileChunk payload sent without a proof. Use
+/// `decode_file_chunk_frame_v1` when the frame flags are at hand.
 pub fn decode_file_chunk_payload_v1(payload: &[u8]) -> Result<FileChunk, DecodeError> {
+	decode_file_chunk_body(payload, 0)
+}
+
+/// Decode a FileChunk frame, including its Merkle proof if flagged, and
+/// inflating compressed data.
+pub fn decode_file_chunk_frame_v1(frame: &Frame) -> Result<FileChunk, DecodeError> {
+	decode_file_chunk_body(&frame.payload, frame.flags)
+}
+
+fn decode_file_chunk_body(payload: &[u8], flags: u8) -> Result<FileChunk, DecodeError> {
 	let (id, i1) = decode_string(payload)?;
 	let (chunk_index, n2) = decode_u32_varint(&payload[i1..])?;
-	let data_start = i1 + n2;
-	if data_start > payload.len() {
-		return Err(DecodeError::UnexpectedEof);
+	let mut data_start = i1 + n2;
+	let mut proof = Vec::new();
+	if flags & FILE_CHUNK_FLAG_PROOF != 0 {
+		let count = *payload.get(data_start).ok_or(DecodeError::UnexpectedEof)? as usize;
+		if count > MAX_PROOF_LEN {
+			return Err(DecodeError::BadMerkleProof);
+		}
+		data_start += 1;
+		let hashes = payload
+			.get(data_start..data_start + count * MERKLE_HASH_LEN)
+			.ok_or(DecodeError::UnexpectedEof)?;
+		proof = hashes
+			.chunks_exact(MERKLE_HASH_LEN)
+			.map(|h| {
+				let mut hash = [0u8; MERKLE_HASH_LEN];
+				hash.copy_from_slice(h);
+				hash
+			})
+			.collect();
+		data_start += hashes.len();
 	}
+	let data = payload.get(data_start..).ok_or(DecodeError::UnexpectedEof)?;
+	let data = if flags & FILE_CHUNK_FLAG_COMPRESSED != 0 {
+		compressibility::inflate(data, MAX_INFLATED_CHUNK_LEN).ok_or(DecodeError::BadCompressedChunk)?
+	} else {
+		data.to_vec()
+	};
 	Ok(FileChunk {
 		id,
 		chunk_index,
-		data: payload[data_start..].to_vec(),
+		proof,
+		data,
 	})
 }
 
@@ -304,6 +1367,281 @@ pub fn decode_file_end_payload_v1(payload: &[u8]) -> Result<String, DecodeError>
 	Ok(id)
 }
 
+fn encode_acl_update_body(update: &AclUpdate, out: &mut Vec<u8>) {
+	encode_string(out, &update.project_id);
+	encode_string(out, &update.user_id);
+	match update.action {
+		AclAction::Grant { role, expires_at } => {
+			out.push(0);
+			out.push(role);
+			// 0 means "never expires".
+			encode_u64_varint(expires_at.unwrap_or(0), out);
+		}
+		AclAction::Revoke => out.push(1),
+	}
+	encode_u64_varint(update.timestamp, out);
+	out.extend_from_slice(&update.author);
+}
+
+/// Canonical bytes an author signs for an AclUpdate.
+pub fn acl_update_signing_bytes(update: &AclUpdate) -> Vec<u8> {
+	let mut out = ACL_SIGNING_CONTEXT.to_vec();
+	encode_acl_update_body(update, &mut out);
+	out
+}
+
+pub fn encode_acl_update_v1(update: &AclUpdate) -> Vec<u8> {
+	let mut payload = Vec::new();
+	encode_acl_update_body(update, &mut payload);
+	payload.extend_from_slice(&update.signature);
+	let frame = Frame {
+		frame_type: FrameType::AclUpdate,
+		flags: 0,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+pub fn decode_acl_update_payload_v1(payload: &[u8]) -> Result<AclUpdate, DecodeError> {
+	let (project_id, mut i) = decode_string(payload)?;
+	let (user_id, n) = decode_string(&payload[i..])?;
+	i += n;
+	let action_tag = *payload.get(i).ok_or(DecodeError::UnexpectedEof)?;
+	i += 1;
+	let action = match action_tag {
+		0 => {
+			let role = *payload.get(i).ok_or(DecodeError::UnexpectedEof)?;
+			if role > 2 {
+				return Err(DecodeError::BadAclUpdate);
+			}
+			i += 1;
+			let (expires_at, n) = decode_u64_varint(&payload[i..])?;
+			i += n;
+			AclAction::Grant {
+				role,
+				expires_at: (expires_at != 0).then_some(expires_at),
+			}
+		}
+		1 => AclAction::Revoke,
+		_ => return Err(DecodeError::BadAclUpdate),
+	};
+	let (timestamp, n) = decode_u64_varint(&payload[i..])?;
+	i += n;
+	if payload.len() != i + ACL_AUTHOR_KEY_LEN + ACL_SIGNATURE_LEN {
+		return Err(DecodeError::BadAclUpdate);
+	}
+	let mut author = [0u8; ACL_AUTHOR_KEY_LEN];
+	author.copy_from_slice(&payload[i..i + ACL_AUTHOR_KEY_LEN]);
+	let mut signature = [0u8; ACL_SIGNATURE_LEN];
+	signature.copy_from_slice(&payload[i + ACL_AUTHOR_KEY_LEN..]);
+	Ok(AclUpdate {
+		project_id,
+		user_id,
+		action,
+		timestamp,
+		author,
+		signature,
+	})
+}
+
+fn encode_project_invite_body(invite: &ProjectInvite, out: &mut Vec<u8>) {
+	encode_string(out, &invite.project_id);
+	out.push(invite.role);
+	encode_u64_varint(invite.timestamp, out);
+	out.extend_from_slice(&invite.inviter);
+	out.extend_from_slice(&invite.invitee);
+	out.extend_from_slice(&invite.sealed_key);
+}
+
+/// Canonical bytes an inviter signs for a ProjectInvite.
+pub fn project_invite_signing_bytes(invite: &ProjectInvite) -> Vec<u8> {
+	let mut out = INVITE_SIGNING_CONTEXT.to_vec();
+	encode_project_invite_body(invite, &mut out);
+	out
+}
+
+pub fn encode_project_invite_v1(invite: &ProjectInvite) -> Vec<u8> {
+	let mut payload = Vec::new();
+	encode_project_invite_body(invite, &mut payload);
+	payload.extend_from_slice(&invite.signature);
+	let frame = Frame {
+		frame_type: FrameType::ProjectInvite,
+		flags: 0,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+pub fn decode_project_invite_payload_v1(payload: &[u8]) -> Result<ProjectInvite, DecodeError> {
+	let (project_id, mut i) = decode_string(payload)?;
+	let role = *payload.get(i).ok_or(DecodeError::UnexpectedEof)?;
+	if role > 2 {
+		return Err(DecodeError::BadProjectInvite);
+	}
+	i += 1;
+	let (timestamp, n) = decode_u64_varint(&payload[i..])?;
+	i += n;
+	let rest = &payload[i..];
+	if rest.len() != 2 * ACL_AUTHOR_KEY_LEN + PROJECT_INVITE_SEALED_KEY_LEN + ACL_SIGNATURE_LEN {
+		return Err(DecodeError::BadProjectInvite);
+	}
+	let (inviter, rest) = rest.split_at(ACL_AUTHOR_KEY_LEN);
+	let (invitee, rest) = rest.split_at(ACL_AUTHOR_KEY_LEN);
+	let (sealed_key, signature) = rest.split_at(PROJECT_INVITE_SEALED_KEY_LEN);
+	Ok(ProjectInvite {
+		project_id,
+		role,
+		timestamp,
+		inviter: inviter.try_into().unwrap(),
+		invitee: invitee.try_into().unwrap(),
+		sealed_key: sealed_key.try_into().unwrap(),
+		signature: signature.try_into().unwrap(),
+	})
+}
+
+/// One recipient's copy in a MultiEnvelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiEnvelopeEntry {
+	/// Peer id the copy is for.
+	pub recipient: String,
+	/// EncryptedEnvelope frame sealed with that peer's session key.
+	pub envelope: Vec<u8>,
+}
+
+/// A frame sealed once per recipient and batched into one container, for
+/// relaying to a small group over a mesh. Each peer opens only its own copy.
+/// Panics with more than `MULTI_ENVELOPE_MAX_RECIPIENTS` entries.
+pub fn encode_multi_envelope_v1(entries: &[MultiEnvelopeEntry]) -> Vec<u8> {
+	assert!(entries.len() <= MULTI_ENVELOPE_MAX_RECIPIENTS, "too many recipients");
+	let mut payload = vec![entries.len() as u8];
+	for entry in entries {
+		encode_string(&mut payload, &entry.recipient);
+		encode_u32_varint(entry.envelope.len() as u32, &mut payload);
+		payload.extend_from_slice(&entry.envelope);
+	}
+	let frame = Frame {
+		frame_type: FrameType::MultiEnvelope,
+		flags: 0,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+pub fn decode_multi_envelope_payload_v1(payload: &[u8]) -> Result<Vec<MultiEnvelopeEntry>, DecodeError> {
+	let (&count, mut rest) = payload.split_first().ok_or(DecodeError::UnexpectedEof)?;
+	let mut entries = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		let (recipient, n) = decode_string(rest)?;
+		let (len, m) = decode_u32_varint(rest.get(n..).ok_or(DecodeError::UnexpectedEof)?)?;
+		let start = n + m;
+		let end = start.checked_add(len as usize).ok_or(DecodeError::UnexpectedEof)?;
+		let envelope = rest.get(start..end).ok_or(DecodeError::UnexpectedEof)?;
+		entries.push(MultiEnvelopeEntry { recipient, envelope: envelope.to_vec() });
+		rest = &rest[end..];
+	}
+	if !rest.is_empty() {
+		return Err(DecodeError::BadMultiEnvelope);
+	}
+	Ok(entries)
+}
+
+/// Message of a burn-after-reading chat, sealed under its own ratcheted
+/// key; see `crate::ephemeral`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EphemeralEnvelope {
+	/// Position in the sender's chain, which picks the message key.
+	pub index: u64,
+	/// Milliseconds until both sides wipe the message.
+	pub ttl_ms: u64,
+	/// Random per message: the chain, and so each message key, repeats
+	/// whenever a session key is reused.
+	pub nonce: [u8; ENVELOPE_NONCE_LEN],
+	/// XChaCha20-Poly1305 under the message key and `nonce`, with
+	/// `ephemeral_associated_data_v1` as associated data.
+	pub ciphertext: Vec<u8>,
+}
+
+/// Bytes an EphemeralEnvelope's ciphertext is bound to: its index and TTL
+/// as they appear on the wire, so neither can be changed in transit.
+pub fn ephemeral_associated_data_v1(index: u64, ttl_ms: u64) -> Vec<u8> {
+	let mut out = Vec::with_capacity(2 * MAX_U64_VARINT_LEN);
+	encode_u64_varint(index, &mut out);
+	encode_u64_varint(ttl_ms, &mut out);
+	out
+}
+
+pub fn encode_ephemeral_envelope_v1(envelope: &EphemeralEnvelope) -> Vec<u8> {
+	let mut payload = ephemeral_associated_data_v1(envelope.index, envelope.ttl_ms);
+	payload.extend_from_slice(&envelope.nonce);
+	payload.extend_from_slice(&envelope.ciphertext);
+	let frame = Frame {
+		frame_type: FrameType::EphemeralEnvelope,
+		flags: 0,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+pub fn decode_ephemeral_envelope_payload_v1(payload: &[u8]) -> Result<EphemeralEnvelope, DecodeError> {
+	let (index, i1) = decode_u64_varint(payload)?;
+	let (ttl_ms, i2) = decode_u64_varint(&payload[i1..])?;
+	let (nonce, ciphertext) = payload[i1 + i2..]
+		.split_first_chunk::<ENVELOPE_NONCE_LEN>()
+		.ok_or(DecodeError::UnexpectedEof)?;
+	Ok(EphemeralEnvelope {
+		index,
+		ttl_ms,
+		nonce: *nonce,
+		ciphertext: ciphertext.to_vec(),
+	})
+}
+
+/// Payload of RekeyRequest and RekeyResponse: the epoch the new key is for
+/// and the sender's fresh X25519 public key. Both are sent sealed under the
+/// current envelope key; see `crate::rekey`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rekey {
+	pub epoch: u32,
+	pub public_key: [u8; REKEY_PUBLIC_KEY_LEN],
+}
+
+fn encode_rekey(frame_type: FrameType, rekey: &Rekey) -> Vec<u8> {
+	let mut payload = Vec::with_capacity(MAX_U32_VARINT_LEN + REKEY_PUBLIC_KEY_LEN);
+	encode_u32_varint(rekey.epoch, &mut payload);
+	payload.extend_from_slice(&rekey.public_key);
+	let frame = Frame {
+		frame_type,
+		flags: 0,
+		payload,
+	};
+	let mut out = Vec::new();
+	encode_v1(&frame, &mut out);
+	out
+}
+
+pub fn encode_rekey_request_v1(rekey: &Rekey) -> Vec<u8> {
+	encode_rekey(FrameType::RekeyRequest, rekey)
+}
+
+pub fn encode_rekey_response_v1(rekey: &Rekey) -> Vec<u8> {
+	encode_rekey(FrameType::RekeyResponse, rekey)
+}
+
+/// Payload of either rekey frame.
+pub fn decode_rekey_payload_v1(payload: &[u8]) -> Result<Rekey, DecodeError> {
+	let (epoch, i) = decode_u32_varint(payload)?;
+	let public_key = payload[i..].try_into().map_err(|_| DecodeError::BadRekey)?;
+	Ok(Rekey { epoch, public_key })
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -344,6 +1682,55 @@ mod tests {
 		assert_eq!(used, bytes.len());
 		assert_eq!(decoded.frame_type, FrameType::ChatText);
 		assert_eq!(decoded.payload, b"hola".to_vec());
+		let chat = decode_chat_text_frame_v1(&decoded).unwrap();
+		assert_eq!(chat, ChatText { text: "hola".to_string(), attribution: None });
+	}
+
+	#[test]
+	fn attributed_chat_text_roundtrip() {
+		let attribution = ChatAttribution {
+			sender: [7u8; CHAT_SENDER_KEY_LEN],
+			sequence: 300,
+			timestamp: 1_700_000_000_000,
+			signature: [9u8; CHAT_SIGNATURE_LEN],
+			bound: false,
+		};
+		let bytes = encode_attributed_chat_text_v1("¿qué tal?", &attribution);
+		let (frame, used) = decode_v1(&bytes, 1024).unwrap();
+		assert_eq!(used, bytes.len());
+		assert_eq!(frame.flags, CHAT_TEXT_FLAG_ATTRIBUTED);
+		let chat = decode_chat_text_frame_v1(&frame).unwrap();
+		assert_eq!(chat.text, "¿qué tal?");
+		assert_eq!(chat.attribution, Some(attribution.clone()));
+
+		let truncated = Frame { payload: frame.payload[..40].to_vec(), ..frame };
+		assert_eq!(decode_chat_text_frame_v1(&truncated), Err(DecodeError::UnexpectedEof));
+
+		let bound = ChatAttribution { bound: true, ..attribution };
+		let (frame, _) = decode_v1(&encode_attributed_chat_text_v1("hi", &bound), 1024).unwrap();
+		assert_eq!(frame.flags, CHAT_TEXT_FLAG_ATTRIBUTED | CHAT_TEXT_FLAG_BOUND);
+		assert_eq!(decode_chat_text_frame_v1(&frame).unwrap().attribution, Some(bound));
+	}
+
+	#[test]
+	fn chat_signing_bytes_cover_every_field() {
+		let base = chat_text_signing_bytes("hi", &[1u8; CHAT_SENDER_KEY_LEN], 1, 2);
+		assert!(base.starts_with(CHAT_SIGNING_CONTEXT));
+		assert_ne!(base, chat_text_signing_bytes("ho", &[1u8; CHAT_SENDER_KEY_LEN], 1, 2));
+		assert_ne!(base, chat_text_signing_bytes("hi", &[2u8; CHAT_SENDER_KEY_LEN], 1, 2));
+		assert_ne!(base, chat_text_signing_bytes("hi", &[1u8; CHAT_SENDER_KEY_LEN], 2, 2));
+		assert_ne!(base, chat_text_signing_bytes("hi", &[1u8; CHAT_SENDER_KEY_LEN], 1, 3));
+
+		let (alice, bob, carol) = ([1u8; CHAT_SENDER_KEY_LEN], [2u8; CHAT_SENDER_KEY_LEN], [3u8; CHAT_SENDER_KEY_LEN]);
+		let bound = bound_chat_text_signing_bytes("hi", &alice, &bob, b"room", 1, 2);
+		assert!(bound.starts_with(BOUND_CHAT_SIGNING_CONTEXT));
+		assert_ne!(bound, bound_chat_text_signing_bytes("hi", &alice, &carol, b"room", 1, 2));
+		assert_ne!(bound, bound_chat_text_signing_bytes("hi", &alice, &bob, b"hall", 1, 2));
+		// The channel is length-prefixed, so it can't absorb the sequence
+		assert_ne!(
+			bound_chat_text_signing_bytes("hi", &alice, &bob, b"room\x01", 2, 2),
+			bound_chat_text_signing_bytes("hi", &alice, &bob, b"room", 1, 2)
+		);
 	}
 
 	#[test]
@@ -353,6 +1740,8 @@ mod tests {
 			filename: "hello.txt".to_string(),
 			mime_type: "text/plain".to_string(),
 			size: 1234,
+			merkle: None,
+			thumbnail: None,
 		};
 		let bytes = encode_file_offer_v1(&offer);
 		let (frame, used) = decode_v1(&bytes, 1024 * 1024).unwrap();
@@ -360,6 +1749,74 @@ mod tests {
 		assert_eq!(frame.frame_type, FrameType::FileOffer);
 		let decoded_offer = decode_file_offer_payload_v1(&frame.payload).unwrap();
 		assert_eq!(decoded_offer, offer);
+
+		let with_root = FileOffer {
+			merkle: Some(MerkleRoot {
+				leaf_size: 64 * 1024,
+				root: [5u8; MERKLE_HASH_LEN],
+			}),
+			..offer
+		};
+		let (frame, _used) = decode_v1(&encode_file_offer_v1(&with_root), 1024 * 1024).unwrap();
+		assert_eq!(decode_file_offer_payload_v1(&frame.payload).unwrap(), with_root);
+		let truncated = &frame.payload[..frame.payload.len() - 1];
+		assert_eq!(decode_file_offer_payload_v1(truncated), Err(DecodeError::UnexpectedEof));
+
+		let thumbnail = OfferThumbnail {
+			nonce: [6u8; ENVELOPE_NONCE_LEN],
+			ciphertext: vec![7u8; 300],
+		};
+		for merkle in [None, with_root.merkle] {
+			let with_thumbnail = FileOffer {
+				merkle,
+				thumbnail: Some(thumbnail.clone()),
+				..with_root.clone()
+			};
+			let (frame, _used) = decode_v1(&encode_file_offer_v1(&with_thumbnail), 1024 * 1024).unwrap();
+			assert_eq!(decode_file_offer_payload_v1(&frame.payload).unwrap(), with_thumbnail);
+			let truncated = &frame.payload[..frame.payload.len() - 1];
+			assert_eq!(decode_file_offer_payload_v1(truncated), Err(DecodeError::UnexpectedEof));
+		}
+	}
+
+	#[test]
+	fn sealed_offer_and_reveal_roundtrip() {
+		let offer = FileOffer {
+			id: "id-s".to_string(),
+			filename: "secret.pdf".to_string(),
+			mime_type: "application/pdf".to_string(),
+			size: 99,
+			merkle: None,
+			thumbnail: None,
+		};
+		let mut plaintext = Vec::new();
+		encode_offer_metadata(&offer.metadata(), &mut plaintext);
+		assert_eq!(decode_offer_metadata(&plaintext).unwrap(), offer.metadata());
+
+		let sealed = SealedFileOffer {
+			id: offer.id.clone(),
+			nonce: [3u8; ENVELOPE_NONCE_LEN],
+			ciphertext: b"opaque".to_vec(),
+		};
+		let bytes = encode_file_offer_sealed_v1(&sealed);
+		assert!(!bytes.windows(6).any(|w| w == b"secret"));
+		let (frame, _used) = decode_v1(&bytes, 1024).unwrap();
+		assert_eq!(frame.frame_type, FrameType::FileOfferSealed);
+		assert_eq!(decode_file_offer_sealed_payload_v1(&frame.payload).unwrap(), sealed);
+		assert_eq!(
+			decode_file_offer_sealed_payload_v1(&frame.payload[..10]),
+			Err(DecodeError::UnexpectedEof)
+		);
+
+		let reveal = FileReveal {
+			id: offer.id.clone(),
+			key: [8u8; OFFER_KEY_LEN],
+		};
+		let (frame, _used) = decode_v1(&encode_file_reveal_v1(&reveal), 1024).unwrap();
+		assert_eq!(frame.frame_type, FrameType::FileReveal);
+		assert_eq!(decode_file_reveal_payload_v1(&frame.payload).unwrap(), reveal);
+		let truncated = &frame.payload[..frame.payload.len() - 1];
+		assert_eq!(decode_file_reveal_payload_v1(truncated), Err(DecodeError::UnexpectedEof));
 	}
 
 	#[test]
@@ -373,12 +1830,84 @@ mod tests {
 
 	#[test]
 	fn file_reject_roundtrip() {
-		let bytes = encode_file_reject_v1("id-r", "too big");
+		let reject = FileReject {
+			id: "id-r".to_string(),
+			code: RejectCode::TooLarge,
+			detail: 1 << 30,
+			reason: "too big".to_string(),
+		};
+		let bytes = encode_file_reject_v1(&reject);
 		let (frame, _used) = decode_v1(&bytes, 1024 * 1024).unwrap();
 		assert_eq!(frame.frame_type, FrameType::FileReject);
 		let rej = decode_file_reject_payload_v1(&frame.payload).unwrap();
 		assert_eq!(rej.id, "id-r");
 		assert_eq!(rej.reason, "too big");
+		assert_eq!(rej, reject);
+
+		// Older peers send only the id and text
+		let mut legacy = Vec::new();
+		encode_string(&mut legacy, "id-r");
+		encode_string(&mut legacy, "nope");
+		let rej = decode_file_reject_payload_v1(&legacy).unwrap();
+		assert_eq!((rej.code, rej.detail, rej.reason.as_str()), (RejectCode::Unspecified, 0, "nope"));
+		// Codes from newer peers degrade to Unspecified
+		legacy.push(200);
+		assert_eq!(decode_file_reject_payload_v1(&legacy).unwrap().code, RejectCode::Unspecified);
+
+		for code in RejectCode::ALL {
+			assert_eq!(RejectCode::from_u8(code as u8), Some(code));
+			assert_eq!(RejectCode::from_name(code.name()), Some(code));
+		}
+	}
+
+	#[test]
+	fn goodbye_roundtrip() {
+		for goodbye in [
+			Goodbye { reason: GoodbyeReason::UserLeft, resume_ticket: Vec::new() },
+			Goodbye { reason: GoodbyeReason::Restarting, resume_ticket: vec![9; 40] },
+		] {
+			let bytes = encode_goodbye_v1(&goodbye);
+			let (frame, _used) = decode_v1(&bytes, 1024).unwrap();
+			assert_eq!(frame.frame_type, FrameType::Goodbye);
+			assert_eq!(decode_goodbye_payload_v1(&frame.payload).unwrap(), goodbye);
+		}
+
+		// Reasons from newer peers degrade to Unspecified
+		assert_eq!(decode_goodbye_payload_v1(&[200]).unwrap(), Goodbye::default());
+		assert_eq!(decode_goodbye_payload_v1(&[]), Err(DecodeError::UnexpectedEof));
+		assert_eq!(decode_goodbye_payload_v1(&[1, 5, 1, 2]), Err(DecodeError::UnexpectedEof));
+
+		for reason in GoodbyeReason::ALL {
+			assert_eq!(GoodbyeReason::from_u8(reason as u8), Some(reason));
+			assert_eq!(GoodbyeReason::from_name(reason.name()), Some(reason));
+		}
+	}
+
+	#[test]
+	fn decode_error_names() {
+		// One of each variant, in `NAMES` order
+		let errors: [DecodeError; DecodeError::NAMES.len()] = [
+			DecodeError::UnexpectedEof,
+			DecodeError::BadMagic,
+			DecodeError::UnsupportedVersion { version: 2 },
+			DecodeError::UnknownFrameType { frame_type: 0x7F },
+			DecodeError::Varint(VarintError::Overflow),
+			DecodeError::LengthTooLarge { length: 2, max: 1 },
+			DecodeError::InvalidUtf8,
+			DecodeError::BadEnvelope,
+			DecodeError::UnknownCipherSuite { suite: 9 },
+			DecodeError::BadAclUpdate,
+			DecodeError::BadProjectInvite,
+			DecodeError::BadRekey,
+			DecodeError::BadMultiEnvelope,
+			DecodeError::BadMerkleProof,
+			DecodeError::BadCompressedChunk,
+			DecodeError::BadHello,
+		];
+		for (i, error) in errors.iter().enumerate() {
+			assert_eq!(error.index(), i);
+			assert_eq!(error.name(), DecodeError::NAMES[i]);
+		}
 	}
 
 	#[test]
@@ -393,6 +1922,171 @@ mod tests {
 		assert_eq!(ct2, ciphertext);
 	}
 
+	#[test]
+	fn envelope_cipher_suites() {
+		let legacy =
+			encode_encrypted_envelope_with_suite_v1(CipherSuite::XChaCha20Poly1305, &[7u8; 24], b"ct").unwrap();
+		assert_eq!(legacy, encode_encrypted_envelope_v1(&[7u8; 24], b"ct"));
+		let (frame, _used) = decode_v1(&legacy, 1024).unwrap();
+		let env = decode_encrypted_envelope_frame_v1(&frame).unwrap();
+		assert_eq!(env.suite, CipherSuite::XChaCha20Poly1305);
+
+		let bytes = encode_encrypted_envelope_with_suite_v1(CipherSuite::Aes256Gcm, &[5u8; 12], b"ct").unwrap();
+		let (mut frame, _used) = decode_v1(&bytes, 1024).unwrap();
+		assert_eq!(frame.flags, ENVELOPE_FLAG_CIPHER_SUITE);
+		let env = decode_encrypted_envelope_frame_v1(&frame).unwrap();
+		assert_eq!(env.suite, CipherSuite::Aes256Gcm);
+		assert_eq!((env.nonce, env.ciphertext), (vec![5u8; 12], b"ct".to_vec()));
+
+		frame.payload[0] = 0x09;
+		assert_eq!(
+			decode_encrypted_envelope_frame_v1(&frame).unwrap_err(),
+			DecodeError::UnknownCipherSuite { suite: 0x09 }
+		);
+
+		assert_eq!(
+			encode_encrypted_envelope_with_suite_v1(CipherSuite::Aes256Gcm, &[5u8; 24], b"ct"),
+			Err(EncodeError::NonceLength { len: 24, expected: 12 })
+		);
+		assert_eq!(
+			encode_encrypted_envelope_with_suite_v1(CipherSuite::XChaCha20Poly1305, &[5u8; 12], b"ct"),
+			Err(EncodeError::NonceLength { len: 12, expected: 24 })
+		);
+	}
+
+	#[test]
+	fn rekey_roundtrip() {
+		let rekey = Rekey { epoch: 300, public_key: [4u8; REKEY_PUBLIC_KEY_LEN] };
+		for (bytes, frame_type) in [
+			(encode_rekey_request_v1(&rekey), FrameType::RekeyRequest),
+			(encode_rekey_response_v1(&rekey), FrameType::RekeyResponse),
+		] {
+			let (frame, _used) = decode_v1(&bytes, 1024).unwrap();
+			assert_eq!(frame.frame_type, frame_type);
+			assert_eq!(decode_rekey_payload_v1(&frame.payload).unwrap(), rekey);
+			assert_eq!(
+				decode_rekey_payload_v1(&frame.payload[..frame.payload.len() - 1]),
+				Err(DecodeError::BadRekey)
+			);
+		}
+	}
+
+	#[test]
+	fn ephemeral_envelope_roundtrip() {
+		let envelope = EphemeralEnvelope {
+			index: 300,
+			ttl_ms: 60_000,
+			nonce: [5; ENVELOPE_NONCE_LEN],
+			ciphertext: vec![7; 20],
+		};
+		let bytes = encode_ephemeral_envelope_v1(&envelope);
+		let (frame, _used) = decode_v1(&bytes, 1024).unwrap();
+		assert_eq!(frame.frame_type, FrameType::EphemeralEnvelope);
+		assert_eq!(decode_ephemeral_envelope_payload_v1(&frame.payload).unwrap(), envelope);
+		assert!(frame.payload.starts_with(&ephemeral_associated_data_v1(300, 60_000)));
+		assert!(decode_ephemeral_envelope_payload_v1(&[0x80]).is_err());
+		assert_eq!(decode_ephemeral_envelope_payload_v1(&[1, 1, 5, 5]), Err(DecodeError::UnexpectedEof));
+	}
+
+	#[test]
+	fn multi_envelope_roundtrip() {
+		let entries: Vec<MultiEnvelopeEntry> = ["bob", "carol", "dave"]
+			.iter()
+			.enumerate()
+			.map(|(i, peer)| MultiEnvelopeEntry {
+				recipient: peer.to_string(),
+				envelope: encode_encrypted_envelope_v1(&[i as u8; ENVELOPE_NONCE_LEN], &[9u8; 40]),
+			})
+			.collect();
+		let bytes = encode_multi_envelope_v1(&entries);
+		let (frame, _used) = decode_v1(&bytes, 1024).unwrap();
+		assert_eq!(frame.frame_type, FrameType::MultiEnvelope);
+		assert_eq!(decode_multi_envelope_payload_v1(&frame.payload).unwrap(), entries);
+
+		let mut trailing = frame.payload.clone();
+		trailing.push(0);
+		assert_eq!(decode_multi_envelope_payload_v1(&trailing), Err(DecodeError::BadMultiEnvelope));
+		assert_eq!(
+			decode_multi_envelope_payload_v1(&frame.payload[..frame.payload.len() - 1]),
+			Err(DecodeError::UnexpectedEof)
+		);
+		// One recipient "a" claiming a u32::MAX-byte envelope.
+		assert_eq!(
+			decode_multi_envelope_payload_v1(&[1, 1, b'a', 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0]),
+			Err(DecodeError::UnexpectedEof)
+		);
+	}
+
+	#[test]
+	fn hello_negotiates_cipher_suite() {
+		let hello = Hello {
+			capabilities: 0x81,
+			max_chunk_size: None,
+		};
+		let (frame, _used) = decode_v1(&encode_hello_v1(&hello), 1024).unwrap();
+		assert_eq!(decode_hello_payload_v1(&frame.payload).unwrap(), hello);
+		let mut payload = frame.payload.clone();
+		payload.push(0xFF);
+		assert_eq!(decode_hello_payload_v1(&payload).unwrap().capabilities, 0x81);
+
+		assert_eq!(CipherSuite::negotiate(HELLO_CAP_AES_256_GCM, 0x81), CipherSuite::Aes256Gcm);
+		assert_eq!(CipherSuite::negotiate(HELLO_CAP_AES_256_GCM, 0x80), CipherSuite::XChaCha20Poly1305);
+		assert_eq!(CipherSuite::negotiate(0, 0x81), CipherSuite::XChaCha20Poly1305);
+	}
+
+	#[test]
+	fn hello_features_intersect() {
+		let local = Hello {
+			capabilities: HELLO_CAP_ANIMATED_QR | HELLO_CAP_FOUNTAIN_CODES,
+			max_chunk_size: Some(64 * 1024),
+		};
+		let (frame, _used) = decode_v1(&encode_hello_v1(&local), 1024).unwrap();
+		assert_eq!(decode_hello_payload_v1(&frame.payload).unwrap(), local);
+
+		// An older peer: capabilities only
+		let remote = Hello {
+			capabilities: HELLO_CAP_ANIMATED_QR | HELLO_CAP_AES_256_GCM,
+			max_chunk_size: None,
+		};
+		let common = local.intersect(&remote);
+		assert_eq!(common, remote.intersect(&local));
+		assert!(common.supports(HELLO_CAP_ANIMATED_QR));
+		assert!(!common.supports(HELLO_CAP_FOUNTAIN_CODES));
+		assert!(!common.supports(HELLO_CAP_ANIMATED_QR | HELLO_CAP_FOUNTAIN_CODES));
+		assert_eq!(common.max_chunk_size, Some(64 * 1024));
+
+		let smaller = Hello {
+			max_chunk_size: Some(16 * 1024),
+			..remote
+		};
+		assert_eq!(local.intersect(&smaller).max_chunk_size, Some(16 * 1024));
+		assert_eq!(Hello::default().intersect(&Hello::default()).max_chunk_size, None);
+	}
+
+	#[test]
+	fn hello_extensions() {
+		let hello = Hello {
+			capabilities: HELLO_CAP_AES_256_GCM,
+			max_chunk_size: Some(300),
+		};
+		let (frame, _used) = decode_v1(&encode_hello_v1(&hello), 1024).unwrap();
+		assert_eq!(frame.payload, [0x01, HELLO_EXT_MAX_CHUNK_SIZE, 2, 0xAC, 0x02]);
+
+		// An unknown extension before it and a cut-off one after are skipped
+		let mut payload = vec![0x01, 0x7F, 3, 1, 2, 3];
+		payload.extend_from_slice(&frame.payload[1..]);
+		payload.extend_from_slice(&[0x02, 9, 1]);
+		assert_eq!(decode_hello_payload_v1(&payload).unwrap(), hello);
+
+		// A known extension must hold exactly one non-zero varint
+		let zero = [0x01, HELLO_EXT_MAX_CHUNK_SIZE, 1, 0x00];
+		let padded = [0x01, HELLO_EXT_MAX_CHUNK_SIZE, 2, 0x05, 0x00];
+		let cut = [0x01, HELLO_EXT_MAX_CHUNK_SIZE, 1, 0x80];
+		for bad in [&zero[..], &padded, &cut] {
+			assert_eq!(decode_hello_payload_v1(bad), Err(DecodeError::BadHello));
+		}
+	}
+
 	#[test]
 	fn file_chunk_roundtrip() {
 		let bytes = encode_file_chunk_v1("id-2", 42, b"chunkdata");
@@ -401,7 +2095,103 @@ mod tests {
 		let decoded = decode_file_chunk_payload_v1(&frame.payload).unwrap();
 		assert_eq!(decoded.id, "id-2");
 		assert_eq!(decoded.chunk_index, 42);
+		assert!(decoded.proof.is_empty());
 		assert_eq!(decoded.data, b"chunkdata".to_vec());
+		assert_eq!(decode_file_chunk_frame_v1(&frame).unwrap(), decoded);
+	}
+
+	#[test]
+	fn file_chunk_into_matches_vec_encoder() {
+		let data = vec![9u8; 16 * 1024];
+		let mut pool = vec![0u8; 32 * 1024];
+		for (id, index, len) in [("id-2", 42, 9), ("", 0, 0), ("transfer-x", 300_000, data.len())] {
+			let expected = encode_file_chunk_v1(id, index, &data[..len]);
+			assert_eq!(file_chunk_v1_len(id, index, len), expected.len());
+			let n = encode_file_chunk_v1_into(id, index, &data[..len], &mut pool);
+			assert_eq!(&pool[..n], expected.as_slice());
+		}
+
+		let mut header = [0u8; MAX_HEADER_LEN_V1];
+		let n = encode_header_v1_into(FrameType::Ping, 0xAA, u32::MAX, &mut header);
+		assert_eq!(n, MAX_HEADER_LEN_V1);
+	}
+
+	#[test]
+	fn decode_ref_points_into_input() {
+		let mut stream = encode_chat_text_v1("hola");
+		stream.extend(encode_file_chunk_v1("id", 3, &[7u8; 300]));
+
+		let (first, used) = decode_v1_ref(&stream, 1024).unwrap();
+		assert_eq!(first.frame_type, FrameType::ChatText);
+		assert_eq!(first.payload(&stream), b"hola");
+		let (second, rest) = decode_v1_ref(&stream[used..], 1024).unwrap();
+		assert_eq!(used + rest, stream.len());
+		assert_eq!(second.to_frame(&stream[used..]), decode_v1(&stream[used..], 1024).unwrap().0);
+
+		assert_eq!(decode_v1_ref(&stream[..used - 1], 1024).unwrap_err(), DecodeError::UnexpectedEof);
+	}
+
+	#[test]
+	fn file_chunk_compressed_roundtrip() {
+		use crate::merkle::{verify_leaf, MerkleTree};
+
+		let text = "holi ".repeat(400).into_bytes();
+		let bytes = encode_file_chunk_compressed_v1("id-z", 0, None, &text).unwrap();
+		assert!(bytes.len() < text.len() / 4);
+		let (frame, _) = decode_v1(&bytes, 1024 * 1024).unwrap();
+		assert_eq!(frame.flags, FILE_CHUNK_FLAG_COMPRESSED);
+		let chunk = decode_file_chunk_frame_v1(&frame).unwrap();
+		assert_eq!((chunk.id.as_str(), chunk.chunk_index), ("id-z", 0));
+		assert_eq!(chunk.data, text);
+
+		// The proof covers the uncompressed data
+		let tree = MerkleTree::from_data(&text, 1024);
+		let proof = tree.proof(1).unwrap();
+		let bytes = encode_file_chunk_compressed_v1("id-z", 1, Some(&proof), &text[1024..]).unwrap();
+		let (frame, _) = decode_v1(&bytes, 1024 * 1024).unwrap();
+		assert_eq!(frame.flags, FILE_CHUNK_FLAG_PROOF | FILE_CHUNK_FLAG_COMPRESSED);
+		let chunk = decode_file_chunk_frame_v1(&frame).unwrap();
+		assert!(verify_leaf(&tree.root(), tree.leaf_count() as u64, 1, &chunk.data, &chunk.proof));
+
+		// Incompressible data goes out plain
+		let bytes = encode_file_chunk_compressed_v1("id-z", 2, None, &[1, 2, 3]).unwrap();
+		assert_eq!(bytes, encode_file_chunk_v1("id-z", 2, &[1, 2, 3]));
+
+		let mut bad = decode_v1(&encode_file_chunk_v1("id-z", 3, b"not deflate"), 1024).unwrap().0;
+		bad.flags = FILE_CHUNK_FLAG_COMPRESSED;
+		assert_eq!(decode_file_chunk_frame_v1(&bad), Err(DecodeError::BadCompressedChunk));
+	}
+
+	#[test]
+	fn file_chunk_proof_verifies_against_offer_root() {
+		use crate::merkle::{verify_leaf, MerkleTree};
+
+		let data: Vec<u8> = (0..5000u32).map(|i| i as u8).collect();
+		let tree = MerkleTree::from_data(&data, 1024);
+		let bytes = encode_file_chunk_with_proof_v1("id-4", 3, &tree.proof(3).unwrap(), &data[3072..4096]).unwrap();
+		let (frame, _used) = decode_v1(&bytes, 1024 * 1024).unwrap();
+		assert_eq!(frame.flags, FILE_CHUNK_FLAG_PROOF);
+		let chunk = decode_file_chunk_frame_v1(&frame).unwrap();
+		assert_eq!(chunk.chunk_index, 3);
+		assert_eq!(chunk.data, &data[3072..4096]);
+		assert!(verify_leaf(
+			&tree.root(),
+			tree.leaf_count() as u64,
+			u64::from(chunk.chunk_index),
+			&chunk.data,
+			&chunk.proof
+		));
+
+		let mut bad = frame.clone();
+		bad.payload[6] = MAX_PROOF_LEN as u8 + 1;
+		assert_eq!(decode_file_chunk_frame_v1(&bad), Err(DecodeError::BadMerkleProof));
+
+		let long = vec![[0; MERKLE_HASH_LEN]; MAX_PROOF_LEN + 1];
+		assert!(encode_file_chunk_compressed_v1("id-4", 3, Some(&long), &data[..1]).is_err());
+		assert_eq!(
+			encode_file_chunk_with_proof_v1("id-4", 3, &long, &data[..1]),
+			Err(EncodeError::ProofTooLong { len: MAX_PROOF_LEN + 1, max: MAX_PROOF_LEN })
+		);
 	}
 
 	#[test]
@@ -412,4 +2202,97 @@ mod tests {
 		let id = decode_file_end_payload_v1(&frame.payload).unwrap();
 		assert_eq!(id, "id-3");
 	}
+
+	#[test]
+	fn acl_update_roundtrip() {
+		let update = AclUpdate {
+			project_id: "proj-1".to_string(),
+			user_id: "u_abc".to_string(),
+			action: AclAction::Grant {
+				role: 1,
+				expires_at: Some(1_700_000_000_000),
+			},
+			timestamp: 1_699_999_999_999,
+			author: [3u8; ACL_AUTHOR_KEY_LEN],
+			signature: [9u8; ACL_SIGNATURE_LEN],
+		};
+		let bytes = encode_acl_update_v1(&update);
+		let (frame, used) = decode_v1(&bytes, 1024 * 1024).unwrap();
+		assert_eq!(used, bytes.len());
+		assert_eq!(frame.frame_type, FrameType::AclUpdate);
+		assert_eq!(decode_acl_update_payload_v1(&frame.payload).unwrap(), update);
+
+		let revoke = AclUpdate {
+			action: AclAction::Revoke,
+			..update
+		};
+		let (frame, _used) = decode_v1(&encode_acl_update_v1(&revoke), 1024 * 1024).unwrap();
+		assert_eq!(decode_acl_update_payload_v1(&frame.payload).unwrap(), revoke);
+	}
+
+	#[test]
+	fn acl_update_rejects_bad_fields() {
+		let update = AclUpdate {
+			project_id: "p".to_string(),
+			user_id: "u".to_string(),
+			action: AclAction::Grant { role: 7, expires_at: None },
+			timestamp: 1,
+			author: [0u8; ACL_AUTHOR_KEY_LEN],
+			signature: [0u8; ACL_SIGNATURE_LEN],
+		};
+		let (frame, _used) = decode_v1(&encode_acl_update_v1(&update), 1024).unwrap();
+		assert_eq!(decode_acl_update_payload_v1(&frame.payload), Err(DecodeError::BadAclUpdate));
+
+		let ok = AclUpdate {
+			action: AclAction::Revoke,
+			..update
+		};
+		let (frame, _used) = decode_v1(&encode_acl_update_v1(&ok), 1024).unwrap();
+		let truncated = &frame.payload[..frame.payload.len() - 1];
+		assert_eq!(decode_acl_update_payload_v1(truncated), Err(DecodeError::BadAclUpdate));
+	}
+
+	#[test]
+	fn acl_signing_bytes_exclude_signature() {
+		let a = AclUpdate {
+			project_id: "p".to_string(),
+			user_id: "u".to_string(),
+			action: AclAction::Revoke,
+			timestamp: 5,
+			author: [1u8; ACL_AUTHOR_KEY_LEN],
+			signature: [0u8; ACL_SIGNATURE_LEN],
+		};
+		let b = AclUpdate {
+			signature: [0xFF; ACL_SIGNATURE_LEN],
+			..a.clone()
+		};
+		assert_eq!(acl_update_signing_bytes(&a), acl_update_signing_bytes(&b));
+		let c = AclUpdate { timestamp: 6, ..a.clone() };
+		assert_ne!(acl_update_signing_bytes(&a), acl_update_signing_bytes(&c));
+	}
+
+	#[test]
+	fn project_invite_roundtrip() {
+		let invite = ProjectInvite {
+			project_id: "proj-1".to_string(),
+			role: 1,
+			timestamp: 1_700_000_000_000,
+			inviter: [1u8; ACL_AUTHOR_KEY_LEN],
+			invitee: [2u8; ACL_AUTHOR_KEY_LEN],
+			sealed_key: [3u8; PROJECT_INVITE_SEALED_KEY_LEN],
+			signature: [4u8; ACL_SIGNATURE_LEN],
+		};
+		let bytes = encode_project_invite_v1(&invite);
+		let (frame, used) = decode_v1(&bytes, 1024).unwrap();
+		assert_eq!(used, bytes.len());
+		assert_eq!(frame.frame_type, FrameType::ProjectInvite);
+		assert_eq!(decode_project_invite_payload_v1(&frame.payload).unwrap(), invite);
+
+		let truncated = &frame.payload[..frame.payload.len() - 1];
+		assert_eq!(decode_project_invite_payload_v1(truncated), Err(DecodeError::BadProjectInvite));
+		let resigned = ProjectInvite { signature: [0u8; ACL_SIGNATURE_LEN], ..invite.clone() };
+		assert_eq!(project_invite_signing_bytes(&invite), project_invite_signing_bytes(&resigned));
+		let other_invitee = ProjectInvite { invitee: [5u8; ACL_AUTHOR_KEY_LEN], ..invite.clone() };
+		assert_ne!(project_invite_signing_bytes(&invite), project_invite_signing_bytes(&other_invitee));
+	}
 }
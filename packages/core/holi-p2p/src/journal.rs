@@ -0,0 +1,468 @@
+//! Frame journal for debugging sessions.
+//!
+//! `JournalTransport` wraps any `Transport` and records the header of every
+//! message it sends or receives (time, direction, frame type, flags, length;
+//! never the payload) into a bounded `FrameJournal`. `to_json()` exports it
+//! for bug reports and `parse_journal_json` reads an export back.
+//!
+//! `ReplayTransport` plays a captured journal back to session code in tests:
+//! inbound entries are delivered as frames with the recorded type, flags and
+//! length (zeroed payloads), each once the outbound entries logged before it
+//! have been sent, and sends that don't match the journal are collected as
+//! divergences.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use holi_clock::Clock;
+
+use crate::frame::{encode_v1, Frame, FrameType, MAGIC, VERSION_V1};
+use crate::schema::frame_schema;
+use crate::transport::{Transport, TransportError};
+use crate::varint::decode_u32_varint;
+
+/// Entries kept by default before the oldest are dropped.
+pub const DEFAULT_JOURNAL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+	Inbound,
+	Outbound,
+}
+
+impl Direction {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Inbound => "in",
+			Self::Outbound => "out",
+		}
+	}
+}
+
+/// Header of one message that crossed the transport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalEntry {
+	pub at_ms: u64,
+	pub direction: Direction,
+	/// `None` for messages that aren't v1 frames (heartbeats, garbage).
+	pub frame_type: Option<FrameType>,
+	pub flags: u8,
+	/// Whole message length in bytes.
+	pub len: usize,
+}
+
+/// Frame type and flags of a v1 frame, read from its header only.
+fn peek_header(bytes: &[u8]) -> Option<(FrameType, u8)> {
+	if bytes.len() < 5 || bytes[0..2] != MAGIC || bytes[2] != VERSION_V1 {
+		return None;
+	}
+	let frame_type = FrameType::from_u8(bytes[3])?;
+	decode_u32_varint(&bytes[5..]).ok()?;
+	Some((frame_type, bytes[4]))
+}
+
+/// Ring buffer of `JournalEntry`, oldest first.
+#[derive(Debug, Clone)]
+pub struct FrameJournal {
+	entries: VecDeque<JournalEntry>,
+	capacity: usize,
+	dropped: u64,
+}
+
+impl Default for FrameJournal {
+	fn default() -> Self {
+		Self::new(DEFAULT_JOURNAL_CAPACITY)
+	}
+}
+
+impl FrameJournal {
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			entries: VecDeque::new(),
+			capacity: capacity.max(1),
+			dropped: 0,
+		}
+	}
+
+	/// Record the header of `bytes`, dropping the oldest entry when full.
+	pub fn record(&mut self, at_ms: u64, direction: Direction, bytes: &[u8]) {
+		let header = peek_header(bytes);
+		self.push(JournalEntry {
+			at_ms,
+			direction,
+			frame_type: header.map(|(t, _)| t),
+			flags: header.map_or(0, |(_, f)| f),
+			len: bytes.len(),
+		});
+	}
+
+	pub fn push(&mut self, entry: JournalEntry) {
+		if self.entries.len() == self.capacity {
+			self.entries.pop_front();
+			self.dropped += 1;
+		}
+		self.entries.push_back(entry);
+	}
+
+	pub fn entries(&self) -> impl Iterator<Item = &JournalEntry> + '_ {
+		self.entries.iter()
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Entries pushed out by newer ones since creation.
+	pub fn dropped(&self) -> u64 {
+		self.dropped
+	}
+
+	/// Remove every entry and reset `dropped`.
+	pub fn clear(&mut self) {
+		self.entries.clear();
+		self.dropped = 0;
+	}
+
+	/// `{"capacity","dropped","entries":[{"atMs","dir","type","name","flags","len"}]}`;
+	/// `type` and `name` are null for non-frame messages.
+	pub fn to_json(&self) -> String {
+		let mut out = format!(
+			"{{\"capacity\":{},\"dropped\":{},\"entries\":[",
+			self.capacity, self.dropped
+		);
+		for (i, e) in self.entries.iter().enumerate() {
+			if i > 0 {
+				out.push(',');
+			}
+			let (ty, name) = match e.frame_type {
+				Some(t) => (
+					(t as u8).to_string(),
+					frame_schema(t).map_or("null".to_string(), |s| format!("\"{}\"", s.name)),
+				),
+				None => ("null".to_string(), "null".to_string()),
+			};
+			out.push_str(&format!(
+				"{{\"atMs\":{},\"dir\":\"{}\",\"type\":{},\"name\":{},\"flags\":{},\"len\":{}}}",
+				e.at_ms,
+				e.direction.as_str(),
+				ty,
+				name,
+				e.flags,
+				e.len
+			));
+		}
+		out.push_str("]}");
+		out
+	}
+}
+
+/// Longest message `parse_journal_json` accepts and `ReplayTransport` synthesizes.
+pub const MAX_REPLAY_ENTRY_LEN: usize = 16 << 20;
+
+fn parse_entry(object: &serde_json::Value) -> Option<JournalEntry> {
+	let direction = match object.get("dir")?.as_str()? {
+		"in" => Direction::Inbound,
+		"out" => Direction::Outbound,
+		_ => return None,
+	};
+	let frame_type = match object.get("type")? {
+		serde_json::Value::Null => None,
+		t => Some(FrameType::from_u8(u8::try_from(t.as_u64()?).ok()?)?),
+	};
+	let len = usize::try_from(object.get("len")?.as_u64()?).ok()?;
+	if len > MAX_REPLAY_ENTRY_LEN {
+		return None;
+	}
+	Some(JournalEntry {
+		at_ms: object.get("atMs")?.as_u64()?,
+		direction,
+		frame_type,
+		flags: u8::try_from(object.get("flags")?.as_u64()?).ok()?,
+		len,
+	})
+}
+
+/// Entries of a `FrameJournal::to_json` export, or `None` if `json` isn't one
+/// or has an entry longer than `MAX_REPLAY_ENTRY_LEN`.
+pub fn parse_journal_json(json: &str) -> Option<Vec<JournalEntry>> {
+	let export: serde_json::Value = serde_json::from_str(json).ok()?;
+	export.get("entries")?.as_array()?.iter().map(parse_entry).collect()
+}
+
+/// `Transport` that journals every message passing through `inner`.
+#[derive(Debug)]
+pub struct JournalTransport<T> {
+	inner: T,
+	journal: Arc<Mutex<FrameJournal>>,
+	clock: Arc<dyn Clock>,
+}
+
+impl<T: Transport> JournalTransport<T> {
+	pub fn new(inner: T, capacity: usize, clock: Arc<dyn Clock>) -> Self {
+		Self {
+			inner,
+			journal: Arc::new(Mutex::new(FrameJournal::new(capacity))),
+			clock,
+		}
+	}
+
+	/// Shared handle to the journal, e.g. to export it from a bug-report button.
+	pub fn journal(&self) -> Arc<Mutex<FrameJournal>> {
+		self.journal.clone()
+	}
+
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+
+	fn record(&self, direction: Direction, bytes: &[u8]) {
+		let now = self.clock.now_ms();
+		self.journal.lock().unwrap().record(now, direction, bytes);
+	}
+}
+
+impl<T: Transport> Transport for JournalTransport<T> {
+	fn send_bytes(&mut self, bytes: &[u8]) -> Result<(), TransportError> {
+		self.inner.send_bytes(bytes)?;
+		self.record(Direction::Outbound, bytes);
+		Ok(())
+	}
+
+	fn poll_recv(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+		let received = self.inner.poll_recv()?;
+		if let Some(bytes) = &received {
+			self.record(Direction::Inbound, bytes);
+		}
+		Ok(received)
+	}
+
+	fn close(&mut self) {
+		self.inner.close();
+	}
+}
+
+/// A send that didn't match the journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+	/// Index of the journal entry that was expected, if any was left.
+	pub index: Option<usize>,
+	pub expected: Option<JournalEntry>,
+	/// What was sent instead (`at_ms` is always 0).
+	pub actual: JournalEntry,
+}
+
+/// `Transport` that replays a captured journal; see the module docs.
+#[derive(Debug)]
+pub struct ReplayTransport {
+	entries: Vec<JournalEntry>,
+	next: usize,
+	divergences: Vec<Divergence>,
+}
+
+impl ReplayTransport {
+	pub fn new(entries: Vec<JournalEntry>) -> Self {
+		Self {
+			entries,
+			next: 0,
+			divergences: Vec::new(),
+		}
+	}
+
+	/// Whether every journal entry was delivered or matched.
+	pub fn is_finished(&self) -> bool {
+		self.next == self.entries.len()
+	}
+
+	pub fn divergences(&self) -> &[Divergence] {
+		&self.divergences
+	}
+
+	/// Bytes with the entry's header and length, at most
+	/// `MAX_REPLAY_ENTRY_LEN`; non-frames are all zeros.
+	fn synthesize(entry: &JournalEntry) -> Vec<u8> {
+		let len = entry.len.min(MAX_REPLAY_ENTRY_LEN);
+		let Some(frame_type) = entry.frame_type else {
+			return vec![0; len];
+		};
+		// The length varint grows with the payload; shrink until the whole frame fits.
+		let mut payload_len = len.saturating_sub(6);
+		loop {
+			let mut out = Vec::with_capacity(len);
+			encode_v1(
+				&Frame {
+					frame_type,
+					flags: entry.flags,
+					payload: vec![0; payload_len],
+				},
+				&mut out,
+			);
+			if out.len() <= len || payload_len == 0 {
+				return out;
+			}
+			payload_len -= 1;
+		}
+	}
+}
+
+impl Transport for ReplayTransport {
+	fn send_bytes(&mut self, bytes: &[u8]) -> Result<(), TransportError> {
+		let header = peek_header(bytes);
+		let actual = JournalEntry {
+			at_ms: 0,
+			direction: Direction::Outbound,
+			frame_type: header.map(|(t, _)| t),
+			flags: header.map_or(0, |(_, f)| f),
+			len: bytes.len(),
+		};
+		let expected = self.entries.get(self.next).copied();
+		let matches = expected.is_some_and(|e| {
+			e.direction == Direction::Outbound
+				&& e.frame_type == actual.frame_type
+				&& e.flags == actual.flags
+		});
+		if matches {
+			self.next += 1;
+		} else {
+			self.divergences.push(Divergence {
+				index: expected.map(|_| self.next),
+				expected,
+				actual,
+			});
+		}
+		Ok(())
+	}
+
+	fn poll_recv(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+		match self.entries.get(self.next) {
+			Some(entry) if entry.direction == Direction::Inbound => {
+				self.next += 1;
+				Ok(Some(Self::synthesize(entry)))
+			}
+			// Waiting for the session to send what it sent in the capture.
+			Some(_) => Ok(None),
+			None => Err(TransportError::Closed),
+		}
+	}
+
+	fn close(&mut self) {
+		self.next = self.entries.len();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::frame::{decode_v1, encode_chat_text_v1, encode_file_accept_v1, encode_file_offer_v1, FileOffer};
+	use crate::keepalive::encode_heartbeat_ping;
+	use crate::transport::MemoryTransport;
+	use holi_clock::MockClock;
+
+	/// Answers every FileOffer with a FileAccept, like the UI would.
+	fn auto_accept(transport: &mut dyn Transport) {
+		while let Ok(Some(bytes)) = transport.poll_recv() {
+			if let Ok((frame, _)) = decode_v1(&bytes, 1 << 20) {
+				if frame.frame_type == FrameType::FileOffer {
+					transport.send_bytes(&encode_file_accept_v1("t1")).unwrap();
+				}
+			}
+		}
+	}
+
+	fn offer() -> Vec<u8> {
+		encode_file_offer_v1(&FileOffer {
+			id: "t1".into(),
+			filename: "a.txt".into(),
+			mime_type: "text/plain".into(),
+			size: 3,
+			merkle: None,
+			thumbnail: None,
+		})
+	}
+
+	#[test]
+	fn records_headers_and_exports_json() {
+		let clock = Arc::new(MockClock::new(1_000));
+		let (a, mut b) = MemoryTransport::pair();
+		let mut a = JournalTransport::new(a, 3, clock.clone());
+		let journal = a.journal();
+
+		a.send_bytes(&encode_chat_text_v1("hola")).unwrap();
+		clock.advance(5);
+		b.send_bytes(&encode_heartbeat_ping(7)).unwrap();
+		b.send_bytes(&offer()).unwrap();
+		auto_accept(&mut a);
+
+		let journal = journal.lock().unwrap();
+		assert_eq!(journal.len(), 3);
+		assert_eq!(journal.dropped(), 1);
+		let kinds: Vec<_> = journal.entries().map(|e| (e.direction, e.frame_type)).collect();
+		assert_eq!(
+			kinds,
+			[
+				(Direction::Inbound, None),
+				(Direction::Inbound, Some(FrameType::FileOffer)),
+				(Direction::Outbound, Some(FrameType::FileAccept)),
+			]
+		);
+
+		let json = journal.to_json();
+		assert!(json.starts_with("{\"capacity\":3,\"dropped\":1,\"entries\":[{\"atMs\":1005,\"dir\":\"in\",\"type\":null"));
+		assert!(!json.contains("hola"));
+		let parsed = parse_journal_json(&json).unwrap();
+		assert_eq!(parsed, journal.entries().copied().collect::<Vec<_>>());
+		assert_eq!(parse_journal_json("{\"capacity\":1,\"dropped\":0,\"entries\":[]}"), Some(Vec::new()));
+		assert_eq!(parse_journal_json("{}"), None);
+		// A `},{` inside a value doesn't split the entry.
+		assert_eq!(
+			parse_journal_json("{\"entries\":[{\"atMs\":1,\"dir\":\"in\",\"type\":null,\"name\":\"a},{b\",\"flags\":0,\"len\":2}]}")
+				.map(|e| e.len()),
+			Some(1)
+		);
+		let huge = format!(
+			"{{\"entries\":[{{\"atMs\":0,\"dir\":\"in\",\"type\":null,\"flags\":0,\"len\":{}}}]}}",
+			MAX_REPLAY_ENTRY_LEN + 1
+		);
+		assert_eq!(parse_journal_json(&huge), None);
+		drop(journal);
+		let journal = a.journal();
+		let mut journal = journal.lock().unwrap();
+		journal.clear();
+		assert!(journal.is_empty());
+		assert_eq!(journal.dropped(), 0);
+	}
+
+	#[test]
+	fn replays_a_capture() {
+		let clock = Arc::new(MockClock::new(0));
+		let (a, mut b) = MemoryTransport::pair();
+		let mut a = JournalTransport::new(a, DEFAULT_JOURNAL_CAPACITY, clock);
+		b.send_bytes(&offer()).unwrap();
+		b.send_bytes(&encode_chat_text_v1("hi")).unwrap();
+		auto_accept(&mut a);
+		let json = a.journal().lock().unwrap().to_json();
+
+		let entries = parse_journal_json(&json).unwrap();
+		let mut replay = ReplayTransport::new(entries.clone());
+		let first = replay.poll_recv().unwrap().unwrap();
+		assert_eq!(first.len(), entries[0].len);
+		assert_eq!(decode_v1(&first, 1 << 20).unwrap().0.frame_type, FrameType::FileOffer);
+		// The chat frame waits until the accept is sent, as in the capture.
+		assert_eq!(replay.poll_recv(), Ok(None));
+		replay.send_bytes(&encode_file_accept_v1("t1")).unwrap();
+		auto_accept(&mut replay);
+		assert!(replay.is_finished());
+		assert!(replay.divergences().is_empty());
+
+		// A session that answers differently is reported.
+		let mut replay = ReplayTransport::new(entries);
+		replay.poll_recv().unwrap();
+		replay.send_bytes(&encode_chat_text_v1("nope")).unwrap();
+		assert_eq!(replay.divergences().len(), 1);
+		assert_eq!(replay.divergences()[0].index, Some(1));
+		assert_eq!(replay.divergences()[0].actual.frame_type, Some(FrameType::ChatText));
+	}
+}
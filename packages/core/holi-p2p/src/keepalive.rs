@@ -0,0 +1,212 @@
+//! Heartbeat frames and NTP-style clock offset estimation.
+//!
+//! Heartbeats are app-level control messages outside the framed protocol:
+//! - ping: `[0x01, t0: u64 BE]`
+//! - pong: `[0x02, t1: u64 BE, t0: u64 BE]`, echoing the ping's timestamp
+//!
+//! Legacy pongs (`[0x02, t1]`, 9 bytes) still count as liveness but carry no
+//! clock sample. Older peers only accept 9-byte heartbeats, so the extended
+//! pong goes only to peers whose Hello sets `HELLO_CAP_CLOCK_SYNC`.
+
+use std::collections::VecDeque;
+
+pub const HEARTBEAT_PING: u8 = 0x01;
+pub const HEARTBEAT_PONG: u8 = 0x02;
+pub const HEARTBEAT_PING_LEN: usize = 9;
+pub const HEARTBEAT_PONG_LEN: usize = 17;
+pub const LEGACY_HEARTBEAT_PONG_LEN: usize = 9;
+
+/// Samples kept by `ClockOffsetEstimator`.
+pub const CLOCK_SAMPLE_WINDOW: usize = 8;
+
+fn read_u64_be(bytes: &[u8]) -> u64 {
+	let mut buf = [0u8; 8];
+	buf.copy_from_slice(&bytes[..8]);
+	u64::from_be_bytes(buf)
+}
+
+/// Whether `bytes` looks like a heartbeat rather than a protocol frame.
+pub fn is_heartbeat(bytes: &[u8]) -> bool {
+	matches!(
+		(bytes.first(), bytes.len()),
+		(Some(&HEARTBEAT_PING), HEARTBEAT_PING_LEN)
+			| (Some(&HEARTBEAT_PONG), HEARTBEAT_PONG_LEN)
+			| (Some(&HEARTBEAT_PONG), LEGACY_HEARTBEAT_PONG_LEN)
+	)
+}
+
+pub fn encode_heartbeat_ping(now_ms: u64) -> [u8; HEARTBEAT_PING_LEN] {
+	let mut out = [0u8; HEARTBEAT_PING_LEN];
+	out[0] = HEARTBEAT_PING;
+	out[1..].copy_from_slice(&now_ms.to_be_bytes());
+	out
+}
+
+/// Answer `ping`. With `clock_sync` (the peer advertised
+/// `HELLO_CAP_CLOCK_SYNC`) the pong echoes the ping's timestamp; otherwise
+/// it is a legacy pong. Returns `None` if `ping` isn't one.
+pub fn encode_heartbeat_pong(ping: &[u8], now_ms: u64, clock_sync: bool) -> Option<Vec<u8>> {
+	if ping.len() != HEARTBEAT_PING_LEN || ping[0] != HEARTBEAT_PING {
+		return None;
+	}
+	let mut out = Vec::with_capacity(HEARTBEAT_PONG_LEN);
+	out.push(HEARTBEAT_PONG);
+	out.extend_from_slice(&now_ms.to_be_bytes());
+	if clock_sync {
+		out.extend_from_slice(&ping[1..]);
+	}
+	Some(out)
+}
+
+/// One ping/pong round trip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+	/// Peer clock minus local clock, in ms.
+	pub offset_ms: i64,
+	pub rtt_ms: u64,
+}
+
+impl ClockSample {
+	/// Build a sample from our ping send time `t0`, the peer's pong time `t1`
+	/// and our pong receive time `t3`. The peer is assumed to answer
+	/// immediately, so its receive and send times are both `t1`.
+	pub fn from_timestamps(t0: u64, t1: u64, t3: u64) -> Option<Self> {
+		if t3 < t0 {
+			return None;
+		}
+		let rtt_ms = t3 - t0;
+		let midpoint = t0 as i128 + (rtt_ms / 2) as i128;
+		Some(ClockSample {
+			offset_ms: (t1 as i128 - midpoint) as i64,
+			rtt_ms,
+		})
+	}
+
+	/// Decode a pong received at `now_ms`. Legacy pongs yield `None`.
+	pub fn from_pong(pong: &[u8], now_ms: u64) -> Option<Self> {
+		if pong.len() != HEARTBEAT_PONG_LEN || pong[0] != HEARTBEAT_PONG {
+			return None;
+		}
+		Self::from_timestamps(read_u64_be(&pong[9..]), read_u64_be(&pong[1..9]), now_ms)
+	}
+}
+
+/// Estimates a peer's clock offset from recent heartbeat round trips.
+///
+/// Like NTP's clock filter, the estimate comes from the sample with the
+/// lowest round-trip time, whose offset error is bounded by `rtt / 2`.
+#[derive(Debug, Clone, Default)]
+pub struct ClockOffsetEstimator {
+	samples: VecDeque<ClockSample>,
+}
+
+impl ClockOffsetEstimator {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn record(&mut self, sample: ClockSample) {
+		if self.samples.len() == CLOCK_SAMPLE_WINDOW {
+			self.samples.pop_front();
+		}
+		self.samples.push_back(sample);
+	}
+
+	/// Record a pong received at `now_ms`. Returns false if it carried no sample.
+	pub fn record_pong(&mut self, pong: &[u8], now_ms: u64) -> bool {
+		match ClockSample::from_pong(pong, now_ms) {
+			Some(sample) => {
+				self.record(sample);
+				true
+			}
+			None => false,
+		}
+	}
+
+	fn best(&self) -> Option<&ClockSample> {
+		self.samples.iter().min_by_key(|s| s.rtt_ms)
+	}
+
+	/// Peer clock minus local clock, in ms.
+	pub fn offset_ms(&self) -> Option<i64> {
+		self.best().map(|s| s.offset_ms)
+	}
+
+	/// Round-trip time of the sample behind `offset_ms`.
+	pub fn rtt_ms(&self) -> Option<u64> {
+		self.best().map(|s| s.rtt_ms)
+	}
+
+	/// Convert a peer timestamp to local time. Unchanged until a sample exists.
+	pub fn to_local_ms(&self, peer_ms: u64) -> u64 {
+		let offset = self.offset_ms().unwrap_or(0);
+		(peer_ms as i128 - offset as i128).max(0) as u64
+	}
+
+	pub fn sample_count(&self) -> usize {
+		self.samples.len()
+	}
+
+	pub fn reset(&mut self) {
+		self.samples.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ping_pong_roundtrip() {
+		let ping = encode_heartbeat_ping(1_000);
+		assert!(is_heartbeat(&ping));
+		// Peer clock is 5 minutes ahead; 40 ms each way.
+		let pong = encode_heartbeat_pong(&ping, 1_040 + 300_000, true).unwrap();
+		assert_eq!(pong.len(), HEARTBEAT_PONG_LEN);
+		assert!(is_heartbeat(&pong));
+
+		let sample = ClockSample::from_pong(&pong, 1_080).unwrap();
+		assert_eq!(sample.rtt_ms, 80);
+		assert_eq!(sample.offset_ms, 300_000);
+	}
+
+	#[test]
+	fn estimator_prefers_lowest_rtt() {
+		let mut est = ClockOffsetEstimator::new();
+		assert_eq!(est.offset_ms(), None);
+		assert_eq!(est.to_local_ms(500), 500);
+
+		est.record(ClockSample::from_timestamps(0, 2_000, 400).unwrap());
+		est.record(ClockSample::from_timestamps(1_000, 1_900, 1_020).unwrap());
+		est.record(ClockSample::from_timestamps(2_000, 3_500, 2_600).unwrap());
+		assert_eq!(est.rtt_ms(), Some(20));
+		assert_eq!(est.offset_ms(), Some(890));
+		assert_eq!(est.to_local_ms(10_890), 10_000);
+
+		for i in 0..CLOCK_SAMPLE_WINDOW as u64 {
+			let t0 = 10_000 + i;
+			est.record(ClockSample::from_timestamps(t0, t0 + 50 - 100, t0 + 100).unwrap());
+		}
+		assert_eq!(est.sample_count(), CLOCK_SAMPLE_WINDOW);
+		assert_eq!(est.offset_ms(), Some(-100));
+	}
+
+	#[test]
+	fn rejects_legacy_and_malformed() {
+		let mut legacy = [0u8; LEGACY_HEARTBEAT_PONG_LEN];
+		legacy[0] = HEARTBEAT_PONG;
+		assert!(is_heartbeat(&legacy));
+
+		let mut est = ClockOffsetEstimator::new();
+		assert!(!est.record_pong(&legacy, 10));
+		assert!(encode_heartbeat_pong(&legacy, 10, true).is_none());
+
+		// Peers without HELLO_CAP_CLOCK_SYNC get the 9-byte pong.
+		let pong = encode_heartbeat_pong(&encode_heartbeat_ping(5), 10, false).unwrap();
+		assert_eq!(pong.len(), LEGACY_HEARTBEAT_PONG_LEN);
+		assert!(!est.record_pong(&pong, 12));
+		assert!(!is_heartbeat(b"HO\x01\x10\x00"));
+		// Pong arriving "before" the ping was sent.
+		assert!(ClockSample::from_timestamps(100, 0, 50).is_none());
+	}
+}
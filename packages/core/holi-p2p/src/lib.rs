@@ -1,5 +1,23 @@
 mod varint;
 
+pub mod compressibility;
+pub mod ephemeral;
 pub mod frame;
+pub mod journal;
+pub mod keepalive;
+pub mod merkle;
+pub mod metrics;
+pub mod offload;
+pub mod outbound;
+pub mod padding;
+pub mod rekey;
+pub mod schema;
+pub mod session;
+pub mod sniff;
+pub mod transfer_policy;
+pub mod transport;
 
-pub use varint::{decode_u32_varint, decode_u64_varint, encode_u32_varint, encode_u64_varint};
+pub use varint::{
+	decode_u32_varint, decode_u64_varint, encode_u32_varint, encode_u32_varint_into,
+	encode_u64_varint, encode_u64_varint_into, varint_len, MAX_U32_VARINT_LEN, MAX_U64_VARINT_LEN,
+};
@@ -0,0 +1,256 @@
+//! Merkle-tree file hashing for per-chunk verification.
+//!
+//! A file is split into leaves of `leaf_size` bytes (the last one may be
+//! shorter; an empty file is one empty leaf). Hashes are BLAKE3 with domain
+//! separation:
+//! - leaf: `blake3(0x00 || data)`
+//! - node: `blake3(0x01 || left || right)`
+//!
+//! A level with an odd number of nodes promotes its last node unchanged.
+//! With `leaf_size` equal to the transfer chunk size, chunk `i` is leaf `i`
+//! and can be checked against the root from the FileOffer as soon as it
+//! arrives, using the proof carried in its FileChunk frame.
+
+pub const MERKLE_HASH_LEN: usize = 32;
+/// Most sibling hashes a proof can need (trees of up to 2^32 leaves).
+pub const MAX_PROOF_LEN: usize = 32;
+
+pub type MerkleHash = [u8; MERKLE_HASH_LEN];
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub fn leaf_hash(data: &[u8]) -> MerkleHash {
+	let mut hasher = blake3::Hasher::new();
+	hasher.update(&[LEAF_PREFIX]);
+	hasher.update(data);
+	*hasher.finalize().as_bytes()
+}
+
+fn node_hash(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+	let mut hasher = blake3::Hasher::new();
+	hasher.update(&[NODE_PREFIX]);
+	hasher.update(left);
+	hasher.update(right);
+	*hasher.finalize().as_bytes()
+}
+
+/// Number of leaves for a file of `size` bytes.
+pub fn leaf_count(size: u64, leaf_size: u32) -> u64 {
+	size.div_ceil(u64::from(leaf_size.max(1))).max(1)
+}
+
+/// Incremental hasher: feed file bytes in any slicing, then `finish`.
+/// Only leaf hashes are kept, so multi-GB files need 32 bytes per leaf.
+#[derive(Debug, Clone)]
+pub struct MerkleBuilder {
+	leaf_size: u32,
+	pending: Vec<u8>,
+	leaves: Vec<MerkleHash>,
+	len: u64,
+}
+
+impl MerkleBuilder {
+	pub fn new(leaf_size: u32) -> Self {
+		let leaf_size = leaf_size.max(1);
+		Self {
+			leaf_size,
+			pending: Vec::with_capacity(leaf_size as usize),
+			leaves: Vec::new(),
+			len: 0,
+		}
+	}
+
+	pub fn update(&mut self, mut data: &[u8]) {
+		self.len += data.len() as u64;
+		while !data.is_empty() {
+			let take = (self.leaf_size as usize - self.pending.len()).min(data.len());
+			self.pending.extend_from_slice(&data[..take]);
+			data = &data[take..];
+			if self.pending.len() == self.leaf_size as usize {
+				self.leaves.push(leaf_hash(&self.pending));
+				self.pending.clear();
+			}
+		}
+	}
+
+	pub fn finish(mut self) -> MerkleTree {
+		if !self.pending.is_empty() || self.leaves.is_empty() {
+			self.leaves.push(leaf_hash(&self.pending));
+		}
+		MerkleTree::from_leaves(self.leaf_size, self.len, self.leaves)
+	}
+}
+
+/// Every level of a file's tree, leaves first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+	leaf_size: u32,
+	len: u64,
+	levels: Vec<Vec<MerkleHash>>,
+}
+
+impl MerkleTree {
+	pub fn from_data(data: &[u8], leaf_size: u32) -> Self {
+		let mut builder = MerkleBuilder::new(leaf_size);
+		builder.update(data);
+		builder.finish()
+	}
+
+	fn from_leaves(leaf_size: u32, len: u64, leaves: Vec<MerkleHash>) -> Self {
+		let mut levels = vec![leaves];
+		while levels[levels.len() - 1].len() > 1 {
+			let next = levels[levels.len() - 1]
+				.chunks(2)
+				.map(|pair| match pair {
+					[left, right] => node_hash(left, right),
+					[single] => *single,
+					_ => unreachable!(),
+				})
+				.collect();
+			levels.push(next);
+		}
+		Self { leaf_size, len, levels }
+	}
+
+	pub fn root(&self) -> MerkleHash {
+		self.levels[self.levels.len() - 1][0]
+	}
+
+	pub fn leaf_size(&self) -> u32 {
+		self.leaf_size
+	}
+
+	/// File size in bytes.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	pub fn leaf_count(&self) -> usize {
+		self.levels[0].len()
+	}
+
+	/// Sibling hashes from leaf `index` up to the root, skipping levels
+	/// where the node was promoted. `None` if `index` is out of range.
+	pub fn proof(&self, index: usize) -> Option<Vec<MerkleHash>> {
+		if index >= self.leaf_count() {
+			return None;
+		}
+		let mut proof = Vec::new();
+		let mut i = index;
+		for level in &self.levels[..self.levels.len() - 1] {
+			let sibling = i ^ 1;
+			if sibling < level.len() {
+				proof.push(level[sibling]);
+			}
+			i /= 2;
+		}
+		Some(proof)
+	}
+}
+
+/// Check leaf `index` of a `leaf_count`-leaf tree against `root`.
+pub fn verify_leaf(
+	root: &MerkleHash,
+	leaf_count: u64,
+	index: u64,
+	data: &[u8],
+	proof: &[MerkleHash],
+) -> bool {
+	if index >= leaf_count {
+		return false;
+	}
+	let mut hash = leaf_hash(data);
+	let mut siblings = proof.iter();
+	let (mut i, mut width) = (index, leaf_count);
+	while width > 1 {
+		if i % 2 == 1 {
+			let Some(left) = siblings.next() else {
+				return false;
+			};
+			hash = node_hash(left, &hash);
+		} else if i + 1 < width {
+			let Some(right) = siblings.next() else {
+				return false;
+			};
+			hash = node_hash(&hash, right);
+		}
+		i /= 2;
+		width = width.div_ceil(2);
+	}
+	siblings.next().is_none() && hash == *root
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sample(len: usize) -> Vec<u8> {
+		(0..len).map(|i| (i * 31 % 251) as u8).collect()
+	}
+
+	#[test]
+	fn streaming_matches_one_shot() {
+		let data = sample(10_000);
+		let mut builder = MerkleBuilder::new(1024);
+		for piece in data.chunks(333) {
+			builder.update(piece);
+		}
+		let tree = builder.finish();
+		assert_eq!(tree, MerkleTree::from_data(&data, 1024));
+		assert_eq!(tree.leaf_count(), 10);
+		assert_eq!(tree.leaf_count() as u64, leaf_count(data.len() as u64, 1024));
+		assert_eq!(tree.len(), 10_000);
+	}
+
+	#[test]
+	fn every_leaf_verifies() {
+		for len in [0, 1, 1024, 3000, 5 * 1024, 7 * 1024 + 1] {
+			let data = sample(len);
+			let tree = MerkleTree::from_data(&data, 1024);
+			let root = tree.root();
+			let count = tree.leaf_count() as u64;
+			let leaves: Vec<&[u8]> = if data.is_empty() {
+				vec![&[]]
+			} else {
+				data.chunks(1024).collect()
+			};
+			for (i, leaf) in leaves.iter().enumerate() {
+				let proof = tree.proof(i).unwrap();
+				assert!(verify_leaf(&root, count, i as u64, leaf, &proof), "len {len} leaf {i}");
+			}
+			assert!(tree.proof(leaves.len()).is_none());
+		}
+	}
+
+	#[test]
+	fn rejects_corruption() {
+		let data = sample(5 * 1024);
+		let tree = MerkleTree::from_data(&data, 1024);
+		let root = tree.root();
+		let proof = tree.proof(2).unwrap();
+		let mut leaf = data[2048..3072].to_vec();
+		assert!(verify_leaf(&root, 5, 2, &leaf, &proof));
+
+		leaf[10] ^= 1;
+		assert!(!verify_leaf(&root, 5, 2, &leaf, &proof));
+		let leaf = &data[2048..3072];
+		assert!(!verify_leaf(&root, 5, 3, leaf, &proof));
+		assert!(!verify_leaf(&root, 3, 2, leaf, &proof));
+		assert!(!verify_leaf(&root, 5, 2, leaf, &proof[..proof.len() - 1]));
+		assert!(!verify_leaf(&root, 5, 7, leaf, &proof));
+	}
+
+	#[test]
+	fn leaf_and_node_hashes_are_separated() {
+		// A two-leaf root must not equal the leaf hash of the concatenated children
+		let tree = MerkleTree::from_data(b"ab", 1);
+		let mut concat = leaf_hash(b"a").to_vec();
+		concat.extend_from_slice(&leaf_hash(b"b"));
+		assert_ne!(tree.root(), leaf_hash(&concat));
+	}
+}
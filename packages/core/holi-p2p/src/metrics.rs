@@ -0,0 +1,274 @@
+//! Frame counters for diagnostics.
+//!
+//! A `Metrics` counts frames by type, with their full length in bytes, and
+//! failed decodes by error name. Every frame header written by
+//! `encode_header_v1_into` and every frame read by `decode_v1_ref` (and so
+//! `decode_v1`) is counted in `Metrics::global()`: header errors
+//! automatically, payload errors when the caller reports them with
+//! `record_decode_error`. `peek_v1_ref` reads a header without counting it,
+//! so a frame peeked at and then decoded counts once. Counters are relaxed
+//! atomics, cheap enough to leave on; `snapshot()` reads them all and
+//! `reset()` zeroes them.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::frame::{DecodeError, FrameType};
+use crate::schema::frame_schema;
+
+/// Frame type bytes run 0x00-0x7F, so counters are indexed by the byte.
+const TYPE_SLOTS: usize = 0x80;
+
+struct Counters {
+	frames: [AtomicU64; TYPE_SLOTS],
+	bytes: [AtomicU64; TYPE_SLOTS],
+}
+
+impl Counters {
+	const fn new() -> Self {
+		Self {
+			frames: [const { AtomicU64::new(0) }; TYPE_SLOTS],
+			bytes: [const { AtomicU64::new(0) }; TYPE_SLOTS],
+		}
+	}
+
+	fn add(&self, frame_type: FrameType, len: usize) {
+		let slot = frame_type as usize;
+		self.frames[slot].fetch_add(1, Ordering::Relaxed);
+		self.bytes[slot].fetch_add(len as u64, Ordering::Relaxed);
+	}
+
+	fn read(&self) -> Vec<FrameCount> {
+		FrameType::ALL
+			.into_iter()
+			.map(|frame_type| FrameCount {
+				frame_type,
+				frames: self.frames[frame_type as usize].load(Ordering::Relaxed),
+				bytes: self.bytes[frame_type as usize].load(Ordering::Relaxed),
+			})
+			.filter(|c| c.frames > 0)
+			.collect()
+	}
+
+	fn reset(&self) {
+		for counter in self.frames.iter().chain(&self.bytes) {
+			counter.store(0, Ordering::Relaxed);
+		}
+	}
+}
+
+/// Frames encoded and decoded, and decodes that failed.
+pub struct Metrics {
+	encoded: Counters,
+	decoded: Counters,
+	decode_errors: [AtomicU64; DecodeError::NAMES.len()],
+}
+
+static GLOBAL: Metrics = Metrics::new();
+
+impl Metrics {
+	pub const fn new() -> Self {
+		Self {
+			encoded: Counters::new(),
+			decoded: Counters::new(),
+			decode_errors: [const { AtomicU64::new(0) }; DecodeError::NAMES.len()],
+		}
+	}
+
+	/// The counters the frame codec records into.
+	pub fn global() -> &'static Metrics {
+		&GLOBAL
+	}
+
+	/// Count a frame of `len` bytes (header included) as encoded.
+	pub fn record_encoded(&self, frame_type: FrameType, len: usize) {
+		self.encoded.add(frame_type, len);
+	}
+
+	/// Count a frame of `len` bytes (header included) as decoded.
+	pub fn record_decoded(&self, frame_type: FrameType, len: usize) {
+		self.decoded.add(frame_type, len);
+	}
+
+	/// Count a failed decode. `decode_v1_ref` does this itself in
+	/// `Metrics::global()`; call it for errors from the
+	/// `decode_*_payload_v1` functions.
+	pub fn record_decode_error(&self, error: &DecodeError) {
+		self.decode_errors[error.index()].fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Current counter values.
+	pub fn snapshot(&self) -> MetricsSnapshot {
+		MetricsSnapshot {
+			encoded: self.encoded.read(),
+			decoded: self.decoded.read(),
+			decode_errors: DecodeError::NAMES
+				.iter()
+				.zip(&self.decode_errors)
+				.map(|(name, n)| (*name, n.load(Ordering::Relaxed)))
+				.filter(|(_, n)| *n > 0)
+				.collect(),
+		}
+	}
+
+	/// Zero every counter.
+	pub fn reset(&self) {
+		self.encoded.reset();
+		self.decoded.reset();
+		for counter in &self.decode_errors {
+			counter.store(0, Ordering::Relaxed);
+		}
+	}
+}
+
+impl Default for Metrics {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Frames and bytes seen for one frame type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameCount {
+	pub frame_type: FrameType,
+	pub frames: u64,
+	pub bytes: u64,
+}
+
+/// Counter values at one point in time. Types and errors never seen since
+/// the last reset are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+	pub encoded: Vec<FrameCount>,
+	pub decoded: Vec<FrameCount>,
+	/// Failed decodes by `DecodeError::name`.
+	pub decode_errors: Vec<(&'static str, u64)>,
+}
+
+impl MetricsSnapshot {
+	pub fn frames_encoded(&self) -> u64 {
+		self.encoded.iter().map(|c| c.frames).sum()
+	}
+
+	pub fn frames_decoded(&self) -> u64 {
+		self.decoded.iter().map(|c| c.frames).sum()
+	}
+
+	pub fn decode_error_count(&self) -> u64 {
+		self.decode_errors.iter().map(|(_, n)| n).sum()
+	}
+
+	/// `{"encoded":[...],"decoded":[...],"decodeErrors":{name: count}}`,
+	/// each frame entry `{"type","name","frames","bytes"}`.
+	pub fn to_json(&self) -> String {
+		let counts = |counts: &[FrameCount]| {
+			counts
+				.iter()
+				.map(|c| {
+					format!(
+						"{{\"type\":{},\"name\":\"{}\",\"frames\":{},\"bytes\":{}}}",
+						c.frame_type as u8,
+						frame_schema(c.frame_type).map_or("unknown", |s| s.name),
+						c.frames,
+						c.bytes
+					)
+				})
+				.collect::<Vec<_>>()
+				.join(",")
+		};
+		let errors = self
+			.decode_errors
+			.iter()
+			.map(|(name, n)| format!("\"{}\":{}", name, n))
+			.collect::<Vec<_>>()
+			.join(",");
+		format!(
+			"{{\"encoded\":[{}],\"decoded\":[{}],\"decodeErrors\":{{{}}}}}",
+			counts(&self.encoded),
+			counts(&self.decoded),
+			errors
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::frame::{decode_v1, encode_chat_text_v1, encode_file_end_v1};
+
+	fn count(counts: &[FrameCount], frame_type: FrameType) -> (u64, u64) {
+		counts
+			.iter()
+			.find(|c| c.frame_type == frame_type)
+			.map_or((0, 0), |c| (c.frames, c.bytes))
+	}
+
+	#[test]
+	fn counts_frames_by_type() {
+		let metrics = Metrics::new();
+		let chat = encode_chat_text_v1("metrics");
+		let end = encode_file_end_v1("f1");
+		metrics.record_encoded(FrameType::ChatText, chat.len());
+		metrics.record_encoded(FrameType::FileEnd, end.len());
+		metrics.record_encoded(FrameType::ChatText, chat.len());
+		metrics.record_decoded(FrameType::ChatText, chat.len());
+
+		let snapshot = metrics.snapshot();
+		assert_eq!(count(&snapshot.encoded, FrameType::ChatText), (2, 2 * chat.len() as u64));
+		assert_eq!(count(&snapshot.encoded, FrameType::FileEnd), (1, end.len() as u64));
+		assert_eq!(snapshot.decoded, [FrameCount { frame_type: FrameType::ChatText, frames: 1, bytes: chat.len() as u64 }]);
+		assert_eq!((snapshot.frames_encoded(), snapshot.frames_decoded()), (3, 1));
+
+		metrics.reset();
+		assert_eq!(metrics.snapshot(), MetricsSnapshot::default());
+	}
+
+	#[test]
+	fn counts_decode_errors_by_name() {
+		let metrics = Metrics::new();
+		for input in [&b"XX\x01\x10\x00"[..], &encode_chat_text_v1("truncated")[..7]] {
+			metrics.record_decode_error(&decode_v1(input, 1024).unwrap_err());
+		}
+		metrics.record_decode_error(&DecodeError::BadRekey);
+		metrics.record_decode_error(&DecodeError::BadHello);
+
+		let snapshot = metrics.snapshot();
+		assert_eq!(
+			snapshot.decode_errors,
+			[("unexpected-eof", 1), ("bad-magic", 1), ("bad-rekey", 1), ("bad-hello", 1)]
+		);
+		assert_eq!(snapshot.decode_error_count(), 4);
+	}
+
+	#[test]
+	fn codec_counts_in_global() {
+		// Other tests encode and decode in parallel, so only check that the
+		// global counts grew by at least what this test did
+		let before = Metrics::global().snapshot();
+		let chat = encode_chat_text_v1("metrics");
+		decode_v1(&chat, 1024).unwrap();
+		assert!(decode_v1(b"XX\x01\x10\x00", 1024).is_err());
+		let after = Metrics::global().snapshot();
+
+		let (frames, bytes) = count(&after.encoded, FrameType::ChatText);
+		let (frames0, bytes0) = count(&before.encoded, FrameType::ChatText);
+		assert!(frames > frames0 && bytes >= bytes0 + chat.len() as u64);
+		let (frames, bytes) = count(&after.decoded, FrameType::ChatText);
+		let (frames0, bytes0) = count(&before.decoded, FrameType::ChatText);
+		assert!(frames > frames0 && bytes >= bytes0 + chat.len() as u64);
+		assert!(after.decode_error_count() > before.decode_error_count());
+	}
+
+	#[test]
+	fn exports_json() {
+		let snapshot = MetricsSnapshot {
+			encoded: vec![FrameCount { frame_type: FrameType::Ping, frames: 2, bytes: 12 }],
+			decoded: Vec::new(),
+			decode_errors: vec![("bad-magic", 1)],
+		};
+		assert_eq!(
+			snapshot.to_json(),
+			"{\"encoded\":[{\"type\":1,\"name\":\"ping\",\"frames\":2,\"bytes\":12}],\"decoded\":[],\"decodeErrors\":{\"bad-magic\":1}}"
+		);
+		assert_eq!(MetricsSnapshot::default().to_json(), "{\"encoded\":[],\"decoded\":[],\"decodeErrors\":{}}");
+	}
+}
@@ -0,0 +1,268 @@
+//! Scheduling of chunk encrypt/decrypt on a pool of workers.
+//!
+//! Frames are handed to workers through one shared buffer (a
+//! `SharedArrayBuffer` on the web) split into fixed-size slots, so frame bytes
+//! are never copied through `postMessage`. Layout, all integers little-endian:
+//!
+//! ```text
+//! control: one u32 doorbell per worker, padded to 16 bytes
+//! slot i:  state: i32 | op: u32 | len: u32 | reserved: u32 | data[capacity]
+//! ```
+//!
+//! The owner writes a frame into a free slot, sets `op`/`len`, stores
+//! `SLOT_SUBMITTED` and bumps the worker's doorbell. The worker seals or opens
+//! the frame in place, updates `len` and stores `SLOT_DONE` (or
+//! `SLOT_FAILED`). `OffloadScheduler` decides which slot each frame goes to
+//! and hands results back in submission order.
+
+use std::collections::BTreeMap;
+
+pub const SLOT_FREE: i32 = 0;
+pub const SLOT_SUBMITTED: i32 = 1;
+pub const SLOT_DONE: i32 = 2;
+pub const SLOT_FAILED: i32 = 3;
+
+/// Encrypt a frame into an EncryptedEnvelope.
+pub const OP_SEAL: u32 = 1;
+/// Decrypt an EncryptedEnvelope back into its inner frame.
+pub const OP_OPEN: u32 = 2;
+
+pub const SLOT_HEADER_LEN: usize = 16;
+/// Room for the frame header, transfer id, proof and envelope around the chunk data.
+pub const FRAME_OVERHEAD_BYTES: usize = 2048;
+
+/// Byte offsets within the shared buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotLayout {
+	pub workers: usize,
+	pub slots_per_worker: usize,
+	/// Data bytes per slot.
+	pub slot_capacity: usize,
+}
+
+impl SlotLayout {
+	/// Layout whose slots fit a FileChunk of `chunk_size` data bytes, sealed.
+	pub fn for_chunk_size(workers: usize, slots_per_worker: usize, chunk_size: usize) -> Self {
+		Self {
+			workers: workers.max(1),
+			slots_per_worker: slots_per_worker.max(1),
+			slot_capacity: (chunk_size + FRAME_OVERHEAD_BYTES).next_multiple_of(16),
+		}
+	}
+
+	pub fn slot_count(&self) -> usize {
+		self.workers * self.slots_per_worker
+	}
+
+	/// Bytes taken by the per-worker doorbells.
+	pub fn control_len(&self) -> usize {
+		(self.workers * 4).next_multiple_of(16)
+	}
+
+	pub fn slot_stride(&self) -> usize {
+		SLOT_HEADER_LEN + self.slot_capacity
+	}
+
+	/// Total size of the shared buffer.
+	pub fn buffer_len(&self) -> usize {
+		self.control_len() + self.slot_count() * self.slot_stride()
+	}
+
+	pub fn doorbell_offset(&self, worker: usize) -> usize {
+		worker * 4
+	}
+
+	pub fn header_offset(&self, slot: usize) -> usize {
+		self.control_len() + slot * self.slot_stride()
+	}
+
+	pub fn data_offset(&self, slot: usize) -> usize {
+		self.header_offset(slot) + SLOT_HEADER_LEN
+	}
+
+	/// Worker that owns `slot`.
+	pub fn worker_of(&self, slot: usize) -> usize {
+		slot / self.slots_per_worker
+	}
+}
+
+/// A frame placed in a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Job {
+	/// Submission order; results come back in this order.
+	pub seq: u64,
+	pub worker: usize,
+	pub slot: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlotState {
+	Free,
+	InFlight(u64),
+	Done(u64),
+}
+
+/// Assigns frames to worker slots and returns finished ones in order.
+///
+/// `submit` returns `None` while every slot is busy, which is the caller's
+/// backpressure signal. A finished frame must be read out of its slot before
+/// the next `submit`, since `next_ready` frees the slot.
+#[derive(Debug, Clone)]
+pub struct OffloadScheduler {
+	layout: SlotLayout,
+	slots: Vec<SlotState>,
+	next_seq: u64,
+	next_out: u64,
+	/// seq -> slot of frames not yet returned by `next_ready`.
+	pending: BTreeMap<u64, usize>,
+}
+
+impl OffloadScheduler {
+	pub fn new(layout: SlotLayout) -> Self {
+		Self {
+			slots: vec![SlotState::Free; layout.slot_count()],
+			layout,
+			next_seq: 0,
+			next_out: 0,
+			pending: BTreeMap::new(),
+		}
+	}
+
+	pub fn layout(&self) -> &SlotLayout {
+		&self.layout
+	}
+
+	/// Frames per worker that are submitted or finished but not yet returned.
+	pub fn worker_load(&self, worker: usize) -> usize {
+		let start = worker * self.layout.slots_per_worker;
+		self.slots[start..start + self.layout.slots_per_worker]
+			.iter()
+			.filter(|s| **s != SlotState::Free)
+			.count()
+	}
+
+	pub fn in_flight(&self) -> usize {
+		self.pending.len()
+	}
+
+	/// Reserve a slot on the least-loaded worker for the next frame.
+	pub fn submit(&mut self) -> Option<Job> {
+		let worker = (0..self.layout.workers)
+			.filter(|&w| self.worker_load(w) < self.layout.slots_per_worker)
+			.min_by_key(|&w| self.worker_load(w))?;
+		let start = worker * self.layout.slots_per_worker;
+		let slot = (start..start + self.layout.slots_per_worker)
+			.find(|&s| self.slots[s] == SlotState::Free)?;
+		let seq = self.next_seq;
+		self.next_seq += 1;
+		self.slots[slot] = SlotState::InFlight(seq);
+		self.pending.insert(seq, slot);
+		Some(Job { seq, worker, slot })
+	}
+
+	/// Record that the worker finished `slot` (successfully or not).
+	/// Returns false if the slot had nothing in flight.
+	pub fn complete(&mut self, slot: usize) -> bool {
+		match self.slots.get(slot) {
+			Some(&SlotState::InFlight(seq)) => {
+				self.slots[slot] = SlotState::Done(seq);
+				true
+			}
+			_ => false,
+		}
+	}
+
+	/// The next finished frame in submission order, freeing its slot.
+	pub fn next_ready(&mut self) -> Option<Job> {
+		let &slot = self.pending.get(&self.next_out)?;
+		let SlotState::Done(seq) = self.slots[slot] else {
+			return None;
+		};
+		self.pending.remove(&seq);
+		self.slots[slot] = SlotState::Free;
+		self.next_out += 1;
+		Some(Job {
+			seq,
+			worker: self.layout.worker_of(slot),
+			slot,
+		})
+	}
+
+	/// Forget everything in flight (e.g. the transfer was cancelled).
+	pub fn reset(&mut self) {
+		self.slots.fill(SlotState::Free);
+		self.pending.clear();
+		self.next_out = self.next_seq;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn layout_offsets() {
+		let layout = SlotLayout::for_chunk_size(3, 2, 16 * 1024);
+		assert_eq!(layout.slot_count(), 6);
+		assert_eq!(layout.control_len(), 16);
+		assert!(layout.slot_capacity.is_multiple_of(16));
+		assert!(layout.slot_capacity >= 16 * 1024 + FRAME_OVERHEAD_BYTES);
+		assert_eq!(layout.header_offset(0), 16);
+		assert_eq!(layout.data_offset(1), 16 + layout.slot_stride() + SLOT_HEADER_LEN);
+		assert_eq!(
+			layout.buffer_len(),
+			layout.header_offset(5) + layout.slot_stride()
+		);
+		assert_eq!(layout.worker_of(3), 1);
+		// Headers stay 4-byte aligned for Int32Array/Atomics access
+		assert!((0..6).all(|s| layout.header_offset(s).is_multiple_of(4)));
+	}
+
+	#[test]
+	fn spreads_load_and_applies_backpressure() {
+		let mut sched = OffloadScheduler::new(SlotLayout::for_chunk_size(2, 2, 1024));
+		let jobs: Vec<Job> = (0..4).map(|_| sched.submit().unwrap()).collect();
+		assert_eq!(jobs.iter().map(|j| j.worker).collect::<Vec<_>>(), vec![0, 1, 0, 1]);
+		assert!(sched.submit().is_none());
+		assert_eq!(sched.in_flight(), 4);
+	}
+
+	#[test]
+	fn results_come_back_in_order() {
+		let mut sched = OffloadScheduler::new(SlotLayout::for_chunk_size(2, 2, 1024));
+		let jobs: Vec<Job> = (0..4).map(|_| sched.submit().unwrap()).collect();
+
+		// Worker 1 finishes first; nothing is released until seq 0 is done
+		assert!(sched.complete(jobs[1].slot));
+		assert!(sched.complete(jobs[3].slot));
+		assert!(sched.next_ready().is_none());
+
+		assert!(sched.complete(jobs[0].slot));
+		assert_eq!(sched.next_ready(), Some(jobs[0]));
+		assert_eq!(sched.next_ready(), Some(jobs[1]));
+		assert!(sched.next_ready().is_none());
+
+		// The freed slots take new frames
+		let next = sched.submit().unwrap();
+		assert_eq!(next.seq, 4);
+		assert!(!sched.complete(next.slot + 100));
+		assert!(sched.complete(jobs[2].slot));
+		assert!(!sched.complete(jobs[2].slot));
+		assert_eq!(sched.next_ready(), Some(jobs[2]));
+		assert_eq!(sched.next_ready(), Some(jobs[3]));
+	}
+
+	#[test]
+	fn reset_drops_in_flight() {
+		let mut sched = OffloadScheduler::new(SlotLayout::for_chunk_size(1, 2, 1024));
+		let a = sched.submit().unwrap();
+		sched.submit().unwrap();
+		sched.reset();
+		assert!(!sched.complete(a.slot));
+		assert_eq!(sched.in_flight(), 0);
+		let b = sched.submit().unwrap();
+		assert_eq!(b.seq, 2);
+		assert!(sched.complete(b.slot));
+		assert_eq!(sched.next_ready(), Some(b));
+	}
+}
@@ -0,0 +1,269 @@
+//! Outbound frame ordering for a single DataChannel.
+//!
+//! Everything shares one ordered channel, so a file transfer that fills the
+//! send buffer delays chat behind megabytes of chunks. `FrameScheduler`
+//! holds frames before they reach the channel and releases them by class:
+//! - `Control` frames (ping/pong, accept/reject, ACL updates, errors) always go first.
+//! - `Chat` and `Bulk` share the rest by deficit round robin, weighted by bytes,
+//!   so chat keeps flowing during a transfer and a busy chat can't stall it.
+//!
+//! Frames within a class keep their order (FileEnd stays behind its chunks).
+
+use std::collections::VecDeque;
+
+use crate::frame::FrameType;
+
+/// Bytes a weight of 1 earns per round.
+pub const QUANTUM_BYTES: usize = 16 * 1024;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+	Control = 0,
+	Chat = 1,
+	Bulk = 2,
+}
+
+impl Priority {
+	pub fn from_u8(value: u8) -> Option<Self> {
+		Some(match value {
+			0 => Self::Control,
+			1 => Self::Chat,
+			2 => Self::Bulk,
+			_ => return None,
+		})
+	}
+
+	pub fn for_frame_type(frame_type: FrameType) -> Self {
+		match frame_type {
+			FrameType::ChatText
+			| FrameType::EphemeralEnvelope
+			| FrameType::FileOffer
+			| FrameType::FileOfferSealed => Self::Chat,
+			FrameType::FileChunk | FrameType::FileEnd => Self::Bulk,
+			FrameType::Ping
+			| FrameType::Pong
+			| FrameType::Hello
+			| FrameType::RekeyRequest
+			| FrameType::RekeyResponse
+			| FrameType::Goodbye
+			| FrameType::FileAccept
+			| FrameType::FileReject
+			| FrameType::FileReveal
+			| FrameType::AclUpdate
+			| FrameType::ProjectInvite
+			| FrameType::ProtocolError
+			| FrameType::EncryptedEnvelope
+			| FrameType::MultiEnvelope => Self::Control,
+		}
+	}
+
+	/// Class of an encoded (plaintext) frame, from its header. Classify
+	/// before sealing: an EncryptedEnvelope hides the inner type.
+	pub fn for_frame_bytes(bytes: &[u8]) -> Option<Self> {
+		bytes
+			.get(3)
+			.and_then(|&t| FrameType::from_u8(t))
+			.map(Self::for_frame_type)
+	}
+}
+
+/// Relative byte shares of chat and bulk traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedulerWeights {
+	pub chat: u32,
+	pub bulk: u32,
+}
+
+impl Default for SchedulerWeights {
+	fn default() -> Self {
+		Self { chat: 4, bulk: 1 }
+	}
+}
+
+const CHAT: usize = 0;
+const BULK: usize = 1;
+
+#[derive(Debug, Clone, Default)]
+pub struct FrameScheduler {
+	control: VecDeque<Vec<u8>>,
+	/// Chat and bulk queues, indexed by `CHAT`/`BULK`.
+	weighted: [VecDeque<Vec<u8>>; 2],
+	deficit: [usize; 2],
+	pending_bytes: [usize; 3],
+	weights: SchedulerWeights,
+	turn: usize,
+}
+
+impl FrameScheduler {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn with_weights(weights: SchedulerWeights) -> Self {
+		Self {
+			weights: SchedulerWeights {
+				chat: weights.chat.max(1),
+				bulk: weights.bulk.max(1),
+			},
+			..Self::default()
+		}
+	}
+
+	pub fn enqueue(&mut self, priority: Priority, frame: Vec<u8>) {
+		self.pending_bytes[priority as usize] += frame.len();
+		match priority {
+			Priority::Control => self.control.push_back(frame),
+			Priority::Chat => self.weighted[CHAT].push_back(frame),
+			Priority::Bulk => self.weighted[BULK].push_back(frame),
+		}
+	}
+
+	/// Bytes waiting in one class.
+	pub fn pending_bytes(&self, priority: Priority) -> usize {
+		self.pending_bytes[priority as usize]
+	}
+
+	pub fn len(&self) -> usize {
+		self.control.len() + self.weighted[CHAT].len() + self.weighted[BULK].len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	fn quantum(&self, class: usize) -> usize {
+		let weight = if class == CHAT { self.weights.chat } else { self.weights.bulk };
+		weight as usize * QUANTUM_BYTES
+	}
+
+	fn take(&mut self, priority: Priority, frame: Vec<u8>) -> Option<(Priority, Vec<u8>)> {
+		self.pending_bytes[priority as usize] -= frame.len();
+		Some((priority, frame))
+	}
+
+	/// The next frame to send and its class.
+	pub fn next_frame(&mut self) -> Option<(Priority, Vec<u8>)> {
+		if let Some(frame) = self.control.pop_front() {
+			return self.take(Priority::Control, frame);
+		}
+		if self.weighted.iter().all(VecDeque::is_empty) {
+			return None;
+		}
+		loop {
+			let turn = self.turn;
+			match self.weighted[turn].front().map(Vec::len) {
+				Some(len) if len <= self.deficit[turn] => {
+					self.deficit[turn] -= len;
+					let frame = self.weighted[turn].pop_front()?;
+					let priority = if turn == CHAT { Priority::Chat } else { Priority::Bulk };
+					return self.take(priority, frame);
+				}
+				// An idle class doesn't bank credit.
+				None => self.deficit[turn] = 0,
+				Some(_) => {}
+			}
+			self.turn ^= 1;
+			self.deficit[self.turn] += self.quantum(self.turn);
+		}
+	}
+
+	/// Frames to hand to the channel now: at least one (if any are queued),
+	/// stopping once `bytes_budget` is reached, so the batch exceeds the
+	/// budget by less than one frame.
+	pub fn next_batch(&mut self, bytes_budget: usize) -> Vec<Vec<u8>> {
+		let mut batch = Vec::new();
+		let mut total = 0;
+		while batch.is_empty() || total < bytes_budget {
+			let Some((_, frame)) = self.next_frame() else {
+				break;
+			};
+			total += frame.len();
+			batch.push(frame);
+		}
+		batch
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::frame::{encode_chat_text_v1, encode_file_chunk_v1, encode_file_end_v1};
+
+	#[test]
+	fn classifies_frames() {
+		assert_eq!(Priority::for_frame_bytes(&encode_chat_text_v1("hi")), Some(Priority::Chat));
+		assert_eq!(Priority::for_frame_bytes(&encode_file_chunk_v1("t", 0, b"x")), Some(Priority::Bulk));
+		assert_eq!(Priority::for_frame_bytes(&encode_file_end_v1("t")), Some(Priority::Bulk));
+		assert_eq!(Priority::for_frame_type(FrameType::Ping), Priority::Control);
+		assert_eq!(Priority::for_frame_bytes(b"HO"), None);
+		assert_eq!(Priority::from_u8(3), None);
+	}
+
+	#[test]
+	fn control_goes_first_and_classes_keep_order() {
+		let mut sched = FrameScheduler::new();
+		sched.enqueue(Priority::Bulk, vec![1; 10]);
+		sched.enqueue(Priority::Bulk, vec![2; 10]);
+		sched.enqueue(Priority::Control, vec![0; 3]);
+		assert_eq!(sched.pending_bytes(Priority::Bulk), 20);
+
+		assert_eq!(sched.next_frame(), Some((Priority::Control, vec![0; 3])));
+		assert_eq!(sched.next_frame().unwrap().1[0], 1);
+		assert_eq!(sched.next_frame().unwrap().1[0], 2);
+		assert!(sched.next_frame().is_none());
+		assert!(sched.is_empty());
+		assert_eq!(sched.pending_bytes(Priority::Bulk), 0);
+	}
+
+	#[test]
+	fn chat_interleaves_with_bulk_by_weight() {
+		let mut sched = FrameScheduler::with_weights(SchedulerWeights { chat: 2, bulk: 1 });
+		for _ in 0..20 {
+			sched.enqueue(Priority::Bulk, vec![0; QUANTUM_BYTES]);
+		}
+		for _ in 0..20 {
+			sched.enqueue(Priority::Chat, vec![1; QUANTUM_BYTES]);
+		}
+		let order: Vec<u8> = (0..12).map(|_| sched.next_frame().unwrap().1[0]).collect();
+		// Every round: two chat frames per bulk frame
+		assert_eq!(order.iter().filter(|&&c| c == 1).count(), 8);
+		assert!(order.windows(4).all(|w| w.contains(&0) && w.contains(&1)));
+	}
+
+	#[test]
+	fn chat_does_not_wait_behind_a_transfer() {
+		let mut sched = FrameScheduler::new();
+		for i in 0..100u8 {
+			sched.enqueue(Priority::Bulk, vec![i; 16 * 1024]);
+		}
+		sched.next_batch(64 * 1024);
+		sched.enqueue(Priority::Chat, b"hola".to_vec());
+		let batch = sched.next_batch(64 * 1024);
+		assert!(batch.iter().any(|f| f == b"hola"));
+	}
+
+	#[test]
+	fn batch_respects_budget() {
+		let mut sched = FrameScheduler::new();
+		for _ in 0..10 {
+			sched.enqueue(Priority::Bulk, vec![0; 1000]);
+		}
+		assert_eq!(sched.next_batch(2500).len(), 3);
+		assert_eq!(sched.next_batch(0).len(), 1);
+		assert_eq!(sched.next_batch(usize::MAX).len(), 6);
+		assert!(sched.next_batch(100).is_empty());
+	}
+
+	#[test]
+	fn oversized_frames_still_drain() {
+		let mut sched = FrameScheduler::new();
+		sched.enqueue(Priority::Bulk, vec![0; 5 * QUANTUM_BYTES]);
+		sched.enqueue(Priority::Chat, vec![1; 10]);
+		let mut out = Vec::new();
+		while let Some((p, _)) = sched.next_frame() {
+			out.push(p);
+		}
+		assert_eq!(out, vec![Priority::Chat, Priority::Bulk]);
+	}
+}
@@ -0,0 +1,67 @@
+//! Length padding for frames sealed in an EncryptedEnvelope.
+//!
+//! A sealed chat message otherwise leaks its length to anyone on the path.
+//! The sender appends zero bytes after the inner frame up to the next
+//! bucket size before encrypting; the frame header still carries the real
+//! payload length, so the receiver drops everything after the frame. Peers
+//! that don't pad read padded frames the same way, since `decode_v1`
+//! ignores trailing bytes.
+
+use crate::frame::peek_v1_ref;
+
+/// Bucket sizes (bytes of plaintext) used when padding is on.
+pub const DEFAULT_PAD_BUCKETS: &[usize] = &[64, 256, 1024, 4096, 16384];
+
+/// Length `len` pads to: the smallest bucket that fits it, or the next
+/// multiple of the largest bucket. Unchanged when `buckets` is empty.
+pub fn padded_len(len: usize, buckets: &[usize]) -> usize {
+	if let Some(&bucket) = buckets.iter().filter(|&&b| b >= len).min() {
+		return bucket;
+	}
+	match buckets.iter().max() {
+		Some(&largest) if largest > 0 => len.div_ceil(largest) * largest,
+		_ => len,
+	}
+}
+
+/// Append zero bytes to an encoded frame up to `padded_len`.
+pub fn pad_frame(frame: &mut Vec<u8>, buckets: &[usize]) {
+	frame.resize(padded_len(frame.len(), buckets), 0);
+}
+
+/// The frame at the start of a possibly padded plaintext. Anything that
+/// doesn't parse as a complete frame is returned whole.
+pub fn unpad_frame(plaintext: &[u8]) -> &[u8] {
+	match peek_v1_ref(plaintext, u32::MAX) {
+		Ok((_frame, used)) => &plaintext[..used],
+		Err(_) => plaintext,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::frame::{decode_v1, encode_chat_text_v1};
+
+	#[test]
+	fn pads_to_buckets() {
+		assert_eq!(padded_len(1, DEFAULT_PAD_BUCKETS), 64);
+		assert_eq!(padded_len(64, DEFAULT_PAD_BUCKETS), 64);
+		assert_eq!(padded_len(65, DEFAULT_PAD_BUCKETS), 256);
+		assert_eq!(padded_len(16385, DEFAULT_PAD_BUCKETS), 32768);
+		assert_eq!(padded_len(10, &[]), 10);
+		assert_eq!(padded_len(10, &[0]), 10);
+	}
+
+	#[test]
+	fn unpad_restores_frame() {
+		let frame = encode_chat_text_v1("hi");
+		let mut padded = frame.clone();
+		pad_frame(&mut padded, DEFAULT_PAD_BUCKETS);
+		assert_eq!(padded.len(), 64);
+		assert_eq!(unpad_frame(&padded), frame.as_slice());
+		assert_eq!(decode_v1(&padded, 1024).unwrap().0.payload, b"hi");
+
+		assert_eq!(unpad_frame(b"not a frame"), b"not a frame");
+	}
+}
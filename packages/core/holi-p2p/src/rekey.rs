@@ -0,0 +1,191 @@
+//! Rotating the envelope key mid-session.
+//!
+//! `RekeySchedule` counts what was sealed under the current key and tracks
+//! the RekeyRequest/RekeyResponse exchange; the crypto is the caller's:
+//!
+//! 1. Once `due()`, the initiator sends RekeyRequest with `epoch + 1` and a
+//!    fresh X25519 public key.
+//! 2. The responder answers with RekeyResponse carrying its own fresh key
+//!    and switches to the new key for sending.
+//! 3. The initiator switches when the response arrives.
+//!
+//! Both sides derive the new key as HKDF-SHA256 with the old key as salt,
+//! the X25519 shared secret as input and `rekey_info(epoch)` as info, so a
+//! leaked old key alone doesn't give the new one. Frames sealed under the
+//! old key can still be in flight after the switch; receivers keep the
+//! previous key for opening until the next rotation.
+//!
+//! If both sides request the same epoch at once, the request with the
+//! larger public key wins and the other side answers it.
+
+use crate::frame::{Rekey, REKEY_PUBLIC_KEY_LEN};
+
+/// Rotate after this many plaintext bytes under one key.
+pub const DEFAULT_REKEY_AFTER_BYTES: u64 = 1 << 30;
+/// Rotate after this many frames under one key.
+pub const DEFAULT_REKEY_AFTER_MESSAGES: u64 = 1 << 20;
+/// HKDF info prefix; the big-endian epoch follows.
+pub const REKEY_INFO: &[u8] = b"holi-rekey-v1";
+
+/// HKDF info for the key of `epoch`.
+pub fn rekey_info(epoch: u32) -> Vec<u8> {
+	let mut info = REKEY_INFO.to_vec();
+	info.extend_from_slice(&epoch.to_be_bytes());
+	info
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RekeyPolicy {
+	pub max_bytes: u64,
+	pub max_messages: u64,
+}
+
+impl Default for RekeyPolicy {
+	fn default() -> Self {
+		RekeyPolicy {
+			max_bytes: DEFAULT_REKEY_AFTER_BYTES,
+			max_messages: DEFAULT_REKEY_AFTER_MESSAGES,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RekeyError {
+	/// The frame is not for the epoch after the current one.
+	WrongEpoch { expected: u32, got: u32 },
+	/// A response to a request we didn't send.
+	NotRequested,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestOutcome {
+	/// Derive the new key, send a RekeyResponse and switch.
+	Accept,
+	/// Our own concurrent request wins; the peer will answer it.
+	Ignore,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RekeySchedule {
+	policy: RekeyPolicy,
+	epoch: u32,
+	sealed_bytes: u64,
+	sealed_messages: u64,
+	pending: Option<Rekey>,
+}
+
+impl RekeySchedule {
+	pub fn new(policy: RekeyPolicy) -> Self {
+		RekeySchedule { policy, ..Self::default() }
+	}
+
+	pub fn set_policy(&mut self, policy: RekeyPolicy) {
+		self.policy = policy;
+	}
+
+	/// Epoch of the current key; 0 for the key the session started with.
+	pub fn epoch(&self) -> u32 {
+		self.epoch
+	}
+
+	/// Our request, while it waits for a response.
+	pub fn pending(&self) -> Option<&Rekey> {
+		self.pending.as_ref()
+	}
+
+	/// Count one frame of `len` bytes sealed under the current key.
+	pub fn record_sealed(&mut self, len: usize) {
+		self.sealed_bytes = self.sealed_bytes.saturating_add(len as u64);
+		self.sealed_messages = self.sealed_messages.saturating_add(1);
+	}
+
+	/// A threshold was reached and no rotation is in flight.
+	pub fn due(&self) -> bool {
+		self.pending.is_none()
+			&& (self.sealed_bytes >= self.policy.max_bytes
+				|| self.sealed_messages >= self.policy.max_messages)
+	}
+
+	/// Request the next epoch with our fresh `public_key`.
+	pub fn start(&mut self, public_key: [u8; REKEY_PUBLIC_KEY_LEN]) -> Rekey {
+		let request = Rekey { epoch: self.epoch.wrapping_add(1), public_key };
+		self.pending = Some(request);
+		request
+	}
+
+	pub fn on_request(&self, request: &Rekey) -> Result<RequestOutcome, RekeyError> {
+		let expected = self.epoch.wrapping_add(1);
+		if request.epoch != expected {
+			return Err(RekeyError::WrongEpoch { expected, got: request.epoch });
+		}
+		match &self.pending {
+			Some(ours) if ours.public_key > request.public_key => Ok(RequestOutcome::Ignore),
+			_ => Ok(RequestOutcome::Accept),
+		}
+	}
+
+	/// Check a response against our pending request.
+	pub fn on_response(&self, response: &Rekey) -> Result<(), RekeyError> {
+		let ours = self.pending.as_ref().ok_or(RekeyError::NotRequested)?;
+		if response.epoch != ours.epoch {
+			return Err(RekeyError::WrongEpoch { expected: ours.epoch, got: response.epoch });
+		}
+		Ok(())
+	}
+
+	/// The key for `epoch` is in use: reset the counters.
+	pub fn complete(&mut self, epoch: u32) {
+		self.epoch = epoch;
+		self.sealed_bytes = 0;
+		self.sealed_messages = 0;
+		self.pending = None;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn policy(max_bytes: u64, max_messages: u64) -> RekeyPolicy {
+		RekeyPolicy { max_bytes, max_messages }
+	}
+
+	#[test]
+	fn due_after_thresholds() {
+		let mut by_bytes = RekeySchedule::new(policy(1000, 100));
+		by_bytes.record_sealed(600);
+		assert!(!by_bytes.due());
+		by_bytes.record_sealed(400);
+		assert!(by_bytes.due());
+
+		let mut by_count = RekeySchedule::new(policy(u64::MAX, 2));
+		by_count.record_sealed(1);
+		by_count.record_sealed(1);
+		assert!(by_count.due());
+		let request = by_count.start([1; REKEY_PUBLIC_KEY_LEN]);
+		assert_eq!(request.epoch, 1);
+		assert!(!by_count.due());
+		assert_eq!(by_count.on_response(&Rekey { epoch: 1, public_key: [2; 32] }), Ok(()));
+		by_count.complete(1);
+		assert_eq!(by_count.epoch(), 1);
+		assert!(!by_count.due());
+	}
+
+	#[test]
+	fn concurrent_requests_pick_one_winner() {
+		let mut alice = RekeySchedule::default();
+		let mut bob = RekeySchedule::default();
+		let from_alice = alice.start([9; REKEY_PUBLIC_KEY_LEN]);
+		let from_bob = bob.start([3; REKEY_PUBLIC_KEY_LEN]);
+		assert_eq!(alice.on_request(&from_bob), Ok(RequestOutcome::Ignore));
+		assert_eq!(bob.on_request(&from_alice), Ok(RequestOutcome::Accept));
+	}
+
+	#[test]
+	fn rejects_stale_frames() {
+		let schedule = RekeySchedule::default();
+		let stale = Rekey { epoch: 5, public_key: [0; REKEY_PUBLIC_KEY_LEN] };
+		assert_eq!(schedule.on_request(&stale), Err(RekeyError::WrongEpoch { expected: 1, got: 5 }));
+		assert_eq!(schedule.on_response(&stale), Err(RekeyError::NotRequested));
+	}
+}
@@ -0,0 +1,745 @@
+//! Machine-readable description of the v1 wire format.
+//!
+//! `schema_json()` is consumed by the TS codegen to produce matching parsers
+//! and docs. The layouts below are checked against the real encoders in the
+//! tests, so a change to `frame.rs` that isn't reflected here fails CI.
+
+use crate::frame::{
+	CipherSuite, FrameType, ACL_AUTHOR_KEY_LEN, ACL_SIGNATURE_LEN, CHAT_SENDER_KEY_LEN,
+	CHAT_SIGNATURE_LEN, CHAT_TEXT_FLAG_ATTRIBUTED, ENVELOPE_AES_GCM_NONCE_LEN,
+	ENVELOPE_FLAG_CIPHER_SUITE, ENVELOPE_NONCE_LEN, FILE_CHUNK_FLAG_PROOF, MAGIC, OFFER_KEY_LEN,
+	PROJECT_INVITE_SEALED_KEY_LEN, REKEY_PUBLIC_KEY_LEN, VERSION_V1,
+};
+use crate::merkle::MERKLE_HASH_LEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+	U8,
+	/// Unsigned LEB128, at most 5 bytes.
+	VarintU32,
+	/// Unsigned LEB128, at most 10 bytes.
+	VarintU64,
+	/// `VarintU32` byte length followed by UTF-8 bytes.
+	String,
+	/// Fixed number of raw bytes.
+	Bytes { len: usize },
+	/// Remaining payload bytes, UTF-8.
+	RestUtf8,
+	/// Remaining payload bytes, raw.
+	RestBytes,
+	/// One `U8` tag selecting which variant's fields follow.
+	Tagged { variants: &'static [Variant] },
+	/// `U8` count followed by that many items of `item_len` raw bytes.
+	CountedBytes { item_len: usize },
+	/// `VarintU32` byte length followed by raw bytes.
+	PrefixedBytes,
+	/// `U8` count followed by that many repetitions of `fields`.
+	Repeated { fields: &'static [Field] },
+	/// Trailing fields, present only if payload bytes remain.
+	Optional { fields: &'static [Field] },
+	/// To the end of the payload: a `U8` tag, a `U8` length and that many
+	/// bytes holding the variant's fields. Unknown tags are skipped.
+	Extensions { variants: &'static [Variant] },
+	/// Fields present only when the header flags have `mask` set.
+	IfFlag { mask: u8, fields: &'static [Field] },
+	/// Fields present only when the header flags have `mask` clear.
+	UnlessFlag { mask: u8, fields: &'static [Field] },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+	pub name: &'static str,
+	pub kind: FieldKind,
+	pub doc: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Variant {
+	pub tag: u8,
+	pub name: &'static str,
+	pub fields: &'static [Field],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSchema {
+	pub frame_type: FrameType,
+	pub name: &'static str,
+	pub doc: &'static str,
+	pub fields: &'static [Field],
+}
+
+const fn field(name: &'static str, kind: FieldKind, doc: &'static str) -> Field {
+	Field { name, kind, doc }
+}
+
+/// Frame header preceding every payload.
+pub const HEADER: &[Field] = &[
+	field("magic", FieldKind::Bytes { len: MAGIC.len() }, "Always \"HO\""),
+	field("version", FieldKind::U8, "Wire format version"),
+	field("frameType", FieldKind::U8, "One of the frame type values"),
+	field("flags", FieldKind::U8, "Reserved, 0 unless noted (see ifFlag fields)"),
+	field("payloadLength", FieldKind::VarintU32, "Payload byte length"),
+];
+
+const OPAQUE: &[Field] = &[field("data", FieldKind::RestBytes, "Opaque bytes")];
+const ID_ONLY: &[Field] = &[field("id", FieldKind::String, "Transfer id")];
+
+const ACL_GRANT: &[Field] = &[
+	field("role", FieldKind::U8, "0 = owner, 1 = editor, 2 = viewer"),
+	field("expiresAt", FieldKind::VarintU64, "Expiry in ms, 0 = never"),
+];
+
+const ACL_ACTIONS: &[Variant] = &[
+	Variant { tag: 0, name: "grant", fields: ACL_GRANT },
+	Variant { tag: 1, name: "revoke", fields: &[] },
+];
+
+const HELLO_EXTENSIONS: &[Variant] = &[Variant {
+	tag: 1,
+	name: "maxChunkSize",
+	fields: &[field("size", FieldKind::VarintU32, "Largest FileChunk data accepted, in bytes; not 0")],
+}];
+
+const FILE_REJECT_CODE: &[Field] = &[
+	field(
+		"code",
+		FieldKind::U8,
+		"0 = unspecified, 1 = too large, 2 = unsupported type, 3 = user declined, 4 = quota exceeded, 5 = busy",
+	),
+	field("detail", FieldKind::VarintU64, "Size limit, ms until quota reset or transfer limit; 0 = none"),
+];
+
+const GOODBYE_RESUME: &[Field] =
+	&[field("resumeTicket", FieldKind::PrefixedBytes, "Opaque token to present when reconnecting")];
+
+const FILE_OFFER_THUMBNAIL: &[Field] = &[
+	field("nonce", FieldKind::Bytes { len: ENVELOPE_NONCE_LEN }, "XChaCha20-Poly1305 nonce"),
+	field("ciphertext", FieldKind::PrefixedBytes, "Encrypted image, offer id as associated data"),
+];
+
+const FILE_OFFER_EXTRAS: &[Field] = &[
+	field("leafSize", FieldKind::VarintU32, "Merkle leaf size in bytes; 0 = no root"),
+	field("merkleRoot", FieldKind::Bytes { len: MERKLE_HASH_LEN }, "BLAKE3 Merkle root of the file"),
+	field("thumbnail", FieldKind::Optional { fields: FILE_OFFER_THUMBNAIL }, "Encrypted preview image"),
+];
+
+const FILE_CHUNK_PROOF: &[Field] = &[field(
+	"proof",
+	FieldKind::CountedBytes { item_len: MERKLE_HASH_LEN },
+	"Sibling hashes from the chunk's leaf up to the offer's Merkle root",
+)];
+
+const CHAT_ATTRIBUTION: &[Field] = &[
+	field("sender", FieldKind::Bytes { len: CHAT_SENDER_KEY_LEN }, "Ed25519 public key of the sender"),
+	field("sequence", FieldKind::VarintU64, "Per-sender message counter"),
+	field("timestamp", FieldKind::VarintU64, "Sender clock in ms"),
+	field("signature", FieldKind::Bytes { len: CHAT_SIGNATURE_LEN }, "Ed25519 signature"),
+];
+
+const REKEY: &[Field] = &[
+	field("epoch", FieldKind::VarintU32, "Key epoch being switched to"),
+	field("publicKey", FieldKind::Bytes { len: REKEY_PUBLIC_KEY_LEN }, "Ephemeral X25519 public key"),
+];
+
+const ENVELOPE_SUITES: &[Field] = &[field(
+	"suite",
+	FieldKind::Tagged {
+		variants: &[
+			Variant {
+				tag: CipherSuite::XChaCha20Poly1305 as u8,
+				name: "xchacha20poly1305",
+				fields: &[field("nonce", FieldKind::Bytes { len: ENVELOPE_NONCE_LEN }, "Nonce")],
+			},
+			Variant {
+				tag: CipherSuite::Aes256Gcm as u8,
+				name: "aes256gcm",
+				fields: &[field("nonce", FieldKind::Bytes { len: ENVELOPE_AES_GCM_NONCE_LEN }, "Nonce")],
+			},
+		],
+	},
+	"Cipher suite",
+)];
+
+const FRAMES: &[FrameSchema] = &[
+	FrameSchema {
+		frame_type: FrameType::Ping,
+		name: "ping",
+		doc: "Keepalive; the payload is echoed back in a pong",
+		fields: OPAQUE,
+	},
+	FrameSchema {
+		frame_type: FrameType::Pong,
+		name: "pong",
+		doc: "Reply to ping",
+		fields: OPAQUE,
+	},
+	FrameSchema {
+		frame_type: FrameType::Hello,
+		name: "hello",
+		doc: "First frame of a session; extensions older peers don't know are skipped",
+		fields: &[
+			field(
+				"capabilities",
+				FieldKind::VarintU32,
+				"Bit set: 1 = opens and prefers AES-256-GCM envelopes, 2 = reads offer thumbnails, \
+				 4 = receives animated QR codes, 8 = decodes fountain-coded QR frames",
+			),
+			field("extensions", FieldKind::Extensions { variants: HELLO_EXTENSIONS }, "Each absent unless stated"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::RekeyRequest,
+		name: "rekeyRequest",
+		doc: "Starts an envelope key rotation; sent sealed under the current key",
+		fields: REKEY,
+	},
+	FrameSchema {
+		frame_type: FrameType::RekeyResponse,
+		name: "rekeyResponse",
+		doc: "Accepts a rekeyRequest; both sides then switch to the derived key",
+		fields: REKEY,
+	},
+	FrameSchema {
+		frame_type: FrameType::Goodbye,
+		name: "goodbye",
+		doc: "Last frame before a deliberate close; a channel closing without one was lost",
+		fields: &[
+			field(
+				"reason",
+				FieldKind::U8,
+				"0 = unspecified, 1 = user left, 2 = app closing, 3 = idle, 4 = protocol violation, 5 = restarting",
+			),
+			field("resume", FieldKind::Optional { fields: GOODBYE_RESUME }, "Absent without a resume ticket"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::ChatText,
+		name: "chatText",
+		doc: "Chat message; when attributed, the signature covers \"holi-chat-v1\", the sender, sequence, timestamp and text, or with flag 0x02 \"holi-chat-v2\" and the recipient and channel too",
+		fields: &[
+			field(
+				"attribution",
+				FieldKind::IfFlag { mask: CHAT_TEXT_FLAG_ATTRIBUTED, fields: CHAT_ATTRIBUTION },
+				"Signed sender attribution, when flagged",
+			),
+			field("text", FieldKind::RestUtf8, "Message text"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::FileOffer,
+		name: "fileOffer",
+		doc: "Sender proposes a file transfer",
+		fields: &[
+			field("id", FieldKind::String, "Transfer id"),
+			field("filename", FieldKind::String, "Suggested file name"),
+			field("mimeType", FieldKind::String, "MIME type"),
+			field("size", FieldKind::VarintU64, "Total size in bytes"),
+			field("merkle", FieldKind::Optional { fields: FILE_OFFER_EXTRAS }, "Lets chunks be verified on arrival"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::FileAccept,
+		name: "fileAccept",
+		doc: "Receiver accepts an offer",
+		fields: ID_ONLY,
+	},
+	FrameSchema {
+		frame_type: FrameType::FileReject,
+		name: "fileReject",
+		doc: "Receiver declines an offer",
+		fields: &[
+			field("id", FieldKind::String, "Transfer id"),
+			field("reason", FieldKind::String, "Human-readable reason, may be empty"),
+			field("code", FieldKind::Optional { fields: FILE_REJECT_CODE }, "Absent from older peers"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::FileChunk,
+		name: "fileChunk",
+		doc: "One piece of file data",
+		fields: &[
+			field("id", FieldKind::String, "Transfer id"),
+			field("chunkIndex", FieldKind::VarintU32, "Zero-based chunk index"),
+			field(
+				"proof",
+				FieldKind::IfFlag { mask: FILE_CHUNK_FLAG_PROOF, fields: FILE_CHUNK_PROOF },
+				"Merkle proof, when flagged",
+			),
+			field("data", FieldKind::RestBytes, "Chunk bytes, raw DEFLATE when flagged compressed (0x02)"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::FileEnd,
+		name: "fileEnd",
+		doc: "All chunks of a transfer were sent",
+		fields: ID_ONLY,
+	},
+	FrameSchema {
+		frame_type: FrameType::FileOfferSealed,
+		name: "fileOfferSealed",
+		doc: "FileOffer with its metadata hidden until the receiver accepts; answered with fileAccept, then fileReveal, then a second fileAccept or fileReject",
+		fields: &[
+			field("id", FieldKind::String, "Transfer id"),
+			field("nonce", FieldKind::Bytes { len: ENVELOPE_NONCE_LEN }, "XChaCha20-Poly1305 nonce"),
+			field(
+				"ciphertext",
+				FieldKind::RestBytes,
+				"Ciphertext of the fileOffer fields after id, with the id as associated data",
+			),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::FileReveal,
+		name: "fileReveal",
+		doc: "Key that opens an accepted fileOfferSealed",
+		fields: &[
+			field("id", FieldKind::String, "Transfer id"),
+			field("key", FieldKind::Bytes { len: OFFER_KEY_LEN }, "Per-offer XChaCha20-Poly1305 key"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::AclUpdate,
+		name: "aclUpdate",
+		doc: "Signed permission change; the signature covers \"holi-acl-v1\" followed by every field before it",
+		fields: &[
+			field("projectId", FieldKind::String, "Project id"),
+			field("userId", FieldKind::String, "User the change applies to"),
+			field("action", FieldKind::Tagged { variants: ACL_ACTIONS }, "Grant or revoke"),
+			field("timestamp", FieldKind::VarintU64, "Author clock in ms"),
+			field("author", FieldKind::Bytes { len: ACL_AUTHOR_KEY_LEN }, "Ed25519 public key of the author"),
+			field("signature", FieldKind::Bytes { len: ACL_SIGNATURE_LEN }, "Ed25519 signature"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::ProjectInvite,
+		name: "projectInvite",
+		doc: "Signed invitation carrying the project key; the signature covers \"holi-invite-v1\" followed by every field before it",
+		fields: &[
+			field("projectId", FieldKind::String, "Project id"),
+			field("role", FieldKind::U8, "Offered role: 0 owner, 1 editor, 2 viewer"),
+			field("timestamp", FieldKind::VarintU64, "Inviter clock in ms"),
+			field("inviter", FieldKind::Bytes { len: ACL_AUTHOR_KEY_LEN }, "Ed25519 public key of the inviter"),
+			field("invitee", FieldKind::Bytes { len: ACL_AUTHOR_KEY_LEN }, "Ed25519 public key of the invitee"),
+			field(
+				"sealedKey",
+				FieldKind::Bytes { len: PROJECT_INVITE_SEALED_KEY_LEN },
+				"Project key sealed with HPKE (X25519, HKDF-SHA256, ChaCha20Poly1305) to the invitee",
+			),
+			field("signature", FieldKind::Bytes { len: ACL_SIGNATURE_LEN }, "Ed25519 signature"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::EncryptedEnvelope,
+		name: "encryptedEnvelope",
+		doc: "Encrypted inner frame",
+		fields: &[
+			field(
+				"cipherSuite",
+				FieldKind::IfFlag { mask: ENVELOPE_FLAG_CIPHER_SUITE, fields: ENVELOPE_SUITES },
+				"AEAD and nonce, when flagged",
+			),
+			field(
+				"nonce",
+				FieldKind::UnlessFlag {
+					mask: ENVELOPE_FLAG_CIPHER_SUITE,
+					fields: &[field("nonce", FieldKind::Bytes { len: ENVELOPE_NONCE_LEN }, "Nonce")],
+				},
+				"XChaCha20-Poly1305 nonce, when not flagged",
+			),
+			field("ciphertext", FieldKind::RestBytes, "Ciphertext of an encoded frame"),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::MultiEnvelope,
+		name: "multiEnvelope",
+		doc: "One frame sealed separately for each recipient; each peer opens its own encryptedEnvelope",
+		fields: &[field(
+			"entries",
+			FieldKind::Repeated {
+				fields: &[
+					field("recipient", FieldKind::String, "Peer id"),
+					field("envelope", FieldKind::PrefixedBytes, "encryptedEnvelope frame for that peer"),
+				],
+			},
+			"One copy per recipient",
+		)],
+	},
+	FrameSchema {
+		frame_type: FrameType::EphemeralEnvelope,
+		name: "ephemeralEnvelope",
+		doc: "Burn-after-reading chat message under a per-message ratcheted key",
+		fields: &[
+			field("index", FieldKind::VarintU64, "Position in the sender's key chain"),
+			field("ttlMs", FieldKind::VarintU64, "Milliseconds until both sides wipe the message"),
+			field("nonce", FieldKind::Bytes { len: ENVELOPE_NONCE_LEN }, "Random XChaCha20-Poly1305 nonce"),
+			field(
+				"ciphertext",
+				FieldKind::RestBytes,
+				"XChaCha20-Poly1305 of the UTF-8 text, index and ttlMs as associated data",
+			),
+		],
+	},
+	FrameSchema {
+		frame_type: FrameType::ProtocolError,
+		name: "protocolError",
+		doc: "Reserved for protocol errors; payload not yet specified",
+		fields: OPAQUE,
+	},
+];
+
+/// Payload layout of every frame type.
+pub fn frame_schemas() -> &'static [FrameSchema] {
+	FRAMES
+}
+
+/// Payload layout of one frame type; `None` only if `FRAMES` misses it.
+pub fn frame_schema(frame_type: FrameType) -> Option<&'static FrameSchema> {
+	FRAMES.iter().find(|s| s.frame_type == frame_type)
+}
+
+fn push_json_string(out: &mut String, value: &str) {
+	out.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
+
+fn push_kind(out: &mut String, kind: &FieldKind) {
+	match kind {
+		FieldKind::U8 => out.push_str("{\"type\":\"u8\"}"),
+		FieldKind::VarintU32 => out.push_str("{\"type\":\"varint32\"}"),
+		FieldKind::VarintU64 => out.push_str("{\"type\":\"varint64\"}"),
+		FieldKind::String => out.push_str("{\"type\":\"string\"}"),
+		FieldKind::Bytes { len } => out.push_str(&format!("{{\"type\":\"bytes\",\"length\":{}}}", len)),
+		FieldKind::RestUtf8 => out.push_str("{\"type\":\"restUtf8\"}"),
+		FieldKind::RestBytes => out.push_str("{\"type\":\"restBytes\"}"),
+		FieldKind::Tagged { variants } => {
+			out.push_str("{\"type\":\"tagged\",\"variants\":");
+			push_variants(out, variants);
+			out.push('}');
+		}
+		FieldKind::CountedBytes { item_len } => {
+			out.push_str(&format!("{{\"type\":\"countedBytes\",\"count\":\"u8\",\"itemLength\":{}}}", item_len))
+		}
+		FieldKind::PrefixedBytes => out.push_str("{\"type\":\"prefixedBytes\"}"),
+		FieldKind::Repeated { fields } => {
+			out.push_str("{\"type\":\"repeated\",\"count\":\"u8\",\"fields\":");
+			push_fields(out, fields);
+			out.push('}');
+		}
+		FieldKind::Optional { fields } => {
+			out.push_str("{\"type\":\"optional\",\"fields\":");
+			push_fields(out, fields);
+			out.push('}');
+		}
+		FieldKind::Extensions { variants } => {
+			out.push_str("{\"type\":\"extensions\",\"variants\":");
+			push_variants(out, variants);
+			out.push('}');
+		}
+		FieldKind::IfFlag { mask, fields } => {
+			out.push_str(&format!("{{\"type\":\"ifFlag\",\"mask\":{},\"fields\":", mask));
+			push_fields(out, fields);
+			out.push('}');
+		}
+		FieldKind::UnlessFlag { mask, fields } => {
+			out.push_str(&format!("{{\"type\":\"unlessFlag\",\"mask\":{},\"fields\":", mask));
+			push_fields(out, fields);
+			out.push('}');
+		}
+	}
+}
+
+fn push_variants(out: &mut String, variants: &[Variant]) {
+	out.push('[');
+	for (i, v) in variants.iter().enumerate() {
+		if i > 0 {
+			out.push(',');
+		}
+		out.push_str(&format!("{{\"tag\":{},\"name\":", v.tag));
+		push_json_string(out, v.name);
+		out.push_str(",\"fields\":");
+		push_fields(out, v.fields);
+		out.push('}');
+	}
+	out.push(']');
+}
+
+fn push_fields(out: &mut String, fields: &[Field]) {
+	out.push('[');
+	for (i, f) in fields.iter().enumerate() {
+		if i > 0 {
+			out.push(',');
+		}
+		out.push_str("{\"name\":");
+		push_json_string(out, f.name);
+		out.push_str(",\"kind\":");
+		push_kind(out, &f.kind);
+		out.push_str(",\"doc\":");
+		push_json_string(out, f.doc);
+		out.push('}');
+	}
+	out.push(']');
+}
+
+/// JSON description of the whole v1 format: header, varint/string rules and
+/// every frame's payload layout.
+pub fn schema_json() -> String {
+	let mut out = String::new();
+	out.push_str(&format!(
+		"{{\"version\":{},\"magic\":[{},{}],",
+		VERSION_V1, MAGIC[0], MAGIC[1]
+	));
+	out.push_str(
+		"\"varint\":{\"encoding\":\"unsigned LEB128\",\"bitsPerByte\":7,\"continuationBit\":128,\"maxBytes32\":5,\"maxBytes64\":10},",
+	);
+	out.push_str("\"string\":{\"length\":\"varint32\",\"encoding\":\"utf-8\"},");
+	out.push_str("\"header\":");
+	push_fields(&mut out, HEADER);
+	out.push_str(",\"frames\":[");
+	for (i, frame) in FRAMES.iter().enumerate() {
+		if i > 0 {
+			out.push(',');
+		}
+		out.push_str(&format!("{{\"type\":{},\"name\":", frame.frame_type as u8));
+		push_json_string(&mut out, frame.name);
+		out.push_str(",\"doc\":");
+		push_json_string(&mut out, frame.doc);
+		out.push_str(",\"fields\":");
+		push_fields(&mut out, frame.fields);
+		out.push('}');
+	}
+	out.push_str("]}");
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::frame::*;
+	use crate::varint::{decode_u32_varint, decode_u64_varint};
+
+	/// Walk `input` with `fields`, returning the bytes consumed.
+	fn walk(fields: &[Field], input: &[u8], flags: u8) -> Result<usize, DecodeError> {
+		let mut i = 0;
+		for f in fields {
+			let rest = input.get(i..).ok_or(DecodeError::UnexpectedEof)?;
+			i += match f.kind {
+				FieldKind::U8 => {
+					rest.first().ok_or(DecodeError::UnexpectedEof)?;
+					1
+				}
+				FieldKind::VarintU32 => decode_u32_varint(rest)?.1,
+				FieldKind::VarintU64 => decode_u64_varint(rest)?.1,
+				FieldKind::String => {
+					let (len, n) = decode_u32_varint(rest)?;
+					let end = n + len as usize;
+					let bytes = rest.get(n..end).ok_or(DecodeError::UnexpectedEof)?;
+					std::str::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8)?;
+					end
+				}
+				FieldKind::Bytes { len } => {
+					rest.get(..len).ok_or(DecodeError::UnexpectedEof)?;
+					len
+				}
+				FieldKind::RestUtf8 => {
+					std::str::from_utf8(rest).map_err(|_| DecodeError::InvalidUtf8)?;
+					rest.len()
+				}
+				FieldKind::RestBytes => rest.len(),
+				FieldKind::Tagged { variants } => {
+					let tag = *rest.first().ok_or(DecodeError::UnexpectedEof)?;
+					let variant = variants
+						.iter()
+						.find(|v| v.tag == tag)
+						.ok_or(DecodeError::BadAclUpdate)?;
+					1 + walk(variant.fields, &rest[1..], flags)?
+				}
+				FieldKind::CountedBytes { item_len } => {
+					let count = *rest.first().ok_or(DecodeError::UnexpectedEof)? as usize;
+					let len = 1 + count * item_len;
+					rest.get(..len).ok_or(DecodeError::UnexpectedEof)?;
+					len
+				}
+				FieldKind::PrefixedBytes => {
+					let (len, n) = decode_u32_varint(rest)?;
+					rest.get(n..n + len as usize).ok_or(DecodeError::UnexpectedEof)?;
+					n + len as usize
+				}
+				FieldKind::Repeated { fields } => {
+					let count = *rest.first().ok_or(DecodeError::UnexpectedEof)?;
+					let mut len = 1;
+					for _ in 0..count {
+						len += walk(fields, rest.get(len..).ok_or(DecodeError::UnexpectedEof)?, flags)?;
+					}
+					len
+				}
+				FieldKind::Optional { fields } if !rest.is_empty() => walk(fields, rest, flags)?,
+				FieldKind::Optional { .. } => 0,
+				FieldKind::Extensions { variants } => {
+					let mut len = 0;
+					while let [tag, n, ..] = rest[len..] {
+						let value = rest.get(len + 2..len + 2 + n as usize).ok_or(DecodeError::UnexpectedEof)?;
+						if let Some(variant) = variants.iter().find(|v| v.tag == tag) {
+							assert_eq!(walk(variant.fields, value, flags)?, value.len());
+						}
+						len += 2 + n as usize;
+					}
+					rest.len()
+				}
+				FieldKind::IfFlag { mask, fields } if flags & mask != 0 => walk(fields, rest, flags)?,
+				FieldKind::IfFlag { .. } => 0,
+				FieldKind::UnlessFlag { mask, fields } if flags & mask == 0 => walk(fields, rest, flags)?,
+				FieldKind::UnlessFlag { .. } => 0,
+			};
+		}
+		Ok(i)
+	}
+
+	fn check(bytes: &[u8]) {
+		let header_len = walk(HEADER, bytes, 0).unwrap();
+		let (frame, used) = decode_v1(bytes, u32::MAX).unwrap();
+		assert_eq!(header_len + frame.payload.len(), used);
+		let schema = frame_schema(frame.frame_type).unwrap();
+		assert_eq!(
+			walk(schema.fields, &frame.payload, frame.flags).unwrap(),
+			frame.payload.len(),
+			"{} layout does not match encoder",
+			schema.name
+		);
+	}
+
+	#[test]
+	fn every_frame_type_has_one_schema() {
+		for t in FrameType::ALL {
+			assert_eq!(FRAMES.iter().filter(|s| s.frame_type == t).count(), 1);
+		}
+		assert_eq!(FRAMES.len(), FrameType::ALL.len());
+		let known = (0..=255u8).filter(|v| FrameType::from_u8(*v).is_some()).count();
+		assert_eq!(known, FrameType::ALL.len());
+	}
+
+	#[test]
+	fn layouts_match_encoders() {
+		check(&encode_chat_text_v1("héllo"));
+		check(&encode_attributed_chat_text_v1(
+			"héllo",
+			&ChatAttribution {
+				sender: [1u8; CHAT_SENDER_KEY_LEN],
+				sequence: 1 << 20,
+				timestamp: 1_700_000_000_000,
+				signature: [2u8; CHAT_SIGNATURE_LEN],
+				bound: true,
+			},
+		));
+		check(&encode_file_offer_v1(&FileOffer {
+			id: "t1".into(),
+			filename: "a.txt".into(),
+			mime_type: "text/plain".into(),
+			size: 1 << 40,
+			merkle: None,
+			thumbnail: None,
+		}));
+		check(&encode_file_offer_v1(&FileOffer {
+			id: "t1".into(),
+			filename: "a.txt".into(),
+			mime_type: "text/plain".into(),
+			size: 3,
+			merkle: Some(MerkleRoot { leaf_size: 1 << 16, root: [1; MERKLE_HASH_LEN] }),
+			thumbnail: None,
+		}));
+		check(&encode_file_offer_v1(&FileOffer {
+			id: "t1".into(),
+			filename: "a.png".into(),
+			mime_type: "image/png".into(),
+			size: 3,
+			merkle: None,
+			thumbnail: Some(OfferThumbnail { nonce: [2; ENVELOPE_NONCE_LEN], ciphertext: vec![3; 200] }),
+		}));
+		check(&encode_file_accept_v1("t1"));
+		check(&encode_file_reject_v1(&FileReject {
+			id: "t1".into(),
+			code: RejectCode::QuotaExceeded,
+			detail: 1 << 40,
+			reason: "busy".into(),
+		}));
+		check(&encode_file_chunk_v1("t1", 300, &[1, 2, 3]));
+		check(&encode_file_chunk_with_proof_v1("t1", 2, &[[4; MERKLE_HASH_LEN]; 3], &[1, 2, 3]).unwrap());
+		check(&encode_file_chunk_compressed_v1("t1", 2, Some(&[[4; MERKLE_HASH_LEN]; 3]), &[0; 300]).unwrap());
+		check(&encode_file_end_v1("t1"));
+		check(&encode_goodbye_v1(&Goodbye { reason: GoodbyeReason::Idle, resume_ticket: Vec::new() }));
+		check(&encode_goodbye_v1(&Goodbye { reason: GoodbyeReason::Restarting, resume_ticket: vec![1; 16] }));
+		check(&encode_file_offer_sealed_v1(&SealedFileOffer {
+			id: "t1".into(),
+			nonce: [5; ENVELOPE_NONCE_LEN],
+			ciphertext: vec![9; 40],
+		}));
+		check(&encode_file_reveal_v1(&FileReveal {
+			id: "t1".into(),
+			key: [6; OFFER_KEY_LEN],
+		}));
+		check(&encode_encrypted_envelope_v1(&[7; ENVELOPE_NONCE_LEN], b"ct"));
+		check(&encode_encrypted_envelope_with_suite_v1(
+			CipherSuite::Aes256Gcm,
+			&[7; ENVELOPE_AES_GCM_NONCE_LEN],
+			b"ct",
+		)
+		.unwrap());
+		check(&encode_hello_v1(&Hello { capabilities: HELLO_CAP_AES_256_GCM, max_chunk_size: None }));
+		check(&encode_hello_v1(&Hello { capabilities: HELLO_CAP_ANIMATED_QR, max_chunk_size: Some(1 << 20) }));
+		check(&encode_multi_envelope_v1(&[
+			MultiEnvelopeEntry { recipient: "a".into(), envelope: vec![1; 30] },
+			MultiEnvelopeEntry { recipient: "bé".into(), envelope: vec![] },
+		]));
+		check(&encode_ephemeral_envelope_v1(&EphemeralEnvelope {
+			index: 1 << 40,
+			ttl_ms: 30_000,
+			nonce: [4; ENVELOPE_NONCE_LEN],
+			ciphertext: vec![3; 17],
+		}));
+		let rekey = Rekey { epoch: 7, public_key: [8; REKEY_PUBLIC_KEY_LEN] };
+		check(&encode_rekey_request_v1(&rekey));
+		check(&encode_rekey_response_v1(&rekey));
+		for action in [
+			AclAction::Grant { role: 1, expires_at: Some(99) },
+			AclAction::Grant { role: 2, expires_at: None },
+			AclAction::Revoke,
+		] {
+			check(&encode_acl_update_v1(&AclUpdate {
+				project_id: "p".into(),
+				user_id: "u".into(),
+				action,
+				timestamp: 12345,
+				author: [1; ACL_AUTHOR_KEY_LEN],
+				signature: [2; ACL_SIGNATURE_LEN],
+			}));
+		}
+		check(&encode_project_invite_v1(&ProjectInvite {
+			project_id: "p".into(),
+			role: 2,
+			timestamp: 12345,
+			inviter: [1; ACL_AUTHOR_KEY_LEN],
+			invitee: [3; ACL_AUTHOR_KEY_LEN],
+			sealed_key: [4; PROJECT_INVITE_SEALED_KEY_LEN],
+			signature: [2; ACL_SIGNATURE_LEN],
+		}));
+	}
+
+	#[test]
+	fn json_lists_every_frame() {
+		let json = schema_json();
+		assert!(json.starts_with("{\"version\":1,"));
+		for s in FRAMES {
+			assert!(json.contains(&format!("{{\"type\":{},\"name\":\"{}\"", s.frame_type as u8, s.name)));
+		}
+		assert!(json.contains("\\\"holi-acl-v1\\\""));
+		assert_eq!(json.matches('{').count(), json.matches('}').count());
+	}
+}
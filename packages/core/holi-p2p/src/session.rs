@@ -0,0 +1,64 @@
+//! How a session ended.
+//!
+//! A peer that leaves on purpose sends a Goodbye frame right before closing
+//! the channel; a channel that closes without one was lost (crash, network
+//! drop, heartbeat timeout). Keep the last Goodbye received and pass it to
+//! `SessionEnd::on_close` when the channel closes, so the UI can show "peer
+//! left" instead of retrying, and retry only when that makes sense.
+
+use crate::frame::{Goodbye, GoodbyeReason};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEnd {
+	/// The peer said goodbye before the channel closed.
+	PeerLeft(Goodbye),
+	/// The channel closed without a goodbye.
+	ConnectionLost,
+}
+
+impl SessionEnd {
+	/// End of a session whose channel just closed, given the Goodbye
+	/// received on it, if any.
+	pub fn on_close(goodbye: Option<Goodbye>) -> Self {
+		goodbye.map_or(Self::ConnectionLost, Self::PeerLeft)
+	}
+
+	/// Whether to reconnect: after a lost connection, or when the peer
+	/// left saying it is restarting.
+	pub fn should_reconnect(&self) -> bool {
+		match self {
+			Self::ConnectionLost => true,
+			Self::PeerLeft(goodbye) => goodbye.reason == GoodbyeReason::Restarting,
+		}
+	}
+
+	/// Name used by the wasm bindings: `"peer-left"` or `"connection-lost"`.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::PeerLeft(_) => "peer-left",
+			Self::ConnectionLost => "connection-lost",
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tells_leaving_from_losing() {
+		let lost = SessionEnd::on_close(None);
+		assert_eq!(lost, SessionEnd::ConnectionLost);
+		assert!(lost.should_reconnect());
+
+		let left = SessionEnd::on_close(Some(Goodbye { reason: GoodbyeReason::UserLeft, resume_ticket: Vec::new() }));
+		assert_eq!(left.name(), "peer-left");
+		assert!(!left.should_reconnect());
+
+		let restarting = SessionEnd::on_close(Some(Goodbye {
+			reason: GoodbyeReason::Restarting,
+			resume_ticket: vec![1, 2, 3],
+		}));
+		assert!(restarting.should_reconnect());
+	}
+}
@@ -0,0 +1,414 @@
+//! Receiving-side checks on what a transferred file really is.
+//!
+//! A FileOffer's filename and MIME type are whatever the sender typed. A
+//! "photo.jpg" that is really HTML becomes a script-running page if the
+//! browser is handed a `text/html` blob or the user opens the saved file.
+//! `check_content` compares the offer with magic bytes at the start of the
+//! data and `ContentCheck::blob_type` picks a type that is safe to hand the
+//! browser; `sanitize_filename` makes the name safe to save under.
+
+/// Bytes of the file start looked at by `sniff_mime`.
+pub const SNIFF_LEN: usize = 512;
+/// Longest sanitized filename, in bytes.
+pub const MAX_FILENAME_LEN: usize = 255;
+/// Used for data that is suspicious or of unknown type.
+pub const FALLBACK_MIME: &str = "application/octet-stream";
+/// Used when nothing of the offered name survives sanitizing.
+pub const FALLBACK_FILENAME: &str = "download";
+
+/// A file type recognised by its signature.
+struct Kind {
+	mime: &'static str,
+	extensions: &'static [&'static str],
+	/// Other MIME types senders use for the same data. A trailing '.' matches
+	/// any type with that prefix.
+	aliases: &'static [&'static str],
+	/// Runs script or native code when opened.
+	active: bool,
+	/// Sniffed from text, so a miss doesn't prove the data is something else.
+	textual: bool,
+}
+
+const fn kind(
+	mime: &'static str,
+	extensions: &'static [&'static str],
+	aliases: &'static [&'static str],
+) -> Kind {
+	Kind { mime, extensions, aliases, active: false, textual: false }
+}
+
+const fn active(
+	mime: &'static str,
+	extensions: &'static [&'static str],
+	aliases: &'static [&'static str],
+) -> Kind {
+	Kind { mime, extensions, aliases, active: true, textual: false }
+}
+
+const fn markup(
+	mime: &'static str,
+	extensions: &'static [&'static str],
+	aliases: &'static [&'static str],
+) -> Kind {
+	Kind { mime, extensions, aliases, active: true, textual: true }
+}
+
+const KINDS: &[Kind] = &[
+	kind("image/jpeg", &["jpg", "jpeg", "jpe", "jfif"], &["image/pjpeg"]),
+	kind("image/png", &["png"], &[]),
+	kind("image/gif", &["gif"], &[]),
+	kind("image/webp", &["webp"], &[]),
+	kind("image/bmp", &["bmp"], &["image/x-ms-bmp"]),
+	kind("image/x-icon", &["ico"], &["image/vnd.microsoft.icon"]),
+	kind("image/avif", &["avif"], &[]),
+	kind("image/heic", &["heic", "heif"], &["image/heif"]),
+	kind("video/mp4", &["mp4", "m4v", "m4a", "mov"], &["audio/mp4", "audio/x-m4a", "video/quicktime"]),
+	kind("video/webm", &["webm", "mkv"], &["audio/webm", "video/x-matroska"]),
+	kind("audio/ogg", &["ogg", "oga", "ogv", "opus"], &["video/ogg", "audio/opus", "application/ogg"]),
+	kind("audio/mpeg", &["mp3"], &["audio/mp3"]),
+	kind("audio/flac", &["flac"], &["audio/x-flac"]),
+	kind("audio/wav", &["wav"], &["audio/x-wav", "audio/wave", "audio/vnd.wave"]),
+	kind("application/pdf", &["pdf"], &[]),
+	kind(
+		"application/zip",
+		&["zip", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub", "jar", "apk"],
+		&[
+			"application/x-zip-compressed",
+			"application/epub+zip",
+			"application/java-archive",
+			"application/vnd.android.package-archive",
+			"application/vnd.openxmlformats-officedocument.",
+			"application/vnd.oasis.opendocument.",
+		],
+	),
+	kind("application/gzip", &["gz", "tgz"], &["application/x-gzip"]),
+	kind("application/zstd", &["zst"], &[]),
+	kind("application/x-7z-compressed", &["7z"], &[]),
+	kind("application/vnd.rar", &["rar"], &["application/x-rar-compressed"]),
+	kind("application/x-bzip2", &["bz2"], &[]),
+	kind("application/x-xz", &["xz"], &[]),
+	kind("font/woff", &["woff"], &[]),
+	kind("font/woff2", &["woff2"], &[]),
+	kind("application/wasm", &["wasm"], &[]),
+	active(
+		"application/x-msdownload",
+		&["exe", "dll", "scr", "com", "sys", "cpl"],
+		&["application/vnd.microsoft.portable-executable", "application/x-dosexec"],
+	),
+	active("application/x-executable", &["elf", "so"], &["application/x-elf", "application/x-sharedlib"]),
+	active("application/x-mach-binary", &["dylib"], &[]),
+	markup("text/html", &["html", "htm", "shtml"], &[]),
+	markup("image/svg+xml", &["svg"], &[]),
+	markup("application/xhtml+xml", &["xhtml", "xht"], &[]),
+	markup("application/xml", &["xml", "xsl", "xslt"], &["text/xml"]),
+];
+
+/// Script and shortcut types with no signature to sniff, active by name.
+const ACTIVE_EXTENSIONS: &[&str] = &[
+	"js", "mjs", "hta", "bat", "cmd", "ps1", "vbs", "vbe", "jse", "wsf", "wsh", "lnk", "msi", "reg", "url",
+	"desktop", "sh", "command", "app",
+];
+const ACTIVE_MIMES: &[&str] = &["text/javascript", "application/javascript", "application/x-javascript"];
+
+/// Signature prefixes, tried in order.
+const MAGIC: &[(&[u8], &str)] = &[
+	(b"\xff\xd8\xff", "image/jpeg"),
+	(b"\x89PNG\r\n\x1a\n", "image/png"),
+	(b"GIF87a", "image/gif"),
+	(b"GIF89a", "image/gif"),
+	(b"\x00\x00\x01\x00", "image/x-icon"),
+	(b"\x1a\x45\xdf\xa3", "video/webm"),
+	(b"OggS", "audio/ogg"),
+	(b"ID3", "audio/mpeg"),
+	(b"fLaC", "audio/flac"),
+	(b"%PDF-", "application/pdf"),
+	(b"PK\x03\x04", "application/zip"),
+	(b"PK\x05\x06", "application/zip"),
+	(b"\x1f\x8b", "application/gzip"),
+	(b"\x28\xb5\x2f\xfd", "application/zstd"),
+	(b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+	(b"Rar!\x1a\x07", "application/vnd.rar"),
+	(b"BZh", "application/x-bzip2"),
+	(b"\xfd7zXZ\x00", "application/x-xz"),
+	(b"wOFF", "font/woff"),
+	(b"wOF2", "font/woff2"),
+	(b"\x00asm", "application/wasm"),
+	(b"MZ", "application/x-msdownload"),
+	(b"\x7fELF", "application/x-executable"),
+	(b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+	(b"\xce\xfa\xed\xfe", "application/x-mach-binary"),
+	(b"BM", "image/bmp"),
+];
+
+/// Tags that make a browser sniff text as HTML (as in the WHATWG MIME
+/// sniffing spec), lowercase.
+const HTML_TAGS: &[&[u8]] = &[
+	b"<!doctype html", b"<html", b"<head", b"<body", b"<script", b"<iframe", b"<h1", b"<div", b"<font",
+	b"<table", b"<a", b"<style", b"<title", b"<b", b"<br", b"<p", b"<!--",
+];
+
+fn starts_with_ignore_case(data: &[u8], prefix: &[u8]) -> bool {
+	data.len() >= prefix.len() && data[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn contains_ignore_case(data: &[u8], needle: &[u8]) -> bool {
+	data.windows(needle.len()).any(|w| w.eq_ignore_ascii_case(needle))
+}
+
+fn sniff_markup(data: &[u8]) -> Option<&'static str> {
+	let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+	let start = data.iter().position(|b| !b.is_ascii_whitespace())?;
+	let text = &data[start..];
+	if starts_with_ignore_case(text, b"<svg") {
+		return Some("image/svg+xml");
+	}
+	if starts_with_ignore_case(text, b"<?xml") {
+		return Some(if contains_ignore_case(text, b"<svg") {
+			"image/svg+xml"
+		} else if contains_ignore_case(text, b"<html") {
+			"application/xhtml+xml"
+		} else {
+			"application/xml"
+		});
+	}
+	let is_html = HTML_TAGS.iter().any(|tag| {
+		starts_with_ignore_case(text, tag)
+			&& (tag == b"<!--" || matches!(text.get(tag.len()), Some(b' ' | b'>' | b'\t' | b'\n' | b'\r')))
+	});
+	is_html.then_some("text/html")
+}
+
+/// MIME type of `data` from its signature, or `None` if unrecognised. Only
+/// the first `SNIFF_LEN` bytes are used.
+pub fn sniff_mime(data: &[u8]) -> Option<&'static str> {
+	let data = &data[..data.len().min(SNIFF_LEN)];
+	if data.len() >= 12 && &data[..4] == b"RIFF" {
+		match &data[8..12] {
+			b"WEBP" => return Some("image/webp"),
+			b"WAVE" => return Some("audio/wav"),
+			_ => {}
+		}
+	}
+	// ISO BMFF: "ftyp" box at offset 4, major brand after it
+	if data.len() >= 12 && &data[4..8] == b"ftyp" {
+		return Some(match &data[8..12] {
+			b"avif" | b"avis" => "image/avif",
+			b"heic" | b"heix" | b"mif1" | b"msf1" => "image/heic",
+			_ => "video/mp4",
+		});
+	}
+	// MPEG audio frame sync without an ID3 tag
+	if data.len() >= 2 && data[0] == 0xff && matches!(data[1], 0xfb | 0xf3 | 0xf2) {
+		return Some("audio/mpeg");
+	}
+	MAGIC
+		.iter()
+		.find(|(magic, _)| data.starts_with(magic))
+		.map(|(_, mime)| *mime)
+		.or_else(|| sniff_markup(data))
+}
+
+/// Lowercase extension of `filename`, without the dot.
+pub fn extension(filename: &str) -> Option<String> {
+	let (stem, ext) = filename.rsplit_once('.')?;
+	(!stem.is_empty() && !ext.is_empty()).then(|| ext.to_ascii_lowercase())
+}
+
+fn essence(mime_type: &str) -> String {
+	mime_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase()
+}
+
+fn kind_by_mime(mime: &str) -> Option<&'static Kind> {
+	KINDS.iter().find(|k| k.mime == mime)
+}
+
+fn kind_by_claim(mime_type: &str) -> Option<&'static Kind> {
+	let mime = essence(mime_type);
+	KINDS.iter().find(|k| {
+		k.mime == mime
+			|| k.aliases.iter().any(|a| if a.ends_with('.') { mime.starts_with(a) } else { mime == *a })
+	})
+}
+
+fn kind_by_extension(ext: &str) -> Option<&'static Kind> {
+	KINDS.iter().find(|k| k.extensions.contains(&ext))
+}
+
+/// Result of `check_content`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentCheck {
+	/// Type found from the data's signature, if recognised.
+	pub detected: Option<&'static str>,
+	/// The data doesn't match the offered extension or MIME type.
+	pub mismatch: bool,
+	/// The file can run script or code if opened: by its content, its
+	/// extension or its offered type.
+	pub active: bool,
+}
+
+impl ContentCheck {
+	pub fn is_suspicious(&self) -> bool {
+		self.mismatch || self.active
+	}
+
+	/// Type to create the received blob with: `FALLBACK_MIME` for anything
+	/// suspicious, else the detected type or, if none, the offered one.
+	pub fn blob_type<'a>(&self, offered_mime: &'a str) -> &'a str {
+		if self.is_suspicious() {
+			return FALLBACK_MIME;
+		}
+		match self.detected {
+			Some(mime) => mime,
+			None if offered_mime.trim().is_empty() => FALLBACK_MIME,
+			None => offered_mime,
+		}
+	}
+}
+
+/// Compare an offer's `filename` and `mime_type` with `sample`, the start of
+/// the received data.
+pub fn check_content(filename: &str, mime_type: &str, sample: &[u8]) -> ContentCheck {
+	let detected = sniff_mime(sample);
+	let ext = extension(&sanitize_filename(filename));
+	let claimed = [ext.as_deref().and_then(kind_by_extension), kind_by_claim(mime_type)];
+	let mismatch = claimed.iter().flatten().any(|expected| match detected {
+		Some(mime) => expected.mime != mime,
+		// Markup may start with anything; a missing binary signature is proof
+		None => !expected.textual,
+	});
+	let active = detected.and_then(kind_by_mime).is_some_and(|k| k.active)
+		|| claimed.iter().flatten().any(|k| k.active)
+		|| ext.as_deref().is_some_and(|e| ACTIVE_EXTENSIONS.contains(&e))
+		|| ACTIVE_MIMES.contains(&essence(mime_type).as_str());
+	ContentCheck { detected, mismatch, active }
+}
+
+/// Device names Windows won't create files under, with any extension.
+const RESERVED_NAMES: &[&str] = &[
+	"con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9", "lpt1",
+	"lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Characters dropped from filenames: path and shell specials, and invisible
+/// or direction-changing ones that disguise the real extension
+/// ("photo\u{202e}gpj.exe" shows as "photoexe.jpg").
+fn is_unsafe_char(c: char) -> bool {
+	c.is_control()
+		|| matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+		|| matches!(c, '\u{200b}'..='\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}' | '\u{feff}')
+}
+
+/// A filename safe to save a received file under: no directories, no
+/// hidden-file or trailing dots, no unsafe characters, not a reserved device
+/// name, at most `MAX_FILENAME_LEN` bytes with the extension kept.
+pub fn sanitize_filename(filename: &str) -> String {
+	let base = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+	let cleaned: String = base.chars().filter(|&c| !is_unsafe_char(c)).collect();
+	let mut name = cleaned.trim_matches(|c: char| c == '.' || c.is_whitespace()).to_string();
+	if name.is_empty() {
+		return FALLBACK_FILENAME.to_string();
+	}
+	let stem = name.split('.').next().unwrap_or_default().trim_end().to_ascii_lowercase();
+	if RESERVED_NAMES.contains(&stem.as_str()) {
+		name.insert(0, '_');
+	}
+	if name.len() > MAX_FILENAME_LEN {
+		let ext = match name.rsplit_once('.') {
+			Some((_, ext)) if ext.len() < 16 => format!(".{ext}"),
+			_ => String::new(),
+		};
+		let mut end = MAX_FILENAME_LEN - ext.len();
+		while !name.is_char_boundary(end) {
+			end -= 1;
+		}
+		name = format!("{}{ext}", &name[..end]);
+	}
+	name
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const JPEG: &[u8] = b"\xff\xd8\xff\xe0\x00\x10JFIF\x00";
+	const HTML: &[u8] = b"\xef\xbb\xbf\n  <!DOCTYPE html><script>alert(1)</script>";
+
+	#[test]
+	fn sniffs_signatures() {
+		assert_eq!(sniff_mime(JPEG), Some("image/jpeg"));
+		assert_eq!(sniff_mime(b"\x00\x00\x00\x20ftypisom\x00\x00"), Some("video/mp4"));
+		assert_eq!(sniff_mime(b"\x00\x00\x00\x1cftypavif\x00\x00"), Some("image/avif"));
+		assert_eq!(sniff_mime(b"RIFF\x00\x00\x00\x00WAVEfmt "), Some("audio/wav"));
+		assert_eq!(sniff_mime(b"MZ\x90\x00"), Some("application/x-msdownload"));
+		assert_eq!(sniff_mime(HTML), Some("text/html"));
+		assert_eq!(sniff_mime(b"<html>"), Some("text/html"));
+		assert_eq!(sniff_mime(b"<?xml version=\"1.0\"?><svg xmlns=\"\">"), Some("image/svg+xml"));
+		assert_eq!(sniff_mime(b"<abbr>not html"), None);
+		assert_eq!(sniff_mime(b"plain text"), None);
+		assert_eq!(sniff_mime(&[]), None);
+	}
+
+	#[test]
+	fn html_posing_as_photo_is_flagged() {
+		let check = check_content("photo.jpg", "image/jpeg", HTML);
+		assert_eq!(check.detected, Some("text/html"));
+		assert!(check.mismatch && check.active);
+		assert_eq!(check.blob_type("image/jpeg"), FALLBACK_MIME);
+	}
+
+	#[test]
+	fn matching_files_pass() {
+		let check = check_content("photo.JPG", "image/jpeg", JPEG);
+		assert!(!check.is_suspicious());
+		assert_eq!(check.blob_type("image/jpeg"), "image/jpeg");
+
+		let docx = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+		assert!(!check_content("report.docx", docx, b"PK\x03\x04rest").is_suspicious());
+
+		// No signature to check, nothing claimed that has one
+		let check = check_content("notes.txt", "text/plain", b"hello");
+		assert!(!check.is_suspicious());
+		assert_eq!(check.blob_type("text/plain"), "text/plain");
+		assert_eq!(check.blob_type(""), FALLBACK_MIME);
+	}
+
+	#[test]
+	fn missing_or_wrong_signature_is_a_mismatch() {
+		assert!(check_content("photo.jpg", "", b"plain text").mismatch);
+		assert!(check_content("photo", "image/png", JPEG).mismatch);
+		assert!(check_content("song.mp3", "audio/mpeg", b"\x89PNG\r\n\x1a\n").mismatch);
+	}
+
+	#[test]
+	fn active_by_name_or_type() {
+		assert!(check_content("setup.exe", "", b"MZ\x90\x00").active);
+		assert!(check_content("run.js", "text/plain", b"alert(1)").active);
+		assert!(check_content("x", "application/javascript", b"alert(1)").active);
+		assert!(check_content("page.html", "text/html", b"hi").active);
+		assert!(!check_content("page.html", "text/html", b"hi").mismatch);
+	}
+
+	#[test]
+	fn sanitizes_filenames() {
+		assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+		assert_eq!(sanitize_filename("C:\\Users\\me\\evil.bat"), "evil.bat");
+		assert_eq!(sanitize_filename(".bashrc"), "bashrc");
+		assert_eq!(sanitize_filename("photo\u{202e}gpj.exe"), "photogpj.exe");
+		assert_eq!(sanitize_filename("a<b>:c\"d|e?f*.txt\u{0}"), "abcdef.txt");
+		assert_eq!(sanitize_filename("CON.txt"), "_CON.txt");
+		assert_eq!(sanitize_filename("trailing. . "), "trailing");
+		assert_eq!(sanitize_filename("/../"), FALLBACK_FILENAME);
+
+		let long = format!("{}.pdf", "é".repeat(200));
+		let name = sanitize_filename(&long);
+		assert!(name.len() <= MAX_FILENAME_LEN);
+		assert!(name.ends_with(".pdf"));
+	}
+
+	#[test]
+	fn extension_ignores_dotfiles() {
+		assert_eq!(extension("a.tar.GZ").as_deref(), Some("gz"));
+		assert_eq!(extension(".hidden"), None);
+		assert_eq!(extension("noext"), None);
+	}
+}
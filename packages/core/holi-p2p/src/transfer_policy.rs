@@ -0,0 +1,279 @@
+//! Abuse limits on incoming file transfers.
+//!
+//! `TransferLimiter` sits where FileOffer and FileChunk frames arrive and
+//! decides, per peer, whether a transfer may go ahead under a
+//! `TransferPolicy`. Refusals are `RejectReason`s, sent as a FileReject
+//! with the matching `RejectCode` and the limit as its detail.
+
+use std::collections::BTreeMap;
+
+use crate::frame::{encode_file_reject_v1, FileOffer, FileReject, RejectCode};
+
+/// Length of the per-peer byte quota window.
+pub const QUOTA_WINDOW_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Limits on incoming transfers; `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferPolicy {
+	/// Largest file a peer may offer, in bytes.
+	pub max_file_size: Option<u64>,
+	/// Transfers in progress at once, across all peers.
+	pub max_concurrent: Option<usize>,
+	/// Bytes one peer may send in `QUOTA_WINDOW_MS`, counted when offers
+	/// are admitted.
+	pub per_peer_daily_bytes: Option<u64>,
+}
+
+/// Why `TransferLimiter` refused a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+	/// The file is over `max_file_size` (or sent more than it offered).
+	TooLarge { max: u64 },
+	/// `max_concurrent` transfers are running.
+	Busy { max: usize },
+	/// The peer's daily quota would be exceeded; it resets in `retry_after_ms`.
+	QuotaExceeded { retry_after_ms: u64 },
+}
+
+impl RejectReason {
+	pub fn code(&self) -> RejectCode {
+		match self {
+			Self::TooLarge { .. } => RejectCode::TooLarge,
+			Self::Busy { .. } => RejectCode::Busy,
+			Self::QuotaExceeded { .. } => RejectCode::QuotaExceeded,
+		}
+	}
+
+	/// FileReject detail for this reason.
+	pub fn detail(&self) -> u64 {
+		match *self {
+			Self::TooLarge { max } => max,
+			Self::Busy { max } => max as u64,
+			Self::QuotaExceeded { retry_after_ms } => retry_after_ms,
+		}
+	}
+
+	/// The limit a received FileReject reports, if it is one of these.
+	pub fn from_reject(reject: &FileReject) -> Option<Self> {
+		Some(match reject.code {
+			RejectCode::TooLarge => Self::TooLarge { max: reject.detail },
+			RejectCode::Busy => Self::Busy { max: reject.detail as usize },
+			RejectCode::QuotaExceeded => Self::QuotaExceeded { retry_after_ms: reject.detail },
+			_ => return None,
+		})
+	}
+}
+
+/// FileReject frame refusing transfer `id` for `reason`.
+pub fn encode_reject(id: &str, reason: &RejectReason) -> Vec<u8> {
+	encode_file_reject_v1(&FileReject {
+		id: id.to_string(),
+		code: reason.code(),
+		detail: reason.detail(),
+		reason: String::new(),
+	})
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveTransfer {
+	offered: u64,
+	received: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PeerUsage {
+	window_start: u64,
+	bytes: u64,
+}
+
+/// Enforces a `TransferPolicy` on incoming transfers, keyed by peer and
+/// transfer id.
+#[derive(Debug, Default)]
+pub struct TransferLimiter {
+	policy: TransferPolicy,
+	active: BTreeMap<(String, String), ActiveTransfer>,
+	usage: BTreeMap<String, PeerUsage>,
+}
+
+impl TransferLimiter {
+	pub fn new(policy: TransferPolicy) -> Self {
+		Self {
+			policy,
+			..Self::default()
+		}
+	}
+
+	pub fn policy(&self) -> &TransferPolicy {
+		&self.policy
+	}
+
+	/// Applies to offers admitted from now on; running transfers continue.
+	pub fn set_policy(&mut self, policy: TransferPolicy) {
+		self.policy = policy;
+	}
+
+	/// Transfers admitted and not yet finished.
+	pub fn active_count(&self) -> usize {
+		self.active.len()
+	}
+
+	/// Bytes `peer` has been admitted in the current quota window.
+	pub fn peer_bytes(&self, peer: &str, now_ms: u64) -> u64 {
+		match self.usage.get(peer) {
+			Some(u) if now_ms.saturating_sub(u.window_start) < QUOTA_WINDOW_MS => u.bytes,
+			_ => 0,
+		}
+	}
+
+	/// Check an offer from `peer` received at `now_ms`. On `Ok` the
+	/// transfer counts as running, and its size against the peer's quota,
+	/// until `finish`. A repeated offer with the same id is admitted again
+	/// without counting twice.
+	pub fn admit(&mut self, peer: &str, offer: &FileOffer, now_ms: u64) -> Result<(), RejectReason> {
+		let key = (peer.to_string(), offer.id.clone());
+		if self.active.contains_key(&key) {
+			return Ok(());
+		}
+		if let Some(max) = self.policy.max_file_size {
+			if offer.size > max {
+				return Err(RejectReason::TooLarge { max });
+			}
+		}
+		if let Some(max) = self.policy.max_concurrent {
+			if self.active.len() >= max {
+				return Err(RejectReason::Busy { max });
+			}
+		}
+		let usage = self.usage.entry(peer.to_string()).or_insert(PeerUsage {
+			window_start: now_ms,
+			bytes: 0,
+		});
+		if now_ms.saturating_sub(usage.window_start) >= QUOTA_WINDOW_MS {
+			*usage = PeerUsage {
+				window_start: now_ms,
+				bytes: 0,
+			};
+		}
+		if let Some(max) = self.policy.per_peer_daily_bytes {
+			if usage.bytes.saturating_add(offer.size) > max {
+				let resets_at = usage.window_start + QUOTA_WINDOW_MS;
+				return Err(RejectReason::QuotaExceeded {
+					retry_after_ms: resets_at.saturating_sub(now_ms),
+				});
+			}
+		}
+		usage.bytes = usage.bytes.saturating_add(offer.size);
+		self.active.insert(
+			key,
+			ActiveTransfer {
+				offered: offer.size,
+				received: 0,
+			},
+		);
+		Ok(())
+	}
+
+	/// Count `len` chunk bytes of transfer `id` from `peer`. Fails once the
+	/// peer sends more than it offered, or for a transfer never admitted
+	/// (reported as a zero-byte limit); the caller then rejects it and
+	/// calls `finish`.
+	pub fn record_chunk(&mut self, peer: &str, id: &str, len: usize) -> Result<(), RejectReason> {
+		let transfer = self
+			.active
+			.get_mut(&(peer.to_string(), id.to_string()))
+			.ok_or(RejectReason::TooLarge { max: 0 })?;
+		transfer.received = transfer.received.saturating_add(len as u64);
+		if transfer.received > transfer.offered {
+			return Err(RejectReason::TooLarge { max: transfer.offered });
+		}
+		Ok(())
+	}
+
+	/// Transfer `id` from `peer` ended (completed, rejected or dropped). Its
+	/// bytes stay counted against the quota.
+	pub fn finish(&mut self, peer: &str, id: &str) {
+		self.active.remove(&(peer.to_string(), id.to_string()));
+	}
+
+	/// Forget running transfers from `peer`, e.g. when it disconnects.
+	pub fn finish_peer(&mut self, peer: &str) {
+		self.active.retain(|(p, _), _| p != peer);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn offer(id: &str, size: u64) -> FileOffer {
+		FileOffer {
+			id: id.to_string(),
+			filename: format!("{id}.bin"),
+			mime_type: "application/octet-stream".to_string(),
+			size,
+			merkle: None,
+			thumbnail: None,
+		}
+	}
+
+	#[test]
+	fn reasons_roundtrip_through_reject_frames() {
+		for reason in [
+			RejectReason::TooLarge { max: 1 << 30 },
+			RejectReason::Busy { max: 3 },
+			RejectReason::QuotaExceeded { retry_after_ms: 5000 },
+		] {
+			let (frame, _used) = crate::frame::decode_v1(&encode_reject("f1", &reason), 1024).unwrap();
+			let reject = crate::frame::decode_file_reject_payload_v1(&frame.payload).unwrap();
+			assert_eq!(reject.id, "f1");
+			assert_eq!(RejectReason::from_reject(&reject), Some(reason));
+		}
+	}
+
+	#[test]
+	fn size_and_concurrency_limits() {
+		let mut limiter = TransferLimiter::new(TransferPolicy {
+			max_file_size: Some(100),
+			max_concurrent: Some(1),
+			per_peer_daily_bytes: None,
+		});
+		assert_eq!(limiter.admit("a", &offer("big", 101), 0), Err(RejectReason::TooLarge { max: 100 }));
+		assert_eq!(limiter.admit("a", &offer("f1", 100), 0), Ok(()));
+		// Same offer again is not a second transfer
+		assert_eq!(limiter.admit("a", &offer("f1", 100), 0), Ok(()));
+		assert_eq!(limiter.admit("b", &offer("f2", 10), 0), Err(RejectReason::Busy { max: 1 }));
+		limiter.finish("a", "f1");
+		assert_eq!(limiter.admit("b", &offer("f2", 10), 0), Ok(()));
+		limiter.finish_peer("b");
+		assert_eq!(limiter.active_count(), 0);
+	}
+
+	#[test]
+	fn daily_quota_per_peer_resets() {
+		let mut limiter = TransferLimiter::new(TransferPolicy {
+			per_peer_daily_bytes: Some(1000),
+			..TransferPolicy::default()
+		});
+		assert_eq!(limiter.admit("a", &offer("f1", 600), 1000), Ok(()));
+		limiter.finish("a", "f1");
+		assert_eq!(
+			limiter.admit("a", &offer("f2", 600), 2000),
+			Err(RejectReason::QuotaExceeded { retry_after_ms: QUOTA_WINDOW_MS - 1000 })
+		);
+		// Other peers have their own quota
+		assert_eq!(limiter.admit("b", &offer("f2", 600), 2000), Ok(()));
+		assert_eq!(limiter.peer_bytes("a", 2000), 600);
+		assert_eq!(limiter.admit("a", &offer("f2", 600), 1000 + QUOTA_WINDOW_MS), Ok(()));
+		assert_eq!(limiter.peer_bytes("a", 1000 + QUOTA_WINDOW_MS), 600);
+	}
+
+	#[test]
+	fn chunks_past_the_offered_size_are_refused() {
+		let mut limiter = TransferLimiter::default();
+		assert_eq!(limiter.record_chunk("a", "f1", 1), Err(RejectReason::TooLarge { max: 0 }));
+		limiter.admit("a", &offer("f1", 10), 0).unwrap();
+		assert_eq!(limiter.record_chunk("a", "f1", 6), Ok(()));
+		assert_eq!(limiter.record_chunk("a", "f1", 4), Ok(()));
+		assert_eq!(limiter.record_chunk("a", "f1", 1), Err(RejectReason::TooLarge { max: 10 }));
+	}
+}
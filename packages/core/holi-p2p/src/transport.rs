@@ -0,0 +1,142 @@
+//! Byte transports that frames travel over.
+//!
+//! Session code talks to a `Transport` instead of an `RTCDataChannel`, so the
+//! same logic runs over WebRTC, a WebSocket relay or WebTransport, and natively
+//! against `MemoryTransport` in tests. Transports are message-oriented and
+//! reliable-ordered: each `send_bytes` arrives as one `poll_recv` result.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "websocket")]
+mod websocket;
+
+#[cfg(feature = "websocket")]
+pub use websocket::WebSocketTransport;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportError {
+	/// The transport was closed by either side.
+	Closed,
+	/// Still connecting; try again once open.
+	NotReady,
+	/// The underlying channel failed; carries its detail.
+	Backend(String),
+}
+
+pub trait Transport {
+	/// Queue one message for the peer.
+	fn send_bytes(&mut self, bytes: &[u8]) -> Result<(), TransportError>;
+
+	/// The next received message, or `Ok(None)` if none is waiting. After
+	/// close, queued messages are still returned before `Err(Closed)`.
+	fn poll_recv(&mut self) -> Result<Option<Vec<u8>>, TransportError>;
+
+	/// Close both directions. Idempotent.
+	fn close(&mut self);
+}
+
+#[derive(Debug, Default)]
+struct Loopback {
+	/// Messages waiting for each end.
+	inboxes: [VecDeque<Vec<u8>>; 2],
+	closed: bool,
+}
+
+/// One end of an in-memory transport pair.
+#[derive(Debug, Clone)]
+pub struct MemoryTransport {
+	shared: Arc<Mutex<Loopback>>,
+	end: usize,
+}
+
+impl MemoryTransport {
+	/// Two connected ends: what one sends, the other receives.
+	pub fn pair() -> (Self, Self) {
+		let shared = Arc::new(Mutex::new(Loopback::default()));
+		(
+			Self { shared: shared.clone(), end: 0 },
+			Self { shared, end: 1 },
+		)
+	}
+
+	/// Messages sent by the peer and not yet received.
+	pub fn pending(&self) -> usize {
+		self.shared.lock().unwrap().inboxes[self.end].len()
+	}
+}
+
+impl Transport for MemoryTransport {
+	fn send_bytes(&mut self, bytes: &[u8]) -> Result<(), TransportError> {
+		let mut shared = self.shared.lock().unwrap();
+		if shared.closed {
+			return Err(TransportError::Closed);
+		}
+		shared.inboxes[self.end ^ 1].push_back(bytes.to_vec());
+		Ok(())
+	}
+
+	fn poll_recv(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+		let mut shared = self.shared.lock().unwrap();
+		match shared.inboxes[self.end].pop_front() {
+			Some(bytes) => Ok(Some(bytes)),
+			None if shared.closed => Err(TransportError::Closed),
+			None => Ok(None),
+		}
+	}
+
+	fn close(&mut self) {
+		self.shared.lock().unwrap().closed = true;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::frame::{decode_v1, encode_chat_text_v1, FrameType};
+
+	#[test]
+	fn memory_pair_delivers_in_order() {
+		let (mut a, mut b) = MemoryTransport::pair();
+		a.send_bytes(&encode_chat_text_v1("uno")).unwrap();
+		a.send_bytes(&encode_chat_text_v1("dos")).unwrap();
+		b.send_bytes(b"pong").unwrap();
+		assert_eq!(b.pending(), 2);
+
+		for expected in ["uno", "dos"] {
+			let bytes = b.poll_recv().unwrap().unwrap();
+			let (frame, _) = decode_v1(&bytes, 1024).unwrap();
+			assert_eq!(frame.frame_type, FrameType::ChatText);
+			assert_eq!(frame.payload, expected.as_bytes());
+		}
+		assert_eq!(b.poll_recv(), Ok(None));
+		assert_eq!(a.poll_recv(), Ok(Some(b"pong".to_vec())));
+	}
+
+	#[test]
+	fn close_drains_then_reports_closed() {
+		let (mut a, mut b) = MemoryTransport::pair();
+		a.send_bytes(b"last").unwrap();
+		a.close();
+		a.close();
+
+		assert_eq!(a.send_bytes(b"late"), Err(TransportError::Closed));
+		assert_eq!(b.send_bytes(b"late"), Err(TransportError::Closed));
+		assert_eq!(b.poll_recv(), Ok(Some(b"last".to_vec())));
+		assert_eq!(b.poll_recv(), Err(TransportError::Closed));
+		assert_eq!(a.poll_recv(), Err(TransportError::Closed));
+	}
+
+	#[test]
+	fn works_through_the_trait() {
+		fn echo(transport: &mut dyn Transport) {
+			while let Ok(Some(bytes)) = transport.poll_recv() {
+				transport.send_bytes(&bytes).unwrap();
+			}
+		}
+		let (mut a, mut b) = MemoryTransport::pair();
+		a.send_bytes(b"hola").unwrap();
+		echo(&mut b);
+		assert_eq!(a.poll_recv(), Ok(Some(b"hola".to_vec())));
+	}
+}
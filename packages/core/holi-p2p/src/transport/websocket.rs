@@ -0,0 +1,127 @@
+//! `Transport` over a browser WebSocket (e.g. a relay when WebRTC can't connect).
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use js_sys::{ArrayBuffer, Uint8Array};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
+
+use super::{Transport, TransportError};
+
+#[derive(Default)]
+struct Inbox {
+	messages: VecDeque<Vec<u8>>,
+	closed: bool,
+	error: Option<String>,
+}
+
+pub struct WebSocketTransport {
+	socket: WebSocket,
+	inbox: Rc<RefCell<Inbox>>,
+	_on_message: Closure<dyn FnMut(MessageEvent)>,
+	_on_close: Closure<dyn FnMut(CloseEvent)>,
+	_on_error: Closure<dyn FnMut(Event)>,
+}
+
+impl WebSocketTransport {
+	/// Open a WebSocket to `url`. Sends fail with `NotReady` until it connects.
+	pub fn connect(url: &str) -> Result<Self, TransportError> {
+		let socket = WebSocket::new(url).map_err(|e| TransportError::Backend(format!("{e:?}")))?;
+		Ok(Self::from_socket(socket))
+	}
+
+	/// Wrap an existing socket. Messages that arrived before this are not seen.
+	pub fn from_socket(socket: WebSocket) -> Self {
+		socket.set_binary_type(BinaryType::Arraybuffer);
+		let inbox = Rc::new(RefCell::new(Inbox::default()));
+
+		let on_message = {
+			let inbox = inbox.clone();
+			Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+				let data = event.data();
+				let bytes = match data.dyn_into::<ArrayBuffer>() {
+					Ok(buffer) => Uint8Array::new(&buffer).to_vec(),
+					// Relays may forward text frames; pass them on as UTF-8.
+					Err(data) => data.as_string().unwrap_or_default().into_bytes(),
+				};
+				inbox.borrow_mut().messages.push_back(bytes);
+			})
+		};
+		let on_close = {
+			let inbox = inbox.clone();
+			Closure::<dyn FnMut(CloseEvent)>::new(move |_: CloseEvent| {
+				inbox.borrow_mut().closed = true;
+			})
+		};
+		let on_error = {
+			let inbox = inbox.clone();
+			Closure::<dyn FnMut(Event)>::new(move |event: Event| {
+				inbox.borrow_mut().error = Some(event.type_());
+			})
+		};
+		socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+		socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+		socket.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+		Self {
+			socket,
+			inbox,
+			_on_message: on_message,
+			_on_close: on_close,
+			_on_error: on_error,
+		}
+	}
+
+	pub fn is_open(&self) -> bool {
+		self.socket.ready_state() == WebSocket::OPEN
+	}
+
+	/// Bytes queued in the browser but not yet sent, for backpressure.
+	pub fn buffered_amount(&self) -> u32 {
+		self.socket.buffered_amount()
+	}
+}
+
+impl Transport for WebSocketTransport {
+	fn send_bytes(&mut self, bytes: &[u8]) -> Result<(), TransportError> {
+		match self.socket.ready_state() {
+			WebSocket::CONNECTING => Err(TransportError::NotReady),
+			WebSocket::OPEN => self
+				.socket
+				.send_with_u8_array(bytes)
+				.map_err(|e| TransportError::Backend(format!("{e:?}"))),
+			_ => Err(TransportError::Closed),
+		}
+	}
+
+	fn poll_recv(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+		let mut inbox = self.inbox.borrow_mut();
+		if let Some(bytes) = inbox.messages.pop_front() {
+			return Ok(Some(bytes));
+		}
+		match (inbox.error.take(), inbox.closed) {
+			(Some(error), _) => Err(TransportError::Backend(error)),
+			(None, true) => Err(TransportError::Closed),
+			(None, false) => Ok(None),
+		}
+	}
+
+	fn close(&mut self) {
+		// Already closing/closed sockets ignore this.
+		let _ = self.socket.close();
+		self.inbox.borrow_mut().closed = true;
+	}
+}
+
+impl Drop for WebSocketTransport {
+	fn drop(&mut self) {
+		// The closures die with us; the socket must not call them afterwards.
+		self.socket.set_onmessage(None);
+		self.socket.set_onclose(None);
+		self.socket.set_onerror(None);
+		let _ = self.socket.close();
+	}
+}
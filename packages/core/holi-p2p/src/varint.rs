@@ -4,6 +4,36 @@ pub enum VarintError {
 	Overflow,
 }
 
+/// Longest encoding of a u32 varint.
+pub const MAX_U32_VARINT_LEN: usize = 5;
+/// Longest encoding of a u64 varint.
+pub const MAX_U64_VARINT_LEN: usize = 10;
+
+/// Encoded length of `value` as a varint.
+pub fn varint_len(value: u64) -> usize {
+	(64 - (value | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+/// Write `value` at the start of `out`, returning the bytes written.
+///
+/// Panics if `out` is shorter than `varint_len(value)`; a buffer of
+/// `MAX_U64_VARINT_LEN` always fits.
+pub fn encode_u64_varint_into(mut value: u64, out: &mut [u8]) -> usize {
+	let mut i = 0;
+	while value >= 0x80 {
+		out[i] = ((value as u8) & 0x7F) | 0x80;
+		value >>= 7;
+		i += 1;
+	}
+	out[i] = value as u8;
+	i + 1
+}
+
+/// `encode_u64_varint_into` for u32; `MAX_U32_VARINT_LEN` bytes always fit.
+pub fn encode_u32_varint_into(value: u32, out: &mut [u8]) -> usize {
+	encode_u64_varint_into(value as u64, out)
+}
+
 pub fn encode_u32_varint(mut value: u32, out: &mut Vec<u8>) {
 	while value >= 0x80 {
 		out.push(((value as u8) & 0x7F) | 0x80);
@@ -70,6 +100,22 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn encode_into_matches_vec_encoding() {
+		let values = [0u64, 1, 127, 128, 16_383, 16_384, u32::MAX as u64, 1 << 56, u64::MAX];
+		for value in values {
+			let mut expected = Vec::new();
+			encode_u64_varint(value, &mut expected);
+			let mut buf = [0xAAu8; MAX_U64_VARINT_LEN + 1];
+			let n = encode_u64_varint_into(value, &mut buf);
+			assert_eq!(&buf[..n], expected.as_slice());
+			assert_eq!(buf[n], 0xAA);
+			assert_eq!(varint_len(value), n);
+		}
+		let mut buf = [0u8; MAX_U32_VARINT_LEN];
+		assert_eq!(encode_u32_varint_into(u32::MAX, &mut buf), MAX_U32_VARINT_LEN);
+	}
+
 	#[test]
 	fn roundtrip_varint_u64() {
 		let values = [0u64, 1, 2, 127, 128, 129, 16_383, 16_384, u64::MAX];
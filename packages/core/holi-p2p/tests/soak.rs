@@ -0,0 +1,584 @@
+//! Soak test for the P2P session layer.
+//!
+//! Two peers exchange chat, files and heartbeats over an in-memory link that
+//! drops, duplicates and reorders packets. A small selective-repeat layer
+//! stands in for SCTP's reliable ordered delivery, so everything above it
+//! (outbound scheduling, framing, Merkle-checked reassembly, clock sync) sees
+//! what it would see on a DataChannel.
+//!
+//! Checks ordering and reassembly, that the working set stays bounded and
+//! that nothing is left allocated once both peers are dropped, then prints
+//! throughput.
+//!
+//! `HOLI_SOAK_FRAMES` sets how many frames to exchange (default 50k in debug
+//! builds, 2M in release) and `HOLI_SOAK_SEED` the RNG seed:
+//!
+//! ```text
+//! HOLI_SOAK_FRAMES=5000000 cargo test --release --test soak
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::time::Instant;
+
+use holi_p2p::frame::{
+	decode_file_accept_payload_v1, decode_file_chunk_frame_v1, decode_file_end_payload_v1,
+	decode_file_offer_payload_v1, decode_v1, encode_chat_text_v1, encode_file_accept_v1,
+	encode_file_chunk_with_proof_v1, encode_file_end_v1, encode_file_offer_v1, FileOffer, FrameType,
+	MerkleRoot,
+};
+use holi_p2p::keepalive::{
+	encode_heartbeat_ping, encode_heartbeat_pong, is_heartbeat, ClockOffsetEstimator, HEARTBEAT_PING,
+};
+use holi_p2p::merkle::{leaf_count, verify_leaf, MerkleTree};
+use holi_p2p::outbound::{FrameScheduler, Priority};
+
+/// Heap bytes currently allocated, and the high-water mark.
+static LIVE_BYTES: AtomicIsize = AtomicIsize::new(0);
+static PEAK_BYTES: AtomicIsize = AtomicIsize::new(0);
+
+struct CountingAlloc;
+
+impl CountingAlloc {
+	fn grow(delta: isize) {
+		let live = LIVE_BYTES.fetch_add(delta, Ordering::Relaxed) + delta;
+		PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+	}
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		Self::grow(layout.size() as isize);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		Self::grow(-(layout.size() as isize));
+		System.dealloc(ptr, layout)
+	}
+
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+		Self::grow(new_size as isize - layout.size() as isize);
+		System.realloc(ptr, layout, new_size)
+	}
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// Link conditions, per packet.
+const LOSS_PER_MILLE: u64 = 20;
+const DUPLICATE_PER_MILLE: u64 = 5;
+const BASE_DELAY_TICKS: u64 = 2;
+/// Random extra delay; packets overtake each other within this window.
+const JITTER_TICKS: u64 = 6;
+/// Retransmit after this many ticks without an ack.
+const RTO_TICKS: u64 = 3 * (BASE_DELAY_TICKS + JITTER_TICKS);
+/// Unacked packets allowed in flight.
+const WINDOW: usize = 512;
+/// Highest out-of-order sequence numbers reported per ack.
+const MAX_SACK: usize = 64;
+/// Bytes moved from the scheduler to the transport per tick.
+const TICK_BUDGET: usize = 256 * 1024;
+/// Bulk bytes queued ahead of the scheduler, as chat.ts keeps the channel fed.
+const BULK_HIGH_WATER: usize = 128 * 1024;
+
+const CHUNK_SIZE: usize = 1024;
+const MAX_FILE_SIZE: u64 = 64 * 1024;
+const MAX_PAYLOAD: u32 = 1 << 20;
+const CHAT_PER_MILLE: u64 = 300;
+const HEARTBEAT_EVERY_TICKS: u64 = 50;
+const MS_PER_TICK: u64 = 1;
+/// Peer clocks at tick 0; B runs `CLOCK_SKEW_MS` ahead of A.
+const EPOCH_MS: u64 = 1_700_000_000_000;
+const CLOCK_SKEW_MS: u64 = 1_500;
+/// Working-set ceiling, whatever the frame count.
+const MAX_PEAK_BYTES: isize = 64 * 1024 * 1024;
+
+/// xorshift64*
+struct Rng(u64);
+
+impl Rng {
+	fn next_u64(&mut self) -> u64 {
+		self.0 ^= self.0 >> 12;
+		self.0 ^= self.0 << 25;
+		self.0 ^= self.0 >> 27;
+		self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+	}
+
+	fn below(&mut self, n: u64) -> u64 {
+		self.next_u64() % n
+	}
+
+	fn chance(&mut self, per_mille: u64) -> bool {
+		self.below(1000) < per_mille
+	}
+}
+
+/// Deterministic contents for file `id`, so the receiver can check them.
+fn file_bytes(id: &str, len: usize) -> Vec<u8> {
+	let mut rng = Rng(u64::from_le_bytes(blake3::hash(id.as_bytes()).as_bytes()[..8].try_into().unwrap()) | 1);
+	let mut out = Vec::with_capacity(len + 8);
+	while out.len() < len {
+		out.extend_from_slice(&rng.next_u64().to_le_bytes());
+	}
+	out.truncate(len);
+	out
+}
+
+enum Packet {
+	Data { seq: u64, bytes: Vec<u8> },
+	/// Everything below `next` arrived, plus the sequence numbers in `sack`.
+	Ack { next: u64, sack: Vec<u64> },
+}
+
+/// One direction of the lossy, reordering network.
+#[derive(Default)]
+struct Link {
+	/// Keyed by (delivery tick, send order).
+	in_flight: BTreeMap<(u64, u64), Packet>,
+	sent: u64,
+	lost: u64,
+}
+
+impl Link {
+	fn send(&mut self, now: u64, packet: Packet, rng: &mut Rng) {
+		if rng.chance(LOSS_PER_MILLE) {
+			self.lost += 1;
+			return;
+		}
+		if let (true, Packet::Data { seq, bytes }) = (rng.chance(DUPLICATE_PER_MILLE), &packet) {
+			let copy = Packet::Data { seq: *seq, bytes: bytes.clone() };
+			self.enqueue(now, copy, rng);
+		}
+		self.enqueue(now, packet, rng);
+	}
+
+	fn enqueue(&mut self, now: u64, packet: Packet, rng: &mut Rng) {
+		let at = now + BASE_DELAY_TICKS + rng.below(JITTER_TICKS + 1);
+		self.sent += 1;
+		self.in_flight.insert((at, self.sent), packet);
+	}
+
+	fn ready(&mut self, now: u64) -> Vec<Packet> {
+		let later = self.in_flight.split_off(&(now + 1, 0));
+		std::mem::replace(&mut self.in_flight, later).into_values().collect()
+	}
+
+	fn is_empty(&self) -> bool {
+		self.in_flight.is_empty()
+	}
+}
+
+/// Selective-repeat reliable, ordered delivery over a `Link`.
+#[derive(Default)]
+struct Transport {
+	next_seq: u64,
+	/// Sent but not acked: seq -> (last send tick, bytes).
+	unacked: BTreeMap<u64, (u64, Vec<u8>)>,
+	next_expected: u64,
+	reorder: BTreeMap<u64, Vec<u8>>,
+	ack_due: bool,
+	retransmits: u64,
+	duplicates: u64,
+}
+
+impl Transport {
+	fn can_send(&self) -> bool {
+		self.unacked.len() < WINDOW
+	}
+
+	fn send(&mut self, now: u64, bytes: Vec<u8>, link: &mut Link, rng: &mut Rng) {
+		let seq = self.next_seq;
+		self.next_seq += 1;
+		link.send(now, Packet::Data { seq, bytes: bytes.clone() }, rng);
+		self.unacked.insert(seq, (now, bytes));
+	}
+
+	/// Retransmit timed-out packets and send a pending ack.
+	fn on_tick(&mut self, now: u64, link: &mut Link, rng: &mut Rng) {
+		for (&seq, (sent_at, bytes)) in self.unacked.iter_mut() {
+			if now >= *sent_at + RTO_TICKS {
+				*sent_at = now;
+				self.retransmits += 1;
+				link.send(now, Packet::Data { seq, bytes: bytes.clone() }, rng);
+			}
+		}
+		if self.ack_due {
+			self.ack_due = false;
+			let sack = self.reorder.keys().take(MAX_SACK).copied().collect();
+			link.send(now, Packet::Ack { next: self.next_expected, sack }, rng);
+		}
+	}
+
+	/// Handle a packet from the peer, appending in-order payloads to `delivered`.
+	fn on_packet(&mut self, packet: Packet, delivered: &mut Vec<Vec<u8>>) {
+		match packet {
+			Packet::Data { seq, bytes } => {
+				self.ack_due = true;
+				if seq < self.next_expected || self.reorder.contains_key(&seq) {
+					self.duplicates += 1;
+					return;
+				}
+				self.reorder.insert(seq, bytes);
+				while let Some(bytes) = self.reorder.remove(&self.next_expected) {
+					delivered.push(bytes);
+					self.next_expected += 1;
+				}
+			}
+			Packet::Ack { next, sack } => {
+				self.unacked = self.unacked.split_off(&next);
+				for seq in sack {
+					self.unacked.remove(&seq);
+				}
+			}
+		}
+	}
+
+	fn is_idle(&self) -> bool {
+		self.unacked.is_empty() && self.reorder.is_empty()
+	}
+}
+
+struct OutgoingFile {
+	id: String,
+	data: Vec<u8>,
+	tree: MerkleTree,
+	accepted: bool,
+	next_chunk: usize,
+}
+
+struct IncomingFile {
+	offer: FileOffer,
+	root: MerkleRoot,
+	data: Vec<u8>,
+	next_chunk: u64,
+}
+
+#[derive(Default)]
+struct PeerStats {
+	frames_sent: u64,
+	frames_received: u64,
+	bytes_received: u64,
+	chats_received: u64,
+	files_received: u64,
+	max_chat_latency_ticks: u64,
+}
+
+struct Peer {
+	name: &'static str,
+	clock_base_ms: u64,
+	/// Frames this peer originates; replies (pongs, accepts) are extra.
+	frame_target: u64,
+	originated: u64,
+	outbound: FrameScheduler,
+	transport: Transport,
+	chats_sent: u64,
+	files_started: u64,
+	outgoing: Option<OutgoingFile>,
+	incoming: HashMap<String, IncomingFile>,
+	clock: ClockOffsetEstimator,
+	stats: PeerStats,
+}
+
+impl Peer {
+	fn new(name: &'static str, clock_base_ms: u64, frame_target: u64) -> Self {
+		Self {
+			name,
+			clock_base_ms,
+			frame_target,
+			originated: 0,
+			outbound: FrameScheduler::new(),
+			transport: Transport::default(),
+			chats_sent: 0,
+			files_started: 0,
+			outgoing: None,
+			incoming: HashMap::new(),
+			clock: ClockOffsetEstimator::new(),
+			stats: PeerStats::default(),
+		}
+	}
+
+	fn clock_ms(&self, now: u64) -> u64 {
+		self.clock_base_ms + now * MS_PER_TICK
+	}
+
+	fn generating(&self) -> bool {
+		self.originated < self.frame_target
+	}
+
+	fn enqueue(&mut self, priority: Priority, frame: Vec<u8>) {
+		self.stats.frames_sent += 1;
+		self.outbound.enqueue(priority, frame);
+	}
+
+	fn originate(&mut self, priority: Priority, frame: Vec<u8>) {
+		self.originated += 1;
+		self.enqueue(priority, frame);
+	}
+
+	/// Queue new chat, heartbeats and file frames.
+	fn generate(&mut self, now: u64, rng: &mut Rng) {
+		if !self.generating() {
+			// Let the transfer in progress finish.
+			self.feed_file();
+			return;
+		}
+		if now.is_multiple_of(HEARTBEAT_EVERY_TICKS) {
+			let ping = encode_heartbeat_ping(self.clock_ms(now));
+			self.originate(Priority::Control, ping.to_vec());
+		}
+		if rng.chance(CHAT_PER_MILLE) {
+			let padding = "x".repeat(rng.below(200) as usize);
+			let text = format!("{} {} {}", self.chats_sent, now, padding);
+			self.chats_sent += 1;
+			let frame = encode_chat_text_v1(&text);
+			self.originate(Priority::for_frame_bytes(&frame).unwrap(), frame);
+		}
+		if self.outgoing.is_none() {
+			let id = format!("{}-{}", self.name, self.files_started);
+			self.files_started += 1;
+			let data = file_bytes(&id, rng.below(MAX_FILE_SIZE + 1) as usize);
+			let tree = MerkleTree::from_data(&data, CHUNK_SIZE as u32);
+			let offer = FileOffer {
+				id: id.clone(),
+				filename: format!("{id}.bin"),
+				mime_type: "application/octet-stream".to_string(),
+				size: data.len() as u64,
+				merkle: Some(MerkleRoot { leaf_size: CHUNK_SIZE as u32, root: tree.root() }),
+				thumbnail: None,
+			};
+			self.originate(Priority::Chat, encode_file_offer_v1(&offer));
+			self.outgoing = Some(OutgoingFile { id, data, tree, accepted: false, next_chunk: 0 });
+		}
+		self.feed_file();
+	}
+
+	/// Queue chunks of the accepted file up to the bulk high-water mark.
+	fn feed_file(&mut self) {
+		let Some(mut file) = self.outgoing.take() else {
+			return;
+		};
+		if !file.accepted {
+			self.outgoing = Some(file);
+			return;
+		}
+		let chunks = file.tree.leaf_count();
+		while file.next_chunk < chunks && self.outbound.pending_bytes(Priority::Bulk) < BULK_HIGH_WATER {
+			let start = (file.next_chunk * CHUNK_SIZE).min(file.data.len());
+			let end = (start + CHUNK_SIZE).min(file.data.len());
+			let proof = file.tree.proof(file.next_chunk).unwrap();
+			let frame =
+				encode_file_chunk_with_proof_v1(&file.id, file.next_chunk as u32, &proof, &file.data[start..end]).unwrap();
+			self.originate(Priority::Bulk, frame);
+			file.next_chunk += 1;
+		}
+		if file.next_chunk == chunks {
+			self.originate(Priority::Bulk, encode_file_end_v1(&file.id));
+		} else {
+			self.outgoing = Some(file);
+		}
+	}
+
+	fn tick(&mut self, now: u64, link: &mut Link, rng: &mut Rng) {
+		self.generate(now, rng);
+		let mut budget = TICK_BUDGET;
+		while budget > 0 && self.transport.can_send() {
+			let Some((_, frame)) = self.outbound.next_frame() else {
+				break;
+			};
+			budget = budget.saturating_sub(frame.len());
+			self.transport.send(now, frame, link, rng);
+		}
+		self.transport.on_tick(now, link, rng);
+	}
+
+	fn receive(&mut self, now: u64, packet: Packet) {
+		let mut delivered = Vec::new();
+		self.transport.on_packet(packet, &mut delivered);
+		for bytes in delivered {
+			self.handle(now, bytes);
+		}
+	}
+
+	fn handle(&mut self, now: u64, bytes: Vec<u8>) {
+		self.stats.frames_received += 1;
+		self.stats.bytes_received += bytes.len() as u64;
+
+		if is_heartbeat(&bytes) {
+			let local = self.clock_ms(now);
+			if bytes[0] == HEARTBEAT_PING {
+				// Both ends are this build, so both advertise clock sync.
+				let pong = encode_heartbeat_pong(&bytes, local, true).unwrap();
+				self.enqueue(Priority::Control, pong);
+			} else {
+				assert!(self.clock.record_pong(&bytes, local), "{}: pong without a clock sample", self.name);
+			}
+			return;
+		}
+
+		let (frame, used) = decode_v1(&bytes, MAX_PAYLOAD).expect("frame decodes");
+		assert_eq!(used, bytes.len());
+		match frame.frame_type {
+			FrameType::ChatText => {
+				let text = String::from_utf8(frame.payload).unwrap();
+				let mut fields = text.split(' ');
+				let n: u64 = fields.next().unwrap().parse().unwrap();
+				let sent_at: u64 = fields.next().unwrap().parse().unwrap();
+				assert_eq!(n, self.stats.chats_received, "{}: chat out of order", self.name);
+				self.stats.chats_received += 1;
+				self.stats.max_chat_latency_ticks = self.stats.max_chat_latency_ticks.max(now - sent_at);
+			}
+			FrameType::FileOffer => {
+				let offer = decode_file_offer_payload_v1(&frame.payload).unwrap();
+				let root = offer.merkle.expect("offer carries a Merkle root");
+				self.enqueue(Priority::Control, encode_file_accept_v1(&offer.id));
+				let file = IncomingFile { data: Vec::with_capacity(offer.size as usize), offer, root, next_chunk: 0 };
+				assert!(self.incoming.insert(file.offer.id.clone(), file).is_none());
+			}
+			FrameType::FileAccept => {
+				let id = decode_file_accept_payload_v1(&frame.payload).unwrap();
+				let file = self.outgoing.as_mut().expect("accept for a file in progress");
+				assert_eq!(file.id, id);
+				file.accepted = true;
+			}
+			FrameType::FileChunk => {
+				let chunk = decode_file_chunk_frame_v1(&frame).unwrap();
+				let file = self.incoming.get_mut(&chunk.id).expect("chunk for an offered file");
+				let leaves = leaf_count(file.offer.size, file.root.leaf_size);
+				assert_eq!(u64::from(chunk.chunk_index), file.next_chunk, "{}: chunk out of order", self.name);
+				assert!(
+					verify_leaf(&file.root.root, leaves, file.next_chunk, &chunk.data, &chunk.proof),
+					"{}: chunk {} of {} fails its proof",
+					self.name,
+					chunk.chunk_index,
+					chunk.id
+				);
+				file.data.extend_from_slice(&chunk.data);
+				file.next_chunk += 1;
+			}
+			FrameType::FileEnd => {
+				let id = decode_file_end_payload_v1(&frame.payload).unwrap();
+				let file = self.incoming.remove(&id).expect("end of an offered file");
+				assert_eq!(file.next_chunk, leaf_count(file.offer.size, file.root.leaf_size));
+				assert_eq!(file.data, file_bytes(&id, file.offer.size as usize), "{}: {} corrupted", self.name, id);
+				self.stats.files_received += 1;
+			}
+			other => panic!("{}: unexpected {:?}", self.name, other),
+		}
+	}
+
+	fn is_done(&self) -> bool {
+		!self.generating()
+			&& self.outgoing.is_none()
+			&& self.outbound.is_empty()
+			&& self.transport.is_idle()
+			&& self.incoming.is_empty()
+	}
+}
+
+struct Report {
+	frames: u64,
+	bytes: u64,
+	ticks: u64,
+	lost: u64,
+	retransmits: u64,
+	duplicates: u64,
+	files: u64,
+	chats: u64,
+	max_chat_latency_ticks: u64,
+	offsets_ms: (i64, i64),
+}
+
+fn run(frames: u64, seed: u64) -> Report {
+	let mut rng = Rng(seed | 1);
+	let mut a = Peer::new("a", EPOCH_MS, frames / 2);
+	let mut b = Peer::new("b", EPOCH_MS + CLOCK_SKEW_MS, frames - frames / 2);
+	let (mut a_to_b, mut b_to_a) = (Link::default(), Link::default());
+
+	// Generous: a tick moves hundreds of frames each way.
+	let max_ticks = 1_000 + frames * 10;
+	let mut now = 0;
+	loop {
+		for packet in a_to_b.ready(now) {
+			b.receive(now, packet);
+		}
+		for packet in b_to_a.ready(now) {
+			a.receive(now, packet);
+		}
+		a.tick(now, &mut a_to_b, &mut rng);
+		b.tick(now, &mut b_to_a, &mut rng);
+		if a.is_done() && b.is_done() && a_to_b.is_empty() && b_to_a.is_empty() {
+			break;
+		}
+		now += 1;
+		assert!(now < max_ticks, "session stalled after {now} ticks");
+	}
+
+	for (peer, other) in [(&a, &b), (&b, &a)] {
+		assert_eq!(peer.stats.frames_received, other.stats.frames_sent, "{} lost frames", peer.name);
+		assert_eq!(peer.stats.chats_received, other.chats_sent);
+		assert_eq!(peer.stats.files_received, other.files_started);
+	}
+	let offset = |p: &Peer| p.clock.offset_ms().expect("clock samples");
+	let rtt = |p: &Peer| p.clock.rtt_ms().unwrap() as i64;
+	assert!((offset(&a) - CLOCK_SKEW_MS as i64).abs() <= rtt(&a) / 2 + 1);
+	assert!((offset(&b) + CLOCK_SKEW_MS as i64).abs() <= rtt(&b) / 2 + 1);
+
+	Report {
+		frames: a.stats.frames_received + b.stats.frames_received,
+		bytes: a.stats.bytes_received + b.stats.bytes_received,
+		ticks: now,
+		lost: a_to_b.lost + b_to_a.lost,
+		retransmits: a.transport.retransmits + b.transport.retransmits,
+		duplicates: a.transport.duplicates + b.transport.duplicates,
+		files: a.stats.files_received + b.stats.files_received,
+		chats: a.stats.chats_received + b.stats.chats_received,
+		max_chat_latency_ticks: a.stats.max_chat_latency_ticks.max(b.stats.max_chat_latency_ticks),
+		offsets_ms: (offset(&a), offset(&b)),
+	}
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+	std::env::var(name).ok().map(|v| v.parse().unwrap_or_else(|_| panic!("{name} must be a number")))
+}
+
+fn main() {
+	let default_frames = if cfg!(debug_assertions) { 50_000 } else { 2_000_000 };
+	let frames = env_u64("HOLI_SOAK_FRAMES").unwrap_or(default_frames);
+	let seed = env_u64("HOLI_SOAK_SEED").unwrap_or(0x486F_6C69);
+	println!("soak: {frames} frames, seed {seed:#x}");
+
+	let baseline = LIVE_BYTES.load(Ordering::Relaxed);
+	PEAK_BYTES.store(baseline, Ordering::Relaxed);
+	let started = Instant::now();
+	let report = run(frames, seed);
+	let elapsed = started.elapsed().as_secs_f64();
+	let leaked = LIVE_BYTES.load(Ordering::Relaxed) - baseline;
+	let peak = PEAK_BYTES.load(Ordering::Relaxed) - baseline;
+
+	assert_eq!(leaked, 0, "{leaked} bytes still allocated after both peers were dropped");
+	assert!(peak < MAX_PEAK_BYTES, "working set peaked at {peak} bytes");
+	assert!(report.frames >= frames);
+
+	println!(
+		"soak: {} frames, {:.1} MiB in {:.2}s ({:.0} frames/s, {:.1} MiB/s)",
+		report.frames,
+		report.bytes as f64 / (1024.0 * 1024.0),
+		elapsed,
+		report.frames as f64 / elapsed,
+		report.bytes as f64 / (1024.0 * 1024.0) / elapsed,
+	);
+	println!(
+		"soak: {} ticks, {} chats, {} files; {} packets lost, {} retransmits, {} duplicates",
+		report.ticks, report.chats, report.files, report.lost, report.retransmits, report.duplicates,
+	);
+	println!(
+		"soak: max chat latency {} ticks, clock offsets {:+} / {:+} ms, peak heap {:.1} MiB, ok",
+		report.max_chat_latency_ticks,
+		report.offsets_ms.0,
+		report.offsets_ms.1,
+		peak as f64 / (1024.0 * 1024.0),
+	);
+}
@@ -0,0 +1,142 @@
+//! # Holi PAKE
+//!
+//! SPAKE2 over Ed25519Group, with the raw output expanded through
+//! HKDF-SHA256 into a domain-separated 32-byte session key.
+//!
+//! Shared by `wasm-crypto` (the plain `Spake2A/B/Symmetric` bindings) and
+//! `wasm-core` (sessions gated on `PairingGuard`) so that a guarded peer
+//! derives the same key as an unguarded one.
+//!
+//! This crate draws no randomness: callers pass the RNG, so each WASM crate
+//! keeps using its own.
+
+use hkdf::Hkdf;
+use rand_core::{CryptoRng, RngCore};
+use sha2::Sha256;
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use std::fmt;
+
+/// Length of the derived session key.
+pub const PAKE_KEY_LEN: usize = 32;
+
+const HOLI_PAKE_SALT_V1: &[u8] = b"holi.pake.salt.v1";
+const HOLI_PAKE_INFO_SESSION_KEY_V1: &[u8] = b"holi.pake.info.session_key.v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PakeError {
+    /// The peer's message was malformed or came from the wrong side.
+    Spake2(spake2::Error),
+}
+
+impl fmt::Display for PakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PakeError::Spake2(e) => write!(f, "SPAKE2 failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PakeError {}
+
+/// One side of a SPAKE2 handshake, from `start_*` until `finish`.
+pub struct PakeSession {
+    state: Spake2<Ed25519Group>,
+    message: Vec<u8>,
+}
+
+impl PakeSession {
+    /// Role A (typically: offerer / initiator). `id_a` / `id_b` bind the
+    /// handshake to its pairing context (e.g. contact IDs).
+    pub fn start_a(
+        password: &[u8],
+        id_a: &[u8],
+        id_b: &[u8],
+        rng: impl CryptoRng + RngCore,
+    ) -> Self {
+        let (state, message) = Spake2::<Ed25519Group>::start_a_with_rng(
+            &Password::new(password),
+            &Identity::new(id_a),
+            &Identity::new(id_b),
+            rng,
+        );
+        PakeSession { state, message }
+    }
+
+    /// Role B (typically: answerer / responder).
+    pub fn start_b(
+        password: &[u8],
+        id_a: &[u8],
+        id_b: &[u8],
+        rng: impl CryptoRng + RngCore,
+    ) -> Self {
+        let (state, message) = Spake2::<Ed25519Group>::start_b_with_rng(
+            &Password::new(password),
+            &Identity::new(id_a),
+            &Identity::new(id_b),
+            rng,
+        );
+        PakeSession { state, message }
+    }
+
+    /// Symmetric mode: both sides start the same way.
+    pub fn start_symmetric(password: &[u8], id_s: &[u8], rng: impl CryptoRng + RngCore) -> Self {
+        let (state, message) = Spake2::<Ed25519Group>::start_symmetric_with_rng(
+            &Password::new(password),
+            &Identity::new(id_s),
+            rng,
+        );
+        PakeSession { state, message }
+    }
+
+    /// Message to send to the peer.
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// Derive the session key from the peer's message.
+    ///
+    /// Succeeds with a wrong password too: a mismatch only shows when the
+    /// first frame sealed under the key fails to open.
+    pub fn finish(self, inbound_msg: &[u8]) -> Result<[u8; PAKE_KEY_LEN], PakeError> {
+        let shared = self.state.finish(inbound_msg).map_err(PakeError::Spake2)?;
+        let mut key = [0u8; PAKE_KEY_LEN];
+        // A 32-byte output is always within HKDF-SHA256's 255 * 32 byte limit.
+        Hkdf::<Sha256>::new(Some(HOLI_PAKE_SALT_V1), &shared)
+            .expand(HOLI_PAKE_INFO_SESSION_KEY_V1, &mut key)
+            .expect("HKDF output length");
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn ab_roundtrip_derives_same_key() {
+        let a = PakeSession::start_a(b"correct horse", b"alice", b"bob", OsRng);
+        let b = PakeSession::start_b(b"correct horse", b"alice", b"bob", OsRng);
+        let a_msg = a.message().to_vec();
+        let b_msg = b.message().to_vec();
+        assert_eq!(a.finish(&b_msg).unwrap(), b.finish(&a_msg).unwrap());
+    }
+
+    #[test]
+    fn wrong_password_derives_different_keys() {
+        let p1 = PakeSession::start_symmetric(b"123456", b"holi:test", OsRng);
+        let p2 = PakeSession::start_symmetric(b"123457", b"holi:test", OsRng);
+        let m1 = p1.message().to_vec();
+        let m2 = p2.message().to_vec();
+        assert_ne!(p1.finish(&m2).unwrap(), p2.finish(&m1).unwrap());
+    }
+
+    #[test]
+    fn malformed_message_is_an_error() {
+        let a = PakeSession::start_a(b"pin", b"alice", b"bob", OsRng);
+        assert_eq!(
+            a.finish(b"short"),
+            Err(PakeError::Spake2(spake2::Error::WrongLength))
+        );
+    }
+}
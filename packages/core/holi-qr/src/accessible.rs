@@ -0,0 +1,437 @@
+//! High-contrast theme enforcement
+//!
+//! `validate_options` only warns about low contrast; `enforce_accessible_theme`
+//! fixes it. Inverted themes are turned dark on light, colors are moved
+//! toward black or white (keeping their hue) just far enough to reach the
+//! policy's contrast, the background pattern is pulled toward the
+//! background, a logo is shrunk until error correction covers it, and the
+//! quiet zone is trimmed toward the spec minimum if that's what it takes
+//! for modules to reach the policy's size at the declared display size.
+//! What can't be fixed that way is an error, not a quietly inaccessible
+//! code.
+
+use crate::error::QrError;
+use crate::logo::check_logo;
+use crate::pattern::{contrast, parse_hex, to_hex, Rgb};
+use crate::qr::QrCode;
+use crate::render::{padded_size, EyeCorner, StyledRenderOptions};
+use crate::validate::SPEC_QUIET_ZONE;
+
+/// WCAG AAA contrast for normal text
+pub const DEFAULT_MIN_CONTRAST: f64 = 7.0;
+/// Smallest module a phone camera picks up off a screen, in CSS pixels
+pub const DEFAULT_MIN_MODULE_PX: f64 = 4.0;
+
+/// What `enforce_accessible_theme` guarantees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccessibilityPolicy {
+    /// WCAG contrast ratio between each dark color and the background (and
+    /// background pattern), 1 to 21
+    pub min_contrast: f64,
+    /// Side of one module at the display size, in CSS pixels
+    pub min_module_px: f64,
+}
+
+impl Default for AccessibilityPolicy {
+    fn default() -> Self {
+        Self {
+            min_contrast: DEFAULT_MIN_CONTRAST,
+            min_module_px: DEFAULT_MIN_MODULE_PX,
+        }
+    }
+}
+
+/// One option `enforce_accessible_theme` changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThemeAdjustment {
+    /// Option name, as in `ValidationIssue::field`
+    pub field: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// The adjusted theme and what it measures
+#[derive(Debug, Clone)]
+pub struct AccessibleTheme {
+    pub options: StyledRenderOptions,
+    /// Empty when the requested theme already complied
+    pub adjustments: Vec<ThemeAdjustment>,
+    /// Lowest contrast between a dark color and the background or its pattern
+    pub contrast: f64,
+    /// Side of one module at the display size, in CSS pixels
+    pub module_px: f64,
+}
+
+/// `color` moved `t` (0 to 1) of the way to `target`
+fn toward(color: Rgb, target: Rgb, t: f64) -> Rgb {
+    [0, 1, 2].map(|i| color[i] + (target[i] - color[i]) * t)
+}
+
+/// Whether `a` is darker than `b`, or as dark: lower contrast against black
+fn darker(a: Rgb, b: Rgb) -> bool {
+    contrast(a, [0.0; 3]) <= contrast(b, [0.0; 3])
+}
+
+/// Whether dark `fg` on light `bg` reaches `required`
+fn passes(fg: Rgb, bg: Rgb, required: f64) -> bool {
+    darker(fg, bg) && contrast(fg, bg) >= required
+}
+
+/// Smallest steps toward black for `fg` and white for `bg` that bring them
+/// to `required`, `fg` ending up the darker
+///
+/// Moves in 1% steps and returns the first pair that passes, trying the
+/// foreground alone, then the background alone, then both at each step. Black on white
+/// (21:1) is reached at the last step, so anything up to 21 passes.
+fn separate(fg: Rgb, bg: Rgb, required: f64, move_bg: bool) -> (Rgb, Rgb) {
+    let (fg_target, bg_target) = ([0.0; 3], [255.0; 3]);
+    for step in 0..=100 {
+        let t = step as f64 / 100.0;
+        let mut candidates = vec![(toward(fg, fg_target, t), bg)];
+        if move_bg {
+            candidates.push((fg, toward(bg, bg_target, t)));
+            candidates.push((toward(fg, fg_target, t), toward(bg, bg_target, t)));
+        }
+        // Hex rounding can lose a hair of contrast; check what will be written
+        let rounded = |c: Rgb| c.map(f64::round);
+        if let Some(&(f, b)) = candidates.iter().find(|&&(f, b)| passes(rounded(f), rounded(b), required)) {
+            return (rounded(f), rounded(b));
+        }
+    }
+    (fg_target, bg_target)
+}
+
+/// `options` adjusted to meet `policy` when shown `display_px` CSS pixels
+/// wide (quiet zone included)
+///
+/// Light-on-dark colors are swapped first, since some scanners can't read
+/// inverted codes. Then whichever of the foreground, the background or both
+/// needs the smallest shift is changed, the foreground first on ties;
+/// gradient stops and eye colors are fitted to that background one by one,
+/// darkened if they're lighter than it. A transparent background is treated
+/// as white and left transparent.
+///
+/// The background pattern is blended toward the background until it has
+/// the policy's contrast with every dark color. A logo that hides more
+/// than error correction restores is shrunk until it doesn't.
+///
+/// # Returns
+/// * `Ok(AccessibleTheme)` - The options to render with and what changed
+/// * `Err(QrError::InvalidArgument)` - A color isn't `#RGB`/`#RRGGBB`, the
+///   policy is out of range, no logo size fits the error correction level,
+///   or `display_px` is too small even with the spec's quiet zone (the
+///   message says how large it needs to be)
+pub fn enforce_accessible_theme(
+    qr: &QrCode,
+    options: &StyledRenderOptions,
+    display_px: f64,
+    policy: &AccessibilityPolicy,
+) -> Result<AccessibleTheme, QrError> {
+    if !(1.0..=21.0).contains(&policy.min_contrast) {
+        return Err(QrError::InvalidArgument(format!(
+            "Minimum contrast must be between 1 and 21 (got {})",
+            policy.min_contrast
+        )));
+    }
+    if !policy.min_module_px.is_finite() || policy.min_module_px <= 0.0 {
+        return Err(QrError::InvalidArgument(format!(
+            "Minimum module size must be positive (got {})",
+            policy.min_module_px
+        )));
+    }
+    if !display_px.is_finite() || display_px <= 0.0 {
+        return Err(QrError::InvalidArgument(format!("Display size must be positive (got {})", display_px)));
+    }
+    padded_size(qr.size(), options.margin)?;
+
+    let hex = |field: &str, color: &str| {
+        parse_hex(color).ok_or_else(|| {
+            QrError::InvalidArgument(format!("{} must be #RGB or #RRGGBB to check its contrast (got {:?})", field, color))
+        })
+    };
+    let mut adjusted = options.clone();
+    let mut adjustments = Vec::new();
+    let mut record = |field: &str, from: &str, to: &str| {
+        if !from.eq_ignore_ascii_case(to) {
+            adjustments.push(ThemeAdjustment { field: field.to_string(), from: from.to_string(), to: to.to_string() });
+        }
+    };
+
+    // Colors
+    let transparent = options.bg_color == "transparent";
+    let bg = if transparent { [255.0; 3] } else { hex("bg_color", &options.bg_color)? };
+    let fg = hex("fg_color", &options.fg_color)?;
+    // Inverted: the light background becomes the modules' color and the
+    // other way round (never with a transparent one, which is white)
+    let (dark, light) = if darker(fg, bg) { (fg, bg) } else { (bg, fg) };
+    let (new_fg, new_bg) = if passes(dark, light, policy.min_contrast) {
+        (dark, light)
+    } else {
+        separate(dark, light, policy.min_contrast, !transparent)
+    };
+    if new_fg != fg {
+        adjusted.fg_color = to_hex(new_fg);
+        record("fg_color", &options.fg_color, &adjusted.fg_color);
+    }
+    if new_bg != bg && !transparent {
+        adjusted.bg_color = to_hex(new_bg);
+        record("bg_color", &options.bg_color, &adjusted.bg_color);
+    }
+    let mut darks = vec![new_fg];
+    // Another dark color fitted to the new background; `None` if it complied
+    let mut fit = |field: &str, color: &str| -> Result<Option<String>, QrError> {
+        let rgb = hex(field, color)?;
+        if passes(rgb, new_bg, policy.min_contrast) {
+            darks.push(rgb);
+            return Ok(None);
+        }
+        let (rgb, _) = separate(rgb, new_bg, policy.min_contrast, false);
+        darks.push(rgb);
+        record(field, color, &to_hex(rgb));
+        Ok(Some(to_hex(rgb)))
+    };
+    if let Some(gradient) = &options.fg_gradient {
+        for (i, stop) in gradient.stops.iter().enumerate() {
+            if let Some(color) = fit(&format!("fg_gradient.stops[{}].color", i), &stop.color)? {
+                if let Some(g) = adjusted.fg_gradient.as_mut() {
+                    g.stops[i].color = color;
+                }
+            }
+        }
+    }
+    for corner in EyeCorner::ALL {
+        let eye = &options.eye_overrides[corner as usize];
+        if let Some(color) = &eye.color {
+            if let Some(color) = fit(&format!("eyes.{}.color", corner.as_str()), color)? {
+                adjusted.eye_overrides[corner as usize].color = Some(color);
+            }
+        }
+        let Some(gradient) = &eye.gradient else { continue };
+        for (i, stop) in gradient.stops.iter().enumerate() {
+            let field = format!("eyes.{}.gradient.stops[{}].color", corner.as_str(), i);
+            if let Some(color) = fit(&field, &stop.color)? {
+                if let Some(g) = adjusted.eye_overrides[corner as usize].gradient.as_mut() {
+                    g.stops[i].color = color;
+                }
+            }
+        }
+    }
+
+    let mut lowest = darks.iter().map(|&d| contrast(d, new_bg)).fold(f64::INFINITY, f64::min);
+
+    // Background pattern: it shows between the modules, so it's background too
+    if let Some(pattern) = &options.background_pattern {
+        let color = hex("background_pattern.color", &pattern.color)?;
+        // The background itself (the last step) passes against every dark color
+        let blended = (0..=100)
+            .map(|step| toward(color, new_bg, step as f64 / 100.0).map(f64::round))
+            .find(|&c| darks.iter().all(|&d| passes(d, c, policy.min_contrast)))
+            .unwrap_or(new_bg);
+        lowest = darks.iter().map(|&d| contrast(d, blended)).fold(lowest, f64::min);
+        if blended != color {
+            if let Some(p) = adjusted.background_pattern.as_mut() {
+                p.color = to_hex(blended);
+                record("background_pattern.color", &pattern.color, &p.color);
+            }
+        }
+    }
+
+    // Logo: shrink it in steps of 1% of its size until error correction covers it
+    if let Some(logo) = &options.logo {
+        let mut trial = logo.clone();
+        let mut fits = |size: f64| {
+            trial.size = size;
+            check_logo(qr, &trial).fits()
+        };
+        if !fits(logo.size) {
+            let size = (1..100)
+                .map(|step| logo.size * (1.0 - step as f64 / 100.0))
+                .find(|&size| fits(size))
+                .ok_or_else(|| {
+                    QrError::InvalidArgument(format!(
+                        "The logo hides more than {:?} error correction restores at any size; use a higher level",
+                        qr.ecl
+                    ))
+                })?;
+            if let Some(l) = adjusted.logo.as_mut() {
+                l.size = size;
+            }
+            record("logo.size", &logo.size.to_string(), &size.to_string());
+        }
+    }
+
+    // Module size: trim the quiet zone, never below the spec's
+    let module_px = |margin: usize| display_px / (qr.size() + 2 * margin) as f64;
+    if module_px(options.margin) < policy.min_module_px {
+        let floor = options.margin.min(SPEC_QUIET_ZONE);
+        let margin = (floor..options.margin)
+            .rev()
+            .find(|&m| module_px(m) >= policy.min_module_px)
+            .ok_or_else(|| {
+                let needed = ((qr.size() + 2 * floor) as f64 * policy.min_module_px).ceil();
+                QrError::InvalidArgument(format!(
+                    "At {}px, modules are {:.2}px, under the {}px minimum; display the code at least {}px wide",
+                    display_px,
+                    module_px(floor),
+                    policy.min_module_px,
+                    needed
+                ))
+            })?;
+        adjusted.margin = margin;
+        record("margin", &options.margin.to_string(), &margin.to_string());
+    }
+
+    Ok(AccessibleTheme {
+        module_px: module_px(adjusted.margin),
+        options: adjusted,
+        adjustments,
+        contrast: lowest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_qr, EyeOverride, ErrorCorrectionLevel, Gradient, GradientStop};
+
+    fn qr() -> QrCode {
+        generate_qr("https://holi.tools/a11y", ErrorCorrectionLevel::Medium).unwrap()
+    }
+
+    fn themed(fg: &str, bg: &str) -> StyledRenderOptions {
+        StyledRenderOptions { fg_color: fg.to_string(), bg_color: bg.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_compliant_theme_is_unchanged() {
+        let theme = enforce_accessible_theme(&qr(), &StyledRenderOptions::default(), 400.0, &Default::default()).unwrap();
+        assert!(theme.adjustments.is_empty());
+        assert!((theme.contrast - 21.0).abs() < 1e-9);
+        assert!(theme.module_px >= DEFAULT_MIN_MODULE_PX);
+    }
+
+    #[test]
+    fn test_low_contrast_is_raised_minimally() {
+        let policy = AccessibilityPolicy::default();
+        let requested = themed("#7A7AE0", "#FFFFFF");
+        let theme = enforce_accessible_theme(&qr(), &requested, 400.0, &policy).unwrap();
+        let (fg, bg) = (parse_hex(&theme.options.fg_color).unwrap(), parse_hex(&theme.options.bg_color).unwrap());
+        assert!(contrast(fg, bg) >= policy.min_contrast);
+        assert!(contrast(fg, bg) < policy.min_contrast + 0.5, "no darker than needed");
+        assert_eq!(theme.options.bg_color, "#FFFFFF", "white can't get lighter; only fg moves");
+        assert!(fg[2] > fg[0], "keeps its blue hue");
+        assert_eq!(theme.adjustments.len(), 1);
+        assert_eq!(theme.adjustments[0].field, "fg_color");
+
+        // Inverted themes are turned dark on light, and pass validation
+        let theme = enforce_accessible_theme(&qr(), &themed("#999999", "#333333"), 400.0, &policy).unwrap();
+        let (fg, bg) = (parse_hex(&theme.options.fg_color).unwrap(), parse_hex(&theme.options.bg_color).unwrap());
+        assert!(contrast(fg, bg) >= policy.min_contrast && fg[0] < bg[0]);
+        assert!(crate::validate_options(&theme.options).is_empty(), "{:?}", crate::validate_options(&theme.options));
+        let theme = enforce_accessible_theme(&qr(), &themed("#FFFFFF", "#000000"), 400.0, &policy).unwrap();
+        assert_eq!((theme.options.fg_color.as_str(), theme.options.bg_color.as_str()), ("#000000", "#FFFFFF"));
+        let fields: Vec<_> = theme.adjustments.iter().map(|a| a.field.as_str()).collect();
+        assert_eq!(fields, ["fg_color", "bg_color"]);
+
+        // Mid-grey on mid-grey needs both to move
+        let max = AccessibilityPolicy { min_contrast: 21.0, ..policy };
+        let theme = enforce_accessible_theme(&qr(), &themed("#808080", "#888888"), 400.0, &max).unwrap();
+        assert_eq!((theme.options.fg_color.as_str(), theme.options.bg_color.as_str()), ("#000000", "#FFFFFF"));
+    }
+
+    #[test]
+    fn test_eye_colors_and_transparency() {
+        let mut options = themed("#000000", "transparent");
+        options.eye_overrides[EyeCorner::TopRight as usize] =
+            EyeOverride { color: Some("#FFAA00".to_string()), ..Default::default() };
+        let theme = enforce_accessible_theme(&qr(), &options, 400.0, &Default::default()).unwrap();
+        assert_eq!(theme.options.bg_color, "transparent");
+        assert_eq!(theme.adjustments.len(), 1);
+        assert_eq!(theme.adjustments[0].field, "eyes.top_right.color");
+        assert!(theme.contrast >= DEFAULT_MIN_CONTRAST);
+
+        // Each gradient stop is a dark color of its own
+        let stop = |offset, color: &str| GradientStop { offset, color: color.to_string() };
+        let gradient = Gradient { stops: vec![stop(0.0, "#000000"), stop(1.0, "#AAAAAA")], ..Default::default() };
+        let options = StyledRenderOptions { fg_gradient: Some(gradient), ..Default::default() };
+        let theme = enforce_accessible_theme(&qr(), &options, 400.0, &Default::default()).unwrap();
+        assert_eq!(theme.adjustments.len(), 1);
+        assert_eq!(theme.adjustments[0].field, "fg_gradient.stops[1].color");
+        let stops = &theme.options.fg_gradient.as_ref().unwrap().stops;
+        assert!(contrast(parse_hex(&stops[1].color).unwrap(), [255.0; 3]) >= DEFAULT_MIN_CONTRAST);
+        assert!(theme.contrast >= DEFAULT_MIN_CONTRAST);
+
+        // An eye lighter than the background is darkened, even with contrast to spare
+        let mut options = themed("#000000", "#555555");
+        options.eye_overrides[EyeCorner::TopLeft as usize] =
+            EyeOverride { color: Some("#FFFFFF".to_string()), ..Default::default() };
+        let policy = AccessibilityPolicy { min_contrast: 3.0, ..Default::default() };
+        let theme = enforce_accessible_theme(&qr(), &options, 400.0, &policy).unwrap();
+        let eye = theme.options.eye_overrides[EyeCorner::TopLeft as usize].color.as_deref().and_then(parse_hex).unwrap();
+        assert!(eye[0] < 0x55 as f64);
+        assert!(crate::validate_options(&theme.options).is_empty());
+
+        let named = themed("navy", "#FFFFFF");
+        assert!(matches!(
+            enforce_accessible_theme(&qr(), &named, 400.0, &Default::default()),
+            Err(QrError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_pattern_and_logo() {
+        use crate::{check_logo, BackgroundPattern, Logo, LogoImage};
+
+        // A mid-grey pattern is pulled toward the white background
+        let options = StyledRenderOptions {
+            background_pattern: Some(BackgroundPattern { color: "#808080".to_string(), ..Default::default() }),
+            ..Default::default()
+        };
+        let theme = enforce_accessible_theme(&qr(), &options, 400.0, &Default::default()).unwrap();
+        assert_eq!(theme.adjustments.len(), 1);
+        assert_eq!(theme.adjustments[0].field, "background_pattern.color");
+        let pattern = parse_hex(&theme.options.background_pattern.as_ref().unwrap().color).unwrap();
+        assert!(contrast([0.0; 3], pattern) >= DEFAULT_MIN_CONTRAST);
+        assert!((theme.contrast - contrast([0.0; 3], pattern)).abs() < 1e-9);
+        // A light one is left alone
+        let options = StyledRenderOptions { background_pattern: Some(BackgroundPattern::default()), ..Default::default() };
+        assert!(enforce_accessible_theme(&qr(), &options, 400.0, &Default::default()).unwrap().adjustments.is_empty());
+
+        // A logo too large for the error correction is shrunk until it fits
+        let logo = Logo {
+            image: LogoImage::Svg { markup: r#"<circle cx="5" cy="5" r="5"/>"#.to_string(), width: 10.0, height: 10.0 },
+            size: 0.3,
+            padding: 1,
+        };
+        let qr = generate_qr("https://holi.tools/a11y", ErrorCorrectionLevel::Low).unwrap();
+        assert!(!check_logo(&qr, &logo).fits());
+        let options = StyledRenderOptions { logo: Some(logo.clone()), ..Default::default() };
+        let theme = enforce_accessible_theme(&qr, &options, 400.0, &Default::default()).unwrap();
+        assert_eq!(theme.adjustments.len(), 1);
+        assert_eq!(theme.adjustments[0].field, "logo.size");
+        let shrunk = theme.options.logo.as_ref().unwrap();
+        assert!(shrunk.size < logo.size && check_logo(&qr, shrunk).fits());
+
+        // Padding alone covers the finders: no size helps
+        let options = StyledRenderOptions { logo: Some(Logo { padding: 100, ..logo }), ..Default::default() };
+        let err = enforce_accessible_theme(&qr, &options, 400.0, &Default::default()).unwrap_err();
+        assert!(err.to_string().contains("higher level"), "{}", err);
+    }
+
+    #[test]
+    fn test_module_size_trims_quiet_zone() {
+        let qr = qr();
+        let n = qr.size();
+        let options = StyledRenderOptions { margin: 10, ..Default::default() };
+        // Room for 4px modules with a 6-module quiet zone, not with 10
+        let display = ((n + 12) * 4) as f64;
+        let theme = enforce_accessible_theme(&qr, &options, display, &Default::default()).unwrap();
+        assert_eq!(theme.options.margin, 6);
+        assert_eq!(theme.adjustments[0].field, "margin");
+        assert!(theme.module_px >= DEFAULT_MIN_MODULE_PX);
+
+        let too_small = ((n + 8) * 4 - 1) as f64;
+        let err = enforce_accessible_theme(&qr, &options, too_small, &Default::default()).unwrap_err();
+        assert!(err.to_string().contains(&format!("at least {}px", (n + 8) * 4)), "{}", err);
+    }
+}
@@ -0,0 +1,290 @@
+//! Artistic QR codes tinted from a reference image
+//!
+//! Every module takes the average color of the image area behind it, then
+//! dark modules are darkened and light modules lightened until their luma
+//! is on the right side of a threshold, so the decoder still sees a clean
+//! two-tone code. `render_artistic` checks the result with `verify_svg` and
+//! widens the gap until it scans.
+
+use crate::error::QrError;
+use crate::qr::QrCode;
+use crate::render::padded_size;
+use std::fmt::Write;
+
+/// Tinting limits for `render_artistic_svg` and `render_artistic`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtisticOptions {
+    /// Quiet zone (in modules); tinted like the light modules
+    pub margin: usize,
+    /// Highest luma (0-255) a dark module may keep
+    pub dark_max_luma: u8,
+    /// Lowest luma (0-255) a light module may keep
+    pub light_min_luma: u8,
+    /// How much each failed verification widens the gap, on both sides
+    pub adjust_step: u8,
+    /// Renders tried by `render_artistic` before giving up
+    pub max_attempts: u32,
+}
+
+impl Default for ArtisticOptions {
+    fn default() -> Self {
+        Self {
+            margin: 4,
+            dark_max_luma: 100,
+            light_min_luma: 165,
+            adjust_step: 20,
+            max_attempts: 8,
+        }
+    }
+}
+
+/// An artistic render that passed verification
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtisticQr {
+    pub svg: String,
+    /// Thresholds the SVG was rendered with, after adjustment
+    pub dark_max_luma: u8,
+    pub light_min_luma: u8,
+    /// Renders needed (1 if the requested thresholds scanned)
+    pub attempts: u32,
+}
+
+/// Same weights as `rgba_to_luma`, so thresholds match what the decoder sees
+fn luma(rgb: [f64; 3]) -> f64 {
+    (rgb[0] * 299.0 + rgb[1] * 587.0 + rgb[2] * 114.0) / 1000.0
+}
+
+/// Scale `rgb` toward black until its luma is at most `max`
+fn darken(rgb: [f64; 3], max: u8) -> [u8; 3] {
+    let l = luma(rgb);
+    let k = if l > max as f64 { max as f64 / l } else { 1.0 };
+    rgb.map(|c| (c * k).floor() as u8)
+}
+
+/// Blend `rgb` toward white until its luma is at least `min`
+fn lighten(rgb: [f64; 3], min: u8) -> [u8; 3] {
+    let l = luma(rgb);
+    let k = if l < min as f64 { (255.0 - min as f64) / (255.0 - l) } else { 1.0 };
+    rgb.map(|c| 255 - ((255.0 - c) * k).floor() as u8)
+}
+
+/// Average color of each cell of a `cells`x`cells` grid laid over the
+/// central square of the image, alpha composited on white
+fn sample_cells(rgba: &[u8], width: u32, height: u32, cells: usize) -> Vec<[f64; 3]> {
+    let (w, h) = (width as usize, height as usize);
+    let side = w.min(h);
+    let (ox, oy) = ((w - side) / 2, (h - side) / 2);
+    let span = |cell: usize| {
+        let start = cell * side / cells;
+        start..(((cell + 1) * side / cells).max(start + 1))
+    };
+
+    let mut colors = Vec::with_capacity(cells * cells);
+    for cy in 0..cells {
+        for cx in 0..cells {
+            let (mut sum, mut count) = ([0.0; 3], 0.0);
+            for y in span(cy) {
+                for x in span(cx) {
+                    let p = &rgba[((oy + y) * w + ox + x) * 4..][..4];
+                    let alpha = p[3] as f64 / 255.0;
+                    for i in 0..3 {
+                        sum[i] += p[i] as f64 * alpha + 255.0 * (1.0 - alpha);
+                    }
+                    count += 1.0;
+                }
+            }
+            colors.push(sum.map(|c| c / count));
+        }
+    }
+    colors
+}
+
+/// Render `qr` tinted toward an RGBA reference image (row-major, e.g. canvas
+/// `ImageData`), without verification
+///
+/// The image is cropped to its central square and stretched over the code
+/// including the quiet zone.
+pub fn render_artistic_svg(
+    qr: &QrCode,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    options: &ArtisticOptions,
+) -> Result<String, QrError> {
+    let expected = (width as usize).checked_mul(height as usize).and_then(|n| n.checked_mul(4));
+    if width == 0 || height == 0 || expected != Some(rgba.len()) {
+        return Err(QrError::InvalidArgument(format!(
+            "Expected {}x{} RGBA pixels ({} bytes), got {} bytes",
+            width,
+            height,
+            width as u128 * height as u128 * 4,
+            rgba.len()
+        )));
+    }
+    if options.dark_max_luma >= options.light_min_luma {
+        return Err(QrError::InvalidArgument(
+            "Dark luma limit must be below the light luma limit".into(),
+        ));
+    }
+
+    let size = qr.size();
+    let margin = options.margin;
+    let total = padded_size(size, margin)?;
+    let modules = qr.matrix();
+    let colors = sample_cells(rgba, width, height, total);
+
+    let mut svg = String::new();
+    write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}" shape-rendering="crispEdges">"#,
+        total, total
+    )?;
+
+    // One rect per run of equal color in a row
+    for y in 0..total {
+        let mut x = 0;
+        while x < total {
+            let tint = |x: usize| {
+                let dark = x >= margin && y >= margin && modules.is_dark(x - margin, y - margin);
+                let color = colors[y * total + x];
+                if dark { darken(color, options.dark_max_luma) } else { lighten(color, options.light_min_luma) }
+            };
+            let color = tint(x);
+            let run = (x + 1..total).take_while(|&next| tint(next) == color).count() + 1;
+            write!(
+                svg,
+                r##"<rect x="{}" y="{}" width="{}" height="1" fill="#{:02X}{:02X}{:02X}"/>"##,
+                x, y, run, color[0], color[1], color[2]
+            )?;
+            x += run;
+        }
+    }
+    svg.push_str("</svg>");
+    Ok(svg)
+}
+
+/// Render an artistic QR that is verified to scan
+///
+/// Starts from the thresholds in `options` and, while the render doesn't
+/// decode back to `qr.text`, moves them `adjust_step` further apart (ending
+/// at plain black and white at the latest).
+///
+/// # Returns
+/// * `Ok(ArtisticQr)` - The first render that scanned
+/// * `Err(QrError)` - Bad image/options, or nothing scanned within `max_attempts`
+#[cfg(feature = "verify")]
+pub fn render_artistic(
+    qr: &QrCode,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    options: &ArtisticOptions,
+) -> Result<ArtisticQr, QrError> {
+    let mut options = options.clone();
+    let mut last_error = None;
+    for attempt in 1..=options.max_attempts {
+        let svg = render_artistic_svg(qr, rgba, width, height, &options)?;
+        match crate::verify_svg(&svg) {
+            Ok(text) if text == qr.text => {
+                return Ok(ArtisticQr {
+                    svg,
+                    dark_max_luma: options.dark_max_luma,
+                    light_min_luma: options.light_min_luma,
+                    attempts: attempt,
+                });
+            }
+            Ok(text) => last_error = Some(format!("decoded {:?}", text)),
+            Err(e) => last_error = Some(e.to_string()),
+        }
+        if options.dark_max_luma == 0 && options.light_min_luma == 255 {
+            break;
+        }
+        options.dark_max_luma = options.dark_max_luma.saturating_sub(options.adjust_step);
+        options.light_min_luma = options.light_min_luma.saturating_add(options.adjust_step);
+    }
+    Err(QrError::VerificationFailed(format!(
+        "Artistic QR did not scan: {}",
+        last_error.unwrap_or_else(|| "no attempts".into())
+    )))
+}
+
+/// Stub function when 'verify' feature is not enabled
+#[cfg(not(feature = "verify"))]
+pub fn render_artistic(
+    _qr: &QrCode,
+    _rgba: &[u8],
+    _width: u32,
+    _height: u32,
+    _options: &ArtisticOptions,
+) -> Result<ArtisticQr, QrError> {
+    Err(QrError::VerificationFailed(
+        "Verification not available. Enable 'verify' feature.".into()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_qr, ErrorCorrectionLevel};
+
+    /// A diagonal red-to-blue gradient over a yellow band
+    fn reference(side: u32) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity((side * side * 4) as usize);
+        for y in 0..side {
+            for x in 0..side {
+                let t = (x + y) * 255 / (2 * side);
+                let band = (side / 3..2 * side / 3).contains(&y);
+                let pixel = if band { [240, 200, 40, 255] } else { [255 - t as u8, 60, t as u8, 255] };
+                rgba.extend_from_slice(&pixel);
+            }
+        }
+        rgba
+    }
+
+    #[test]
+    fn test_tints_respect_thresholds() {
+        for rgb in [[255.0, 255.0, 255.0], [0.0, 0.0, 0.0], [200.0, 30.0, 90.0], [20.0, 240.0, 250.0]] {
+            assert!(luma(darken(rgb, 100).map(f64::from)) <= 100.0);
+            assert!(luma(lighten(rgb, 165).map(f64::from)) >= 165.0);
+        }
+        // Colors already on the right side are kept
+        assert_eq!(darken([10.0, 20.0, 30.0], 100), [10, 20, 30]);
+        assert_eq!(lighten([250.0, 240.0, 230.0], 165), [250, 240, 230]);
+    }
+
+    #[test]
+    fn test_artistic_svg() {
+        let qr = generate_qr("artistic", ErrorCorrectionLevel::High).unwrap();
+        let image = reference(64);
+        let svg = render_artistic_svg(&qr, &image, 64, 64, &ArtisticOptions::default()).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("shape-rendering=\"crispEdges\""));
+        // The image shows through: many distinct colors, not two
+        let mut fills: Vec<_> = svg.match_indices("fill=\"#").map(|(i, _)| &svg[i + 7..i + 13]).collect();
+        fills.sort_unstable();
+        fills.dedup();
+        assert!(fills.len() > 20);
+
+        assert!(render_artistic_svg(&qr, &image, 64, 63, &ArtisticOptions::default()).is_err());
+        let inverted = ArtisticOptions { dark_max_luma: 200, light_min_luma: 100, ..Default::default() };
+        assert!(render_artistic_svg(&qr, &image, 64, 64, &inverted).is_err());
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn test_artistic_scans() {
+        let text = "https://holi.tools/p/artistic";
+        let qr = generate_qr(text, ErrorCorrectionLevel::High).unwrap();
+        // Tall image: only the central square is used
+        let mut image = reference(96);
+        image.extend(reference(96));
+        let art = render_artistic(&qr, &image, 96, 192, &ArtisticOptions::default()).unwrap();
+        assert_eq!(crate::verify_svg(&art.svg).unwrap(), text);
+        assert!(art.dark_max_luma <= 100 && art.light_min_luma >= 165);
+
+        // Thresholds that start almost touching get pulled apart until it scans
+        let tight = ArtisticOptions { dark_max_luma: 127, light_min_luma: 128, ..Default::default() };
+        let art = render_artistic(&qr, &image, 96, 192, &tight).unwrap();
+        assert_eq!(crate::verify_svg(&art.svg).unwrap(), text);
+    }
+}
@@ -0,0 +1,254 @@
+//! Block statistics shared by the decoder and the camera hints
+//!
+//! rxing's `HybridBinarizer` thresholds each 8x8 block against the black
+//! points of the blocks around it, worked out from the block's minimum,
+//! maximum and mean luma, and keeps those statistics to itself.
+//! `BlockStats` is the same pass with the statistics kept: `BlockBinarizer`
+//! thresholds from them for the decoder, and `analyze_frames` turns the
+//! same numbers into scanning hints instead of working them out again.
+
+#[cfg(feature = "decode")]
+use rxing::common::{BitArray, BitMatrix, GlobalHistogramBinarizer, LineOrientation};
+#[cfg(feature = "decode")]
+use rxing::{Binarizer, Luma8LuminanceSource, LuminanceSource};
+#[cfg(feature = "decode")]
+use std::borrow::Cow;
+#[cfg(feature = "decode")]
+use std::cell::OnceCell;
+
+/// Block edge used by `HybridBinarizer`
+pub(crate) const BLOCK: usize = 8;
+/// At or below this max - min range a block is flat: no threshold of its own
+pub(crate) const MIN_DYNAMIC_RANGE: u8 = 24;
+
+/// Luma statistics of one block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Block {
+    pub(crate) min: u8,
+    pub(crate) max: u8,
+    pub(crate) sum: u32,
+    /// Steepest step between neighboring pixels
+    pub(crate) step: u8,
+}
+
+impl Block {
+    /// Whether the binarizer thresholds the block on its own contrast
+    pub(crate) fn textured(&self) -> bool {
+        self.max - self.min > MIN_DYNAMIC_RANGE
+    }
+}
+
+/// Statistics of every 8x8 block of a grayscale frame, row by row
+///
+/// As in `HybridBinarizer`, the last row and column of blocks are moved in
+/// to end at the frame's edge, overlapping their neighbors when a side
+/// isn't a multiple of 8. A frame under 8 pixels on a side has no blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BlockStats {
+    width: usize,
+    height: usize,
+    cols: usize,
+    rows: usize,
+    pub(crate) blocks: Vec<Block>,
+}
+
+impl BlockStats {
+    /// Statistics of `luma`, which must hold `width * height` bytes
+    pub(crate) fn new(luma: &[u8], width: usize, height: usize) -> Self {
+        let count = |side: usize| if side < BLOCK { 0 } else { side.div_ceil(BLOCK) };
+        let (cols, rows) = (count(width), count(height));
+        let mut blocks = Vec::with_capacity(cols * rows);
+        for row in 0..rows {
+            let top = (row * BLOCK).min(height - BLOCK);
+            for col in 0..cols {
+                let left = (col * BLOCK).min(width - BLOCK);
+                let pixel = |x: usize, y: usize| luma[(top + y) * width + left + x];
+                let mut block = Block { min: u8::MAX, max: 0, sum: 0, step: 0 };
+                for y in 0..BLOCK {
+                    for x in 0..BLOCK {
+                        let p = pixel(x, y);
+                        (block.min, block.max) = (block.min.min(p), block.max.max(p));
+                        block.sum += p as u32;
+                        if x + 1 < BLOCK {
+                            block.step = block.step.max(p.abs_diff(pixel(x + 1, y)));
+                        }
+                        if y + 1 < BLOCK {
+                            block.step = block.step.max(p.abs_diff(pixel(x, y + 1)));
+                        }
+                    }
+                }
+                blocks.push(block);
+            }
+        }
+        BlockStats { width, height, cols, rows, blocks }
+    }
+
+    /// Pixel offset of block `(col, row)`'s top-left corner
+    #[cfg(feature = "decode")]
+    fn origin(&self, col: usize, row: usize) -> (usize, usize) {
+        ((col * BLOCK).min(self.width - BLOCK), (row * BLOCK).min(self.height - BLOCK))
+    }
+
+    /// Black point of every block: its mean, or for a flat block half its
+    /// minimum, raised to the black point of the blocks above and to the
+    /// left when it is darker than that
+    #[cfg(feature = "decode")]
+    fn black_points(&self) -> Vec<u32> {
+        let mut points = vec![0u32; self.blocks.len()];
+        for (i, block) in self.blocks.iter().enumerate() {
+            let (col, row) = (i % self.cols, i / self.cols);
+            points[i] = if block.textured() {
+                block.sum / (BLOCK * BLOCK) as u32
+            } else if col > 0 && row > 0 {
+                let neighbors = (points[i - self.cols] + 2 * points[i - 1] + points[i - self.cols - 1]) / 4;
+                if (block.min as u32) < neighbors { neighbors } else { block.min as u32 / 2 }
+            } else {
+                block.min as u32 / 2
+            };
+        }
+        points
+    }
+
+    /// Dark pixels of `luma`: each block thresholded at the mean black
+    /// point of the 5x5 blocks around it. `None` if the frame is under 5
+    /// blocks on a side, where `HybridBinarizer` falls back to a global
+    /// histogram.
+    #[cfg(feature = "decode")]
+    fn threshold(&self, luma: &[u8]) -> Option<BitMatrix> {
+        if self.cols < 5 || self.rows < 5 {
+            return None;
+        }
+        let points = self.black_points();
+        let mut matrix = BitMatrix::new(self.width as u32, self.height as u32).ok()?;
+        for row in 0..self.rows {
+            let cy = row.clamp(2, self.rows - 3);
+            for col in 0..self.cols {
+                let cx = col.clamp(2, self.cols - 3);
+                let sum: u32 = (cy - 2..=cy + 2).flat_map(|y| &points[y * self.cols + cx - 2..=y * self.cols + cx + 2]).sum();
+                let threshold = sum / 25;
+                let (left, top) = self.origin(col, row);
+                for y in top..top + BLOCK {
+                    for x in left..left + BLOCK {
+                        if luma[y * self.width + x] as u32 <= threshold {
+                            matrix.set(x as u32, y as u32);
+                        }
+                    }
+                }
+            }
+        }
+        Some(matrix)
+    }
+}
+
+/// `HybridBinarizer` over precomputed `BlockStats`
+///
+/// 1D rows and frames too small for block thresholding go through rxing's
+/// `GlobalHistogramBinarizer`, as in `HybridBinarizer`.
+#[cfg(feature = "decode")]
+pub(crate) struct BlockBinarizer {
+    histogram: GlobalHistogramBinarizer<Luma8LuminanceSource>,
+    stats: BlockStats,
+    matrix: OnceCell<BitMatrix>,
+}
+
+#[cfg(feature = "decode")]
+impl BlockBinarizer {
+    /// Binarizer for `source`, working out its statistics
+    pub(crate) fn new(source: Luma8LuminanceSource) -> Self {
+        let stats = BlockStats::new(&source.get_matrix(), source.get_width(), source.get_height());
+        Self::with_stats(source, stats)
+    }
+
+    /// Binarizer for `source`, whose statistics are already known
+    pub(crate) fn with_stats(source: Luma8LuminanceSource, stats: BlockStats) -> Self {
+        BlockBinarizer {
+            histogram: GlobalHistogramBinarizer::new(source),
+            stats,
+            matrix: OnceCell::new(),
+        }
+    }
+}
+
+#[cfg(feature = "decode")]
+impl Binarizer for BlockBinarizer {
+    type Source = Luma8LuminanceSource;
+
+    fn get_luminance_source(&self) -> &Self::Source {
+        self.histogram.get_luminance_source()
+    }
+
+    fn get_black_row(&self, y: usize) -> rxing::common::Result<Cow<'_, BitArray>> {
+        self.histogram.get_black_row(y)
+    }
+
+    fn get_black_row_from_matrix(&self, y: usize) -> rxing::common::Result<Cow<'_, BitArray>> {
+        match self.matrix.get() {
+            Some(matrix) => Ok(Cow::Owned(matrix.getRow(y as u32))),
+            None => self.get_black_row(y),
+        }
+    }
+
+    fn get_black_matrix(&self) -> rxing::common::Result<&BitMatrix> {
+        if let Some(matrix) = self.matrix.get() {
+            return Ok(matrix);
+        }
+        let luma = self.get_luminance_source().get_matrix();
+        let matrix = match self.stats.threshold(&luma) {
+            Some(matrix) => matrix,
+            None => self.histogram.get_black_matrix()?.clone(),
+        };
+        Ok(self.matrix.get_or_init(|| matrix))
+    }
+
+    fn get_black_line(
+        &self,
+        l: usize,
+        lt: LineOrientation,
+    ) -> rxing::common::Result<Cow<'_, BitArray>> {
+        self.histogram.get_black_line(l, lt)
+    }
+
+    fn create_binarizer(&self, source: Self::Source) -> Self {
+        Self::new(source)
+    }
+
+    fn get_width(&self) -> usize {
+        self.histogram.get_width()
+    }
+
+    fn get_height(&self) -> usize {
+        self.histogram.get_height()
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "decode")]
+mod tests {
+    use super::*;
+    use rxing::common::HybridBinarizer;
+
+    #[test]
+    fn test_matches_hybrid_binarizer() {
+        // Gradients, flat patches and noise, on sides that are and aren't
+        // multiples of the block
+        for (w, h) in [(64, 48), (75, 53), (20, 90)] {
+            let luma: Vec<u8> = (0..w * h)
+                .map(|i| {
+                    let (x, y) = (i % w, i / w);
+                    let noise = ((i * 2654435761usize) >> 13) as u8 % 40;
+                    if (x / 5 + y / 7) % 3 == 0 { noise } else { (x * 3 + y) as u8 / 2 + 100 }
+                })
+                .collect();
+            let ours = BlockBinarizer::new(Luma8LuminanceSource::new(luma.clone(), w as u32, h as u32));
+            let theirs = HybridBinarizer::new(Luma8LuminanceSource::new(luma, w as u32, h as u32));
+            assert_eq!(ours.get_black_matrix().unwrap(), theirs.get_black_matrix().unwrap(), "{}x{}", w, h);
+        }
+    }
+
+    #[test]
+    fn test_small_frames_have_no_blocks() {
+        assert!(BlockStats::new(&[0; 7 * 100], 7, 100).blocks.is_empty());
+        let stats = BlockStats::new(&[9; 12 * 8], 12, 8);
+        assert_eq!(stats.blocks, [Block { min: 9, max: 9, sum: 9 * 64, step: 0 }; 2]);
+    }
+}
@@ -0,0 +1,330 @@
+//! Camera frame quality, for coaching the user while scanning
+//!
+//! When a live scan doesn't decode, the user needs to know what to change.
+//! `analyze_frames` looks at a few consecutive grayscale frames through the
+//! 8x8 block statistics the decoder thresholds with (minimum, maximum and
+//! mean luma per block), and turns them into `ScanHint`s the scanner UI can
+//! show. The sharpest frame is then decoded from those same statistics.
+
+use crate::binarize::BlockStats;
+use crate::error::QrError;
+
+/// Mean luma below which the frame is too dark to threshold reliably
+const DARK_LUMA: f32 = 50.0;
+/// Blocks whose darkest pixel is at least this bright are blown out
+const GLARE_LUMA: u8 = 245;
+/// Share of blown-out blocks that counts as glare
+const GLARE_SHARE: f32 = 0.05;
+/// Share of blocks with usable range below which there is nothing to read
+const MIN_TEXTURED_SHARE: f32 = 0.02;
+/// `FrameStats::sharpness` below this is out of focus or smeared
+const MIN_SHARPNESS: f32 = 0.35;
+/// Mean change between frames above this means the camera is moving
+const MAX_MOTION: f32 = 0.08;
+/// Codes narrower than this share of the frame's shorter side are too far
+const MIN_COVERAGE: f32 = 0.25;
+
+/// Block statistics of one grayscale frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// Mean luma, 0-255
+    pub brightness: f32,
+    /// Share of blocks blown out to white (0.0-1.0)
+    pub glare: f32,
+    /// Share of blocks with enough range for the binarizer to threshold
+    pub textured: f32,
+    /// Steepest step between neighboring pixels relative to the block's
+    /// range, averaged over textured blocks: near 1.0 for crisp edges, low
+    /// when edges are smeared over several pixels. 0.0 without texture.
+    pub sharpness: f32,
+}
+
+/// Something the user can fix, most important first in `ScanAnalysis::hints`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanHint {
+    /// Turn on the torch or find more light
+    TooDark,
+    /// Tilt the code or the phone to move a reflection off it
+    Glare,
+    /// The camera moved between frames
+    HoldStill,
+    /// Out of focus; move back a little or tap to focus
+    Blurry,
+    /// Nothing in view has enough contrast to be a code
+    LowContrast,
+    /// The code was found but is small in the frame; move closer
+    TooFar,
+}
+
+impl ScanHint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TooDark => "tooDark",
+            Self::Glare => "glare",
+            Self::HoldStill => "holdStill",
+            Self::Blurry => "blurry",
+            Self::LowContrast => "lowContrast",
+            Self::TooFar => "tooFar",
+        }
+    }
+}
+
+/// Result of `analyze_frames`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanAnalysis {
+    /// Statistics of the sharpest frame, which the hints are based on
+    pub stats: FrameStats,
+    /// Mean change between consecutive frames, 0.0-1.0
+    pub motion: f32,
+    /// Text decoded from the sharpest frame (needs the 'decode' feature)
+    pub text: Option<String>,
+    /// Width of the decoded code as a share of the frame's shorter side
+    pub coverage: Option<f32>,
+    /// What to tell the user; empty when nothing looks wrong
+    pub hints: Vec<ScanHint>,
+}
+
+/// Block statistics of a `width * height` grayscale frame
+pub fn frame_stats(luma: &[u8], width: u32, height: u32) -> Result<FrameStats, QrError> {
+    let (w, h) = frame_size(luma, width, height)?;
+    Ok(summarize(luma, &BlockStats::new(luma, w, h)))
+}
+
+/// `FrameStats` of `luma` from its binarizer statistics
+fn summarize(luma: &[u8], stats: &BlockStats) -> FrameStats {
+    let (mut glare, mut textured, mut sharpness) = (0usize, 0usize, 0.0f32);
+    for block in &stats.blocks {
+        if block.min >= GLARE_LUMA {
+            glare += 1;
+        }
+        if block.textured() {
+            textured += 1;
+            sharpness += block.step as f32 / (block.max - block.min) as f32;
+        }
+    }
+    let brightness = luma.iter().map(|&l| l as u64).sum::<u64>() as f32 / luma.len().max(1) as f32;
+    let share = |n: usize| n as f32 / stats.blocks.len().max(1) as f32;
+    FrameStats {
+        brightness,
+        glare: share(glare),
+        textured: share(textured),
+        sharpness: sharpness / textured.max(1) as f32,
+    }
+}
+
+/// Estimate focus, lighting and motion over consecutive frames
+///
+/// # Arguments
+/// * `frames` - A few consecutive grayscale frames, oldest first
+/// * `width` / `height` - Size of every frame in pixels
+///
+/// # Returns
+/// * `Ok(ScanAnalysis)` - With the sharpest frame decoded when the
+///   'decode' feature is on
+/// * `Err(QrError::InvalidArgument)` - No frames, or a frame of the wrong size
+pub fn analyze_frames(frames: &[&[u8]], width: u32, height: u32) -> Result<ScanAnalysis, QrError> {
+    let mut blocks = Vec::with_capacity(frames.len());
+    for frame in frames {
+        let (w, h) = frame_size(frame, width, height)?;
+        let frame_blocks = BlockStats::new(frame, w, h);
+        blocks.push((summarize(frame, &frame_blocks), frame_blocks));
+    }
+    let Some((sharpest, (stats, sharpest_blocks))) = frames
+        .iter()
+        .zip(blocks)
+        .max_by(|a, b| a.1 .0.sharpness.total_cmp(&b.1 .0.sharpness))
+    else {
+        return Err(QrError::InvalidArgument("No frames to analyze".into()));
+    };
+
+    let pairs = frames.len().saturating_sub(1).max(1) as f32;
+    let motion = frames
+        .windows(2)
+        .map(|pair| {
+            let total: u64 = pair[0].iter().zip(pair[1]).map(|(&a, &b)| a.abs_diff(b) as u64).sum();
+            total as f32 / (255.0 * pair[0].len().max(1) as f32)
+        })
+        .sum::<f32>()
+        / pairs;
+
+    let (text, coverage) = decode_frame(sharpest, sharpest_blocks, width, height);
+
+    let mut hints = Vec::new();
+    if stats.brightness < DARK_LUMA {
+        hints.push(ScanHint::TooDark);
+    }
+    if stats.glare >= GLARE_SHARE {
+        hints.push(ScanHint::Glare);
+    }
+    if motion > MAX_MOTION {
+        hints.push(ScanHint::HoldStill);
+    }
+    // Focus can't be judged in the dark or without anything in view
+    if hints.first() != Some(&ScanHint::TooDark) {
+        if stats.textured < MIN_TEXTURED_SHARE {
+            hints.push(ScanHint::LowContrast);
+        } else if stats.sharpness < MIN_SHARPNESS && text.is_none() {
+            hints.push(ScanHint::Blurry);
+        }
+    }
+    if coverage.is_some_and(|c| c < MIN_COVERAGE) {
+        hints.push(ScanHint::TooFar);
+    }
+    Ok(ScanAnalysis { stats, motion, text, coverage, hints })
+}
+
+/// Frame dimensions, checked against the buffer length
+fn frame_size(luma: &[u8], width: u32, height: u32) -> Result<(usize, usize), QrError> {
+    let (w, h) = (width as usize, height as usize);
+    if w.checked_mul(h) != Some(luma.len()) {
+        return Err(QrError::InvalidArgument(format!(
+            "Expected {} luma bytes for {}x{}, got {}",
+            width as u64 * height as u64,
+            width,
+            height,
+            luma.len()
+        )));
+    }
+    Ok((w, h))
+}
+
+/// Decoded text and coverage of `frame`, if it scans, thresholded from the
+/// `blocks` already worked out for its hints
+#[cfg(feature = "decode")]
+fn decode_frame(frame: &[u8], blocks: BlockStats, width: u32, height: u32) -> (Option<String>, Option<f32>) {
+    let source = rxing::Luma8LuminanceSource::new(frame.to_vec(), width, height);
+    let mut bitmap = rxing::BinaryBitmap::new(crate::binarize::BlockBinarizer::with_stats(source, blocks));
+    match crate::verify::decode_bitmap(&mut bitmap) {
+        Ok((text, points)) => (Some(text), code_coverage(&points, width.min(height))),
+        Err(_) => (None, None),
+    }
+}
+
+/// Stub function when 'decode' feature is not enabled (nothing decodes)
+#[cfg(not(feature = "decode"))]
+fn decode_frame(_frame: &[u8], _blocks: BlockStats, _width: u32, _height: u32) -> (Option<String>, Option<f32>) {
+    (None, None)
+}
+
+/// Side of the code between finder centers over `short_side`, from the
+/// points `decode_bitmap` returns
+#[cfg(feature = "decode")]
+fn code_coverage(points: &[(f32, f32)], short_side: u32) -> Option<f32> {
+    let [bottom_left, top_left, top_right, ..] = points else {
+        return None;
+    };
+    let distance = |a: &(f32, f32), b: &(f32, f32)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+    let side = distance(bottom_left, top_left).max(distance(top_left, top_right));
+    Some(side / short_side.max(1) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_qr, ErrorCorrectionLevel};
+
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 240;
+
+    /// A camera-like frame: the code at `module_px` per module, centered on
+    /// a light background
+    fn frame(module_px: usize) -> Vec<u8> {
+        let qr = generate_qr("https://holi.tools/scan", ErrorCorrectionLevel::Medium).unwrap();
+        let (w, h) = (WIDTH as usize, HEIGHT as usize);
+        let side = qr.size() * module_px;
+        let (left, top) = ((w - side) / 2, (h - side) / 2);
+        (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let inside = (left..left + side).contains(&x) && (top..top + side).contains(&y);
+                if inside && qr.matrix().is_dark((x - left) / module_px, (y - top) / module_px) { 30 } else { 200 }
+            })
+            .collect()
+    }
+
+    /// Horizontal and vertical box blur of `radius`
+    fn blur(luma: &[u8], radius: usize) -> Vec<u8> {
+        let (w, h) = (WIDTH as usize, HEIGHT as usize);
+        let pass = |src: &[u8], dx: usize, dy: usize| -> Vec<u8> {
+            (0..w * h)
+                .map(|i| {
+                    let (x, y) = (i % w, i / w);
+                    let taps: Vec<u32> = (0..=2 * radius)
+                        .filter_map(|k| {
+                            let sx = (x + k * dx).checked_sub(radius * dx)?;
+                            let sy = (y + k * dy).checked_sub(radius * dy)?;
+                            (sx < w && sy < h).then(|| src[sy * w + sx] as u32)
+                        })
+                        .collect();
+                    (taps.iter().sum::<u32>() / taps.len() as u32) as u8
+                })
+                .collect()
+        };
+        pass(&pass(luma, 1, 0), 0, 1)
+    }
+
+    fn hints(frames: &[&[u8]]) -> Vec<ScanHint> {
+        analyze_frames(frames, WIDTH, HEIGHT).unwrap().hints
+    }
+
+    #[test]
+    fn test_good_frames_need_no_hints() {
+        let good = frame(6);
+        let analysis = analyze_frames(&[&good, &good], WIDTH, HEIGHT).unwrap();
+        assert_eq!(analysis.hints, []);
+        assert_eq!(analysis.motion, 0.0);
+        assert!(analysis.stats.sharpness > 0.9, "{:?}", analysis.stats);
+        #[cfg(feature = "decode")]
+        assert_eq!(analysis.text.as_deref(), Some("https://holi.tools/scan"));
+    }
+
+    #[test]
+    fn test_lighting_and_focus_hints() {
+        let good = frame(6);
+        let dark: Vec<u8> = good.iter().map(|&l| l / 6).collect();
+        assert_eq!(hints(&[&dark]), [ScanHint::TooDark]);
+
+        let mut glare = good.clone();
+        glare[..WIDTH as usize * 40].fill(255);
+        assert!(hints(&[&glare]).contains(&ScanHint::Glare));
+
+        let blurred = blur(&good, 4);
+        let stats = frame_stats(&blurred, WIDTH, HEIGHT).unwrap();
+        assert!(stats.sharpness < MIN_SHARPNESS, "{:?}", stats);
+        assert!(hints(&[&blurred]).contains(&ScanHint::Blurry));
+
+        let flat = vec![128; (WIDTH * HEIGHT) as usize];
+        assert_eq!(hints(&[&flat]), [ScanHint::LowContrast]);
+    }
+
+    #[test]
+    fn test_motion_between_frames() {
+        let good = frame(6);
+        let mut shifted = good.clone();
+        shifted.rotate_right(5);
+        let analysis = analyze_frames(&[&good, &shifted, &good], WIDTH, HEIGHT).unwrap();
+        assert!(analysis.motion > MAX_MOTION, "{}", analysis.motion);
+        assert!(analysis.hints.contains(&ScanHint::HoldStill));
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn test_small_code_is_too_far() {
+        let near = analyze_frames(&[&frame(6)], WIDTH, HEIGHT).unwrap();
+        assert!(near.coverage.unwrap() > MIN_COVERAGE);
+        let far = analyze_frames(&[&frame(2)], WIDTH, HEIGHT).unwrap();
+        assert!(far.text.is_some());
+        assert!(far.coverage.unwrap() < MIN_COVERAGE, "{:?}", far.coverage);
+        assert_eq!(far.hints, [ScanHint::TooFar]);
+    }
+
+    #[test]
+    fn test_rejects_bad_frames() {
+        assert!(analyze_frames(&[], WIDTH, HEIGHT).is_err());
+        assert!(analyze_frames(&[&[0; 10]], WIDTH, HEIGHT).is_err());
+        assert!(frame_stats(&[], u32::MAX, u32::MAX).is_err());
+        // Smaller than one block
+        let tiny = frame_stats(&[0; 4], 2, 2).unwrap();
+        assert_eq!((tiny.glare, tiny.textured, tiny.sharpness), (0.0, 0.0, 0.0));
+    }
+}
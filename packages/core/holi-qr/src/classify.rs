@@ -0,0 +1,377 @@
+//! Decoded payload classification
+//!
+//! Recognizes what a scanned QR code contains (URL, WiFi config, vCard,
+//! crypto address, ...) and flags patterns a scanner should warn about
+//! before acting on it.
+
+/// What the payload is, with the fields a UI needs to describe it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadKind {
+    /// Any `scheme:` URI that isn't one of the more specific kinds
+    Url { scheme: String, host: Option<String> },
+    /// `WIFI:T:<security>;S:<ssid>;P:<password>;H:<hidden>;;`
+    Wifi { ssid: String, security: String, hidden: bool },
+    /// `BEGIN:VCARD` or `MECARD:` contact
+    VCard { name: Option<String> },
+    /// BIP21-style URI (`bitcoin:...`) or a bare address
+    CryptoAddress { currency: String, address: String },
+    Email { address: String },
+    Phone { number: String },
+    Text,
+}
+
+/// Pattern worth warning about before opening the payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadRisk {
+    /// `data:` URI, can embed whole pages or files
+    DataUri,
+    /// `javascript:` / `vbscript:` URI
+    ScriptUri,
+    /// Host has an `xn--` label (punycode, possible lookalike domain)
+    PunycodeHost,
+    /// Host contains non-ASCII characters (possible lookalike domain)
+    NonAsciiHost,
+    /// Host is a raw IP address instead of a domain
+    IpAddressHost,
+    /// `user:pass@` before the host, often used to disguise the real host
+    CredentialsInUrl,
+    /// Plain `http://`
+    InsecureHttp,
+    /// Path ends in an executable or installer extension
+    ExecutableDownload,
+    /// WiFi network without a password
+    OpenWifi,
+}
+
+/// Result of `classify_payload`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadClassification {
+    pub kind: PayloadKind,
+    pub risks: Vec<PayloadRisk>,
+}
+
+impl PayloadClassification {
+    pub fn is_risky(&self) -> bool {
+        !self.risks.is_empty()
+    }
+}
+
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "msi", "bat", "cmd", "com", "scr", "ps1", "vbs", "jar", "apk", "dmg", "pkg", "deb", "rpm",
+    "appimage", "sh",
+];
+
+const CRYPTO_SCHEMES: &[&str] = &[
+    "bitcoin", "ethereum", "litecoin", "bitcoincash", "dogecoin", "monero", "solana",
+];
+
+/// Classify decoded QR text.
+pub fn classify_payload(text: &str) -> PayloadClassification {
+    let trimmed = text.trim();
+    let upper = trimmed.to_ascii_uppercase();
+
+    if upper.starts_with("WIFI:") {
+        return classify_wifi(&trimmed[5..]);
+    }
+    if upper.starts_with("BEGIN:VCARD") {
+        return plain(PayloadKind::VCard { name: vcard_name(trimmed) });
+    }
+    if upper.starts_with("MECARD:") {
+        return plain(PayloadKind::VCard { name: mecard_name(&trimmed[7..]) });
+    }
+    if let Some(address) = bare_crypto_address(trimmed) {
+        return plain(address);
+    }
+    if let Some(scheme) = uri_scheme(trimmed) {
+        return classify_uri(trimmed, &scheme);
+    }
+    if is_email(trimmed) {
+        return plain(PayloadKind::Email { address: trimmed.to_string() });
+    }
+    plain(PayloadKind::Text)
+}
+
+fn plain(kind: PayloadKind) -> PayloadClassification {
+    PayloadClassification { kind, risks: Vec::new() }
+}
+
+/// Lowercased scheme if `text` looks like a `scheme:rest` URI (no whitespace,
+/// so prose like "Note: ..." stays text).
+fn uri_scheme(text: &str) -> Option<String> {
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+    let colon = text.find(':')?;
+    let scheme = &text[..colon];
+    let mut chars = scheme.chars();
+    let first = chars.next()?;
+    let valid = first.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+fn classify_uri(text: &str, scheme: &str) -> PayloadClassification {
+    let rest = &text[scheme.len() + 1..];
+    match scheme {
+        "mailto" => {
+            let address = rest.split('?').next().unwrap_or_default().to_string();
+            return plain(PayloadKind::Email { address });
+        }
+        "tel" => return plain(PayloadKind::Phone { number: rest.to_string() }),
+        s if CRYPTO_SCHEMES.contains(&s) => {
+            let address = rest.split('?').next().unwrap_or_default().to_string();
+            return plain(PayloadKind::CryptoAddress { currency: s.to_string(), address });
+        }
+        _ => {}
+    }
+
+    let mut risks = Vec::new();
+    match scheme {
+        "data" => risks.push(PayloadRisk::DataUri),
+        "javascript" | "vbscript" => risks.push(PayloadRisk::ScriptUri),
+        "http" => risks.push(PayloadRisk::InsecureHttp),
+        _ => {}
+    }
+
+    let mut host = None;
+    if let Some(after) = rest.strip_prefix("//") {
+        let end = after.find(['/', '?', '#']).unwrap_or(after.len());
+        let authority = &after[..end];
+        let path = after[end..].split(['?', '#']).next().unwrap_or_default();
+
+        let host_port = match authority.rfind('@') {
+            Some(at) => {
+                risks.push(PayloadRisk::CredentialsInUrl);
+                &authority[at + 1..]
+            }
+            None => authority,
+        };
+        let h = strip_port(host_port).to_lowercase();
+        host_risks(&h, &mut risks);
+        if has_executable_extension(path) {
+            risks.push(PayloadRisk::ExecutableDownload);
+        }
+        host = Some(h);
+    }
+
+    PayloadClassification {
+        kind: PayloadKind::Url { scheme: scheme.to_string(), host },
+        risks,
+    }
+}
+
+fn strip_port(host_port: &str) -> &str {
+    if let Some(inner) = host_port.strip_prefix('[') {
+        // [ipv6]:port
+        return inner.split(']').next().unwrap_or_default();
+    }
+    match host_port.rfind(':') {
+        Some(i) if host_port[i + 1..].chars().all(|c| c.is_ascii_digit()) => &host_port[..i],
+        _ => host_port,
+    }
+}
+
+fn host_risks(host: &str, risks: &mut Vec<PayloadRisk>) {
+    if host.split('.').any(|label| label.starts_with("xn--")) {
+        risks.push(PayloadRisk::PunycodeHost);
+    }
+    if !host.is_ascii() {
+        risks.push(PayloadRisk::NonAsciiHost);
+    }
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        risks.push(PayloadRisk::IpAddressHost);
+    }
+}
+
+fn has_executable_extension(path: &str) -> bool {
+    let file = path.rsplit('/').next().unwrap_or_default();
+    match file.rsplit_once('.') {
+        Some((_, ext)) => EXECUTABLE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()),
+        None => false,
+    }
+}
+
+/// Split `K:V;K:V;;` fields, honoring `\;` `\:` `\\` escapes.
+fn split_fields(body: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    for c in body.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == ';' {
+            if let Some((k, v)) = current.split_once(':') {
+                fields.push((k.to_ascii_uppercase(), v.to_string()));
+            }
+            current.clear();
+        } else {
+            current.push(c);
+        }
+    }
+    if let Some((k, v)) = current.split_once(':') {
+        fields.push((k.to_ascii_uppercase(), v.to_string()));
+    }
+    fields
+}
+
+fn classify_wifi(body: &str) -> PayloadClassification {
+    let fields = split_fields(body);
+    let get = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+
+    let security = get("T").unwrap_or_else(|| "nopass".to_string());
+    let open = security.is_empty() || security.eq_ignore_ascii_case("nopass");
+    let kind = PayloadKind::Wifi {
+        ssid: get("S").unwrap_or_default(),
+        security: if open { "nopass".to_string() } else { security },
+        hidden: get("H").is_some_and(|h| h.eq_ignore_ascii_case("true")),
+    };
+    PayloadClassification {
+        kind,
+        risks: if open { vec![PayloadRisk::OpenWifi] } else { Vec::new() },
+    }
+}
+
+fn vcard_name(text: &str) -> Option<String> {
+    text.lines()
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let key = key.split(';').next()?.to_ascii_uppercase();
+            (key == "FN").then(|| value.trim().to_string())
+        })
+        .filter(|name| !name.is_empty())
+}
+
+fn mecard_name(body: &str) -> Option<String> {
+    split_fields(body)
+        .into_iter()
+        .find(|(k, _)| k == "N")
+        .map(|(_, v)| v.replace(',', " ").trim().to_string())
+}
+
+pub(crate) fn is_email(text: &str) -> bool {
+    match text.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !text.contains(char::is_whitespace)
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+        }
+        None => false,
+    }
+}
+
+fn bare_crypto_address(text: &str) -> Option<PayloadKind> {
+    let is_base58 = |s: &str| {
+        s.chars()
+            .all(|c| c.is_ascii_alphanumeric() && !matches!(c, '0' | 'O' | 'I' | 'l'))
+    };
+    let is_bech32_btc = (14..=74).contains(&text.len())
+        && text.to_ascii_lowercase().starts_with("bc1")
+        && text[3..].chars().all(|c| c.is_ascii_alphanumeric());
+    let is_legacy_btc = (26..=35).contains(&text.len())
+        && (text.starts_with('1') || text.starts_with('3'))
+        && is_base58(text);
+    let currency = if text.len() == 42
+        && text.starts_with("0x")
+        && text[2..].chars().all(|c| c.is_ascii_hexdigit())
+    {
+        "ethereum"
+    } else if is_bech32_btc || is_legacy_btc {
+        "bitcoin"
+    } else {
+        return None;
+    };
+    Some(PayloadKind::CryptoAddress {
+        currency: currency.to_string(),
+        address: text.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_https_url_is_clean() {
+        let c = classify_payload("https://holi.tools/qr?x=1");
+        assert_eq!(
+            c.kind,
+            PayloadKind::Url { scheme: "https".into(), host: Some("holi.tools".into()) }
+        );
+        assert!(!c.is_risky());
+    }
+
+    #[test]
+    fn test_url_risks() {
+        let c = classify_payload("http://user:pw@xn--pple-43d.com:8080/setup.EXE");
+        assert!(c.risks.contains(&PayloadRisk::InsecureHttp));
+        assert!(c.risks.contains(&PayloadRisk::CredentialsInUrl));
+        assert!(c.risks.contains(&PayloadRisk::PunycodeHost));
+        assert!(c.risks.contains(&PayloadRisk::ExecutableDownload));
+
+        assert!(classify_payload("https://аpple.com").risks.contains(&PayloadRisk::NonAsciiHost));
+        assert!(classify_payload("https://192.168.0.1/").risks.contains(&PayloadRisk::IpAddressHost));
+        assert!(classify_payload("https://[::1]:443/").risks.contains(&PayloadRisk::IpAddressHost));
+        assert_eq!(classify_payload("data:text/html;base64,PGgxPg==").risks, vec![PayloadRisk::DataUri]);
+        assert_eq!(classify_payload("javascript:alert(1)").risks, vec![PayloadRisk::ScriptUri]);
+    }
+
+    #[test]
+    fn test_wifi() {
+        let c = classify_payload(r"WIFI:T:WPA;S:my\;net;P:secret;H:true;;");
+        assert_eq!(
+            c.kind,
+            PayloadKind::Wifi { ssid: "my;net".into(), security: "WPA".into(), hidden: true }
+        );
+        assert!(!c.is_risky());
+
+        let open = classify_payload("WIFI:S:cafe;T:nopass;;");
+        assert_eq!(open.risks, vec![PayloadRisk::OpenWifi]);
+    }
+
+    #[test]
+    fn test_contacts() {
+        let vcard = "BEGIN:VCARD\nVERSION:3.0\nFN;CHARSET=UTF-8:Ada Lovelace\nEND:VCARD";
+        assert_eq!(classify_payload(vcard).kind, PayloadKind::VCard { name: Some("Ada Lovelace".into()) });
+        assert_eq!(
+            classify_payload("MECARD:N:Lovelace,Ada;TEL:123;;").kind,
+            PayloadKind::VCard { name: Some("Lovelace Ada".into()) }
+        );
+        assert_eq!(
+            classify_payload("mailto:ada@example.com?subject=hi").kind,
+            PayloadKind::Email { address: "ada@example.com".into() }
+        );
+        assert_eq!(classify_payload("ada@example.com").kind, PayloadKind::Email { address: "ada@example.com".into() });
+        assert_eq!(classify_payload("tel:+34600000000").kind, PayloadKind::Phone { number: "+34600000000".into() });
+    }
+
+    #[test]
+    fn test_crypto_addresses() {
+        assert_eq!(
+            classify_payload("bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=0.1").kind,
+            PayloadKind::CryptoAddress {
+                currency: "bitcoin".into(),
+                address: "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".into()
+            }
+        );
+        assert!(matches!(
+            classify_payload("0x52908400098527886E0F7030069857D2E4169EE7").kind,
+            PayloadKind::CryptoAddress { ref currency, .. } if currency == "ethereum"
+        ));
+        assert!(matches!(
+            classify_payload("1BoatSLRHtKNngkdXEeobR76b53LETtpyT").kind,
+            PayloadKind::CryptoAddress { ref currency, .. } if currency == "bitcoin"
+        ));
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(classify_payload("hello world").kind, PayloadKind::Text);
+        assert_eq!(classify_payload("").kind, PayloadKind::Text);
+        assert_eq!(classify_payload("Note: call me").kind, PayloadKind::Text);
+    }
+}
@@ -0,0 +1,254 @@
+//! Symbols with data fast_qr can't encode itself
+//!
+//! fast_qr writes one segment with no header. For Structured Append and ECI
+//! headers, and Kanji segments, it still lays out the symbol (function patterns, format and
+//! version information, mask), and the data modules are then rewritten with
+//! codewords built here: the bit stream and its padding, split into blocks,
+//! each with its Reed-Solomon check words, and interleaved.
+
+use crate::error::QrError;
+use crate::layout::placement_order;
+use crate::qr::{capacity, count_bits, data_codewords, ErrorCorrectionLevel, ALPHANUMERIC};
+use fast_qr::qr::QRBuilder;
+use fast_qr::{Mode, Module, Version};
+
+/// Error correction blocks per version (1-40) for L, M, Q, H (ISO/IEC 18004 table 9)
+const EC_BLOCKS: [[u8; 40]; 4] = [
+    [
+        1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18,
+        19, 19, 20, 21, 22, 24, 25,
+    ],
+    [
+        1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31,
+        33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ],
+    [
+        1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40,
+        43, 45, 48, 51, 53, 56, 59, 62, 65, 68,
+    ],
+    [
+        1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48,
+        51, 54, 57, 60, 63, 66, 70, 74, 77, 81,
+    ],
+];
+
+/// fast_qr's versions by number - 1
+const VERSIONS: [Version; 40] = [
+    Version::V01, Version::V02, Version::V03, Version::V04, Version::V05, Version::V06, Version::V07, Version::V08,
+    Version::V09, Version::V10, Version::V11, Version::V12, Version::V13, Version::V14, Version::V15, Version::V16,
+    Version::V17, Version::V18, Version::V19, Version::V20, Version::V21, Version::V22, Version::V23, Version::V24,
+    Version::V25, Version::V26, Version::V27, Version::V28, Version::V29, Version::V30, Version::V31, Version::V32,
+    Version::V33, Version::V34, Version::V35, Version::V36, Version::V37, Version::V38, Version::V39, Version::V40,
+];
+
+/// A `version` symbol holding the bit stream `data`
+///
+/// fast_qr lays out the function patterns and picks the mask for a symbol
+/// of `template` in `mode` (the same content, without the headers written
+/// here, cut to what fits); only the data modules are replaced.
+pub(crate) fn build_symbol(
+    data: BitBuffer,
+    template: &[u8],
+    mode: Mode,
+    version: usize,
+    ecl: ErrorCorrectionLevel,
+) -> Result<fast_qr::QRCode, QrError> {
+    let template = &template[..template.len().min(capacity(version, ecl, mode))];
+    let mut inner = QRBuilder::new(template)
+        .mode(mode)
+        .ecl(ecl.into())
+        .version(VERSIONS[version - 1])
+        .build()
+        .map_err(|e| QrError::GenerationFailed(format!("{:?}", e)))?;
+    let mask = inner.mask.ok_or_else(|| QrError::GenerationFailed("No mask was applied".into()))?;
+
+    let order = placement_order(&inner);
+    let codewords = interleave(&data.into_codewords(data_codewords(version, ecl)), version, ecl, order.len() / 8);
+
+    // Remainder bits past the last codeword stay light
+    for (bit, &module) in order.iter().enumerate() {
+        let dark = codewords.get(bit / 8).is_some_and(|c| c >> (7 - bit % 8) & 1 == 1);
+        inner.data[module] = Module::data(dark);
+    }
+    fast_qr::datamasking::mask(&mut inner, mask);
+    Ok(inner)
+}
+
+/// Bits written most significant first
+#[derive(Default)]
+pub(crate) struct BitBuffer {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitBuffer {
+    /// The low `width` bits of `value`
+    pub(crate) fn push(&mut self, value: usize, width: usize) {
+        for i in (0..width).rev() {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if let Some(last) = self.bytes.last_mut() {
+                *last |= ((value >> i & 1) as u8) << (7 - self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+
+    /// Mode indicator, character count and `data` encoded in `mode`
+    pub(crate) fn push_segment(&mut self, data: &[u8], mode: Mode, version: usize) {
+        let indicator = match mode {
+            Mode::Numeric => 0b0001,
+            Mode::Alphanumeric => 0b0010,
+            Mode::Byte => 0b0100,
+        };
+        self.push(indicator, 4);
+        self.push(data.len(), count_bits(mode, version));
+        match mode {
+            // 10 bits per 3 digits, 7 for 2, 4 for 1
+            Mode::Numeric => {
+                for digits in data.chunks(3) {
+                    let value = digits.iter().fold(0, |acc, d| acc * 10 + (d - b'0') as usize);
+                    self.push(value, [0, 4, 7, 10][digits.len()]);
+                }
+            }
+            // 11 bits per 2 characters, 6 for 1
+            Mode::Alphanumeric => {
+                for pair in data.chunks(2) {
+                    let value = pair
+                        .iter()
+                        .map(|c| ALPHANUMERIC.iter().position(|a| a == c).unwrap_or_default())
+                        .fold(0, |acc, c| acc * 45 + c);
+                    self.push(value, [0, 6, 11][pair.len()]);
+                }
+            }
+            Mode::Byte => {
+                for &byte in data {
+                    self.push(byte as usize, 8);
+                }
+            }
+        }
+    }
+
+    /// `count` data codewords: the bits, a terminator of up to four zero
+    /// bits, zeros to a byte boundary, then alternating pad codewords
+    fn into_codewords(mut self, count: usize) -> Vec<u8> {
+        // The rest of the last byte is zeros already; the terminator only
+        // needs a new one if fewer than four bits are left
+        if (8 - self.len % 8) % 8 < 4 && self.bytes.len() < count {
+            self.bytes.push(0);
+        }
+        for pad in [0xEC, 0x11].into_iter().cycle().take(count.saturating_sub(self.bytes.len())) {
+            self.bytes.push(pad);
+        }
+        self.bytes
+    }
+}
+
+/// Product in GF(256) modulo the QR field polynomial 0x11D
+fn gf_mul(a: u8, b: u8) -> u8 {
+    let (mut a, mut b, mut product) = (a as u16, b, 0u16);
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        a <<= 1;
+        if a & 0x100 != 0 {
+            a ^= 0x11D;
+        }
+        b >>= 1;
+    }
+    product as u8
+}
+
+/// `count` Reed-Solomon check words for `data`: the remainder of dividing it
+/// by (x - α^0)(x - α^1)...(x - α^(count-1))
+pub(crate) fn check_words(data: &[u8], count: usize) -> Vec<u8> {
+    // Generator coefficients below the leading 1, highest degree first
+    let mut generator = vec![0u8; count];
+    if let Some(last) = generator.last_mut() {
+        *last = 1;
+    }
+    let mut root = 1u8;
+    for _ in 0..count {
+        for j in 0..count {
+            generator[j] = gf_mul(generator[j], root) ^ generator.get(j + 1).copied().unwrap_or_default();
+        }
+        root = gf_mul(root, 2);
+    }
+
+    let mut remainder = vec![0u8; count];
+    for &byte in data {
+        let factor = byte ^ remainder.first().copied().unwrap_or_default();
+        remainder.rotate_left(1);
+        if let Some(last) = remainder.last_mut() {
+            *last = 0;
+        }
+        for (r, &g) in remainder.iter_mut().zip(&generator) {
+            *r ^= gf_mul(g, factor);
+        }
+    }
+    remainder
+}
+
+/// `data` split into the blocks of a `version` symbol, each followed by its
+/// check words, interleaved into the `total` codewords placed in the symbol
+fn interleave(data: &[u8], version: usize, ecl: ErrorCorrectionLevel, total: usize) -> Vec<u8> {
+    let level = match ecl {
+        ErrorCorrectionLevel::Low => 0,
+        ErrorCorrectionLevel::Medium => 1,
+        ErrorCorrectionLevel::Quartile => 2,
+        ErrorCorrectionLevel::High => 3,
+    };
+    let block_count = EC_BLOCKS[level][version - 1] as usize;
+    let check = (total - data.len()) / block_count;
+    // The last `total % block_count` blocks hold one more data codeword
+    let short = total / block_count - check;
+    let long_from = block_count - total % block_count;
+
+    let mut rest = data;
+    let blocks: Vec<(&[u8], Vec<u8>)> = (0..block_count)
+        .map(|i| {
+            let (block, tail) = rest.split_at(short + (i >= long_from) as usize);
+            rest = tail;
+            (block, check_words(block, check))
+        })
+        .collect();
+
+    let mut codewords = Vec::with_capacity(total);
+    for i in 0..=short {
+        codewords.extend(blocks.iter().filter_map(|(block, _)| block.get(i)));
+    }
+    for i in 0..check {
+        codewords.extend(blocks.iter().filter_map(|(_, words)| words.get(i)));
+    }
+    codewords
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: [ErrorCorrectionLevel; 4] =
+        [ErrorCorrectionLevel::Low, ErrorCorrectionLevel::Medium, ErrorCorrectionLevel::Quartile, ErrorCorrectionLevel::High];
+
+    #[test]
+    fn test_block_table() {
+        for (version, &fast_qr_version) in (1..=40).zip(&VERSIONS) {
+            let inner = QRBuilder::new("1").version(fast_qr_version).build().unwrap();
+            assert_eq!(inner.size, 17 + 4 * version);
+            let total = placement_order(&inner).len() / 8;
+            for (level, ecl) in LEVELS.into_iter().enumerate() {
+                let blocks = EC_BLOCKS[level][version - 1] as usize;
+                let check = total - data_codewords(version, ecl);
+                assert_eq!(check % blocks, 0, "version {} {:?}", version, ecl);
+                assert_eq!(interleave(&vec![0; data_codewords(version, ecl)], version, ecl, total).len(), total);
+            }
+        }
+        // ISO/IEC 18004 annex I: "01234567" at 1-M
+        assert_eq!(
+            check_words(&[0x10, 0x20, 0x0C, 0x56, 0x61, 0x80, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11], 10),
+            [0xA5, 0x24, 0xD4, 0xC1, 0xED, 0x36, 0xC7, 0x87, 0x2C, 0x55]
+        );
+    }
+}
@@ -0,0 +1,407 @@
+//! ISO/IEC 18004 conformance checks
+//!
+//! Checks generated symbols against the standard's tables and placement
+//! rules instead of anything fast_qr computes: format and version
+//! information against the reference codewords of Annexes C and D,
+//! function patterns (6.3), codeword counts (table 1), Reed-Solomon check
+//! words, character capacities (table 7) and the mask penalty rules of
+//! 7.8.3. A fast_qr upgrade that drifts from the standard fails here even
+//! if it still round-trips through its own decoder.
+//!
+//! fast_qr picks the mask with its own scoring (data modules only, before
+//! format information is placed), so its choice is not required to be the
+//! one with the lowest 7.8.3 penalty; decoders accept all eight.
+
+use crate::qr::data_codewords;
+use crate::{capacity, generate_qr, ErrorCorrectionLevel, Mode, QrCode};
+
+const LEVELS: [ErrorCorrectionLevel; 4] =
+    [ErrorCorrectionLevel::Low, ErrorCorrectionLevel::Medium, ErrorCorrectionLevel::Quartile, ErrorCorrectionLevel::High];
+
+/// Annex C, table C.1: format information as placed (masked with 0x5412),
+/// by level L, M, Q, H and then mask pattern 0-7
+const FORMAT_INFO: [[u32; 8]; 4] = [
+    [0x77C4, 0x72F3, 0x7DAA, 0x789D, 0x662F, 0x6318, 0x6C41, 0x6976],
+    [0x5412, 0x5125, 0x5E7C, 0x5B4B, 0x45F9, 0x40CE, 0x4F97, 0x4AA0],
+    [0x355F, 0x3068, 0x3F31, 0x3A06, 0x24B4, 0x2183, 0x2EDA, 0x2BED],
+    [0x1689, 0x13BE, 0x1CE7, 0x19D0, 0x0762, 0x0255, 0x0D0C, 0x083B],
+];
+
+/// Annex D, table D.1: version information for versions 7-40
+const VERSION_INFO: [u32; 34] = [
+    0x07C94, 0x085BC, 0x09A99, 0x0A4D3, 0x0BBF6, 0x0C762, 0x0D847, 0x0E60D, 0x0F928, 0x10B78, 0x1145D, 0x12A17,
+    0x13532, 0x149A6, 0x15683, 0x168C9, 0x177EC, 0x18EC4, 0x191E1, 0x1AFAB, 0x1B08E, 0x1CC1A, 0x1D33F, 0x1ED75,
+    0x1F250, 0x209D5, 0x216F0, 0x228BA, 0x2379F, 0x24B0B, 0x2542E, 0x26A64, 0x27541, 0x28C69,
+];
+
+/// Annex E, table E.1: alignment pattern row/column centers, some versions
+const ALIGNMENT_CENTERS: [(usize, &[usize]); 6] = [
+    (1, &[]),
+    (2, &[6, 18]),
+    (7, &[6, 22, 38]),
+    (14, &[6, 26, 46, 66]),
+    (32, &[6, 34, 60, 86, 112, 138]),
+    (40, &[6, 30, 58, 86, 114, 142, 170]),
+];
+
+/// Table 7 for versions 1 and 40: numeric, alphanumeric and byte capacity
+/// at L, M, Q, H
+const CAPACITY: [(usize, [[usize; 3]; 4]); 2] = [
+    (1, [[41, 25, 17], [34, 20, 14], [27, 16, 11], [17, 10, 7]]),
+    (40, [[7089, 4296, 2953], [5596, 3391, 2331], [3993, 2420, 1663], [3057, 1852, 1273]]),
+];
+
+/// Remainder of a BCH division of `data` (already shifted) by `generator`
+fn bch_remainder(mut data: u32, generator: u32) -> u32 {
+    let degree = 31 - generator.leading_zeros();
+    while data >> degree != 0 {
+        data ^= generator << (31 - data.leading_zeros() - degree);
+    }
+    data
+}
+
+/// The symbol at exactly `version`: as many digits as fit
+fn symbol(version: usize, ecl: ErrorCorrectionLevel) -> QrCode {
+    let qr = generate_qr(&"7".repeat(capacity(version, ecl, Mode::Numeric)), ecl).unwrap();
+    assert_eq!(qr.size(), 17 + 4 * version);
+    qr
+}
+
+/// Alignment pattern centers by the Annex E rule: first at 6, last at
+/// size - 7, the rest evenly spaced by an even step
+fn alignment_centers(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = if version == 32 { 26 } else { (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2 };
+    let last = 17 + 4 * version - 7;
+    let mut centers: Vec<usize> = (0..count - 1).map(|i| last - i * step).collect();
+    centers.push(6);
+    centers.reverse();
+    centers
+}
+
+/// Alignment patterns that do not overlap a finder
+fn alignment_patterns(version: usize) -> Vec<(usize, usize)> {
+    let centers = alignment_centers(version);
+    let last = centers.last().copied().unwrap_or(0);
+    let mut patterns = Vec::new();
+    for &y in &centers {
+        for &x in &centers {
+            if (x, y) != (6, 6) && (x, y) != (6, last) && (x, y) != (last, 6) {
+                patterns.push((x, y));
+            }
+        }
+    }
+    patterns
+}
+
+/// Whether (x, y) is a function module or reserved for format/version
+/// information, per 6.3 alone
+fn is_function(version: usize, x: usize, y: usize) -> bool {
+    let size = 17 + 4 * version;
+    // Finders with separators, plus the format information next to them
+    let corner = (x < 9 || x >= size - 8) && y < 9 || x < 9 && y >= size - 8;
+    let timing = x == 6 || y == 6;
+    let version_info = version >= 7 && ((x >= size - 11 && y < 6) || (y >= size - 11 && x < 6));
+    let alignment = alignment_patterns(version).iter().any(|&(cx, cy)| x.abs_diff(cx) <= 2 && y.abs_diff(cy) <= 2);
+    corner || timing || version_info || alignment
+}
+
+/// Both copies of the format information as 15-bit values
+fn read_format(qr: &QrCode) -> (u32, u32) {
+    let (m, size) = (qr.matrix(), qr.size());
+    let bit = |x: usize, y: usize, i: usize| (m.is_dark(x, y) as u32) << i;
+    let mut first = 0;
+    let mut second = 0;
+    for i in 0..15 {
+        first |= match i {
+            0..=5 => bit(8, i, i),
+            6 => bit(8, 7, i),
+            7 => bit(8, 8, i),
+            8 => bit(7, 8, i),
+            _ => bit(14 - i, 8, i),
+        };
+        second |= if i < 8 { bit(size - 1 - i, 8, i) } else { bit(8, size - 15 + i, i) };
+    }
+    (first, second)
+}
+
+/// Level index (L, M, Q, H) and mask pattern of `qr`, from table C.1
+fn format_of(qr: &QrCode) -> (usize, usize) {
+    let (first, second) = read_format(qr);
+    assert_eq!(first, second, "format information copies differ");
+    (0..32)
+        .map(|i| (i / 8, i % 8))
+        .find(|&(level, mask)| FORMAT_INFO[level][mask] == first)
+        .unwrap_or_else(|| panic!("{:#06x} is not in table C.1", first))
+}
+
+/// Data masking condition of 7.8.2 for module (x, y)
+fn mask_applies(mask: usize, x: usize, y: usize) -> bool {
+    let (i, j) = (y, x);
+    match mask {
+        0 => (i + j) % 2 == 0,
+        1 => i % 2 == 0,
+        2 => j % 3 == 0,
+        3 => (i + j) % 3 == 0,
+        4 => (i / 2 + j / 3) % 2 == 0,
+        5 => (i * j) % 2 + (i * j) % 3 == 0,
+        6 => ((i * j) % 2 + (i * j) % 3) % 2 == 0,
+        _ => ((i + j) % 2 + (i * j) % 3) % 2 == 0,
+    }
+}
+
+/// Unmasked bits of every non-function module in placement order (7.7.3)
+fn read_data_bits(qr: &QrCode) -> Vec<bool> {
+    let size = qr.size();
+    let version = (size - 17) / 4;
+    let (_, mask) = format_of(qr);
+    let mut bits = Vec::new();
+    let mut upward = true;
+    let mut right = size - 1;
+    loop {
+        for i in 0..size {
+            let y = if upward { size - 1 - i } else { i };
+            for x in [right, right - 1] {
+                if !is_function(version, x, y) {
+                    bits.push(qr.matrix().is_dark(x, y) != mask_applies(mask, x, y));
+                }
+            }
+        }
+        upward = !upward;
+        if right < 2 {
+            break;
+        }
+        // The vertical timing pattern is skipped entirely
+        right = if right == 8 { 5 } else { right - 2 };
+    }
+    bits
+}
+
+/// Product in GF(256) modulo the QR field polynomial 0x11D
+fn gf_mul(a: u8, b: u8) -> u8 {
+    let (mut a, mut b, mut product) = (a as u16, b, 0u16);
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        a <<= 1;
+        if a & 0x100 != 0 {
+            a ^= 0x11D;
+        }
+        b >>= 1;
+    }
+    product as u8
+}
+
+/// Whether `codewords` (data then check words) is a Reed-Solomon codeword
+/// with `check` check words: zero at α^0 through α^(check-1)
+fn rs_valid(codewords: &[u8], check: usize) -> bool {
+    let mut root = 1u8;
+    (0..check).all(|_| {
+        let value = codewords.iter().fold(0u8, |acc, &c| gf_mul(acc, root) ^ c);
+        root = gf_mul(root, 2);
+        value == 0
+    })
+}
+
+/// Rule 1 of 7.8.3 on one row or column: 3 + (n - 5) per run of n >= 5
+fn run_penalty(line: &[bool]) -> usize {
+    line.chunk_by(|a, b| a == b).filter(|run| run.len() >= 5).map(|run| run.len() - 2).sum()
+}
+
+/// Rule 3 on one row or column: 40 per 1:1:3:1:1 pattern with four light
+/// modules before or after it
+fn finder_penalty(line: &[bool]) -> usize {
+    const AFTER: [bool; 11] = [true, false, true, true, true, false, true, false, false, false, false];
+    let before: Vec<bool> = AFTER.iter().rev().copied().collect();
+    line.windows(11).filter(|w| *w == AFTER || *w == before.as_slice()).count() * 40
+}
+
+/// Rule 4: 10 per full 5% the dark share is away from 50%
+fn balance_penalty(dark: usize, total: usize) -> usize {
+    (dark * 100 / total.max(1)).abs_diff(50) / 5 * 10
+}
+
+/// Total penalty of 7.8.3 for a square of `size` modules
+fn penalty(size: usize, dark: impl Fn(usize, usize) -> bool) -> usize {
+    let rows: Vec<Vec<bool>> = (0..size).map(|y| (0..size).map(|x| dark(x, y)).collect()).collect();
+    let columns: Vec<Vec<bool>> = (0..size).map(|x| (0..size).map(|y| dark(x, y)).collect()).collect();
+    let lines = rows.iter().chain(&columns);
+    let runs: usize = lines.clone().map(|l| run_penalty(l)).sum();
+    let finders: usize = lines.map(|l| finder_penalty(l)).sum();
+    let blocks = (0..size.saturating_sub(1))
+        .flat_map(|y| (0..size - 1).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            let c = dark(x, y);
+            dark(x + 1, y) == c && dark(x, y + 1) == c && dark(x + 1, y + 1) == c
+        })
+        .count()
+        * 3;
+    let dark_modules = rows.iter().flatten().filter(|&&d| d).count();
+    runs + blocks + finders + balance_penalty(dark_modules, size * size)
+}
+
+#[test]
+fn format_information_matches_annex_c() {
+    // The table is the BCH(15, 5) code of table C.1, masked with 0x5412
+    for (row, bits) in FORMAT_INFO.iter().zip([0b01, 0b00, 0b11, 0b10]) {
+        for (mask, &expected) in row.iter().enumerate() {
+            let data = (bits << 3 | mask as u32) << 10;
+            assert_eq!((data | bch_remainder(data, 0x537)) ^ 0x5412, expected);
+        }
+    }
+
+    for version in [1, 2, 6, 7, 21, 40] {
+        for (index, ecl) in LEVELS.into_iter().enumerate() {
+            let qr = symbol(version, ecl);
+            assert_eq!(format_of(&qr).0, index, "version {} {:?}", version, ecl);
+            // Dark module beside the lower copy
+            assert!(qr.matrix().is_dark(8, qr.size() - 8));
+        }
+    }
+}
+
+#[test]
+fn version_information_matches_annex_d() {
+    for (i, &expected) in VERSION_INFO.iter().enumerate() {
+        let data = ((i + 7) as u32) << 12;
+        assert_eq!(data | bch_remainder(data, 0x1F25), expected);
+    }
+
+    for version in 7..=40 {
+        let qr = symbol(version, ErrorCorrectionLevel::Low);
+        let (m, size) = (qr.matrix(), qr.size());
+        let (mut above, mut left) = (0, 0);
+        for i in 0..18 {
+            let (a, b) = (size - 11 + i % 3, i / 3);
+            above |= (m.is_dark(a, b) as u32) << i;
+            left |= (m.is_dark(b, a) as u32) << i;
+        }
+        assert_eq!((above, left), (VERSION_INFO[version - 7], VERSION_INFO[version - 7]), "version {}", version);
+    }
+}
+
+#[test]
+fn function_patterns_follow_clause_6_3() {
+    for (version, centers) in ALIGNMENT_CENTERS {
+        assert_eq!(alignment_centers(version), centers, "version {}", version);
+    }
+
+    for version in 1..=40 {
+        let qr = symbol(version, ErrorCorrectionLevel::Medium);
+        let (m, size) = (qr.matrix(), qr.size());
+        // Finders: dark ring, light ring, dark 3x3 core; light separator around
+        for (fx, fy) in [(0, 0), (size - 7, 0), (0, size - 7)] {
+            for dy in -1i32..=7 {
+                for dx in -1i32..=7 {
+                    let (x, y) = (fx as i32 + dx, fy as i32 + dy);
+                    if x < 0 || y < 0 || x >= size as i32 || y >= size as i32 {
+                        continue;
+                    }
+                    let ring = dx.min(dy).min(6 - dx).min(6 - dy);
+                    let expected = ring == 0 || ring >= 2;
+                    assert_eq!(m.is_dark(x as usize, y as usize), expected, "finder ({}, {}) v{}", x, y, version);
+                }
+            }
+        }
+        // Timing patterns start and end dark
+        for i in 8..size - 8 {
+            assert_eq!(m.is_dark(i, 6), i % 2 == 0, "timing ({}, 6) v{}", i, version);
+            assert_eq!(m.is_dark(6, i), i % 2 == 0, "timing (6, {}) v{}", i, version);
+        }
+        for (cx, cy) in alignment_patterns(version) {
+            for y in cy - 2..=cy + 2 {
+                for x in cx - 2..=cx + 2 {
+                    let ring = x.abs_diff(cx).max(y.abs_diff(cy));
+                    assert_eq!(m.is_dark(x, y), ring != 1, "alignment ({}, {}) v{}", x, y, version);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn codeword_counts_match_table_1() {
+    // Total codewords and remainder bits of table 1, some versions
+    let table = [(1, 26, 0), (2, 44, 7), (7, 196, 0), (14, 581, 3), (21, 1156, 4), (28, 1921, 3), (40, 3706, 0)];
+    for (version, total, remainder) in table {
+        let size = 17 + 4 * version;
+        let modules = (0..size * size).filter(|&i| !is_function(version, i % size, i / size)).count();
+        assert_eq!((modules / 8, modules % 8), (total, remainder), "version {}", version);
+    }
+    for version in 1..=40 {
+        let size = 17 + 4 * version;
+        let modules = (0..size * size).filter(|&i| !is_function(version, i % size, i / size)).count();
+        assert!([0, 3, 4, 7].contains(&(modules % 8)), "version {}", version);
+        for ecl in LEVELS {
+            assert!(data_codewords(version, ecl) < modules / 8, "version {} {:?}", version, ecl);
+        }
+    }
+}
+
+#[test]
+fn codewords_pass_reed_solomon_check() {
+    // Versions 1 and 2 hold a single block at every level, so the check
+    // words follow the data directly
+    for version in [1, 2] {
+        for ecl in LEVELS {
+            let digits = capacity(version, ecl, Mode::Numeric);
+            let qr = symbol(version, ecl);
+            let bits = read_data_bits(&qr);
+            let codewords: Vec<u8> =
+                bits.chunks_exact(8).map(|byte| byte.iter().fold(0u8, |acc, &b| acc << 1 | b as u8)).collect();
+            let data = data_codewords(version, ecl);
+            assert!(rs_valid(&codewords, codewords.len() - data), "version {} {:?}", version, ecl);
+
+            // Numeric mode indicator, then the character count in 10 bits
+            let field = |from: usize, len: usize| bits[from..from + len].iter().fold(0, |acc, &b| acc << 1 | b as usize);
+            assert_eq!((field(0, 4), field(4, 10)), (0b0001, digits), "version {} {:?}", version, ecl);
+        }
+    }
+    // A corrupted codeword is caught
+    let qr = symbol(1, ErrorCorrectionLevel::Medium);
+    let mut codewords: Vec<u8> =
+        read_data_bits(&qr).chunks_exact(8).map(|byte| byte.iter().fold(0u8, |acc, &b| acc << 1 | b as u8)).collect();
+    codewords[3] ^= 0x40;
+    assert!(!rs_valid(&codewords, 10));
+}
+
+#[test]
+fn capacity_matches_table_7() {
+    for (version, rows) in CAPACITY {
+        for (ecl, row) in LEVELS.into_iter().zip(rows) {
+            let got = [Mode::Numeric, Mode::Alphanumeric, Mode::Byte].map(|mode| capacity(version, ecl, mode));
+            assert_eq!(got, row, "version {} {:?}", version, ecl);
+        }
+    }
+    // Each capacity fills the symbol exactly: one more character needs the next version
+    for version in [1, 9, 10, 26, 27, 39] {
+        for ecl in LEVELS {
+            let fits = capacity(version, ecl, Mode::Byte);
+            let qr = generate_qr(&"a".repeat(fits + 1), ecl).unwrap();
+            assert!(qr.size() > 17 + 4 * version, "version {} {:?}", version, ecl);
+        }
+    }
+}
+
+#[test]
+fn penalty_rules_match_reference_vectors() {
+    assert_eq!(run_penalty(&[true; 5]), 3);
+    assert_eq!(run_penalty(&[false; 7]), 5);
+    assert_eq!(run_penalty(&[true, true, true, true, false, false, false, false]), 0);
+
+    let light = [false; 4];
+    let finder = [true, false, true, true, true, false, true];
+    assert_eq!(finder_penalty(&[&light[..], &finder].concat()), 40);
+    assert_eq!(finder_penalty(&[&finder[..], &light].concat()), 40);
+    assert_eq!(finder_penalty(&finder), 0);
+    assert_eq!(finder_penalty(&[&light[..3], &finder, &light[..3]].concat()), 0);
+
+    // 21 rows and 21 columns of one run (19 each), 400 2x2 blocks, 100% dark
+    assert_eq!(penalty(21, |_, _| true), 42 * 19 + 400 * 3 + 100);
+    assert_eq!(penalty(21, |x, y| (x + y) % 2 == 0), 0);
+    assert_eq!([0, 45, 50, 54, 55, 70, 100].map(|dark| balance_penalty(dark, 100)), [100, 10, 0, 0, 10, 40, 100]);
+}
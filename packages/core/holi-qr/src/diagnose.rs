@@ -0,0 +1,269 @@
+//! Decode diagnostics
+//!
+//! `decode_luma` fails with the same opaque error whether the image holds
+//! no code at all or a code with a few smudged modules. `diagnose_luma`
+//! repeats the decoder's steps one at a time (finder patterns, format
+//! information, error correction per block) and reports how far it got, so
+//! a scanner can tell "point the camera at a code" from "this code is
+//! damaged".
+
+use crate::{ErrorCorrectionLevel, QrError};
+
+/// Furthest step decoding reached, in pipeline order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DecodeStage {
+    /// Fewer than three finder patterns; probably not a QR code
+    #[default]
+    NotFound,
+    /// Finder patterns found, but the grid could not be sampled or its
+    /// version and format information could not be read
+    FormatUnreadable,
+    /// Format read, but some blocks have more errors than their error
+    /// correction can fix
+    DataDamaged,
+    /// Decoded; the code may still have needed corrections
+    Decoded,
+}
+
+impl DecodeStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotFound => "notFound",
+            Self::FormatUnreadable => "formatUnreadable",
+            Self::DataDamaged => "dataDamaged",
+            Self::Decoded => "decoded",
+        }
+    }
+
+    /// Whether a QR code is in the image, readable or not
+    pub fn found(&self) -> bool {
+        *self > Self::NotFound
+    }
+}
+
+/// What `diagnose_luma` learned at each step
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodeDiagnostics {
+    pub stage: DecodeStage,
+    /// Decoded text, when `stage` is `Decoded`
+    pub text: Option<String>,
+    /// Confirmed finder patterns, 0-3
+    pub finder_patterns: usize,
+    /// Finder centers in pixels (bottom-left, top-left, top-right, then any
+    /// alignment pattern); empty when fewer than three were found
+    pub points: Vec<(f32, f32)>,
+    pub version: Option<usize>,
+    pub ecl: Option<ErrorCorrectionLevel>,
+    /// Data mask pattern, 0-7
+    pub mask: Option<u8>,
+    /// Error correction blocks read
+    pub blocks: usize,
+    /// Blocks with more errors than they can correct
+    pub failed_blocks: usize,
+    /// Codewords corrected in the blocks that did decode
+    pub corrected_codewords: usize,
+    /// Codewords the blocks could correct in total (half their ECC codewords)
+    pub correctable_codewords: usize,
+}
+
+impl DecodeDiagnostics {
+    /// Error correction left unused, 0.0-1.0: 1.0 for a clean read, near
+    /// 0.0 when a little more damage would have made it unreadable. 0.0
+    /// unless decoded.
+    pub fn confidence(&self) -> f32 {
+        if self.stage != DecodeStage::Decoded {
+            return 0.0;
+        }
+        if self.correctable_codewords == 0 {
+            return 1.0;
+        }
+        1.0 - (self.corrected_codewords as f32 / self.correctable_codewords as f32).min(1.0)
+    }
+}
+
+/// Decode an 8-bit grayscale buffer (`width * height` bytes), reporting how
+/// far decoding got instead of failing
+///
+/// # Returns
+/// * `Ok(DecodeDiagnostics)` - With `text` set when the code decoded
+/// * `Err(QrError::InvalidArgument)` - The buffer size is wrong
+#[cfg(feature = "decode")]
+pub fn diagnose_luma(luma: Vec<u8>, width: u32, height: u32) -> Result<DecodeDiagnostics, QrError> {
+    let mut diagnostics = DecodeDiagnostics::default();
+    if luma.is_empty() && (width == 0 || height == 0) {
+        // rxing cannot binarize an empty image, and there is nothing to find
+        return Ok(diagnostics);
+    }
+    let mut bitmap = crate::verify::luma_bitmap(luma, width, height)?;
+    // The full decoder also tries mirrored and pure-barcode reads, so its
+    // verdict on the text wins over the steps traced below
+    if let Ok((text, points)) = crate::verify::decode_bitmap(&mut bitmap) {
+        diagnostics.text = Some(text);
+        diagnostics.points = points;
+    }
+    // The steps run on the black matrix the decoder left in `bitmap`
+    trace(&bitmap, &mut diagnostics);
+    if diagnostics.text.is_some() {
+        diagnostics.stage = DecodeStage::Decoded;
+    }
+    Ok(diagnostics)
+}
+
+/// Run the decoder's steps one at a time, recording each in `d`
+#[cfg(feature = "decode")]
+fn trace(bitmap: &rxing::BinaryBitmap<crate::binarize::BlockBinarizer>, d: &mut DecodeDiagnostics) {
+    use rxing::common::reedsolomon::{get_predefined_genericgf, PredefinedGenericGF, ReedSolomonDecoder};
+    use rxing::common::DetectorRXingResult;
+    use rxing::qrcode::decoder::{self, BitMatrixParser, DataBlock};
+    use rxing::qrcode::detector::{Detector, FinderPatternFinder};
+    use rxing::{DecodeHintType, DecodeHintValue, Point};
+
+    let image = bitmap.get_black_matrix();
+    let mut hints = rxing::DecodingHintDictionary::new();
+    hints.insert(DecodeHintType::TRY_HARDER, DecodeHintValue::TryHarder(true));
+
+    let mut finder = FinderPatternFinder::new(image);
+    let found = finder.find(&hints);
+    // The finder confirms a candidate once it crosses it on two rows
+    d.finder_patterns = finder.getPossibleCenters().iter().filter(|p| p.getCount() >= 2).count().min(3);
+    let Ok(info) = found else {
+        return;
+    };
+    d.finder_patterns = 3;
+    d.stage = DecodeStage::FormatUnreadable;
+    if d.points.is_empty() {
+        d.points = [info.getBottomLeft(), info.getTopLeft(), info.getTopRight()]
+            .map(|p| {
+                let point = Point::from(p);
+                (point.x, point.y)
+            })
+            .to_vec();
+    }
+
+    let Ok(detected) = Detector::new(image).processFinderPatternInfo(info) else {
+        return;
+    };
+    let Ok(mut parser) = BitMatrixParser::new(detected.getBits().clone()) else {
+        return;
+    };
+    let Ok(version) = parser.readVersion() else {
+        return;
+    };
+    let Ok(format) = parser.readFormatInformation() else {
+        return;
+    };
+    let (level, mask) = (format.getErrorCorrectionLevel(), format.getDataMask());
+    d.version = Some(version.getVersionNumber() as usize);
+    d.ecl = match level {
+        decoder::ErrorCorrectionLevel::L => Some(ErrorCorrectionLevel::Low),
+        decoder::ErrorCorrectionLevel::M => Some(ErrorCorrectionLevel::Medium),
+        decoder::ErrorCorrectionLevel::Q => Some(ErrorCorrectionLevel::Quartile),
+        decoder::ErrorCorrectionLevel::H => Some(ErrorCorrectionLevel::High),
+        decoder::ErrorCorrectionLevel::Invalid => None,
+    };
+    d.mask = Some(mask);
+
+    let Ok(blocks) = parser.readCodewords().and_then(|codewords| DataBlock::getDataBlocks(&codewords, version, level))
+    else {
+        return;
+    };
+    d.stage = DecodeStage::DataDamaged;
+    d.blocks = blocks.len();
+    let rs = ReedSolomonDecoder::new(get_predefined_genericgf(PredefinedGenericGF::QrCodeField256));
+    for block in &blocks {
+        let ec = block.getCodewords().len() - block.getNumDataCodewords() as usize;
+        let mut received: Vec<i32> = block.getCodewords().iter().map(|&c| c as i32).collect();
+        d.correctable_codewords += ec / 2;
+        match rs.decode(&mut received, ec as i32) {
+            Ok(corrected) => d.corrected_codewords += corrected,
+            Err(_) => d.failed_blocks += 1,
+        }
+    }
+}
+
+/// Stub function when 'decode' feature is not enabled
+#[cfg(not(feature = "decode"))]
+pub fn diagnose_luma(_luma: Vec<u8>, _width: u32, _height: u32) -> Result<DecodeDiagnostics, QrError> {
+    Err(QrError::VerificationFailed(
+        "Decoding not available. Enable 'decode' feature.".into()
+    ))
+}
+
+#[cfg(test)]
+#[cfg(feature = "decode")]
+mod tests {
+    use super::*;
+    use crate::generate_qr;
+
+    const MODULE_PX: usize = 4;
+    const QUIET: usize = 4;
+
+    /// The code drawn at `MODULE_PX`, with `flip` deciding which of its
+    /// modules to invert; returns the buffer and its side in pixels
+    fn draw(ecl: ErrorCorrectionLevel, flip: impl Fn(usize, usize) -> bool) -> (Vec<u8>, u32) {
+        let qr = generate_qr("https://holi.tools/diagnose", ecl).unwrap();
+        let size = qr.size();
+        let modules = size + 2 * QUIET;
+        let side = modules * MODULE_PX;
+        let luma = (0..side * side)
+            .map(|i| {
+                let (x, y) = (i % side / MODULE_PX, i / side / MODULE_PX);
+                let (qx, qy) = (x.wrapping_sub(QUIET), y.wrapping_sub(QUIET));
+                let flipped = qx < size && qy < size && flip(qx, qy);
+                if qr.matrix().is_dark(qx, qy) != flipped { 0 } else { 255 }
+            })
+            .collect();
+        (luma, side as u32)
+    }
+
+    /// Modules outside the finders, separators and format information
+    fn in_data(x: usize, y: usize, size: usize) -> bool {
+        let corner = |a: usize, b: usize| a < 9 && (b < 9 || b >= size - 8);
+        !corner(x, y) && !corner(y, x)
+    }
+
+    #[test]
+    fn test_clean_code_decodes_with_full_confidence() {
+        let (luma, side) = draw(ErrorCorrectionLevel::Medium, |_, _| false);
+        let d = diagnose_luma(luma, side, side).unwrap();
+        assert_eq!(d.stage, DecodeStage::Decoded);
+        assert_eq!(d.text.as_deref(), Some("https://holi.tools/diagnose"));
+        assert_eq!((d.finder_patterns, d.ecl, d.failed_blocks), (3, Some(ErrorCorrectionLevel::Medium), 0));
+        assert!(d.points.len() >= 3 && d.blocks > 0 && d.mask.is_some());
+        assert_eq!(d.confidence(), 1.0);
+    }
+
+    #[test]
+    fn test_damage_lowers_confidence_then_fails_blocks() {
+        let size = generate_qr("https://holi.tools/diagnose", ErrorCorrectionLevel::High).unwrap().size();
+        // A smudge across a few data rows
+        let (luma, side) = draw(ErrorCorrectionLevel::High, |x, y| in_data(x, y, size) && (10..12).contains(&y));
+        let d = diagnose_luma(luma, side, side).unwrap();
+        assert_eq!(d.stage, DecodeStage::Decoded);
+        assert!(d.corrected_codewords > 0);
+        assert!(d.confidence() > 0.0 && d.confidence() < 1.0, "{}", d.confidence());
+
+        // Every data module inverted
+        let (luma, side) = draw(ErrorCorrectionLevel::High, |x, y| in_data(x, y, size) && (x + y) % 3 > 0);
+        let d = diagnose_luma(luma, side, side).unwrap();
+        assert_eq!(d.stage, DecodeStage::DataDamaged);
+        assert!(d.stage.found() && d.text.is_none());
+        assert_eq!(d.finder_patterns, 3);
+        assert!(d.failed_blocks > 0);
+        assert_eq!(d.confidence(), 0.0);
+    }
+
+    #[test]
+    fn test_no_code_and_bad_buffers() {
+        let d = diagnose_luma(vec![200; 100 * 100], 100, 100).unwrap();
+        assert_eq!(d.stage, DecodeStage::NotFound);
+        assert!(!d.stage.found() && d.points.is_empty());
+
+        assert!(diagnose_luma(vec![0; 10], 4, 4).is_err());
+        assert_eq!(diagnose_luma(Vec::new(), 0, 0).unwrap().stage, DecodeStage::NotFound);
+
+        // Later stages mean more of the code was read
+        assert!(DecodeStage::FormatUnreadable.found());
+        assert!(DecodeStage::NotFound < DecodeStage::FormatUnreadable && DecodeStage::DataDamaged < DecodeStage::Decoded);
+    }
+}
@@ -0,0 +1,223 @@
+//! ECI headers: declaring the character set of byte-mode data
+//!
+//! Byte mode says nothing about how its bytes are to be read. The standard
+//! default is ISO-8859-1, most phones assume UTF-8 and older Japanese
+//! scanners Shift-JIS; an Extended Channel Interpretation header names the
+//! charset so none of them have to guess.
+
+use crate::codewords::{build_symbol, BitBuffer};
+use crate::error::QrError;
+use crate::qr::{data_codewords, segment_bits, ErrorCorrectionLevel, QrCode};
+use fast_qr::Mode;
+
+/// Mode indicator and an 8-bit ECI designator
+const HEADER_BITS: usize = 12;
+
+/// Character set declared in an ECI header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    /// Latin-1, what byte mode means without a header
+    Iso8859_1,
+    /// Needs the `shift-jis` feature
+    ShiftJis,
+}
+
+impl Charset {
+    pub const ALL: [Charset; 3] = [Self::Utf8, Self::Iso8859_1, Self::ShiftJis];
+
+    /// ECI assignment number
+    pub fn eci(&self) -> u8 {
+        match self {
+            Self::Utf8 => 26,
+            Self::Iso8859_1 => 3,
+            Self::ShiftJis => 20,
+        }
+    }
+
+    /// Name as accepted by `try_from`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Iso8859_1 => "iso-8859-1",
+            Self::ShiftJis => "shift_jis",
+        }
+    }
+
+    /// `text` in this charset
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The encoded bytes
+    /// * `Err(QrError::InvalidArgument)` - A character the charset lacks, or
+    ///   Shift-JIS without the `shift-jis` feature
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, QrError> {
+        match self {
+            Self::Utf8 => Ok(text.as_bytes().to_vec()),
+            Self::Iso8859_1 => text.chars().map(|c| u8::try_from(c).map_err(|_| unrepresentable(c, *self))).collect(),
+            Self::ShiftJis => encode_shift_jis(text),
+        }
+    }
+}
+
+/// Unknown charsets are an error: encoding in the wrong one garbles the text
+impl TryFrom<&str> for Charset {
+    type Error = QrError;
+
+    fn try_from(s: &str) -> Result<Self, QrError> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            "iso-8859-1" | "iso8859-1" | "latin1" | "latin-1" => Ok(Self::Iso8859_1),
+            "shift_jis" | "shift-jis" | "sjis" => Ok(Self::ShiftJis),
+            _ => Err(QrError::InvalidArgument(format!("Unknown charset: {:?}", s))),
+        }
+    }
+}
+
+fn unrepresentable(c: char, charset: Charset) -> QrError {
+    QrError::InvalidArgument(format!("{:?} has no {} encoding", c, charset.as_str()))
+}
+
+/// Shift-JIS as the WHATWG encoder writes it
+#[cfg(feature = "shift-jis")]
+fn encode_shift_jis(text: &str) -> Result<Vec<u8>, QrError> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\u{0}'..='\u{80}' => bytes.push(c as u8),
+            '\u{A5}' => bytes.push(0x5C),
+            '\u{203E}' => bytes.push(0x7E),
+            // Half-width katakana are single bytes
+            '\u{FF61}'..='\u{FF9F}' => bytes.push((c as u32 - 0xFF61 + 0xA1) as u8),
+            _ => {
+                let code = shift_jis_code(c).ok_or_else(|| unrepresentable(c, Charset::ShiftJis))?;
+                bytes.extend(code.to_be_bytes());
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Double-byte Shift-JIS code of `c`, lead byte high
+#[cfg(feature = "shift-jis")]
+pub(crate) fn shift_jis_code(c: char) -> Option<u16> {
+    let pointer = encoding_index_japanese::jis0208::backward_remapped(c as u32);
+    if pointer == 0xFFFF {
+        return None;
+    }
+    let (lead, trail) = (pointer / 188, pointer % 188);
+    Some((lead + if lead < 0x1F { 0x81 } else { 0xC1 }) << 8 | (trail + if trail < 0x3F { 0x40 } else { 0x41 }))
+}
+
+/// Stub function when 'shift-jis' feature is not enabled
+#[cfg(not(feature = "shift-jis"))]
+pub(crate) fn shift_jis_code(_c: char) -> Option<u16> {
+    None
+}
+
+/// Stub function when 'shift-jis' feature is not enabled
+#[cfg(not(feature = "shift-jis"))]
+fn encode_shift_jis(_text: &str) -> Result<Vec<u8>, QrError> {
+    Err(QrError::InvalidArgument("Shift-JIS not available. Enable 'shift-jis' feature.".into()))
+}
+
+/// Generate a QR code with `text` in `charset`, declared by an ECI header
+///
+/// For scanners that misread plain byte mode, or text that must arrive in a
+/// legacy charset. The data is one byte-mode segment after the header;
+/// `text` on the result is `text` unchanged.
+///
+/// # Returns
+/// * `Ok(QrCode)` - The smallest symbol that holds it
+/// * `Err(QrError::InvalidArgument)` - `text` has characters `charset` lacks
+/// * `Err(QrError::InputTooLong)` - Too long for version 40, in bytes
+///
+/// # Example
+/// ```rust
+/// use holi_qr::{generate_qr_eci, Charset, ErrorCorrectionLevel};
+///
+/// let qr = generate_qr_eci("Crème brûlée", ErrorCorrectionLevel::Medium, Charset::Iso8859_1).unwrap();
+/// assert_eq!(qr.text, "Crème brûlée");
+/// ```
+pub fn generate_qr_eci(text: &str, ecl: ErrorCorrectionLevel, charset: Charset) -> Result<QrCode, QrError> {
+    if text.is_empty() {
+        return Err(QrError::EmptyInput);
+    }
+
+    let bytes = charset.encode(text)?;
+    let version = (1..=40)
+        .find(|&v| HEADER_BITS + segment_bits(Mode::Byte, bytes.len(), v) <= data_codewords(v, ecl) * 8)
+        .ok_or(QrError::InputTooLong { length: bytes.len() })?;
+
+    let mut data = BitBuffer::default();
+    data.push(0b0111, 4);
+    data.push(charset.eci() as usize, 8);
+    data.push_segment(&bytes, Mode::Byte, version);
+    let inner = build_symbol(data, &bytes, Mode::Byte, version, ecl)?;
+    let mut qr = QrCode::new(inner, &bytes, Mode::Byte, ecl)?;
+    qr.text = text.to_string();
+    Ok(qr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eci_header() {
+        let qr = generate_qr_eci("Añejo", ErrorCorrectionLevel::Low, Charset::Iso8859_1).unwrap();
+        assert_eq!(qr.text, "Añejo");
+        // 0111 | ECI 3 | 0100 | 5 bytes | "A", "ñ" as one Latin-1 byte
+        assert_eq!(qr.debug_layout().codewords[..5], [0x70, 0x34, 0x05, 0x41, 0xF1]);
+
+        let utf8 = generate_qr_eci("Añejo", ErrorCorrectionLevel::Low, Charset::Utf8).unwrap();
+        assert_eq!(utf8.debug_layout().codewords[..6], [0x71, 0xA4, 0x06, 0x41, 0xC3, 0xB1]);
+
+        assert!(matches!(
+            generate_qr_eci("日本", ErrorCorrectionLevel::Low, Charset::Iso8859_1),
+            Err(QrError::InvalidArgument(_))
+        ));
+        assert!(matches!(generate_qr_eci("", ErrorCorrectionLevel::Low, Charset::Utf8), Err(QrError::EmptyInput)));
+        // 2953 bytes fit in 40-L without a header; the header costs one
+        assert!(generate_qr_eci(&"x".repeat(2952), ErrorCorrectionLevel::Low, Charset::Utf8).is_ok());
+        assert!(matches!(
+            generate_qr_eci(&"x".repeat(2953), ErrorCorrectionLevel::Low, Charset::Utf8),
+            Err(QrError::InputTooLong { length: 2953 })
+        ));
+    }
+
+    #[test]
+    fn test_charset_names() {
+        for charset in Charset::ALL {
+            assert_eq!(Charset::try_from(charset.as_str()).unwrap(), charset);
+        }
+        assert_eq!(Charset::try_from("Latin1").unwrap(), Charset::Iso8859_1);
+        assert_eq!(Charset::try_from("SJIS").unwrap(), Charset::ShiftJis);
+        assert!(Charset::try_from("windows-1252").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "shift-jis")]
+    fn test_shift_jis() {
+        assert_eq!(
+            Charset::ShiftJis.encode("日本語ｱ¥a").unwrap(),
+            [0x93, 0xFA, 0x96, 0x7B, 0x8C, 0xEA, 0xB1, 0x5C, 0x61]
+        );
+        assert!(Charset::ShiftJis.encode("😀").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "verify")]
+    fn test_eci_scans() {
+        use crate::{render_svg, verify_svg};
+
+        let mut cases = vec![(Charset::Iso8859_1, "Crème brûlée, 5 francs"), (Charset::Utf8, "Crème brûlée, 5€ ✓")];
+        if cfg!(feature = "shift-jis") {
+            cases.push((Charset::ShiftJis, "日本語のテキスト"));
+        }
+        for (charset, text) in cases {
+            let qr = generate_qr_eci(text, ErrorCorrectionLevel::Medium, charset).unwrap();
+            assert_eq!(verify_svg(&render_svg(&qr)).unwrap(), text, "{}", charset.as_str());
+        }
+    }
+}
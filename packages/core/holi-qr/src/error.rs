@@ -13,11 +13,31 @@ pub enum QrError {
     #[error("Input text is too long ({length} characters)")]
     InputTooLong { length: usize },
 
+    /// The input needs a larger symbol than allowed: `capacity` is in UTF-8
+    /// bytes, `over` is the characters that do not fit
+    #[error("Input is {over} characters over the {capacity} bytes that fit in version {max_version}")]
+    ExceedsMaxVersion { max_version: usize, capacity: usize, over: usize },
+
     /// Internal QR generation error
     #[error("QR generation failed: {0}")]
     GenerationFailed(String),
     
+    /// An argument is out of range
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    /// SVG rendering or PNG encoding failed
+    #[error("Render failed: {0}")]
+    RenderFailed(String),
+
     /// QR verification failed
     #[error("Verification failed: {0}")]
     VerificationFailed(String),
 }
+
+/// Writing SVG into a `String` only fails if a `Display` impl does
+impl From<std::fmt::Error> for QrError {
+    fn from(_: std::fmt::Error) -> Self {
+        QrError::RenderFailed("SVG formatting failed".into())
+    }
+}
@@ -0,0 +1,232 @@
+//! 3D export for printed keychains and signs
+//!
+//! The code becomes a height field: a base plate covering the whole symbol
+//! (quiet zone included) with the dark modules raised on top. The mesh is
+//! closed: every edge is matched by one running the other way, so it has no
+//! holes. It is not always manifold, though. Where two dark modules touch
+//! only at a corner, four wall faces meet on that vertical edge; slicers
+//! print it as is, but strict mesh checks report it.
+
+use crate::error::QrError;
+use crate::qr::QrCode;
+use crate::render::MAX_MARGIN;
+
+/// Dimensions of the extruded code, in mm
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtrudeOptions {
+    /// Side of one module
+    pub module_size: f64,
+    /// Thickness of the plate under every module
+    pub base_height: f64,
+    /// How far dark modules rise above the plate
+    pub module_height: f64,
+    /// Quiet zone around the code (in modules), at most `MAX_MARGIN`
+    pub margin: usize,
+}
+
+impl Default for ExtrudeOptions {
+    fn default() -> Self {
+        Self {
+            module_size: 2.0,
+            base_height: 1.5,
+            module_height: 1.0,
+            margin: 2,
+        }
+    }
+}
+
+type Vec3 = [f32; 3];
+
+/// One facet, counter-clockwise seen from outside
+type Triangle = [Vec3; 3];
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normal(t: &Triangle) -> Vec3 {
+    let n = cross(sub(t[1], t[0]), sub(t[2], t[0]));
+    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if len == 0.0 { n } else { n.map(|c| c / len) }
+}
+
+/// Push quad `a b c d` as two triangles, flipped if needed so the face
+/// points along `outward`
+fn push_quad(mesh: &mut Vec<Triangle>, [a, b, c, d]: [Vec3; 4], outward: Vec3) {
+    let n = cross(sub(b, a), sub(c, a));
+    if n[0] * outward[0] + n[1] * outward[1] + n[2] * outward[2] >= 0.0 {
+        mesh.extend([[a, b, c], [a, c, d]]);
+    } else {
+        mesh.extend([[a, c, b], [a, d, c]]);
+    }
+}
+
+/// Triangles of the extruded code
+///
+/// Faces are split per module and walls at every height level so that
+/// edges always meet end to end (no T-junctions).
+fn extrude_mesh(qr: &QrCode, options: &ExtrudeOptions) -> Result<Vec<Triangle>, QrError> {
+    for (name, value) in [
+        ("Module size", options.module_size),
+        ("Base height", options.base_height),
+        ("Module height", options.module_height),
+    ] {
+        if !value.is_finite() || value <= 0.0 {
+            return Err(QrError::InvalidArgument(format!("{} must be positive (got {})", name, value)));
+        }
+    }
+    // The mesh grows with the square of the plate side
+    if options.margin > MAX_MARGIN {
+        return Err(QrError::InvalidArgument(format!(
+            "Margin must be at most {} modules (got {})",
+            MAX_MARGIN, options.margin
+        )));
+    }
+
+    let size = qr.size();
+    let modules = qr.matrix();
+    let n = size + options.margin * 2;
+    let s = options.module_size as f32;
+    let base = options.base_height as f32;
+    let top = (options.base_height + options.module_height) as f32;
+    let levels = [0.0, base, top];
+
+    // Height of cell (col, row); outside the plate is 0. Rows run down the
+    // code but up the Y axis, so the print reads the right way from above.
+    let height = |col: isize, row: isize| -> f32 {
+        if col < 0 || row < 0 || col as usize >= n || row as usize >= n {
+            return 0.0;
+        }
+        let (x, y) = (col as usize, row as usize);
+        let dark = x >= options.margin && y >= options.margin && modules.is_dark(x - options.margin, y - options.margin);
+        if dark { top } else { base }
+    };
+    let x_at = |col: isize| col as f32 * s;
+    let y_at = |row: isize| (n as isize - row) as f32 * s;
+
+    let mut mesh = Vec::new();
+    for row in 0..n as isize {
+        for col in 0..n as isize {
+            let (x0, x1, y0, y1) = (x_at(col), x_at(col + 1), y_at(row + 1), y_at(row));
+            let h = height(col, row);
+            let corners = |z| [[x0, y0, z], [x1, y0, z], [x1, y1, z], [x0, y1, z]];
+            push_quad(&mut mesh, corners(h), [0.0, 0.0, 1.0]);
+            push_quad(&mut mesh, corners(0.0), [0.0, 0.0, -1.0]);
+        }
+    }
+
+    // Walls between horizontally and vertically adjacent cells (and the
+    // outside), facing the lower side
+    let mut wall = |a: (f32, f32), b: (f32, f32), low: f32, high: f32, outward: Vec3| {
+        for step in levels.windows(2).filter(|l| l[0] >= low && l[1] <= high) {
+            let quad = [[a.0, a.1, step[0]], [b.0, b.1, step[0]], [b.0, b.1, step[1]], [a.0, a.1, step[1]]];
+            push_quad(&mut mesh, quad, outward);
+        }
+    };
+    for row in 0..n as isize {
+        for col in 0..=n as isize {
+            let (left, right) = (height(col - 1, row), height(col, row));
+            if left != right {
+                let x = x_at(col);
+                let outward = if left > right { [1.0, 0.0, 0.0] } else { [-1.0, 0.0, 0.0] };
+                wall((x, y_at(row + 1)), (x, y_at(row)), left.min(right), left.max(right), outward);
+            }
+        }
+    }
+    for row in 0..=n as isize {
+        for col in 0..n as isize {
+            let (above, below) = (height(col, row - 1), height(col, row));
+            if above != below {
+                let y = y_at(row);
+                let outward = if above > below { [0.0, -1.0, 0.0] } else { [0.0, 1.0, 0.0] };
+                wall((x_at(col), y), (x_at(col + 1), y), above.min(below), above.max(below), outward);
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+/// Render `qr` as a binary STL for 3D printing
+///
+/// Units are mm. The plate spans `(size + 2 * margin) * module_size` on X
+/// and Y and sits on Z = 0.
+pub fn render_stl(qr: &QrCode, options: &ExtrudeOptions) -> Result<Vec<u8>, QrError> {
+    let mesh = extrude_mesh(qr, options)?;
+
+    let mut stl = Vec::with_capacity(84 + mesh.len() * 50);
+    let mut header = [0u8; 80];
+    let title = b"holi-qr extruded QR code";
+    header[..title.len()].copy_from_slice(title);
+    stl.extend_from_slice(&header);
+    stl.extend_from_slice(&(mesh.len() as u32).to_le_bytes());
+    for triangle in &mesh {
+        for v in std::iter::once(normal(triangle)).chain(triangle.iter().copied()) {
+            for c in v {
+                stl.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        stl.extend_from_slice(&0u16.to_le_bytes());
+    }
+    Ok(stl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_qr, ErrorCorrectionLevel};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_mesh_is_watertight() {
+        let qr = generate_qr("https://holi.tools/3d", ErrorCorrectionLevel::Medium).unwrap();
+        let mesh = extrude_mesh(&qr, &ExtrudeOptions::default()).unwrap();
+
+        // Every directed edge is matched by the same edge in reverse
+        let key = |v: Vec3| v.map(f32::to_bits);
+        let mut edges: HashMap<_, i32> = HashMap::new();
+        for t in &mesh {
+            for i in 0..3 {
+                let (a, b) = (key(t[i]), key(t[(i + 1) % 3]));
+                *edges.entry((a, b)).or_default() += 1;
+                *edges.entry((b, a)).or_default() -= 1;
+            }
+        }
+        assert!(edges.values().all(|&count| count == 0));
+
+        // Outward normals: the signed volume matches plate + raised modules
+        let volume: f32 = mesh
+            .iter()
+            .map(|t| {
+                let c = cross(t[1], t[2]);
+                (t[0][0] * c[0] + t[0][1] * c[1] + t[0][2] * c[2]) / 6.0
+            })
+            .sum();
+        let o = ExtrudeOptions::default();
+        let n = (qr.size() + o.margin * 2) as f64;
+        let dark = qr.matrix().dark_count() as f64;
+        let expected = (n * n * o.base_height + dark * o.module_height) * o.module_size * o.module_size;
+        assert!((volume as f64 - expected).abs() < expected * 1e-4, "{} vs {}", volume, expected);
+    }
+
+    #[test]
+    fn test_binary_stl_layout() {
+        let qr = generate_qr("stl", ErrorCorrectionLevel::Low).unwrap();
+        let stl = render_stl(&qr, &ExtrudeOptions::default()).unwrap();
+
+        let count = u32::from_le_bytes(stl[80..84].try_into().unwrap()) as usize;
+        assert!(count > 0);
+        assert_eq!(stl.len(), 84 + count * 50);
+        assert!(stl.starts_with(b"holi-qr"));
+
+        let bad = ExtrudeOptions { module_height: 0.0, ..Default::default() };
+        assert!(render_stl(&qr, &bad).is_err());
+        for margin in [MAX_MARGIN + 1, usize::MAX] {
+            let wide = ExtrudeOptions { margin, ..Default::default() };
+            assert!(matches!(render_stl(&qr, &wide), Err(QrError::InvalidArgument(_))));
+        }
+    }
+}
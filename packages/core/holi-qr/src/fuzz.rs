@@ -0,0 +1,182 @@
+//! Hostile-input tests for the rendering pipeline
+//!
+//! Feeds random combinations of option values a JS caller can send (huge
+//! margins, NaN and infinite floats, junk colors, every shape) through the
+//! renderers and checks they return `Ok` or `Err` without panicking. The
+//! generator is seeded, so a failing case replays with the same inputs.
+
+use std::cell::Cell;
+
+use crate::{
+    capacity, contour_body_path, generate_qr, liquid_body_path, parse_styled_svg, render_artistic_svg,
+    render_stl, render_svg_layout, render_svg_styled, render_svg_styled_diff, render_svg_with_options,
+    try_render_svg_layout, try_render_svg_styled, try_render_svg_styled_diff, try_render_svg_with_options,
+    validate_options, ArtisticOptions, BackgroundPattern, BodyShape, ErrorCorrectionLevel, ExtrudeOptions,
+    EyeBallShape, EyeFrameShape, EyeOverride, Gradient, GradientKind, GradientStop, Logo, LogoImage, Mode, OutlineStyle, PatternKind, RenderOptions, Severity,
+    StyledRenderOptions, MAX_MARGIN,
+};
+
+const CASES: usize = 400;
+
+const MARGINS: [usize; 6] = [0, 1, 4, MAX_MARGIN, MAX_MARGIN + 1, usize::MAX];
+const FLOATS: [f64; 8] = [0.1, 0.5, 0.0, -1.0, 2.0, 1e300, f64::NAN, f64::INFINITY];
+const COLORS: [&str; 8] = ["#000000", "#fff", "transparent", "", "navy", "#GGGGGG", "\"/><script>", "#ÿÿÿ"];
+
+/// xorshift, as in the pattern noise
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[(self.next() % items.len() as u64) as usize]
+    }
+
+    fn coin(&mut self) -> bool {
+        self.next() & 1 == 1
+    }
+}
+
+fn hostile_gradient(rng: &mut Rng) -> Gradient {
+    let stops = rng.pick(&[0, 1, 3]);
+    Gradient {
+        kind: rng.pick(&[GradientKind::Linear, GradientKind::Radial]),
+        stops: (0..stops)
+            .map(|_| GradientStop { offset: rng.pick(&FLOATS), color: rng.pick(&COLORS).to_string() })
+            .collect(),
+        angle: rng.pick(&FLOATS),
+    }
+}
+
+fn hostile_options(rng: &mut Rng) -> StyledRenderOptions {
+    let eye = |rng: &mut Rng| EyeOverride {
+        frame_shape: rng.coin().then(|| rng.pick(&EyeFrameShape::ALL)),
+        ball_shape: rng.coin().then(|| rng.pick(&EyeBallShape::ALL)),
+        color: rng.coin().then(|| rng.pick(&COLORS).to_string()),
+        gradient: rng.coin().then(|| hostile_gradient(rng)),
+    };
+    StyledRenderOptions {
+        margin: rng.pick(&MARGINS),
+        fg_color: rng.pick(&COLORS).to_string(),
+        fg_gradient: rng.coin().then(|| hostile_gradient(rng)),
+        bg_color: rng.pick(&COLORS).to_string(),
+        body_shape: rng.pick(&BodyShape::ALL),
+        eye_frame_shape: rng.pick(&EyeFrameShape::ALL),
+        eye_ball_shape: rng.pick(&EyeBallShape::ALL),
+        eye_overrides: [eye(rng), eye(rng), eye(rng)],
+        background_pattern: rng.coin().then(|| BackgroundPattern {
+            kind: rng.pick(&[PatternKind::DotsGrid, PatternKind::DiagonalLines, PatternKind::Noise]),
+            color: rng.pick(&COLORS).to_string(),
+            density: rng.pick(&FLOATS),
+        }),
+        outline: rng.coin().then(|| OutlineStyle { stroke_width: rng.pick(&FLOATS), contour_only: rng.coin() }),
+        protect_function_patterns: rng.coin(),
+        logo: rng.coin().then(|| Logo {
+            image: if rng.coin() {
+                LogoImage::Svg { markup: rng.pick(&COLORS).to_string(), width: rng.pick(&FLOATS), height: rng.pick(&FLOATS) }
+            } else {
+                LogoImage::Raster { mime_type: rng.pick(&COLORS).to_string(), data: vec![0; rng.pick(&[0, 1, 4])] }
+            },
+            size: rng.pick(&FLOATS),
+            padding: rng.pick(&[0, 1, usize::MAX]),
+        }),
+    }
+}
+
+#[test]
+fn hostile_styled_options_never_panic() {
+    let qr = generate_qr("https://holi.tools/fuzz", ErrorCorrectionLevel::Medium).unwrap();
+    let mut rng = Rng(0x5EED_F022);
+    let mut prev = StyledRenderOptions::default();
+    for case in 0..CASES {
+        let options = hostile_options(&mut rng);
+        let result = try_render_svg_styled(&qr, &options);
+        let clean = !validate_options(&options).iter().any(|i| i.severity == Severity::Error);
+        match &result {
+            Ok(svg) => {
+                assert!(svg.starts_with("<svg"), "case {}", case);
+                let _ = parse_styled_svg(svg);
+            }
+            Err(e) => assert!(!clean, "case {}: {:?} rejected with {}", case, options, e),
+        }
+        let bad_logo = options.logo.as_ref().is_some_and(|logo| logo.check_drawable().is_err());
+        assert_eq!(result.is_err(), options.margin > MAX_MARGIN || bad_logo, "case {}", case);
+        assert!(render_svg_styled(&qr, &options).ends_with("</svg>"), "case {}", case);
+        let _ = try_render_svg_styled_diff(&qr, &prev, &options);
+        let _ = render_svg_styled_diff(&qr, &prev, &options);
+        prev = options;
+    }
+}
+
+#[test]
+fn hostile_margins_are_errors() {
+    let qr = generate_qr("margins", ErrorCorrectionLevel::Low).unwrap();
+    for margin in MARGINS {
+        let ok = margin <= MAX_MARGIN;
+        assert_eq!(try_render_svg_layout(&qr, margin).is_ok(), ok, "{}", margin);
+        let basic = RenderOptions { margin, ..Default::default() };
+        assert_eq!(try_render_svg_with_options(&qr, &basic).is_ok(), ok);
+        // The infallible renderers draw it clamped
+        let clamped = margin.min(MAX_MARGIN);
+        assert_eq!(render_svg_layout(&qr, margin), render_svg_layout(&qr, clamped));
+        assert_eq!(render_svg_with_options(&qr, &basic), render_svg_with_options(&qr, &RenderOptions { margin: clamped, ..basic.clone() }));
+        let extrude = ExtrudeOptions { margin, ..Default::default() };
+        assert_eq!(render_stl(&qr, &extrude).is_ok(), ok, "{}", margin);
+        let image = vec![128u8; 8 * 8 * 4];
+        let artistic = ArtisticOptions { margin, ..Default::default() };
+        assert_eq!(render_artistic_svg(&qr, &image, 8, 8, &artistic).is_ok(), ok, "{}", margin);
+    }
+}
+
+#[test]
+fn hostile_sizes_and_floats_never_panic() {
+    let qr = generate_qr("sizes", ErrorCorrectionLevel::Low).unwrap();
+    let mut rng = Rng(0xBAD_512E);
+    for _ in 0..CASES {
+        let (width, height) = (rng.pick(&[0, 1, 7, u32::MAX]), rng.pick(&[0, 1, 7, u32::MAX]));
+        let image = vec![0u8; rng.pick(&[0, 4, 196])];
+        let _ = render_artistic_svg(&qr, &image, width, height, &ArtisticOptions::default());
+
+        let extrude = ExtrudeOptions {
+            module_size: rng.pick(&FLOATS),
+            base_height: rng.pick(&FLOATS),
+            module_height: rng.pick(&FLOATS),
+            margin: rng.pick(&[0, 1, 2]),
+        };
+        let _ = render_stl(&qr, &extrude);
+    }
+    for version in [0, 41, usize::MAX] {
+        assert_eq!(capacity(version, ErrorCorrectionLevel::High, Mode::Byte), 0);
+    }
+}
+
+#[test]
+fn inconsistent_matrices_never_panic() {
+    // A closure that changes its answer on every call
+    let calls = Cell::new(0u32);
+    let flaky = |x: usize, y: usize| {
+        calls.set(calls.get().wrapping_mul(1_103_515_245).wrapping_add(12_345));
+        (calls.get() >> 16) & 1 == 1 || (x + y).is_multiple_of(3)
+    };
+    assert!(liquid_body_path(40, 0.0, flaky).is_ok());
+    assert!(contour_body_path(40, 0.0, flaky).is_ok());
+    assert!(liquid_body_path(usize::MAX, 0.0, |_, _| true).is_err());
+    assert!(contour_body_path(usize::MAX, 0.0, |_, _| true).is_err());
+    assert!(liquid_body_path(0, f64::NAN, |_, _| true).unwrap().is_empty());
+}
+
+#[test]
+fn garbage_svg_never_panics() {
+    const PIECES: [&str; 10] =
+        ["<svg", " viewBox=\"", "0 0 29 29", "\">", "<path d=\"", "\"", "<rect fill=\"", "</defs>", "<defs>", "é<"];
+    let mut rng = Rng(0x0DD_5A7);
+    for _ in 0..CASES {
+        let svg: String = (0..rng.next() % 12).map(|_| rng.pick(&PIECES)).collect();
+        let _ = parse_styled_svg(&svg);
+    }
+}
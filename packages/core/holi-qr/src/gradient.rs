@@ -0,0 +1,208 @@
+//! Gradient fills for the body and eyes
+//!
+//! Gradients are emitted as `<linearGradient>`/`<radialGradient>` in one
+//! `<defs>` block and referenced from the paths with `url(#...)`. They span
+//! each path's bounding box, so the body and every eye with its own
+//! gradient run from edge to edge of what they cover.
+//!
+//! Ids end in a hash of the render's gradients: a page that inlines several
+//! codes would otherwise paint them all with the first one's gradient.
+//! Renders with the same gradients share ids, and their definitions too.
+
+use crate::error::QrError;
+use std::fmt::Write;
+
+/// Id prefix of the gradient elements in the SVG (`holi-gradient-fg-<suffix>`,
+/// `holi-gradient-top_left-<suffix>`, ...)
+pub const GRADIENT_ID_PREFIX: &str = "holi-gradient-";
+
+/// Gradient geometry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GradientKind {
+    /// Along a line through the center, at `Gradient::angle`
+    #[default]
+    Linear,
+    /// Outward from the center
+    Radial,
+}
+
+impl GradientKind {
+    /// Parse from string (for WASM/JSON interop)
+    #[allow(clippy::should_implement_trait)] // Infallible: unknown names fall back to Linear.
+    pub fn from_str(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "radial" => Self::Radial,
+            _ => Self::Linear,
+        }
+    }
+
+    /// Name as accepted by `from_str`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Linear => "linear",
+            Self::Radial => "radial",
+        }
+    }
+}
+
+/// One color of a gradient
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradientStop {
+    /// Position along the gradient, 0.0 to 1.0 (clamped)
+    pub offset: f64,
+    /// `#RGB` or `#RRGGBB`
+    pub color: String,
+}
+
+/// Gradient that replaces a solid foreground color
+///
+/// A gradient without stops is ignored and the solid color is used.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    /// Colors in order of `offset`
+    pub stops: Vec<GradientStop>,
+    /// Direction of a linear gradient, in degrees clockwise from
+    /// left-to-right (90 runs top to bottom); ignored for radial gradients
+    pub angle: f64,
+}
+
+impl Gradient {
+    /// Whether the gradient is drawn at all
+    pub(crate) fn is_drawn(&self) -> bool {
+        !self.stops.is_empty()
+    }
+
+    /// Bytes `write_def` writes, roughly
+    pub(crate) fn markup_capacity(&self) -> usize {
+        180 + self.stops.iter().map(|s| 48 + s.color.len()).sum::<usize>()
+    }
+
+    /// The gradient element with id `GRADIENT_ID_PREFIX` + `name` + `-` + `suffix`
+    pub(crate) fn write_def(&self, name: &str, suffix: &str, svg: &mut String) -> Result<(), QrError> {
+        let tag = match self.kind {
+            GradientKind::Linear => {
+                let angle = if self.angle.is_finite() { self.angle.to_radians() } else { 0.0 };
+                let (dx, dy) = (angle.cos() / 2.0, angle.sin() / 2.0);
+                write!(
+                    svg,
+                    r#"<linearGradient id="{}{}-{}" x1="{:.4}" y1="{:.4}" x2="{:.4}" y2="{:.4}">"#,
+                    GRADIENT_ID_PREFIX,
+                    name,
+                    suffix,
+                    0.5 - dx,
+                    0.5 - dy,
+                    0.5 + dx,
+                    0.5 + dy,
+                )?;
+                "linearGradient"
+            }
+            GradientKind::Radial => {
+                write!(
+                    svg,
+                    r#"<radialGradient id="{}{}-{}" cx="0.5" cy="0.5" r="0.5">"#,
+                    GRADIENT_ID_PREFIX,
+                    name,
+                    suffix
+                )?;
+                "radialGradient"
+            }
+        };
+        for stop in &self.stops {
+            let offset = if stop.offset.is_nan() { 0.0 } else { stop.offset.clamp(0.0, 1.0) };
+            write!(svg, r#"<stop offset="{}" stop-color="{}"/>"#, offset, stop.color)?;
+        }
+        write!(svg, "</{}>", tag)?;
+        Ok(())
+    }
+}
+
+/// `url(#...)` reference to the gradient written as `name` with `suffix`
+pub(crate) fn gradient_url(name: &str, suffix: &str) -> String {
+    format!("url(#{}{}-{})", GRADIENT_ID_PREFIX, name, suffix)
+}
+
+/// Id suffix for a render with `gradients`: 64-bit FNV-1a of their names
+/// and what they draw, in hex
+pub(crate) fn gradient_suffix<'a>(gradients: impl IntoIterator<Item = (&'a str, &'a Gradient)>) -> String {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for (name, gradient) in gradients {
+        feed(name.as_bytes());
+        feed(gradient.kind.as_str().as_bytes());
+        feed(&gradient.angle.to_bits().to_le_bytes());
+        for stop in &gradient.stops {
+            feed(&stop.offset.to_bits().to_le_bytes());
+            feed(stop.color.as_bytes());
+            // Keeps ("#0", "00") apart from ("#00", "0")
+            feed(&[0]);
+        }
+        feed(&[0xFF]);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops(colors: &[&str]) -> Vec<GradientStop> {
+        let last = (colors.len() - 1).max(1) as f64;
+        colors
+            .iter()
+            .enumerate()
+            .map(|(i, c)| GradientStop { offset: i as f64 / last, color: c.to_string() })
+            .collect()
+    }
+
+    #[test]
+    fn test_linear_direction() {
+        let mut svg = String::new();
+        let down = Gradient { kind: GradientKind::Linear, stops: stops(&["#000000", "#123456"]), angle: 90.0 };
+        down.write_def("fg", "0", &mut svg).unwrap();
+        assert_eq!(
+            svg,
+            r##"<linearGradient id="holi-gradient-fg-0" x1="0.5000" y1="0.0000" x2="0.5000" y2="1.0000"><stop offset="0" stop-color="#000000"/><stop offset="1" stop-color="#123456"/></linearGradient>"##
+        );
+
+        // A bad angle falls back to left-to-right; offsets are clamped
+        svg.clear();
+        let odd = Gradient {
+            angle: f64::NAN,
+            stops: vec![GradientStop { offset: -1.0, color: "#000".into() }, GradientStop { offset: f64::NAN, color: "#111".into() }],
+            ..down
+        };
+        odd.write_def("top_left", "0", &mut svg).unwrap();
+        assert!(svg.contains(r#"x1="0.0000" y1="0.5000" x2="1.0000" y2="0.5000""#));
+        assert_eq!(svg.matches(r#"offset="0""#).count(), 2);
+    }
+
+    #[test]
+    fn test_radial() {
+        let mut svg = String::new();
+        let radial = Gradient { kind: GradientKind::Radial, stops: stops(&["#000000", "#333333", "#000000"]), angle: 45.0 };
+        radial.write_def("fg", &gradient_suffix([("fg", &radial)]), &mut svg).unwrap();
+        assert!(svg.starts_with(r#"<radialGradient id="holi-gradient-fg-"#));
+        assert!(svg.contains(r#"" cx="0.5" cy="0.5" r="0.5">"#));
+        assert!(svg.contains(r#"offset="0.5""#));
+        assert!(svg.ends_with("</radialGradient>"));
+        assert!(svg.len() <= radial.markup_capacity());
+        assert_eq!(GradientKind::from_str("RADIAL"), GradientKind::Radial);
+        assert_eq!(GradientKind::from_str("conic"), GradientKind::Linear);
+    }
+
+    #[test]
+    fn test_suffix_follows_the_gradients() {
+        let blue = Gradient { stops: stops(&["#000000", "#0000FF"]), ..Default::default() };
+        let red = Gradient { stops: stops(&["#000000", "#FF0000"]), ..Default::default() };
+        assert_eq!(gradient_suffix([("fg", &blue)]), gradient_suffix([("fg", &blue.clone())]));
+        assert_ne!(gradient_suffix([("fg", &blue)]), gradient_suffix([("fg", &red)]));
+        assert_ne!(gradient_suffix([("fg", &blue)]), gradient_suffix([("top_left", &blue)]));
+        assert_ne!(gradient_suffix([("fg", &blue)]), gradient_suffix([("fg", &Gradient { angle: 90.0, ..blue.clone() })]));
+        assert_eq!(gradient_suffix([("fg", &blue)]).len(), 16);
+    }
+}
@@ -0,0 +1,170 @@
+//! Kanji mode: Japanese text at 13 bits a character
+//!
+//! In byte mode a kanji or kana costs 24 bits as UTF-8. Kanji mode packs
+//! any double-byte Shift-JIS character into 13, so Japanese payloads fit
+//! smaller symbols. ASCII between them goes in its own segments; scanners
+//! read Kanji segments as Shift-JIS whatever they assume for byte mode.
+//!
+//! The Shift-JIS table comes with the `shift-jis` feature; without it text
+//! is encoded as by `generate_qr`.
+
+use crate::codewords::{build_symbol, BitBuffer};
+use crate::eci::shift_jis_code;
+use crate::error::QrError;
+use crate::qr::{best_mode, data_codewords, generate_qr, min_version, ErrorCorrectionLevel, QrCode};
+use crate::segments::Segment;
+use fast_qr::Mode;
+
+/// A run of `text` encoded in one mode
+enum Run<'a> {
+    /// 13-bit Kanji values
+    Kanji(Vec<u16>),
+    Ascii(&'a str),
+}
+
+impl Run<'_> {
+    fn segment(&self, version: usize) -> Segment {
+        match self {
+            Run::Kanji(values) => Segment::kanji(values.len(), version),
+            Run::Ascii(text) => Segment::new(best_mode(text.as_bytes()), text.len(), version),
+        }
+    }
+}
+
+/// Kanji mode value of `c`: its Shift-JIS code in 0x8140-0x9FFC or
+/// 0xE040-0xEBBF, folded into 13 bits
+fn kanji_value(c: char) -> Option<u16> {
+    let code = shift_jis_code(c)?;
+    let offset = match code {
+        0x8140..=0x9FFC => code - 0x8140,
+        0xE040..=0xEBBF => code - 0xC140,
+        _ => return None,
+    };
+    Some((offset >> 8) * 0xC0 + (offset & 0xFF))
+}
+
+/// `text` split into Kanji and ASCII runs; `None` if it has any other
+/// character
+fn runs(text: &str) -> Option<Vec<Run<'_>>> {
+    let mut runs = Vec::new();
+    let mut ascii_from = None;
+    for (i, c) in text.char_indices() {
+        if c.is_ascii() {
+            ascii_from.get_or_insert(i);
+            continue;
+        }
+        let value = kanji_value(c)?;
+        if let Some(from) = ascii_from.take() {
+            runs.push(Run::Ascii(&text[from..i]));
+        }
+        match runs.last_mut() {
+            Some(Run::Kanji(values)) => values.push(value),
+            _ => runs.push(Run::Kanji(vec![value])),
+        }
+    }
+    if let Some(from) = ascii_from {
+        runs.push(Run::Ascii(&text[from..]));
+    }
+    Some(runs)
+}
+
+/// Generate a QR code with Japanese text in Kanji mode
+///
+/// Characters in the Shift-JIS double-byte range are written as Kanji
+/// segments and ASCII around them in its own; `text` on the result is
+/// `text` unchanged. Text with no Kanji-mode characters, with characters
+/// that are neither, or that Kanji mode would not fit in a smaller or equal
+/// symbol, is encoded as by `generate_qr`. So is everything without the
+/// `shift-jis` feature.
+///
+/// # Returns
+/// * `Ok(QrCode)` - The smallest symbol that holds it
+/// * `Err(QrError::InputTooLong)` - Too long for version 40
+///
+/// # Example
+/// ```rust
+/// use holi_qr::{generate_qr, generate_qr_kanji, ErrorCorrectionLevel};
+///
+/// let text = "東京タワーは港区にある電波塔です。".repeat(8);
+/// let qr = generate_qr_kanji(&text, ErrorCorrectionLevel::Medium).unwrap();
+/// assert_eq!(qr.text, text);
+/// assert!(qr.size() <= generate_qr(&text, ErrorCorrectionLevel::Medium).unwrap().size());
+/// ```
+pub fn generate_qr_kanji(text: &str, ecl: ErrorCorrectionLevel) -> Result<QrCode, QrError> {
+    let Some(runs) = runs(text).filter(|runs| runs.iter().any(|run| matches!(run, Run::Kanji(_)))) else {
+        return generate_qr(text, ecl);
+    };
+
+    let plain = min_version(best_mode(text.as_bytes()), text.len(), ecl).unwrap_or(41);
+    let fits = |v: usize| runs.iter().map(|run| run.segment(v).bits).sum::<usize>() <= data_codewords(v, ecl) * 8;
+    let Some(version) = (1..=40).find(|&v| fits(v)).filter(|&v| v <= plain) else {
+        return generate_qr(text, ecl);
+    };
+
+    let mut data = BitBuffer::default();
+    let mut segments = Vec::with_capacity(runs.len());
+    for run in &runs {
+        let segment = run.segment(version);
+        match run {
+            Run::Kanji(values) => {
+                data.push(0b1000, 4);
+                data.push(values.len(), segment.bits - 4 - 13 * values.len());
+                for &value in values {
+                    data.push(value as usize, 13);
+                }
+            }
+            Run::Ascii(ascii) => data.push_segment(ascii.as_bytes(), best_mode(ascii.as_bytes()), version),
+        }
+        segments.push(segment);
+    }
+    let inner = build_symbol(data, text.as_bytes(), Mode::Byte, version, ecl)?;
+    QrCode::from_segments(inner, text.to_string(), segments, ecl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segments::SegmentMode;
+
+    #[test]
+    #[cfg(feature = "shift-jis")]
+    fn test_kanji_segments() {
+        // ISO/IEC 18004 7.4.6: 点 (0x935F) is 0xD9F, 茗 (0xE4AA) is 0x1AAA
+        let qr = generate_qr_kanji("点茗", ErrorCorrectionLevel::Low).unwrap();
+        assert_eq!(qr.text, "点茗");
+        assert_eq!(qr.segments(), [Segment { mode: SegmentMode::Kanji, length: 2, bits: 38 }]);
+        // 1000 | 00000010 | 0110110011111 | 1101010101010
+        assert_eq!(qr.debug_layout().codewords[..5], [0x80, 0x26, 0xCF, 0xEA, 0xA8]);
+
+        let qr = generate_qr_kanji("ID 42 東京", ErrorCorrectionLevel::Low).unwrap();
+        let modes: Vec<_> = qr.segments().iter().map(|s| (s.mode, s.length)).collect();
+        assert_eq!(modes, [(SegmentMode::Alphanumeric, 6), (SegmentMode::Kanji, 2)]);
+
+        let text = "東京タワーは港区にある電波塔です。".repeat(8);
+        let kanji = generate_qr_kanji(&text, ErrorCorrectionLevel::Medium).unwrap();
+        assert!(kanji.size() < generate_qr(&text, ErrorCorrectionLevel::Medium).unwrap().size());
+    }
+
+    #[test]
+    fn test_falls_back_to_byte_mode() {
+        // No Kanji, or a character Kanji mode lacks
+        for text in ["https://holi.tools", "東京 😀", "ｶﾀｶﾅ"] {
+            let qr = generate_qr_kanji(text, ErrorCorrectionLevel::Medium).unwrap();
+            assert_eq!(qr.matrix(), generate_qr(text, ErrorCorrectionLevel::Medium).unwrap().matrix(), "{}", text);
+            assert_eq!(qr.segments()[0].mode, SegmentMode::from(best_mode(text.as_bytes())));
+        }
+        assert!(matches!(generate_qr_kanji("", ErrorCorrectionLevel::Medium), Err(QrError::EmptyInput)));
+    }
+
+    #[test]
+    #[cfg(all(feature = "verify", feature = "shift-jis"))]
+    fn test_kanji_scans() {
+        use crate::{render_svg, verify_svg};
+
+        let long = "日本語のテキストを漢字モードで符号化する。".repeat(10);
+        for text in ["東京タワー 333m", "点茗", long.as_str()] {
+            let qr = generate_qr_kanji(text, ErrorCorrectionLevel::Medium).unwrap();
+            assert_eq!(verify_svg(&render_svg(&qr)).unwrap(), text);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Module roles and raw codewords, for teaching and for debugging scans
+//!
+//! Every module of a symbol is either a function pattern (finders, timing,
+//! format...) or carries one bit of the interleaved codeword stream: data
+//! codewords first, then error correction. `QrCode::debug_layout` recovers
+//! both from the final matrix by undoing the mask.
+
+use crate::error::QrError;
+use crate::qr::{data_codewords, QrCode};
+use crate::render::{padded_size, render_svg_with_options, RenderOptions, MAX_MARGIN};
+use fast_qr::ModuleType;
+use std::fmt::Write;
+
+/// What a module is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModuleRole {
+    /// The three 7x7 position squares
+    Finder,
+    /// Light border around the finders
+    Separator,
+    Alignment,
+    Timing,
+    /// ECC level and mask, twice
+    Format,
+    /// Version number (version 7 and up), twice
+    Version,
+    /// The single always-dark module next to the bottom-left finder
+    DarkModule,
+    /// Bit of a data codeword
+    Data,
+    /// Bit of an error correction codeword
+    Ecc,
+    /// Leftover bits after the last codeword
+    Remainder,
+}
+
+impl ModuleRole {
+    pub const ALL: [ModuleRole; 10] = [
+        Self::Finder,
+        Self::Separator,
+        Self::Alignment,
+        Self::Timing,
+        Self::Format,
+        Self::Version,
+        Self::DarkModule,
+        Self::Data,
+        Self::Ecc,
+        Self::Remainder,
+    ];
+
+    /// Name for WASM/JSON interop
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Finder => "finder",
+            Self::Separator => "separator",
+            Self::Alignment => "alignment",
+            Self::Timing => "timing",
+            Self::Format => "format",
+            Self::Version => "version",
+            Self::DarkModule => "dark-module",
+            Self::Data => "data",
+            Self::Ecc => "ecc",
+            Self::Remainder => "remainder",
+        }
+    }
+
+    /// Overlay color used by `render_svg_layout`
+    pub fn color(&self) -> &'static str {
+        match self {
+            Self::Finder => "#C62828",
+            Self::Separator => "#9E9E9E",
+            Self::Alignment => "#EF6C00",
+            Self::Timing => "#6A1B9A",
+            Self::Format => "#1565C0",
+            Self::Version => "#00838F",
+            Self::DarkModule => "#000000",
+            Self::Data => "#2E7D32",
+            Self::Ecc => "#4E342E",
+            Self::Remainder => "#616161",
+        }
+    }
+}
+
+/// Structure of a symbol, from `QrCode::debug_layout`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugLayout {
+    /// Modules per side
+    pub size: usize,
+    /// 1 to 40
+    pub version: usize,
+    /// Mask pattern, 0 to 7
+    pub mask: u8,
+    /// Role of each module, row by row
+    pub roles: Vec<ModuleRole>,
+    /// Codeword each data/ECC module belongs to (index into `codewords`), row by row
+    pub codeword_index: Vec<Option<usize>>,
+    /// Codewords in placement order (interleaved across blocks), mask removed
+    pub codewords: Vec<u8>,
+    /// How many of `codewords` are data; the rest are error correction
+    pub data_codewords: usize,
+}
+
+impl QrCode {
+    /// Classify every module and read back the codewords
+    pub fn debug_layout(&self) -> DebugLayout {
+        let size = self.inner.size;
+        let version = (size - 17) / 4;
+        let data_codewords = data_codewords(version, self.ecl);
+
+        // Masking flips data modules only, so applying it again undoes it
+        let mut unmasked = self.inner.clone();
+        let mask = self.mask;
+        fast_qr::datamasking::mask(&mut unmasked, mask);
+
+        let mut roles: Vec<ModuleRole> = self.inner.data[..size * size]
+            .iter()
+            .map(|m| match m.module_type() {
+                ModuleType::FinderPattern => ModuleRole::Finder,
+                ModuleType::Empty => ModuleRole::Separator,
+                ModuleType::Alignment => ModuleRole::Alignment,
+                ModuleType::Timing => ModuleRole::Timing,
+                ModuleType::Format => ModuleRole::Format,
+                ModuleType::Version => ModuleRole::Version,
+                ModuleType::DarkModule => ModuleRole::DarkModule,
+                ModuleType::Data => ModuleRole::Data,
+            })
+            .collect();
+
+        let order = placement_order(&self.inner);
+        let total_codewords = order.len() / 8;
+        let mut codewords = vec![0u8; total_codewords];
+        let mut codeword_index = vec![None; size * size];
+        for (bit, &module) in order.iter().enumerate() {
+            let codeword = bit / 8;
+            if codeword >= total_codewords {
+                roles[module] = ModuleRole::Remainder;
+                continue;
+            }
+            if unmasked.data[module].value() {
+                codewords[codeword] |= 0x80 >> (bit % 8);
+            }
+            codeword_index[module] = Some(codeword);
+            if codeword >= data_codewords {
+                roles[module] = ModuleRole::Ecc;
+            }
+        }
+
+        DebugLayout {
+            size,
+            version,
+            mask: mask as u8,
+            roles,
+            codeword_index,
+            codewords,
+            data_codewords,
+        }
+    }
+
+    /// Every module as `(x, y, is_dark, role)`, row by row
+    ///
+    /// For renderers and exporters that need positions and roles together;
+    /// finder modules are those with `ModuleRole::Finder`.
+    pub fn iter_modules(&self) -> impl Iterator<Item = (usize, usize, bool, ModuleRole)> + '_ {
+        let size = self.size();
+        let roles = self.debug_layout().roles;
+        roles
+            .into_iter()
+            .enumerate()
+            .map(move |(i, role)| (i % size, i / size, self.matrix().is_dark(i % size, i / size), role))
+    }
+}
+
+/// Indices (`y * size + x`) of the data modules of `qr` in placement order,
+/// remainder bits included
+///
+/// Column pairs from the right (skipping the vertical timing column),
+/// alternately upward and downward.
+pub(crate) fn placement_order(qr: &fast_qr::QRCode) -> Vec<usize> {
+    let size = qr.size;
+    let mut order = Vec::new();
+    let mut upward = true;
+    for x in (0..6).chain(7..size).rev().step_by(2) {
+        for i in 0..size {
+            let y = if upward { size - 1 - i } else { i };
+            for x in [x, x - 1] {
+                if qr.data[y * size + x].module_type() == ModuleType::Data {
+                    order.push(y * size + x);
+                }
+            }
+        }
+        upward = !upward;
+    }
+    order
+}
+
+/// Render `qr` with every module colored by its role
+///
+/// Dark modules use the role's color, light modules a pale tint of it, so
+/// the structure is visible while the code still reads as a QR code. A
+/// margin over `MAX_MARGIN` is drawn as `MAX_MARGIN`.
+pub fn render_svg_layout(qr: &QrCode, margin: usize) -> String {
+    let margin = margin.min(MAX_MARGIN);
+    // Writing to a String doesn't fail; should it, still show the code
+    try_render_svg_layout(qr, margin)
+        .unwrap_or_else(|_| render_svg_with_options(qr, &RenderOptions { margin, ..Default::default() }))
+}
+
+/// `render_svg_layout`, refusing a margin it would clamp
+///
+/// # Returns
+/// * `Err(QrError::InvalidArgument)` - Margin over `MAX_MARGIN`
+pub fn try_render_svg_layout(qr: &QrCode, margin: usize) -> Result<String, QrError> {
+    let layout = qr.debug_layout();
+    let size = layout.size;
+    let total = padded_size(size, margin)?;
+    let modules = qr.matrix();
+
+    let mut svg = String::new();
+    write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}"><rect width="{}" height="{}" fill="#FFFFFF"/>"##,
+        total, total, total, total
+    )?;
+    for role in ModuleRole::ALL {
+        for dark in [false, true] {
+            let mut path = String::new();
+            for (i, _) in layout.roles.iter().enumerate().filter(|&(i, &r)| r == role && modules.is_dark(i % size, i / size) == dark) {
+                write!(path, "M{},{}h1v1h-1z", i % size + margin, i / size + margin)?;
+            }
+            if path.is_empty() {
+                continue;
+            }
+            let opacity = if dark { "" } else { r#" fill-opacity="0.2""# };
+            write!(svg, r#"<path d="{}" fill="{}"{}/>"#, path, role.color(), opacity)?;
+        }
+    }
+    svg.push_str("</svg>");
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_qr, ErrorCorrectionLevel};
+
+    fn count(layout: &DebugLayout, role: ModuleRole) -> usize {
+        layout.roles.iter().filter(|&&r| r == role).count()
+    }
+
+    #[test]
+    fn test_version_1_layout() {
+        let qr = generate_qr("hi", ErrorCorrectionLevel::Medium).unwrap();
+        let layout = qr.debug_layout();
+        assert_eq!((layout.size, layout.version), (21, 1));
+        assert_eq!(count(&layout, ModuleRole::Finder), 3 * 49);
+        assert_eq!(count(&layout, ModuleRole::Timing), 2 * 5);
+        assert_eq!(count(&layout, ModuleRole::Version), 0);
+        assert_eq!(count(&layout, ModuleRole::DarkModule), 1);
+
+        // 26 codewords: 16 data + 10 ECC, no remainder bits
+        assert_eq!(layout.codewords.len(), 26);
+        assert_eq!(layout.data_codewords, 16);
+        assert_eq!(count(&layout, ModuleRole::Data), 16 * 8);
+        assert_eq!(count(&layout, ModuleRole::Ecc), 10 * 8);
+        assert_eq!(count(&layout, ModuleRole::Remainder), 0);
+
+        // Byte mode (0100), length 2, "hi", terminator, then pad bytes
+        let c = &layout.codewords;
+        assert_eq!(c[0], 0x40);
+        assert_eq!(c[1], 0x26);
+        assert_eq!(c[2], 0x86);
+        assert_eq!(c[3], 0x90);
+        assert_eq!(&c[4..8], &[0xEC, 0x11, 0xEC, 0x11]);
+
+        let bits = layout.codeword_index.iter().flatten().filter(|&&i| i == 3).count();
+        assert_eq!(bits, 8);
+    }
+
+    #[test]
+    fn test_iter_modules() {
+        let qr = generate_qr("https://holi.tools/iter", ErrorCorrectionLevel::Quartile).unwrap();
+        let size = qr.size();
+        let modules: Vec<_> = qr.iter_modules().collect();
+        assert_eq!(modules.len(), size * size);
+        assert_eq!(modules[size + 2], (2, 1, false, ModuleRole::Finder));
+
+        for &(x, y, is_dark, role) in &modules {
+            assert_eq!(is_dark, qr.matrix().is_dark(x, y));
+            let corner = (x < 7 || x >= size - 7) && (y < 7 || y >= size - 7) && !(x >= size - 7 && y >= size - 7);
+            assert_eq!(role == ModuleRole::Finder, corner, "({}, {})", x, y);
+        }
+        assert!(modules.iter().any(|&(_, _, _, role)| role == ModuleRole::Ecc));
+    }
+
+    #[test]
+    fn test_larger_versions() {
+        let text = "https://holi.tools/".repeat(8);
+        let qr = generate_qr(&text, ErrorCorrectionLevel::Quartile).unwrap();
+        let layout = qr.debug_layout();
+        assert!(layout.version >= 7);
+        assert_eq!(count(&layout, ModuleRole::Version), 2 * 18);
+        assert!(count(&layout, ModuleRole::Alignment) > 0);
+        assert_eq!(count(&layout, ModuleRole::Data) + count(&layout, ModuleRole::Ecc), layout.codewords.len() * 8);
+
+        let svg = render_svg_layout(&qr, 4);
+        for role in [ModuleRole::Finder, ModuleRole::Version, ModuleRole::Data, ModuleRole::Ecc] {
+            assert!(svg.contains(role.color()), "{}", role.as_str());
+        }
+    }
+}
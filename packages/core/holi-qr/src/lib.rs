@@ -14,16 +14,87 @@
 //! let svg = render_svg(&qr);
 //! println!("{}", svg);
 //! ```
+//!
+//! Nothing here may panic on caller input: a panic aborts the whole WASM
+//! instance. Rendering reports bad options as `QrError` instead.
+
+#![deny(clippy::unwrap_used, clippy::expect_used)]
 
+mod accessible;
+mod artistic;
+mod binarize;
+mod camera;
+mod classify;
+mod codewords;
+#[cfg(test)]
+mod conformance;
+mod diagnose;
+mod eci;
 mod error;
+mod extrude;
+#[cfg(test)]
+mod fuzz;
+mod gradient;
+mod kanji;
+mod layout;
+mod logo;
+mod matrix;
+mod pattern;
+mod payloads;
+mod presets;
+mod print;
+mod protect;
 mod qr;
 mod render;
+mod segments;
 mod shapes;
+mod signed;
+mod structured;
+#[cfg(test)]
+mod snapshots;
+mod validate;
 mod verify;
 
+pub use accessible::{
+    enforce_accessible_theme, AccessibilityPolicy, AccessibleTheme, ThemeAdjustment, DEFAULT_MIN_CONTRAST,
+    DEFAULT_MIN_MODULE_PX,
+};
+pub use artistic::{render_artistic, render_artistic_svg, ArtisticOptions, ArtisticQr};
+pub use camera::{analyze_frames, frame_stats, FrameStats, ScanAnalysis, ScanHint};
+pub use classify::{classify_payload, PayloadClassification, PayloadKind, PayloadRisk};
+pub use diagnose::{diagnose_luma, DecodeDiagnostics, DecodeStage};
+pub use eci::{generate_qr_eci, Charset};
 pub use error::QrError;
-pub use qr::{generate_qr, QrCode, ErrorCorrectionLevel};
-pub use render::{render_svg, render_svg_with_options, render_svg_styled, RenderOptions, StyledRenderOptions};
-pub use shapes::{BodyShape, EyeFrameShape, EyeBallShape, body_path, eye_frame_path, eye_ball_path};
-pub use verify::{verify_svg, decode_image};
+pub use extrude::{render_stl, ExtrudeOptions};
+pub use gradient::{Gradient, GradientKind, GradientStop, GRADIENT_ID_PREFIX};
+pub use kanji::generate_qr_kanji;
+pub use layout::{render_svg_layout, try_render_svg_layout, DebugLayout, ModuleRole};
+pub use logo::{check_logo, render_svg_with_logo, Logo, LogoFit, LogoImage, MAX_LOGO_SIZE};
+pub use matrix::ModuleMatrix;
+pub use pattern::{constrain_pattern_color, BackgroundPattern, PatternKind, PATTERN_CONTRAST_KEEP};
+pub use payloads::{generate_wifi_qr, GeoPoint, MailTo, SmsMessage, Tel, VEvent, WifiAuth, WifiCredentials};
+pub use presets::{preset, render_with_preset, StylePreset, PRESETS};
+pub use protect::{shape_substitutions, ProtectionRule, ShapeSubstitution, PROTECTION_RULES};
+pub use print::{print_requirements, print_requirements_at_dpi, PrintRequirements};
+pub use qr::{capacity, fit_best_ecc, generate_qr, generate_qr_bytes, generate_qr_constrained, QrCode, ErrorCorrectionLevel};
+pub use fast_qr::Mode;
+pub use render::{
+    parse_styled_svg, render_svg, render_svg_with_options, render_svg_styled, render_svg_styled_diff,
+    render_svg_styled_into, try_render_svg_styled, try_render_svg_styled_diff, try_render_svg_styled_into,
+    try_render_svg_with_options, EyeCorner, EyeOverride, OutlineStyle, RenderOptions, StyledRenderOptions, StyledSvgLayers,
+    SvgLayer, SvgPart, SvgPatch, MAX_MARGIN,
+};
+pub use segments::{optimize_payload, PayloadHint, PayloadHintKind, Segment, SegmentMode};
+pub use structured::{generate_qr_structured, MAX_STRUCTURED_SYMBOLS};
+pub use shapes::{BodyShape, EyeFrameShape, EyeBallShape, body_path, contour_body_path, eye_frame_path, eye_ball_path, liquid_body_path};
+pub use signed::{
+    encode_signed_payload, fingerprint_digest, generate_signed_qr, signed_qr_key_id, signed_qr_message,
+    split_signed_payload, QrSigner, SignedPayload, KEY_ID_LEN, SIGNATURE_LEN, SIGNED_QR_VERSION,
+    SIGNED_TRAILER_LEN,
+};
+pub use validate::{unknown_shape_issue, validate_options, Severity, ValidationIssue, MIN_SCAN_CONTRAST, SPEC_QUIET_ZONE};
+pub use verify::{
+    verify_svg, verify_batch, decode_image, decode_luma, check_readability, rasterize_svg, render_png, rgba_to_luma, BatchStats,
+    Readability, VerifyResult, MAX_RASTER_SIZE, PNG_MODULE_PIXELS,
+};
 
@@ -0,0 +1,359 @@
+//! Logo in the middle of a styled code
+//!
+//! The modules under the logo (plus `padding` around it) are left out of
+//! the body path and the image is drawn in the cleared square. Every
+//! codeword that loses a module has to be recovered by error correction,
+//! so `check_logo` counts them against what the code's ECC level can fix;
+//! `render_svg_with_logo` also decodes the result before handing it out.
+//!
+//! Both kinds of image go in as an `<image>` with a data URI. A page that
+//! inlines the SVG runs no script and loads nothing from an SVG logo that way.
+
+use crate::error::QrError;
+use crate::layout::ModuleRole;
+use crate::qr::QrCode;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Largest logo side, as a share of the symbol side
+pub const MAX_LOGO_SIZE: f64 = 0.4;
+
+const SVG_MIME_TYPE: &str = "image/svg+xml";
+/// Room for the `<svg>` document an SVG logo is wrapped in, less the markup
+const SVG_WRAPPER_LEN: usize = 192;
+
+/// What to draw in the cleared square
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogoImage {
+    /// Encoded image file (PNG, JPEG, WebP...), embedded as a data URI
+    Raster { mime_type: String, data: Vec<u8> },
+    /// SVG markup (elements, or a whole `<svg>` document) drawn in a
+    /// `width` x `height` coordinate space, scaled to fit the square
+    ///
+    /// Embedded as an `image/svg+xml` data URI, where browsers don't run its
+    /// scripts or event handlers or fetch what it links to.
+    Svg { markup: String, width: f64, height: f64 },
+}
+
+/// Image embedded over the center of the code
+#[derive(Debug, Clone, PartialEq)]
+pub struct Logo {
+    pub image: LogoImage,
+    /// Side of the image, as a share of the symbol side (0 to `MAX_LOGO_SIZE`)
+    pub size: f64,
+    /// Modules kept clear around the image
+    pub padding: usize,
+}
+
+impl Logo {
+    /// First module and side of the cleared square, in modules
+    ///
+    /// The square is centered on the symbol and covers whole modules.
+    pub fn cleared_area(&self, symbol_size: usize) -> (usize, usize) {
+        let image = (self.size.clamp(0.0, MAX_LOGO_SIZE) * symbol_size as f64).ceil() as usize;
+        let mut side = image.saturating_add(self.padding.saturating_mul(2)).min(symbol_size);
+        // Same parity as the symbol, so the square sits exactly in the middle
+        if (symbol_size - side) % 2 == 1 {
+            side += 1;
+        }
+        ((symbol_size - side) / 2, side)
+    }
+
+    /// Whether module `(x, y)` is hidden by the logo
+    pub(crate) fn clears(&self, symbol_size: usize, x: usize, y: usize) -> bool {
+        let (start, side) = self.cleared_area(symbol_size);
+        (start..start + side).contains(&x) && (start..start + side).contains(&y)
+    }
+
+    /// Refuse what can't be drawn: a size outside 0-`MAX_LOGO_SIZE` or an
+    /// empty image
+    pub(crate) fn check_drawable(&self) -> Result<(), QrError> {
+        if !self.size.is_finite() || self.size <= 0.0 || self.size > MAX_LOGO_SIZE {
+            return Err(QrError::InvalidArgument(format!(
+                "Logo size must be above 0 and at most {} (got {})",
+                MAX_LOGO_SIZE, self.size
+            )));
+        }
+        let empty = match &self.image {
+            LogoImage::Raster { data, .. } => data.is_empty(),
+            LogoImage::Svg { markup, width, height } => {
+                markup.trim().is_empty() || !(width.is_finite() && *width > 0.0 && height.is_finite() && *height > 0.0)
+            }
+        };
+        if empty {
+            return Err(QrError::InvalidArgument("Logo image is empty".into()));
+        }
+        Ok(())
+    }
+
+    /// Bytes `write_markup` is expected to write
+    pub(crate) fn markup_capacity(&self) -> usize {
+        let (mime_type, data) = match &self.image {
+            LogoImage::Raster { mime_type, data } => (mime_type.len(), data.len()),
+            LogoImage::Svg { markup, .. } => (SVG_MIME_TYPE.len(), markup.len() + SVG_WRAPPER_LEN),
+        };
+        128 + mime_type + data.div_ceil(3) * 4
+    }
+
+    /// Draw the image centered in the cleared square, `margin` modules in
+    pub(crate) fn write_markup(&self, symbol_size: usize, margin: usize, svg: &mut String) -> Result<(), QrError> {
+        let (start, side) = self.cleared_area(symbol_size);
+        let inner = side.saturating_sub(self.padding.saturating_mul(2)).max(1) as f64;
+        let offset = (margin + start) as f64 + (side as f64 - inner) / 2.0;
+        let (mime_type, data) = match &self.image {
+            LogoImage::Raster { mime_type, data } => (mime_type.replace(['"', '<', '>', '&'], ""), Cow::Borrowed(data.as_slice())),
+            LogoImage::Svg { markup, width, height } => {
+                let markup = markup.trim_start();
+                // An XML prolog is only allowed at the start of a document
+                let markup = match markup.strip_prefix("<?xml") {
+                    Some(rest) => rest.split_once("?>").map_or("", |(_, body)| body),
+                    None => markup,
+                };
+                let document = format!(
+                    r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" viewBox="0 0 {} {}" preserveAspectRatio="xMidYMid meet">{}</svg>"#,
+                    width, height, markup
+                );
+                (SVG_MIME_TYPE.to_string(), Cow::Owned(document.into_bytes()))
+            }
+        };
+        write!(
+            svg,
+            r#"<image x="{o}" y="{o}" width="{s}" height="{s}" preserveAspectRatio="xMidYMid meet" href="data:{};base64,"#,
+            mime_type,
+            o = offset,
+            s = inner
+        )?;
+        push_base64(&data, svg);
+        svg.push_str(r#""/>"#);
+        Ok(())
+    }
+}
+
+fn push_base64(data: &[u8], out: &mut String) {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let n = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+}
+
+/// How much of a code a logo hides, from `check_logo`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogoFit {
+    /// Data and error correction modules under the logo
+    pub cleared_modules: usize,
+    /// Codewords with at least one of those modules
+    pub damaged_codewords: usize,
+    /// Codewords the error correction can restore: half the ECC codewords
+    pub correctable_codewords: usize,
+    /// The logo hides finder, timing, format or version modules, which
+    /// error correction doesn't cover (alignment patterns may be hidden)
+    pub covers_function_patterns: bool,
+}
+
+impl LogoFit {
+    /// Whether error correction can make up for the logo
+    ///
+    /// A rough bound: codewords are spread over several blocks that each
+    /// correct their own share, so check the result with a scanner or
+    /// `render_svg_with_logo` too.
+    pub fn fits(&self) -> bool {
+        !self.covers_function_patterns && self.damaged_codewords <= self.correctable_codewords
+    }
+}
+
+/// Count what `logo` hides of `qr` against its error correction
+pub fn check_logo(qr: &QrCode, logo: &Logo) -> LogoFit {
+    let layout = qr.debug_layout();
+    let size = layout.size;
+    let mut fit = LogoFit {
+        cleared_modules: 0,
+        damaged_codewords: 0,
+        correctable_codewords: (layout.codewords.len() - layout.data_codewords) / 2,
+        covers_function_patterns: false,
+    };
+    let mut damaged = HashSet::new();
+    for (i, role) in layout.roles.iter().enumerate() {
+        if !logo.clears(size, i % size, i / size) {
+            continue;
+        }
+        match role {
+            ModuleRole::Data | ModuleRole::Ecc | ModuleRole::Remainder => {
+                fit.cleared_modules += 1;
+                damaged.extend(layout.codeword_index[i]);
+            }
+            ModuleRole::Alignment => {}
+            _ => fit.covers_function_patterns = true,
+        }
+    }
+    fit.damaged_codewords = damaged.len();
+    fit
+}
+
+/// `render_svg_styled`, refusing a logo the code can't survive
+///
+/// Checks the logo with `check_logo`, then, with the `verify` feature,
+/// decodes the rendered SVG with `verify_svg`; a logo that also covers too
+/// much of what the scanner needs fails here instead of in the user's camera.
+///
+/// # Returns
+/// * `Ok(String)` - The SVG, known to decode to `qr.text` with `verify`
+/// * `Err(QrError::InvalidArgument)` - The logo hides more than the ECC level can restore
+/// * `Err(QrError::VerificationFailed)` - The rendered code doesn't decode back
+pub fn render_svg_with_logo(qr: &QrCode, options: &crate::StyledRenderOptions) -> Result<String, QrError> {
+    if let Some(logo) = &options.logo {
+        let fit = check_logo(qr, logo);
+        if fit.covers_function_patterns {
+            return Err(QrError::InvalidArgument(
+                "Logo covers finder, timing or format modules; make it smaller".into(),
+            ));
+        }
+        if !fit.fits() {
+            return Err(QrError::InvalidArgument(format!(
+                "Logo hides {} codewords; {:?} error correction restores at most {}. Make it smaller or raise the level",
+                fit.damaged_codewords, qr.ecl, fit.correctable_codewords
+            )));
+        }
+    }
+    let svg = crate::try_render_svg_styled(qr, options)?;
+    #[cfg(feature = "verify")]
+    {
+        let decoded = crate::verify_svg(&svg)?;
+        if decoded != qr.text {
+            return Err(QrError::VerificationFailed(format!("Decoded {:?} instead of the payload", decoded)));
+        }
+    }
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_qr, render_svg_styled, try_render_svg_styled, ErrorCorrectionLevel, StyledRenderOptions};
+
+    fn svg_logo(size: f64) -> Logo {
+        Logo {
+            image: LogoImage::Svg {
+                markup: r##"<circle cx="5" cy="5" r="5" fill="#E91E63"/>"##.to_string(),
+                width: 10.0,
+                height: 10.0,
+            },
+            size,
+            padding: 1,
+        }
+    }
+
+    #[test]
+    fn test_cleared_area_is_centered() {
+        assert_eq!(svg_logo(0.2).cleared_area(25), (9, 7)); // 5 modules + 1 padding each side
+        assert_eq!(svg_logo(0.22).cleared_area(25), (8, 9)); // 6 + 2, widened to odd
+        let (start, side) = svg_logo(0.2).cleared_area(25);
+        assert_eq!(start * 2 + side, 25);
+        let (start, side) = svg_logo(0.25).cleared_area(29);
+        assert_eq!(start * 2 + side, 29);
+        // Never wider than the symbol
+        let huge = Logo { padding: usize::MAX, ..svg_logo(0.4) };
+        assert_eq!(huge.cleared_area(21), (0, 21));
+    }
+
+    #[test]
+    fn test_base64() {
+        let encode = |data: &[u8]| {
+            let mut out = String::new();
+            push_base64(data, &mut out);
+            out
+        };
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(&[0xFF, 0xEF]), "/+8=");
+    }
+
+    #[test]
+    fn test_fit_depends_on_ecc_level() {
+        let text = "https://holi.tools/logo";
+        let logo = svg_logo(0.22);
+        let high = check_logo(&generate_qr(text, ErrorCorrectionLevel::High).unwrap(), &logo);
+        let low = check_logo(&generate_qr(text, ErrorCorrectionLevel::Low).unwrap(), &logo);
+        assert!(high.cleared_modules > 0 && high.damaged_codewords > 0);
+        assert!(high.fits(), "{:?}", high);
+        assert!(!low.fits(), "{:?}", low);
+
+        let qr = generate_qr(text, ErrorCorrectionLevel::High).unwrap();
+        assert!(check_logo(&qr, &Logo { padding: 100, ..svg_logo(0.1) }).covers_function_patterns);
+    }
+
+    #[test]
+    fn test_logo_clears_modules_and_draws_image() {
+        let qr = generate_qr("https://holi.tools/logo", ErrorCorrectionLevel::High).unwrap();
+        let plain = render_svg_styled(&qr, &StyledRenderOptions::default());
+        let options = StyledRenderOptions { logo: Some(svg_logo(0.22)), ..Default::default() };
+        let svg = render_svg_styled(&qr, &options);
+        assert!(svg.len() < plain.len() + 600, "modules under the logo are dropped");
+        assert!(svg.contains(r#"href="data:image/svg+xml;base64,"#) && !svg.contains("<circle"));
+        assert!(svg.ends_with(r#""/></svg>"#));
+
+        // Script, handlers and links in the logo stay inside the data URI
+        let hostile = Logo {
+            image: LogoImage::Svg {
+                markup: r#"<?xml version="1.0"?><svg onload="alert(1)"><script>alert(2)</script><a href="javascript:alert(3)"/></svg>"#.into(),
+                width: 10.0,
+                height: 10.0,
+            },
+            ..svg_logo(0.2)
+        };
+        let svg = render_svg_styled(&qr, &StyledRenderOptions { logo: Some(hostile), ..Default::default() });
+        assert!(!svg.contains("alert") && !svg.contains("<script") && !svg.contains("<?xml"));
+
+        let raster = Logo {
+            image: LogoImage::Raster { mime_type: "image/png".into(), data: vec![1, 2, 3] },
+            ..svg_logo(0.2)
+        };
+        let svg = render_svg_styled(&qr, &StyledRenderOptions { logo: Some(raster), ..Default::default() });
+        assert!(svg.contains(r#"href="data:image/png;base64,AQID"/>"#));
+
+        for bad in [Logo { size: 0.0, ..svg_logo(0.2) }, Logo { size: f64::NAN, ..svg_logo(0.2) }] {
+            let options = StyledRenderOptions { logo: Some(bad), ..Default::default() };
+            assert!(matches!(try_render_svg_styled(&qr, &options), Err(QrError::InvalidArgument(_))));
+            assert_eq!(render_svg_styled(&qr, &options), plain);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "verify")]
+mod verify_tests {
+    use super::*;
+    use crate::{generate_qr, ErrorCorrectionLevel, StyledRenderOptions};
+
+    #[test]
+    fn test_render_svg_with_logo() {
+        let text = "https://holi.tools/logo";
+        let logo = Logo {
+            image: LogoImage::Svg {
+                markup: r##"<rect width="10" height="10" rx="2" fill="#1565C0"/>"##.to_string(),
+                width: 10.0,
+                height: 10.0,
+            },
+            size: 0.2,
+            padding: 1,
+        };
+        let options = StyledRenderOptions { logo: Some(logo), ..Default::default() };
+
+        let high = generate_qr(text, ErrorCorrectionLevel::High).unwrap();
+        let svg = render_svg_with_logo(&high, &options).expect("High ECC survives the logo");
+        assert!(svg.contains("data:image/svg+xml;base64,"));
+
+        let low = generate_qr(text, ErrorCorrectionLevel::Low).unwrap();
+        assert!(matches!(render_svg_with_logo(&low, &options), Err(QrError::InvalidArgument(_))));
+    }
+}
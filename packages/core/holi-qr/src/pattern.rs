@@ -0,0 +1,257 @@
+//! Decorative background patterns drawn behind the QR modules
+//!
+//! Patterns are emitted as an SVG `<pattern>` filling the symbol area only;
+//! the quiet zone stays plain so scanners still find the code's edge. The
+//! pattern color is pulled toward the background until it keeps most of the
+//! foreground/background contrast, so light modules never read as dark.
+//! As with gradients, the pattern's id ends in a hash of what it draws, so
+//! codes inlined in one page don't share a tile.
+
+use crate::error::QrError;
+use std::fmt::Write;
+
+/// Share of the foreground/background contrast ratio the pattern must keep
+pub const PATTERN_CONTRAST_KEEP: f64 = 0.7;
+
+/// Id prefix of the `<pattern>` element in the SVG (`holi-bg-pattern-<suffix>`)
+pub const PATTERN_ID: &str = "holi-bg-pattern";
+
+/// Side of the noise tile, in modules
+const NOISE_TILE: usize = 8;
+
+/// Background pattern styles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PatternKind {
+    #[default]
+    DotsGrid,
+    DiagonalLines,
+    Noise,
+}
+
+impl PatternKind {
+    /// Parse from string (for WASM/JSON interop)
+    #[allow(clippy::should_implement_trait)] // Infallible: unknown names fall back to DotsGrid.
+    pub fn from_str(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "diagonal-lines" | "diagonallines" | "lines" => Self::DiagonalLines,
+            "noise" => Self::Noise,
+            _ => Self::DotsGrid,
+        }
+    }
+
+    /// Name as accepted by `from_str`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DotsGrid => "dots-grid",
+            Self::DiagonalLines => "diagonal-lines",
+            Self::Noise => "noise",
+        }
+    }
+}
+
+/// Decorative layer behind the modules
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundPattern {
+    pub kind: PatternKind,
+    /// Pattern color (`#RGB` or `#RRGGBB`); lightened toward the background
+    /// if it would cost too much contrast
+    pub color: String,
+    /// 0.0 (sparse, thin) to 1.0 (dense, bold)
+    pub density: f64,
+}
+
+impl Default for BackgroundPattern {
+    fn default() -> Self {
+        Self {
+            kind: PatternKind::DotsGrid,
+            color: "#E0E0E0".to_string(),
+            density: 0.5,
+        }
+    }
+}
+
+pub(crate) type Rgb = [f64; 3];
+
+pub(crate) fn parse_hex(color: &str) -> Option<Rgb> {
+    let hex = color.strip_prefix('#')?;
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok().map(f64::from);
+    match hex.len() {
+        3 => {
+            let c: Vec<_> = hex.chars().map(|c| channel(&c.to_string().repeat(2))).collect::<Option<_>>()?;
+            Some([c[0], c[1], c[2]])
+        }
+        6 if hex.is_ascii() => Some([channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?]),
+        _ => None,
+    }
+}
+
+pub(crate) fn to_hex(rgb: Rgb) -> String {
+    format!("#{:02X}{:02X}{:02X}", rgb[0].round() as u8, rgb[1].round() as u8, rgb[2].round() as u8)
+}
+
+/// WCAG relative luminance
+fn luminance(rgb: Rgb) -> f64 {
+    let linear = |c: f64| {
+        let c = c / 255.0;
+        if c <= 0.03928 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * linear(rgb[0]) + 0.7152 * linear(rgb[1]) + 0.0722 * linear(rgb[2])
+}
+
+/// WCAG contrast ratio, 1.0 to 21.0
+pub(crate) fn contrast(a: Rgb, b: Rgb) -> f64 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// `pattern` blended toward `bg` until it keeps `PATTERN_CONTRAST_KEEP` of
+/// the fg/bg contrast. `None` if a color isn't hex.
+///
+/// A transparent background is treated as white.
+pub fn constrain_pattern_color(pattern: &str, fg: &str, bg: &str) -> Option<String> {
+    let (pattern, fg) = (parse_hex(pattern)?, parse_hex(fg)?);
+    let bg = if bg == "transparent" { [255.0; 3] } else { parse_hex(bg)? };
+    let required = contrast(fg, bg) * PATTERN_CONTRAST_KEEP;
+
+    // bg itself (t = 1.0) always passes
+    (0..=10)
+        .map(|step| {
+            let t = step as f64 / 10.0;
+            [0, 1, 2].map(|i| pattern[i] + (bg[i] - pattern[i]) * t)
+        })
+        .find(|&c| contrast(fg, c) >= required)
+        .map(to_hex)
+}
+
+/// `<defs>` and the patterned `<rect>` over the symbol area, or `None` if the
+/// colors can't be checked
+pub(crate) fn pattern_svg(
+    pattern: &BackgroundPattern,
+    fg: &str,
+    bg: &str,
+    size: usize,
+    margin: usize,
+) -> Result<Option<String>, QrError> {
+    let Some(color) = constrain_pattern_color(&pattern.color, fg, bg) else { return Ok(None) };
+    let density = pattern.density.clamp(0.0, 1.0);
+    let mut tile = String::new();
+    let cell = match pattern.kind {
+        PatternKind::DotsGrid => {
+            let r = 0.08 + 0.22 * density;
+            write!(tile, r#"<circle cx="0.5" cy="0.5" r="{:.3}" fill="{}"/>"#, r, color)?;
+            1.0
+        }
+        PatternKind::DiagonalLines => {
+            let spacing = 2.0 - density;
+            let width = 0.1 + 0.2 * density;
+            write!(
+                tile,
+                r#"<path d="M0,{s} L{s},0 M-1,1 L1,-1 M{p},{q} L{q},{p}" stroke="{}" stroke-width="{:.3}"/>"#,
+                color,
+                width,
+                s = spacing,
+                p = spacing - 1.0,
+                q = spacing + 1.0,
+            )?;
+            spacing
+        }
+        PatternKind::Noise => {
+            // Fixed speckle layout so renders are reproducible
+            let specks = (NOISE_TILE * NOISE_TILE) as f64 * (0.25 + 1.75 * density);
+            let mut state: u32 = 0x9E37_79B9;
+            let mut next = || {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state % 1000) as f64 / 1000.0
+            };
+            write!(tile, r#"<path fill="{}" d=""#, color)?;
+            for _ in 0..specks as usize {
+                let (x, y) = (next() * NOISE_TILE as f64, next() * NOISE_TILE as f64);
+                write!(tile, "M{:.2},{:.2}h0.2v0.2h-0.2z", x, y)?;
+            }
+            tile.push_str(r#""/>"#);
+            NOISE_TILE as f64
+        }
+    };
+
+    // 64-bit FNV-1a of the tile and its size
+    let hash = tile
+        .bytes()
+        .chain(cell.to_bits().to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3));
+    Ok(Some(format!(
+        r#"<defs><pattern id="{id}-{hash:016x}" width="{cell}" height="{cell}" patternUnits="userSpaceOnUse">{tile}</pattern></defs><rect x="{m}" y="{m}" width="{size}" height="{size}" fill="url(#{id}-{hash:016x})"/>"#,
+        id = PATTERN_ID,
+        cell = cell,
+        tile = tile,
+        m = margin,
+        size = size,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("#fff"), Some([255.0; 3]));
+        assert_eq!(parse_hex("#102030"), Some([16.0, 32.0, 48.0]));
+        assert_eq!(parse_hex("red"), None);
+        assert_eq!(parse_hex("#12345"), None);
+    }
+
+    #[test]
+    fn test_pattern_color_keeps_contrast() {
+        // Light enough already: unchanged
+        assert_eq!(constrain_pattern_color("#EEEEEE", "#000000", "#FFFFFF").as_deref(), Some("#EEEEEE"));
+
+        // Too dark: pulled toward the background until it passes
+        let fixed = constrain_pattern_color("#333333", "#000000", "#FFFFFF").unwrap();
+        let c = parse_hex(&fixed).unwrap();
+        assert!(c[0] > 51.0);
+        assert!(contrast([0.0; 3], c) >= 21.0 * PATTERN_CONTRAST_KEEP);
+
+        // Against a transparent background the pattern is checked against white
+        assert!(constrain_pattern_color("#888888", "#1F3A5F", "transparent").is_some());
+        assert_eq!(constrain_pattern_color("navy", "#000000", "#FFFFFF"), None);
+    }
+
+    #[test]
+    fn test_pattern_svg_covers_symbol_only() {
+        let mut ids = Vec::new();
+        for kind in [PatternKind::DotsGrid, PatternKind::DiagonalLines, PatternKind::Noise] {
+            let pattern = BackgroundPattern { kind, ..Default::default() };
+            let svg = pattern_svg(&pattern, "#000000", "#FFFFFF", 21, 4).unwrap().unwrap();
+            assert!(svg.starts_with(r#"<defs><pattern id="holi-bg-pattern-"#));
+            assert!(svg.contains(r#"<rect x="4" y="4" width="21" height="21" fill="url(#holi-bg-pattern-"#));
+            assert_eq!(PatternKind::from_str(kind.as_str()), kind);
+            ids.push(svg[svg.find("id=").unwrap()..][..37].to_string());
+        }
+        // Each tile has an id of its own, and the same tile the same id
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+        let again = pattern_svg(&BackgroundPattern::default(), "#000000", "#FFFFFF", 33, 2).unwrap().unwrap();
+        assert!(again.contains(&ids[0]));
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn test_patterned_codes_scan() {
+        use crate::{generate_qr, render_svg_styled, verify_svg, ErrorCorrectionLevel, StyledRenderOptions};
+
+        let text = "https://holi.tools/p/pattern";
+        let qr = generate_qr(text, ErrorCorrectionLevel::Medium).unwrap();
+        for kind in [PatternKind::DotsGrid, PatternKind::DiagonalLines, PatternKind::Noise] {
+            // A dark pattern color at full density gets lightened, not drawn as-is
+            let options = StyledRenderOptions {
+                background_pattern: Some(BackgroundPattern { kind, color: "#404040".to_string(), density: 1.0 }),
+                ..Default::default()
+            };
+            let svg = render_svg_styled(&qr, &options);
+            assert!(svg.contains(PATTERN_ID));
+            assert_eq!(verify_svg(&svg).unwrap(), text, "{:?}", kind);
+        }
+    }
+}
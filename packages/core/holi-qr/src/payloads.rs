@@ -0,0 +1,500 @@
+//! Builders for structured payloads
+//!
+//! WiFi logins, map pins, text messages, emails, phone numbers and calendar
+//! events, escaped the way phone scanners parse them, so callers don't
+//! hand-roll URI or iCalendar escaping. `classify_payload` reads the WiFi,
+//! email and phone ones back.
+
+use crate::classify::is_email;
+use crate::error::QrError;
+use crate::qr::{generate_qr, ErrorCorrectionLevel, QrCode};
+use std::fmt::Write;
+
+/// Security of a WiFi network, the `T:` field of a `WIFI:` payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WifiAuth {
+    /// WPA/WPA2 personal
+    #[default]
+    Wpa,
+    /// WPA3 personal
+    Sae,
+    Wep,
+    /// Open network
+    NoPass,
+}
+
+impl WifiAuth {
+    pub const ALL: [WifiAuth; 4] = [Self::Wpa, Self::Sae, Self::Wep, Self::NoPass];
+
+    /// Value of the `T:` field
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wpa => "WPA",
+            Self::Sae => "SAE",
+            Self::Wep => "WEP",
+            Self::NoPass => "nopass",
+        }
+    }
+}
+
+/// Unknown security types are an error: guessing would make a code that
+/// can't join the network
+impl TryFrom<&str> for WifiAuth {
+    type Error = QrError;
+
+    fn try_from(s: &str) -> Result<Self, QrError> {
+        match s.to_ascii_lowercase().as_str() {
+            "wpa" | "wpa2" => Ok(Self::Wpa),
+            "sae" | "wpa3" => Ok(Self::Sae),
+            "wep" => Ok(Self::Wep),
+            "nopass" | "none" | "open" | "" => Ok(Self::NoPass),
+            _ => Err(QrError::InvalidArgument(format!("Unknown WiFi security: {:?}", s))),
+        }
+    }
+}
+
+/// A network to join by scanning
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WifiCredentials {
+    pub ssid: String,
+    /// Ignored for `WifiAuth::NoPass`
+    pub password: String,
+    pub auth: WifiAuth,
+    /// The network doesn't broadcast its SSID
+    pub hidden: bool,
+}
+
+/// Backslash before the characters that delimit `WIFI:` fields
+fn push_escaped(out: &mut String, value: &str) {
+    for c in value.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+impl WifiCredentials {
+    /// `WIFI:T:<auth>;S:<ssid>;P:<password>;H:true;;`
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The payload
+    /// * `Err(QrError::InvalidArgument)` - Empty SSID, a secured network
+    ///   without a password, or a WPA passphrase that isn't 8 to 63
+    ///   characters (or a 64-digit hex key)
+    pub fn to_payload(&self) -> Result<String, QrError> {
+        if self.ssid.is_empty() {
+            return Err(QrError::InvalidArgument("WiFi SSID cannot be empty".into()));
+        }
+        let secured = self.auth != WifiAuth::NoPass;
+        if secured && self.password.is_empty() {
+            return Err(QrError::InvalidArgument(format!("{} network needs a password", self.auth.as_str())));
+        }
+        let length = self.password.chars().count();
+        let hex_key = length == 64 && self.password.chars().all(|c| c.is_ascii_hexdigit());
+        if self.auth == WifiAuth::Wpa && !(8..=63).contains(&length) && !hex_key {
+            return Err(QrError::InvalidArgument(format!(
+                "WPA passphrase must be 8 to 63 characters (got {})",
+                length
+            )));
+        }
+
+        let mut payload = String::with_capacity(24 + 2 * (self.ssid.len() + self.password.len()));
+        payload.push_str("WIFI:T:");
+        payload.push_str(self.auth.as_str());
+        payload.push_str(";S:");
+        push_escaped(&mut payload, &self.ssid);
+        if secured {
+            payload.push_str(";P:");
+            push_escaped(&mut payload, &self.password);
+        }
+        if self.hidden {
+            payload.push_str(";H:true");
+        }
+        payload.push_str(";;");
+        Ok(payload)
+    }
+}
+
+/// QR code that joins `credentials`' network when scanned
+pub fn generate_wifi_qr(credentials: &WifiCredentials, ecl: ErrorCorrectionLevel) -> Result<QrCode, QrError> {
+    generate_qr(&credentials.to_payload()?, ecl)
+}
+
+/// `value` with everything but RFC 3986 unreserved characters and `keep`
+/// percent-encoded
+fn push_percent_encoded(out: &mut String, value: &str, keep: &str) {
+    for c in value.chars() {
+        if c.is_ascii_alphanumeric() || "-._~".contains(c) || keep.contains(c) {
+            out.push(c);
+        } else {
+            let mut bytes = [0u8; 4];
+            for b in c.encode_utf8(&mut bytes).bytes() {
+                let _ = write!(out, "%{:02X}", b);
+            }
+        }
+    }
+}
+
+/// Append `?name=value` or `&name=value` for each non-empty value
+fn push_query(out: &mut String, params: &[(&str, &str)]) {
+    for (name, value) in params.iter().filter(|(_, v)| !v.is_empty()) {
+        out.push(if out.contains('?') { '&' } else { '?' });
+        out.push_str(name);
+        out.push('=');
+        push_percent_encoded(out, value, "");
+    }
+}
+
+/// `number` as a `tel:`/`sms:` path: a leading `+`, digits and the
+/// visual separators `-.()`; spaces are dropped
+fn phone_number(number: &str) -> Result<String, QrError> {
+    let number: String = number.chars().filter(|c| !c.is_whitespace()).collect();
+    let valid = number.chars().enumerate().all(|(i, c)| c.is_ascii_digit() || "-.()".contains(c) || (c == '+' && i == 0));
+    if !valid || !number.chars().any(|c| c.is_ascii_digit()) {
+        return Err(QrError::InvalidArgument(format!("Not a phone number: {:?}", number)));
+    }
+    Ok(number)
+}
+
+/// A map location, `geo:` URI (RFC 5870)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GeoPoint {
+    /// Degrees, -90 to 90
+    pub latitude: f64,
+    /// Degrees, -180 to 180
+    pub longitude: f64,
+    /// Meters
+    pub altitude: Option<f64>,
+    /// Name shown on the pin
+    pub label: Option<String>,
+}
+
+impl GeoPoint {
+    /// `geo:<lat>,<lon>[,<alt>][?q=<lat>,<lon>(<label>)]`
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The payload
+    /// * `Err(QrError::InvalidArgument)` - A coordinate out of range or not finite
+    pub fn to_payload(&self) -> Result<String, QrError> {
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(QrError::InvalidArgument(format!(
+                "Coordinates {}, {} are out of range",
+                self.latitude, self.longitude
+            )));
+        }
+        let mut payload = format!("geo:{},{}", self.latitude, self.longitude);
+        if let Some(altitude) = self.altitude {
+            if !altitude.is_finite() {
+                return Err(QrError::InvalidArgument(format!("Altitude must be finite (got {})", altitude)));
+            }
+            write!(payload, ",{}", altitude)?;
+        }
+        if let Some(label) = self.label.as_deref().filter(|l| !l.is_empty()) {
+            write!(payload, "?q={},{}", self.latitude, self.longitude)?;
+            payload.push_str("%28");
+            push_percent_encoded(&mut payload, label, "");
+            payload.push_str("%29");
+        }
+        Ok(payload)
+    }
+}
+
+/// A text message to send, `sms:` URI (RFC 5724)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SmsMessage {
+    pub number: String,
+    /// Prefilled text, may be empty
+    pub body: String,
+}
+
+impl SmsMessage {
+    /// `sms:<number>[?body=<text>]`
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The payload
+    /// * `Err(QrError::InvalidArgument)` - Not a phone number
+    pub fn to_payload(&self) -> Result<String, QrError> {
+        let mut payload = format!("sms:{}", phone_number(&self.number)?);
+        push_query(&mut payload, &[("body", &self.body)]);
+        Ok(payload)
+    }
+}
+
+/// An email to write, `mailto:` URI (RFC 6068)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MailTo {
+    /// At least one address
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub subject: String,
+    pub body: String,
+}
+
+impl MailTo {
+    /// `mailto:<to>,<to>[?cc=...&subject=...&body=...]`
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The payload
+    /// * `Err(QrError::InvalidArgument)` - No recipient, or an address that
+    ///   isn't one
+    pub fn to_payload(&self) -> Result<String, QrError> {
+        if self.to.is_empty() {
+            return Err(QrError::InvalidArgument("Email needs a recipient".into()));
+        }
+        if let Some(bad) = self.to.iter().chain(&self.cc).find(|a| !is_email(a)) {
+            return Err(QrError::InvalidArgument(format!("Not an email address: {:?}", bad)));
+        }
+        let mut payload = String::from("mailto:");
+        for (i, address) in self.to.iter().enumerate() {
+            if i > 0 {
+                payload.push(',');
+            }
+            push_percent_encoded(&mut payload, address, "@!$'*+=");
+        }
+        // Line breaks are CRLF in mail bodies
+        let body = self.body.replace("\r\n", "\n").replace('\n', "\r\n");
+        push_query(&mut payload, &[("cc", &self.cc.join(",")), ("subject", &self.subject), ("body", &body)]);
+        Ok(payload)
+    }
+}
+
+/// A number to call, `tel:` URI (RFC 3966)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Tel {
+    /// International numbers start with `+`
+    pub number: String,
+}
+
+impl Tel {
+    /// `tel:<number>`, spaces removed
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The payload
+    /// * `Err(QrError::InvalidArgument)` - Not a phone number
+    pub fn to_payload(&self) -> Result<String, QrError> {
+        Ok(format!("tel:{}", phone_number(&self.number)?))
+    }
+}
+
+/// Earliest and latest times an iCalendar date can hold (years 0000-9999)
+const ICAL_RANGE: std::ops::RangeInclusive<i64> = -62_167_219_200..=253_402_300_799;
+
+/// A calendar event, iCalendar `VEVENT` (RFC 5545)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct VEvent {
+    pub summary: String,
+    /// Seconds since the Unix epoch, UTC
+    pub start_unix: i64,
+    /// Exclusive; for all-day events, the day after the last one
+    pub end_unix: Option<i64>,
+    /// Dates only; the times of `start_unix`/`end_unix` are dropped
+    pub all_day: bool,
+    pub location: String,
+    pub description: String,
+}
+
+/// `YYYYMMDD`, plus `THHMMSSZ` unless `date_only`
+fn push_ical_time(out: &mut String, unix: i64, date_only: bool) -> Result<(), QrError> {
+    let (days, secs) = (unix.div_euclid(86_400), unix.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01 (H. Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    write!(out, "{:04}{:02}{:02}", year, month, day)?;
+    if !date_only {
+        write!(out, "T{:02}{:02}{:02}Z", secs / 3_600, secs / 60 % 60, secs % 60)?;
+    }
+    Ok(())
+}
+
+/// One content line, text escaped and folded at 75 octets
+fn push_ical_line(out: &mut String, name: &str, value: &str) {
+    let mut line = String::with_capacity(name.len() + 1 + value.len());
+    line.push_str(name);
+    line.push(':');
+    for c in value.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                line.push('\\');
+                line.push(c);
+            }
+            '\n' => line.push_str("\\n"),
+            '\r' => {}
+            c => line.push(c),
+        }
+    }
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+impl VEvent {
+    /// `BEGIN:VEVENT ... END:VEVENT`, lines joined with CRLF
+    ///
+    /// # Returns
+    /// * `Ok(String)` - The payload
+    /// * `Err(QrError::InvalidArgument)` - No summary, the end before the
+    ///   start, or a time outside years 0000-9999
+    pub fn to_payload(&self) -> Result<String, QrError> {
+        if self.summary.trim().is_empty() {
+            return Err(QrError::InvalidArgument("Event needs a summary".into()));
+        }
+        for time in std::iter::once(self.start_unix).chain(self.end_unix) {
+            if !ICAL_RANGE.contains(&time) {
+                return Err(QrError::InvalidArgument(format!("Event time {} is out of range", time)));
+            }
+        }
+        if self.end_unix.is_some_and(|end| end < self.start_unix) {
+            return Err(QrError::InvalidArgument("Event ends before it starts".into()));
+        }
+
+        let mut payload = String::from("BEGIN:VEVENT\r\n");
+        push_ical_line(&mut payload, "SUMMARY", &self.summary);
+        let times = [("DTSTART", Some(self.start_unix)), ("DTEND", self.end_unix)];
+        for (name, time) in times.into_iter().filter_map(|(n, t)| Some((n, t?))) {
+            payload.push_str(name);
+            payload.push_str(if self.all_day { ";VALUE=DATE:" } else { ":" });
+            push_ical_time(&mut payload, time, self.all_day)?;
+            payload.push_str("\r\n");
+        }
+        if !self.location.is_empty() {
+            push_ical_line(&mut payload, "LOCATION", &self.location);
+        }
+        if !self.description.is_empty() {
+            push_ical_line(&mut payload, "DESCRIPTION", &self.description);
+        }
+        payload.push_str("END:VEVENT");
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{classify_payload, PayloadKind, PayloadRisk};
+
+    fn wifi(ssid: &str, password: &str, auth: WifiAuth) -> WifiCredentials {
+        WifiCredentials { ssid: ssid.to_string(), password: password.to_string(), auth, hidden: false }
+    }
+
+    #[test]
+    fn test_wifi_payload_escaping() {
+        let home = wifi("Casa;Pérez", r#"p\a:s,s"word"#, WifiAuth::Wpa);
+        assert_eq!(home.to_payload().unwrap(), r#"WIFI:T:WPA;S:Casa\;Pérez;P:p\\a\:s\,s\"word;;"#);
+
+        let hidden = WifiCredentials { hidden: true, ..wifi("lab", "ignored", WifiAuth::NoPass) };
+        assert_eq!(hidden.to_payload().unwrap(), "WIFI:T:nopass;S:lab;H:true;;");
+
+        // Reads back through the classifier unchanged
+        for (credentials, security) in [(home, "WPA"), (hidden, "nopass")] {
+            let classification = classify_payload(&credentials.to_payload().unwrap());
+            assert_eq!(
+                classification.kind,
+                PayloadKind::Wifi { ssid: credentials.ssid.clone(), security: security.into(), hidden: credentials.hidden }
+            );
+            assert_eq!(classification.risks.contains(&PayloadRisk::OpenWifi), credentials.auth == WifiAuth::NoPass);
+        }
+    }
+
+    #[test]
+    fn test_wifi_checks() {
+        assert!(wifi("", "password", WifiAuth::Wpa).to_payload().is_err());
+        assert!(wifi("net", "", WifiAuth::Wep).to_payload().is_err());
+        assert!(wifi("net", "short", WifiAuth::Wpa).to_payload().is_err());
+        assert!(wifi("net", "short", WifiAuth::Sae).to_payload().is_ok());
+        assert!(wifi("net", &"ab".repeat(32), WifiAuth::Wpa).to_payload().is_ok());
+        assert!(wifi("net", &"x".repeat(64), WifiAuth::Wpa).to_payload().is_err());
+
+        assert_eq!(WifiAuth::try_from("WPA2").unwrap(), WifiAuth::Wpa);
+        for auth in WifiAuth::ALL {
+            assert_eq!(WifiAuth::try_from(auth.as_str()).unwrap(), auth);
+        }
+        assert!(WifiAuth::try_from("wpa-enterprise").is_err());
+
+        let qr = generate_wifi_qr(&wifi("net", "password", WifiAuth::Wpa), ErrorCorrectionLevel::Medium).unwrap();
+        assert!(qr.size() >= 21);
+    }
+
+    #[test]
+    fn test_uri_payloads() {
+        let pin = GeoPoint { latitude: 40.4168, longitude: -3.7038, altitude: None, label: Some("Sol & Co".into()) };
+        assert_eq!(pin.to_payload().unwrap(), "geo:40.4168,-3.7038?q=40.4168,-3.7038%28Sol%20%26%20Co%29");
+        assert_eq!(GeoPoint { altitude: Some(650.0), label: None, ..pin.clone() }.to_payload().unwrap(), "geo:40.4168,-3.7038,650");
+        assert!(GeoPoint { latitude: 91.0, ..pin.clone() }.to_payload().is_err());
+        assert!(GeoPoint { longitude: f64::NAN, ..pin.clone() }.to_payload().is_err());
+        assert!(GeoPoint { altitude: Some(f64::INFINITY), ..pin }.to_payload().is_err());
+
+        let sms = SmsMessage { number: "+34 600 00 00 00".into(), body: "¿Vienes? 5€".into() };
+        assert_eq!(sms.to_payload().unwrap(), "sms:+34600000000?body=%C2%BFVienes%3F%205%E2%82%AC");
+        assert!(SmsMessage { number: "call me".into(), body: String::new() }.to_payload().is_err());
+        assert!(Tel { number: "+".into() }.to_payload().is_err());
+        assert!(Tel { number: "600+1".into() }.to_payload().is_err());
+
+        let mail = MailTo {
+            to: vec!["ada@example.com".into(), "bob+qr@example.com".into()],
+            cc: vec!["eve@example.com".into()],
+            subject: "Hi & bye".into(),
+            body: "one\ntwo".into(),
+        };
+        assert_eq!(
+            mail.to_payload().unwrap(),
+            "mailto:ada@example.com,bob+qr@example.com?cc=eve%40example.com&subject=Hi%20%26%20bye&body=one%0D%0Atwo"
+        );
+        assert!(MailTo { to: vec![], ..mail.clone() }.to_payload().is_err());
+        assert!(MailTo { cc: vec!["not-an-address".into()], ..mail.clone() }.to_payload().is_err());
+
+        // Email and phone read back through the classifier
+        let tel = Tel { number: "+34 600 000 000".into() };
+        assert_eq!(classify_payload(&tel.to_payload().unwrap()).kind, PayloadKind::Phone { number: "+34600000000".into() });
+        let single = MailTo { to: vec!["ada@example.com".into()], ..Default::default() };
+        assert_eq!(single.to_payload().unwrap(), "mailto:ada@example.com");
+        assert_eq!(
+            classify_payload(&mail.to_payload().unwrap()).kind,
+            PayloadKind::Email { address: "ada@example.com,bob+qr@example.com".into() }
+        );
+    }
+
+    #[test]
+    fn test_vevent_payload() {
+        let event = VEvent {
+            summary: "Launch; v2, finally".into(),
+            start_unix: 1_709_210_096, // 2024-02-29 12:34:56 UTC
+            end_unix: Some(1_709_213_696),
+            all_day: false,
+            location: String::new(),
+            description: "Line one\nLine two".into(),
+        };
+        assert_eq!(
+            event.to_payload().unwrap(),
+            "BEGIN:VEVENT\r\nSUMMARY:Launch\\; v2\\, finally\r\nDTSTART:20240229T123456Z\r\nDTEND:20240229T133456Z\r\n\
+             DESCRIPTION:Line one\\nLine two\r\nEND:VEVENT"
+        );
+
+        let day = VEvent { all_day: true, start_unix: -86_400, end_unix: None, ..event.clone() };
+        assert!(day.to_payload().unwrap().contains("\r\nDTSTART;VALUE=DATE:19691231\r\n"));
+
+        // Long lines fold at 75 octets without splitting characters
+        let long = VEvent { location: "é".repeat(100), ..event.clone() };
+        let payload = long.to_payload().unwrap();
+        assert!(payload.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(payload.replace("\r\n ", "").matches('é').count(), 100);
+
+        assert!(VEvent { summary: " ".into(), ..event.clone() }.to_payload().is_err());
+        assert!(VEvent { end_unix: Some(0), ..event.clone() }.to_payload().is_err());
+        assert!(VEvent { start_unix: i64::MAX, end_unix: None, ..event }.to_payload().is_err());
+    }
+}
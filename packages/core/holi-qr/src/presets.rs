@@ -0,0 +1,137 @@
+//! Named style presets for one-click QR styling
+//!
+//! Each preset pairs shapes and colors with the error correction level they
+//! need to stay scannable; ornate shapes eat into the error budget, so they
+//! ask for more redundancy than the plain style.
+
+use crate::error::QrError;
+use crate::qr::{generate_qr, ErrorCorrectionLevel};
+use crate::render::{render_svg_styled, StyledRenderOptions};
+use crate::shapes::{BodyShape, EyeBallShape, EyeFrameShape};
+
+/// A named, fully specified QR style
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StylePreset {
+    /// Stable identifier (used by the UI and WASM bindings)
+    pub name: &'static str,
+    /// Short English description for tooltips
+    pub description: &'static str,
+    /// Error correction level the preset is verified with
+    pub ecl: ErrorCorrectionLevel,
+    pub fg_color: &'static str,
+    pub bg_color: &'static str,
+    pub body_shape: BodyShape,
+    pub eye_frame_shape: EyeFrameShape,
+    pub eye_ball_shape: EyeBallShape,
+}
+
+impl StylePreset {
+    /// Render options for this preset, with the default margin
+    pub fn options(&self) -> StyledRenderOptions {
+        StyledRenderOptions {
+            fg_color: self.fg_color.to_string(),
+            bg_color: self.bg_color.to_string(),
+            body_shape: self.body_shape,
+            eye_frame_shape: self.eye_frame_shape,
+            eye_ball_shape: self.eye_ball_shape,
+            ..Default::default()
+        }
+    }
+}
+
+/// All presets, in display order
+pub const PRESETS: &[StylePreset] = &[
+    StylePreset {
+        name: "classic",
+        description: "Black squares on white; scans everywhere",
+        ecl: ErrorCorrectionLevel::Medium,
+        fg_color: "#000000",
+        bg_color: "#FFFFFF",
+        body_shape: BodyShape::Square,
+        eye_frame_shape: EyeFrameShape::Square,
+        eye_ball_shape: EyeBallShape::Square,
+    },
+    StylePreset {
+        name: "neon",
+        description: "Electric violet on neon yellow with rounded modules",
+        ecl: ErrorCorrectionLevel::Quartile,
+        fg_color: "#5B00D6",
+        bg_color: "#E6FF3F",
+        body_shape: BodyShape::Rounded,
+        eye_frame_shape: EyeFrameShape::Rounded,
+        eye_ball_shape: EyeBallShape::Circle,
+    },
+    StylePreset {
+        name: "pastel-dots",
+        description: "Soft purple dots on blush pink",
+        ecl: ErrorCorrectionLevel::Quartile,
+        fg_color: "#5E4E91",
+        bg_color: "#FDF1F5",
+        body_shape: BodyShape::Dots,
+        eye_frame_shape: EyeFrameShape::Circle,
+        eye_ball_shape: EyeBallShape::Circle,
+    },
+    StylePreset {
+        name: "corporate",
+        description: "Navy with classic rounded corners",
+        ecl: ErrorCorrectionLevel::Medium,
+        fg_color: "#1F3A5F",
+        bg_color: "#FFFFFF",
+        body_shape: BodyShape::ClassyRounded,
+        eye_frame_shape: EyeFrameShape::Rounded,
+        eye_ball_shape: EyeBallShape::Rounded,
+    },
+    StylePreset {
+        name: "paper",
+        description: "Charcoal on warm paper with cushioned eyes",
+        ecl: ErrorCorrectionLevel::Quartile,
+        fg_color: "#2D2A26",
+        bg_color: "#F5F0E6",
+        body_shape: BodyShape::MiniSquare,
+        eye_frame_shape: EyeFrameShape::HeavyRounded,
+        eye_ball_shape: EyeBallShape::Cushion,
+    },
+];
+
+/// Look up a preset by name (case-insensitive)
+pub fn preset(name: &str) -> Option<&'static StylePreset> {
+    PRESETS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// Generate and render `text` with the named preset
+///
+/// # Returns
+/// * `Ok(String)` - The styled SVG
+/// * `Err(QrError)` - Unknown preset name, or the text can't be encoded
+pub fn render_with_preset(text: &str, name: &str) -> Result<String, QrError> {
+    let preset = preset(name).ok_or_else(|| QrError::InvalidArgument(format!("Unknown preset: {}", name)))?;
+    let qr = generate_qr(text, preset.ecl)?;
+    Ok(render_svg_styled(&qr, &preset.options()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_lookup() {
+        assert_eq!(preset("Neon").unwrap().name, "neon");
+        assert!(preset("missing").is_none());
+        assert!(matches!(render_with_preset("hi", "missing"), Err(QrError::InvalidArgument(_))));
+
+        let mut names: Vec<_> = PRESETS.iter().map(|p| p.name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), PRESETS.len());
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn test_presets_scan() {
+        let text = "https://holi.tools/p/presets";
+        for p in PRESETS {
+            let svg = render_with_preset(text, p.name).unwrap();
+            assert_eq!(crate::verify_svg(&svg).unwrap(), text, "preset {}", p.name);
+        }
+    }
+}
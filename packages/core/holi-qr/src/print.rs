@@ -0,0 +1,115 @@
+//! Physical print sizing
+//!
+//! Codes printed too small for the distance they are scanned from are the
+//! most common cause of "it doesn't scan". This turns a scan distance into a
+//! minimum printed size and the raster resolution needed to hit it.
+
+use crate::error::QrError;
+use crate::qr::QrCode;
+
+/// Scan distance divided by this gives the minimum symbol width (the
+/// common 10:1 rule for phone cameras).
+pub const SCAN_DISTANCE_RATIO: f64 = 10.0;
+/// Smallest module most printers and cameras handle reliably, in mm.
+pub const MIN_MODULE_SIZE_MM: f64 = 0.4;
+/// Quiet zone required by the spec, in modules per side.
+pub const QUIET_ZONE_MODULES: usize = 4;
+/// Resolution assumed for raster export.
+pub const DEFAULT_PRINT_DPI: u32 = 300;
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// Minimum physical size and raster resolution for a code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrintRequirements {
+    /// Modules per side, including the quiet zone
+    pub modules: usize,
+    /// Minimum size of one module, in mm
+    pub module_size_mm: f64,
+    /// Minimum printed width/height including the quiet zone, in mm
+    pub min_size_mm: f64,
+    /// Resolution the pixel sizes below are computed for
+    pub dpi: u32,
+    /// Whole pixels per module at `dpi` (never below 1)
+    pub pixels_per_module: u32,
+    /// Raster width/height including the quiet zone, in pixels
+    pub pixel_size: u32,
+}
+
+/// Minimum print size for `qr` scanned from `scan_distance_m` meters, at
+/// `DEFAULT_PRINT_DPI`.
+pub fn print_requirements(qr: &QrCode, scan_distance_m: f64) -> Result<PrintRequirements, QrError> {
+    print_requirements_at_dpi(qr, scan_distance_m, DEFAULT_PRINT_DPI)
+}
+
+/// Same as `print_requirements` for a specific printer resolution.
+pub fn print_requirements_at_dpi(
+    qr: &QrCode,
+    scan_distance_m: f64,
+    dpi: u32,
+) -> Result<PrintRequirements, QrError> {
+    if !scan_distance_m.is_finite() || scan_distance_m <= 0.0 {
+        return Err(QrError::InvalidArgument(format!(
+            "Scan distance must be positive (got {})",
+            scan_distance_m
+        )));
+    }
+    if dpi == 0 {
+        return Err(QrError::InvalidArgument("DPI must be positive".to_string()));
+    }
+
+    let symbol_modules = qr.size();
+    let modules = symbol_modules + 2 * QUIET_ZONE_MODULES;
+
+    let symbol_mm = scan_distance_m * 1000.0 / SCAN_DISTANCE_RATIO;
+    let module_size_mm = (symbol_mm / symbol_modules as f64).max(MIN_MODULE_SIZE_MM);
+
+    // Round up to whole pixels so modules stay crisp and never shrink below the minimum.
+    let pixels_per_module = ((module_size_mm / MM_PER_INCH * dpi as f64).ceil() as u32).max(1);
+    let pixel_size = pixels_per_module * modules as u32;
+
+    Ok(PrintRequirements {
+        modules,
+        module_size_mm,
+        min_size_mm: module_size_mm * modules as f64,
+        dpi,
+        pixels_per_module,
+        pixel_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qr::{generate_qr, ErrorCorrectionLevel};
+
+    #[test]
+    fn test_size_scales_with_distance() {
+        let qr = generate_qr("https://holi.tools", ErrorCorrectionLevel::Medium).unwrap();
+        let near = print_requirements(&qr, 0.3).unwrap();
+        let far = print_requirements(&qr, 3.0).unwrap();
+
+        assert_eq!(near.modules, qr.size() + 8);
+        assert!(far.min_size_mm > near.min_size_mm * 9.0);
+        assert!(far.pixel_size > near.pixel_size);
+        // 3 m -> 300 mm symbol, plus quiet zone
+        assert!((far.module_size_mm * qr.size() as f64 - 300.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_minimum_module_size() {
+        let qr = generate_qr("hi", ErrorCorrectionLevel::Low).unwrap();
+        let req = print_requirements(&qr, 0.01).unwrap();
+        assert_eq!(req.module_size_mm, MIN_MODULE_SIZE_MM);
+        assert!(req.pixels_per_module as f64 / req.dpi as f64 * MM_PER_INCH >= MIN_MODULE_SIZE_MM);
+        assert_eq!(req.pixel_size, req.pixels_per_module * req.modules as u32);
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        let qr = generate_qr("hi", ErrorCorrectionLevel::Low).unwrap();
+        assert!(print_requirements(&qr, 0.0).is_err());
+        assert!(print_requirements(&qr, f64::NAN).is_err());
+        assert!(print_requirements_at_dpi(&qr, 1.0, 0).is_err());
+    }
+}
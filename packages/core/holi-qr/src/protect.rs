@@ -0,0 +1,106 @@
+//! Keeping function patterns readable under decorative body shapes
+//!
+//! Timing, format, version and alignment modules are drawn with the body
+//! shape like data modules, but a decoder can't error-correct them the same
+//! way: a timing row of thin crosses stops looking like alternating modules
+//! at small raster sizes. Each rule below names the roles it protects and the
+//! minimum `BodyShape::coverage` they need; modules under a shape that falls
+//! short are drawn with a fuller shape instead.
+
+use crate::layout::ModuleRole;
+use crate::qr::QrCode;
+use crate::shapes::BodyShape;
+
+/// Minimum coverage for a set of module roles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProtectionRule {
+    pub roles: &'static [ModuleRole],
+    pub min_coverage: f64,
+}
+
+/// Rules applied by `render_svg_styled` when `protect_function_patterns` is set
+pub const PROTECTION_RULES: &[ProtectionRule] = &[
+    // Alternation must stay unambiguous: the decoder counts modules on it
+    ProtectionRule { roles: &[ModuleRole::Timing], min_coverage: 0.7 },
+    ProtectionRule {
+        roles: &[ModuleRole::Format, ModuleRole::Version, ModuleRole::DarkModule],
+        min_coverage: 0.6,
+    },
+    ProtectionRule { roles: &[ModuleRole::Alignment], min_coverage: 0.6 },
+];
+
+/// Modules of one role drawn with a different shape than requested
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeSubstitution {
+    pub role: ModuleRole,
+    pub requested: BodyShape,
+    pub used: BodyShape,
+    /// Dark modules affected
+    pub modules: usize,
+}
+
+/// Closest full-coverage shape: rounded for round shapes, square otherwise
+fn safer_shape(shape: BodyShape) -> BodyShape {
+    match shape {
+        BodyShape::Dots | BodyShape::TinyDots | BodyShape::Blob | BodyShape::Leaf
+        | BodyShape::Clover | BodyShape::Heart => BodyShape::Rounded,
+        _ => BodyShape::Square,
+    }
+}
+
+/// Shape to draw a module of `role` with, when `shape` was requested
+pub(crate) fn protected_shape(role: ModuleRole, shape: BodyShape) -> BodyShape {
+    let weak = PROTECTION_RULES
+        .iter()
+        .any(|rule| rule.roles.contains(&role) && shape.coverage() < rule.min_coverage);
+    if weak { safer_shape(shape) } else { shape }
+}
+
+/// Substitutions `render_svg_styled` makes for `shape` on `qr`, per role
+///
+/// Empty when the shape is full enough everywhere.
+pub fn shape_substitutions(qr: &QrCode, shape: BodyShape) -> Vec<ShapeSubstitution> {
+    let mut substitutions: Vec<ShapeSubstitution> = Vec::new();
+    for (_, _, dark, role) in qr.iter_modules() {
+        let used = protected_shape(role, shape);
+        if !dark || used == shape {
+            continue;
+        }
+        match substitutions.iter_mut().find(|s| s.role == role) {
+            Some(s) => s.modules += 1,
+            None => substitutions.push(ShapeSubstitution { role, requested: shape, used, modules: 1 }),
+        }
+    }
+    substitutions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_qr, ErrorCorrectionLevel};
+
+    #[test]
+    fn test_substitutions() {
+        let qr = generate_qr("https://holi.tools/protect", ErrorCorrectionLevel::Medium).unwrap();
+        assert!(shape_substitutions(&qr, BodyShape::Square).is_empty());
+        assert!(shape_substitutions(&qr, BodyShape::Octagon).is_empty());
+
+        // Crosses are too thin for timing only
+        let cross = shape_substitutions(&qr, BodyShape::Cross);
+        assert_eq!(cross.len(), 1);
+        assert_eq!((cross[0].role, cross[0].used), (ModuleRole::Timing, BodyShape::Square));
+        // Two timing lines of size - 16 modules, each starting and ending dark
+        let dark_timing = qr.size() - 15;
+        assert_eq!(cross[0].modules, dark_timing);
+
+        // Hash is thinner still: format and alignment too
+        let roles: Vec<_> = shape_substitutions(&qr, BodyShape::Hash).iter().map(|s| s.role).collect();
+        for role in [ModuleRole::Timing, ModuleRole::Format, ModuleRole::DarkModule, ModuleRole::Alignment] {
+            assert!(roles.contains(&role), "{:?}", role);
+        }
+        assert!(!roles.contains(&ModuleRole::Data));
+
+        assert_eq!(protected_shape(ModuleRole::Timing, BodyShape::TinyDots), BodyShape::Rounded);
+        assert_eq!(protected_shape(ModuleRole::Data, BodyShape::TinyDots), BodyShape::TinyDots);
+    }
+}
@@ -1,8 +1,10 @@
 //! QR code generation
 
 use crate::error::QrError;
+use crate::matrix::ModuleMatrix;
+use crate::segments::Segment;
 use fast_qr::qr::QRBuilder;
-use fast_qr::ECL;
+use fast_qr::{Mode, ECL};
 
 /// Error correction level for QR codes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -28,11 +30,115 @@ impl From<ErrorCorrectionLevel> for ECL {
     }
 }
 
+/// Data codewords per version (1-40) for L, M, Q, H (ISO/IEC 18004 table 7)
+const DATA_CODEWORDS: [[u16; 40]; 4] = [
+    [
+        19, 34, 55, 80, 108, 136, 156, 194, 232, 274, 324, 370, 428, 461, 523, 589, 647, 721, 795,
+        861, 932, 1006, 1094, 1174, 1276, 1370, 1468, 1531, 1631, 1735, 1843, 1955, 2071, 2191,
+        2306, 2434, 2566, 2702, 2812, 2956,
+    ],
+    [
+        16, 28, 44, 64, 86, 108, 124, 154, 182, 216, 254, 290, 334, 365, 415, 453, 507, 563, 627,
+        669, 714, 782, 860, 914, 1000, 1062, 1128, 1193, 1267, 1373, 1455, 1541, 1631, 1725, 1812,
+        1914, 1992, 2102, 2216, 2334,
+    ],
+    [
+        13, 22, 34, 48, 62, 76, 88, 110, 132, 154, 180, 206, 244, 261, 295, 325, 367, 397, 445,
+        485, 512, 568, 614, 664, 718, 754, 808, 871, 911, 985, 1033, 1115, 1171, 1231, 1286, 1354,
+        1426, 1502, 1582, 1666,
+    ],
+    [
+        9, 16, 26, 36, 46, 60, 66, 86, 100, 122, 140, 158, 180, 197, 223, 253, 283, 313, 341, 385,
+        406, 442, 464, 514, 538, 596, 628, 661, 701, 745, 793, 845, 901, 961, 986, 1054, 1096,
+        1142, 1222, 1276,
+    ],
+];
+
+
+/// Data codewords of a `version` (1-40) symbol at `ecl`; 0 for other versions
+pub(crate) fn data_codewords(version: usize, ecl: ErrorCorrectionLevel) -> usize {
+    let ecl_index = match ecl {
+        ErrorCorrectionLevel::Low => 0,
+        ErrorCorrectionLevel::Medium => 1,
+        ErrorCorrectionLevel::Quartile => 2,
+        ErrorCorrectionLevel::High => 3,
+    };
+    version
+        .checked_sub(1)
+        .and_then(|i| DATA_CODEWORDS[ecl_index].get(i))
+        .map_or(0, |&n| n as usize)
+}
+
+/// The alphanumeric mode character set, in code value order
+pub(crate) const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Mode fast_qr picks for `data`: numeric, then alphanumeric, then byte
+pub(crate) fn best_mode(data: &[u8]) -> Mode {
+    if data.iter().all(u8::is_ascii_digit) {
+        Mode::Numeric
+    } else if data.iter().all(|c| ALPHANUMERIC.contains(c)) {
+        Mode::Alphanumeric
+    } else {
+        Mode::Byte
+    }
+}
+
+/// Width of the character count field of a `mode` segment in a `version` symbol
+pub(crate) fn count_bits(mode: Mode, version: usize) -> usize {
+    match (mode, version) {
+        (Mode::Numeric, 1..=9) => 10,
+        (Mode::Numeric, 10..=26) => 12,
+        (Mode::Numeric, _) => 14,
+        (Mode::Alphanumeric, 1..=9) => 9,
+        (Mode::Alphanumeric, 10..=26) => 11,
+        (Mode::Alphanumeric, _) => 13,
+        (Mode::Byte, 1..=9) => 8,
+        (Mode::Byte, _) => 16,
+    }
+}
+
+/// Bits of a `length`-character segment of `mode` in a `version` symbol,
+/// including the mode indicator and character count
+pub(crate) fn segment_bits(mode: Mode, length: usize, version: usize) -> usize {
+    let data = match mode {
+        Mode::Numeric => length / 3 * 10 + [0, 4, 7][length % 3],
+        Mode::Alphanumeric => length / 2 * 11 + length % 2 * 6,
+        Mode::Byte => length * 8,
+    };
+    4 + count_bits(mode, version) + data
+}
+
+/// Smallest version (1-40) a `length`-character segment of `mode` fits at `ecl`
+pub(crate) fn min_version(mode: Mode, length: usize, ecl: ErrorCorrectionLevel) -> Option<usize> {
+    (1..=40).find(|&v| segment_bits(mode, length, v) <= data_codewords(v, ecl) * 8)
+}
+
+/// How many characters (bytes in byte mode) of `mode` fit in a `version`
+/// symbol at `ecl`; 0 for versions outside 1-40
+pub fn capacity(version: usize, ecl: ErrorCorrectionLevel, mode: Mode) -> usize {
+    let Some(bits) = (data_codewords(version, ecl) * 8).checked_sub(4 + count_bits(mode, version)) else {
+        return 0;
+    };
+    match mode {
+        // 10 bits per 3 digits, 7 for 2, 4 for 1
+        Mode::Numeric => bits / 10 * 3 + [0, 0, 0, 0, 1, 1, 1, 2, 2, 2][bits % 10],
+        // 11 bits per 2 characters, 6 for 1
+        Mode::Alphanumeric => bits / 11 * 2 + (bits % 11 >= 6) as usize,
+        Mode::Byte => bits / 8,
+    }
+}
+
 /// A generated QR code
 #[derive(Debug)]
 pub struct QrCode {
     /// The underlying fast_qr code
     pub(crate) inner: fast_qr::QRCode,
+    /// Mask pattern fast_qr applied
+    pub(crate) mask: fast_qr::Mask,
+    /// Dark modules, packed
+    matrix: ModuleMatrix,
+    /// How the payload was encoded, in order
+    pub(crate) segments: Vec<Segment>,
     /// The original input text
     pub text: String,
     /// The error correction level used
@@ -40,27 +146,39 @@ pub struct QrCode {
 }
 
 impl QrCode {
+    pub(crate) fn new(inner: fast_qr::QRCode, data: &[u8], mode: Mode, ecl: ErrorCorrectionLevel) -> Result<Self, QrError> {
+        let version = inner.size.saturating_sub(17) / 4;
+        let segment = Segment::new(mode, data.len(), version);
+        Self::from_segments(inner, String::from_utf8_lossy(data).into_owned(), vec![segment], ecl)
+    }
+
+    /// Wrap a symbol built from several segments (or with headers fast_qr
+    /// doesn't write)
+    pub(crate) fn from_segments(
+        inner: fast_qr::QRCode,
+        text: String,
+        segments: Vec<Segment>,
+        ecl: ErrorCorrectionLevel,
+    ) -> Result<Self, QrError> {
+        let mask = inner.mask.ok_or_else(|| QrError::GenerationFailed("No mask was applied".into()))?;
+        let matrix = ModuleMatrix::from_qr(&inner);
+        Ok(QrCode { inner, mask, matrix, segments, text, ecl })
+    }
+
     /// Get the size of the QR code in modules
     pub fn size(&self) -> usize {
         self.inner.size
     }
 
+    /// Dark modules packed one bit each; what the renderers read
+    pub fn matrix(&self) -> &ModuleMatrix {
+        &self.matrix
+    }
+
     /// Get the flattened module data (row by row)
     /// 1 = dark, 0 = light
     pub fn get_modules(&self) -> Vec<u8> {
-        let size = self.inner.size;
-        let mut modules = Vec::with_capacity(size * size);
-        
-        // fast_qr stores modules in .data as Vec<Module>
-        // Module is a tuple struct Module(u8) where .value() returns true if dark
-        for module in self.inner.data.iter() {
-            if module.value() {
-                modules.push(1);
-            } else {
-                modules.push(0);
-            }
-        }
-        modules
+        self.matrix.to_bytes()
     }
 }
 
@@ -73,6 +191,9 @@ impl QrCode {
 /// # Returns
 /// A QrCode on success, or QrError on failure
 ///
+/// Non-ASCII text is written as UTF-8 bytes with no charset header; see
+/// `generate_qr_eci` to declare the charset or use another one.
+///
 /// # Example
 /// ```rust
 /// use holi_qr::{generate_qr, ErrorCorrectionLevel};
@@ -85,16 +206,75 @@ pub fn generate_qr(text: &str, ecl: ErrorCorrectionLevel) -> Result<QrCode, QrEr
         return Err(QrError::EmptyInput);
     }
 
+    let mode = best_mode(text.as_bytes());
     let inner = QRBuilder::new(text)
+        .mode(mode)
         .ecl(ecl.into())
         .build()
         .map_err(|e| QrError::GenerationFailed(format!("{:?}", e)))?;
 
-    Ok(QrCode {
-        inner,
-        text: text.to_string(),
-        ecl,
-    })
+    QrCode::new(inner, text.as_bytes(), mode, ecl)
+}
+
+/// Generate a QR code no larger than `max_version` (1-40)
+///
+/// For fixed-size layouts (labels, print templates) where a bigger symbol
+/// than planned would not fit.
+///
+/// # Returns
+/// * `Ok(QrCode)` - The code, at version `max_version` or below
+/// * `Err(QrError::ExceedsMaxVersion)` - With how many characters past the
+///   version's byte capacity the text has; a character cut by the limit counts
+pub fn generate_qr_constrained(text: &str, ecl: ErrorCorrectionLevel, max_version: usize) -> Result<QrCode, QrError> {
+    if !(1..=40).contains(&max_version) {
+        return Err(QrError::InvalidArgument(format!("Version must be 1-40 (got {})", max_version)));
+    }
+    if text.is_empty() {
+        return Err(QrError::EmptyInput);
+    }
+
+    let capacity = capacity(max_version, ecl, best_mode(text.as_bytes()));
+    if text.len() > capacity {
+        let over = text.char_indices().filter(|(i, c)| i + c.len_utf8() > capacity).count();
+        return Err(QrError::ExceedsMaxVersion { max_version, capacity, over });
+    }
+    generate_qr(text, ecl)
+}
+
+/// Generate the most robust QR code that fits in `max_version` (1-40)
+///
+/// Tries H, Q, M, then L and keeps the first that fits; the chosen level is
+/// the result's `ecl`.
+///
+/// # Returns
+/// * `Ok(QrCode)` - At the highest level that fits
+/// * `Err(QrError::ExceedsMaxVersion)` - Not even at L, with the overflow at L
+pub fn fit_best_ecc(text: &str, max_version: usize) -> Result<QrCode, QrError> {
+    for ecl in [ErrorCorrectionLevel::High, ErrorCorrectionLevel::Quartile, ErrorCorrectionLevel::Medium] {
+        match generate_qr_constrained(text, ecl, max_version) {
+            Err(QrError::ExceedsMaxVersion { .. }) => continue,
+            result => return result,
+        }
+    }
+    generate_qr_constrained(text, ErrorCorrectionLevel::Low, max_version)
+}
+
+/// Generate a QR code from raw bytes, forcing byte mode
+///
+/// Use this for binary payloads (e.g. signed QRs) that must survive
+/// scanning unchanged. `text` on the result is a lossy UTF-8 view.
+pub fn generate_qr_bytes(data: &[u8], ecl: ErrorCorrectionLevel) -> Result<QrCode, QrError> {
+    if data.is_empty() {
+        return Err(QrError::EmptyInput);
+    }
+
+    let inner = QRBuilder::new(data)
+        .mode(Mode::Byte)
+        .ecl(ecl.into())
+        .build()
+        .map_err(|e| QrError::GenerationFailed(format!("{:?}", e)))?;
+
+    QrCode::new(inner, data, Mode::Byte, ecl)
 }
 
 #[cfg(test)]
@@ -114,6 +294,61 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_capacity_table() {
+        // ISO/IEC 18004 table 7 corners
+        assert_eq!(capacity(1, ErrorCorrectionLevel::Low, Mode::Numeric), 41);
+        assert_eq!(capacity(1, ErrorCorrectionLevel::High, Mode::Alphanumeric), 10);
+        assert_eq!(capacity(10, ErrorCorrectionLevel::Medium, Mode::Byte), 213);
+        assert_eq!(capacity(40, ErrorCorrectionLevel::Low, Mode::Numeric), 7089);
+        assert_eq!(capacity(40, ErrorCorrectionLevel::Low, Mode::Alphanumeric), 4296);
+        assert_eq!(capacity(40, ErrorCorrectionLevel::High, Mode::Byte), 1273);
+    }
+
+    #[test]
+    fn test_generate_constrained() {
+        let url = "https://holi.tools/".to_string() + &"a".repeat(40);
+        let qr = generate_qr_constrained(&url, ErrorCorrectionLevel::Medium, 4).unwrap();
+        assert!(qr.size() <= 17 + 4 * 4);
+
+        // 62 bytes fit in version 4-M; each extra byte is reported
+        let long = url + &"b".repeat(10);
+        match generate_qr_constrained(&long, ErrorCorrectionLevel::Medium, 4) {
+            Err(QrError::ExceedsMaxVersion { max_version: 4, capacity: 62, over }) => assert_eq!(over, long.len() - 62),
+            other => panic!("unexpected {:?}", other.map(|qr| qr.size())),
+        }
+
+        // Multibyte text is reported in characters, counting the one the limit cuts
+        let accents = "https://holi.tools/".to_string() + &"a".repeat(42) + "ñandú";
+        assert_eq!(accents.len() - 62, 6);
+        match generate_qr_constrained(&accents, ErrorCorrectionLevel::Medium, 4) {
+            Err(QrError::ExceedsMaxVersion { capacity: 62, over, .. }) => assert_eq!(over, 5),
+            other => panic!("unexpected {:?}", other.map(|qr| qr.size())),
+        }
+
+        // Exactly at capacity still fits
+        let digits = "7".repeat(41);
+        assert_eq!(generate_qr_constrained(&digits, ErrorCorrectionLevel::Low, 1).unwrap().size(), 21);
+        assert!(generate_qr_constrained(&(digits + "7"), ErrorCorrectionLevel::Low, 1).is_err());
+        assert!(generate_qr_constrained("hi", ErrorCorrectionLevel::Low, 41).is_err());
+    }
+
+    #[test]
+    fn test_fit_best_ecc() {
+        // 30 bytes: H fits in version 4 (34), not in version 3 (24 at H, 32 at Q)
+        let text = "https://holi.tools/fit-best-ec";
+        assert_eq!(fit_best_ecc(text, 4).unwrap().ecl, ErrorCorrectionLevel::High);
+        let qr = fit_best_ecc(text, 3).unwrap();
+        assert_eq!(qr.ecl, ErrorCorrectionLevel::Quartile);
+        assert_eq!(qr.size(), 29);
+
+        assert_eq!(fit_best_ecc(&"x".repeat(17), 1).unwrap().ecl, ErrorCorrectionLevel::Low);
+        assert!(matches!(
+            fit_best_ecc(&"x".repeat(18), 1),
+            Err(QrError::ExceedsMaxVersion { capacity: 17, over: 1, .. })
+        ));
+    }
+
     #[test]
     fn test_error_correction_levels() {
         for ecl in [
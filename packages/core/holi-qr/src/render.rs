@@ -1,11 +1,34 @@
 //! SVG rendering for QR codes
 
+use crate::error::QrError;
+use crate::gradient::{gradient_suffix, gradient_url, Gradient};
+use crate::pattern::{pattern_svg, BackgroundPattern};
+use crate::layout::ModuleRole;
+use crate::logo::Logo;
+use crate::protect::protected_shape;
 use crate::qr::QrCode;
-use crate::shapes::{BodyShape, EyeFrameShape, EyeBallShape, body_path, eye_frame_path, eye_ball_path};
+use crate::shapes::{
+    BodyShape, EyeFrameShape, EyeBallShape, contour_body_path, eye_frame_path, eye_ball_path, liquid_body_path, push_body_path,
+};
 use fast_qr::convert::svg::SvgBuilder;
 use fast_qr::convert::Builder;
+use std::borrow::Cow;
 use std::fmt::Write;
 
+/// Largest quiet zone the renderers accept, in modules
+pub const MAX_MARGIN: usize = 64;
+
+/// Side of the rendered image, quiet zone included, in modules
+pub(crate) fn padded_size(size: usize, margin: usize) -> Result<usize, QrError> {
+    if margin > MAX_MARGIN {
+        return Err(QrError::InvalidArgument(format!(
+            "Margin must be at most {} modules (got {})",
+            MAX_MARGIN, margin
+        )));
+    }
+    Ok(size + margin * 2)
+}
+
 /// Options for SVG rendering (basic)
 #[derive(Debug, Clone)]
 pub struct RenderOptions {
@@ -34,6 +57,9 @@ pub struct StyledRenderOptions {
     pub margin: usize,
     /// Foreground color (dark modules)
     pub fg_color: String,
+    /// Gradient drawn instead of `fg_color`, on the body and on the eyes
+    /// without their own color or gradient
+    pub fg_gradient: Option<Gradient>,
     /// Background color (light modules)
     pub bg_color: String,
     /// Shape for body modules
@@ -42,6 +68,74 @@ pub struct StyledRenderOptions {
     pub eye_frame_shape: EyeFrameShape,
     /// Shape for eye balls
     pub eye_ball_shape: EyeBallShape,
+    /// Per-corner eye overrides, indexed by `EyeCorner`
+    pub eye_overrides: [EyeOverride; 3],
+    /// Decorative pattern behind the modules (symbol area only)
+    pub background_pattern: Option<BackgroundPattern>,
+    /// Stroke shapes instead of filling them
+    pub outline: Option<OutlineStyle>,
+    /// Draw timing, format, version and alignment modules with a fuller
+    /// shape when `body_shape` is too thin for them (see `shape_substitutions`)
+    pub protect_function_patterns: bool,
+    /// Image over the center; the modules under it are left out (see `check_logo`)
+    pub logo: Option<Logo>,
+}
+
+/// Stroke-only rendering, for engraving and coloring-book style exports
+///
+/// Outlined codes generally don't scan; they are meant to be filled in,
+/// cut or engraved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineStyle {
+    /// Stroke width, in modules
+    pub stroke_width: f64,
+    /// Outline whole clusters of connected modules instead of each module
+    /// (ignored for `BodyShape::Liquid`, which is already one outline per cluster)
+    pub contour_only: bool,
+}
+
+impl Default for OutlineStyle {
+    fn default() -> Self {
+        Self {
+            stroke_width: 0.1,
+            contour_only: false,
+        }
+    }
+}
+
+/// One of the three finder patterns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EyeCorner {
+    TopLeft = 0,
+    TopRight = 1,
+    BottomLeft = 2,
+}
+
+impl EyeCorner {
+    pub const ALL: [EyeCorner; 3] = [Self::TopLeft, Self::TopRight, Self::BottomLeft];
+
+    /// Name as used in WASM/JSON options
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TopLeft => "top_left",
+            Self::TopRight => "top_right",
+            Self::BottomLeft => "bottom_left",
+        }
+    }
+}
+
+/// Style for one eye that replaces the shared eye settings
+///
+/// Unset fields fall back to `eye_frame_shape`, `eye_ball_shape` and
+/// `fg_color`. An eye with its own color or gradient is drawn as a separate
+/// `<path>`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EyeOverride {
+    pub frame_shape: Option<EyeFrameShape>,
+    pub ball_shape: Option<EyeBallShape>,
+    pub color: Option<String>,
+    /// Takes precedence over `color`
+    pub gradient: Option<Gradient>,
 }
 
 impl Default for StyledRenderOptions {
@@ -49,10 +143,99 @@ impl Default for StyledRenderOptions {
         Self {
             margin: 4,
             fg_color: "#000000".to_string(),
+            fg_gradient: None,
             bg_color: "#FFFFFF".to_string(),
             body_shape: BodyShape::Square,
             eye_frame_shape: EyeFrameShape::Square,
             eye_ball_shape: EyeBallShape::Square,
+            eye_overrides: Default::default(),
+            background_pattern: None,
+            outline: None,
+            protect_function_patterns: true,
+            logo: None,
+        }
+    }
+}
+
+impl StyledRenderOptions {
+    /// Frame and ball shape of one eye, after overrides
+    pub fn eye_shapes(&self, corner: EyeCorner) -> (EyeFrameShape, EyeBallShape) {
+        let eye = &self.eye_overrides[corner as usize];
+        (
+            eye.frame_shape.unwrap_or(self.eye_frame_shape),
+            eye.ball_shape.unwrap_or(self.eye_ball_shape),
+        )
+    }
+
+    /// Color of one eye, after overrides
+    pub fn eye_color(&self, corner: EyeCorner) -> &str {
+        self.eye_overrides[corner as usize].color.as_deref().unwrap_or(&self.fg_color)
+    }
+
+    /// Gradient of one eye, if it has its own
+    fn eye_gradient(&self, corner: EyeCorner) -> Option<&Gradient> {
+        self.eye_overrides[corner as usize].gradient.as_ref().filter(|g| g.is_drawn())
+    }
+
+    /// Paint of the body and the eyes without their own: `fg_color` or a
+    /// reference to `fg_gradient`
+    fn fg_fill(&self) -> String {
+        match self.fg_gradient.as_ref().filter(|g| g.is_drawn()) {
+            Some(_) => gradient_url("fg", &self.gradient_suffix()),
+            None => self.fg_color.clone(),
+        }
+    }
+
+    /// The gradients in use, by the name their ids are built from
+    fn gradients(&self) -> Vec<(&'static str, &Gradient)> {
+        let eyes = EyeCorner::ALL.into_iter().filter_map(|c| self.eye_gradient(c).map(|g| (c.as_str(), g)));
+        self.fg_gradient.iter().filter(|g| g.is_drawn()).map(|g| ("fg", g)).chain(eyes).collect()
+    }
+
+    /// Suffix of this render's gradient ids
+    fn gradient_suffix(&self) -> String {
+        gradient_suffix(self.gradients())
+    }
+
+    /// `<defs>` with the gradients in use, if any
+    fn gradient_defs(&self) -> Result<Option<String>, QrError> {
+        let gradients = self.gradients();
+        if gradients.is_empty() {
+            return Ok(None);
+        }
+        let suffix = gradient_suffix(gradients.iter().copied());
+        let mut defs = String::with_capacity(16 + gradients.iter().map(|(_, g)| g.markup_capacity()).sum::<usize>());
+        defs.push_str("<defs>");
+        for (name, gradient) in gradients {
+            gradient.write_def(name, &suffix, &mut defs)?;
+        }
+        defs.push_str("</defs>");
+        Ok(Some(defs))
+    }
+
+    /// Attribute carrying a path's color: `fill`, or `stroke` when outlined
+    fn color_attribute(&self) -> &'static str {
+        if self.outline.is_some() { "stroke" } else { "fill" }
+    }
+
+    /// Paint attributes of a `<path>` in `color`
+    fn paint(&self, color: &str) -> String {
+        match &self.outline {
+            Some(outline) => format!(r#"fill="none" stroke="{}" stroke-width="{}""#, color, outline.stroke_width),
+            None => format!(r#"fill="{}""#, color),
+        }
+    }
+
+    /// Whether module `(x, y)` is hidden by the logo
+    fn logo_clears(&self, size: usize, x: usize, y: usize) -> bool {
+        self.logo.as_ref().is_some_and(|logo| logo.clears(size, x, y))
+    }
+
+    /// `<defs>` + `<rect>` of the background pattern, if any (and its colors can be checked)
+    fn pattern_markup(&self, size: usize) -> Result<Option<String>, QrError> {
+        match &self.background_pattern {
+            Some(pattern) => pattern_svg(pattern, &self.fg_color, &self.bg_color, size, self.margin),
+            None => Ok(None),
         }
     }
 }
@@ -63,12 +246,23 @@ pub fn render_svg(qr: &QrCode) -> String {
 }
 
 /// Render a QR code to SVG string with basic options
+///
+/// A margin over `MAX_MARGIN` is drawn as `MAX_MARGIN`.
 pub fn render_svg_with_options(qr: &QrCode, options: &RenderOptions) -> String {
     let mut builder = SvgBuilder::default();
-    builder.margin(options.margin);
+    builder.margin(options.margin.min(MAX_MARGIN));
     builder.to_str(&qr.inner)
 }
 
+/// `render_svg_with_options`, refusing a margin it would clamp
+///
+/// # Returns
+/// * `Err(QrError::InvalidArgument)` - Margin over `MAX_MARGIN`
+pub fn try_render_svg_with_options(qr: &QrCode, options: &RenderOptions) -> Result<String, QrError> {
+    padded_size(qr.size(), options.margin)?;
+    Ok(render_svg_with_options(qr, options))
+}
+
 /// Render a QR code to SVG string with styled shapes
 ///
 /// This is the main rendering function that supports:
@@ -76,19 +270,79 @@ pub fn render_svg_with_options(qr: &QrCode, options: &RenderOptions) -> String {
 /// - Custom eye frame shapes
 /// - Custom eye ball shapes
 /// - Custom colors
+///
+/// A margin over `MAX_MARGIN` is drawn as `MAX_MARGIN`, and a logo that
+/// can't be drawn is left out; `try_render_svg_styled` refuses both.
 pub fn render_svg_styled(qr: &QrCode, options: &StyledRenderOptions) -> String {
+    let mut svg = String::new();
+    render_svg_styled_into(qr, options, &mut svg);
+    svg
+}
+
+/// `render_svg_styled`, refusing options it would have to adjust
+///
+/// # Returns
+/// * `Ok(String)` - The SVG
+/// * `Err(QrError)` - Margin over `MAX_MARGIN`, a logo that can't be drawn,
+///   or the SVG couldn't be written
+pub fn try_render_svg_styled(qr: &QrCode, options: &StyledRenderOptions) -> Result<String, QrError> {
+    let mut svg = String::new();
+    try_render_svg_styled_into(qr, options, &mut svg)?;
+    Ok(svg)
+}
+
+/// `render_svg_styled` into an existing buffer, which is cleared first
+///
+/// Live previews re-render on every change; keeping one buffer across calls
+/// skips the allocation and regrowth of a fresh string each time.
+pub fn render_svg_styled_into(qr: &QrCode, options: &StyledRenderOptions, svg: &mut String) {
+    let options = drawable(options);
+    if try_render_svg_styled_into(qr, &options, svg).is_err() {
+        // Only writing the SVG is left to fail, which a String doesn't;
+        // should it anyway, still hand back a code that scans
+        svg.clear();
+        svg.push_str(&render_svg_with_options(qr, &RenderOptions { margin: options.margin, ..Default::default() }));
+    }
+}
+
+/// `options` as the infallible renderers draw them: the margin clamped to
+/// `MAX_MARGIN` and a logo that can't be drawn left out
+fn drawable(options: &StyledRenderOptions) -> Cow<'_, StyledRenderOptions> {
+    let bad_logo = options.logo.as_ref().is_some_and(|logo| logo.check_drawable().is_err());
+    if options.margin <= MAX_MARGIN && !bad_logo {
+        return Cow::Borrowed(options);
+    }
+    let mut options = options.clone();
+    options.margin = options.margin.min(MAX_MARGIN);
+    if bad_logo {
+        options.logo = None;
+    }
+    Cow::Owned(options)
+}
+
+/// `try_render_svg_styled` into an existing buffer, which is cleared first
+///
+/// On error the buffer's contents are unspecified.
+pub fn try_render_svg_styled_into(qr: &QrCode, options: &StyledRenderOptions, svg: &mut String) -> Result<(), QrError> {
     let size = qr.size();
-    let margin = options.margin;
-    let total = size + margin * 2;
+    let total = padded_size(size, options.margin)?;
+    let pattern = options.pattern_markup(size)?;
+    let gradients = options.gradient_defs()?;
+    if let Some(logo) = &options.logo {
+        logo.check_drawable()?;
+    }
     
-    let mut svg = String::new();
+    svg.clear();
+    svg.reserve(
+        styled_svg_capacity(qr, options) + pattern.as_ref().map_or(0, String::len) + gradients.as_ref().map_or(0, String::len),
+    );
     
     // SVG header
     write!(
         svg,
         r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}">"#,
         total, total
-    ).unwrap();
+    )?;
     
     // Background
     if options.bg_color != "transparent" {
@@ -96,87 +350,369 @@ pub fn render_svg_styled(qr: &QrCode, options: &StyledRenderOptions) -> String {
             svg,
             r#"<rect width="{}" height="{}" fill="{}"/>"#,
             total, total, options.bg_color
-        ).unwrap();
+        )?;
     }
     
-    // Get module data
-    let modules = qr.get_modules();
-    
-    // Helper to check if module is dark
-    let is_dark = |x: usize, y: usize| -> bool {
-        if x >= size || y >= size { return false; }
-        modules[y * size + x] == 1
-    };
+    // Decorative pattern
+    if let Some(pattern) = pattern {
+        svg.push_str(&pattern);
+    }
     
-    // Check if position is in finder pattern zone (7x7 corners)
-    let is_finder_zone = |x: usize, y: usize| -> bool {
-        // Top-left
-        if x < 7 && y < 7 { return true; }
-        // Top-right
-        if x >= size - 7 && y < 7 { return true; }
-        // Bottom-left
-        if x < 7 && y >= size - 7 { return true; }
-        false
-    };
+    // Gradients the paths refer to
+    if let Some(gradients) = gradients {
+        svg.push_str(&gradients);
+    }
     
-    // Build body path (all data modules except finder zones)
-    let mut body_path_str = String::new();
-    for y in 0..size {
-        for x in 0..size {
-            if is_finder_zone(x, y) { continue; }
-            if is_dark(x, y) {
-                let px = (x + margin) as f64;
-                let py = (y + margin) as f64;
-                body_path_str.push_str(&body_path(options.body_shape, px, py));
-            }
-        }
+    // Render body straight into the buffer, dropping the element if empty
+    let element_start = svg.len();
+    svg.push_str(r#"<path d=""#);
+    let body_start = svg.len();
+    push_styled_body_path(qr, options, svg)?;
+    if svg.len() == body_start {
+        svg.truncate(element_start);
+    } else {
+        write!(svg, r#"" {}/>"#, options.paint(&options.fg_fill()))?;
     }
     
-    // Render body
-    if !body_path_str.is_empty() {
+    // Render finder patterns, one path per color
+    for group in finder_groups(options) {
         write!(
             svg,
-            r#"<path d="{}" fill="{}"/>"#,
-            body_path_str, options.fg_color
-        ).unwrap();
+            r#"<path d="{}" {}/>"#,
+            styled_finder_path(qr, options, &group.corners), options.paint(&group.fill)
+        )?;
     }
     
-    // Build finder patterns (eye frames + eye balls)
-    let mut finder_path = String::new();
+    // Logo over the cleared center
+    if let Some(logo) = &options.logo {
+        logo.write_markup(size, options.margin, svg)?;
+    }
     
-    // Finder pattern positions (top-left corner of each 7x7 pattern)
-    let finder_positions = [
-        (0, 0),                     // Top-left
-        (size - 7, 0),              // Top-right
-        (0, size - 7),              // Bottom-left
-    ];
+    // Close SVG
+    svg.push_str("</svg>");
     
-    for (ox, oy) in finder_positions {
+    Ok(())
+}
+
+/// Longest path data of one eye (frame + ball), in bytes
+const EYE_PATH_COST: usize = 1024;
+
+/// Bytes `render_svg_styled` is expected to write, less the background
+/// pattern and gradients
+fn styled_svg_capacity(qr: &QrCode, options: &StyledRenderOptions) -> usize {
+    let logo = options.logo.as_ref().map_or(0, Logo::markup_capacity);
+    let dark = qr.matrix().dark_count();
+    let colors = options.fg_color.len() + options.bg_color.len()
+        + options.eye_overrides.iter().filter_map(|e| e.color.as_ref()).map(String::len).sum::<usize>();
+    // Header, background and the tags around each path
+    256 + colors + logo + dark * options.body_shape.path_cost() + EyeCorner::ALL.len() * (EYE_PATH_COST + 64)
+}
+
+/// Path data for all dark data modules, excluding the finder zones
+fn styled_body_path(qr: &QrCode, options: &StyledRenderOptions) -> Result<String, QrError> {
+    let mut path = String::new();
+    push_styled_body_path(qr, options, &mut path)?;
+    Ok(path)
+}
+
+/// `styled_body_path` appended to `out`
+fn push_styled_body_path(qr: &QrCode, options: &StyledRenderOptions, out: &mut String) -> Result<(), QrError> {
+    let size = qr.size();
+    let margin = options.margin;
+    
+    let modules = qr.matrix();
+    let is_body = |x, y| !is_finder_zone(size, x, y) && modules.is_dark(x, y) && !options.logo_clears(size, x, y);
+    if options.body_shape == BodyShape::Liquid {
+        out.push_str(&liquid_body_path(size, margin as f64, is_body)?);
+        return Ok(());
+    }
+    if options.outline.as_ref().is_some_and(|o| o.contour_only) {
+        out.push_str(&contour_body_path(size, margin as f64, is_body)?);
+        return Ok(());
+    }
+
+    // Build body path (all dark modules except finders)
+    for (x, y, dark, role) in qr.iter_modules() {
+        if !dark || role == ModuleRole::Finder || options.logo_clears(size, x, y) { continue; }
+        let shape = if options.protect_function_patterns {
+            protected_shape(role, options.body_shape)
+        } else {
+            options.body_shape
+        };
+        push_body_path(out, shape, (x + margin) as f64, (y + margin) as f64)?;
+    }
+    Ok(())
+}
+
+/// Check if position is in finder pattern zone (7x7 corners)
+fn is_finder_zone(size: usize, x: usize, y: usize) -> bool {
+    // Top-left
+    if x < 7 && y < 7 { return true; }
+    // Top-right
+    if x + 7 >= size && y < 7 { return true; }
+    // Bottom-left
+    if x < 7 && y + 7 >= size { return true; }
+    false
+}
+
+/// Finder patterns drawn as one `<path>`
+struct FinderGroup {
+    part: SvgPart,
+    fill: String,
+    corners: Vec<EyeCorner>,
+}
+
+/// Finder paths in paint order: eyes in the foreground paint together, then
+/// each eye with its own color or gradient
+fn finder_groups(options: &StyledRenderOptions) -> Vec<FinderGroup> {
+    let (shared, own): (Vec<_>, Vec<_>) = EyeCorner::ALL
+        .into_iter()
+        .partition(|&c| options.eye_overrides[c as usize].color.is_none() && options.eye_gradient(c).is_none());
+    let mut groups = Vec::with_capacity(3);
+    if !shared.is_empty() {
+        groups.push(FinderGroup { part: SvgPart::Finders, fill: options.fg_fill(), corners: shared });
+    }
+    for corner in own {
+        let fill = match options.eye_gradient(corner) {
+            Some(_) => gradient_url(corner.as_str(), &options.gradient_suffix()),
+            None => options.eye_color(corner).to_string(),
+        };
+        groups.push(FinderGroup { part: SvgPart::Eye(corner), fill, corners: vec![corner] });
+    }
+    groups
+}
+
+/// Path data for the given finder patterns (eye frames + eye balls)
+fn styled_finder_path(qr: &QrCode, options: &StyledRenderOptions, corners: &[EyeCorner]) -> String {
+    let size = qr.size();
+    let margin = options.margin;
+    let mut finder_path = String::new();
+    
+    for &corner in corners {
+        // Top-left corner of the 7x7 pattern
+        let (ox, oy) = match corner {
+            EyeCorner::TopLeft => (0, 0),
+            EyeCorner::TopRight => (size.saturating_sub(7), 0),
+            EyeCorner::BottomLeft => (0, size.saturating_sub(7)),
+        };
         let fx = (ox + margin) as f64;
         let fy = (oy + margin) as f64;
+        let (frame_shape, ball_shape) = options.eye_shapes(corner);
         
         // Eye frame (outer 7x7)
-        finder_path.push_str(&eye_frame_path(options.eye_frame_shape, fx, fy));
+        finder_path.push_str(&eye_frame_path(frame_shape, fx, fy));
         
         // Eye ball (inner 3x3, offset by 2 from frame origin)
         let bx = fx + 2.0;
         let by = fy + 2.0;
-        finder_path.push_str(&eye_ball_path(options.eye_ball_shape, bx, by));
+        finder_path.push_str(&eye_ball_path(ball_shape, bx, by));
     }
-    
-    // Render finder patterns
-    if !finder_path.is_empty() {
-        write!(
-            svg,
-            r#"<path d="{}" fill="{}"/>"#,
-            finder_path, options.fg_color
-        ).unwrap();
+    finder_path
+}
+
+/// An element of the SVG written by `render_svg_styled`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SvgPart {
+    /// Background `<rect>` (absent when transparent)
+    Background,
+    /// `<path>` of the data modules
+    Body,
+    /// `<path>` of the finder patterns drawn in the foreground color
+    Finders,
+    /// `<path>` of one eye with its own color or gradient
+    Eye(EyeCorner),
+}
+
+/// One attribute change for an already-rendered styled SVG
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgPatch {
+    pub part: SvgPart,
+    /// Position of the element among the `<svg>` element's children
+    pub index: usize,
+    /// `"fill"`, `"stroke"` (outlined) or `"d"`
+    pub attribute: &'static str,
+    pub value: String,
+}
+
+/// Patches that turn `render_svg_styled(qr, prev)` into `render_svg_styled(qr, next)`
+///
+/// Meant for live style editors: a color change touches one attribute instead
+/// of replacing a multi-MB SVG. Only the parts that changed are re-rendered.
+///
+/// # Returns
+/// * `Some(patches)` - Attribute updates (empty if nothing changed)
+/// * `None` - The structure changed (margin, background to/from
+///   transparent, background pattern, gradients, outline style, logo, or
+///   which eyes have their own color); render the whole SVG again
+pub fn render_svg_styled_diff(
+    qr: &QrCode,
+    prev: &StyledRenderOptions,
+    next: &StyledRenderOptions,
+) -> Option<Vec<SvgPatch>> {
+    try_render_svg_styled_diff(qr, &drawable(prev), &drawable(next)).ok().flatten()
+}
+
+/// `render_svg_styled_diff` between `try_render_svg_styled` outputs
+///
+/// # Returns
+/// * `Ok(Some(patches))` - Attribute updates (empty if nothing changed)
+/// * `Ok(None)` - The structure changed; render the whole SVG again
+/// * `Err(QrError)` - As for `try_render_svg_styled` with `next`
+pub fn try_render_svg_styled_diff(
+    qr: &QrCode,
+    prev: &StyledRenderOptions,
+    next: &StyledRenderOptions,
+) -> Result<Option<Vec<SvgPatch>>, QrError> {
+    let size = qr.size();
+    padded_size(size, next.margin)?;
+    let transparent = |o: &StyledRenderOptions| o.bg_color == "transparent";
+    if prev.margin != next.margin
+        || transparent(prev) != transparent(next)
+        || prev.outline != next.outline
+        || prev.logo != next.logo
+    {
+        return Ok(None);
     }
-    
-    // Close SVG
-    svg.push_str("</svg>");
-    
-    svg
+
+    let modules = qr.matrix();
+    let has_body = (0..size * size).any(|i| {
+        let (x, y) = (i % size, i / size);
+        modules.is_dark(x, y) && !is_finder_zone(size, x, y) && !next.logo_clears(size, x, y)
+    });
+    let pattern = next.pattern_markup(size)?;
+    if prev.pattern_markup(size)? != pattern {
+        return Ok(None);
+    }
+    let gradients = next.gradient_defs()?;
+    if prev.gradient_defs()? != gradients {
+        return Ok(None);
+    }
+    let background = !transparent(next) as usize;
+    let body_index = background + if pattern.is_some() { 2 } else { 0 } + gradients.is_some() as usize;
+    let finders_index = body_index + has_body as usize;
+
+    let (prev_groups, next_groups) = (finder_groups(prev), finder_groups(next));
+    let same_groups = prev_groups.len() == next_groups.len()
+        && prev_groups.iter().zip(&next_groups).all(|(p, n)| p.part == n.part && p.corners == n.corners);
+    if !same_groups {
+        return Ok(None);
+    }
+
+    let mut patches = Vec::new();
+    let mut patch = |part, index, attribute, value| patches.push(SvgPatch { part, index, attribute, value });
+
+    if background == 1 && prev.bg_color != next.bg_color {
+        patch(SvgPart::Background, 0, "fill", next.bg_color.clone());
+    }
+    let color = next.color_attribute();
+    if has_body && (prev.body_shape != next.body_shape || prev.protect_function_patterns != next.protect_function_patterns) {
+        patch(SvgPart::Body, body_index, "d", styled_body_path(qr, next)?);
+    }
+    let body_fill = next.fg_fill();
+    if has_body && prev.fg_fill() != body_fill {
+        patch(SvgPart::Body, body_index, color, body_fill);
+    }
+    for (i, (old, new)) in prev_groups.iter().zip(&next_groups).enumerate() {
+        if new.corners.iter().any(|&c| prev.eye_shapes(c) != next.eye_shapes(c)) {
+            patch(new.part, finders_index + i, "d", styled_finder_path(qr, next, &new.corners));
+        }
+        if old.fill != new.fill {
+            patch(new.part, finders_index + i, color, new.fill.clone());
+        }
+    }
+    Ok(Some(patches))
+}
+
+/// One shape of a styled SVG
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgLayer {
+    /// SVG path data (also valid for a canvas `Path2D`)
+    pub path: String,
+    /// Fill color, `"none"` for outlined shapes
+    pub fill: String,
+    /// Stroke color and width, for outlined shapes
+    pub stroke: Option<(String, f64)>,
+}
+
+/// The drawable parts of an SVG from `render_svg_styled`
+///
+/// Lets a 2D canvas (e.g. `OffscreenCanvas` with `Path2D`) draw the QR code
+/// without a full SVG renderer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyledSvgLayers {
+    /// Side of the square viewBox, in modules
+    pub size: f64,
+    /// Background fill, `None` when transparent
+    pub background: Option<String>,
+    /// Shapes in paint order
+    pub layers: Vec<SvgLayer>,
+}
+
+/// Value of `name="..."` in a tag
+fn svg_attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+/// Split an SVG from `render_svg_styled` into its background and paths
+///
+/// Only the elements that renderer writes are understood (`svg`, `rect`,
+/// `path`); anything else is an error rather than being silently skipped.
+/// The decorative background pattern (`<defs>` and its `url(#...)` rect) is
+/// left out. Gradient fills can't be drawn from path data alone and are an
+/// error.
+pub fn parse_styled_svg(svg: &str) -> Result<StyledSvgLayers, QrError> {
+    let unsupported = |what: &str| QrError::InvalidArgument(format!("Unsupported SVG: {}", what));
+    let mut size = None;
+    let mut background = None;
+    let mut layers = Vec::new();
+    let mut in_defs = false;
+
+    for tag in svg.split('<').map(str::trim).filter(|t| !t.is_empty()) {
+        let name = tag.split(|c: char| c.is_whitespace() || c == '>' || c == '/').next().unwrap_or("");
+        if in_defs {
+            in_defs = !tag.starts_with("/defs");
+            continue;
+        }
+        match name {
+            "defs" => in_defs = true,
+            "rect" if svg_attr(tag, "fill").is_some_and(|f| f.starts_with("url(")) => {}
+            "svg" if size.is_some() => return Err(unsupported("nested <svg> (logo)")),
+            "svg" => {
+                let view_box = svg_attr(tag, "viewBox").ok_or_else(|| unsupported("missing viewBox"))?;
+                let dims: Vec<f64> = view_box.split_whitespace().filter_map(|v| v.parse().ok()).collect();
+                match dims[..] {
+                    [0.0, 0.0, w, h] if w == h && w > 0.0 => size = Some(w),
+                    _ => return Err(unsupported("viewBox must be square and start at 0 0")),
+                }
+            }
+            "rect" => background = svg_attr(tag, "fill").map(str::to_string),
+            "path" if ["fill", "stroke"].iter().any(|a| svg_attr(tag, a).is_some_and(|v| v.starts_with("url("))) => {
+                return Err(unsupported("gradient fill"));
+            }
+            "path" => layers.push(SvgLayer {
+                path: svg_attr(tag, "d").ok_or_else(|| unsupported("path without d"))?.to_string(),
+                fill: svg_attr(tag, "fill").unwrap_or("#000000").to_string(),
+                stroke: match (svg_attr(tag, "stroke"), svg_attr(tag, "stroke-width")) {
+                    (Some(color), width) => Some((
+                        color.to_string(),
+                        width.map_or(Ok(1.0), str::parse).map_err(|_| unsupported("bad stroke-width"))?,
+                    )),
+                    (None, _) => None,
+                },
+            }),
+            "" if tag.starts_with("/svg") => {}
+            _ if tag.starts_with("?xml") => {}
+            other => return Err(unsupported(&format!("<{}> element", other))),
+        }
+    }
+
+    Ok(StyledSvgLayers {
+        size: size.ok_or_else(|| unsupported("no <svg> element"))?,
+        background,
+        layers,
+    })
 }
 
 #[cfg(test)]
@@ -221,6 +757,262 @@ mod tests {
         assert!(svg.contains("path")); // Should have paths for shapes
     }
 
+    #[test]
+    fn test_render_into_reuses_buffer() {
+        let qr = generate_qr("https://holi.tools/preview", ErrorCorrectionLevel::High).unwrap();
+        let mut buffer = String::new();
+        for shape in BodyShape::ALL {
+            let options = StyledRenderOptions { body_shape: shape, bg_color: "transparent".to_string(), ..Default::default() };
+            render_svg_styled_into(&qr, &options, &mut buffer);
+            assert_eq!(buffer, render_svg_styled(&qr, &options));
+            // The estimate is enough that the buffer never has to grow
+            assert!(buffer.len() <= styled_svg_capacity(&qr, &options), "{:?}", shape);
+        }
+        let capacity = buffer.capacity();
+        render_svg_styled_into(&qr, &StyledRenderOptions::default(), &mut buffer);
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    /// Apply patches to the parsed layers of an SVG
+    fn apply(svg: &str, patches: &[SvgPatch]) -> StyledSvgLayers {
+        let mut layers = parse_styled_svg(svg).unwrap();
+        let offset = layers.background.is_some() as usize;
+        for p in patches {
+            match (p.part, p.attribute) {
+                (SvgPart::Background, "fill") => layers.background = Some(p.value.clone()),
+                (_, "fill") => layers.layers[p.index - offset].fill = p.value.clone(),
+                (_, "d") => layers.layers[p.index - offset].path = p.value.clone(),
+                other => panic!("unexpected patch {:?}", other),
+            }
+        }
+        layers
+    }
+
+    #[test]
+    fn test_styled_diff_matches_full_render() {
+        let qr = generate_qr("https://holi.tools/diff", ErrorCorrectionLevel::Medium).unwrap();
+        let prev = StyledRenderOptions::default();
+        let before = render_svg_styled(&qr, &prev);
+
+        assert_eq!(render_svg_styled_diff(&qr, &prev, &prev), Some(vec![]));
+
+        let color = StyledRenderOptions { fg_color: "#ff0066".to_string(), ..prev.clone() };
+        let patches = render_svg_styled_diff(&qr, &prev, &color).unwrap();
+        assert!(patches.iter().all(|p| p.attribute == "fill"));
+        assert_eq!(patches.len(), 2);
+
+        let next = StyledRenderOptions {
+            bg_color: "#eeeeee".to_string(),
+            body_shape: BodyShape::Dots,
+            eye_ball_shape: EyeBallShape::Circle,
+            ..color
+        };
+        let patches = render_svg_styled_diff(&qr, &prev, &next).unwrap();
+        assert_eq!(apply(&before, &patches), parse_styled_svg(&render_svg_styled(&qr, &next)).unwrap());
+    }
+
+    #[test]
+    fn test_eye_overrides() {
+        let qr = generate_qr("https://holi.tools/eyes", ErrorCorrectionLevel::Medium).unwrap();
+        let mut options = StyledRenderOptions::default();
+        options.eye_overrides[EyeCorner::TopRight as usize].frame_shape = Some(EyeFrameShape::Circle);
+        let plain = parse_styled_svg(&render_svg_styled(&qr, &StyledRenderOptions::default())).unwrap();
+        let layers = parse_styled_svg(&render_svg_styled(&qr, &options)).unwrap();
+        assert_eq!(layers.layers.len(), 2);
+        assert_ne!(layers.layers[1].path, plain.layers[1].path);
+
+        // A colored accent eye gets its own path, after the shared one
+        let accent = EyeOverride {
+            ball_shape: Some(EyeBallShape::Circle),
+            color: Some("#ff0066".to_string()),
+            ..Default::default()
+        };
+        options.eye_overrides[EyeCorner::BottomLeft as usize] = accent;
+        let layers = parse_styled_svg(&render_svg_styled(&qr, &options)).unwrap();
+        assert_eq!(layers.layers.len(), 3);
+        assert_eq!(layers.layers[1].fill, "#000000");
+        assert_eq!(layers.layers[2].fill, "#ff0066");
+        assert_eq!(options.eye_shapes(EyeCorner::BottomLeft), (EyeFrameShape::Square, EyeBallShape::Circle));
+
+        // Recoloring the accent is a patch; un-accenting it restructures the SVG
+        let mut recolored = options.clone();
+        recolored.eye_overrides[EyeCorner::BottomLeft as usize].color = Some("#00aaff".to_string());
+        let patches = render_svg_styled_diff(&qr, &options, &recolored).unwrap();
+        assert_eq!(patches, vec![SvgPatch {
+            part: SvgPart::Eye(EyeCorner::BottomLeft),
+            index: 3,
+            attribute: "fill",
+            value: "#00aaff".to_string(),
+        }]);
+        let mut plain = options.clone();
+        plain.eye_overrides[EyeCorner::BottomLeft as usize].color = None;
+        assert_eq!(render_svg_styled_diff(&qr, &options, &plain), None);
+    }
+
+    #[test]
+    fn test_background_pattern_layer() {
+        let qr = generate_qr("pattern", ErrorCorrectionLevel::Medium).unwrap();
+        let options = StyledRenderOptions {
+            background_pattern: Some(BackgroundPattern::default()),
+            ..Default::default()
+        };
+        let svg = render_svg_styled(&qr, &options);
+        assert!(svg.contains("<defs><pattern"));
+
+        // Parsed layers skip the decoration
+        let layers = parse_styled_svg(&svg).unwrap();
+        assert_eq!(layers, parse_styled_svg(&render_svg_styled(&qr, &StyledRenderOptions::default())).unwrap());
+
+        // Paths sit after the background, defs and pattern rect
+        let dots = StyledRenderOptions { body_shape: BodyShape::Dots, ..options.clone() };
+        let patches = render_svg_styled_diff(&qr, &options, &dots).unwrap();
+        assert_eq!((patches[0].part, patches[0].index), (SvgPart::Body, 3));
+
+        // Changing the pattern, or colors it is constrained by, restructures
+        let plain = StyledRenderOptions::default();
+        assert_eq!(render_svg_styled_diff(&qr, &plain, &options), None);
+        let dense = StyledRenderOptions {
+            background_pattern: Some(BackgroundPattern { density: 1.0, ..Default::default() }),
+            ..options.clone()
+        };
+        assert_eq!(render_svg_styled_diff(&qr, &options, &dense), None);
+    }
+
+    #[test]
+    fn test_gradient_fills() {
+        use crate::gradient::{GradientKind, GradientStop};
+        let qr = generate_qr("gradient", ErrorCorrectionLevel::Medium).unwrap();
+        let stops = |from: &str, to: &str| vec![
+            GradientStop { offset: 0.0, color: from.to_string() },
+            GradientStop { offset: 1.0, color: to.to_string() },
+        ];
+        let mut options = StyledRenderOptions {
+            fg_gradient: Some(Gradient { kind: GradientKind::Linear, stops: stops("#000000", "#1a237e"), angle: 45.0 }),
+            ..Default::default()
+        };
+        options.eye_overrides[EyeCorner::TopRight as usize].gradient =
+            Some(Gradient { kind: GradientKind::Radial, stops: stops("#b00020", "#000000"), angle: 0.0 });
+        let svg = render_svg_styled(&qr, &options);
+        assert_eq!(svg.matches("<defs>").count(), 1);
+        let suffix = options.gradient_suffix();
+        assert!(svg.contains(&format!(r#"<linearGradient id="holi-gradient-fg-{}""#, suffix)));
+        assert!(svg.contains(&format!(r#"<radialGradient id="holi-gradient-top_right-{}""#, suffix)));
+        // Body and shared eyes, then the eye with its own gradient
+        assert_eq!(svg.matches(&format!(r##"fill="url(#holi-gradient-fg-{})""##, suffix)).count(), 2);
+        assert_eq!(svg.matches(&format!(r##"fill="url(#holi-gradient-top_right-{})""##, suffix)).count(), 1);
+        assert!(parse_styled_svg(&svg).is_err());
+
+        // Codes with other gradients, inlined in the same page, keep their own
+        let mut other = options.clone();
+        other.fg_gradient.as_mut().unwrap().stops = stops("#000000", "#004d40");
+        let other_svg = render_svg_styled(&qr, &other);
+        assert!(!other_svg.contains(&suffix));
+        assert!(other_svg.contains(&format!(r##"fill="url(#holi-gradient-fg-{})""##, other.gradient_suffix())));
+
+        // fg_color is hidden behind the gradient; the body sits after the defs
+        let recolored = StyledRenderOptions { fg_color: "#ff0000".to_string(), ..options.clone() };
+        assert_eq!(render_svg_styled_diff(&qr, &options, &recolored), Some(vec![]));
+        let dots = StyledRenderOptions { body_shape: BodyShape::Dots, ..options.clone() };
+        let patches = render_svg_styled_diff(&qr, &options, &dots).unwrap();
+        assert_eq!((patches[0].part, patches[0].index), (SvgPart::Body, 2));
+        let flat = StyledRenderOptions { fg_gradient: None, ..options.clone() };
+        assert_eq!(render_svg_styled_diff(&qr, &options, &flat), None);
+
+        // Without stops the solid colors are drawn as before
+        let empty = StyledRenderOptions { fg_gradient: Some(Gradient::default()), ..Default::default() };
+        assert_eq!(render_svg_styled(&qr, &empty), render_svg_styled(&qr, &StyledRenderOptions::default()));
+    }
+
+    #[test]
+    fn test_styled_diff_needs_full_render() {
+        let qr = generate_qr("test", ErrorCorrectionLevel::Medium).unwrap();
+        let prev = StyledRenderOptions::default();
+        let margin = StyledRenderOptions { margin: 2, ..prev.clone() };
+        let transparent = StyledRenderOptions { bg_color: "transparent".to_string(), ..prev.clone() };
+        assert_eq!(render_svg_styled_diff(&qr, &prev, &margin), None);
+        assert_eq!(render_svg_styled_diff(&qr, &prev, &transparent), None);
+
+        // Without a background the paths shift down one index
+        let dots = StyledRenderOptions { body_shape: BodyShape::Dots, ..transparent.clone() };
+        let patches = render_svg_styled_diff(&qr, &transparent, &dots).unwrap();
+        assert_eq!((patches[0].part, patches[0].index), (SvgPart::Body, 0));
+    }
+
+    #[test]
+    fn test_parse_styled_svg() {
+        let qr = generate_qr("layers", ErrorCorrectionLevel::Medium).unwrap();
+        let options = StyledRenderOptions {
+            fg_color: "#112233".to_string(),
+            ..Default::default()
+        };
+        let layers = parse_styled_svg(&render_svg_styled(&qr, &options)).unwrap();
+        assert_eq!(layers.size, (qr.size() + 8) as f64);
+        assert_eq!(layers.background.as_deref(), Some("#FFFFFF"));
+        assert_eq!(layers.layers.len(), 2);
+        assert!(layers.layers.iter().all(|l| l.fill == "#112233" && l.path.starts_with('M')));
+
+        let transparent = StyledRenderOptions {
+            bg_color: "transparent".to_string(),
+            ..Default::default()
+        };
+        let layers = parse_styled_svg(&render_svg_styled(&qr, &transparent)).unwrap();
+        assert_eq!(layers.background, None);
+
+        assert!(parse_styled_svg("<svg viewBox=\"0 0 10 10\"><circle r=\"1\"/></svg>").is_err());
+        assert!(parse_styled_svg("<svg viewBox=\"0 0 10 20\"></svg>").is_err());
+        assert!(parse_styled_svg("not svg").is_err());
+    }
+
+    #[test]
+    fn test_outline_mode() {
+        let qr = generate_qr("outline", ErrorCorrectionLevel::Medium).unwrap();
+        let options = StyledRenderOptions {
+            outline: Some(OutlineStyle::default()),
+            ..Default::default()
+        };
+        let svg = render_svg_styled(&qr, &options);
+        assert_eq!(svg.matches(r##"fill="none" stroke="#000000" stroke-width="0.1""##).count(), 2);
+
+        let layers = parse_styled_svg(&svg).unwrap();
+        assert!(layers.layers.iter().all(|l| l.fill == "none" && l.stroke == Some(("#000000".to_string(), 0.1))));
+
+        // Cluster contours draw fewer segments than one square per module
+        let contours = StyledRenderOptions {
+            outline: Some(OutlineStyle { contour_only: true, ..Default::default() }),
+            ..Default::default()
+        };
+        assert!(styled_body_path(&qr, &contours).unwrap().len() < styled_body_path(&qr, &options).unwrap().len());
+
+        // Colors are patched on the stroke; toggling the outline needs a full render
+        let red = StyledRenderOptions { fg_color: "#FF0000".to_string(), ..options.clone() };
+        let patches = render_svg_styled_diff(&qr, &options, &red).unwrap();
+        assert!(patches.iter().all(|p| p.attribute == "stroke"));
+        assert_eq!(render_svg_styled_diff(&qr, &options, &contours), None);
+        assert_eq!(render_svg_styled_diff(&qr, &StyledRenderOptions::default(), &options), None);
+    }
+
+    #[test]
+    fn test_function_pattern_protection() {
+        use crate::shapes::body_path;
+
+        let qr = generate_qr("protect", ErrorCorrectionLevel::Medium).unwrap();
+        let cross = StyledRenderOptions { body_shape: BodyShape::Cross, ..Default::default() };
+        let raw = StyledRenderOptions { protect_function_patterns: false, ..cross.clone() };
+
+        // Timing modules are squares unless protection is off
+        let timing = body_path(BodyShape::Square, 12.0, 10.0);
+        assert!(styled_body_path(&qr, &cross).unwrap().contains(&timing));
+        assert!(!styled_body_path(&qr, &raw).unwrap().contains(&timing));
+
+        // Square needs no substitution, so protection changes nothing
+        let square = StyledRenderOptions::default();
+        let square_raw = StyledRenderOptions { protect_function_patterns: false, ..Default::default() };
+        assert_eq!(styled_body_path(&qr, &square).unwrap(), styled_body_path(&qr, &square_raw).unwrap());
+
+        let patches = render_svg_styled_diff(&qr, &cross, &raw).unwrap();
+        assert_eq!(patches[0].attribute, "d");
+    }
+
     #[test]
     fn test_all_body_shapes() {
         let qr = generate_qr("test", ErrorCorrectionLevel::Medium).unwrap();
@@ -230,6 +1022,7 @@ mod tests {
             BodyShape::Diamond,
             BodyShape::Star,
             BodyShape::Cross,
+            BodyShape::Liquid,
         ];
         
         for shape in shapes {
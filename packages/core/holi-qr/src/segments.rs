@@ -0,0 +1,219 @@
+//! Segment breakdown and payload hints
+//!
+//! The whole payload is encoded as one segment, in the densest mode every
+//! character allows. One lowercase letter is enough to push a URL from
+//! alphanumeric (5.5 bits a character) to byte mode (8 bits), so small
+//! rewrites can buy a smaller symbol. `optimize_payload` suggests them.
+//! `generate_qr_kanji` splits Japanese text into Kanji and ASCII segments.
+
+use crate::classify::{classify_payload, PayloadKind};
+use crate::qr::{best_mode, min_version, segment_bits, ErrorCorrectionLevel, QrCode};
+use fast_qr::Mode;
+
+/// Encoding mode of a segment: fast_qr's modes, plus Kanji
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentMode {
+    Numeric,
+    Alphanumeric,
+    Byte,
+    /// Double-byte Shift-JIS characters, 13 bits each
+    Kanji,
+}
+
+impl SegmentMode {
+    /// `"numeric"`, `"alphanumeric"`, `"byte"` or `"kanji"`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Numeric => "numeric",
+            Self::Alphanumeric => "alphanumeric",
+            Self::Byte => "byte",
+            Self::Kanji => "kanji",
+        }
+    }
+}
+
+impl From<Mode> for SegmentMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Numeric => Self::Numeric,
+            Mode::Alphanumeric => Self::Alphanumeric,
+            Mode::Byte => Self::Byte,
+        }
+    }
+}
+
+/// A run of the payload encoded in one mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub mode: SegmentMode,
+    /// Characters (bytes in byte mode)
+    pub length: usize,
+    /// Bits in the symbol, including mode indicator and character count
+    pub bits: usize,
+}
+
+impl Segment {
+    pub(crate) fn new(mode: Mode, length: usize, version: usize) -> Self {
+        Segment { mode: mode.into(), length, bits: segment_bits(mode, length, version) }
+    }
+
+    /// `length` Kanji characters: 13 bits each, after a count of 8, 10 or
+    /// 12 bits
+    pub(crate) fn kanji(length: usize, version: usize) -> Self {
+        let count = match version {
+            1..=9 => 8,
+            10..=26 => 10,
+            _ => 12,
+        };
+        Segment { mode: SegmentMode::Kanji, length, bits: 4 + count + 13 * length }
+    }
+
+    /// `"numeric"`, `"alphanumeric"`, `"byte"` or `"kanji"`
+    pub fn mode_name(&self) -> &'static str {
+        self.mode.as_str()
+    }
+}
+
+impl QrCode {
+    /// Segments the payload was encoded as, in order
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+}
+
+/// Rewrite suggested by `optimize_payload`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadHintKind {
+    /// Uppercase the URL so it encodes in alphanumeric mode. Scheme and
+    /// host ignore case; paths and queries may not.
+    Uppercase,
+    /// Drop `http://` / `https://`. Most scanners still offer to open a
+    /// bare host, but some treat it as text.
+    StripScheme,
+}
+
+impl PayloadHintKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Uppercase => "uppercase",
+            Self::StripScheme => "stripScheme",
+        }
+    }
+}
+
+/// A smaller encoding of the same payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadHint {
+    pub kind: PayloadHintKind,
+    /// The payload rewritten
+    pub text: String,
+    /// Whether scanners are certain to treat `text` like the original
+    pub lossless: bool,
+    /// Fewer bits in the data segment
+    pub bits_saved: usize,
+    /// Fewer modules in the symbol; 0 when both fit the same version
+    pub modules_saved: usize,
+    /// Version `text` needs at the given level, if it fits at all
+    pub version: Option<usize>,
+}
+
+/// Rewrites of `text` that encode in fewer bits at `ecl`
+///
+/// Only http(s) URLs are rewritten; other payloads return no hints. Each
+/// hint applies one change to `text`, so run again on a chosen hint's text
+/// to see what else applies.
+pub fn optimize_payload(text: &str, ecl: ErrorCorrectionLevel) -> Vec<PayloadHint> {
+    let PayloadKind::Url { scheme, .. } = classify_payload(text).kind else {
+        return Vec::new();
+    };
+    if scheme != "http" && scheme != "https" {
+        return Vec::new();
+    }
+    let trimmed = text.trim();
+    let Some(rest) = trimmed[scheme.len() + 1..].strip_prefix("//") else {
+        return Vec::new();
+    };
+
+    let mut candidates = Vec::new();
+    let upper = trimmed.to_ascii_uppercase();
+    if best_mode(upper.as_bytes()) == Mode::Alphanumeric {
+        // Safe when nothing case-sensitive follows the host
+        let lossless = !rest.trim_end_matches('/').contains('/');
+        candidates.push((PayloadHintKind::Uppercase, upper, lossless));
+    }
+    candidates.push((PayloadHintKind::StripScheme, rest.to_string(), false));
+
+    let cost = |s: &str| {
+        let mode = best_mode(s.as_bytes());
+        let version = min_version(mode, s.len(), ecl);
+        (segment_bits(mode, s.len(), version.unwrap_or(40)), version)
+    };
+    let modules = |version: usize| (17 + 4 * version).pow(2);
+    let (bits, version) = cost(text);
+    candidates
+        .into_iter()
+        .filter_map(|(kind, hinted, lossless)| {
+            let (hinted_bits, hinted_version) = cost(&hinted);
+            let modules_saved = match (version, hinted_version) {
+                (Some(before), Some(after)) => modules(before).saturating_sub(modules(after)),
+                _ => 0,
+            };
+            (hinted_bits < bits).then(|| PayloadHint {
+                kind,
+                text: hinted,
+                lossless,
+                bits_saved: bits - hinted_bits,
+                modules_saved,
+                version: hinted_version,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_qr;
+
+    #[test]
+    fn test_segments_match_generated_symbol() {
+        let cases = [("0123456789", Mode::Numeric), ("HOLI.TOOLS/QR", Mode::Alphanumeric), ("holi.tools", Mode::Byte)];
+        for (text, mode) in cases {
+            let qr = generate_qr(text, ErrorCorrectionLevel::Medium).unwrap();
+            let segments = qr.segments();
+            assert_eq!(segments.len(), 1);
+            assert_eq!((segments[0].mode, segments[0].length), (mode.into(), text.len()));
+            let version = (qr.size() - 17) / 4;
+            assert!(segments[0].bits <= crate::qr::data_codewords(version, ErrorCorrectionLevel::Medium) * 8);
+        }
+        // 4 mode + 10 count + 3 * 10 data bits
+        let qr = generate_qr("123456789", ErrorCorrectionLevel::Low).unwrap();
+        assert_eq!(qr.segments()[0].bits, 44);
+    }
+
+    #[test]
+    fn test_url_hints() {
+        let hints = optimize_payload("https://holi.tools/", ErrorCorrectionLevel::Medium);
+        let kinds: Vec<_> = hints.iter().map(|h| h.kind).collect();
+        assert_eq!(kinds, [PayloadHintKind::Uppercase, PayloadHintKind::StripScheme]);
+        assert_eq!(hints[0].text, "HTTPS://HOLI.TOOLS/");
+        assert!(hints[0].lossless);
+        assert_eq!(hints[1].text, "holi.tools/");
+        assert!(!hints[1].lossless);
+        assert!(hints.iter().all(|h| h.bits_saved > 0));
+
+        // A case-sensitive path makes uppercasing lossy
+        let long = format!("https://holi.tools/share/{}", "abcdefgh".repeat(8));
+        let hints = optimize_payload(&long, ErrorCorrectionLevel::Medium);
+        assert!(!hints[0].lossless);
+        assert!(hints[0].modules_saved > 0, "{:?}", hints[0]);
+
+        // Characters outside the alphanumeric set rule out uppercasing
+        let hints = optimize_payload("https://holi.tools/?q=1", ErrorCorrectionLevel::Medium);
+        assert_eq!(hints.iter().map(|h| h.kind).collect::<Vec<_>>(), [PayloadHintKind::StripScheme]);
+
+        assert!(optimize_payload("hello world", ErrorCorrectionLevel::Medium).is_empty());
+        assert!(optimize_payload("mailto:ada@example.com", ErrorCorrectionLevel::Medium).is_empty());
+        assert!(optimize_payload("http:", ErrorCorrectionLevel::Medium).is_empty());
+    }
+}
@@ -5,6 +5,7 @@
 //! - Eye frame shapes (outer finder pattern frames)
 //! - Eye ball shapes (inner finder pattern centers)
 
+use crate::error::QrError;
 use std::fmt::Write;
 
 /// Body shape types for data modules
@@ -31,6 +32,8 @@ pub enum BodyShape {
     TinyDots,
     Hash,
     Leaf,
+    /// Orthogonal neighbors merge into one rounded blob; see `liquid_body_path`
+    Liquid,
 }
 
 /// Eye frame shape types (outer 7x7 finder pattern)
@@ -70,7 +73,32 @@ pub enum EyeBallShape {
 }
 
 impl BodyShape {
+    pub const ALL: [BodyShape; 21] = [
+        Self::Square,
+        Self::Rounded,
+        Self::Dots,
+        Self::Diamond,
+        Self::Star,
+        Self::Classy,
+        Self::ClassyRounded,
+        Self::Arrow,
+        Self::ArrowLeft,
+        Self::Heart,
+        Self::Hexagon,
+        Self::Octagon,
+        Self::Cross,
+        Self::Plus,
+        Self::Blob,
+        Self::Clover,
+        Self::MiniSquare,
+        Self::TinyDots,
+        Self::Hash,
+        Self::Leaf,
+        Self::Liquid,
+    ];
+
     /// Parse from string (for WASM/JSON interop)
+    #[allow(clippy::should_implement_trait)] // Infallible: unknown names fall back to Square.
     pub fn from_str(s: &str) -> Self {
         match s.to_lowercase().as_str() {
             "square" => Self::Square,
@@ -93,12 +121,105 @@ impl BodyShape {
             "tiny-dots" | "tinydots" => Self::TinyDots,
             "hash" => Self::Hash,
             "leaf" => Self::Leaf,
+            "liquid" | "connected" => Self::Liquid,
             _ => Self::Square,
         }
     }
+
+    /// Name as accepted by `from_str`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Square => "square",
+            Self::Rounded => "rounded",
+            Self::Dots => "dots",
+            Self::Diamond => "diamond",
+            Self::Star => "star",
+            Self::Classy => "classy",
+            Self::ClassyRounded => "classy-rounded",
+            Self::Arrow => "arrow",
+            Self::ArrowLeft => "arrow-left",
+            Self::Heart => "heart",
+            Self::Hexagon => "hexagon",
+            Self::Octagon => "octagon",
+            Self::Cross => "cross",
+            Self::Plus => "plus",
+            Self::Blob => "blob",
+            Self::Clover => "clover",
+            Self::MiniSquare => "mini-square",
+            Self::TinyDots => "tiny-dots",
+            Self::Hash => "hash",
+            Self::Leaf => "leaf",
+            Self::Liquid => "liquid",
+        }
+    }
+
+    /// Approximate share of the module cell the shape covers (0.0-1.0)
+    ///
+    /// Scanners sample module centers and run lengths; low coverage makes
+    /// dark modules look light at small sizes.
+    pub fn coverage(&self) -> f64 {
+        match self {
+            Self::Square | Self::Liquid => 1.0,
+            Self::Rounded | Self::ClassyRounded => 0.99,
+            Self::Classy => 0.97,
+            Self::Octagon => 0.82,
+            Self::Hexagon => 0.8,
+            Self::Clover => 0.76,
+            Self::Plus => 0.75,
+            Self::Blob | Self::Leaf => 0.67,
+            Self::Dots | Self::Cross => 0.64,
+            Self::Hash => 0.56,
+            Self::Arrow | Self::ArrowLeft => 0.55,
+            Self::Diamond => 0.5,
+            Self::Heart => 0.41,
+            Self::MiniSquare => 0.36,
+            Self::Star => 0.3,
+            Self::TinyDots => 0.28,
+        }
+    }
+
+    /// Longest `body_path` of one module in bytes, with 3-digit coordinates
+    ///
+    /// Used to size SVG buffers up front. Liquid and contour outlines
+    /// come out shorter than this per module.
+    pub fn path_cost(&self) -> usize {
+        match self {
+            Self::Square => 16,
+            Self::MiniSquare => 26,
+            Self::Classy => 40,
+            Self::Diamond => 45,
+            Self::Arrow | Self::ArrowLeft => 54,
+            Self::Liquid => 59,
+            Self::TinyDots => 63,
+            Self::Hexagon => 67,
+            Self::Dots => 68,
+            Self::Cross => 72,
+            Self::Plus => 80,
+            Self::Octagon => 89,
+            Self::Rounded | Self::ClassyRounded => 91,
+            Self::Star => 105,
+            Self::Heart | Self::Blob | Self::Leaf => 127,
+            Self::Hash => 165,
+            Self::Clover => 288,
+        }
+    }
 }
 
 impl EyeFrameShape {
+    pub const ALL: [EyeFrameShape; 10] = [
+        Self::Square,
+        Self::Circle,
+        Self::Rounded,
+        Self::Leaf,
+        Self::Cushion,
+        Self::Double,
+        Self::Fancy,
+        Self::DotsSquare,
+        Self::HeavyRounded,
+        Self::CloverFrame,
+    ];
+
+    #[allow(clippy::should_implement_trait)] // Infallible: unknown names fall back to Square.
     pub fn from_str(s: &str) -> Self {
         match s.to_lowercase().as_str() {
             "square" => Self::Square,
@@ -114,9 +235,43 @@ impl EyeFrameShape {
             _ => Self::Square,
         }
     }
+
+    /// Name as accepted by `from_str`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Square => "square",
+            Self::Circle => "circle",
+            Self::Rounded => "rounded",
+            Self::Leaf => "leaf",
+            Self::Cushion => "cushion",
+            Self::Double => "double",
+            Self::Fancy => "fancy",
+            Self::DotsSquare => "dots-square",
+            Self::HeavyRounded => "heavy-rounded",
+            Self::CloverFrame => "clover-frame",
+        }
+    }
 }
 
 impl EyeBallShape {
+    pub const ALL: [EyeBallShape; 14] = [
+        Self::Square,
+        Self::Circle,
+        Self::Diamond,
+        Self::Rounded,
+        Self::Star,
+        Self::Heart,
+        Self::Hexagon,
+        Self::BarsH,
+        Self::BarsV,
+        Self::DotsGrid,
+        Self::Flower,
+        Self::Clover,
+        Self::Cushion,
+        Self::Octagon,
+    ];
+
+    #[allow(clippy::should_implement_trait)] // Infallible: unknown names fall back to Square.
     pub fn from_str(s: &str) -> Self {
         match s.to_lowercase().as_str() {
             "square" => Self::Square,
@@ -136,25 +291,98 @@ impl EyeBallShape {
             _ => Self::Square,
         }
     }
+
+    /// Name as accepted by `from_str`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Square => "square",
+            Self::Circle => "circle",
+            Self::Diamond => "diamond",
+            Self::Rounded => "rounded",
+            Self::Star => "star",
+            Self::Heart => "heart",
+            Self::Hexagon => "hexagon",
+            Self::BarsH => "bars-h",
+            Self::BarsV => "bars-v",
+            Self::DotsGrid => "dots-grid",
+            Self::Flower => "flower",
+            Self::Clover => "clover",
+            Self::Cushion => "cushion",
+            Self::Octagon => "octagon",
+        }
+    }
+}
+
+/// Strict parsing: like `from_str`, but unknown names are an error
+impl TryFrom<&str> for BodyShape {
+    type Error = QrError;
+
+    fn try_from(s: &str) -> Result<Self, QrError> {
+        match Self::from_str(s) {
+            Self::Square if !s.eq_ignore_ascii_case("square") => {
+                Err(QrError::InvalidArgument(format!("Unknown body shape: {:?}", s)))
+            }
+            shape => Ok(shape),
+        }
+    }
+}
+
+/// Strict parsing: like `from_str`, but unknown names are an error
+impl TryFrom<&str> for EyeFrameShape {
+    type Error = QrError;
+
+    fn try_from(s: &str) -> Result<Self, QrError> {
+        match Self::from_str(s) {
+            Self::Square if !s.eq_ignore_ascii_case("square") => {
+                Err(QrError::InvalidArgument(format!("Unknown eye frame shape: {:?}", s)))
+            }
+            shape => Ok(shape),
+        }
+    }
+}
+
+/// Strict parsing: like `from_str`, but unknown names are an error
+impl TryFrom<&str> for EyeBallShape {
+    type Error = QrError;
+
+    fn try_from(s: &str) -> Result<Self, QrError> {
+        match Self::from_str(s) {
+            Self::Square if !s.eq_ignore_ascii_case("square") => {
+                Err(QrError::InvalidArgument(format!("Unknown eye ball shape: {:?}", s)))
+            }
+            shape => Ok(shape),
+        }
+    }
 }
 
 /// Generate SVG path for a body module at position (px, py)
 /// Module size is 1x1
 pub fn body_path(shape: BodyShape, px: f64, py: f64) -> String {
+    let mut path = String::with_capacity(shape.path_cost());
+    // Writing to a String can't fail
+    let _ = push_body_path(&mut path, shape, px, py);
+    path
+}
+
+/// `body_path` appended to `out`, for renderers assembling one large path
+pub(crate) fn push_body_path(out: &mut String, shape: BodyShape, px: f64, py: f64) -> std::fmt::Result {
     match shape {
-        BodyShape::Square => format!("M{},{}h1v1h-1z", px, py),
+        BodyShape::Square => write!(out, "M{},{}h1v1h-1z", px, py),
         
-        BodyShape::Rounded => format!(
+        BodyShape::Rounded => write!(
+            out,
             "M{},{}h0.8q0.1,0 0.1,0.1v0.8q0,0.1 -0.1,0.1h-0.8q-0.1,0 -0.1,-0.1v-0.8q0,-0.1 0.1,-0.1z",
             px + 0.1, py
         ),
         
-        BodyShape::Dots => format!(
+        BodyShape::Dots => write!(
+            out,
             "M{},{} m-0.45,0 a0.45,0.45 0 1,0 0.9,0 a0.45,0.45 0 1,0 -0.9,0",
             px + 0.5, py + 0.5
         ),
         
-        BodyShape::Diamond => format!(
+        BodyShape::Diamond => write!(
+            out,
             "M{},{} L{},{} L{},{} L{},{} Z",
             px + 0.5, py,
             px + 1.0, py + 0.5,
@@ -162,7 +390,8 @@ pub fn body_path(shape: BodyShape, px: f64, py: f64) -> String {
             px, py + 0.5
         ),
         
-        BodyShape::Star => format!(
+        BodyShape::Star => write!(
+            out,
             "M{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} Z",
             px + 0.5, py,
             px + 0.65, py + 0.35,
@@ -174,27 +403,32 @@ pub fn body_path(shape: BodyShape, px: f64, py: f64) -> String {
             px + 0.35, py + 0.35
         ),
         
-        BodyShape::Classy => format!(
+        BodyShape::Classy => write!(
+            out,
             "M{},{} h1 v0.6 q0,0.4 -0.4,0.4 h-0.6 Z",
             px, py
         ),
         
-        BodyShape::ClassyRounded => format!(
+        BodyShape::ClassyRounded => write!(
+            out,
             "M{},{}h0.8q0.1,0 0.1,0.1v0.8q0,0.1 -0.1,0.1h-0.8q-0.1,0 -0.1,-0.1v-0.8q0,-0.1 0.1,-0.1z",
             px + 0.1, py
         ),
         
-        BodyShape::Arrow => format!(
+        BodyShape::Arrow => write!(
+            out,
             "M{},{} h0.5 v-0.2 l0.5,0.5 l-0.5,0.5 v-0.2 h-0.5 Z",
             px, py + 0.2
         ),
         
-        BodyShape::ArrowLeft => format!(
+        BodyShape::ArrowLeft => write!(
+            out,
             "M{},{} h-0.5 v-0.2 l-0.5,0.5 l0.5,0.5 v-0.2 h0.5 Z",
             px + 1.0, py + 0.2
         ),
         
-        BodyShape::Heart => format!(
+        BodyShape::Heart => write!(
+            out,
             "M{},{} L{},{} Q{},{} {},{} Q{},{} {},{} Q{},{} {},{} Q{},{} {},{} Z",
             px + 0.5, py + 0.9,
             px + 0.1, py + 0.5,
@@ -204,7 +438,8 @@ pub fn body_path(shape: BodyShape, px: f64, py: f64) -> String {
             px + 1.0, py + 0.2, px + 0.9, py + 0.5
         ),
         
-        BodyShape::Hexagon => format!(
+        BodyShape::Hexagon => write!(
+            out,
             "M{},{} L{},{} L{},{} L{},{} L{},{} L{},{} Z",
             px + 0.2, py,
             px + 0.8, py,
@@ -214,7 +449,8 @@ pub fn body_path(shape: BodyShape, px: f64, py: f64) -> String {
             px, py + 0.5
         ),
         
-        BodyShape::Octagon => format!(
+        BodyShape::Octagon => write!(
+            out,
             "M{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} Z",
             px + 0.3, py,
             px + 0.7, py,
@@ -226,17 +462,20 @@ pub fn body_path(shape: BodyShape, px: f64, py: f64) -> String {
             px, py + 0.3
         ),
         
-        BodyShape::Cross => format!(
+        BodyShape::Cross => write!(
+            out,
             "M{},{} h0.4 v0.3 h0.3 v0.4 h-0.3 v0.3 h-0.4 v-0.3 h-0.3 v-0.4 h0.3 Z",
             px + 0.3, py
         ),
         
-        BodyShape::Plus => format!(
+        BodyShape::Plus => write!(
+            out,
             "M{},{} h0.5 v0.25 h0.25 v0.5 h-0.25 v0.25 h-0.5 v-0.25 h-0.25 v-0.5 h0.25 Z",
             px + 0.25, py
         ),
         
-        BodyShape::Blob => format!(
+        BodyShape::Blob => write!(
+            out,
             "M{},{} Q{},{} {},{} Q{},{} {},{} Q{},{} {},{} Q{},{} {},{} Z",
             px + 0.5, py + 0.05,
             px + 0.95, py + 0.05, px + 0.95, py + 0.5,
@@ -245,22 +484,19 @@ pub fn body_path(shape: BodyShape, px: f64, py: f64) -> String {
             px + 0.05, py + 0.05, px + 0.5, py + 0.05
         ),
         
-        BodyShape::Clover => {
-            let mut s = String::new();
-            // 4 circles forming a clover (Thicker r=0.30)
-            for (dx, dy) in [(0.5, 0.25), (0.75, 0.5), (0.5, 0.75), (0.25, 0.5)] {
-                write!(
-                    s,
-                    "M{},{} m-0.30,0 a0.30,0.30 0 1,0 0.60,0 a0.30,0.30 0 1,0 -0.60,0 ",
-                    px + dx, py + dy
-                ).unwrap();
-            }
-            s
-        },
+        // 4 circles forming a clover (Thicker r=0.30)
+        BodyShape::Clover => [(0.5, 0.25), (0.75, 0.5), (0.5, 0.75), (0.25, 0.5)]
+            .iter()
+            .try_for_each(|(dx, dy)| write!(
+                out,
+                "M{},{} m-0.30,0 a0.30,0.30 0 1,0 0.60,0 a0.30,0.30 0 1,0 -0.60,0 ",
+                px + dx, py + dy
+            )),
         
-        BodyShape::MiniSquare => format!("M{},{}h0.6v0.6h-0.6z", px + 0.2, py + 0.2),
+        BodyShape::MiniSquare => write!(out, "M{},{}h0.6v0.6h-0.6z", px + 0.2, py + 0.2),
         
-        BodyShape::TinyDots => format!(
+        BodyShape::TinyDots => write!(
+            out,
             "M{},{} m-0.3,0 a0.3,0.3 0 1,0 0.6,0 a0.3,0.3 0 1,0 -0.6,0",
             px + 0.5, py + 0.5
         ),
@@ -269,13 +505,15 @@ pub fn body_path(shape: BodyShape, px: f64, py: f64) -> String {
              // Explicit 12-point Polygon (Thickness 0.4, Width 0.9)
              let x1 = px+0.05; let x2 = px+0.3; let x3 = px+0.7; let x4 = px+0.95;
              let y1 = py+0.05; let y2 = py+0.3; let y3 = py+0.7; let y4 = py+0.95;
-             format!(
+             write!(
+                 out,
                  "M{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} L{},{} Z",
                  x2, y1, x3, y1, x3, y2, x4, y2, x4, y3, x3, y3, x3, y4, x2, y4, x2, y3, x1, y3, x1, y2, x2, y2
              )
         },
         
-        BodyShape::Leaf => format!(
+        BodyShape::Leaf => write!(
+            out,
             "M{},{} Q{},{} {},{} Q{},{} {},{} Q{},{} {},{} Q{},{} {},{} Z",
             px + 0.5, py + 0.05,
             px + 0.95, py + 0.05, px + 0.95, py + 0.5,
@@ -283,7 +521,162 @@ pub fn body_path(shape: BodyShape, px: f64, py: f64) -> String {
             px + 0.05, py + 0.95, px + 0.05, py + 0.5,
             px + 0.05, py + 0.05, px + 0.5, py + 0.05
         ),
+
+        // A lone module; connected ones need `liquid_body_path`
+        BodyShape::Liquid => write!(
+            out,
+            "M{},{} m-0.5,0 a0.5,0.5 0 1,0 1,0 a0.5,0.5 0 1,0 -1,0",
+            px + 0.5, py + 0.5
+        ),
+    }
+}
+
+/// Radius of outside corners in `liquid_body_path`
+const LIQUID_CONVEX_RADIUS: f64 = 0.5;
+/// Radius of the fillet filling inside corners in `liquid_body_path`
+const LIQUID_CONCAVE_RADIUS: f64 = 0.3;
+
+/// Unit steps for the four directions: right, down, left, up (screen coordinates)
+const STEPS: [(i64, i64); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// A corner of a traced outline: grid vertex, direction in, direction out
+type Corner = (i64, i64, usize, usize);
+
+/// Largest matrix `liquid_body_path` and `contour_body_path` trace: a
+/// version 40 symbol with a `MAX_MARGIN` quiet zone on each side
+const MAX_TRACE_SIZE: usize = 177 + 2 * crate::render::MAX_MARGIN;
+
+/// Outlines of the dark clusters of a `size`x`size` matrix
+///
+/// Each outline runs clockwise around dark cells (holes counter-clockwise),
+/// so the loops fill correctly with the default nonzero rule. Cells touching
+/// only diagonally end up in separate outlines.
+fn trace_contours(size: usize, is_dark: impl Fn(usize, usize) -> bool) -> Result<Vec<Vec<Corner>>, QrError> {
+    if size > MAX_TRACE_SIZE {
+        return Err(QrError::InvalidArgument(format!(
+            "Matrix of {} modules is over the {} that can be traced",
+            size, MAX_TRACE_SIZE
+        )));
+    }
+    // Read each cell once: a closure answering differently on a second call
+    // would leave edges that don't close
+    let cells: Vec<bool> = (0..size * size).map(|i| is_dark(i % size, i / size)).collect();
+    let dark = |x: i64, y: i64| x >= 0 && y >= 0 && (x as usize) < size && (y as usize) < size && cells[y as usize * size + x as usize];
+
+    // Boundary edges, clockwise around dark cells: a bitmask of outgoing
+    // directions per grid vertex
+    let side = size + 1;
+    let mut edges = vec![0u8; side * side];
+    let vertex = |x: i64, y: i64| y as usize * side + x as usize;
+    for y in 0..size as i64 {
+        for x in 0..size as i64 {
+            if !dark(x, y) { continue; }
+            if !dark(x, y - 1) { edges[vertex(x, y)] |= 1 << 0; }
+            if !dark(x + 1, y) { edges[vertex(x + 1, y)] |= 1 << 1; }
+            if !dark(x, y + 1) { edges[vertex(x + 1, y + 1)] |= 1 << 2; }
+            if !dark(x - 1, y) { edges[vertex(x, y + 1)] |= 1 << 3; }
+        }
     }
+    let broken = || QrError::RenderFailed("Traced outline does not close".into());
+
+    let mut contours = Vec::new();
+    for start in 0..edges.len() {
+        while edges[start] != 0 {
+            let (sx, sy) = ((start % side) as i64, (start / side) as i64);
+            let start_dir = edges[start].trailing_zeros() as usize;
+
+            // Walk the loop, collecting the corners
+            let mut corners = Vec::new();
+            let (mut x, mut y, mut dir) = (sx, sy, start_dir);
+            loop {
+                edges[vertex(x, y)] &= !(1 << dir);
+                x += STEPS[dir].0;
+                y += STEPS[dir].1;
+                let mut available = edges[vertex(x, y)];
+                if (x, y) == (sx, sy) {
+                    available |= 1 << start_dir;
+                }
+                // Prefer turning right (into the cell): at a diagonal touch
+                // this keeps the two cells apart
+                let out = [(dir + 1) % 4, dir, (dir + 3) % 4]
+                    .into_iter()
+                    .find(|d| available & (1 << d) != 0)
+                    .ok_or_else(broken)?;
+                if out != dir {
+                    corners.push((x, y, dir, out));
+                }
+                if (x, y) == (sx, sy) && out == start_dir {
+                    break;
+                }
+                dir = out;
+            }
+            contours.push(corners);
+        }
+    }
+    Ok(contours)
+}
+
+/// Generate the connected ("liquid") body path for a `size`x`size` matrix
+///
+/// Dark modules sharing an edge are merged: the outline of each cluster is
+/// traced once, outside corners are rounded and inside corners get a fillet,
+/// so there are no seams between modules. Modules touching only diagonally
+/// stay separate. `offset` is added to both coordinates (the quiet zone).
+pub fn liquid_body_path(size: usize, offset: f64, is_dark: impl Fn(usize, usize) -> bool) -> Result<String, QrError> {
+    let mut path = String::new();
+    for corners in trace_contours(size, is_dark)? {
+        push_liquid_loop(&mut path, &corners, offset)?;
+    }
+    Ok(path)
+}
+
+/// Generate the outer contours of connected dark modules, with square corners
+///
+/// Same outlines as `liquid_body_path` without the rounding; meant to be
+/// stroked (engraving, coloring-book exports) rather than filled, where
+/// per-module paths would draw the shared edges too.
+pub fn contour_body_path(size: usize, offset: f64, is_dark: impl Fn(usize, usize) -> bool) -> Result<String, QrError> {
+    let mut path = String::new();
+    for corners in trace_contours(size, is_dark)? {
+        // The last corner is where the trace started
+        let start = corners.len().saturating_sub(1);
+        for (i, &(x, y, _, _)) in corners[start..].iter().chain(&corners[..start]).enumerate() {
+            let command = if i == 0 { 'M' } else { 'L' };
+            write!(path, "{}{},{}", command, x as f64 + offset, y as f64 + offset)?;
+        }
+        path.push('z');
+    }
+    Ok(path)
+}
+
+/// Append one traced outline, rounding each corner with an arc
+fn push_liquid_loop(path: &mut String, corners: &[Corner], offset: f64) -> Result<(), QrError> {
+    let radius = |dir_in: usize, dir_out: usize| {
+        if dir_out == (dir_in + 1) % 4 { LIQUID_CONVEX_RADIUS } else { LIQUID_CONCAVE_RADIUS }
+    };
+    let point = |x: i64, y: i64, dir: usize, distance: f64| {
+        (x as f64 + offset + STEPS[dir].0 as f64 * distance, y as f64 + offset + STEPS[dir].1 as f64 * distance)
+    };
+
+    let Some(&(x, y, dir_in, dir_out)) = corners.last() else { return Ok(()) };
+    let (mx, my) = point(x, y, dir_out, radius(dir_in, dir_out));
+    write!(path, "M{},{}", mx, my)?;
+    let mut last = (mx, my);
+    for &(x, y, dir_in, dir_out) in corners {
+        let r = radius(dir_in, dir_out);
+        let (bx, by) = point(x, y, dir_in, -r);
+        let (ax, ay) = point(x, y, dir_out, r);
+        // Arcs of adjacent corners often meet on a unit edge
+        if (bx, by) != last {
+            write!(path, "L{},{}", bx, by)?;
+        }
+        // Clockwise sweep for outside corners, counter-clockwise for fillets
+        let sweep = u8::from(dir_out == (dir_in + 1) % 4);
+        write!(path, "A{},{} 0 0,{} {},{}", r, r, sweep, ax, ay)?;
+        last = (ax, ay);
+    }
+    path.push('z');
+    Ok(())
 }
 
 /// Generate SVG path for eye frame at position (fx, fy)
@@ -437,58 +830,38 @@ pub fn eye_ball_path(shape: EyeBallShape, bx: f64, by: f64) -> String {
             bx + 2.05, by
         ),
         
-        EyeBallShape::DotsGrid => {
-            let mut s = String::new();
-            for row in 0..3 {
-                for col in 0..3 {
-                    let cx = bx + 0.5 + col as f64;
-                    let cy = by + 0.5 + row as f64;
-                    write!(
-                        s,
-                        "M{},{} a0.45,0.45 0 1,1 -0.9,0 a0.45,0.45 0 1,1 0.9,0 ",
-                        cx + 0.45, cy
-                    ).unwrap();
-                }
-            }
-            s
-        },
+        EyeBallShape::DotsGrid => (0..9)
+            .map(|i| {
+                let cx = bx + 0.5 + (i % 3) as f64;
+                let cy = by + 0.5 + (i / 3) as f64;
+                format!("M{},{} a0.45,0.45 0 1,1 -0.9,0 a0.45,0.45 0 1,1 0.9,0 ", cx + 0.45, cy)
+            })
+            .collect(),
         
         EyeBallShape::Flower => {
-            let mut s = String::new();
             // 4 petals + center
-            for (dx, dy) in [(1.5, 0.2), (2.8, 1.5), (1.5, 2.8), (0.2, 1.5)] {
-                write!(
-                    s,
-                    "M{},{} m-0.7,0 a0.7,0.7 0 1,0 1.4,0 a0.7,0.7 0 1,0 -1.4,0 ",
-                    bx + dx, by + dy
-                ).unwrap();
-            }
-            // Center
-            write!(
-                s,
+            let petals = [(1.5, 0.2), (2.8, 1.5), (1.5, 2.8), (0.2, 1.5)].iter().map(|(dx, dy)| format!(
+                "M{},{} m-0.7,0 a0.7,0.7 0 1,0 1.4,0 a0.7,0.7 0 1,0 -1.4,0 ",
+                bx + dx, by + dy
+            ));
+            let center = format!(
                 "M{},{} m-0.6,0 a0.6,0.6 0 1,0 1.2,0 a0.6,0.6 0 1,0 -1.2,0 ",
                 bx + 1.5, by + 1.5
-            ).unwrap();
-            s
+            );
+            petals.chain(std::iter::once(center)).collect()
         },
         
         EyeBallShape::Clover => {
-            let mut s = String::new();
             // Add Center Mass
-            write!(
-                s,
+            let center = format!(
                 "M{},{} m-0.7,0 a0.7,0.7 0 1,0 1.4,0 a0.7,0.7 0 1,0 -1.4,0 ",
                 bx + 1.5, by + 1.5
-            ).unwrap();
-            
-            for (dx, dy) in [(1.5, 0.6), (2.4, 1.5), (1.5, 2.4), (0.6, 1.5)] {
-                write!(
-                    s,
-                    "M{},{} m-0.6,0 a0.6,0.6 0 1,0 1.2,0 a0.6,0.6 0 1,0 -1.2,0 ",
-                    bx + dx, by + dy
-                ).unwrap();
-            }
-            s
+            );
+            let leaves = [(1.5, 0.6), (2.4, 1.5), (1.5, 2.4), (0.6, 1.5)].iter().map(|(dx, dy)| format!(
+                "M{},{} m-0.6,0 a0.6,0.6 0 1,0 1.2,0 a0.6,0.6 0 1,0 -1.2,0 ",
+                bx + dx, by + dy
+            ));
+            std::iter::once(center).chain(leaves).collect()
         },
         
         EyeBallShape::Cushion => format!(
@@ -534,6 +907,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_path_cost_covers_largest_codes() {
+        // Version 40 with the widest margin
+        let last = (177 + 2 * crate::render::MAX_MARGIN - 1) as f64;
+        for shape in BodyShape::ALL {
+            for (x, y) in [(0.0, 0.0), (97.0, 113.0), (last, last)] {
+                assert!(body_path(shape, x, y).len() <= shape.path_cost(), "{:?} at {},{}", shape, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_liquid_merges_neighbors() {
+        let grid = |cells: &'static [(usize, usize)]| move |x, y| cells.contains(&(x, y));
+
+        // A lone module is a circle: four rounded corners, no straight runs
+        let lone = liquid_body_path(3, 0.0, grid(&[(1, 1)])).unwrap();
+        assert_eq!(lone.matches('M').count(), 1);
+        assert_eq!(lone.matches("0 0,1").count(), 4);
+        assert!(!lone.contains('L'));
+
+        // An L-shaped cluster is one outline with a single fillet
+        let l_shape = liquid_body_path(3, 4.0, grid(&[(0, 0), (1, 0), (0, 1)])).unwrap();
+        assert_eq!(l_shape.matches('M').count(), 1);
+        assert_eq!(l_shape.matches("0 0,1").count(), 5);
+        assert_eq!(l_shape.matches("0 0,0").count(), 1);
+        assert!(l_shape.starts_with("M4.5,4"));
+
+        // Diagonal neighbors stay separate shapes
+        let diagonal = liquid_body_path(2, 0.0, grid(&[(0, 0), (1, 1)])).unwrap();
+        assert_eq!(diagonal.matches('M').count(), 2);
+
+        // A ring keeps its hole: outer and inner outlines
+        let ring: &'static [(usize, usize)] = &[(0, 0), (1, 0), (2, 0), (0, 1), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let ring = liquid_body_path(3, 0.0, grid(ring)).unwrap();
+        assert_eq!(ring.matches('M').count(), 2);
+        assert_eq!(ring.matches("0 0,0").count(), 4);
+
+        assert!(liquid_body_path(3, 0.0, |_, _| false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_contour_outlines_clusters() {
+        // Two modules side by side: one rectangle, no shared edge
+        let pair = contour_body_path(3, 1.0, |x, y| y == 1 && x < 2).unwrap();
+        assert_eq!(pair, "M1,2L3,2L3,3L1,3z");
+
+        // An L-shaped cluster has six corners
+        let l_shape = contour_body_path(2, 0.0, |x, y| (x, y) != (1, 1)).unwrap();
+        assert_eq!(l_shape.matches(['M', 'L']).count(), 6);
+    }
+
     #[test]
     fn test_eye_frame_paths_valid() {
         let shapes = [
@@ -548,6 +973,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_shape_names_round_trip() {
+        for shape in BodyShape::ALL {
+            assert_eq!(BodyShape::from_str(shape.as_str()), shape);
+        }
+        for shape in EyeFrameShape::ALL {
+            assert_eq!(EyeFrameShape::from_str(shape.as_str()), shape);
+        }
+        for shape in EyeBallShape::ALL {
+            assert_eq!(EyeBallShape::from_str(shape.as_str()), shape);
+            assert_eq!(EyeBallShape::try_from(shape.as_str()).unwrap(), shape);
+        }
+    }
+
+    #[test]
+    fn test_strict_parsing() {
+        assert_eq!(BodyShape::try_from("Classy-Rounded").unwrap(), BodyShape::ClassyRounded);
+        assert_eq!(BodyShape::try_from("SQUARE").unwrap(), BodyShape::Square);
+        assert_eq!(BodyShape::try_from("connected").unwrap(), BodyShape::Liquid);
+        assert!(matches!(BodyShape::try_from("dotz"), Err(QrError::InvalidArgument(_))));
+        assert!(EyeFrameShape::try_from("").is_err());
+        assert!(EyeBallShape::try_from("flowr").is_err());
+        assert_eq!(BodyShape::from_str("dotz"), BodyShape::Square);
+    }
+
     #[test]
     fn test_eye_ball_paths_valid() {
         let shapes = [
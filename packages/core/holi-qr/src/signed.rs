@@ -0,0 +1,158 @@
+//! Signed QR payloads
+//!
+//! Tamper-evident codes for posters and tickets, verifiable fully offline.
+//! The byte-mode payload is `content || signature (64) || key id (8)`.
+//!
+//! This crate only knows the layout and key ids; signing and verification
+//! are supplied by the caller (see `holi-wasm-crypto`, `holi_wasm_core`).
+
+use sha2::{Digest, Sha256};
+
+use crate::error::QrError;
+use crate::qr::{generate_qr_bytes, ErrorCorrectionLevel, QrCode};
+
+pub const SIGNATURE_LEN: usize = 64;
+pub const KEY_ID_LEN: usize = 8;
+/// Bytes appended to the content.
+pub const SIGNED_TRAILER_LEN: usize = SIGNATURE_LEN + KEY_ID_LEN;
+
+/// Version of the signed payload layout; bump together with the context.
+pub const SIGNED_QR_VERSION: u32 = 1;
+/// Domain separator prepended to the content before signing.
+const SIGNED_QR_CONTEXT: &[u8] = b"holi-signed-qr-v1";
+/// Domain of the public key fingerprint digest.
+const FINGERPRINT_DOMAIN: &[u8] = b"holi-fp-v1";
+
+/// Fingerprint digest of a public key: `SHA-256("holi-fp-v1" || public_key)`.
+///
+/// The one definition shared with the fingerprints users compare on screen
+/// (`holi-wasm-crypto`), so a key id is always a prefix of them.
+pub fn fingerprint_digest(public_key: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(FINGERPRINT_DOMAIN)
+        .chain_update(public_key)
+        .finalize()
+        .into()
+}
+
+/// Key id for an Ed25519 public key: the first 8 bytes of its fingerprint digest.
+pub fn signed_qr_key_id(public_key: &[u8]) -> [u8; KEY_ID_LEN] {
+    let mut id = [0u8; KEY_ID_LEN];
+    id.copy_from_slice(&fingerprint_digest(public_key)[..KEY_ID_LEN]);
+    id
+}
+
+/// Produces signatures for `generate_signed_qr`.
+pub trait QrSigner {
+    /// Short identifier the verifier uses to pick the public key.
+    fn key_id(&self) -> [u8; KEY_ID_LEN];
+    fn sign(&self, message: &[u8]) -> [u8; SIGNATURE_LEN];
+}
+
+/// A payload split into its parts. Not yet verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedPayload<'a> {
+    pub content: &'a [u8],
+    pub signature: [u8; SIGNATURE_LEN],
+    pub key_id: [u8; KEY_ID_LEN],
+}
+
+impl SignedPayload<'_> {
+    /// Bytes the signature must cover.
+    pub fn message(&self) -> Vec<u8> {
+        signed_qr_message(self.content)
+    }
+}
+
+/// Bytes a signer signs for `content`.
+pub fn signed_qr_message(content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(SIGNED_QR_CONTEXT.len() + content.len());
+    out.extend_from_slice(SIGNED_QR_CONTEXT);
+    out.extend_from_slice(content);
+    out
+}
+
+/// Sign `content` and build the byte-mode payload.
+pub fn encode_signed_payload(content: &[u8], signer: &dyn QrSigner) -> Vec<u8> {
+    let signature = signer.sign(&signed_qr_message(content));
+    let mut out = Vec::with_capacity(content.len() + SIGNED_TRAILER_LEN);
+    out.extend_from_slice(content);
+    out.extend_from_slice(&signature);
+    out.extend_from_slice(&signer.key_id());
+    out
+}
+
+/// Split a decoded payload into content, signature and key id.
+pub fn split_signed_payload(bytes: &[u8]) -> Result<SignedPayload<'_>, QrError> {
+    if bytes.len() <= SIGNED_TRAILER_LEN {
+        return Err(QrError::InvalidArgument(
+            "Payload too short for a signed QR".into(),
+        ));
+    }
+    let (content, trailer) = bytes.split_at(bytes.len() - SIGNED_TRAILER_LEN);
+    let mut signature = [0u8; SIGNATURE_LEN];
+    signature.copy_from_slice(&trailer[..SIGNATURE_LEN]);
+    let mut key_id = [0u8; KEY_ID_LEN];
+    key_id.copy_from_slice(&trailer[SIGNATURE_LEN..]);
+    Ok(SignedPayload {
+        content,
+        signature,
+        key_id,
+    })
+}
+
+/// Generate a byte-mode QR carrying `content` signed by `signer`.
+pub fn generate_signed_qr(
+    content: &[u8],
+    signer: &dyn QrSigner,
+    ecl: ErrorCorrectionLevel,
+) -> Result<QrCode, QrError> {
+    if content.is_empty() {
+        return Err(QrError::EmptyInput);
+    }
+    generate_qr_bytes(&encode_signed_payload(content, signer), ecl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeSigner;
+
+    impl QrSigner for FakeSigner {
+        fn key_id(&self) -> [u8; KEY_ID_LEN] {
+            [7; KEY_ID_LEN]
+        }
+
+        fn sign(&self, message: &[u8]) -> [u8; SIGNATURE_LEN] {
+            let mut sig = [0u8; SIGNATURE_LEN];
+            for (i, b) in message.iter().enumerate() {
+                sig[i % SIGNATURE_LEN] ^= b;
+            }
+            sig
+        }
+    }
+
+    #[test]
+    fn test_payload_roundtrip() {
+        let payload = encode_signed_payload(b"ticket #42", &FakeSigner);
+        assert_eq!(payload.len(), 10 + SIGNED_TRAILER_LEN);
+
+        let parsed = split_signed_payload(&payload).unwrap();
+        assert_eq!(parsed.content, b"ticket #42");
+        assert_eq!(parsed.key_id, [7; KEY_ID_LEN]);
+        assert_eq!(parsed.signature, FakeSigner.sign(&parsed.message()));
+    }
+
+    #[test]
+    fn test_rejects_short_payload() {
+        assert!(split_signed_payload(&[0u8; SIGNED_TRAILER_LEN]).is_err());
+        assert!(generate_signed_qr(b"", &FakeSigner, ErrorCorrectionLevel::Medium).is_err());
+    }
+
+    #[test]
+    fn test_generates_byte_mode_qr() {
+        let qr = generate_signed_qr(b"poster", &FakeSigner, ErrorCorrectionLevel::Medium).unwrap();
+        assert!(qr.size() >= 21);
+    }
+}
@@ -0,0 +1,188 @@
+//! Golden SVG snapshots
+//!
+//! Renders every body shape at every ECC level and three payload sizes,
+//! every eye frame/ball pair, the presets and the optional layers, and
+//! compares the hash and byte size of each SVG with `snapshots/svg.txt`.
+//! Any change to shapes.rs or render.rs that alters output fails here until
+//! the corpus is regenerated and the diff of that file reviewed:
+//!
+//! ```sh
+//! HOLI_QR_UPDATE_SNAPSHOTS=1 cargo test snapshots
+//! ```
+
+use crate::{
+    generate_qr, render_svg, render_svg_styled, BackgroundPattern, BodyShape, EyeBallShape, EyeCorner,
+    EyeFrameShape, EyeOverride, ErrorCorrectionLevel, Gradient, GradientKind, GradientStop, OutlineStyle, PatternKind, StyledRenderOptions, PRESETS,
+};
+
+const CORPUS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/snapshots/svg.txt");
+const UPDATE_VAR: &str = "HOLI_QR_UPDATE_SNAPSHOTS";
+
+/// Upper bound on SVG size per module of the rendered area (quiet zone
+/// included); the fanciest shapes stay well below it
+const MAX_BYTES_PER_MODULE: usize = 160;
+
+/// Payloads landing around versions 1, 4 and 10 (at M)
+const PAYLOADS: [(&str, &str); 3] = [
+    ("short", "holi"),
+    ("url", "https://holi.tools/qr/snapshot?ref=golden-corpus&v=4"),
+    (
+        "long",
+        "https://holi.tools/qr/snapshot/long-payload?utm_source=golden&utm_medium=test&utm_campaign=corpus&\
+         lorem=ipsum-dolor-sit-amet-consectetur-adipiscing-elit-sed-do-eiusmod-tempor-incididunt-ut-labore&v=10",
+    ),
+];
+
+const LEVELS: [(&str, ErrorCorrectionLevel); 4] = [
+    ("L", ErrorCorrectionLevel::Low),
+    ("M", ErrorCorrectionLevel::Medium),
+    ("Q", ErrorCorrectionLevel::Quartile),
+    ("H", ErrorCorrectionLevel::High),
+];
+
+struct Case {
+    name: String,
+    /// Modules per side of the whole image
+    side: usize,
+    svg: String,
+}
+
+fn styled(name: String, text: &str, ecl: ErrorCorrectionLevel, options: &StyledRenderOptions) -> Case {
+    let qr = generate_qr(text, ecl).unwrap();
+    Case { name, side: qr.size() + options.margin * 2, svg: render_svg_styled(&qr, options) }
+}
+
+fn cases() -> Vec<Case> {
+    let mut cases = Vec::new();
+
+    for (payload, text) in PAYLOADS {
+        for (level, ecl) in LEVELS {
+            let qr = generate_qr(text, ecl).unwrap();
+            cases.push(Case { name: format!("plain/{}/{}", payload, level), side: qr.size() + 8, svg: render_svg(&qr) });
+
+            for shape in BodyShape::ALL {
+                let options = StyledRenderOptions { body_shape: shape, ..Default::default() };
+                cases.push(styled(format!("body/{}/{}/{}", shape.as_str(), payload, level), text, ecl, &options));
+            }
+        }
+    }
+
+    let (_, text) = PAYLOADS[1];
+    for frame in EyeFrameShape::ALL {
+        for ball in EyeBallShape::ALL {
+            let options = StyledRenderOptions { eye_frame_shape: frame, eye_ball_shape: ball, ..Default::default() };
+            let name = format!("eyes/{}/{}", frame.as_str(), ball.as_str());
+            cases.push(styled(name, text, ErrorCorrectionLevel::Medium, &options));
+        }
+    }
+
+    for preset in PRESETS {
+        cases.push(styled(format!("preset/{}", preset.name), text, preset.ecl, &preset.options()));
+    }
+
+    let dots = StyledRenderOptions { body_shape: BodyShape::Dots, ..Default::default() };
+    let mut top_right = [EyeOverride::default(), EyeOverride::default(), EyeOverride::default()];
+    top_right[EyeCorner::TopRight as usize] = EyeOverride {
+        frame_shape: Some(EyeFrameShape::Circle),
+        ball_shape: Some(EyeBallShape::Heart),
+        color: Some("#FF0066".to_string()),
+        gradient: None,
+    };
+    let stops = vec![
+        GradientStop { offset: 0.0, color: "#1F3A5F".to_string() },
+        GradientStop { offset: 1.0, color: "#5B00D6".to_string() },
+    ];
+    let linear = Gradient { kind: GradientKind::Linear, stops: stops.clone(), angle: 45.0 };
+    let radial = Gradient { kind: GradientKind::Radial, stops, angle: 0.0 };
+    let layers = [
+        ("transparent", StyledRenderOptions { bg_color: "transparent".to_string(), ..dots.clone() }),
+        ("colors", StyledRenderOptions { fg_color: "#1F3A5F".to_string(), bg_color: "#FFF8E7".to_string(), ..dots.clone() }),
+        ("margin-0", StyledRenderOptions { margin: 0, ..dots.clone() }),
+        ("eye-override", StyledRenderOptions { eye_overrides: top_right, ..dots.clone() }),
+        ("outline", StyledRenderOptions { outline: Some(OutlineStyle::default()), ..dots.clone() }),
+        (
+            "outline-contour",
+            StyledRenderOptions {
+                outline: Some(OutlineStyle { stroke_width: 0.2, contour_only: true }),
+                ..Default::default()
+            },
+        ),
+        ("gradient-linear", StyledRenderOptions { fg_gradient: Some(linear), ..dots.clone() }),
+        ("gradient-radial", StyledRenderOptions { fg_gradient: Some(radial), ..Default::default() }),
+        ("unprotected-cross", StyledRenderOptions { body_shape: BodyShape::Cross, protect_function_patterns: false, ..Default::default() }),
+    ];
+    for (name, options) in layers {
+        cases.push(styled(format!("layer/{}", name), text, ErrorCorrectionLevel::Medium, &options));
+    }
+    for kind in [PatternKind::DotsGrid, PatternKind::DiagonalLines, PatternKind::Noise] {
+        let options = StyledRenderOptions {
+            background_pattern: Some(BackgroundPattern { kind, ..Default::default() }),
+            ..dots.clone()
+        };
+        cases.push(styled(format!("layer/pattern-{}", kind.as_str()), text, ErrorCorrectionLevel::Medium, &options));
+    }
+
+    cases
+}
+
+/// 64-bit FNV-1a: stable across Rust releases, unlike `DefaultHasher`
+fn fnv1a(data: &str) -> u64 {
+    data.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// `name -> (hash, bytes)` lines of the corpus file
+fn parse_corpus(corpus: &str) -> Vec<(&str, &str, usize)> {
+    corpus
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            Some((fields.next()?, fields.next()?, fields.next()?.parse().ok()?))
+        })
+        .collect()
+}
+
+#[test]
+fn test_svg_snapshots() {
+    let cases = cases();
+    let actual: String = cases.iter().map(|c| format!("{} {:016x} {}\n", c.name, fnv1a(&c.svg), c.svg.len())).collect();
+
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        std::fs::write(CORPUS, &actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(CORPUS).unwrap_or_default();
+    let (expected, actual) = (parse_corpus(&expected), parse_corpus(&actual));
+    let mut problems = Vec::new();
+    for (name, hash, bytes) in &actual {
+        match expected.iter().find(|(n, _, _)| n == name) {
+            None => problems.push(format!("new: {}", name)),
+            Some((_, h, b)) if h != hash => problems.push(format!("changed: {} ({} -> {} bytes)", name, b, bytes)),
+            Some(_) => {}
+        }
+    }
+    for (name, _, _) in &expected {
+        if !actual.iter().any(|(n, _, _)| n == name) {
+            problems.push(format!("removed: {}", name));
+        }
+    }
+    assert!(
+        problems.is_empty(),
+        "{} of {} snapshots differ; review and run `{}=1 cargo test snapshots` to accept:\n{}",
+        problems.len(),
+        actual.len(),
+        UPDATE_VAR,
+        problems.join("\n")
+    );
+}
+
+#[test]
+fn test_svg_sizes_bounded() {
+    let cases = cases();
+    assert!(cases.len() > 400);
+    for case in cases {
+        assert!(case.svg.starts_with("<svg") && case.svg.ends_with("</svg>"), "{}", case.name);
+        let budget = case.side * case.side * MAX_BYTES_PER_MODULE;
+        assert!(case.svg.len() <= budget, "{}: {} bytes, budget {}", case.name, case.svg.len(), budget);
+    }
+}
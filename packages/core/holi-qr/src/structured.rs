@@ -0,0 +1,189 @@
+//! Structured Append: one payload over up to 16 linked symbols
+//!
+//! ISO/IEC 18004 lets a payload too long for one symbol be split across up
+//! to 16. Each symbol starts with a header holding its position, the number
+//! of symbols and a parity byte of the whole payload, so scanners that
+//! support it put the parts back together in order; others read each part
+//! on its own.
+//!
+//! fast_qr has no Structured Append mode; the symbols are built by
+//! `codewords`.
+
+use crate::codewords::{build_symbol, BitBuffer};
+use crate::error::QrError;
+use crate::qr::{best_mode, capacity, data_codewords, generate_qr, segment_bits, ErrorCorrectionLevel, QrCode};
+use fast_qr::Mode;
+
+/// Most symbols a Structured Append series can have
+pub const MAX_STRUCTURED_SYMBOLS: usize = 16;
+
+/// Mode indicator, position, last position and parity
+const HEADER_BITS: usize = 20;
+
+/// Generate a series of linked QR codes for text too long for one
+///
+/// Uses as few symbols as hold `text` (at most `max_symbols`, 1-16), all
+/// the same version, with the text split evenly on character boundaries.
+/// Each code's `text` is its part; joined in order they give `text` back.
+/// Text that fits in a single symbol gives one plain code, as
+/// `generate_qr`.
+///
+/// # Returns
+/// * `Ok(Vec<QrCode>)` - The symbols, in reading order
+/// * `Err(QrError::InputTooLong)` - Not even `max_symbols` version-40
+///   symbols hold `text`
+///
+/// # Example
+/// ```rust
+/// use holi_qr::{generate_qr_structured, ErrorCorrectionLevel};
+///
+/// let text = "holi ".repeat(1000);
+/// let parts = generate_qr_structured(&text, 4, ErrorCorrectionLevel::Medium).unwrap();
+/// assert_eq!(parts.len(), 3);
+/// assert_eq!(parts.iter().map(|qr| qr.text.as_str()).collect::<String>(), text);
+/// ```
+pub fn generate_qr_structured(
+    text: &str,
+    max_symbols: usize,
+    ecl: ErrorCorrectionLevel,
+) -> Result<Vec<QrCode>, QrError> {
+    if !(1..=MAX_STRUCTURED_SYMBOLS).contains(&max_symbols) {
+        return Err(QrError::InvalidArgument(format!(
+            "Structured Append takes 1 to {} symbols (got {})",
+            MAX_STRUCTURED_SYMBOLS, max_symbols
+        )));
+    }
+    if text.is_empty() {
+        return Err(QrError::EmptyInput);
+    }
+
+    let mode = best_mode(text.as_bytes());
+    if text.len() <= capacity(40, ecl, mode) {
+        return Ok(vec![generate_qr(text, ecl)?]);
+    }
+    let largest = part_capacity(40, ecl, mode);
+    let parts = (text.len().div_ceil(largest)..=max_symbols)
+        .map(|count| split(text, count))
+        .find(|parts| parts.iter().all(|part| part.len() <= largest))
+        .ok_or(QrError::InputTooLong { length: text.len() })?;
+
+    let longest = parts.iter().map(|part| part.len()).max().unwrap_or_default();
+    let version = (1..=40).find(|&v| part_capacity(v, ecl, mode) >= longest).unwrap_or(40);
+    let parity = text.bytes().fold(0, |acc, b| acc ^ b);
+    parts
+        .iter()
+        .enumerate()
+        .map(|(position, part)| structured_symbol(part, position, parts.len(), parity, version, mode, ecl))
+        .collect()
+}
+
+/// Most characters of `mode` a `version` symbol holds after the header
+fn part_capacity(version: usize, ecl: ErrorCorrectionLevel, mode: Mode) -> usize {
+    let bits = data_codewords(version, ecl) * 8;
+    let mut length = capacity(version, ecl, mode);
+    while length > 0 && HEADER_BITS + segment_bits(mode, length, version) > bits {
+        length -= 1;
+    }
+    length
+}
+
+/// `text` in at most `count` pieces of about equal length, cut on character
+/// boundaries
+fn split(text: &str, count: usize) -> Vec<&str> {
+    let target = text.len().div_ceil(count);
+    let mut parts = Vec::with_capacity(count);
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = target.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end += 1;
+        }
+        let (part, tail) = rest.split_at(end);
+        parts.push(part);
+        rest = tail;
+    }
+    parts
+}
+
+/// Symbol `position` of `total` holding `part`
+fn structured_symbol(
+    part: &str,
+    position: usize,
+    total: usize,
+    parity: u8,
+    version: usize,
+    mode: Mode,
+    ecl: ErrorCorrectionLevel,
+) -> Result<QrCode, QrError> {
+    let mut data = BitBuffer::default();
+    data.push(0b0011, 4);
+    data.push(position, 4);
+    data.push(total - 1, 4);
+    data.push(parity as usize, 8);
+    data.push_segment(part.as_bytes(), mode, version);
+    let inner = build_symbol(data, part.as_bytes(), mode, version, ecl)?;
+    QrCode::new(inner, part.as_bytes(), mode, ecl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codewords::check_words;
+
+    #[test]
+    fn test_header() {
+        // 2-L holds a single block, so the data codewords come first in order
+        let qr = structured_symbol("HELLO WORLD", 1, 3, 0x5A, 2, Mode::Alphanumeric, ErrorCorrectionLevel::Low).unwrap();
+        let layout = qr.debug_layout();
+        assert_eq!(layout.version, 2);
+        // 0011 0001 | 0010 0101 | 1010 0010 (Structured Append 2 of 3, parity
+        // 0x5A, then the alphanumeric mode indicator)
+        assert_eq!(layout.codewords[..3], [0x31, 0x25, 0xA2]);
+        let data = &layout.codewords[..layout.data_codewords];
+        assert_eq!(check_words(data, layout.codewords.len() - data.len()), layout.codewords[data.len()..]);
+        // 94 bits: the terminator runs two bits into codeword 12, then padding
+        assert_eq!(layout.codewords[12..15], [0x00, 0xEC, 0x11]);
+    }
+
+    #[test]
+    fn test_split_long_text() {
+        let text = "Paella de marisco, ración completa · ".repeat(150);
+        let parts = generate_qr_structured(&text, 16, ErrorCorrectionLevel::Medium).unwrap();
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|qr| qr.size() == parts[0].size()));
+        assert_eq!(parts.iter().map(|qr| qr.text.as_str()).collect::<String>(), text);
+        for (position, qr) in parts.iter().enumerate() {
+            let first = qr.debug_layout().codewords[0];
+            assert_eq!((first >> 4, first & 0xF), (0b0011, position as u8));
+        }
+
+        assert!(matches!(generate_qr_structured(&text, 2, ErrorCorrectionLevel::Medium), Err(QrError::InputTooLong { .. })));
+        assert!(generate_qr_structured(&text, 0, ErrorCorrectionLevel::Medium).is_err());
+        assert!(generate_qr_structured(&text, 17, ErrorCorrectionLevel::Medium).is_err());
+        assert!(matches!(generate_qr_structured("", 4, ErrorCorrectionLevel::Medium), Err(QrError::EmptyInput)));
+
+        // Short text is one ordinary symbol
+        let single = generate_qr_structured("https://holi.tools", 4, ErrorCorrectionLevel::Medium).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].matrix(), generate_qr("https://holi.tools", ErrorCorrectionLevel::Medium).unwrap().matrix());
+    }
+
+    #[test]
+    #[cfg(feature = "verify")]
+    fn test_parts_scan() {
+        use crate::{render_svg, verify_svg};
+
+        // 5-Q has two short and two long blocks
+        let digits: String = (0..100).map(|i| char::from(b'0' + i % 10)).collect();
+        let cases = [
+            ("HELLO WORLD", 2, Mode::Alphanumeric, ErrorCorrectionLevel::Low),
+            (digits.as_str(), 5, Mode::Numeric, ErrorCorrectionLevel::Quartile),
+            ("structured append, byte mode", 4, Mode::Byte, ErrorCorrectionLevel::High),
+        ];
+        for (part, version, mode, ecl) in cases {
+            let qr = structured_symbol(part, 0, 2, 0, version, mode, ecl).unwrap();
+            assert_eq!(qr.size(), 17 + 4 * version);
+            assert_eq!(verify_svg(&render_svg(&qr)).unwrap(), part, "{:?} {:?}", mode, ecl);
+        }
+    }
+}
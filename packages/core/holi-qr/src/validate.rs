@@ -0,0 +1,275 @@
+//! Checks on styling options before rendering
+//!
+//! `try_render_svg_styled` only refuses what it can't draw at all (an oversized
+//! margin, an empty or out-of-range logo); `validate_options` reports what will break the SVG (errors) or
+//! likely break scanning (warnings), so callers can surface it instead of
+//! shipping a bad code.
+
+use crate::gradient::Gradient;
+use crate::logo::MAX_LOGO_SIZE;
+use crate::pattern::{contrast, parse_hex};
+use crate::render::{EyeCorner, StyledRenderOptions, MAX_MARGIN};
+use crate::shapes::{BodyShape, EyeBallShape, EyeFrameShape};
+
+/// Quiet zone required by ISO/IEC 18004, in modules
+pub const SPEC_QUIET_ZONE: usize = 4;
+
+/// Below this fg/bg contrast ratio many phone scanners give up
+pub const MIN_SCAN_CONTRAST: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The options produce a broken SVG; don't render
+    Error,
+    /// The SVG renders but may not scan
+    Warning,
+}
+
+/// One problem found by `validate_options`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// Stable identifier, e.g. `"invalid-color"`, for UI translations
+    pub code: &'static str,
+    /// Option the issue is about, e.g. `"fg_color"` or `"eyes.top_right.color"`
+    pub field: String,
+    /// English description
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn error(code: &'static str, field: impl Into<String>, message: String) -> Self {
+        Self { severity: Severity::Error, code, field: field.into(), message }
+    }
+
+    fn warning(code: &'static str, field: impl Into<String>, message: String) -> Self {
+        Self { severity: Severity::Warning, code, field: field.into(), message }
+    }
+}
+
+/// Colors are written into SVG attributes as-is
+fn check_color(issues: &mut Vec<ValidationIssue>, field: &str, color: &str) {
+    if color.trim().is_empty() || color.contains(['"', '<', '>', '&']) {
+        issues.push(ValidationIssue::error("invalid-color", field, format!("{:?} is not a color", color)));
+    } else if parse_hex(color).is_none() && !(field == "bg_color" && color == "transparent") {
+        issues.push(ValidationIssue::warning(
+            "unchecked-color",
+            field,
+            format!("{:?} is not a hex color; contrast can't be checked", color),
+        ));
+    }
+}
+
+/// Errors and warnings for `options`, errors first
+///
+/// Empty when the options are fine.
+pub fn validate_options(options: &StyledRenderOptions) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    if options.margin > MAX_MARGIN {
+        issues.push(ValidationIssue::error(
+            "margin-too-large",
+            "margin",
+            format!("Margin of {} modules is over the {} the renderer accepts", options.margin, MAX_MARGIN),
+        ));
+    } else if options.margin < SPEC_QUIET_ZONE {
+        issues.push(ValidationIssue::warning(
+            "small-quiet-zone",
+            "margin",
+            format!("Margin of {} modules is below the {} the spec requires", options.margin, SPEC_QUIET_ZONE),
+        ));
+    }
+
+    check_color(&mut issues, "fg_color", &options.fg_color);
+    check_color(&mut issues, "bg_color", &options.bg_color);
+    let bg = if options.bg_color == "transparent" { Some([255.0; 3]) } else { parse_hex(&options.bg_color) };
+    let check_contrast = |issues: &mut Vec<ValidationIssue>, field: &str, fg: &str| {
+        let (Some(fg), Some(bg)) = (parse_hex(fg), bg) else { return };
+        let luma = |c: [f64; 3]| c[0] * 0.299 + c[1] * 0.587 + c[2] * 0.114;
+        if contrast(fg, bg) < MIN_SCAN_CONTRAST {
+            issues.push(ValidationIssue::warning(
+                "low-contrast",
+                field,
+                format!("Contrast {:.1}:1 against the background is below {}:1", contrast(fg, bg), MIN_SCAN_CONTRAST),
+            ));
+        } else if luma(fg) > luma(bg) {
+            issues.push(ValidationIssue::warning(
+                "inverted-colors",
+                field,
+                "Light modules on a dark background; some scanners can't read inverted codes".to_string(),
+            ));
+        }
+    };
+    check_contrast(&mut issues, "fg_color", &options.fg_color);
+
+    let check_gradient = |issues: &mut Vec<ValidationIssue>, field: &str, gradient: &Gradient| {
+        if gradient.stops.is_empty() {
+            issues.push(ValidationIssue::warning(
+                "empty-gradient",
+                field,
+                "Gradient has no stops; the solid color is drawn instead".to_string(),
+            ));
+        }
+        for (i, stop) in gradient.stops.iter().enumerate() {
+            let color_field = format!("{}.stops[{}].color", field, i);
+            check_color(issues, &color_field, &stop.color);
+            check_contrast(issues, &color_field, &stop.color);
+            if !(0.0..=1.0).contains(&stop.offset) {
+                issues.push(ValidationIssue::warning(
+                    "offset-clamped",
+                    format!("{}.stops[{}].offset", field, i),
+                    format!("Offset {} is outside 0-1 and will be clamped", stop.offset),
+                ));
+            }
+        }
+    };
+    if let Some(gradient) = &options.fg_gradient {
+        check_gradient(&mut issues, "fg_gradient", gradient);
+    }
+
+    for corner in EyeCorner::ALL {
+        let eye = &options.eye_overrides[corner as usize];
+        if let Some(color) = &eye.color {
+            let field = format!("eyes.{}.color", corner.as_str());
+            check_color(&mut issues, &field, color);
+            check_contrast(&mut issues, &field, color);
+        }
+        if let Some(gradient) = &eye.gradient {
+            check_gradient(&mut issues, &format!("eyes.{}.gradient", corner.as_str()), gradient);
+        }
+    }
+
+    if let Some(pattern) = &options.background_pattern {
+        check_color(&mut issues, "background_pattern.color", &pattern.color);
+        if !(0.0..=1.0).contains(&pattern.density) {
+            issues.push(ValidationIssue::warning(
+                "density-clamped",
+                "background_pattern.density",
+                format!("Density {} is outside 0-1 and will be clamped", pattern.density),
+            ));
+        }
+    }
+
+    if let Some(outline) = &options.outline {
+        if !outline.stroke_width.is_finite() || outline.stroke_width <= 0.0 {
+            issues.push(ValidationIssue::error(
+                "invalid-stroke-width",
+                "outline.stroke_width",
+                format!("Stroke width must be positive (got {})", outline.stroke_width),
+            ));
+        } else if outline.stroke_width > 0.5 {
+            issues.push(ValidationIssue::warning(
+                "wide-stroke",
+                "outline.stroke_width",
+                format!("Stroke width {} is over half a module; neighbors will merge", outline.stroke_width),
+            ));
+        }
+    }
+
+    // Whether the code survives the logo depends on its ECC level; see `check_logo`
+    if let Some(logo) = &options.logo {
+        if !logo.size.is_finite() || logo.size <= 0.0 || logo.size > MAX_LOGO_SIZE {
+            issues.push(ValidationIssue::error(
+                "invalid-logo-size",
+                "logo.size",
+                format!("Logo size must be above 0 and at most {} (got {})", MAX_LOGO_SIZE, logo.size),
+            ));
+        } else if logo.check_drawable().is_err() {
+            issues.push(ValidationIssue::error("empty-logo", "logo.image", "Logo image is empty".to_string()));
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.severity != Severity::Error);
+    issues
+}
+
+/// Warning for a shape name that `from_str` doesn't know (and would draw as a square)
+///
+/// `field` is the option the name came from, e.g. `"body_shape"` or
+/// `"eyes.top_left.frame_shape"`; its suffix picks the shape kind.
+pub fn unknown_shape_issue(field: &str, name: &str) -> Option<ValidationIssue> {
+    let known = if field.ends_with("body_shape") {
+        BodyShape::try_from(name).is_ok()
+    } else if field.ends_with("frame_shape") {
+        EyeFrameShape::try_from(name).is_ok()
+    } else {
+        EyeBallShape::try_from(name).is_ok()
+    };
+    (!known).then(|| {
+        ValidationIssue::warning("unknown-shape", field, format!("Unknown shape {:?}; drawing squares instead", name))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackgroundPattern, EyeOverride, GradientStop, Logo, LogoImage, OutlineStyle};
+
+    fn codes(options: &StyledRenderOptions) -> Vec<&'static str> {
+        validate_options(options).iter().map(|i| i.code).collect()
+    }
+
+    #[test]
+    fn test_defaults_are_clean() {
+        assert!(validate_options(&StyledRenderOptions::default()).is_empty());
+        for preset in crate::PRESETS {
+            assert!(validate_options(&preset.options()).is_empty(), "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn test_issues() {
+        let options = StyledRenderOptions {
+            margin: 1,
+            fg_color: "#DDDDDD".to_string(),
+            bg_color: "red\" onload=\"x".to_string(),
+            outline: Some(OutlineStyle { stroke_width: 0.0, contour_only: false }),
+            ..Default::default()
+        };
+        let issues = validate_options(&options);
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(codes(&options), ["invalid-color", "invalid-stroke-width", "small-quiet-zone"]);
+
+        let mut eyes: [EyeOverride; 3] = Default::default();
+        eyes[EyeCorner::TopRight as usize].color = Some("#F0F0F0".to_string());
+        let options = StyledRenderOptions {
+            fg_color: "#FFFFFF".to_string(),
+            bg_color: "#000000".to_string(),
+            eye_overrides: eyes,
+            background_pattern: Some(BackgroundPattern { color: "navy".to_string(), density: 2.0, ..Default::default() }),
+            ..Default::default()
+        };
+        assert_eq!(codes(&options), ["inverted-colors", "inverted-colors", "unchecked-color", "density-clamped"]);
+        assert_eq!(validate_options(&options)[1].field, "eyes.top_right.color");
+
+        let logo = |size, data: Vec<u8>| {
+            Some(Logo { image: LogoImage::Raster { mime_type: "image/png".to_string(), data }, size, padding: 1 })
+        };
+        assert!(codes(&StyledRenderOptions { logo: logo(0.2, vec![1]), ..Default::default() }).is_empty());
+        assert_eq!(codes(&StyledRenderOptions { logo: logo(0.6, vec![1]), ..Default::default() }), ["invalid-logo-size"]);
+        assert_eq!(codes(&StyledRenderOptions { logo: logo(0.2, Vec::new()), ..Default::default() }), ["empty-logo"]);
+
+        let stop = |offset, color: &str| GradientStop { offset, color: color.to_string() };
+        let mut eyes: [EyeOverride; 3] = Default::default();
+        eyes[EyeCorner::BottomLeft as usize].gradient = Some(Gradient::default());
+        let options = StyledRenderOptions {
+            fg_gradient: Some(Gradient { stops: vec![stop(0.0, "#000000"), stop(1.5, "#EEEEEE")], ..Default::default() }),
+            eye_overrides: eyes,
+            ..Default::default()
+        };
+        assert_eq!(codes(&options), ["low-contrast", "offset-clamped", "empty-gradient"]);
+        assert_eq!(validate_options(&options)[0].field, "fg_gradient.stops[1].color");
+    }
+
+    #[test]
+    fn test_unknown_shape_names() {
+        assert!(unknown_shape_issue("body_shape", "dots").is_none());
+        assert!(unknown_shape_issue("body_shape", "Square").is_none());
+        assert!(unknown_shape_issue("body_shape", "connected").is_none());
+        assert!(unknown_shape_issue("eye_frame_shape", "circle").is_none());
+        assert_eq!(unknown_shape_issue("body_shape", "dotz").unwrap().code, "unknown-shape");
+        assert!(unknown_shape_issue("eye_ball_shape", "bars-h").is_none());
+        assert!(unknown_shape_issue("eye_ball_shape", "flowr").is_some());
+        assert!(unknown_shape_issue("eyes.top_left.frame_shape", "double").is_none());
+    }
+}
@@ -3,8 +3,166 @@
 //! This module provides the ability to:
 //! 1. Verify that a generated QR code SVG is scannable
 //! 2. Decode QR codes from raw image data (for user-uploaded images)
+//! 3. Rasterize SVG output to PNG with the same renderer
+//!
+//! The 'decode' feature alone enables only the pixel-buffer decoders
+//! (`decode_luma`, `check_readability`), for builds that rasterize elsewhere
+//! (e.g. a browser canvas) and don't want resvg or image codecs. The 'png'
+//! feature alone enables only the PNG output (`rasterize_svg`,
+//! `render_png`), without rxing.
+//!
+//! `verify_batch` checks many SVGs (a label sheet) reusing one pixmap per
+//! worker; the 'parallel' feature spreads it over a rayon pool.
 
 use crate::error::QrError;
+use crate::{QrCode, StyledRenderOptions};
+
+/// Largest width or height `rasterize_svg` renders, in pixels
+///
+/// 4096 x 4096 RGBA is 64 MiB, and covers `render_png` of any symbol at the
+/// largest margin.
+pub const MAX_RASTER_SIZE: u32 = 4096;
+
+/// Render an SVG into a pixmap `width` pixels wide.
+///
+/// With `height` unset the SVG's aspect ratio is kept; otherwise the image is
+/// scaled to fit inside `width` x `height`. Either side above
+/// `MAX_RASTER_SIZE` is an `InvalidArgument` error.
+#[cfg(feature = "png")]
+fn render_pixmap(
+    svg: &str,
+    width: u32,
+    height: Option<u32>,
+    white_background: bool,
+) -> Result<tiny_skia::Pixmap, QrError> {
+    if width > MAX_RASTER_SIZE {
+        return Err(QrError::InvalidArgument(format!(
+            "Width {} is over the maximum of {} pixels",
+            width, MAX_RASTER_SIZE
+        )));
+    }
+    let tree = parse_svg(svg, &resvg::usvg::Options::default())?;
+
+    let tree_size = tree.size();
+    let height = height.unwrap_or_else(|| {
+        ((width as f32 * tree_size.height() / tree_size.width()).round() as u32).max(1)
+    });
+    if height > MAX_RASTER_SIZE {
+        return Err(QrError::InvalidArgument(format!(
+            "Height {} is over the maximum of {} pixels",
+            height, MAX_RASTER_SIZE
+        )));
+    }
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| QrError::RenderFailed("Failed to create pixmap".into()))?;
+    if white_background {
+        pixmap.fill(tiny_skia::Color::WHITE);
+    }
+    draw_tree(&tree, &mut pixmap);
+
+    Ok(pixmap)
+}
+
+#[cfg(feature = "png")]
+fn parse_svg(svg: &str, options: &resvg::usvg::Options) -> Result<resvg::usvg::Tree, QrError> {
+    resvg::usvg::Tree::from_str(svg, options)
+        .map_err(|e| QrError::RenderFailed(format!("SVG parse error: {}", e)))
+}
+
+/// Draw `tree` scaled to fit `pixmap`
+#[cfg(feature = "png")]
+fn draw_tree(tree: &resvg::usvg::Tree, pixmap: &mut tiny_skia::Pixmap) {
+    let tree_size = tree.size();
+    let scale = (pixmap.width() as f32 / tree_size.width()).min(pixmap.height() as f32 / tree_size.height());
+    let transform = tiny_skia::Transform::from_scale(scale, scale);
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+}
+
+/// Width and height `verify_svg` renders at
+#[cfg(feature = "verify")]
+const VERIFY_SIZE: u32 = 800;
+
+/// Parser options and pixmap kept across `verify_batch` items
+#[cfg(feature = "verify")]
+struct Verifier {
+    options: resvg::usvg::Options<'static>,
+    pixmap: tiny_skia::Pixmap,
+}
+
+#[cfg(feature = "verify")]
+impl Verifier {
+    fn new() -> Option<Self> {
+        Some(Verifier {
+            options: resvg::usvg::Options::default(),
+            pixmap: tiny_skia::Pixmap::new(VERIFY_SIZE, VERIFY_SIZE)?,
+        })
+    }
+
+    fn verify(&mut self, svg: &str) -> Result<String, QrError> {
+        let tree = parse_svg(svg, &self.options).map_err(|e| match e {
+            QrError::RenderFailed(msg) => QrError::VerificationFailed(msg),
+            other => other,
+        })?;
+        // White background, important for transparent QRs
+        self.pixmap.fill(tiny_skia::Color::WHITE);
+        draw_tree(&tree, &mut self.pixmap);
+
+        // Convert RGBA to grayscale (luma) for rxing
+        let luma = rgba_to_luma(self.pixmap.data());
+        decode_luma(luma, self.pixmap.width(), self.pixmap.height())
+    }
+}
+
+#[cfg(feature = "verify")]
+fn verify_with(verifier: &mut Option<Verifier>, svg: &str) -> VerifyResult {
+    verifier
+        .as_mut()
+        .ok_or_else(|| QrError::VerificationFailed("Failed to create pixmap".into()))?
+        .verify(svg)
+}
+
+/// Rasterize an SVG (e.g. from `render_svg_styled`) to PNG bytes
+///
+/// Uses the same renderer as `verify_svg`, so exported PNGs match what was
+/// verified. Transparency in the SVG is kept.
+///
+/// # Arguments
+/// * `svg` - The SVG string to rasterize
+/// * `width` - Output width in pixels; height follows the SVG's aspect ratio
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - PNG file bytes
+/// * `Err(QrError::InvalidArgument)` - The width is zero, or either side
+///   would be over `MAX_RASTER_SIZE`
+/// * `Err(QrError)` - Error if the SVG is invalid
+#[cfg(feature = "png")]
+pub fn rasterize_svg(svg: &str, width: u32) -> Result<Vec<u8>, QrError> {
+    if width == 0 {
+        return Err(QrError::InvalidArgument("Width must be positive".into()));
+    }
+    let pixmap = render_pixmap(svg, width, None, false)?;
+    pixmap
+        .encode_png()
+        .map_err(|e| QrError::RenderFailed(format!("PNG encode error: {}", e)))
+}
+
+/// Pixels per module in `render_png` output
+pub const PNG_MODULE_PIXELS: usize = 10;
+
+/// Render a QR code straight to PNG bytes
+///
+/// Draws `render_svg_styled` output at `PNG_MODULE_PIXELS` per module, so
+/// module edges land on whole pixels; use `rasterize_svg` for another size.
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - PNG file bytes
+/// * `Err(QrError)` - The options are invalid (see `try_render_svg_styled`)
+#[cfg(feature = "png")]
+pub fn render_png(qr: &QrCode, options: &StyledRenderOptions) -> Result<Vec<u8>, QrError> {
+    let width = crate::render::padded_size(qr.size(), options.margin)? * PNG_MODULE_PIXELS;
+    rasterize_svg(&crate::try_render_svg_styled(qr, options)?, width as u32)
+}
 
 /// Verify that an SVG QR code is scannable using rxing (ZXing port)
 ///
@@ -18,54 +176,111 @@ use crate::error::QrError;
 /// * `Err(QrError)` - Error if the QR code cannot be decoded
 #[cfg(feature = "verify")]
 pub fn verify_svg(svg: &str) -> Result<String, QrError> {
-    use resvg::usvg;
-    use rxing::{BarcodeFormat, DecodeHintType, DecodeHintValue};
-    use rxing::common::HybridBinarizer;
+    verify_with(&mut Verifier::new(), svg)
+}
+
+/// Outcome of verifying one SVG: the decoded text, or why it didn't scan
+pub type VerifyResult = Result<String, QrError>;
+
+/// `verify_svg` for every SVG in `svgs`, results in the same order
+///
+/// Each worker renders into one reused pixmap rather than allocating per
+/// SVG. With the 'parallel' feature the SVGs are spread over rayon's pool.
+#[cfg(feature = "verify")]
+pub fn verify_batch(svgs: &[String]) -> Vec<VerifyResult> {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        svgs.par_iter().map_init(Verifier::new, |verifier, svg| verify_with(verifier, svg)).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        let mut verifier = Verifier::new();
+        svgs.iter().map(|svg| verify_with(&mut verifier, svg)).collect()
+    }
+}
+
+/// Totals over a `verify_batch` run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchStats {
+    pub total: usize,
+    pub scannable: usize,
+    /// Indices of the SVGs that did not scan
+    pub failed: Vec<usize>,
+}
+
+impl BatchStats {
+    pub fn from_results(results: &[VerifyResult]) -> Self {
+        let failed: Vec<usize> = results.iter().enumerate().filter(|(_, r)| r.is_err()).map(|(i, _)| i).collect();
+        BatchStats { total: results.len(), scannable: results.len() - failed.len(), failed }
+    }
+
+    pub fn all_scannable(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Convert RGBA pixels (e.g. canvas `ImageData`) to 8-bit grayscale
+///
+/// Alpha is ignored; draw on an opaque background first.
+pub fn rgba_to_luma(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .map(|p| {
+            // Luminosity formula
+            let (r, g, b) = (p[0] as u32, p[1] as u32, p[2] as u32);
+            ((r * 299 + g * 587 + b * 114) / 1000) as u8
+        })
+        .collect()
+}
+
+/// Decode a QR code from an 8-bit grayscale buffer (`width * height` bytes)
+///
+/// # Returns
+/// * `Ok(String)` - The decoded text if successful
+/// * `Err(QrError)` - Error if no QR code found or the buffer size is wrong
+#[cfg(feature = "decode")]
+pub fn decode_luma(luma: Vec<u8>, width: u32, height: u32) -> Result<String, QrError> {
+    decode_bitmap(&mut luma_bitmap(luma, width, height)?).map(|(text, _)| text)
+}
+
+/// `luma` ready for `decode_bitmap`, once its size is checked
+#[cfg(feature = "decode")]
+pub(crate) fn luma_bitmap(
+    luma: Vec<u8>,
+    width: u32,
+    height: u32,
+) -> Result<rxing::BinaryBitmap<crate::binarize::BlockBinarizer>, QrError> {
+    use crate::binarize::BlockBinarizer;
     use rxing::BinaryBitmap;
     use rxing::Luma8LuminanceSource;
-    use rxing::MultiFormatReader;
-    use rxing::Reader;
-    
-    // Parse SVG using resvg
-    let options = usvg::Options::default();
-    let tree = usvg::Tree::from_str(svg, &options)
-        .map_err(|e| QrError::VerificationFailed(format!("SVG parse error: {}", e)))?;
-    
-    // Render to pixmap at high resolution
-    let size = 800u32;
-    
-    let mut pixmap = tiny_skia::Pixmap::new(size, size)
-        .ok_or_else(|| QrError::VerificationFailed("Failed to create pixmap".into()))?;
-    
-    // White background (important for transparent QRs)
-    pixmap.fill(tiny_skia::Color::WHITE);
-    
-    // Calculate scale to fit
-    let tree_size = tree.size();
-    let scale = (size as f32 / tree_size.width()).min(size as f32 / tree_size.height());
-    
-    let transform = tiny_skia::Transform::from_scale(scale, scale);
-    resvg::render(&tree, transform, &mut pixmap.as_mut());
-    
-    // Convert RGBA to grayscale (luma) for rxing
-    let pixels = pixmap.data();
-    let width = pixmap.width() as usize;
-    let height = pixmap.height() as usize;
-    
-    let mut luma: Vec<u8> = Vec::with_capacity(width * height);
-    for chunk in pixels.chunks(4) {
-        // RGBA -> grayscale using luminosity formula
-        let r = chunk[0] as u32;
-        let g = chunk[1] as u32;
-        let b = chunk[2] as u32;
-        let gray = ((r * 299 + g * 587 + b * 114) / 1000) as u8;
-        luma.push(gray);
+
+    if (width as usize).checked_mul(height as usize) != Some(luma.len()) {
+        return Err(QrError::InvalidArgument(format!(
+            "Expected {} luma bytes for {}x{}, got {}",
+            width as u64 * height as u64,
+            width,
+            height,
+            luma.len()
+        )));
     }
     
     // Create rxing source using Luma8 (grayscale bytes)
-    let source = Luma8LuminanceSource::new(luma, width as u32, height as u32);
-    let mut bitmap = BinaryBitmap::new(HybridBinarizer::new(source));
-    
+    let source = Luma8LuminanceSource::new(luma, width, height);
+    Ok(BinaryBitmap::new(BlockBinarizer::new(source)))
+}
+
+/// Decode `bitmap`, also returning where the finder patterns were found, in
+/// pixels (bottom-left, top-left, top-right, then any alignment pattern).
+/// Its black matrix stays cached in `bitmap` for the caller to look at
+/// afterwards.
+#[cfg(feature = "decode")]
+pub(crate) fn decode_bitmap(
+    bitmap: &mut rxing::BinaryBitmap<crate::binarize::BlockBinarizer>,
+) -> Result<(String, Vec<(f32, f32)>), QrError> {
+    use rxing::{BarcodeFormat, DecodeHintType, DecodeHintValue};
+    use rxing::MultiFormatReader;
+    use rxing::Reader;
+
     // Configure hints for better detection
     let mut hints = rxing::DecodingHintDictionary::new();
     hints.insert(
@@ -79,10 +294,55 @@ pub fn verify_svg(svg: &str) -> Result<String, QrError> {
     
     // Decode
     let mut reader = MultiFormatReader::default();
-    let result = reader.decode_with_hints(&mut bitmap, &hints)
-        .map_err(|e| QrError::VerificationFailed(format!("Decode error: {:?}", e)))?;;
+    let result = reader.decode_with_hints(bitmap, &hints)
+        .map_err(|e| QrError::VerificationFailed(format!("Decode error: {:?}", e)))?;
     
-    Ok(result.getText().to_string())
+    let points = result.getPoints().iter().map(|p| (p.x, p.y)).collect();
+    Ok((result.getText().to_string(), points))
+}
+
+/// Contrast factors tried by `check_readability`, strongest first.
+#[cfg(feature = "decode")]
+const CONTRAST_STEPS: [f32; 6] = [0.75, 0.5, 0.35, 0.25, 0.15, 0.1];
+
+/// Result of `check_readability`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Readability {
+    /// Decoded text, `None` if the image does not scan
+    pub text: Option<String>,
+    /// How much contrast can be lost before decoding fails, 0.0 - 1.0.
+    /// 0.0 when unreadable; 0.9 means it still scans at 10% contrast.
+    pub margin: f32,
+}
+
+impl Readability {
+    pub fn is_scannable(&self) -> bool {
+        self.text.is_some()
+    }
+}
+
+/// Decode a grayscale image and estimate how close it is to unreadable
+///
+/// The image is re-decoded with its contrast progressively squeezed toward
+/// mid-gray; the margin is the largest reduction that still decodes.
+#[cfg(feature = "decode")]
+pub fn check_readability(luma: &[u8], width: u32, height: u32) -> Readability {
+    let Ok(text) = decode_luma(luma.to_vec(), width, height) else {
+        return Readability { text: None, margin: 0.0 };
+    };
+
+    let mut margin = 0.0;
+    for factor in CONTRAST_STEPS {
+        let squeezed = luma
+            .iter()
+            .map(|&l| (128.0 + (l as f32 - 128.0) * factor).round() as u8)
+            .collect();
+        if decode_luma(squeezed, width, height).ok().as_deref() != Some(text.as_str()) {
+            break;
+        }
+        margin = 1.0 - factor;
+    }
+    Readability { text: Some(text), margin }
 }
 
 /// Decode a QR code from raw image bytes (PNG/JPEG)
@@ -98,12 +358,6 @@ pub fn verify_svg(svg: &str) -> Result<String, QrError> {
 #[cfg(feature = "verify")]
 pub fn decode_image(image_data: &[u8]) -> Result<String, QrError> {
     use image::GenericImageView;
-    use rxing::{BarcodeFormat, DecodeHintType, DecodeHintValue};
-    use rxing::common::HybridBinarizer;
-    use rxing::BinaryBitmap;
-    use rxing::Luma8LuminanceSource;
-    use rxing::MultiFormatReader;
-    use rxing::Reader;
     
     // Load image
     let img = image::load_from_memory(image_data)
@@ -115,27 +369,7 @@ pub fn decode_image(image_data: &[u8]) -> Result<String, QrError> {
     let gray = img.to_luma8();
     let luma: Vec<u8> = gray.into_raw();
     
-    // Create rxing source
-    let source = Luma8LuminanceSource::new(luma, width, height);
-    let mut bitmap = BinaryBitmap::new(HybridBinarizer::new(source));
-    
-    // Configure hints
-    let mut hints = rxing::DecodingHintDictionary::new();
-    hints.insert(
-        DecodeHintType::POSSIBLE_FORMATS,
-        DecodeHintValue::PossibleFormats(vec![BarcodeFormat::QR_CODE].into_iter().collect()),
-    );
-    hints.insert(
-        DecodeHintType::TRY_HARDER,
-        DecodeHintValue::TryHarder(true),
-    );
-    
-    // Decode
-    let mut reader = MultiFormatReader::default();
-    let result = reader.decode_with_hints(&mut bitmap, &hints)
-        .map_err(|e| QrError::VerificationFailed(format!("Decode error: {:?}", e)))?;;
-    
-    Ok(result.getText().to_string())
+    decode_luma(luma, width, height)
 }
 
 /// Stub function when 'verify' feature is not enabled
@@ -146,6 +380,12 @@ pub fn verify_svg(_svg: &str) -> Result<String, QrError> {
     ))
 }
 
+/// Stub function when 'verify' feature is not enabled (every SVG fails)
+#[cfg(not(feature = "verify"))]
+pub fn verify_batch(svgs: &[String]) -> Vec<VerifyResult> {
+    svgs.iter().map(|svg| verify_svg(svg)).collect()
+}
+
 /// Stub function when 'verify' feature is not enabled
 #[cfg(not(feature = "verify"))]
 pub fn decode_image(_image_data: &[u8]) -> Result<String, QrError> {
@@ -154,6 +394,36 @@ pub fn decode_image(_image_data: &[u8]) -> Result<String, QrError> {
     ))
 }
 
+/// Stub function when 'decode' feature is not enabled
+#[cfg(not(feature = "decode"))]
+pub fn decode_luma(_luma: Vec<u8>, _width: u32, _height: u32) -> Result<String, QrError> {
+    Err(QrError::VerificationFailed(
+        "Decoding not available. Enable 'verify' feature.".into()
+    ))
+}
+
+/// Stub function when 'decode' feature is not enabled (always unreadable)
+#[cfg(not(feature = "decode"))]
+pub fn check_readability(_luma: &[u8], _width: u32, _height: u32) -> Readability {
+    Readability { text: None, margin: 0.0 }
+}
+
+/// Stub function when 'png' feature is not enabled
+#[cfg(not(feature = "png"))]
+pub fn rasterize_svg(_svg: &str, _width: u32) -> Result<Vec<u8>, QrError> {
+    Err(QrError::RenderFailed(
+        "Rasterization not available. Enable 'png' feature.".into()
+    ))
+}
+
+/// Stub function when 'png' feature is not enabled
+#[cfg(not(feature = "png"))]
+pub fn render_png(_qr: &QrCode, _options: &StyledRenderOptions) -> Result<Vec<u8>, QrError> {
+    Err(QrError::RenderFailed(
+        "PNG rendering not available. Enable 'png' feature.".into()
+    ))
+}
+
 #[cfg(all(test, feature = "verify"))]
 mod tests {
     use super::*;
@@ -184,4 +454,109 @@ mod tests {
         let decoded = verify_svg(&svg).expect("Dots shape should be scannable");
         assert_eq!(decoded, text);
     }
+
+    #[test]
+    fn test_verify_liquid_all_ecc_levels() {
+        use crate::BodyShape;
+
+        let text = "https://holi.tools/liquid?ref=verify";
+        for ecl in [
+            ErrorCorrectionLevel::Low,
+            ErrorCorrectionLevel::Medium,
+            ErrorCorrectionLevel::Quartile,
+            ErrorCorrectionLevel::High,
+        ] {
+            let qr = generate_qr(text, ecl).unwrap();
+            let options = StyledRenderOptions {
+                body_shape: BodyShape::Liquid,
+                ..Default::default()
+            };
+            let svg = render_svg_styled(&qr, &options);
+
+            let decoded = verify_svg(&svg).unwrap_or_else(|e| panic!("Liquid at {:?}: {}", ecl, e));
+            assert_eq!(decoded, text);
+        }
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let texts = ["label-1", "label-2", "label-3"];
+        let mut svgs: Vec<String> = texts
+            .iter()
+            .map(|t| render_svg_styled(&generate_qr(t, ErrorCorrectionLevel::Medium).unwrap(), &Default::default()))
+            .collect();
+        svgs.insert(1, "not svg".to_string());
+        svgs.push(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"/>"#.to_string());
+
+        let results = verify_batch(&svgs);
+        assert_eq!(results.len(), svgs.len());
+        let decoded: Vec<_> = results.iter().map(|r| r.as_deref().ok()).collect();
+        assert_eq!(decoded, [Some("label-1"), None, Some("label-2"), Some("label-3"), None]);
+        for (svg, result) in svgs.iter().zip(&results) {
+            assert_eq!(verify_svg(svg).ok(), result.as_ref().ok().cloned());
+        }
+
+        let stats = BatchStats::from_results(&results);
+        assert_eq!(stats, BatchStats { total: 5, scannable: 3, failed: vec![1, 4] });
+        assert!(!stats.all_scannable());
+        assert!(verify_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_rasterize_png() {
+        let qr = generate_qr("raster", ErrorCorrectionLevel::Medium).unwrap();
+        let svg = render_svg_styled(&qr, &StyledRenderOptions::default());
+
+        let png = rasterize_svg(&svg, 512).expect("Should rasterize");
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let decoded = decode_image(&png).expect("Raster output should scan");
+        assert_eq!(decoded, "raster");
+
+        assert!(rasterize_svg(&svg, 0).is_err());
+        assert!(rasterize_svg("not svg", 512).is_err());
+
+        assert!(rasterize_svg(&svg, MAX_RASTER_SIZE).is_ok());
+        assert!(matches!(rasterize_svg(&svg, MAX_RASTER_SIZE + 1), Err(QrError::InvalidArgument(_))));
+        let tall = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="1000"/>"#;
+        assert!(matches!(rasterize_svg(tall, 100), Err(QrError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_render_png() {
+        let qr = generate_qr("render png", ErrorCorrectionLevel::Medium).unwrap();
+        let options = StyledRenderOptions { margin: 2, ..Default::default() };
+
+        let png = render_png(&qr, &options).expect("Should render");
+        let image = image::load_from_memory(&png).unwrap();
+        let side = ((qr.size() + 4) * PNG_MODULE_PIXELS) as u32;
+        assert_eq!((image.width(), image.height()), (side, side));
+        assert_eq!(decode_image(&png).unwrap(), "render png");
+
+        let too_wide = StyledRenderOptions { margin: crate::MAX_MARGIN + 1, ..Default::default() };
+        assert!(matches!(render_png(&qr, &too_wide), Err(QrError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_readability_margin() {
+        let qr = generate_qr("margin", ErrorCorrectionLevel::Medium).unwrap();
+        let svg = render_svg_styled(&qr, &StyledRenderOptions::default());
+        let pixmap = render_pixmap(&svg, 400, Some(400), true).unwrap();
+        let luma = rgba_to_luma(pixmap.data());
+        assert_eq!(luma.len(), 400 * 400);
+
+        let crisp = check_readability(&luma, 400, 400);
+        assert_eq!(crisp.text.as_deref(), Some("margin"));
+        assert!(crisp.margin >= 0.5, "margin {}", crisp.margin);
+
+        // Washed out toward mid-gray: still scans, with less headroom
+        let faded: Vec<u8> = luma.iter().map(|&l| (128 + (l as i32 - 128) / 4) as u8).collect();
+        let faded = check_readability(&faded, 400, 400);
+        assert!(faded.margin < crisp.margin);
+
+        let blank = check_readability(&vec![255; 400 * 400], 400, 400);
+        assert!(!blank.is_scannable());
+        assert_eq!(blank.margin, 0.0);
+        assert!(decode_luma(vec![0; 10], 400, 400).is_err());
+    }
 }
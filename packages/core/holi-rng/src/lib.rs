@@ -0,0 +1,135 @@
+//! # Holi RNG
+//!
+//! Injectable randomness, so every key and nonce drawn by the wasm packages
+//! can be reproduced in tests.
+//!
+//! Production builds always read from `OsRng`. Builds with the `test-rng`
+//! feature (and unit tests) can install a seeded ChaCha20 stream so that
+//! envelopes and keys are reproducible against fixed vectors. The stream is
+//! per thread, and each wasm module has its own: seed the module whose
+//! output you check.
+
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+
+#[cfg(any(test, feature = "test-rng"))]
+use rand::SeedableRng;
+#[cfg(any(test, feature = "test-rng"))]
+use rand_chacha::ChaCha20Rng;
+#[cfg(any(test, feature = "test-rng"))]
+use std::cell::RefCell;
+#[cfg(feature = "test-rng")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(any(test, feature = "test-rng"))]
+thread_local! {
+    static SEEDED_RNG: RefCell<Option<ChaCha20Rng>> = const { RefCell::new(None) };
+}
+
+/// RNG handle used by all crypto APIs.
+///
+/// Delegates to the seeded test stream when one is installed, otherwise `OsRng`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HoliRng;
+
+impl RngCore for HoliRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0u8; 4];
+        self.fill_bytes(&mut buf);
+        u32::from_le_bytes(buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.fill_bytes(&mut buf);
+        u64::from_le_bytes(buf)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        #[cfg(any(test, feature = "test-rng"))]
+        {
+            let seeded = SEEDED_RNG.with(|r| match r.borrow_mut().as_mut() {
+                Some(rng) => {
+                    rng.fill_bytes(dest);
+                    true
+                }
+                None => false,
+            });
+            if seeded {
+                return;
+            }
+        }
+        OsRng.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+// Sound because the only non-test source is OsRng; the seeded stream is
+// compiled out of production builds.
+impl CryptoRng for HoliRng {}
+
+/// Fill a fixed-size array from `HoliRng`.
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut out = [0u8; N];
+    HoliRng.fill_bytes(&mut out);
+    out
+}
+
+/// Install a deterministic RNG seeded with `seed` for the current thread.
+#[cfg(any(test, feature = "test-rng"))]
+pub fn seed_test_rng(seed: [u8; 32]) {
+    SEEDED_RNG.with(|r| *r.borrow_mut() = Some(ChaCha20Rng::from_seed(seed)));
+}
+
+/// Remove the deterministic RNG and go back to `OsRng`.
+#[cfg(any(test, feature = "test-rng"))]
+pub fn reset_test_rng() {
+    SEEDED_RNG.with(|r| *r.borrow_mut() = None);
+}
+
+/// Seed the crypto RNG from JS (32 bytes). Only available with `test-rng`.
+#[cfg(feature = "test-rng")]
+#[wasm_bindgen]
+pub fn set_test_rng_seed(seed: &[u8]) -> Result<(), JsValue> {
+    if seed.len() != 32 {
+        return Err(JsValue::from_str("Seed must be 32 bytes"));
+    }
+    let mut s = [0u8; 32];
+    s.copy_from_slice(seed);
+    seed_test_rng(s);
+    Ok(())
+}
+
+/// Restore `OsRng` after `set_test_rng_seed`. Only available with `test-rng`.
+#[cfg(feature = "test-rng")]
+#[wasm_bindgen]
+pub fn clear_test_rng() {
+    reset_test_rng();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_rng_is_reproducible() {
+        seed_test_rng([7u8; 32]);
+        let a: [u8; 32] = random_bytes();
+        seed_test_rng([7u8; 32]);
+        let b: [u8; 32] = random_bytes();
+        reset_test_rng();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_unseeded_rng_differs() {
+        reset_test_rng();
+        let a: [u8; 32] = random_bytes();
+        let b: [u8; 32] = random_bytes();
+        assert_ne!(a, b);
+    }
+}
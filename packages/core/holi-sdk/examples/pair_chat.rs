@@ -0,0 +1,536 @@
+//! Pairing and chat between two native processes over TCP.
+//!
+//! The host listens and shows a pairing QR; the guest connects with the
+//! blob from that QR. Both run SPAKE2 over the code in the blob, confirm the
+//! key with an encrypted Hello, exchange a chat message each, send a
+//! Merkle-checked file from host to guest and end with a Goodbye. Every
+//! frame after the handshake travels in an EncryptedEnvelope.
+//!
+//! ```text
+//! cargo run --example pair_chat --features crypto -- host [lan-ip:port]
+//! cargo run --example pair_chat --features crypto -- join 'holi-pair:v1?...'
+//! cargo run --example pair_chat --features crypto
+//! ```
+//!
+//! With no arguments the host starts the guest as a child process and exits
+//! non-zero if either side fails, which is how CI runs it. With `qr-verify`
+//! the guest's blob is scanned back from a PNG of the QR instead of being
+//! passed on as text.
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+use holi_sdk::crypto::{Spake2A, Spake2B};
+use holi_sdk::p2p::frame::{
+    decode_chat_text_frame_v1, decode_encrypted_envelope_frame_v1, decode_file_accept_payload_v1,
+    decode_file_chunk_frame_v1, decode_file_end_payload_v1, decode_file_offer_payload_v1,
+    decode_goodbye_payload_v1, decode_hello_payload_v1, decode_v1, encode_chat_text_v1,
+    encode_encrypted_envelope_v1, encode_file_accept_v1, encode_file_chunk_v1,
+    encode_file_chunk_with_proof_v1, encode_file_end_v1, encode_file_offer_v1, encode_goodbye_v1,
+    encode_hello_v1, CipherSuite, FileOffer, Frame, FrameType, Goodbye, GoodbyeReason, Hello,
+    MerkleRoot, HELLO_CAP_MERKLE_PROOFS,
+};
+use holi_sdk::p2p::merkle::{leaf_count, verify_leaf, MerkleTree};
+use holi_sdk::p2p::session::SessionEnd;
+use holi_sdk::p2p::transport::{Transport, TransportError};
+use holi_sdk::qr::{generate_qr, ErrorCorrectionLevel, QrCode};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const BLOB_PREFIX: &str = "holi-pair:v1?";
+/// Largest frame either side accepts.
+const MAX_FRAME: u32 = 1 << 20;
+/// FileChunk data size, and the Merkle leaf size.
+const CHUNK_SIZE: u32 = 16 * 1024;
+/// How long to wait for the peer's next message.
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the pairing QR carries: where to connect, the code both sides feed
+/// to SPAKE2, and a room id that binds the handshake to this pairing.
+#[derive(Debug, Clone, PartialEq)]
+struct PairingBlob {
+    addr: String,
+    code: String,
+    room: String,
+}
+
+impl PairingBlob {
+    fn encode(&self) -> String {
+        format!(
+            "{}addr={}&code={}&room={}",
+            BLOB_PREFIX, self.addr, self.code, self.room
+        )
+    }
+
+    fn parse(blob: &str) -> Result<Self> {
+        let query = blob
+            .strip_prefix(BLOB_PREFIX)
+            .ok_or("not a holi pairing blob")?;
+        let field = |name: &str| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+                .map(str::to_string)
+                .ok_or_else(|| format!("pairing blob has no {}", name))
+        };
+        Ok(Self {
+            addr: field("addr")?,
+            code: field("code")?,
+            room: field("room")?,
+        })
+    }
+
+    /// SPAKE2 identities of the host (role A) and the guest (role B).
+    fn identities(&self) -> (Vec<u8>, Vec<u8>) {
+        (
+            format!("holi:pair:host:{}", self.room).into_bytes(),
+            format!("holi:pair:guest:{}", self.room).into_bytes(),
+        )
+    }
+}
+
+/// `Transport` over a TCP stream. Messages are framed with a 4-byte
+/// big-endian length; a reader thread queues them for `poll_recv`.
+struct TcpTransport {
+    stream: TcpStream,
+    inbox: Receiver<Vec<u8>>,
+}
+
+impl TcpTransport {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let mut reader = stream.try_clone()?;
+        let (tx, inbox) = mpsc::channel();
+        thread::spawn(move || {
+            let mut len = [0u8; 4];
+            while reader.read_exact(&mut len).is_ok() {
+                let len = u32::from_be_bytes(len);
+                if len > MAX_FRAME + 64 {
+                    break;
+                }
+                let mut message = vec![0u8; len as usize];
+                if reader.read_exact(&mut message).is_err() || tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self { stream, inbox })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send_bytes(&mut self, bytes: &[u8]) -> std::result::Result<(), TransportError> {
+        let len = u32::try_from(bytes.len())
+            .map_err(|_| TransportError::Backend("message too large".into()))?;
+        let mut message = Vec::with_capacity(4 + bytes.len());
+        message.extend_from_slice(&len.to_be_bytes());
+        message.extend_from_slice(bytes);
+        self.stream.write_all(&message).map_err(|e| match e.kind() {
+            io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::NotConnected => TransportError::Closed,
+            _ => TransportError::Backend(e.to_string()),
+        })
+    }
+
+    fn poll_recv(&mut self) -> std::result::Result<Option<Vec<u8>>, TransportError> {
+        match self.inbox.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(TransportError::Closed),
+        }
+    }
+
+    fn close(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Wait for the next message; `Ok(None)` once the transport closed.
+fn recv_raw(transport: &mut dyn Transport) -> Result<Option<Vec<u8>>> {
+    let deadline = Instant::now() + RECV_TIMEOUT;
+    loop {
+        match transport.poll_recv() {
+            Ok(Some(message)) => return Ok(Some(message)),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(1)),
+            Ok(None) => return Err("timed out waiting for the peer".into()),
+            Err(TransportError::Closed) => return Ok(None),
+            Err(e) => return Err(format!("transport failed: {:?}", e).into()),
+        }
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<Frame> {
+    let (frame, _used) = decode_v1(bytes, MAX_FRAME).map_err(|e| format!("bad frame: {:?}", e))?;
+    Ok(frame)
+}
+
+/// One side of a paired session: frames go out and come in sealed under
+/// the SPAKE2 session key.
+struct Session<T: Transport> {
+    transport: T,
+    cipher: XChaCha20Poly1305,
+    goodbye: Option<Goodbye>,
+    /// What both sides' Hellos allow.
+    allowed: Hello,
+}
+
+impl<T: Transport> Session<T> {
+    /// Run SPAKE2 as the host (`initiator`) or the guest, then check both
+    /// sides derived the same key by exchanging encrypted Hellos.
+    fn pair(mut transport: T, blob: &PairingBlob, initiator: bool) -> Result<Self> {
+        enum Pake {
+            Host(Spake2A),
+            Guest(Spake2B),
+        }
+        let (id_a, id_b) = blob.identities();
+        let code = blob.code.as_bytes();
+        let start_failed = |_| "SPAKE2 start failed";
+        let mut pake = if initiator {
+            Pake::Host(Spake2A::new(code, &id_a, &id_b).map_err(start_failed)?)
+        } else {
+            Pake::Guest(Spake2B::new(code, &id_a, &id_b).map_err(start_failed)?)
+        };
+        let ours = match &pake {
+            Pake::Host(a) => a.message(),
+            Pake::Guest(b) => b.message(),
+        };
+        transport
+            .send_bytes(&ours)
+            .map_err(|e| format!("{:?}", e))?;
+        let theirs = recv_raw(&mut transport)?.ok_or("peer left during pairing")?;
+        // The crypto types report errors as `JsValue`, which only exists in
+        // WASM; reject what `finish` would refuse before calling it.
+        if theirs.len() != ours.len() || theirs.first() == ours.first() {
+            return Err("malformed SPAKE2 message".into());
+        }
+        let finished = match &mut pake {
+            Pake::Host(a) => a.finish(&theirs),
+            Pake::Guest(b) => b.finish(&theirs),
+        };
+        let key = finished.map_err(|_| "SPAKE2 failed")?;
+
+        let mut session = Self {
+            transport,
+            cipher: XChaCha20Poly1305::new_from_slice(&key)
+                .map_err(|_| "session key must be 32 bytes")?,
+            goodbye: None,
+            allowed: Hello::default(),
+        };
+        let hello = Hello {
+            capabilities: HELLO_CAP_MERKLE_PROOFS,
+            max_chunk_size: Some(CHUNK_SIZE),
+        };
+        session.send(&encode_hello_v1(&hello))?;
+        let frame = session
+            .recv()
+            .map_err(|e| format!("key confirmation failed, the pairing codes differ? ({})", e))?
+            .ok_or("peer left during pairing")?;
+        if frame.frame_type != FrameType::Hello {
+            return Err(format!("expected Hello, got {:?}", frame.frame_type).into());
+        }
+        let theirs = decode_hello_payload_v1(&frame.payload).map_err(|e| format!("{:?}", e))?;
+        session.allowed = hello.intersect(&theirs);
+        println!("paired; session allows {:?}", session.allowed);
+        Ok(session)
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<()> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, frame)
+            .map_err(|_| "encryption failed")?;
+        let envelope = encode_encrypted_envelope_v1(&nonce.into(), &ciphertext);
+        self.transport
+            .send_bytes(&envelope)
+            .map_err(|e| format!("send failed: {:?}", e))?;
+        Ok(())
+    }
+
+    /// Next frame from the peer, unsealed; `Ok(None)` once the channel
+    /// closed. A Goodbye is kept for `end`, not returned.
+    fn recv(&mut self) -> Result<Option<Frame>> {
+        loop {
+            let Some(bytes) = recv_raw(&mut self.transport)? else {
+                return Ok(None);
+            };
+            let outer = decode(&bytes)?;
+            if outer.frame_type != FrameType::EncryptedEnvelope {
+                return Err(
+                    format!("unencrypted {:?} frame after pairing", outer.frame_type).into(),
+                );
+            }
+            let envelope =
+                decode_encrypted_envelope_frame_v1(&outer).map_err(|e| format!("{:?}", e))?;
+            if envelope.suite != CipherSuite::XChaCha20Poly1305 {
+                return Err("cipher suite was not negotiated".into());
+            }
+            let inner = self
+                .cipher
+                .decrypt(
+                    XNonce::from_slice(&envelope.nonce),
+                    envelope.ciphertext.as_slice(),
+                )
+                .map_err(|_| "envelope did not decrypt")?;
+            let frame = decode(&inner)?;
+            if frame.frame_type == FrameType::Goodbye {
+                self.goodbye = Some(
+                    decode_goodbye_payload_v1(&frame.payload).map_err(|e| format!("{:?}", e))?,
+                );
+                continue;
+            }
+            return Ok(Some(frame));
+        }
+    }
+
+    fn expect(&mut self, frame_type: FrameType) -> Result<Frame> {
+        match self.recv()? {
+            Some(frame) if frame.frame_type == frame_type => Ok(frame),
+            Some(frame) => {
+                Err(format!("expected {:?}, got {:?}", frame_type, frame.frame_type).into())
+            }
+            None => Err(format!("channel closed waiting for {:?}", frame_type).into()),
+        }
+    }
+
+    fn chat(&mut self, text: &str) -> Result<()> {
+        println!("> {}", text);
+        self.send(&encode_chat_text_v1(text))
+    }
+
+    fn read_chat(&mut self) -> Result<String> {
+        let frame = self.expect(FrameType::ChatText)?;
+        let text = decode_chat_text_frame_v1(&frame)
+            .map_err(|e| format!("{:?}", e))?
+            .text;
+        println!("< {}", text);
+        Ok(text)
+    }
+
+    /// Leave on purpose: Goodbye, then close.
+    fn leave(mut self) -> Result<()> {
+        self.send(&encode_goodbye_v1(&Goodbye {
+            reason: GoodbyeReason::UserLeft,
+            resume_ticket: Vec::new(),
+        }))?;
+        self.transport.close();
+        Ok(())
+    }
+
+    /// Wait for the peer to close and say how the session ended.
+    fn end(mut self) -> Result<SessionEnd> {
+        if let Some(frame) = self.recv()? {
+            return Err(format!("unexpected {:?} before close", frame.frame_type).into());
+        }
+        self.transport.close();
+        Ok(SessionEnd::on_close(self.goodbye))
+    }
+}
+
+/// Deterministic file contents, so the guest can check what arrived.
+fn sample_file() -> Vec<u8> {
+    (0..100_000u32)
+        .map(|i| (i.wrapping_mul(31) ^ (i >> 7)) as u8)
+        .collect()
+}
+
+fn send_file<T: Transport>(session: &mut Session<T>, id: &str, data: &[u8]) -> Result<()> {
+    let tree = MerkleTree::from_data(data, CHUNK_SIZE);
+    session.send(&encode_file_offer_v1(&FileOffer {
+        id: id.to_string(),
+        filename: "sample.bin".to_string(),
+        mime_type: "application/octet-stream".to_string(),
+        size: data.len() as u64,
+        merkle: Some(MerkleRoot {
+            leaf_size: CHUNK_SIZE,
+            root: tree.root(),
+        }),
+        thumbnail: None,
+    }))?;
+    let accepted = session.expect(FrameType::FileAccept)?;
+    if decode_file_accept_payload_v1(&accepted.payload).map_err(|e| format!("{:?}", e))? != id {
+        return Err("peer accepted another file".into());
+    }
+    // Older peers would read a proof as chunk data
+    let proofs = session.allowed.supports(HELLO_CAP_MERKLE_PROOFS);
+    for (index, chunk) in data.chunks(CHUNK_SIZE as usize).enumerate() {
+        let frame = if proofs {
+            let proof = tree.proof(index).ok_or("no Merkle proof for chunk")?;
+            encode_file_chunk_with_proof_v1(id, index as u32, &proof, chunk)
+                .map_err(|e| format!("{:?}", e))?
+        } else {
+            encode_file_chunk_v1(id, index as u32, chunk)
+        };
+        session.send(&frame)?;
+    }
+    session.send(&encode_file_end_v1(id))?;
+    println!("sent {} bytes in {} chunks", data.len(), tree.leaf_count());
+    Ok(())
+}
+
+/// Accept the next offered file and check every chunk against its root, or
+/// the whole file if the peer sends no proofs.
+fn receive_file<T: Transport>(session: &mut Session<T>) -> Result<Vec<u8>> {
+    let frame = session.expect(FrameType::FileOffer)?;
+    let offer = decode_file_offer_payload_v1(&frame.payload).map_err(|e| format!("{:?}", e))?;
+    let merkle = offer.merkle.ok_or("offer has no Merkle root")?;
+    let leaves = leaf_count(offer.size, merkle.leaf_size);
+    println!("receiving {} ({} bytes)", offer.filename, offer.size);
+    session.send(&encode_file_accept_v1(&offer.id))?;
+
+    let proofs = session.allowed.supports(HELLO_CAP_MERKLE_PROOFS);
+    let mut data = Vec::with_capacity(offer.size.min(1 << 24) as usize);
+    loop {
+        let frame = session.recv()?.ok_or("channel closed mid-transfer")?;
+        match frame.frame_type {
+            FrameType::FileChunk => {
+                let chunk = decode_file_chunk_frame_v1(&frame).map_err(|e| format!("{:?}", e))?;
+                let index = chunk.chunk_index as u64;
+                if index != (data.len() / merkle.leaf_size as usize) as u64
+                    || (proofs
+                        && !verify_leaf(&merkle.root, leaves, index, &chunk.data, &chunk.proof))
+                {
+                    return Err(format!("chunk {} failed verification", index).into());
+                }
+                data.extend_from_slice(&chunk.data);
+            }
+            FrameType::FileEnd => {
+                let id =
+                    decode_file_end_payload_v1(&frame.payload).map_err(|e| format!("{:?}", e))?;
+                if id != offer.id || data.len() as u64 != offer.size {
+                    return Err("transfer ended early".into());
+                }
+                if !proofs && MerkleTree::from_data(&data, merkle.leaf_size).root() != merkle.root {
+                    return Err("file does not match its Merkle root".into());
+                }
+                return Ok(data);
+            }
+            other => return Err(format!("unexpected {:?} during transfer", other).into()),
+        }
+    }
+}
+
+/// The QR in the terminal, two modules per line, light modules drawn.
+fn print_qr(qr: &QrCode) {
+    const MARGIN: usize = 2;
+    let matrix = qr.matrix();
+    let n = qr.size();
+    let dark = |x: usize, y: usize| {
+        (MARGIN..n + MARGIN).contains(&x)
+            && (MARGIN..n + MARGIN).contains(&y)
+            && matrix.is_dark(x - MARGIN, y - MARGIN)
+    };
+    for y in (0..n + 2 * MARGIN).step_by(2) {
+        let line: String = (0..n + 2 * MARGIN)
+            .map(
+                |x| match (dark(x, y), dark(x, y + 1) || y + 1 == n + 2 * MARGIN) {
+                    (true, true) => ' ',
+                    (true, false) => '▄',
+                    (false, true) => '▀',
+                    (false, false) => '█',
+                },
+            )
+            .collect();
+        println!("{}", line);
+    }
+}
+
+/// What a guest learns from scanning `qr`.
+#[cfg(feature = "qr-verify")]
+fn scan(qr: &QrCode, _blob: &str) -> Result<String> {
+    use holi_sdk::qr::{decode_image, render_png, StyledRenderOptions};
+    let png = render_png(qr, &StyledRenderOptions::default())?;
+    Ok(decode_image(&png)?)
+}
+
+#[cfg(not(feature = "qr-verify"))]
+fn scan(_qr: &QrCode, blob: &str) -> Result<String> {
+    Ok(blob.to_string())
+}
+
+fn host(bind: &str, spawn_guest: bool) -> Result<()> {
+    let listener = TcpListener::bind(bind)?;
+    let mut room = [0u8; 8];
+    OsRng.fill_bytes(&mut room);
+    let pairing = PairingBlob {
+        addr: listener.local_addr()?.to_string(),
+        code: format!("{:06}", OsRng.next_u32() % 1_000_000),
+        room: room.iter().map(|b| format!("{:02x}", b)).collect(),
+    };
+    let blob = pairing.encode();
+    let qr = generate_qr(&blob, ErrorCorrectionLevel::Medium)?;
+    print_qr(&qr);
+    println!("{}", blob);
+
+    let guest = if spawn_guest {
+        let scanned = scan(&qr, &blob)?;
+        Some(
+            Command::new(std::env::current_exe()?)
+                .arg("join")
+                .arg(scanned)
+                .spawn()?,
+        )
+    } else {
+        None
+    };
+
+    let (stream, peer) = listener.accept()?;
+    println!("guest connected from {}", peer);
+    let mut session = Session::pair(TcpTransport::new(stream)?, &pairing, true)?;
+    session.chat("hola from the host")?;
+    session.read_chat()?;
+    send_file(&mut session, "sample-1", &sample_file())?;
+    session.read_chat()?;
+    session.leave()?;
+
+    if let Some(mut guest) = guest {
+        let status = guest.wait()?;
+        if !status.success() {
+            return Err(format!("guest failed: {}", status).into());
+        }
+        println!("end-to-end check passed");
+    }
+    Ok(())
+}
+
+fn join(blob: &str) -> Result<()> {
+    let blob = PairingBlob::parse(blob)?;
+    let stream = TcpStream::connect(&blob.addr)?;
+    let mut session = Session::pair(TcpTransport::new(stream)?, &blob, false)?;
+    session.read_chat()?;
+    session.chat("hola from the guest")?;
+    let data = receive_file(&mut session)?;
+    if data != sample_file() {
+        return Err("file contents differ".into());
+    }
+    session.chat(&format!("got all {} bytes", data.len()))?;
+    match session.end()? {
+        SessionEnd::PeerLeft(goodbye) => println!("host left ({})", goodbye.reason.name()),
+        SessionEnd::ConnectionLost => return Err("connection lost without a Goodbye".into()),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => host("127.0.0.1:0", true),
+        ["host"] => host("127.0.0.1:0", false),
+        ["host", addr] => host(addr, false),
+        ["join", blob] => join(blob),
+        _ => Err("usage: pair_chat [host [addr] | join <blob>]".into()),
+    };
+    if let Err(e) = result {
+        eprintln!("pair_chat: {}", e);
+        std::process::exit(1);
+    }
+}
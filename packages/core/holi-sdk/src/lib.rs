@@ -0,0 +1,271 @@
+//! # Holi SDK
+//!
+//! One dependency for native Rust users of Holi.tools.
+//!
+//! The internal crates (`holi-qr`, `holi-p2p`, the WASM crypto package) are
+//! free to move things around; this crate re-exports the parts that are
+//! stable and follows semver for them. Anything not reachable from here is
+//! internal and may change without notice.
+//!
+//! ## Features
+//!
+//! | Feature        | Default | Module     |
+//! |----------------|---------|------------|
+//! | `qr`           | yes     | [`qr`]     |
+//! | `qr-png`       | no      | PNG export in [`qr`] |
+//! | `qr-verify`    | no      | `qr-png`, plus scanning and verification |
+//! | `qr-parallel`  | no      | `qr-verify`, with `verify_batch` on a rayon pool |
+//! | `qr-shift-jis` | no      | Shift-JIS for `generate_qr_eci` and `generate_qr_kanji` |
+//! | `p2p`          | yes     | [`p2p`]    |
+//! | `crypto`       | no      | `crypto`   |
+//!
+//! ## Example
+//!
+//! ```rust
+//! use holi_sdk::qr::{generate_qr, render_svg, ErrorCorrectionLevel};
+//!
+//! let qr = generate_qr("https://holi.tools", ErrorCorrectionLevel::Medium).unwrap();
+//! let svg = render_svg(&qr);
+//! assert!(svg.starts_with("<svg"));
+//! ```
+//!
+//! `examples/pair_chat.rs` wires the pieces together natively: two processes
+//! pair over TCP from a QR-encoded blob, then chat and send a file.
+
+/// Version of the SDK surface.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// QR generation, styling and presets, payload building and classification,
+/// and signed QR payloads.
+#[cfg(feature = "qr")]
+pub mod qr {
+    pub use holi_qr::{
+        analyze_frames, body_path, capacity, check_logo, classify_payload, contour_body_path,
+        enforce_accessible_theme, eye_ball_path, eye_frame_path, fit_best_ecc, frame_stats,
+        generate_qr, generate_qr_bytes, generate_qr_constrained, generate_qr_eci,
+        generate_qr_kanji, generate_qr_structured, generate_wifi_qr, liquid_body_path,
+        optimize_payload, preset, print_requirements, print_requirements_at_dpi, render_artistic,
+        render_artistic_svg, render_stl, render_svg, render_svg_layout, render_svg_styled,
+        render_svg_with_options, render_with_preset, shape_substitutions, try_render_svg_layout,
+        try_render_svg_styled, try_render_svg_with_options, validate_options, AccessibilityPolicy,
+        AccessibleTheme, ArtisticOptions, ArtisticQr, BackgroundPattern, BodyShape, Charset,
+        DebugLayout, ErrorCorrectionLevel, ExtrudeOptions, EyeBallShape, EyeFrameShape, FrameStats,
+        GeoPoint, Gradient, GradientKind, GradientStop, Logo, LogoFit, LogoImage, MailTo, Mode,
+        ModuleMatrix, ModuleRole, OutlineStyle, PatternKind, PayloadClassification, PayloadHint,
+        PayloadHintKind, PayloadKind, PayloadRisk, PrintRequirements, QrCode, QrError,
+        RenderOptions, ScanAnalysis, ScanHint, Segment, SegmentMode, Severity, ShapeSubstitution,
+        SmsMessage, StylePreset, StyledRenderOptions, Tel, ThemeAdjustment, VEvent,
+        ValidationIssue, WifiAuth, WifiCredentials, DEFAULT_MIN_CONTRAST, DEFAULT_MIN_MODULE_PX,
+        MAX_LOGO_SIZE, MAX_STRUCTURED_SYMBOLS, PRESETS,
+    };
+
+    /// Signed QR payloads, verifiable offline.
+    pub mod signed {
+        pub use holi_qr::{
+            encode_signed_payload, generate_signed_qr, signed_qr_key_id, signed_qr_message,
+            split_signed_payload, QrSigner, SignedPayload, KEY_ID_LEN, SIGNATURE_LEN,
+            SIGNED_TRAILER_LEN,
+        };
+    }
+
+    #[cfg(feature = "qr-png")]
+    pub use holi_qr::{rasterize_svg, render_png, PNG_MODULE_PIXELS};
+
+    #[cfg(feature = "qr-verify")]
+    pub use holi_qr::{
+        check_readability, decode_image, decode_luma, diagnose_luma, render_svg_with_logo,
+        verify_batch, verify_svg, BatchStats, DecodeDiagnostics, DecodeStage, Readability,
+        VerifyResult,
+    };
+}
+
+/// P2P wire format:
+///
+/// - [`frame`](p2p::frame): frame encoding and decoding
+/// - [`session`](p2p::session): session close semantics
+/// - [`transport`](p2p::transport): pluggable byte transports
+/// - [`keepalive`](p2p::keepalive): heartbeats and clock sync
+/// - [`merkle`](p2p::merkle): Merkle file hashing
+/// - [`compressibility`](p2p::compressibility): compressibility checks
+/// - [`padding`](p2p::padding), [`rekey`](p2p::rekey): envelope padding and key rotation
+/// - [`ephemeral`](p2p::ephemeral): burn-after-reading chat bookkeeping
+/// - [`outbound`](p2p::outbound), [`offload`](p2p::offload): frame prioritization and worker offload
+/// - [`schema`](p2p::schema), [`journal`](p2p::journal): frame schema and session journals
+#[cfg(feature = "p2p")]
+pub mod p2p {
+    pub use holi_p2p::{
+        decode_u32_varint, decode_u64_varint, encode_u32_varint, encode_u32_varint_into,
+        encode_u64_varint, encode_u64_varint_into, varint_len, MAX_U32_VARINT_LEN,
+        MAX_U64_VARINT_LEN,
+    };
+
+    pub mod frame {
+        pub use holi_p2p::frame::{
+            acl_update_signing_bytes, bound_chat_text_signing_bytes, chat_text_signing_bytes,
+            decode_acl_update_payload_v1, decode_chat_text_frame_v1,
+            decode_encrypted_envelope_frame_v1, decode_encrypted_envelope_payload_v1,
+            decode_ephemeral_envelope_payload_v1, decode_file_accept_payload_v1,
+            decode_file_chunk_frame_v1, decode_file_chunk_payload_v1, decode_file_end_payload_v1,
+            decode_file_offer_payload_v1, decode_file_offer_sealed_payload_v1,
+            decode_file_reject_payload_v1, decode_file_reveal_payload_v1,
+            decode_goodbye_payload_v1, decode_hello_payload_v1, decode_multi_envelope_payload_v1,
+            decode_offer_metadata, decode_project_invite_payload_v1, decode_rekey_payload_v1,
+            decode_v1, decode_v1_ref, encode_acl_update_v1, encode_attributed_chat_text_v1,
+            encode_chat_text_v1, encode_encrypted_envelope_v1,
+            encode_encrypted_envelope_with_suite_v1, encode_ephemeral_envelope_v1,
+            encode_file_accept_v1, encode_file_chunk_compressed_v1, encode_file_chunk_v1,
+            encode_file_chunk_v1_into, encode_file_chunk_with_proof_v1, encode_file_end_v1,
+            encode_file_offer_sealed_v1, encode_file_offer_v1, encode_file_reject_v1,
+            encode_file_reveal_v1, encode_goodbye_v1, encode_header_v1_into, encode_hello_v1,
+            encode_multi_envelope_v1, encode_offer_metadata, encode_project_invite_v1,
+            encode_rekey_request_v1, encode_rekey_response_v1, encode_v1,
+            ephemeral_associated_data_v1, file_chunk_v1_len, project_invite_signing_bytes,
+            AclAction, AclUpdate, ChatAttribution, ChatText, CipherSuite, DecodeError, EncodeError,
+            EncryptedEnvelope, EphemeralEnvelope, FileChunk, FileOffer, FileReject, FileReveal,
+            Frame, FrameRef, FrameType, Goodbye, GoodbyeReason, Hello, MerkleRoot,
+            MultiEnvelopeEntry, OfferMetadata, OfferThumbnail, ProjectInvite, RejectCode, Rekey,
+            SealedFileOffer, ACL_AUTHOR_KEY_LEN, ACL_SIGNATURE_LEN, CHAT_SENDER_KEY_LEN,
+            CHAT_SIGNATURE_LEN, CHAT_TEXT_FLAG_ATTRIBUTED, CHAT_TEXT_FLAG_BOUND,
+            ENVELOPE_AES_GCM_NONCE_LEN, ENVELOPE_FLAG_CIPHER_SUITE, ENVELOPE_NONCE_LEN,
+            FILE_CHUNK_FLAG_COMPRESSED, FILE_CHUNK_FLAG_PROOF, HELLO_CAP_AES_256_GCM,
+            HELLO_CAP_ANIMATED_QR, HELLO_CAP_BOUND_CHAT, HELLO_CAP_CLOCK_SYNC,
+            HELLO_CAP_COMPRESSED_CHUNKS, HELLO_CAP_FOUNTAIN_CODES, HELLO_CAP_MERKLE_PROOFS,
+            HELLO_CAP_OFFER_THUMBNAIL, MAGIC, MAX_HEADER_LEN_V1, MAX_INFLATED_CHUNK_LEN,
+            MULTI_ENVELOPE_MAX_RECIPIENTS, OFFER_KEY_LEN, PROJECT_INVITE_SEALED_KEY_LEN,
+            REKEY_PUBLIC_KEY_LEN, VERSION_V1,
+        };
+    }
+
+    pub mod session {
+        pub use holi_p2p::session::SessionEnd;
+    }
+
+    pub mod transport {
+        pub use holi_p2p::transport::{MemoryTransport, Transport, TransportError};
+    }
+
+    pub mod keepalive {
+        pub use holi_p2p::keepalive::{
+            encode_heartbeat_ping, encode_heartbeat_pong, is_heartbeat, ClockOffsetEstimator,
+            ClockSample, CLOCK_SAMPLE_WINDOW, HEARTBEAT_PING, HEARTBEAT_PING_LEN, HEARTBEAT_PONG,
+            HEARTBEAT_PONG_LEN, LEGACY_HEARTBEAT_PONG_LEN,
+        };
+    }
+
+    pub mod merkle {
+        pub use holi_p2p::merkle::{
+            leaf_count, leaf_hash, verify_leaf, MerkleBuilder, MerkleHash, MerkleTree,
+            MAX_PROOF_LEN, MERKLE_HASH_LEN,
+        };
+    }
+
+    pub mod compressibility {
+        pub use holi_p2p::compressibility::{
+            byte_entropy, deflate, has_compressed_magic, inflate, is_precompressed_mime,
+            should_compress, ENTROPY_THRESHOLD, SAMPLE_LEN,
+        };
+    }
+
+    pub mod padding {
+        pub use holi_p2p::padding::{pad_frame, padded_len, unpad_frame, DEFAULT_PAD_BUCKETS};
+    }
+
+    pub mod rekey {
+        pub use holi_p2p::rekey::{
+            rekey_info, RekeyError, RekeyPolicy, RekeySchedule, RequestOutcome,
+            DEFAULT_REKEY_AFTER_BYTES, DEFAULT_REKEY_AFTER_MESSAGES, REKEY_INFO,
+        };
+    }
+
+    pub mod ephemeral {
+        pub use holi_p2p::ephemeral::{
+            chain_info, BurnLedger, BurnReport, EphemeralError, ReceiveWindow,
+            DEFAULT_EPHEMERAL_TTL_MS, EPHEMERAL_CHAIN_INFO, EPHEMERAL_RATCHET_INFO,
+            MAX_SKIPPED_KEYS,
+        };
+    }
+
+    pub mod outbound {
+        pub use holi_p2p::outbound::{FrameScheduler, Priority, SchedulerWeights, QUANTUM_BYTES};
+    }
+
+    pub mod offload {
+        pub use holi_p2p::offload::{
+            Job, OffloadScheduler, SlotLayout, FRAME_OVERHEAD_BYTES, OP_OPEN, OP_SEAL, SLOT_DONE,
+            SLOT_FAILED, SLOT_FREE, SLOT_HEADER_LEN, SLOT_SUBMITTED,
+        };
+    }
+
+    pub mod schema {
+        pub use holi_p2p::schema::{
+            frame_schema, frame_schemas, schema_json, Field, FieldKind, FrameSchema, Variant,
+            HEADER,
+        };
+    }
+
+    pub mod journal {
+        pub use holi_p2p::journal::{
+            parse_journal_json, Direction, Divergence, FrameJournal, JournalEntry,
+            JournalTransport, ReplayTransport, DEFAULT_JOURNAL_CAPACITY, MAX_REPLAY_ENTRY_LEN,
+        };
+    }
+}
+
+/// Identity keys, symmetric encryption, HPKE sealed boxes, PAKE pairing and
+/// key fingerprints.
+///
+/// These types are shared with the WASM build; fallible methods report errors
+/// as `wasm_bindgen::JsValue`.
+#[cfg(feature = "crypto")]
+pub mod crypto {
+    pub use holi_wasm_crypto::encryption::EncryptionKey;
+    pub use holi_wasm_crypto::fingerprint::{
+        compare_fingerprint, fingerprint_bytes, fingerprint_emoji, fingerprint_hex,
+        fingerprint_zbase32, parse_fingerprint_hex, parse_fingerprint_zbase32, verify_fingerprint,
+        FingerprintMatch,
+    };
+    pub use holi_wasm_crypto::hpke::{hpke_seal, HpkeKeyPair};
+    pub use holi_wasm_crypto::identity::IdentityKey;
+    pub use holi_wasm_crypto::pake::{Spake2A, Spake2B, Spake2Symmetric};
+    pub use holi_wasm_crypto::signed_qr::{signed_qr_payload, verify_signed_qr};
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_qr_surface() {
+        use crate::qr::{classify_payload, generate_qr, ErrorCorrectionLevel, PayloadRisk};
+
+        let qr = generate_qr("http://holi.tools", ErrorCorrectionLevel::Medium).unwrap();
+        assert!(qr.size() > 0);
+        let classification = classify_payload("http://holi.tools");
+        assert!(classification.risks.contains(&PayloadRisk::InsecureHttp));
+    }
+
+    #[cfg(feature = "p2p")]
+    #[test]
+    fn test_p2p_surface() {
+        use crate::p2p::frame::{decode_v1, encode_chat_text_v1, FrameType};
+
+        let bytes = encode_chat_text_v1("hola");
+        let (frame, used) = decode_v1(&bytes, 1024).unwrap();
+        assert_eq!(used, bytes.len());
+        assert_eq!(frame.frame_type, FrameType::ChatText);
+        assert_eq!(frame.payload, b"hola");
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_crypto_surface() {
+        use crate::crypto::IdentityKey;
+
+        let key = IdentityKey::generate();
+        let signature = key.sign(b"holi");
+        assert!(IdentityKey::verify_signature(
+            &key.public_key_bytes(),
+            b"holi",
+            &signature
+        ));
+    }
+}
@@ -0,0 +1,79 @@
+//! Holi.tools Build Info
+//!
+//! Shared pieces behind each package's `<pkg>_build_info()` binding, so bug
+//! reports can say exactly which module and feature set a user was running.
+//!
+//! The git hash is read from `HOLI_GIT_HASH` at compile time (CI sets it to
+//! the commit being built); local builds without it report `"unknown"`.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// Commit the module was built from, or `"unknown"`.
+pub const GIT_HASH: &str = match option_env!("HOLI_GIT_HASH") {
+    Some(hash) => hash,
+    None => "unknown",
+};
+
+/// Returned to JS as `{ package, version, gitHash, features, protocols }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    pub package: &'static str,
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    /// Cargo features the module was compiled with.
+    pub features: Vec<&'static str>,
+    /// Wire format versions understood, e.g. `{ "frame": 1 }`.
+    pub protocols: BTreeMap<&'static str, u32>,
+}
+
+impl BuildInfo {
+    /// Call with `env!("CARGO_PKG_NAME")` and `env!("CARGO_PKG_VERSION")`.
+    pub fn new(package: &'static str, version: &'static str) -> Self {
+        BuildInfo {
+            package,
+            version,
+            git_hash: GIT_HASH,
+            features: Vec::new(),
+            protocols: BTreeMap::new(),
+        }
+    }
+
+    /// List `name` if `enabled`, typically `cfg!(feature = "...")`.
+    pub fn with_feature(mut self, name: &'static str, enabled: bool) -> Self {
+        if enabled {
+            self.features.push(name);
+        }
+        self
+    }
+
+    pub fn with_protocol(mut self, name: &'static str, version: u32) -> Self {
+        self.protocols.insert(name, version);
+        self
+    }
+
+    pub fn to_js(&self) -> JsValue {
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        self.serialize(&serializer).unwrap_or(JsValue::NULL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lists_enabled_features_only() {
+        let info = BuildInfo::new("holi_example", "1.2.3")
+            .with_feature("on", true)
+            .with_feature("off", false)
+            .with_protocol("frame", 1);
+        assert_eq!(info.features, vec!["on"]);
+        assert_eq!(info.protocols.get("frame"), Some(&1));
+        assert_eq!(info.git_hash, GIT_HASH);
+        assert!(!info.git_hash.is_empty());
+    }
+}
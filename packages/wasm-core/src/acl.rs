@@ -1,5 +1,10 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use serde::{Serialize, Deserialize};
+use holi_clock::{default_clock, Clock};
+use holi_p2p::frame::{acl_update_signing_bytes, AclAction, AclUpdate, ACL_SIGNATURE_LEN};
+use crate::identity::IdentityKey;
+use crate::identity_core::user_id_from_public_key;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum PermissionRole {
@@ -8,66 +13,325 @@ pub enum PermissionRole {
     Viewer,
 }
 
+/// Operations a peer can perform on a shared project.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Read,
+    Write,
+    Share,
+    Admin,
+}
+
+/// Which operations each role grants. Roles are cumulative: every role
+/// allows everything the role below it allows.
+const POLICY: &[(PermissionRole, &[Operation])] = &[
+    (PermissionRole::Viewer, &[Operation::Read]),
+    (PermissionRole::Editor, &[Operation::Read, Operation::Write, Operation::Share]),
+    (PermissionRole::Owner, &[Operation::Read, Operation::Write, Operation::Share, Operation::Admin]),
+];
+
+impl PermissionRole {
+    /// Wire encoding used in `AclUpdate` frames.
+    pub fn to_wire(&self) -> u8 {
+        match self {
+            PermissionRole::Owner => 0,
+            PermissionRole::Editor => 1,
+            PermissionRole::Viewer => 2,
+        }
+    }
+
+    pub fn from_wire(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PermissionRole::Owner),
+            1 => Some(PermissionRole::Editor),
+            2 => Some(PermissionRole::Viewer),
+            _ => None,
+        }
+    }
+
+    /// Whether this role grants `op` according to the policy table.
+    pub fn allows(&self, op: Operation) -> bool {
+        POLICY
+            .iter()
+            .find(|(role, _)| role == self)
+            .is_some_and(|(_, ops)| ops.contains(&op))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PeerPermission {
     pub user_id: String,
     pub role: PermissionRole,
     pub is_revoked: bool,
+    /// Timestamp (ms) the author gave the last change, compared by
+    /// `apply_update`. Local changes use the local clock, which is theirs.
     pub since: u64,
+    /// Grant stops applying at this timestamp (ms). `None` means no expiry.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// Whether the last change to this entry was authored by an owner.
+    #[serde(default)]
+    pub set_by_owner: bool,
+}
+
+impl PeerPermission {
+    /// Whether this grant is in force at `now` (not revoked, not expired).
+    pub fn is_active_at(&self, now: u64) -> bool {
+        !self.is_revoked && self.expires_at.is_none_or(|exp| now < exp)
+    }
+}
+
+/// How far ahead of the local clock an `AclUpdate` timestamp may be.
+pub const MAX_CLOCK_SKEW_MS: u64 = 5 * 60 * 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AclMergeError {
+    BadSignature,
+    BadRole,
+    /// The author has no active grant allowing this change.
+    NotAuthorized,
+    /// The update is for another project.
+    WrongProject,
+    /// The timestamp is more than `MAX_CLOCK_SKEW_MS` ahead of the local clock.
+    FutureTimestamp,
+}
+
+/// Build and sign an `AclUpdate` with `author`'s identity key.
+pub fn sign_acl_update(
+    author: &IdentityKey,
+    project_id: &str,
+    user_id: &str,
+    action: AclAction,
+    timestamp: u64,
+) -> AclUpdate {
+    let mut update = AclUpdate {
+        project_id: project_id.to_string(),
+        user_id: user_id.to_string(),
+        action,
+        timestamp,
+        author: author.public_key_bytes(),
+        signature: [0u8; ACL_SIGNATURE_LEN],
+    };
+    update.signature = author.sign(&acl_update_signing_bytes(&update));
+    update
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+fn shared_default_clock() -> Arc<dyn Clock> {
+    Arc::new(default_clock())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct AccessControlList {
+    /// Project whose updates `apply_update` accepts.
+    #[serde(default)]
+    project_id: String,
     // Maps user_id -> PeerPermission
     permissions: HashMap<String, PeerPermission>,
+    #[serde(skip, default = "shared_default_clock")]
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for AccessControlList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AccessControlList {
     pub fn new() -> Self {
+        Self::with_clock(shared_default_clock())
+    }
+
+    /// ACL that reads the current time from `clock` (grant timestamps, expiry checks).
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self::for_project("", clock)
+    }
+
+    /// ACL of `project_id`; `apply_update` rejects updates for other projects.
+    pub fn for_project(project_id: &str, clock: Arc<dyn Clock>) -> Self {
         AccessControlList {
+            project_id: project_id.to_string(),
             permissions: HashMap::new(),
+            clock,
         }
     }
 
+    pub fn project_id(&self) -> &str {
+        &self.project_id
+    }
+
     pub fn grant(&mut self, user_id: &str, role: PermissionRole) {
-        // If entry exists, update it. If revoked, unrevoke it.
-        let now = if cfg!(target_arch = "wasm32") {
-            js_sys::Date::now() as u64
-        } else {
-            0 // Mock time for testing
-        };
+        self.grant_until(user_id, role, None);
+    }
+
+    /// Grant `role` to `user_id`, optionally expiring at `expires_at` (ms).
+    ///
+    /// Unchecked: for the project's creator setting up the list, so the
+    /// entry counts as owner-set. Use `grant_by` for a member's change.
+    pub fn grant_until(&mut self, user_id: &str, role: PermissionRole, expires_at: Option<u64>) {
+        let now = self.clock.now_ms();
+        self.set_grant(user_id, role, expires_at, true, now);
+    }
 
-        let perm = self.permissions.entry(user_id.to_string()).or_insert(PeerPermission {
+    /// Grant `role` to `user_id` on behalf of `author_id`, who must hold
+    /// `Share` (`Admin` to grant owner) now. `timestamp` is the author's, as
+    /// signed in the matching `AclUpdate`, so every peer keeps the same one.
+    pub fn grant_by(
+        &mut self,
+        author_id: &str,
+        user_id: &str,
+        role: PermissionRole,
+        expires_at: Option<u64>,
+        timestamp: u64,
+    ) -> Result<(), AclMergeError> {
+        let required = if role == PermissionRole::Owner { Operation::Admin } else { Operation::Share };
+        let by_owner = self
+            .check_access(author_id)
+            .filter(|r| r.allows(required))
+            .map(|r| *r == PermissionRole::Owner)
+            .ok_or(AclMergeError::NotAuthorized)?;
+        self.set_grant(user_id, role, expires_at, by_owner, timestamp);
+        Ok(())
+    }
+
+    fn set_grant(
+        &mut self,
+        user_id: &str,
+        role: PermissionRole,
+        expires_at: Option<u64>,
+        by_owner: bool,
+        timestamp: u64,
+    ) {
+        // Replaces any earlier entry, revoked or not.
+        self.permissions.insert(user_id.to_string(), PeerPermission {
             user_id: user_id.to_string(),
-            role: role.clone(),
+            role,
             is_revoked: false,
-            since: now,
+            since: timestamp,
+            expires_at,
+            set_by_owner: by_owner,
         });
-        
-        perm.role = role;
-        perm.is_revoked = false;
-        perm.since = now;
     }
 
+    /// Unchecked, like `grant`: the tombstone is owner-set and stamped now,
+    /// so an older grant arriving later doesn't bring the user back.
     pub fn revoke(&mut self, user_id: &str) {
+        let now = self.clock.now_ms();
         if let Some(perm) = self.permissions.get_mut(user_id) {
             perm.is_revoked = true;
+            perm.since = now;
+            perm.set_by_owner = true;
         }
         // If user doesn't exist, we don't need to do anything (default deny)
     }
 
     pub fn check_access(&self, user_id: &str) -> Option<&PermissionRole> {
-        if let Some(perm) = self.permissions.get(user_id) {
-            if !perm.is_revoked {
-                return Some(&perm.role);
-            }
-        }
-        None
+        self.check_access_at(user_id, self.clock.now_ms())
+    }
+
+    /// Active role of `user_id` at `now`, if any.
+    pub fn check_access_at(&self, user_id: &str, now: u64) -> Option<&PermissionRole> {
+        self.permissions
+            .get(user_id)
+            .filter(|perm| perm.is_active_at(now))
+            .map(|perm| &perm.role)
     }
 
     pub fn is_allowed(&self, user_id: &str) -> bool {
         self.check_access(user_id).is_some()
     }
+
+    /// Whether `user_id` may perform `op` at `now`.
+    ///
+    /// Precedence: unknown, revoked or expired grants deny everything;
+    /// otherwise the role's policy entry decides.
+    pub fn can_at(&self, user_id: &str, op: Operation, now: u64) -> bool {
+        self.check_access_at(user_id, now)
+            .is_some_and(|role| role.allows(op))
+    }
+
+    pub fn can(&self, user_id: &str, op: Operation) -> bool {
+        self.can_at(user_id, op, self.clock.now_ms())
+    }
+
+    pub fn can_read(&self, user_id: &str) -> bool {
+        self.can(user_id, Operation::Read)
+    }
+
+    pub fn can_write(&self, user_id: &str) -> bool {
+        self.can(user_id, Operation::Write)
+    }
+
+    pub fn can_share(&self, user_id: &str) -> bool {
+        self.can(user_id, Operation::Share)
+    }
+
+    pub fn can_admin(&self, user_id: &str) -> bool {
+        self.can(user_id, Operation::Admin)
+    }
+
+    /// Merge a signed permission change received from a peer.
+    ///
+    /// The update must be for this list's project and no more than
+    /// `MAX_CLOCK_SKEW_MS` in the future. The author must hold an active
+    /// grant now, by the local clock (the timestamp is the author's to
+    /// choose, so a revoked member could backdate it): `Share` to grant
+    /// editor/viewer, `Admin` to grant owner or revoke. Conflicts resolve by owner priority
+    /// first (an owner-authored entry is only replaced by a newer owner
+    /// write), then last-writer-wins on timestamp, with revoke winning ties.
+    ///
+    /// Returns `Ok(true)` if the update changed the list.
+    pub fn apply_update(&mut self, update: &AclUpdate) -> Result<bool, AclMergeError> {
+        if !IdentityKey::verify(&update.author, &acl_update_signing_bytes(update), &update.signature) {
+            return Err(AclMergeError::BadSignature);
+        }
+        if update.project_id != self.project_id {
+            return Err(AclMergeError::WrongProject);
+        }
+        let now = self.clock.now_ms();
+        if update.timestamp > now.saturating_add(MAX_CLOCK_SKEW_MS) {
+            return Err(AclMergeError::FutureTimestamp);
+        }
+
+        let (role, expires_at, is_revoke) = match update.action {
+            AclAction::Grant { role, expires_at } => {
+                let role = PermissionRole::from_wire(role).ok_or(AclMergeError::BadRole)?;
+                (role, expires_at, false)
+            }
+            // Revocations are kept as tombstones so stale grants can't resurrect them.
+            AclAction::Revoke => (PermissionRole::Viewer, None, true),
+        };
+
+        let required = if is_revoke || role == PermissionRole::Owner {
+            Operation::Admin
+        } else {
+            Operation::Share
+        };
+        let author_id = user_id_from_public_key(&update.author);
+        let author_role = self
+            .check_access_at(&author_id, now)
+            .filter(|r| r.allows(required))
+            .ok_or(AclMergeError::NotAuthorized)?;
+        let by_owner = *author_role == PermissionRole::Owner;
+
+        if let Some(existing) = self.permissions.get(&update.user_id) {
+            let incoming = (by_owner, update.timestamp, is_revoke);
+            let current = (existing.set_by_owner, existing.since, existing.is_revoked);
+            if incoming <= current {
+                return Ok(false);
+            }
+        }
+
+        self.permissions.insert(update.user_id.clone(), PeerPermission {
+            user_id: update.user_id.clone(),
+            role,
+            is_revoked: is_revoke,
+            since: update.timestamp,
+            expires_at,
+            set_by_owner: by_owner,
+        });
+        Ok(true)
+    }
 }
 
 #[cfg(test)]
@@ -94,4 +358,204 @@ mod tests {
         assert!(!acl.is_allowed("user_123"));
         assert_eq!(acl.check_access("user_123"), None);
     }
+
+    #[test]
+    fn test_role_policy_table() {
+        let mut acl = AccessControlList::new();
+        acl.grant("owner", PermissionRole::Owner);
+        acl.grant("editor", PermissionRole::Editor);
+        acl.grant("viewer", PermissionRole::Viewer);
+
+        assert!(acl.can_read("owner") && acl.can_write("owner") && acl.can_share("owner") && acl.can_admin("owner"));
+        assert!(acl.can_read("editor") && acl.can_write("editor") && acl.can_share("editor"));
+        assert!(!acl.can_admin("editor"));
+        assert!(acl.can_read("viewer"));
+        assert!(!acl.can_write("viewer") && !acl.can_share("viewer") && !acl.can_admin("viewer"));
+        assert!(!acl.can_read("stranger"));
+    }
+
+    #[test]
+    fn test_revocation_overrides_role() {
+        let mut acl = AccessControlList::new();
+        acl.grant("owner", PermissionRole::Owner);
+        acl.revoke("owner");
+        assert!(!acl.can_read("owner"));
+        assert!(!acl.can_admin("owner"));
+
+        // Re-granting clears the revocation.
+        acl.grant("owner", PermissionRole::Viewer);
+        assert!(acl.can_read("owner"));
+        assert!(!acl.can_admin("owner"));
+    }
+
+    #[test]
+    fn test_grant_expiry() {
+        let mut acl = AccessControlList::new();
+        acl.grant_until("guest", PermissionRole::Editor, Some(1_000));
+
+        assert!(acl.can_at("guest", Operation::Write, 999));
+        assert!(!acl.can_at("guest", Operation::Write, 1_000));
+        assert!(!acl.can_at("guest", Operation::Read, 5_000));
+        assert_eq!(acl.check_access_at("guest", 5_000), None);
+
+        // A fresh grant without expiry replaces the old one.
+        acl.grant("guest", PermissionRole::Editor);
+        assert!(acl.can_at("guest", Operation::Write, 5_000));
+    }
+
+    fn grant_wire(role: PermissionRole) -> AclAction {
+        AclAction::Grant { role: role.to_wire(), expires_at: None }
+    }
+
+    fn acl_with_owner(owner: &IdentityKey) -> AccessControlList {
+        let mut acl = AccessControlList::for_project("p", shared_default_clock());
+        acl.grant(&user_id_from_public_key(&owner.public_key_bytes()), PermissionRole::Owner);
+        acl
+    }
+
+    #[test]
+    fn test_apply_signed_grant_and_revoke() {
+        let owner = IdentityKey::generate();
+        let mut acl = acl_with_owner(&owner);
+
+        let grant = sign_acl_update(&owner, "p", "bob", grant_wire(PermissionRole::Editor), 10);
+        assert_eq!(acl.apply_update(&grant), Ok(true));
+        assert!(acl.can_write("bob"));
+        // Replaying the same update is a no-op.
+        assert_eq!(acl.apply_update(&grant), Ok(false));
+
+        let revoke = sign_acl_update(&owner, "p", "bob", AclAction::Revoke, 20);
+        assert_eq!(acl.apply_update(&revoke), Ok(true));
+        assert!(!acl.can_read("bob"));
+
+        // An older grant arriving late does not resurrect the revoked user.
+        assert_eq!(acl.apply_update(&grant), Ok(false));
+        assert!(!acl.can_read("bob"));
+    }
+
+    #[test]
+    fn test_apply_rejects_forged_and_unauthorized() {
+        let owner = IdentityKey::generate();
+        let stranger = IdentityKey::generate();
+        let mut acl = acl_with_owner(&owner);
+
+        let mut forged = sign_acl_update(&owner, "p", "bob", grant_wire(PermissionRole::Owner), 10);
+        forged.user_id = "mallory".to_string();
+        assert_eq!(acl.apply_update(&forged), Err(AclMergeError::BadSignature));
+
+        let unknown = sign_acl_update(&stranger, "p", "bob", grant_wire(PermissionRole::Viewer), 10);
+        assert_eq!(acl.apply_update(&unknown), Err(AclMergeError::NotAuthorized));
+
+        // Viewers can't reshare; editors can share but not revoke.
+        let viewer = IdentityKey::generate();
+        let editor = IdentityKey::generate();
+        let viewer_id = user_id_from_public_key(&viewer.public_key_bytes());
+        let editor_id = user_id_from_public_key(&editor.public_key_bytes());
+        acl.apply_update(&sign_acl_update(&owner, "p", &viewer_id, grant_wire(PermissionRole::Viewer), 1)).unwrap();
+        acl.apply_update(&sign_acl_update(&owner, "p", &editor_id, grant_wire(PermissionRole::Editor), 1)).unwrap();
+
+        let reshare = sign_acl_update(&viewer, "p", "carol", grant_wire(PermissionRole::Viewer), 5);
+        assert_eq!(acl.apply_update(&reshare), Err(AclMergeError::NotAuthorized));
+        let share = sign_acl_update(&editor, "p", "carol", grant_wire(PermissionRole::Viewer), 5);
+        assert_eq!(acl.apply_update(&share), Ok(true));
+        let revoke = sign_acl_update(&editor, "p", &viewer_id, AclAction::Revoke, 6);
+        assert_eq!(acl.apply_update(&revoke), Err(AclMergeError::NotAuthorized));
+    }
+
+    #[test]
+    fn test_merge_owner_priority_and_lww() {
+        let owner = IdentityKey::generate();
+        let editor = IdentityKey::generate();
+        let mut acl = acl_with_owner(&owner);
+        let editor_id = user_id_from_public_key(&editor.public_key_bytes());
+        acl.apply_update(&sign_acl_update(&owner, "p", &editor_id, grant_wire(PermissionRole::Editor), 1)).unwrap();
+
+        // Owner's viewer grant beats a later editor write for the same user.
+        let by_owner = sign_acl_update(&owner, "p", "dave", grant_wire(PermissionRole::Viewer), 10);
+        let by_editor = sign_acl_update(&editor, "p", "dave", grant_wire(PermissionRole::Editor), 20);
+        let mut a = acl_with_owner(&owner);
+        a.apply_update(&sign_acl_update(&owner, "p", &editor_id, grant_wire(PermissionRole::Editor), 1)).unwrap();
+        a.apply_update(&by_owner).unwrap();
+        a.apply_update(&by_editor).unwrap();
+        acl.apply_update(&by_editor).unwrap();
+        acl.apply_update(&by_owner).unwrap();
+        assert_eq!(a.check_access("dave"), Some(&PermissionRole::Viewer));
+        assert_eq!(acl.check_access("dave"), Some(&PermissionRole::Viewer));
+
+        // Between equal-priority authors the newest write wins, in any order.
+        let older = sign_acl_update(&owner, "p", "erin", grant_wire(PermissionRole::Viewer), 30);
+        let newer = sign_acl_update(&owner, "p", "erin", grant_wire(PermissionRole::Editor), 40);
+        acl.apply_update(&newer).unwrap();
+        acl.apply_update(&older).unwrap();
+        assert_eq!(acl.check_access("erin"), Some(&PermissionRole::Editor));
+    }
+
+    #[test]
+    fn test_apply_checks_project_and_clock() {
+        let owner = IdentityKey::generate();
+        let editor = IdentityKey::generate();
+        let editor_id = user_id_from_public_key(&editor.public_key_bytes());
+        let clock = Arc::new(holi_clock::MockClock::new(100_000));
+        let mut acl = AccessControlList::for_project("p", clock.clone());
+        acl.grant(&user_id_from_public_key(&owner.public_key_bytes()), PermissionRole::Owner);
+        acl.apply_update(&sign_acl_update(&owner, "p", &editor_id, grant_wire(PermissionRole::Editor), 1_000))
+            .unwrap();
+
+        let other = sign_acl_update(&owner, "q", "bob", grant_wire(PermissionRole::Viewer), 2_000);
+        assert_eq!(acl.apply_update(&other), Err(AclMergeError::WrongProject));
+        let late = 100_000 + MAX_CLOCK_SKEW_MS + 1;
+        let ahead = sign_acl_update(&owner, "p", "bob", grant_wire(PermissionRole::Viewer), late);
+        assert_eq!(acl.apply_update(&ahead), Err(AclMergeError::FutureTimestamp));
+
+        // Once revoked, a backdated update from the editor is refused.
+        acl.apply_update(&sign_acl_update(&owner, "p", &editor_id, AclAction::Revoke, 50_000)).unwrap();
+        let backdated = sign_acl_update(&editor, "p", "bob", grant_wire(PermissionRole::Viewer), 10_000);
+        assert_eq!(acl.apply_update(&backdated), Err(AclMergeError::NotAuthorized));
+    }
+
+    #[test]
+    fn test_grant_by_author_role() {
+        let mut acl = AccessControlList::new();
+        acl.grant("owner", PermissionRole::Owner);
+        acl.grant_by("owner", "editor", PermissionRole::Editor, None, 10).unwrap();
+        acl.grant_by("editor", "viewer", PermissionRole::Viewer, None, 20).unwrap();
+        assert!(acl.permissions["owner"].set_by_owner);
+        assert!(acl.permissions["editor"].set_by_owner);
+        assert!(!acl.permissions["viewer"].set_by_owner);
+
+        assert_eq!(acl.grant_by("viewer", "x", PermissionRole::Viewer, None, 30), Err(AclMergeError::NotAuthorized));
+        assert_eq!(acl.grant_by("editor", "x", PermissionRole::Owner, None, 30), Err(AclMergeError::NotAuthorized));
+    }
+
+    #[test]
+    fn test_local_changes_keep_author_timestamps() {
+        let owner = IdentityKey::generate();
+        let owner_id = user_id_from_public_key(&owner.public_key_bytes());
+        let update = sign_acl_update(&owner, "p", "carol", grant_wire(PermissionRole::Viewer), 500);
+
+        let mut local = acl_with_owner(&owner);
+        local.grant_by(&owner_id, "carol", PermissionRole::Viewer, None, update.timestamp).unwrap();
+        let mut remote = acl_with_owner(&owner);
+        assert_eq!(remote.apply_update(&update), Ok(true));
+        assert_eq!(local.permissions["carol"].since, 500);
+        assert_eq!(remote.permissions["carol"].since, 500);
+        assert_eq!(local.apply_update(&update), Ok(false));
+
+        // A local revoke outlives grants stamped before it
+        local.revoke("carol");
+        let regrant = sign_acl_update(&owner, "p", "carol", grant_wire(PermissionRole::Viewer), 600);
+        assert_eq!(local.apply_update(&regrant), Ok(false));
+        assert!(!local.can_read("carol"));
+    }
+
+    #[test]
+    fn test_expiry_follows_clock() {
+        let clock = Arc::new(holi_clock::MockClock::new(1_000));
+        let mut acl = AccessControlList::with_clock(clock.clone());
+        acl.grant_until("guest", PermissionRole::Viewer, Some(2_000));
+
+        assert!(acl.can_read("guest"));
+        clock.advance(1_000);
+        assert!(!acl.can_read("guest"));
+    }
 }
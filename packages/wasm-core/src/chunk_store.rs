@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::crypto::ProjectKey;
+use crate::storage::{StorageError, StorageProvider};
+
+/// Storage prefix of encrypted chunks; each chunk lives at `<root>chunks/<hex id>`.
+pub const CHUNK_PREFIX: &str = "chunks/";
+/// Storage path of the encrypted reference-count index, under the store's root.
+pub const CHUNK_INDEX_PATH: &str = "chunk_index.bin";
+/// BLAKE3 context deriving the chunk id key from the project key.
+const CHUNK_ID_KEY_CONTEXT: &str = "holi.tools chunk store id key v1";
+/// Files are split into chunks of this many bytes (the last one may be shorter).
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// Length of a chunk id (keyed BLAKE3 digest).
+pub const CHUNK_ID_LEN: usize = 32;
+
+/// Keyed BLAKE3 digest of a chunk's plaintext.
+pub type ChunkId = [u8; CHUNK_ID_LEN];
+
+#[derive(Serialize, Deserialize, Default)]
+struct ChunkIndex {
+    refs: HashMap<String, u32>,
+}
+
+/// Content-addressed store of encrypted blocks for one project.
+///
+/// Chunks are addressed by a BLAKE3 hash of their plaintext keyed from the
+/// project key, so the same bytes are stored once no matter how many files
+/// (or re-sent transfers) contain them, while ids reveal nothing about the
+/// content to anyone without the key. Every `put`/`retain` adds a reference
+/// and every `release` drops one; `collect_garbage` deletes chunks nobody
+/// references anymore.
+///
+/// Both keys come from the project key, so a store is reopened with `load`
+/// from the same key and root.
+pub struct ChunkStore {
+    key: ProjectKey,
+    id_key: [u8; 32],
+    /// Storage path prefix of this store's chunks and index.
+    root: String,
+    refs: HashMap<ChunkId, u32>,
+}
+
+impl ChunkStore {
+    /// An empty store under `root` (e.g. `projects/<id>/`, or "").
+    pub fn new(key: ProjectKey, root: &str) -> Self {
+        ChunkStore {
+            id_key: blake3::derive_key(CHUNK_ID_KEY_CONTEXT, &key.key_bytes),
+            key,
+            root: root.to_string(),
+            refs: HashMap::new(),
+        }
+    }
+
+    /// Id of a chunk with this plaintext.
+    pub fn chunk_id(&self, data: &[u8]) -> ChunkId {
+        *blake3::keyed_hash(&self.id_key, data).as_bytes()
+    }
+
+    fn chunk_prefix(&self) -> String {
+        format!("{}{}", self.root, CHUNK_PREFIX)
+    }
+
+    fn chunk_path(&self, id: &ChunkId) -> String {
+        format!("{}{}", self.chunk_prefix(), hex::encode(id))
+    }
+
+    fn index_path(&self) -> String {
+        format!("{}{}", self.root, CHUNK_INDEX_PATH)
+    }
+
+    /// Store `data` (if not already present) and add a reference to it.
+    pub fn put(&mut self, storage: &dyn StorageProvider, data: &[u8]) -> Result<ChunkId, StorageError> {
+        let id = self.chunk_id(data);
+        if !self.retain(&id) {
+            let encrypted = self.key.encrypt(data).map_err(StorageError::IOError)?;
+            storage.write(&self.chunk_path(&id), &encrypted)?;
+            self.refs.insert(id, 1);
+        }
+        Ok(id)
+    }
+
+    /// Add a reference to a chunk that is already stored.
+    /// Returns false if the chunk is unknown.
+    pub fn retain(&mut self, id: &ChunkId) -> bool {
+        match self.refs.get_mut(id) {
+            Some(count) => {
+                *count += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop a reference. The chunk stays readable until `collect_garbage`.
+    pub fn release(&mut self, id: &ChunkId) {
+        if let Some(count) = self.refs.get_mut(id) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Read and decrypt a chunk, checking it still hashes to `id`.
+    pub fn get(&self, storage: &dyn StorageProvider, id: &ChunkId) -> Result<Vec<u8>, StorageError> {
+        let encrypted = storage.read(&self.chunk_path(id))?;
+        let data = self.key.decrypt(&encrypted).map_err(StorageError::IOError)?;
+        if self.chunk_id(&data) != *id {
+            return Err(StorageError::IOError("Chunk hash mismatch".into()));
+        }
+        Ok(data)
+    }
+
+    /// Whether a chunk is stored and referenced.
+    pub fn contains(&self, id: &ChunkId) -> bool {
+        self.refs.get(id).is_some_and(|&count| count > 0)
+    }
+
+    /// The ids in `ids` this store does not hold yet, in order. A resumed
+    /// transfer only needs to fetch these.
+    pub fn missing(&self, ids: &[ChunkId]) -> Vec<ChunkId> {
+        ids.iter().filter(|id| !self.contains(id)).copied().collect()
+    }
+
+    /// Number of referenced chunks.
+    pub fn len(&self) -> usize {
+        self.refs.values().filter(|&&count| count > 0).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Split `data` into chunks, store them and return the manifest (chunk ids in order).
+    pub fn put_file(&mut self, storage: &dyn StorageProvider, data: &[u8], chunk_size: usize) -> Result<Vec<ChunkId>, StorageError> {
+        data.chunks(chunk_size.max(1))
+            .map(|chunk| self.put(storage, chunk))
+            .collect()
+    }
+
+    /// Reassemble a file from its manifest.
+    pub fn get_file(&self, storage: &dyn StorageProvider, manifest: &[ChunkId]) -> Result<Vec<u8>, StorageError> {
+        let mut data = Vec::new();
+        for id in manifest {
+            data.extend_from_slice(&self.get(storage, id)?);
+        }
+        Ok(data)
+    }
+
+    /// Drop the references held by a file's manifest.
+    pub fn release_file(&mut self, manifest: &[ChunkId]) {
+        for id in manifest {
+            self.release(id);
+        }
+    }
+
+    /// Delete unreferenced chunks, plus stray chunk files under this store's
+    /// root the index does not know about (e.g. left by an interrupted
+    /// write). Returns how many were deleted.
+    ///
+    /// Only call this on a store opened with `load`: a new one references
+    /// nothing, so every chunk under its root would go.
+    pub fn collect_garbage(&mut self, storage: &dyn StorageProvider) -> Result<usize, StorageError> {
+        self.refs.retain(|_, count| *count > 0);
+        let prefix = self.chunk_prefix();
+        let mut deleted = 0;
+        for path in storage.list()? {
+            let Some(name) = path.strip_prefix(&prefix) else {
+                continue;
+            };
+            let known = hex::decode(name)
+                .ok()
+                .and_then(|bytes| ChunkId::try_from(bytes.as_slice()).ok())
+                .is_some_and(|id| self.refs.contains_key(&id));
+            if !known {
+                storage.delete(&path)?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Encrypt the reference counts and write them to `CHUNK_INDEX_PATH`
+    /// under the store's root.
+    pub fn save(&self, storage: &dyn StorageProvider) -> Result<(), StorageError> {
+        let index = ChunkIndex {
+            refs: self.refs.iter().map(|(id, &count)| (hex::encode(id), count)).collect(),
+        };
+        let json = serde_json::to_vec(&index)
+            .map_err(|e| StorageError::IOError(format!("Serialization failed: {}", e)))?;
+        let encrypted = self.key.encrypt(&json).map_err(StorageError::IOError)?;
+        storage.write(&self.index_path(), &encrypted)
+    }
+
+    /// Load an index written by `save`. A missing index yields an empty store.
+    pub fn load(storage: &dyn StorageProvider, key: ProjectKey, root: &str) -> Result<Self, StorageError> {
+        let mut store = Self::new(key, root);
+        let encrypted = match storage.read(&store.index_path()) {
+            Ok(data) => data,
+            Err(StorageError::NotFound) => return Ok(store),
+            Err(e) => return Err(e),
+        };
+        let json = store.key.decrypt(&encrypted).map_err(StorageError::IOError)?;
+        let index: ChunkIndex = serde_json::from_slice(&json)
+            .map_err(|e| StorageError::IOError(format!("Deserialization failed: {}", e)))?;
+        for (name, count) in index.refs {
+            let id = hex::decode(&name)
+                .ok()
+                .and_then(|bytes| ChunkId::try_from(bytes.as_slice()).ok())
+                .ok_or_else(|| StorageError::IOError(format!("Invalid chunk id: {}", name)))?;
+            store.refs.insert(id, count);
+        }
+        Ok(store)
+    }
+}
+
+/// Concatenate chunk ids into the flat byte form used across the JS boundary.
+pub fn manifest_to_bytes(manifest: &[ChunkId]) -> Vec<u8> {
+    manifest.concat()
+}
+
+/// Inverse of `manifest_to_bytes`.
+pub fn manifest_from_bytes(bytes: &[u8]) -> Result<Vec<ChunkId>, StorageError> {
+    if !bytes.len().is_multiple_of(CHUNK_ID_LEN) {
+        return Err(StorageError::IOError("Manifest length must be a multiple of 32".into()));
+    }
+    Ok(bytes
+        .chunks_exact(CHUNK_ID_LEN)
+        .map(|c| ChunkId::try_from(c).unwrap())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+
+    fn stored_chunks(storage: &InMemoryStorage) -> usize {
+        storage.list().unwrap().iter().filter(|p| p.starts_with(CHUNK_PREFIX)).count()
+    }
+
+    #[test]
+    fn test_dedupe_and_roundtrip() {
+        let storage = InMemoryStorage::new();
+        let mut store = ChunkStore::new(ProjectKey::generate(), "");
+
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 7) as u8).collect();
+        let first = store.put_file(&storage, &data, 4096).unwrap();
+        let second = store.put_file(&storage, &data, 4096).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 3);
+        assert_eq!(stored_chunks(&storage), 3);
+
+        assert_eq!(store.get_file(&storage, &first).unwrap(), data);
+
+        let raw = storage.read(&store.chunk_path(&first[0])).unwrap();
+        assert!(!raw.windows(4).any(|w| w == [0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn test_refcount_garbage_collection() {
+        let storage = InMemoryStorage::new();
+        let mut store = ChunkStore::new(ProjectKey::generate(), "");
+
+        let a = store.put_file(&storage, b"shared-block-unique-a", 12).unwrap();
+        let b = store.put_file(&storage, b"shared-block-unique-b", 12).unwrap();
+        assert_eq!(a[0], b[0]);
+        assert_eq!(stored_chunks(&storage), 3);
+
+        store.release_file(&a);
+        assert_eq!(store.collect_garbage(&storage).unwrap(), 1);
+        assert_eq!(store.get_file(&storage, &b).unwrap(), b"shared-block-unique-b");
+
+        store.release_file(&b);
+        assert_eq!(store.collect_garbage(&storage).unwrap(), 2);
+        assert!(store.is_empty());
+        assert_eq!(stored_chunks(&storage), 0);
+    }
+
+    #[test]
+    fn test_missing_for_resumption() {
+        let storage = InMemoryStorage::new();
+        let mut store = ChunkStore::new(ProjectKey::generate(), "");
+
+        let parts: [&[u8]; 3] = [b"one", b"two", b"three"];
+        let ids: Vec<ChunkId> = parts.iter().map(|p| store.chunk_id(p)).collect();
+        store.put(&storage, parts[1]).unwrap();
+
+        assert_eq!(store.missing(&ids), vec![ids[0], ids[2]]);
+        assert!(store.retain(&ids[1]));
+        assert!(!store.retain(&ids[0]));
+    }
+
+    #[test]
+    fn test_index_persistence_and_tampering() {
+        let storage = InMemoryStorage::new();
+        let key = ProjectKey::generate();
+        let mut store = ChunkStore::new(key.clone(), "p/");
+        let manifest = store.put_file(&storage, b"persist me", 4).unwrap();
+        store.save(&storage).unwrap();
+
+        let loaded = ChunkStore::load(&storage, key.clone(), "p/").unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.get_file(&storage, &manifest).unwrap(), b"persist me");
+
+        // A chunk swapped for another one (same key) fails the hash check
+        let other = storage.read(&loaded.chunk_path(&manifest[1])).unwrap();
+        storage.write(&loaded.chunk_path(&manifest[0]), &other).unwrap();
+        assert!(loaded.get(&storage, &manifest[0]).is_err());
+
+        assert!(ChunkStore::load(&storage, ProjectKey::generate(), "p/").is_err());
+        assert!(ChunkStore::load(&storage, key.clone(), "q/").unwrap().is_empty());
+        assert!(ChunkStore::load(&InMemoryStorage::new(), key, "p/").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ids_are_keyed_per_project() {
+        let storage = InMemoryStorage::new();
+        let mut a = ChunkStore::new(ProjectKey::generate(), "a/");
+        let mut b = ChunkStore::new(ProjectKey::generate(), "b/");
+        assert_ne!(a.chunk_id(b"same"), b.chunk_id(b"same"));
+        assert_ne!(a.chunk_id(b"same"), *blake3::hash(b"same").as_bytes());
+
+        // Collecting one project's garbage leaves the other's chunks alone
+        a.put(&storage, b"same").unwrap();
+        let id = b.put(&storage, b"same").unwrap();
+        a.release(&a.chunk_id(b"same"));
+        assert_eq!(a.collect_garbage(&storage).unwrap(), 1);
+        assert_eq!(b.get(&storage, &id).unwrap(), b"same");
+    }
+
+    #[test]
+    fn test_manifest_bytes() {
+        let store = ChunkStore::new(ProjectKey::generate(), "");
+        let manifest = vec![store.chunk_id(b"a"), store.chunk_id(b"b")];
+        let bytes = manifest_to_bytes(&manifest);
+        assert_eq!(bytes.len(), 64);
+        assert_eq!(manifest_from_bytes(&bytes).unwrap(), manifest);
+        assert!(manifest_from_bytes(&bytes[1..]).is_err());
+    }
+}
@@ -1,7 +1,8 @@
 use chacha20poly1305::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{Aead, AeadCore, KeyInit},
     XChaCha20Poly1305, XNonce
 };
+use holi_rng::HoliRng;
 use serde::{Serialize, Deserialize};
 use std::fmt;
 use wasm_bindgen::prelude::*;
@@ -17,7 +18,7 @@ pub struct ProjectKey {
 impl ProjectKey {
     #[wasm_bindgen(constructor)]
     pub fn generate() -> Self {
-        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let key = XChaCha20Poly1305::generate_key(&mut HoliRng);
         ProjectKey {
             key_bytes: key.into(),
         }
@@ -40,7 +41,7 @@ impl ProjectKey {
     /// Returns: nonce (24 bytes) + ciphertext + tag (16 bytes)
     pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
         let cipher = XChaCha20Poly1305::new(&self.key_bytes.into());
-        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng); // 24-bytes; unique per message
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut HoliRng); // 24-bytes; unique per message
         
         let ciphertext = cipher.encrypt(&nonce, plaintext)
             .map_err(|e| format!("Encryption failed: {}", e))?;
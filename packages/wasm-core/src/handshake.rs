@@ -1,12 +1,12 @@
+use holi_rng::HoliRng;
 use rand::RngCore;
-use rand::rngs::OsRng;
 use crate::identity::IdentityKey;
 use wasm_bindgen::prelude::*;
 
 /// Generates a random 32-byte challenge (Nonce).
 pub fn generate_challenge() -> [u8; 32] {
     let mut nonce = [0u8; 32];
-    OsRng.fill_bytes(&mut nonce);
+    HoliRng.fill_bytes(&mut nonce);
     nonce
 }
 
@@ -82,6 +82,12 @@ impl HandshakeSimulator {
     }
 }
 
+impl Default for HandshakeSimulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,5 +1,5 @@
 use ed25519_dalek::{SigningKey, VerifyingKey, Signer, Verifier, Signature};
-use rand::rngs::OsRng;
+use holi_rng::HoliRng;
 use serde::{Serialize, Deserialize};
 use std::fmt;
 
@@ -14,7 +14,7 @@ pub struct IdentityKey {
 
 impl IdentityKey {
     pub fn generate() -> Self {
-        let mut csprng = OsRng;
+        let mut csprng = HoliRng;
         let signing_key = SigningKey::generate(&mut csprng);
         IdentityKey {
             secret_bytes: signing_key.to_bytes(),
@@ -1,6 +1,11 @@
 use serde::{Serialize, Deserialize};
 use crate::identity::IdentityKey;
-use wasm_bindgen::prelude::*;
+use holi_clock::{default_clock, Clock};
+
+/// Simple User ID derivation: "u_" + first 16 chars of pubkey hex
+pub fn user_id_from_public_key(public_key: &[u8; 32]) -> String {
+    format!("u_{}", &hex::encode(public_key)[0..16])
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct UserIdentity {
@@ -14,17 +19,14 @@ pub struct UserIdentity {
 
 impl UserIdentity {
     pub fn new(display_name: String, device_fingerprint: String) -> Self {
+        Self::new_with_clock(display_name, device_fingerprint, &default_clock())
+    }
+
+    /// Like `new`, stamping `created_at` from `clock`.
+    pub fn new_with_clock(display_name: String, device_fingerprint: String, clock: &dyn Clock) -> Self {
         let key = IdentityKey::generate();
-        let pub_key = hex::encode(key.public_key_bytes());
-        
-        // Simple User ID derivation: "u_" + first 16 chars of pubkey hex
-        let user_id = format!("u_{}", &pub_key[0..16]);
-        
-        let created_at = if cfg!(target_arch = "wasm32") {
-            js_sys::Date::now() as u64
-        } else {
-            0
-        };
+        let user_id = user_id_from_public_key(&key.public_key_bytes());
+        let created_at = clock.now_ms();
 
         UserIdentity {
             user_id,
@@ -39,8 +41,7 @@ impl UserIdentity {
     // For pure Rust testing where js_sys might not be available
     pub fn new_test(display_name: String) -> Self {
         let key = IdentityKey::generate();
-        let pub_key = hex::encode(key.public_key_bytes());
-        let user_id = format!("u_{}", &pub_key[0..16]);
+        let user_id = user_id_from_public_key(&key.public_key_bytes());
         
         UserIdentity {
             user_id,
@@ -65,6 +66,13 @@ mod tests {
         assert_eq!(user.device_fingerprint, "test-device");
     }
 
+    #[test]
+    fn test_created_at_uses_clock() {
+        let clock = holi_clock::MockClock::new(1_700_000_000_000);
+        let user = UserIdentity::new_with_clock("Carol".to_string(), "dev".to_string(), &clock);
+        assert_eq!(user.created_at, 1_700_000_000_000);
+    }
+
     #[test]
     fn test_serialization() {
         let user = UserIdentity::new_test("Bob".to_string());
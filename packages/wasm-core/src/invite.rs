@@ -0,0 +1,233 @@
+use holi_hpke::HpkeError;
+use holi_p2p::frame::{
+    project_invite_signing_bytes, AclAction, AclUpdate, ProjectInvite, ACL_SIGNATURE_LEN,
+    PROJECT_INVITE_SEALED_KEY_LEN,
+};
+use holi_rng::HoliRng;
+use rand::RngCore;
+use crate::acl::{sign_acl_update, AccessControlList, Operation, PermissionRole};
+use crate::crypto::ProjectKey;
+use crate::identity::IdentityKey;
+use crate::identity_core::user_id_from_public_key;
+
+/// HPKE `info` for project keys sealed into a ProjectInvite.
+pub const INVITE_HPKE_INFO: &[u8] = b"holi.project-invite.v1";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InviteError {
+    /// The vault holds no key for the project.
+    UnknownProject,
+    /// The inviter may not grant this role (`Share`, or `Admin` for owners).
+    NotAuthorized,
+    /// The invite is addressed to another identity.
+    WrongInvitee,
+    /// The invite is signed by someone other than the peer we expected it from.
+    UntrustedInviter,
+    /// We already hold a key for the project, and the inviter may not
+    /// replace it (`Share` in our ACL of it).
+    ProjectExists,
+    BadSignature,
+    BadRole,
+    /// The sealed key does not open with our HPKE key.
+    Hpke(HpkeError),
+}
+
+/// Public keys of a peer being invited, as exchanged when pairing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Invitee {
+    /// Ed25519 identity key; the ACL grant goes to its user id.
+    pub identity: [u8; 32],
+    /// X25519 key the project key is sealed to.
+    pub hpke_public_key: [u8; 32],
+}
+
+/// Invite `invitee` to `project_id` as `role`.
+///
+/// Seals `project_key` to the invitee's X25519 key and signs both the
+/// ProjectInvite and the matching AclUpdate grant with `inviter`, who must
+/// hold `Share` (`Admin` to invite an owner) in `acl`.
+/// Send the invite to the invitee and the AclUpdate to every member.
+pub fn create_project_invite(
+    inviter: &IdentityKey,
+    acl: &AccessControlList,
+    project_id: &str,
+    project_key: &ProjectKey,
+    invitee: &Invitee,
+    role: PermissionRole,
+    timestamp: u64,
+) -> Result<(ProjectInvite, AclUpdate), InviteError> {
+    let required = if role == PermissionRole::Owner { Operation::Admin } else { Operation::Share };
+    if !acl.can_at(&user_id_from_public_key(&inviter.public_key_bytes()), required, timestamp) {
+        return Err(InviteError::NotAuthorized);
+    }
+
+    let mut ephemeral = [0u8; 32];
+    HoliRng.fill_bytes(&mut ephemeral);
+    let sealed = holi_hpke::seal(
+        &ephemeral,
+        &invitee.hpke_public_key,
+        INVITE_HPKE_INFO,
+        project_id.as_bytes(),
+        &project_key.key_bytes,
+    )
+    .map_err(InviteError::Hpke)?;
+    let sealed_key: [u8; PROJECT_INVITE_SEALED_KEY_LEN] =
+        sealed.try_into().expect("sealed 32-byte key has a fixed length");
+
+    let mut invite = ProjectInvite {
+        project_id: project_id.to_string(),
+        role: role.to_wire(),
+        timestamp,
+        inviter: inviter.public_key_bytes(),
+        invitee: invitee.identity,
+        sealed_key,
+        signature: [0u8; ACL_SIGNATURE_LEN],
+    };
+    invite.signature = inviter.sign(&project_invite_signing_bytes(&invite));
+
+    let grant = sign_acl_update(
+        inviter,
+        project_id,
+        &user_id_from_public_key(&invitee.identity),
+        AclAction::Grant { role: role.to_wire(), expires_at: None },
+        timestamp,
+    );
+    Ok((invite, grant))
+}
+
+/// Check an invite addressed to `invitee` and unseal the project key.
+///
+/// `inviter` is the identity key of the peer we expect the invite from, as
+/// learned when pairing; a valid signature by anyone else is refused, so a
+/// stranger can't talk us into a project of theirs. `hpke_secret` is the
+/// X25519 secret whose public key was given to the inviter. Membership
+/// itself comes from the AclUpdate that accompanies the invite; apply it
+/// with `AccessControlList::apply_update`.
+pub fn accept_project_invite(
+    invite: &ProjectInvite,
+    invitee: &IdentityKey,
+    hpke_secret: &[u8; 32],
+    inviter: &[u8; 32],
+) -> Result<ProjectKey, InviteError> {
+    if invite.invitee != invitee.public_key_bytes() {
+        return Err(InviteError::WrongInvitee);
+    }
+    if invite.inviter != *inviter {
+        return Err(InviteError::UntrustedInviter);
+    }
+    if !IdentityKey::verify(&invite.inviter, &project_invite_signing_bytes(invite), &invite.signature) {
+        return Err(InviteError::BadSignature);
+    }
+    PermissionRole::from_wire(invite.role).ok_or(InviteError::BadRole)?;
+    let key_bytes = holi_hpke::open(hpke_secret, INVITE_HPKE_INFO, invite.project_id.as_bytes(), &invite.sealed_key)
+        .map_err(InviteError::Hpke)?;
+    Ok(ProjectKey { key_bytes: key_bytes.try_into().map_err(|_| InviteError::Hpke(HpkeError::OpenFailed))? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holi_p2p::frame::{decode_project_invite_payload_v1, decode_v1, encode_project_invite_v1};
+
+    struct Peer {
+        identity: IdentityKey,
+        hpke_secret: [u8; 32],
+    }
+
+    impl Peer {
+        fn new(seed: u8) -> Self {
+            Peer { identity: IdentityKey::generate(), hpke_secret: [seed; 32] }
+        }
+
+        fn user_id(&self) -> String {
+            user_id_from_public_key(&self.identity.public_key_bytes())
+        }
+
+        fn as_invitee(&self) -> Invitee {
+            Invitee {
+                identity: self.identity.public_key_bytes(),
+                hpke_public_key: holi_hpke::public_key(&self.hpke_secret),
+            }
+        }
+    }
+
+    fn owner_acl(owner: &Peer) -> AccessControlList {
+        let mut acl = AccessControlList::for_project("proj", std::sync::Arc::new(holi_clock::MockClock::new(0)));
+        acl.grant(&owner.user_id(), PermissionRole::Owner);
+        acl
+    }
+
+    #[test]
+    fn test_invite_roundtrip() {
+        let owner = Peer::new(1);
+        let bob = Peer::new(2);
+        let acl = owner_acl(&owner);
+        let key = ProjectKey::generate();
+
+        let (invite, grant) = create_project_invite(
+            &owner.identity,
+            &acl,
+            "proj",
+            &key,
+            &bob.as_invitee(),
+            PermissionRole::Editor,
+            1_000,
+        )
+        .unwrap();
+
+        let (frame, _) = decode_v1(&encode_project_invite_v1(&invite), 1024).unwrap();
+        let received = decode_project_invite_payload_v1(&frame.payload).unwrap();
+        let opened = accept_project_invite(&received, &bob.identity, &bob.hpke_secret, &owner.identity.public_key_bytes()).unwrap();
+        assert_eq!(opened.key_bytes, key.key_bytes);
+
+        // Every member (and Bob) learns the membership from the signed grant.
+        let mut member_acl = owner_acl(&owner);
+        assert_eq!(member_acl.apply_update(&grant), Ok(true));
+        assert!(member_acl.can_at(&bob.user_id(), Operation::Write, 1_000));
+    }
+
+    #[test]
+    fn test_invite_rejections() {
+        let owner = Peer::new(1);
+        let bob = Peer::new(2);
+        let carol = Peer::new(3);
+        let mut acl = owner_acl(&owner);
+        acl.grant(&carol.user_id(), PermissionRole::Viewer);
+        let key = ProjectKey::generate();
+        let invite_bob = |inviter: &Peer, role| {
+            create_project_invite(
+                &inviter.identity,
+                &acl,
+                "proj",
+                &key,
+                &bob.as_invitee(),
+                role,
+                1_000,
+            )
+        };
+
+        assert_eq!(invite_bob(&carol, PermissionRole::Viewer).unwrap_err(), InviteError::NotAuthorized);
+        let (invite, _) = invite_bob(&owner, PermissionRole::Viewer).unwrap();
+        let owner_key = owner.identity.public_key_bytes();
+
+        // Validly signed, but not by the peer Bob paired with
+        assert_eq!(
+            accept_project_invite(&invite, &bob.identity, &bob.hpke_secret, &carol.identity.public_key_bytes()).unwrap_err(),
+            InviteError::UntrustedInviter
+        );
+
+        assert_eq!(
+            accept_project_invite(&invite, &carol.identity, &carol.hpke_secret, &owner_key).unwrap_err(),
+            InviteError::WrongInvitee
+        );
+        assert_eq!(
+            accept_project_invite(&invite, &bob.identity, &carol.hpke_secret, &owner_key).unwrap_err(),
+            InviteError::Hpke(HpkeError::OpenFailed)
+        );
+        let upgraded = ProjectInvite { role: 0, ..invite.clone() };
+        assert_eq!(
+            accept_project_invite(&upgraded, &bob.identity, &bob.hpke_secret, &owner_key).unwrap_err(),
+            InviteError::BadSignature
+        );
+    }
+}
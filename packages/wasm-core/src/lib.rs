@@ -1,8 +1,14 @@
-use std::{cell::RefCell, rc::Rc};
+#[cfg(target_arch = "wasm32")]
+use std::{cell::RefCell, rc::Rc, sync::Arc};
 
+#[cfg(target_arch = "wasm32")]
 use gloo::render::{request_animation_frame, AnimationFrame};
+#[cfg(target_arch = "wasm32")]
+use holi_clock::Clock;
 use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
 use web_sys::{HtmlCanvasElement, Window};
+#[cfg(target_arch = "wasm32")]
 use wgpu::util::DeviceExt;
 
 pub mod identity;
@@ -11,10 +17,21 @@ pub mod handshake;
 pub mod acl;
 pub mod crypto;
 pub mod storage;
+pub mod chunk_store;
 pub mod vault;
+pub mod scan_log;
+pub mod pairing_guard;
+pub mod tickets;
+pub mod chat;
+pub mod outbox;
+pub mod invite;
+#[cfg(feature = "thumbnails")]
+pub mod thumbnail;
 
 // --- Estructuras de Datos ---
+// The wgpu renderer is only wired up on wasm32 (`start`/`stop`).
 
+#[cfg(target_arch = "wasm32")]
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct Vertex {
@@ -22,6 +39,7 @@ struct Vertex {
     uv: [f32; 2],       // Coordenadas de textura para efectos
 }
 
+#[cfg(target_arch = "wasm32")]
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct Uniforms {
@@ -30,6 +48,7 @@ struct Uniforms {
 }
 
 // --- Shader WGSL ---
+#[cfg(target_arch = "wasm32")]
 const SHADER: &str = r#"
 struct Uniforms {
     view_proj: mat4x4<f32>,
@@ -84,6 +103,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 
 // --- Math Helpers (CPU Side) ---
 
+#[cfg(target_arch = "wasm32")]
 fn generate_view_projection(width: f32, height: f32, time: f32) -> [[f32; 4]; 4] {
     let aspect = width / height;
     let fov_y = 45.0f32.to_radians();
@@ -122,10 +142,12 @@ fn generate_view_projection(width: f32, height: f32, time: f32) -> [[f32; 4]; 4]
     multiply_matrices(proj, view)
 }
 
+#[cfg(target_arch = "wasm32")]
 fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
     [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
 }
 
+#[cfg(target_arch = "wasm32")]
 fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
     [
         a[1] * b[2] - a[2] * b[1],
@@ -134,15 +156,18 @@ fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
     ]
 }
 
+#[cfg(target_arch = "wasm32")]
 fn normalize(v: [f32; 3]) -> [f32; 3] {
     let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
     if len == 0.0 { [0.0; 3] } else { [v[0] / len, v[1] / len, v[2] / len] }
 }
 
+#[cfg(target_arch = "wasm32")]
 fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
     a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
 }
 
+#[cfg(target_arch = "wasm32")]
 fn multiply_matrices(a: [[f32; 4]; 4], b: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
     let mut out = [[0.0; 4]; 4];
     for i in 0..4 {
@@ -153,6 +178,7 @@ fn multiply_matrices(a: [[f32; 4]; 4], b: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
     out
 }
 
+#[cfg(target_arch = "wasm32")]
 struct State {
     surface: wgpu::Surface<'static>,
     device: wgpu::Device,
@@ -166,9 +192,11 @@ struct State {
     depth_texture: wgpu::Texture,
     depth_view: wgpu::TextureView,
     num_indices: u32,
-    _start: f64,
+    clock: Arc<dyn Clock>,
+    start: f64,
 }
 
+#[cfg(target_arch = "wasm32")]
 impl State {
     fn resize_if_needed(&mut self, window: &Window, canvas: &HtmlCanvasElement) {
         // Capping DPR for performance
@@ -271,6 +299,7 @@ impl State {
     }
 }
 
+#[cfg(target_arch = "wasm32")]
 fn create_plane_mesh(device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer, u32) {
     let size = 30;
     let scale = 0.5;
@@ -316,6 +345,7 @@ fn create_plane_mesh(device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer, u32)
     (vertex_buffer, index_buffer, indices.len() as u32)
 }
 
+#[cfg(target_arch = "wasm32")]
 thread_local! {
     static RAF_HANDLE: RefCell<Option<AnimationFrame>> = const { RefCell::new(None) };
 }
@@ -323,6 +353,12 @@ thread_local! {
 #[wasm_bindgen]
 #[cfg(target_arch = "wasm32")]
 pub async fn start(canvas: HtmlCanvasElement) -> Result<(), JsValue> {
+    start_with_clock(canvas, Arc::new(holi_clock::default_clock())).await
+}
+
+/// `start`, with animation time read from `clock`.
+#[cfg(target_arch = "wasm32")]
+async fn start_with_clock(canvas: HtmlCanvasElement, clock: Arc<dyn Clock>) -> Result<(), JsValue> {
     console_error_panic_hook::set_once();
     
     let window = web_sys::window().ok_or("no global window")?;
@@ -499,7 +535,8 @@ pub async fn start(canvas: HtmlCanvasElement) -> Result<(), JsValue> {
         depth_texture,
         depth_view,
         num_indices,
-        _start: js_sys::Date::now(),
+        start: clock.now_ms() as f64,
+        clock,
     };
 
     let state = Rc::new(RefCell::new(state));
@@ -507,8 +544,10 @@ pub async fn start(canvas: HtmlCanvasElement) -> Result<(), JsValue> {
 
     fn schedule(state: Rc<RefCell<State>>, canvas: Rc<HtmlCanvasElement>, window: Rc<Window>) {
         let handle = request_animation_frame(move |_ts| {
-            let now = js_sys::Date::now();
-            let start_time = state.borrow()._start;
+            let (now, start_time) = {
+                let st = state.borrow();
+                (st.clock.now_ms() as f64, st.start)
+            };
             let t = ((now - start_time) / 1000.0) as f32;
 
             {
@@ -545,8 +584,18 @@ pub fn generate_qr_svg(text: &str) -> String {
         .build()
         .unwrap(); // In production propagate error properly
 
-    let svg = SvgBuilder::default()
-        .to_str(&qrcode);
+    SvgBuilder::default()
+        .to_str(&qrcode)
+}
 
-    svg
+/// Which build of this module is running:
+/// `{ package, version, gitHash, features, protocols }`.
+#[wasm_bindgen]
+pub fn core_build_info() -> JsValue {
+    holi_wasm_build_info::BuildInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .with_feature("thumbnails", cfg!(feature = "thumbnails"))
+        .with_feature("test-rng", cfg!(feature = "test-rng"))
+        .with_protocol("frame", holi_p2p::frame::VERSION_V1 as u32)
+        .with_protocol("signedQr", holi_qr::SIGNED_QR_VERSION)
+        .to_js()
 }
@@ -0,0 +1,366 @@
+//! Store-and-forward for peers that are offline
+//!
+//! Messages to a peer that isn't connected are sealed and queued here, then
+//! sent when it reconnects. A frame leaves the queue only once it was
+//! handed to the transport (`flush`, or `peek` then `ack` for async sends),
+//! so a send that fails on a flaky reconnect is retried next time instead
+//! of lost. `save`/`load` (or `sealed`/`open` from JS) keep the queues
+//! across restarts, encrypted.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use wasm_bindgen::prelude::*;
+use holi_clock::{default_clock, Clock};
+use holi_p2p::frame::{encode_encrypted_envelope_v1, ENVELOPE_NONCE_LEN};
+use crate::crypto::ProjectKey;
+use crate::storage::{StorageError, StorageProvider};
+
+/// Storage path of the encrypted outbox.
+pub const OUTBOX_PATH: &str = "outbox.bin";
+/// Queued frames are dropped after this long (ms) undelivered.
+pub const DEFAULT_TTL_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+/// At most this many frames wait for one peer.
+pub const DEFAULT_MAX_FRAMES_PER_PEER: usize = 500;
+/// At most this many frame bytes wait for one peer.
+pub const DEFAULT_MAX_BYTES_PER_PEER: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutboxError {
+    /// The frame alone is over the per-peer byte cap.
+    FrameTooLarge { len: usize, max: usize },
+    /// The peer's queue is at its frame or byte cap.
+    Full,
+    Encrypt(String),
+}
+
+/// A frame waiting for its peer to come back.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueuedFrame {
+    /// EncryptedEnvelope frame, sent as-is on reconnect.
+    #[serde(with = "hex::serde")]
+    pub frame: Vec<u8>,
+    pub queued_at: u64,
+    pub expires_at: u64,
+}
+
+/// Store-and-forward queue of frames for peers that are offline.
+///
+/// Frames are sealed in an EncryptedEnvelope with a key both sides keep
+/// across sessions (e.g. the project key, or a session key derived from a
+/// shared password), so they stay confidential while stored here and the
+/// peer opens them with `decrypt_envelope_v1`. Queues are capped per peer
+/// and expired frames are dropped instead of delivered.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct Outbox {
+    queues: BTreeMap<String, VecDeque<QueuedFrame>>,
+    ttl_ms: u64,
+    max_frames_per_peer: usize,
+    max_bytes_per_peer: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Outbox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Outbox {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(default_clock()))
+    }
+
+    /// `enqueue` for JS; throws if the frame was not queued.
+    pub fn queue(&mut self, peer_id: &str, key: &ProjectKey, inner_frame: &[u8]) -> Result<(), JsValue> {
+        self.enqueue(peer_id, key, inner_frame)
+            .map_err(|e| JsValue::from_str(&format!("Not queued: {:?}", e)))
+    }
+
+    /// Oldest unexpired frame for `peer_id`, left in the queue; send it, then
+    /// `ack` it.
+    pub fn next(&mut self, peer_id: &str) -> Option<Vec<u8>> {
+        self.peek(peer_id).map(|f| f.frame.clone())
+    }
+
+    /// Remove `frame` from `peer_id`'s queue once it was sent. Returns
+    /// whether it was still queued.
+    pub fn ack(&mut self, peer_id: &str, frame: &[u8]) -> bool {
+        let Some(queue) = self.queues.get_mut(peer_id) else {
+            return false;
+        };
+        let Some(i) = queue.iter().position(|f| f.frame == frame) else {
+            return false;
+        };
+        queue.remove(i);
+        if queue.is_empty() {
+            self.queues.remove(peer_id);
+        }
+        true
+    }
+
+    /// Queues encrypted with `key`, to store wherever the app keeps state.
+    pub fn sealed(&self, key: &ProjectKey) -> Result<Vec<u8>, String> {
+        let json = serde_json::to_vec(&self.queues).map_err(|e| format!("Serialization failed: {}", e))?;
+        key.encrypt(&json)
+    }
+
+    /// Outbox with the queues of `sealed`, minus what expired meanwhile.
+    pub fn open(key: &ProjectKey, sealed: &[u8]) -> Result<Outbox, String> {
+        Self::open_with_clock(key, sealed, Arc::new(default_clock()))
+    }
+}
+
+impl Outbox {
+    fn open_with_clock(key: &ProjectKey, sealed: &[u8], clock: Arc<dyn Clock>) -> Result<Self, String> {
+        let mut outbox = Self::with_clock(clock);
+        let json = key.decrypt(sealed)?;
+        outbox.queues = serde_json::from_slice(&json).map_err(|e| format!("Deserialization failed: {}", e))?;
+        outbox.purge_expired();
+        Ok(outbox)
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Outbox {
+            queues: BTreeMap::new(),
+            ttl_ms: DEFAULT_TTL_MS,
+            max_frames_per_peer: DEFAULT_MAX_FRAMES_PER_PEER,
+            max_bytes_per_peer: DEFAULT_MAX_BYTES_PER_PEER,
+            clock,
+        }
+    }
+
+    /// Applies to frames queued from now on.
+    pub fn set_ttl_ms(&mut self, ttl_ms: u64) {
+        self.ttl_ms = ttl_ms;
+    }
+
+    pub fn set_max_frames_per_peer(&mut self, max_frames: usize) {
+        self.max_frames_per_peer = max_frames.max(1);
+    }
+
+    pub fn set_max_bytes_per_peer(&mut self, max_bytes: usize) {
+        self.max_bytes_per_peer = max_bytes;
+    }
+
+    /// Seal `inner_frame` with `key` and queue it for `peer_id`.
+    ///
+    /// A full queue rejects the new frame rather than dropping older ones,
+    /// so the caller can tell the user the message was not queued.
+    pub fn enqueue(&mut self, peer_id: &str, key: &ProjectKey, inner_frame: &[u8]) -> Result<(), OutboxError> {
+        let sealed = key.encrypt(inner_frame).map_err(OutboxError::Encrypt)?;
+        let (nonce, ciphertext) = sealed.split_at(ENVELOPE_NONCE_LEN);
+        let frame = encode_encrypted_envelope_v1(nonce.try_into().unwrap(), ciphertext);
+        if frame.len() > self.max_bytes_per_peer {
+            return Err(OutboxError::FrameTooLarge { len: frame.len(), max: self.max_bytes_per_peer });
+        }
+
+        let now = self.clock.now_ms();
+        let queue = self.queues.entry(peer_id.to_string()).or_default();
+        queue.retain(|f| f.expires_at > now);
+        let bytes: usize = queue.iter().map(|f| f.frame.len()).sum();
+        if queue.len() >= self.max_frames_per_peer || bytes + frame.len() > self.max_bytes_per_peer {
+            return Err(OutboxError::Full);
+        }
+        queue.push_back(QueuedFrame { frame, queued_at: now, expires_at: now.saturating_add(self.ttl_ms) });
+        Ok(())
+    }
+
+    /// Oldest unexpired frame for `peer_id`; expired ones ahead of it are
+    /// dropped.
+    pub fn peek(&mut self, peer_id: &str) -> Option<&QueuedFrame> {
+        let now = self.clock.now_ms();
+        let queue = self.queues.get_mut(peer_id)?;
+        while queue.front().is_some_and(|f| f.expires_at <= now) {
+            queue.pop_front();
+        }
+        if queue.is_empty() {
+            self.queues.remove(peer_id);
+            return None;
+        }
+        self.queues.get(peer_id)?.front()
+    }
+
+    /// Send `peer_id`'s frames with `send`, oldest first, removing each once
+    /// sent. Call on reconnect, before any new traffic. Stops at the first
+    /// error and keeps that frame and the rest for the next reconnect.
+    /// Returns how many were sent.
+    pub fn flush<E>(&mut self, peer_id: &str, mut send: impl FnMut(&[u8]) -> Result<(), E>) -> Result<usize, E> {
+        let mut sent = 0;
+        while let Some(frame) = self.peek(peer_id).map(|f| f.frame.clone()) {
+            send(&frame)?;
+            self.ack(peer_id, &frame);
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// Frames waiting for `peer_id`, oldest first.
+    pub fn pending(&self, peer_id: &str) -> Vec<&QueuedFrame> {
+        self.queues.get(peer_id).map(|q| q.iter().collect()).unwrap_or_default()
+    }
+
+    /// Total frame bytes waiting for `peer_id`.
+    pub fn pending_bytes(&self, peer_id: &str) -> usize {
+        self.pending(peer_id).iter().map(|f| f.frame.len()).sum()
+    }
+
+    /// Peers with at least one queued frame.
+    pub fn peers(&self) -> Vec<&str> {
+        self.queues.iter().filter(|(_, q)| !q.is_empty()).map(|(peer, _)| peer.as_str()).collect()
+    }
+
+    /// Drop expired frames for every peer. Returns how many were dropped.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now_ms();
+        let mut dropped = 0;
+        for queue in self.queues.values_mut() {
+            let before = queue.len();
+            queue.retain(|f| f.expires_at > now);
+            dropped += before - queue.len();
+        }
+        self.queues.retain(|_, q| !q.is_empty());
+        dropped
+    }
+
+    /// Encrypt the queues with `key` and write them to `OUTBOX_PATH`.
+    pub fn save(&self, storage: &dyn StorageProvider, key: &ProjectKey) -> Result<(), StorageError> {
+        storage.write(OUTBOX_PATH, &self.sealed(key).map_err(StorageError::IOError)?)
+    }
+
+    /// Load queues written by `save`, dropping what expired meanwhile.
+    /// A missing file yields an empty outbox.
+    pub fn load(storage: &dyn StorageProvider, key: &ProjectKey, clock: Arc<dyn Clock>) -> Result<Self, StorageError> {
+        match storage.read(OUTBOX_PATH) {
+            Ok(sealed) => Self::open_with_clock(key, &sealed, clock).map_err(StorageError::IOError),
+            Err(StorageError::NotFound) => Ok(Self::with_clock(clock)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use holi_clock::MockClock;
+    use holi_p2p::frame::{decode_encrypted_envelope_payload_v1, decode_v1, encode_chat_text_v1, FrameType};
+
+    fn open(key: &ProjectKey, envelope: &[u8]) -> Vec<u8> {
+        let (frame, _) = decode_v1(envelope, 1024 * 1024).unwrap();
+        assert_eq!(frame.frame_type, FrameType::EncryptedEnvelope);
+        let (nonce, ciphertext) = decode_encrypted_envelope_payload_v1(&frame.payload).unwrap();
+        key.decrypt(&[nonce.as_slice(), &ciphertext].concat()).unwrap()
+    }
+
+    #[test]
+    fn test_drain_in_order() {
+        let key = ProjectKey::generate();
+        let mut outbox = Outbox::with_clock(Arc::new(MockClock::new(0)));
+        outbox.enqueue("bob", &key, &encode_chat_text_v1("uno")).unwrap();
+        outbox.enqueue("bob", &key, &encode_chat_text_v1("dos")).unwrap();
+        outbox.enqueue("carol", &key, &encode_chat_text_v1("tres")).unwrap();
+        assert_eq!(outbox.peers(), vec!["bob", "carol"]);
+
+        let mut frames = Vec::new();
+        let sent = outbox.flush("bob", |f| {
+            frames.push(f.to_vec());
+            Ok::<_, ()>(())
+        });
+        assert_eq!(sent, Ok(2));
+        assert_eq!(open(&key, &frames[0]), encode_chat_text_v1("uno"));
+        assert_eq!(open(&key, &frames[1]), encode_chat_text_v1("dos"));
+        assert!(outbox.peek("bob").is_none());
+        assert_eq!(outbox.peers(), vec!["carol"]);
+    }
+
+    #[test]
+    fn test_failed_send_keeps_frames() {
+        let key = ProjectKey::generate();
+        let mut outbox = Outbox::with_clock(Arc::new(MockClock::new(0)));
+        for text in ["uno", "dos", "tres"] {
+            outbox.enqueue("bob", &key, &encode_chat_text_v1(text)).unwrap();
+        }
+
+        // The channel drops after the first frame
+        let mut budget = 1;
+        let sent = outbox.flush("bob", |_| if budget > 0 { budget -= 1; Ok(()) } else { Err("closed") });
+        assert_eq!(sent, Err("closed"));
+        assert_eq!(outbox.pending("bob").len(), 2);
+
+        // Peeking alone removes nothing
+        let frame = outbox.next("bob").unwrap();
+        assert_eq!(outbox.next("bob"), Some(frame.clone()));
+        assert_eq!(open(&key, &frame), encode_chat_text_v1("dos"));
+        assert!(outbox.ack("bob", &frame));
+        assert!(!outbox.ack("bob", &frame));
+        assert_eq!(outbox.flush("bob", |_| Ok::<_, ()>(())), Ok(1));
+        assert!(outbox.peers().is_empty());
+    }
+
+    #[test]
+    fn test_expired_frames_are_not_delivered() {
+        let key = ProjectKey::generate();
+        let clock = Arc::new(MockClock::new(0));
+        let mut outbox = Outbox::with_clock(clock.clone());
+        outbox.set_ttl_ms(1_000);
+        outbox.enqueue("bob", &key, b"old").unwrap();
+        clock.advance(600);
+        outbox.enqueue("bob", &key, b"new").unwrap();
+        outbox.enqueue("carol", &key, b"old").unwrap();
+
+        clock.advance(500);
+        assert_eq!(outbox.purge_expired(), 1);
+        let frame = outbox.next("bob").unwrap();
+        assert_eq!(open(&key, &frame), b"new");
+        assert!(outbox.ack("bob", &frame));
+        assert!(outbox.next("bob").is_none());
+    }
+
+    #[test]
+    fn test_caps() {
+        let key = ProjectKey::generate();
+        let mut outbox = Outbox::with_clock(Arc::new(MockClock::new(0)));
+        outbox.set_max_frames_per_peer(2);
+        outbox.enqueue("bob", &key, b"a").unwrap();
+        outbox.enqueue("bob", &key, b"b").unwrap();
+        assert_eq!(outbox.enqueue("bob", &key, b"c"), Err(OutboxError::Full));
+        outbox.enqueue("carol", &key, b"c").unwrap();
+
+        let one = outbox.pending_bytes("carol");
+        outbox.set_max_frames_per_peer(100);
+        outbox.set_max_bytes_per_peer(one * 2);
+        assert_eq!(outbox.enqueue("carol", &key, b"d"), Ok(()));
+        assert_eq!(outbox.enqueue("carol", &key, b"e"), Err(OutboxError::Full));
+        assert!(matches!(
+            outbox.enqueue("dave", &key, &[0u8; 1024]),
+            Err(OutboxError::FrameTooLarge { .. })
+        ));
+        assert_eq!(outbox.pending("carol").len(), 2);
+    }
+
+    #[test]
+    fn test_encrypted_persistence() {
+        let clock: Arc<dyn Clock> = Arc::new(MockClock::new(0));
+        let storage = InMemoryStorage::new();
+        let storage_key = ProjectKey::generate();
+        let peer_key = ProjectKey::generate();
+
+        let mut outbox = Outbox::with_clock(clock.clone());
+        outbox.enqueue("bob-the-peer", &peer_key, &encode_chat_text_v1("hola")).unwrap();
+        outbox.save(&storage, &storage_key).unwrap();
+
+        let raw = storage.read(OUTBOX_PATH).unwrap();
+        assert!(!raw.windows(12).any(|w| w == b"bob-the-peer"));
+
+        let mut loaded = Outbox::load(&storage, &storage_key, clock.clone()).unwrap();
+        let frame = loaded.next("bob-the-peer").unwrap();
+        assert_eq!(open(&peer_key, &frame), encode_chat_text_v1("hola"));
+
+        assert!(Outbox::load(&storage, &ProjectKey::generate(), clock.clone()).is_err());
+        assert!(Outbox::load(&InMemoryStorage::new(), &storage_key, clock).unwrap().peers().is_empty());
+    }
+}
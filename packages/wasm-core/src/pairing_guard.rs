@@ -0,0 +1,619 @@
+//! Rate limit on pairing codes
+//!
+//! SPAKE2 keeps a short PIN safe from offline guessing, but whoever can start
+//! handshakes can still try every code online. `PairingGuard` counts failed
+//! handshakes per code: each failure doubles the wait before the next
+//! attempt, and after `max_failures` the code is refused until `reset`.
+//!
+//! Handshakes run through the guard: `start_a` / `start_b` /
+//! `start_symmetric` refuse a code that is backed off or locked out, and
+//! `finish` counts the attempt as failed until `confirm` says otherwise. A
+//! wrong code only shows once the first frame sealed under the derived key
+//! fails to open (SPAKE2's `finish` succeeds either way), so a handshake
+//! that is never confirmed stays a failure. Every outcome is written to the
+//! guard's storage before it is returned. Rust callers can also wrap a whole
+//! handshake in `pair`.
+//!
+//! Codes are stored as keyed hashes: a plain hash of a 6-digit PIN is
+//! reversed by trying all million of them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use wasm_bindgen::prelude::*;
+use holi_clock::{default_clock, Clock};
+use holi_pake::{PakeError, PakeSession, PAKE_KEY_LEN};
+use holi_rng::HoliRng;
+use crate::crypto::ProjectKey;
+use crate::storage::{InMemoryStorage, StorageError, StorageProvider};
+
+/// Storage path of the encrypted attempt records.
+pub const PAIRING_GUARD_PATH: &str = "pairing_attempts.bin";
+/// Failed handshakes after which a pairing code is refused for good.
+pub const DEFAULT_MAX_FAILURES: u32 = 5;
+/// Wait after the first failure; doubles with each further failure.
+pub const DEFAULT_BASE_BACKOFF_MS: u64 = 1_000;
+pub const DEFAULT_MAX_BACKOFF_MS: u64 = 5 * 60_000;
+/// Records of codes that are not locked out are forgotten after this long.
+pub const DEFAULT_RECORD_TTL_MS: u64 = 24 * 60 * 60_000;
+
+/// BLAKE3 key derivation context for the code hash key.
+const CODE_HASH_KEY_CONTEXT: &str = "holi.tools pairing guard code key v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PairingPolicy {
+    pub max_failures: u32,
+    pub base_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub record_ttl_ms: u64,
+}
+
+impl Default for PairingPolicy {
+    fn default() -> Self {
+        PairingPolicy {
+            max_failures: DEFAULT_MAX_FAILURES,
+            base_backoff_ms: DEFAULT_BASE_BACKOFF_MS,
+            max_backoff_ms: DEFAULT_MAX_BACKOFF_MS,
+            record_ttl_ms: DEFAULT_RECORD_TTL_MS,
+        }
+    }
+}
+
+impl PairingPolicy {
+    /// Wait required after `failures` consecutive failures.
+    pub fn backoff_ms(&self, failures: u32) -> u64 {
+        if failures == 0 {
+            return 0;
+        }
+        let factor = 1u64.checked_shl(failures - 1).unwrap_or(u64::MAX);
+        self.base_backoff_ms.saturating_mul(factor).min(self.max_backoff_ms)
+    }
+}
+
+/// Why a handshake with a pairing code was refused. Serializes as
+/// `{ "kind": "backoff", "retryAfterMs": .., "failures": .. }` or
+/// `{ "kind": "lockedOut", "failures": .. }` for the UI.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PairingLockout {
+    /// Too soon after the last failure.
+    #[serde(rename_all = "camelCase")]
+    Backoff { retry_after_ms: u64, failures: u32 },
+    /// Too many failures; this code must be replaced with a new one.
+    LockedOut { failures: u32 },
+}
+
+impl fmt::Display for PairingLockout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PairingLockout::Backoff { retry_after_ms, failures } => write!(
+                f,
+                "Pairing code failed {} times; retry in {} ms",
+                failures, retry_after_ms
+            ),
+            PairingLockout::LockedOut { failures } => write!(
+                f,
+                "Pairing code locked after {} failed attempts; generate a new code",
+                failures
+            ),
+        }
+    }
+}
+
+/// Why a guarded handshake could not go on.
+#[derive(Debug, Clone)]
+pub enum PairingError {
+    Lockout(PairingLockout),
+    Pake(PakeError),
+    Storage(StorageError),
+    /// `finish` on a finished session, or `confirm` on one not finished or
+    /// already confirmed.
+    WrongStage,
+}
+
+impl fmt::Display for PairingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PairingError::Lockout(lockout) => lockout.fmt(f),
+            PairingError::Pake(e) => e.fmt(f),
+            PairingError::Storage(e) => write!(f, "Storage error: {:?}", e),
+            PairingError::WrongStage => write!(f, "Pairing session is not at that stage"),
+        }
+    }
+}
+
+/// Lockouts go to JS as the `PairingLockout` object, the rest as strings.
+fn pairing_error(e: PairingError) -> JsValue {
+    match e {
+        PairingError::Lockout(lockout) => lockout_value(lockout),
+        e => JsValue::from_str(&e.to_string()),
+    }
+}
+
+fn lockout_value(lockout: PairingLockout) -> JsValue {
+    serde_wasm_bindgen::to_value(&lockout).unwrap_or_else(|_| lockout.to_string().into())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+struct AttemptRecord {
+    failures: u32,
+    last_failure_ms: u64,
+}
+
+/// SPAKE2 handshake started by a `PairingGuard`, for one pairing code.
+#[wasm_bindgen]
+pub struct PairingSession {
+    code_hash: String,
+    pake: Option<PakeSession>,
+    message: Vec<u8>,
+    /// Finished, and counted as failed until confirmed.
+    pending: bool,
+}
+
+#[wasm_bindgen]
+impl PairingSession {
+    /// Message to send to the peer.
+    pub fn message(&self) -> Vec<u8> {
+        self.message.clone()
+    }
+}
+
+/// Rate limit on PAKE handshakes per pairing code, with its records
+/// encrypted and the codes hashed under a `ProjectKey`.
+#[wasm_bindgen]
+pub struct PairingGuard {
+    records: HashMap<String, AttemptRecord>,
+    policy: PairingPolicy,
+    clock: Arc<dyn Clock>,
+    key: ProjectKey,
+    hash_key: [u8; 32],
+    storage: Arc<dyn StorageProvider>,
+}
+
+#[wasm_bindgen]
+impl PairingGuard {
+    #[wasm_bindgen(constructor)]
+    pub fn new(key: &ProjectKey) -> Self {
+        Self::with_clock(key, Arc::new(default_clock()))
+    }
+
+    /// Start SPAKE2 role A with `code`. Throws the `PairingLockout` object
+    /// if the code is refused.
+    pub fn start_a(&self, code: &[u8], id_a: &[u8], id_b: &[u8]) -> Result<PairingSession, JsValue> {
+        self.start(code, |rng| PakeSession::start_a(code, id_a, id_b, rng))
+            .map_err(pairing_error)
+    }
+
+    /// Start SPAKE2 role B with `code`, refused like `start_a`.
+    pub fn start_b(&self, code: &[u8], id_a: &[u8], id_b: &[u8]) -> Result<PairingSession, JsValue> {
+        self.start(code, |rng| PakeSession::start_b(code, id_a, id_b, rng))
+            .map_err(pairing_error)
+    }
+
+    /// Start SPAKE2 symmetric mode with `code`, refused like `start_a`.
+    pub fn start_symmetric(&self, code: &[u8], id_s: &[u8]) -> Result<PairingSession, JsValue> {
+        self.start(code, |rng| PakeSession::start_symmetric(code, id_s, rng))
+            .map_err(pairing_error)
+    }
+
+    /// Derive the 32-byte session key from the peer's message. The attempt
+    /// counts as failed until `confirm(session, true)`.
+    pub fn finish(&mut self, session: &mut PairingSession, inbound_msg: &[u8]) -> Result<Vec<u8>, JsValue> {
+        self.finish_session(session, inbound_msg)
+            .map(|key| key.to_vec())
+            .map_err(pairing_error)
+    }
+
+    /// Call once the first frame sealed under the session key was tried:
+    /// `opened` if it opened. Returns the lockout now in effect after a
+    /// failure, or `undefined`.
+    pub fn confirm(&mut self, session: &mut PairingSession, opened: bool) -> Result<JsValue, JsValue> {
+        match self.confirm_session(session, opened) {
+            Ok(Some(lockout)) => Ok(lockout_value(lockout)),
+            Ok(None) => Ok(JsValue::UNDEFINED),
+            Err(e) => Err(pairing_error(e)),
+        }
+    }
+
+    /// Records encrypted under the guard's key, for `PairingGuard.open`.
+    pub fn sealed(&self) -> Result<Vec<u8>, String> {
+        let json = serde_json::to_vec(&self.records).map_err(|e| format!("Serialization failed: {}", e))?;
+        self.key.encrypt(&json)
+    }
+
+    /// Guard with the records of `sealed`.
+    pub fn open(key: &ProjectKey, sealed: &[u8]) -> Result<PairingGuard, String> {
+        Self::open_with_clock(key, sealed, Arc::new(default_clock()))
+    }
+}
+
+impl PairingGuard {
+    /// Guard that keeps its records in memory only; see `load`.
+    pub fn with_clock(key: &ProjectKey, clock: Arc<dyn Clock>) -> Self {
+        PairingGuard {
+            records: HashMap::new(),
+            policy: PairingPolicy::default(),
+            clock,
+            key: key.clone(),
+            hash_key: blake3::derive_key(CODE_HASH_KEY_CONTEXT, &key.key_bytes),
+            storage: Arc::new(InMemoryStorage::new()),
+        }
+    }
+
+    fn open_with_clock(key: &ProjectKey, sealed: &[u8], clock: Arc<dyn Clock>) -> Result<Self, String> {
+        let mut guard = Self::with_clock(key, clock);
+        let json = key.decrypt(sealed)?;
+        guard.records = serde_json::from_slice(&json).map_err(|e| format!("Deserialization failed: {}", e))?;
+        guard.prune();
+        Ok(guard)
+    }
+
+    /// Hex keyed hash of the code, so stored records don't reveal it.
+    fn code_hash(&self, code: &[u8]) -> String {
+        blake3::keyed_hash(&self.hash_key, code).to_hex().to_string()
+    }
+
+    fn start(
+        &self,
+        code: &[u8],
+        start: impl FnOnce(HoliRng) -> PakeSession,
+    ) -> Result<PairingSession, PairingError> {
+        let code_hash = self.code_hash(code);
+        self.check_hash(&code_hash).map_err(PairingError::Lockout)?;
+        let pake = start(HoliRng);
+        Ok(PairingSession {
+            code_hash,
+            message: pake.message().to_vec(),
+            pake: Some(pake),
+            pending: false,
+        })
+    }
+
+    /// Derive the session key, recording a failure first so that a
+    /// handshake which is never confirmed still counts.
+    pub fn finish_session(
+        &mut self,
+        session: &mut PairingSession,
+        inbound_msg: &[u8],
+    ) -> Result<[u8; PAKE_KEY_LEN], PairingError> {
+        let pake = session.pake.take().ok_or(PairingError::WrongStage)?;
+        // Another session with the same code may have failed since this one started
+        self.check_hash(&session.code_hash).map_err(PairingError::Lockout)?;
+        self.record_failure_hash(&session.code_hash);
+        self.save().map_err(PairingError::Storage)?;
+        let key = pake.finish(inbound_msg).map_err(PairingError::Pake)?;
+        session.pending = true;
+        Ok(key)
+    }
+
+    /// Settle a finished session: `opened` clears the code's failures,
+    /// otherwise the failure `finish` recorded stands and the lockout now in
+    /// effect is returned.
+    pub fn confirm_session(
+        &mut self,
+        session: &mut PairingSession,
+        opened: bool,
+    ) -> Result<Option<PairingLockout>, PairingError> {
+        if !session.pending {
+            return Err(PairingError::WrongStage);
+        }
+        session.pending = false;
+        if opened {
+            self.records.remove(&session.code_hash);
+            self.save().map_err(PairingError::Storage)?;
+            return Ok(None);
+        }
+        Ok(self.check_hash(&session.code_hash).err())
+    }
+
+    /// Run a pairing `handshake` with `code`, through key confirmation:
+    /// refused while the code is backed off or locked out, otherwise its
+    /// outcome is recorded and returned.
+    pub fn pair<T, E>(
+        &mut self,
+        code: &[u8],
+        handshake: impl FnOnce() -> Result<T, E>,
+    ) -> Result<Result<T, E>, PairingLockout> {
+        self.check(code)?;
+        let outcome = handshake();
+        self.record(code, &outcome);
+        Ok(outcome)
+    }
+
+    pub fn policy(&self) -> &PairingPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: PairingPolicy) {
+        self.policy = PairingPolicy {
+            max_failures: policy.max_failures.max(1),
+            ..policy
+        };
+    }
+
+    /// Whether a handshake with `code` may start now.
+    pub fn check(&self, code: &[u8]) -> Result<(), PairingLockout> {
+        self.check_hash(&self.code_hash(code))
+    }
+
+    fn check_hash(&self, code_hash: &str) -> Result<(), PairingLockout> {
+        let Some(record) = self.records.get(code_hash) else {
+            return Ok(());
+        };
+        if record.failures >= self.policy.max_failures {
+            return Err(PairingLockout::LockedOut { failures: record.failures });
+        }
+        let ready_at = record.last_failure_ms + self.policy.backoff_ms(record.failures);
+        let now = self.clock.now_ms();
+        if now < ready_at {
+            return Err(PairingLockout::Backoff {
+                retry_after_ms: ready_at - now,
+                failures: record.failures,
+            });
+        }
+        Ok(())
+    }
+
+    /// Count a failed handshake. Returns the lockout now in effect.
+    pub fn record_failure(&mut self, code: &[u8]) -> PairingLockout {
+        self.record_failure_hash(&self.code_hash(code))
+    }
+
+    fn record_failure_hash(&mut self, code_hash: &str) -> PairingLockout {
+        let now = self.clock.now_ms();
+        let record = self.records.entry(code_hash.to_string()).or_insert(AttemptRecord {
+            failures: 0,
+            last_failure_ms: now,
+        });
+        record.failures = record.failures.saturating_add(1);
+        record.last_failure_ms = now;
+        let failures = record.failures;
+        if failures >= self.policy.max_failures {
+            PairingLockout::LockedOut { failures }
+        } else {
+            PairingLockout::Backoff {
+                retry_after_ms: self.policy.backoff_ms(failures),
+                failures,
+            }
+        }
+    }
+
+    /// A successful handshake clears the code's failures.
+    pub fn record_success(&mut self, code: &[u8]) {
+        self.records.remove(&self.code_hash(code));
+    }
+
+    /// Record the outcome of a handshake, after key confirmation.
+    pub fn record<T, E>(&mut self, code: &[u8], outcome: &Result<T, E>) -> Option<PairingLockout> {
+        match outcome {
+            Ok(_) => {
+                self.record_success(code);
+                None
+            }
+            Err(_) => Some(self.record_failure(code)),
+        }
+    }
+
+    pub fn failures(&self, code: &[u8]) -> u32 {
+        self.records.get(&self.code_hash(code)).map_or(0, |r| r.failures)
+    }
+
+    /// Forget a code's failures, e.g. when the user confirms it in person.
+    pub fn reset(&mut self, code: &[u8]) {
+        self.records.remove(&self.code_hash(code));
+    }
+
+    /// Drop records past `record_ttl_ms`, except lockouts.
+    pub fn prune(&mut self) {
+        let now = self.clock.now_ms();
+        let policy = self.policy;
+        self.records.retain(|_, r| {
+            r.failures >= policy.max_failures || now.saturating_sub(r.last_failure_ms) < policy.record_ttl_ms
+        });
+    }
+
+    /// Write the sealed records to `PAIRING_GUARD_PATH` of the guard's storage.
+    pub fn save(&self) -> Result<(), StorageError> {
+        self.storage.write(PAIRING_GUARD_PATH, &self.sealed().map_err(StorageError::IOError)?)
+    }
+
+    /// Load records written by `save`; the guard keeps saving to `storage`.
+    /// A missing file yields an empty guard.
+    pub fn load(
+        storage: Arc<dyn StorageProvider>,
+        key: &ProjectKey,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, StorageError> {
+        let mut guard = match storage.read(PAIRING_GUARD_PATH) {
+            Ok(sealed) => Self::open_with_clock(key, &sealed, clock).map_err(StorageError::IOError)?,
+            Err(StorageError::NotFound) => Self::with_clock(key, clock),
+            Err(e) => return Err(e),
+        };
+        guard.storage = storage;
+        Ok(guard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use holi_clock::MockClock;
+
+    #[test]
+    fn test_backoff_doubles_then_locks_out() {
+        let clock = Arc::new(MockClock::new(0));
+        let mut guard = PairingGuard::with_clock(&ProjectKey::generate(), clock.clone());
+        let code = b"1234";
+
+        assert_eq!(guard.check(code), Ok(()));
+        assert_eq!(
+            guard.record_failure(code),
+            PairingLockout::Backoff { retry_after_ms: 1_000, failures: 1 }
+        );
+        assert_eq!(
+            guard.check(code),
+            Err(PairingLockout::Backoff { retry_after_ms: 1_000, failures: 1 })
+        );
+        clock.advance(1_000);
+        assert_eq!(guard.check(code), Ok(()));
+
+        guard.record_failure(code);
+        clock.advance(1_500);
+        assert_eq!(
+            guard.check(code),
+            Err(PairingLockout::Backoff { retry_after_ms: 500, failures: 2 })
+        );
+        // Other codes are unaffected
+        assert_eq!(guard.check(b"9999"), Ok(()));
+
+        for _ in 0..3 {
+            guard.record_failure(code);
+        }
+        clock.advance(DEFAULT_MAX_BACKOFF_MS * 10);
+        assert_eq!(guard.check(code), Err(PairingLockout::LockedOut { failures: 5 }));
+
+        guard.reset(code);
+        assert_eq!(guard.check(code), Ok(()));
+    }
+
+    #[test]
+    fn test_success_clears_failures() {
+        let clock = Arc::new(MockClock::new(0));
+        let mut guard = PairingGuard::with_clock(&ProjectKey::generate(), clock.clone());
+        let code = b"4321";
+
+        let failed: Result<(), &str> = Err("SPAKE2 failed");
+        assert!(guard.record(code, &failed).is_some());
+        assert_eq!(guard.failures(code), 1);
+        assert_eq!(guard.record(code, &Ok::<_, ()>(())), None);
+        assert_eq!(guard.failures(code), 0);
+    }
+
+    #[test]
+    fn test_pair_records_key_confirmation() {
+        let clock = Arc::new(MockClock::new(0));
+        let mut guard = PairingGuard::with_clock(&ProjectKey::generate(), clock.clone());
+        let code = b"482913";
+
+        assert_eq!(guard.pair(code, || Err::<(), _>("key confirmation failed")), Ok(Err("key confirmation failed")));
+        let mut ran = false;
+        let refused = guard.pair(code, || {
+            ran = true;
+            Ok::<_, ()>(())
+        });
+        assert_eq!(refused, Err(PairingLockout::Backoff { retry_after_ms: 1_000, failures: 1 }));
+        assert!(!ran);
+
+        clock.advance(1_000);
+        assert_eq!(guard.pair(code, || Ok::<_, ()>("key")), Ok(Ok("key")));
+        assert_eq!(guard.failures(code), 0);
+    }
+
+    #[test]
+    fn test_code_hashes_are_keyed() {
+        let a = PairingGuard::new(&ProjectKey::generate());
+        let b = PairingGuard::new(&ProjectKey::generate());
+        assert_ne!(a.code_hash(b"123456"), b.code_hash(b"123456"));
+        assert_ne!(a.code_hash(b"123456"), a.code_hash(b"123457"));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = PairingPolicy::default();
+        assert_eq!(policy.backoff_ms(0), 0);
+        assert_eq!(policy.backoff_ms(3), 4_000);
+        assert_eq!(policy.backoff_ms(40), DEFAULT_MAX_BACKOFF_MS);
+        assert_eq!(policy.backoff_ms(u32::MAX), DEFAULT_MAX_BACKOFF_MS);
+    }
+
+    #[test]
+    fn test_persists_lockouts_and_prunes_stale_records() {
+        let clock = Arc::new(MockClock::new(0));
+        let storage = Arc::new(InMemoryStorage::new());
+        let key = ProjectKey::generate();
+        let mut guard = PairingGuard::load(storage.clone(), &key, clock.clone()).unwrap();
+
+        for _ in 0..DEFAULT_MAX_FAILURES {
+            guard.record_failure(b"locked");
+        }
+        guard.record_failure(b"stale");
+        guard.save().unwrap();
+        assert!(!storage.read(PAIRING_GUARD_PATH).unwrap().windows(6).any(|w| w == b"locked"));
+
+        clock.advance(DEFAULT_RECORD_TTL_MS);
+        let loaded = PairingGuard::load(storage, &key, clock.clone()).unwrap();
+        assert_eq!(loaded.check(b"locked"), Err(PairingLockout::LockedOut { failures: 5 }));
+        assert_eq!(loaded.failures(b"stale"), 0);
+
+        let empty = PairingGuard::load(Arc::new(InMemoryStorage::new()), &key, clock).unwrap();
+        assert_eq!(empty.failures(b"locked"), 0);
+    }
+
+    /// Role A through `guard`, and a plain role B peer using `peer_code`.
+    fn start_pair(guard: &PairingGuard, code: &[u8], peer_code: &[u8]) -> (PairingSession, PakeSession) {
+        let session = guard.start_a(code, b"holi:test:a", b"holi:test:b").unwrap();
+        let peer = PakeSession::start_b(peer_code, b"holi:test:a", b"holi:test:b", HoliRng);
+        (session, peer)
+    }
+
+    #[test]
+    fn test_sessions_are_gated_and_persisted() {
+        let clock = Arc::new(MockClock::new(0));
+        let storage = Arc::new(InMemoryStorage::new());
+        let key = ProjectKey::generate();
+        let mut guard = PairingGuard::load(storage.clone(), &key, clock.clone()).unwrap();
+        let code = b"482913";
+
+        // A wrong code still derives a key; confirming that it failed keeps the failure
+        let (mut session, peer) = start_pair(&guard, code, b"482914");
+        let key_a = guard.finish_session(&mut session, peer.message()).unwrap();
+        assert_eq!(PairingGuard::load(storage.clone(), &key, clock.clone()).unwrap().failures(code), 1);
+        assert_eq!(
+            guard.confirm_session(&mut session, false).unwrap(),
+            Some(PairingLockout::Backoff { retry_after_ms: 1_000, failures: 1 })
+        );
+        assert!(matches!(guard.confirm_session(&mut session, false), Err(PairingError::WrongStage)));
+        assert!(matches!(guard.finish_session(&mut session, peer.message()), Err(PairingError::WrongStage)));
+        assert_ne!(key_a, peer.finish(&session.message()).unwrap());
+        assert!(guard.check(code).is_err());
+
+        // The right code, confirmed, clears the record in storage too
+        clock.advance(1_000);
+        let (mut session, peer) = start_pair(&guard, code, code);
+        let key_a = guard.finish_session(&mut session, peer.message()).unwrap();
+        assert_eq!(key_a, peer.finish(&session.message()).unwrap());
+        assert_eq!(guard.confirm_session(&mut session, true).unwrap(), None);
+        assert_eq!(PairingGuard::load(storage, &key, clock).unwrap().failures(code), 0);
+    }
+
+    #[test]
+    fn test_unconfirmed_sessions_count_as_failures() {
+        let clock = Arc::new(MockClock::new(0));
+        let mut guard = PairingGuard::with_clock(&ProjectKey::generate(), clock.clone());
+        let code = b"1234";
+
+        for failures in 1..=DEFAULT_MAX_FAILURES {
+            let (mut session, peer) = start_pair(&guard, code, code);
+            guard.finish_session(&mut session, peer.message()).unwrap();
+            assert_eq!(guard.failures(code), failures);
+            clock.advance(DEFAULT_MAX_BACKOFF_MS);
+        }
+        assert_eq!(guard.check(code), Err(PairingLockout::LockedOut { failures: 5 }));
+
+        // A malformed peer message counts as well
+        let mut guard = PairingGuard::with_clock(&ProjectKey::generate(), clock);
+        let (mut session, _) = start_pair(&guard, code, code);
+        assert!(matches!(guard.finish_session(&mut session, b"short"), Err(PairingError::Pake(_))));
+        assert_eq!(guard.failures(code), 1);
+    }
+
+    #[test]
+    fn test_lockout_serializes_for_ui() {
+        let json = serde_json::to_string(&PairingLockout::Backoff { retry_after_ms: 10, failures: 2 }).unwrap();
+        assert_eq!(json, r#"{"kind":"backoff","retryAfterMs":10,"failures":2}"#);
+        let json = serde_json::to_string(&PairingLockout::LockedOut { failures: 5 }).unwrap();
+        assert_eq!(json, r#"{"kind":"lockedOut","failures":5}"#);
+    }
+}
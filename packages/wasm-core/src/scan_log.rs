@@ -0,0 +1,206 @@
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use holi_clock::{default_clock, Clock};
+use crate::crypto::ProjectKey;
+use crate::storage::{StorageError, StorageProvider};
+
+/// Storage path of the encrypted scan history.
+pub const SCAN_LOG_PATH: &str = "scan_log.bin";
+/// Same content scanned again within this window (ms) is not recorded twice.
+pub const DEFAULT_DEDUPE_WINDOW_MS: u64 = 5_000;
+/// Oldest entries are dropped beyond this many.
+pub const DEFAULT_MAX_ENTRIES: usize = 200;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanSource {
+    Camera,
+    Image,
+    Clipboard,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScanEntry {
+    /// Hex SHA-256 of `content`, used for dedupe.
+    pub content_hash: String,
+    pub content: String,
+    pub timestamp: u64,
+    pub source: ScanSource,
+}
+
+fn content_hash(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+/// History of decoded QR results, newest last.
+#[derive(Debug)]
+pub struct ScanLog {
+    entries: Vec<ScanEntry>,
+    dedupe_window_ms: u64,
+    max_entries: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for ScanLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScanLog {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(default_clock()))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        ScanLog {
+            entries: Vec::new(),
+            dedupe_window_ms: DEFAULT_DEDUPE_WINDOW_MS,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            clock,
+        }
+    }
+
+    pub fn set_dedupe_window_ms(&mut self, window_ms: u64) {
+        self.dedupe_window_ms = window_ms;
+    }
+
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries.max(1);
+        self.trim();
+    }
+
+    /// Record a decoded result. Returns false if the same content was
+    /// already recorded within the dedupe window.
+    pub fn record(&mut self, content: &str, source: ScanSource) -> bool {
+        let now = self.clock.now_ms();
+        let hash = content_hash(content);
+
+        let duplicate = self
+            .entries
+            .iter()
+            .rev()
+            .take_while(|e| now.saturating_sub(e.timestamp) < self.dedupe_window_ms)
+            .any(|e| e.content_hash == hash);
+        if duplicate {
+            return false;
+        }
+
+        self.entries.push(ScanEntry {
+            content_hash: hash,
+            content: content.to_string(),
+            timestamp: now,
+            source,
+        });
+        self.trim();
+        true
+    }
+
+    /// Up to `limit` entries, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<&ScanEntry> {
+        self.entries.iter().rev().take(limit).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn trim(&mut self) {
+        if self.entries.len() > self.max_entries {
+            let excess = self.entries.len() - self.max_entries;
+            self.entries.drain(..excess);
+        }
+    }
+
+    /// Encrypt the history with `key` and write it to `SCAN_LOG_PATH`.
+    pub fn save(&self, storage: &dyn StorageProvider, key: &ProjectKey) -> Result<(), StorageError> {
+        let json = serde_json::to_vec(&self.entries)
+            .map_err(|e| StorageError::IOError(format!("Serialization failed: {}", e)))?;
+        let encrypted = key.encrypt(&json).map_err(StorageError::IOError)?;
+        storage.write(SCAN_LOG_PATH, &encrypted)
+    }
+
+    /// Load a history written by `save`. A missing file yields an empty log.
+    pub fn load(storage: &dyn StorageProvider, key: &ProjectKey, clock: Arc<dyn Clock>) -> Result<Self, StorageError> {
+        let mut log = Self::with_clock(clock);
+        let encrypted = match storage.read(SCAN_LOG_PATH) {
+            Ok(data) => data,
+            Err(StorageError::NotFound) => return Ok(log),
+            Err(e) => return Err(e),
+        };
+        let json = key.decrypt(&encrypted).map_err(StorageError::IOError)?;
+        log.entries = serde_json::from_slice(&json)
+            .map_err(|e| StorageError::IOError(format!("Deserialization failed: {}", e)))?;
+        log.trim();
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use holi_clock::MockClock;
+
+    #[test]
+    fn test_dedupe_within_window() {
+        let clock = Arc::new(MockClock::new(10_000));
+        let mut log = ScanLog::with_clock(clock.clone());
+
+        assert!(log.record("https://holi.tools", ScanSource::Camera));
+        clock.advance(1_000);
+        assert!(!log.record("https://holi.tools", ScanSource::Camera));
+        assert!(log.record("WIFI:S:home;;", ScanSource::Camera));
+
+        clock.advance(DEFAULT_DEDUPE_WINDOW_MS);
+        assert!(log.record("https://holi.tools", ScanSource::Image));
+        assert_eq!(log.len(), 3);
+
+        let recent = log.recent(2);
+        assert_eq!(recent[0].content, "https://holi.tools");
+        assert_eq!(recent[0].source, ScanSource::Image);
+        assert_eq!(recent[1].content, "WIFI:S:home;;");
+    }
+
+    #[test]
+    fn test_max_entries_drops_oldest() {
+        let clock = Arc::new(MockClock::new(0));
+        let mut log = ScanLog::with_clock(clock);
+        log.set_max_entries(2);
+        log.record("a", ScanSource::Camera);
+        log.record("b", ScanSource::Camera);
+        log.record("c", ScanSource::Camera);
+
+        let contents: Vec<_> = log.recent(10).iter().map(|e| e.content.as_str()).collect();
+        assert_eq!(contents, vec!["c", "b"]);
+    }
+
+    #[test]
+    fn test_encrypted_persistence() {
+        let clock: Arc<dyn Clock> = Arc::new(MockClock::new(0));
+        let storage = InMemoryStorage::new();
+        let key = ProjectKey::generate();
+
+        let mut log = ScanLog::with_clock(clock.clone());
+        log.record("secret payload", ScanSource::Clipboard);
+        log.save(&storage, &key).unwrap();
+
+        let raw = storage.read(SCAN_LOG_PATH).unwrap();
+        assert!(!raw.windows(6).any(|w| w == b"secret"));
+
+        let loaded = ScanLog::load(&storage, &key, clock.clone()).unwrap();
+        assert_eq!(loaded.recent(1)[0].content, "secret payload");
+
+        assert!(ScanLog::load(&storage, &ProjectKey::generate(), clock.clone()).is_err());
+        assert!(ScanLog::load(&InMemoryStorage::new(), &key, clock).unwrap().is_empty());
+    }
+}
@@ -1,6 +1,5 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use wasm_bindgen::prelude::*;
 
 #[derive(Debug, Clone)]
 pub enum StorageError {
@@ -31,6 +30,12 @@ impl InMemoryStorage {
     }
 }
 
+impl Default for InMemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StorageProvider for InMemoryStorage {
     fn read(&self, path: &str) -> Result<Vec<u8>, StorageError> {
         let files = self.files.lock().unwrap();
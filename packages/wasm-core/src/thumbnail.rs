@@ -0,0 +1,250 @@
+//! Encrypted image previews on file offers.
+//!
+//! The sender shrinks the image client-side (`attach_thumbnail`), encodes it
+//! as a small JPEG and encrypts it with the session key, offer id as
+//! associated data, so the preview rides in the FileOffer without the relay
+//! seeing it. The receiver opens it with the same key (`open_thumbnail`) and
+//! gets RGBA pixels ready for an `ImageData`. Only attach thumbnails for
+//! peers that advertise `HELLO_CAP_OFFER_THUMBNAIL`.
+
+use std::io::Cursor;
+
+use chacha20poly1305::{aead::Aead, aead::KeyInit, aead::Payload, XChaCha20Poly1305};
+use holi_p2p::frame::{
+    decode_file_offer_payload_v1, decode_v1, encode_file_offer_v1, FileOffer, FrameType, OfferThumbnail,
+    ENVELOPE_NONCE_LEN,
+};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageReader, Limits};
+use holi_rng::HoliRng;
+use rand::RngCore;
+use wasm_bindgen::prelude::*;
+
+/// Longest side of a thumbnail, in pixels.
+pub const DEFAULT_MAX_EDGE: u32 = 160;
+/// Received thumbnails larger than this on either side are refused before
+/// decoding.
+pub const MAX_DECODED_EDGE: u32 = 512;
+/// Source images larger than this on either side are refused before decoding.
+pub const MAX_SOURCE_EDGE: u32 = 16384;
+/// Most bytes the decoder may allocate for a source image.
+pub const MAX_SOURCE_ALLOC: u64 = 256 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 70;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThumbnailError {
+    /// The source or received image could not be read.
+    Decode(String),
+    Encode(String),
+    Encrypt,
+    /// Wrong key, or the thumbnail was not made for this offer.
+    Decrypt,
+    /// The offer carries no thumbnail.
+    Missing,
+}
+
+/// Decoded thumbnail, 4 bytes per pixel, rows top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Shrink `image_bytes` (any PNG or JPEG) so neither side exceeds
+/// `max_edge`, and encode it as JPEG. Smaller images keep their size.
+pub fn make_thumbnail(image_bytes: &[u8], max_edge: u32) -> Result<Vec<u8>, ThumbnailError> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_EDGE);
+    limits.max_image_height = Some(MAX_SOURCE_EDGE);
+    limits.max_alloc = Some(MAX_SOURCE_ALLOC);
+    let image = decode_with_limits(image_bytes, limits)?;
+    let max_edge = max_edge.max(1);
+    let image = if image.width() > max_edge || image.height() > max_edge {
+        image.thumbnail(max_edge, max_edge)
+    } else {
+        image
+    };
+    // JPEG has no alpha; transparent areas come out black
+    let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+    let mut out = Vec::new();
+    rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))
+        .map_err(|e| ThumbnailError::Encode(e.to_string()))?;
+    Ok(out)
+}
+
+/// Encrypt an encoded thumbnail for the offer `offer_id`.
+pub fn seal_thumbnail(
+    session_key: &[u8; 32],
+    offer_id: &str,
+    encoded: &[u8],
+) -> Result<OfferThumbnail, ThumbnailError> {
+    let mut nonce = [0u8; ENVELOPE_NONCE_LEN];
+    HoliRng.fill_bytes(&mut nonce);
+    let ciphertext = XChaCha20Poly1305::new(session_key.into())
+        .encrypt((&nonce).into(), Payload { msg: encoded, aad: offer_id.as_bytes() })
+        .map_err(|_| ThumbnailError::Encrypt)?;
+    Ok(OfferThumbnail { nonce, ciphertext })
+}
+
+/// Make a thumbnail of `image_bytes` and put it on `offer`, replacing any
+/// thumbnail already there.
+pub fn attach_thumbnail(
+    offer: &mut FileOffer,
+    image_bytes: &[u8],
+    session_key: &[u8; 32],
+    max_edge: u32,
+) -> Result<(), ThumbnailError> {
+    let encoded = make_thumbnail(image_bytes, max_edge)?;
+    offer.thumbnail = Some(seal_thumbnail(session_key, &offer.id, &encoded)?);
+    Ok(())
+}
+
+/// Decrypt and decode the thumbnail on `offer`.
+pub fn open_thumbnail(offer: &FileOffer, session_key: &[u8; 32]) -> Result<RgbaImage, ThumbnailError> {
+    let thumbnail = offer.thumbnail.as_ref().ok_or(ThumbnailError::Missing)?;
+    let encoded = XChaCha20Poly1305::new(session_key.into())
+        .decrypt(
+            (&thumbnail.nonce).into(),
+            Payload { msg: &thumbnail.ciphertext, aad: offer.id.as_bytes() },
+        )
+        .map_err(|_| ThumbnailError::Decrypt)?;
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODED_EDGE);
+    limits.max_image_height = Some(MAX_DECODED_EDGE);
+    let image = decode_with_limits(&encoded, limits)?.to_rgba8();
+    Ok(RgbaImage { width: image.width(), height: image.height(), pixels: image.into_raw() })
+}
+
+/// Decode a PNG or JPEG, refusing it before any pixels are read if it is
+/// over `limits`.
+fn decode_with_limits(bytes: &[u8], limits: Limits) -> Result<DynamicImage, ThumbnailError> {
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| ThumbnailError::Decode(e.to_string()))?;
+    reader.limits(limits);
+    reader.decode().map_err(|e| ThumbnailError::Decode(e.to_string()))
+}
+
+fn session_key(bytes: &[u8]) -> Result<[u8; 32], JsValue> {
+    bytes.try_into().map_err(|_| JsValue::from_str("Session key must be 32 bytes"))
+}
+
+fn decode_offer(frame_bytes: &[u8]) -> Result<FileOffer, JsValue> {
+    let (frame, _used) = decode_v1(frame_bytes, 1024 * 1024)
+        .map_err(|e| JsValue::from_str(&format!("Bad frame: {:?}", e)))?;
+    if frame.frame_type != FrameType::FileOffer {
+        return Err(JsValue::from_str("Expected a FileOffer frame"));
+    }
+    decode_file_offer_payload_v1(&frame.payload).map_err(|e| JsValue::from_str(&format!("Bad FileOffer: {:?}", e)))
+}
+
+/// Re-encode a FileOffer frame with an encrypted thumbnail of `image_bytes`.
+/// `max_edge` of 0 uses `DEFAULT_MAX_EDGE`.
+#[wasm_bindgen]
+pub fn attach_offer_thumbnail(
+    offer_frame: &[u8],
+    image_bytes: &[u8],
+    session_key_bytes: &[u8],
+    max_edge: u32,
+) -> Result<Vec<u8>, JsValue> {
+    let mut offer = decode_offer(offer_frame)?;
+    let max_edge = if max_edge == 0 { DEFAULT_MAX_EDGE } else { max_edge };
+    attach_thumbnail(&mut offer, image_bytes, &session_key(session_key_bytes)?, max_edge)
+        .map_err(|e| JsValue::from_str(&format!("Thumbnail error: {:?}", e)))?;
+    Ok(encode_file_offer_v1(&offer))
+}
+
+/// `{ width, height, rgba }` for the thumbnail on a FileOffer frame, or
+/// null if it has none. `rgba` fits `new ImageData(rgba, width, height)`.
+#[wasm_bindgen]
+pub fn open_offer_thumbnail(offer_frame: &[u8], session_key_bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let offer = decode_offer(offer_frame)?;
+    let image = match open_thumbnail(&offer, &session_key(session_key_bytes)?) {
+        Ok(image) => image,
+        Err(ThumbnailError::Missing) => return Ok(JsValue::NULL),
+        Err(e) => return Err(JsValue::from_str(&format!("Thumbnail error: {:?}", e))),
+    };
+    let obj = js_sys::Object::new();
+    js_sys::Reflect::set(&obj, &"width".into(), &image.width.into())?;
+    js_sys::Reflect::set(&obj, &"height".into(), &image.height.into())?;
+    let rgba = js_sys::Uint8ClampedArray::from(image.pixels.as_slice());
+    js_sys::Reflect::set(&obj, &"rgba".into(), &rgba.into())?;
+    Ok(obj.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, y| Rgb([(x % 256) as u8, (y % 256) as u8, 128]));
+        let mut out = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(image).write_to(&mut out, ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    fn offer(id: &str) -> FileOffer {
+        FileOffer {
+            id: id.to_string(),
+            filename: "photo.png".to_string(),
+            mime_type: "image/png".to_string(),
+            size: 1000,
+            merkle: None,
+            thumbnail: None,
+        }
+    }
+
+    #[test]
+    fn thumbnail_roundtrip_keeps_aspect_ratio() {
+        let key = [7u8; 32];
+        let mut offer = offer("t1");
+        attach_thumbnail(&mut offer, &png(640, 320), &key, 160).unwrap();
+        let image = open_thumbnail(&offer, &key).unwrap();
+        assert_eq!((image.width, image.height), (160, 80));
+        assert_eq!(image.pixels.len(), 160 * 80 * 4);
+        assert!(image.pixels.chunks(4).all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn small_images_are_not_enlarged() {
+        let encoded = make_thumbnail(&png(20, 10), 160).unwrap();
+        let image = image::load_from_memory(&encoded).unwrap();
+        assert_eq!((image.width(), image.height()), (20, 10));
+    }
+
+    #[test]
+    fn thumbnail_is_bound_to_key_and_offer() {
+        let key = [7u8; 32];
+        let mut offer = offer("t1");
+        attach_thumbnail(&mut offer, &png(64, 64), &key, 32).unwrap();
+        assert_eq!(open_thumbnail(&offer, &[8u8; 32]), Err(ThumbnailError::Decrypt));
+
+        let mut moved = self::offer("t2");
+        moved.thumbnail = offer.thumbnail.clone();
+        assert_eq!(open_thumbnail(&moved, &key), Err(ThumbnailError::Decrypt));
+        assert_eq!(open_thumbnail(&self::offer("t3"), &key), Err(ThumbnailError::Missing));
+    }
+
+    #[test]
+    fn oversized_received_thumbnail_is_refused() {
+        let key = [7u8; 32];
+        let mut offer = offer("t1");
+        let big = make_thumbnail(&png(MAX_DECODED_EDGE + 1, 4), MAX_DECODED_EDGE + 1).unwrap();
+        offer.thumbnail = Some(seal_thumbnail(&key, &offer.id, &big).unwrap());
+        assert!(matches!(open_thumbnail(&offer, &key), Err(ThumbnailError::Decode(_))));
+    }
+
+    #[test]
+    fn oversized_source_image_is_refused() {
+        let wide = png(MAX_SOURCE_EDGE + 1, 1);
+        assert!(matches!(make_thumbnail(&wide, 160), Err(ThumbnailError::Decode(_))));
+    }
+
+    #[test]
+    fn garbage_image_is_a_decode_error() {
+        assert!(matches!(make_thumbnail(b"not an image", 160), Err(ThumbnailError::Decode(_))));
+    }
+}
@@ -0,0 +1,443 @@
+//! Offline single-use event tickets.
+//!
+//! A ticket is a signed QR (see `holi_qr::signed`) whose content is
+//! `holi-ticket:v1:<event>:<ticket id>`. Gates redeem it offline and sign a
+//! receipt; devices then exchange receipts to agree on who redeemed first.
+//!
+//! Only receipts from validators the book trusts are accepted, and the list
+//! of issued ids is only imported with the issuer's signature over it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use holi_clock::{default_clock, Clock};
+use holi_qr::{QrSigner, KEY_ID_LEN, SIGNATURE_LEN};
+use holi_rng::HoliRng;
+use rand::RngCore;
+use crate::identity::IdentityKey;
+
+/// Prefix of the signed content of a ticket token.
+const TICKET_CONTENT_PREFIX: &str = "holi-ticket:v1:";
+/// Domain separator prepended to receipt bytes before signing.
+const RECEIPT_SIGNING_CONTEXT: &[u8] = b"holi-ticket-receipt-v1";
+/// Domain separator prepended to the issued ticket list before signing.
+const ISSUED_SIGNING_CONTEXT: &[u8] = b"holi-ticket-issued-v1";
+const TICKET_ID_LEN: usize = 16;
+
+impl QrSigner for IdentityKey {
+    fn key_id(&self) -> [u8; KEY_ID_LEN] {
+        holi_qr::signed_qr_key_id(&self.public_key_bytes())
+    }
+
+    fn sign(&self, message: &[u8]) -> [u8; SIGNATURE_LEN] {
+        IdentityKey::sign(self, message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TicketError {
+    /// Not a signed ticket, or not signed by this book's issuer.
+    BadToken,
+    /// Valid ticket for a different event.
+    WrongEvent,
+    /// `issue` was called with a key other than the book's issuer.
+    WrongIssuer,
+    BadReceipt,
+    /// The receipt or redeeming key is not one of the book's validators.
+    UntrustedValidator,
+    /// The ticket was already redeemed; carries the first receipt.
+    AlreadyRedeemed(RedemptionReceipt),
+}
+
+/// Proof that `validator` accepted `ticket_id` at `redeemed_at`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RedemptionReceipt {
+    pub event_id: String,
+    pub ticket_id: String,
+    pub redeemed_at: u64,
+    /// Ed25519 public key of the validating device.
+    pub validator: [u8; 32],
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+impl RedemptionReceipt {
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut out = RECEIPT_SIGNING_CONTEXT.to_vec();
+        for part in [self.event_id.as_bytes(), self.ticket_id.as_bytes()] {
+            out.extend_from_slice(&(part.len() as u32).to_be_bytes());
+            out.extend_from_slice(part);
+        }
+        out.extend_from_slice(&self.redeemed_at.to_be_bytes());
+        out.extend_from_slice(&self.validator);
+        out
+    }
+
+    pub fn verify(&self) -> bool {
+        let Ok(signature) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        IdentityKey::verify(&self.validator, &self.signing_bytes(), &signature)
+    }
+
+    /// Earlier redemption wins; ties break on validator key so every device agrees.
+    fn precedes(&self, other: &RedemptionReceipt) -> bool {
+        (self.redeemed_at, self.validator) < (other.redeemed_at, other.validator)
+    }
+}
+
+/// Exported book state for syncing validation devices.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TicketBookState {
+    pub event_id: String,
+    pub issuer: [u8; 32],
+    pub issued: BTreeSet<String>,
+    /// Issuer signature over `issued`; required to import a non-empty list.
+    #[serde(default, with = "hex::serde")]
+    pub issued_signature: Vec<u8>,
+    pub receipts: Vec<RedemptionReceipt>,
+}
+
+fn issued_signing_bytes(event_id: &str, issued: &BTreeSet<String>) -> Vec<u8> {
+    let mut out = ISSUED_SIGNING_CONTEXT.to_vec();
+    for part in std::iter::once(event_id).chain(issued.iter().map(String::as_str)) {
+        out.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        out.extend_from_slice(part.as_bytes());
+    }
+    out
+}
+
+fn shared_default_clock() -> Arc<dyn Clock> {
+    Arc::new(default_clock())
+}
+
+/// Single-use signed tickets for one event.
+///
+/// The issuer's book mints tokens; validation devices open a book with just
+/// the event id and issuer public key, trust the gates' keys with
+/// `trust_validator`, redeem offline and exchange receipts via
+/// `export_state` / `import_state`.
+#[derive(Debug)]
+pub struct TicketBook {
+    event_id: String,
+    issuer: [u8; 32],
+    /// Ticket ids minted by this book (empty on validation devices).
+    issued: BTreeSet<String>,
+    /// Issuer signature over `issued`, empty while nothing is issued.
+    issued_signature: Vec<u8>,
+    /// Keys whose receipts are accepted; always includes the issuer.
+    validators: BTreeSet<[u8; 32]>,
+    redemptions: BTreeMap<String, RedemptionReceipt>,
+    clock: Arc<dyn Clock>,
+}
+
+impl TicketBook {
+    pub fn new(event_id: &str, issuer: [u8; 32]) -> Self {
+        Self::with_clock(event_id, issuer, shared_default_clock())
+    }
+
+    pub fn with_clock(event_id: &str, issuer: [u8; 32], clock: Arc<dyn Clock>) -> Self {
+        TicketBook {
+            event_id: event_id.to_string(),
+            issuer,
+            issued: BTreeSet::new(),
+            issued_signature: Vec::new(),
+            validators: BTreeSet::from([issuer]),
+            redemptions: BTreeMap::new(),
+            clock,
+        }
+    }
+
+    pub fn event_id(&self) -> &str {
+        &self.event_id
+    }
+
+    /// Accept receipts signed by `validator`, and let it redeem on this book.
+    pub fn trust_validator(&mut self, validator: [u8; 32]) {
+        self.validators.insert(validator);
+    }
+
+    pub fn is_trusted_validator(&self, validator: &[u8; 32]) -> bool {
+        self.validators.contains(validator)
+    }
+
+    /// Mint `count` tickets, returning their signed QR payloads.
+    pub fn issue(&mut self, issuer: &IdentityKey, count: usize) -> Result<Vec<Vec<u8>>, TicketError> {
+        if issuer.public_key_bytes() != self.issuer {
+            return Err(TicketError::WrongIssuer);
+        }
+        let mut tokens = Vec::with_capacity(count);
+        while tokens.len() < count {
+            let mut id = [0u8; TICKET_ID_LEN];
+            HoliRng.fill_bytes(&mut id);
+            let ticket_id = hex::encode(id);
+            if !self.issued.insert(ticket_id.clone()) {
+                continue;
+            }
+            let content = format!("{}{}:{}", TICKET_CONTENT_PREFIX, self.event_id, ticket_id);
+            tokens.push(holi_qr::encode_signed_payload(content.as_bytes(), issuer));
+        }
+        self.issued_signature = issuer.sign(&issued_signing_bytes(&self.event_id, &self.issued)).to_vec();
+        Ok(tokens)
+    }
+
+    /// Check a scanned token's signature and event. Returns the ticket id.
+    pub fn validate(&self, token: &[u8]) -> Result<String, TicketError> {
+        let signed = holi_qr::split_signed_payload(token).map_err(|_| TicketError::BadToken)?;
+        if signed.key_id != holi_qr::signed_qr_key_id(&self.issuer)
+            || !IdentityKey::verify(&self.issuer, &signed.message(), &signed.signature)
+        {
+            return Err(TicketError::BadToken);
+        }
+        let content = std::str::from_utf8(signed.content).map_err(|_| TicketError::BadToken)?;
+        let (event_id, ticket_id) = content
+            .strip_prefix(TICKET_CONTENT_PREFIX)
+            .and_then(|rest| rest.rsplit_once(':'))
+            .ok_or(TicketError::BadToken)?;
+        if event_id != self.event_id {
+            return Err(TicketError::WrongEvent);
+        }
+        Ok(ticket_id.to_string())
+    }
+
+    /// Validate and redeem a token, signing a receipt with `validator`.
+    pub fn redeem(&mut self, token: &[u8], validator: &IdentityKey) -> Result<RedemptionReceipt, TicketError> {
+        if !self.is_trusted_validator(&validator.public_key_bytes()) {
+            return Err(TicketError::UntrustedValidator);
+        }
+        let ticket_id = self.validate(token)?;
+        if let Some(existing) = self.redemptions.get(&ticket_id) {
+            return Err(TicketError::AlreadyRedeemed(existing.clone()));
+        }
+        let mut receipt = RedemptionReceipt {
+            event_id: self.event_id.clone(),
+            ticket_id: ticket_id.clone(),
+            redeemed_at: self.clock.now_ms(),
+            validator: validator.public_key_bytes(),
+            signature: Vec::new(),
+        };
+        receipt.signature = validator.sign(&receipt.signing_bytes()).to_vec();
+        self.redemptions.insert(ticket_id, receipt.clone());
+        Ok(receipt)
+    }
+
+    /// Merge a receipt from another device. Returns true if state changed.
+    pub fn apply_receipt(&mut self, receipt: &RedemptionReceipt) -> Result<bool, TicketError> {
+        if receipt.event_id != self.event_id || !receipt.verify() {
+            return Err(TicketError::BadReceipt);
+        }
+        if !self.is_trusted_validator(&receipt.validator) {
+            return Err(TicketError::UntrustedValidator);
+        }
+        match self.redemptions.get(&receipt.ticket_id) {
+            Some(existing) if !receipt.precedes(existing) => Ok(false),
+            _ => {
+                self.redemptions.insert(receipt.ticket_id.clone(), receipt.clone());
+                Ok(true)
+            }
+        }
+    }
+
+    pub fn redemption(&self, ticket_id: &str) -> Option<&RedemptionReceipt> {
+        self.redemptions.get(ticket_id)
+    }
+
+    pub fn is_redeemed(&self, ticket_id: &str) -> bool {
+        self.redemptions.contains_key(ticket_id)
+    }
+
+    pub fn issued_count(&self) -> usize {
+        self.issued.len()
+    }
+
+    pub fn redeemed_count(&self) -> usize {
+        self.redemptions.len()
+    }
+
+    pub fn export_state(&self) -> TicketBookState {
+        TicketBookState {
+            event_id: self.event_id.clone(),
+            issuer: self.issuer,
+            issued: self.issued.clone(),
+            issued_signature: self.issued_signature.clone(),
+            receipts: self.redemptions.values().cloned().collect(),
+        }
+    }
+
+    pub fn export_json(&self) -> Result<String, String> {
+        serde_json::to_string(&self.export_state())
+            .map_err(|e| format!("Serialization failed: {}", e))
+    }
+
+    /// Merge state exported by another device for the same event and issuer.
+    /// The issued list must carry the issuer's signature and replaces ours
+    /// when larger. Receipts are verified individually; invalid ones and
+    /// those from untrusted validators are skipped.
+    /// Returns the number of receipts that changed local state.
+    pub fn import_state(&mut self, state: &TicketBookState) -> Result<usize, String> {
+        if state.event_id != self.event_id || state.issuer != self.issuer {
+            return Err("State belongs to a different event or issuer".to_string());
+        }
+        if !state.issued.is_empty() {
+            let signed = <[u8; 64]>::try_from(state.issued_signature.as_slice()).is_ok_and(|signature| {
+                IdentityKey::verify(&self.issuer, &issued_signing_bytes(&self.event_id, &state.issued), &signature)
+            });
+            if !signed {
+                return Err("Issued tickets are not signed by the issuer".to_string());
+            }
+            // The issuer's list only grows, so the larger signed one is newer
+            if state.issued.len() > self.issued.len() {
+                self.issued = state.issued.clone();
+                self.issued_signature = state.issued_signature.clone();
+            }
+        }
+        Ok(state
+            .receipts
+            .iter()
+            .filter(|r| self.apply_receipt(r) == Ok(true))
+            .count())
+    }
+
+    pub fn import_json(&mut self, json: &str) -> Result<usize, String> {
+        let state: TicketBookState = serde_json::from_str(json)
+            .map_err(|e| format!("Deserialization failed: {}", e))?;
+        self.import_state(&state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holi_clock::MockClock;
+
+    fn issuer_book(issuer: &IdentityKey, clock: Arc<MockClock>) -> TicketBook {
+        TicketBook::with_clock("concert", issuer.public_key_bytes(), clock)
+    }
+
+    #[test]
+    fn test_issue_and_redeem_once() {
+        let issuer = IdentityKey::generate();
+        let gate = IdentityKey::generate();
+        let clock = Arc::new(MockClock::new(1_000));
+        let mut book = issuer_book(&issuer, clock.clone());
+        book.trust_validator(gate.public_key_bytes());
+
+        let tokens = book.issue(&issuer, 3).unwrap();
+        assert_eq!(book.issued_count(), 3);
+
+        let receipt = book.redeem(&tokens[0], &gate).unwrap();
+        assert!(receipt.verify());
+        assert_eq!(receipt.redeemed_at, 1_000);
+        assert!(book.is_redeemed(&receipt.ticket_id));
+
+        match book.redeem(&tokens[0], &gate) {
+            Err(TicketError::AlreadyRedeemed(first)) => assert_eq!(first, receipt),
+            other => panic!("expected AlreadyRedeemed, got {:?}", other),
+        }
+        assert_eq!(book.redeemed_count(), 1);
+    }
+
+    #[test]
+    fn test_rejects_forged_and_foreign_tokens() {
+        let issuer = IdentityKey::generate();
+        let mallory = IdentityKey::generate();
+        let clock = Arc::new(MockClock::new(0));
+        let mut book = issuer_book(&issuer, clock.clone());
+
+        assert_eq!(book.issue(&mallory, 1), Err(TicketError::WrongIssuer));
+
+        let mut token = book.issue(&issuer, 1).unwrap().remove(0);
+        let mut other_event = TicketBook::with_clock("other", issuer.public_key_bytes(), clock);
+        assert_eq!(other_event.validate(&token), Err(TicketError::WrongEvent));
+        let forged = other_event.issue(&issuer, 1).unwrap().remove(0);
+        assert_eq!(book.validate(&forged), Err(TicketError::WrongEvent));
+
+        token[20] ^= 1;
+        assert_eq!(book.validate(&token), Err(TicketError::BadToken));
+        assert_eq!(book.validate(b"not a ticket"), Err(TicketError::BadToken));
+    }
+
+    #[test]
+    fn test_offline_devices_sync_receipts() {
+        let issuer = IdentityKey::generate();
+        let gate_a = IdentityKey::generate();
+        let gate_b = IdentityKey::generate();
+        let clock = Arc::new(MockClock::new(0));
+        let mut book = issuer_book(&issuer, clock.clone());
+        let tokens = book.issue(&issuer, 2).unwrap();
+
+        let mut device_a = issuer_book(&issuer, clock.clone());
+        let mut device_b = issuer_book(&issuer, clock.clone());
+        for device in [&mut device_a, &mut device_b] {
+            device.trust_validator(gate_a.public_key_bytes());
+            device.trust_validator(gate_b.public_key_bytes());
+        }
+        device_a.import_json(&book.export_json().unwrap()).unwrap();
+
+        // Same ticket redeemed on two offline gates; the earlier one wins.
+        clock.set(500);
+        let first = device_a.redeem(&tokens[0], &gate_a).unwrap();
+        clock.set(900);
+        device_b.redeem(&tokens[0], &gate_b).unwrap();
+        device_b.redeem(&tokens[1], &gate_b).unwrap();
+
+        assert_eq!(device_a.import_json(&device_b.export_json().unwrap()).unwrap(), 1);
+        assert_eq!(device_b.import_json(&device_a.export_json().unwrap()).unwrap(), 1);
+        assert_eq!(device_a.redemption(&first.ticket_id), Some(&first));
+        assert_eq!(device_b.redemption(&first.ticket_id), Some(&first));
+        assert_eq!(device_a.export_state(), device_b.export_state());
+        assert_eq!(device_a.issued_count(), 2);
+
+        let mut tampered = first.clone();
+        tampered.redeemed_at = 1;
+        assert_eq!(book.apply_receipt(&tampered), Err(TicketError::BadReceipt));
+    }
+
+    #[test]
+    fn test_rejects_untrusted_validators() {
+        let issuer = IdentityKey::generate();
+        let gate = IdentityKey::generate();
+        let rogue = IdentityKey::generate();
+        let clock = Arc::new(MockClock::new(0));
+        let mut book = issuer_book(&issuer, clock.clone());
+        let tokens = book.issue(&issuer, 1).unwrap();
+
+        assert_eq!(book.redeem(&tokens[0], &rogue), Err(TicketError::UntrustedValidator));
+
+        // A validly signed receipt from an unknown key is not merged
+        let mut rogue_book = issuer_book(&issuer, clock.clone());
+        rogue_book.trust_validator(rogue.public_key_bytes());
+        let forged = rogue_book.redeem(&tokens[0], &rogue).unwrap();
+        assert_eq!(book.apply_receipt(&forged), Err(TicketError::UntrustedValidator));
+        assert_eq!(book.import_json(&rogue_book.export_json().unwrap()).unwrap(), 0);
+        assert!(!book.is_redeemed(&forged.ticket_id));
+
+        book.trust_validator(gate.public_key_bytes());
+        assert!(book.redeem(&tokens[0], &gate).is_ok());
+    }
+
+    #[test]
+    fn test_issued_list_must_be_signed() {
+        let issuer = IdentityKey::generate();
+        let clock = Arc::new(MockClock::new(0));
+        let mut book = issuer_book(&issuer, clock.clone());
+        book.issue(&issuer, 2).unwrap();
+
+        let mut device = issuer_book(&issuer, clock.clone());
+        let mut state = book.export_state();
+        state.issued.insert("00".repeat(TICKET_ID_LEN));
+        assert!(device.import_state(&state).is_err());
+        state.issued_signature.clear();
+        assert!(device.import_state(&state).is_err());
+        assert_eq!(device.issued_count(), 0);
+
+        device.import_state(&book.export_state()).unwrap();
+        assert_eq!(device.issued_count(), 2);
+        // An older signed list does not shrink a newer one
+        let older = device.export_state();
+        book.issue(&issuer, 1).unwrap();
+        device.import_state(&book.export_state()).unwrap();
+        device.import_state(&older).unwrap();
+        assert_eq!(device.issued_count(), 3);
+    }
+}
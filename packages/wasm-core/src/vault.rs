@@ -1,9 +1,29 @@
 use std::collections::HashMap;
-use serde::{Serialize, Deserialize};
 use wasm_bindgen::prelude::*;
 use crate::identity::IdentityKey;
 use crate::crypto::ProjectKey;
-use crate::storage::{StorageProvider, InMemoryStorage};
+use crate::acl::{AccessControlList, Operation, PermissionRole};
+use crate::identity_core::user_id_from_public_key;
+use crate::invite::{accept_project_invite, create_project_invite, InviteError, Invitee};
+use holi_p2p::frame::{
+    decode_file_chunk_frame_v1, decode_v1, encode_file_chunk_v1, AclUpdate, FrameType, ProjectInvite,
+};
+use crate::storage::{StorageError, StorageProvider, InMemoryStorage};
+use crate::chunk_store::{
+    manifest_from_bytes, manifest_to_bytes, ChunkId, ChunkStore, CHUNK_ID_LEN, DEFAULT_CHUNK_SIZE,
+};
+
+fn storage_error(e: StorageError) -> JsValue {
+    JsValue::from_str(&format!("Storage error: {:?}", e))
+}
+
+/// Largest FileChunk payload accepted from a transfer.
+const MAX_CHUNK_FRAME_PAYLOAD: u32 = 1024 * 1024;
+
+/// Storage root of a project's chunk store.
+fn chunk_root(project_id: &str) -> String {
+    format!("projects/{}/", hex::encode(project_id))
+}
 
 #[wasm_bindgen]
 pub struct Vault {
@@ -20,6 +40,10 @@ pub struct Vault {
     // Note: dyn StorageProvider must be Send + Sync which it is.
     #[wasm_bindgen(skip)]
     pub storage: Box<dyn StorageProvider>,
+    // Encrypted, deduplicated file blocks shared by stored files and incoming transfers,
+    // one store per project, keyed from its project key and opened on first use.
+    #[wasm_bindgen(skip)]
+    pub chunks: HashMap<String, ChunkStore>,
 }
 
 #[wasm_bindgen]
@@ -31,6 +55,7 @@ impl Vault {
             identity,
             projects: HashMap::new(),
             storage,
+            chunks: HashMap::new(),
         }
     }
 
@@ -60,6 +85,154 @@ impl Vault {
             Err(JsValue::from_str("Project not found"))
         }
     }
+
+    /// Store a file as content-addressed chunks of a project. Returns its
+    /// manifest (concatenated 32-byte chunk ids), needed to read or delete it.
+    pub fn store_file(&mut self, project_id: &str, data: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let manifest = self.update_chunks(project_id, |store, storage| {
+            store.put_file(storage, data, DEFAULT_CHUNK_SIZE)
+        })?;
+        Ok(manifest_to_bytes(&manifest))
+    }
+
+    pub fn load_file(&mut self, project_id: &str, manifest: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let manifest = manifest_from_bytes(manifest).map_err(storage_error)?;
+        let (store, storage) = self.chunk_store(project_id)?;
+        store.get_file(storage, &manifest).map_err(storage_error)
+    }
+
+    /// Drop a file's references; its chunks are deleted by `collect_garbage`
+    /// unless another file or transfer still uses them.
+    pub fn delete_file(&mut self, project_id: &str, manifest: &[u8]) -> Result<(), JsValue> {
+        let manifest = manifest_from_bytes(manifest).map_err(storage_error)?;
+        self.update_chunks(project_id, |store, _| {
+            store.release_file(&manifest);
+            Ok(())
+        })
+    }
+
+    /// Store one chunk received in a transfer. Returns its 32-byte id.
+    pub fn put_chunk(&mut self, project_id: &str, data: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let id = self.update_chunks(project_id, |store, storage| store.put(storage, data))?;
+        Ok(id.to_vec())
+    }
+
+    /// Store the chunk carried by a received FileChunk frame. Returns its
+    /// 32-byte id, for the transfer's manifest.
+    pub fn receive_chunk_frame(&mut self, project_id: &str, frame: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let (frame, _) = decode_v1(frame, MAX_CHUNK_FRAME_PAYLOAD).map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+        if frame.frame_type != FrameType::FileChunk {
+            return Err(JsValue::from_str("Not a FileChunk frame"));
+        }
+        let chunk = decode_file_chunk_frame_v1(&frame).map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+        self.put_chunk(project_id, &chunk.data)
+    }
+
+    /// FileChunk frame for chunk `index` of a stored file, e.g. to send or
+    /// re-send it in transfer `file_id`.
+    pub fn chunk_frame(&mut self, project_id: &str, file_id: &str, manifest: &[u8], index: u32) -> Result<Vec<u8>, JsValue> {
+        let manifest = manifest_from_bytes(manifest).map_err(storage_error)?;
+        let id = manifest
+            .get(index as usize)
+            .ok_or_else(|| JsValue::from_str("Chunk index out of range"))?;
+        let (store, storage) = self.chunk_store(project_id)?;
+        let data = store.get(storage, id).map_err(storage_error)?;
+        Ok(encode_file_chunk_v1(file_id, index, &data))
+    }
+
+    /// Add a reference to an already stored chunk (e.g. a re-sent file).
+    /// Returns false if the chunk is not stored.
+    pub fn retain_chunk(&mut self, project_id: &str, id: &[u8]) -> Result<bool, JsValue> {
+        let id = ChunkId::try_from(id)
+            .map_err(|_| JsValue::from_str(&format!("Chunk id must be {} bytes", CHUNK_ID_LEN)))?;
+        self.update_chunks(project_id, |store, _| Ok(store.retain(&id)))
+    }
+
+    /// Of the ids in a transfer's manifest, those not stored yet. A resumed
+    /// or re-sent transfer only needs to request these.
+    pub fn missing_chunks(&mut self, project_id: &str, manifest: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let manifest = manifest_from_bytes(manifest).map_err(storage_error)?;
+        let (store, _) = self.chunk_store(project_id)?;
+        Ok(manifest_to_bytes(&store.missing(&manifest)))
+    }
+
+    /// Delete chunks no file or transfer of the project references. Returns
+    /// how many were deleted.
+    pub fn collect_garbage(&mut self, project_id: &str) -> Result<usize, JsValue> {
+        self.update_chunks(project_id, |store, storage| store.collect_garbage(storage))
+    }
+}
+
+impl Vault {
+    /// The project's chunk store, loaded from storage on first use so its
+    /// reference counts survive reopening the vault.
+    fn chunk_store(&mut self, project_id: &str) -> Result<(&mut ChunkStore, &dyn StorageProvider), JsValue> {
+        let storage = &*self.storage;
+        let store = match self.chunks.entry(project_id.to_string()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let key = self.projects.get(project_id).ok_or_else(|| JsValue::from_str("Project not found"))?;
+                let store = ChunkStore::load(storage, key.clone(), &chunk_root(project_id)).map_err(storage_error)?;
+                entry.insert(store)
+            }
+        };
+        Ok((store, storage))
+    }
+
+    /// Run `f` on the project's chunk store and save its index.
+    fn update_chunks<R>(
+        &mut self,
+        project_id: &str,
+        f: impl FnOnce(&mut ChunkStore, &dyn StorageProvider) -> Result<R, StorageError>,
+    ) -> Result<R, JsValue> {
+        let (store, storage) = self.chunk_store(project_id)?;
+        let result = f(store, storage).map_err(storage_error)?;
+        store.save(storage).map_err(storage_error)?;
+        Ok(result)
+    }
+
+    /// Invite `invitee` to one of our projects; see `create_project_invite`.
+    pub fn invite_peer(
+        &self,
+        acl: &AccessControlList,
+        project_id: &str,
+        invitee: &Invitee,
+        role: PermissionRole,
+        timestamp: u64,
+    ) -> Result<(ProjectInvite, AclUpdate), InviteError> {
+        let key = self.projects.get(project_id).ok_or(InviteError::UnknownProject)?;
+        create_project_invite(&self.identity, acl, project_id, key, invitee, role, timestamp)
+    }
+
+    /// Join the project of an invite from `inviter` addressed to our identity,
+    /// storing its key; see `accept_project_invite`.
+    ///
+    /// If we already hold the project, its key is only replaced when the
+    /// inviter holds `Share` in `acl`, our ACL of it.
+    pub fn accept_invite(
+        &mut self,
+        invite: &ProjectInvite,
+        hpke_secret: &[u8; 32],
+        inviter: &[u8; 32],
+        acl: Option<&AccessControlList>,
+    ) -> Result<(), InviteError> {
+        if self.projects.contains_key(&invite.project_id) {
+            let inviter_id = user_id_from_public_key(&invite.inviter);
+            if !acl.is_some_and(|acl| acl.can_at(&inviter_id, Operation::Share, invite.timestamp)) {
+                return Err(InviteError::ProjectExists);
+            }
+        }
+        let key = accept_project_invite(invite, &self.identity, hpke_secret, inviter)?;
+        self.projects.insert(invite.project_id.clone(), key);
+        self.chunks.remove(&invite.project_id);
+        Ok(())
+    }
+}
+
+impl Default for Vault {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -81,4 +254,83 @@ mod tests {
         let decrypted = vault.decrypt_project_data("test-project", &encrypted).unwrap();
         assert_eq!(data, decrypted.as_slice());
     }
+
+    #[test]
+    fn test_vault_invite() {
+        let mut owner = Vault::new();
+        let mut bob = Vault::new();
+        owner.create_project("shared");
+        let mut acl = AccessControlList::new();
+        acl.grant(&crate::identity_core::user_id_from_public_key(&owner.identity.public_key_bytes()), PermissionRole::Owner);
+
+        let bob_hpke_secret = [5u8; 32];
+        let invitee = Invitee {
+            identity: bob.identity.public_key_bytes(),
+            hpke_public_key: holi_hpke::public_key(&bob_hpke_secret),
+        };
+        assert_eq!(
+            owner.invite_peer(&acl, "missing", &invitee, PermissionRole::Viewer, 1).unwrap_err(),
+            InviteError::UnknownProject
+        );
+        let (invite, _grant) = owner.invite_peer(&acl, "shared", &invitee, PermissionRole::Viewer, 1).unwrap();
+        let owner_key = owner.identity.public_key_bytes();
+        bob.accept_invite(&invite, &bob_hpke_secret, &owner_key, None).unwrap();
+
+        let encrypted = owner.encrypt_project_data("shared", b"plan").unwrap();
+        assert_eq!(bob.decrypt_project_data("shared", &encrypted).unwrap(), b"plan");
+
+        // Someone Bob also paired with can't swap the key of a project he holds
+        let mut mallory = Vault::new();
+        mallory.create_project("shared");
+        let mut mallory_acl = AccessControlList::new();
+        mallory_acl.grant(&user_id_from_public_key(&mallory.identity.public_key_bytes()), PermissionRole::Owner);
+        let (hijack, _) = mallory.invite_peer(&mallory_acl, "shared", &invitee, PermissionRole::Owner, 2).unwrap();
+        let mallory_key = mallory.identity.public_key_bytes();
+        assert_eq!(bob.accept_invite(&hijack, &bob_hpke_secret, &mallory_key, None), Err(InviteError::ProjectExists));
+        assert_eq!(bob.accept_invite(&hijack, &bob_hpke_secret, &mallory_key, Some(&acl)), Err(InviteError::ProjectExists));
+        assert_eq!(bob.decrypt_project_data("shared", &encrypted).unwrap(), b"plan");
+
+        // A sharer in Bob's ACL can re-invite him
+        let (again, _) = owner.invite_peer(&acl, "shared", &invitee, PermissionRole::Editor, 3).unwrap();
+        bob.accept_invite(&again, &bob_hpke_secret, &owner_key, Some(&acl)).unwrap();
+    }
+
+    #[test]
+    fn test_vault_files_share_chunks() {
+        let mut vault = Vault::new();
+        vault.create_project("files");
+        let data = vec![7u8; DEFAULT_CHUNK_SIZE + 10];
+
+        let manifest = vault.store_file("files", &data).unwrap();
+        assert_eq!(manifest.len(), 2 * CHUNK_ID_LEN);
+        assert!(vault.missing_chunks("files", &manifest).unwrap().is_empty());
+
+        // A transfer of the same file: its chunks are already here
+        let frame = vault.chunk_frame("files", "t1", &manifest, 0).unwrap();
+        let copy = vault.receive_chunk_frame("files", &frame).unwrap();
+        assert_eq!(copy, manifest[..CHUNK_ID_LEN]);
+
+        vault.delete_file("files", &manifest).unwrap();
+        assert_eq!(vault.collect_garbage("files").unwrap(), 1);
+        assert_eq!(vault.missing_chunks("files", &manifest).unwrap(), manifest[CHUNK_ID_LEN..]);
+    }
+
+    #[test]
+    fn test_vault_reopen_keeps_chunks() {
+        let mut vault = Vault::new();
+        vault.create_project("files");
+        let manifest = vault.store_file("files", b"kept across sessions").unwrap();
+
+        // Same storage and project key, fresh in-memory state
+        let key = vault.projects["files"].clone();
+        let mut reopened = Vault { chunks: HashMap::new(), ..vault };
+        assert_eq!(reopened.collect_garbage("files").unwrap(), 0);
+        assert_eq!(reopened.load_file("files", &manifest).unwrap(), b"kept across sessions");
+
+        // The chunks only open with the key they were stored under
+        let root = chunk_root("files");
+        assert!(ChunkStore::load(&*reopened.storage, ProjectKey::generate(), &root).is_err());
+        let store = ChunkStore::load(&*reopened.storage, key, &root).unwrap();
+        assert_eq!(store.len(), 1);
+    }
 }
@@ -3,10 +3,11 @@
 //! Provides authenticated encryption for project data.
 
 use chacha20poly1305::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{Aead, AeadCore, KeyInit},
     XChaCha20Poly1305, XNonce
 };
 use serde::{Serialize, Deserialize};
+use crate::rng::HoliRng;
 use std::fmt;
 use wasm_bindgen::prelude::*;
 
@@ -23,7 +24,7 @@ impl EncryptionKey {
     /// Generate a new random encryption key
     #[wasm_bindgen(constructor)]
     pub fn generate() -> Self {
-        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let key = XChaCha20Poly1305::generate_key(&mut HoliRng);
         EncryptionKey {
             key_bytes: key.into(),
         }
@@ -48,7 +49,7 @@ impl EncryptionKey {
     /// Returns: nonce (24 bytes) + ciphertext + tag (16 bytes)
     pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, JsValue> {
         let cipher = XChaCha20Poly1305::new(&self.key_bytes.into());
-        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut HoliRng);
 
         let ciphertext = cipher.encrypt(&nonce, plaintext)
             .map_err(|e| JsValue::from_str(&format!("Encryption failed: {}", e)))?;
@@ -77,7 +78,7 @@ impl EncryptionKey {
 
     /// Export key as hex string
     pub fn to_hex(&self) -> String {
-        hex::encode(&self.key_bytes)
+        hex::encode(self.key_bytes)
     }
 
     /// Import key from hex string
@@ -0,0 +1,56 @@
+//! Crypto events delivered to JS subscribers.
+
+use holi_wasm_events::{Event, EventEmitter};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+    static EVENTS: EventEmitter = const { EventEmitter::new() };
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PakeRole {
+    A,
+    B,
+    Symmetric,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PakeState {
+    /// Session key derived.
+    Established,
+    /// Peer message was malformed or the state was already consumed.
+    Failed,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum CryptoEvent {
+    PakeState { role: PakeRole, state: PakeState },
+}
+
+impl Event for CryptoEvent {
+    fn kind(&self) -> &'static str {
+        match self {
+            CryptoEvent::PakeState { .. } => "pakeState",
+        }
+    }
+}
+
+pub(crate) fn emit(event: CryptoEvent) {
+    EVENTS.with(|e| e.emit(&event));
+}
+
+/// Register `callback` for events of `kind` (`"*"` for all). Returns a subscription id.
+#[wasm_bindgen]
+pub fn subscribe_crypto_events(kind: &str, callback: js_sys::Function) -> u32 {
+    EVENTS.with(|e| e.subscribe(kind, callback))
+}
+
+/// Remove a subscription created by `subscribe_crypto_events`.
+#[wasm_bindgen]
+pub fn unsubscribe_crypto_events(id: u32) -> bool {
+    EVENTS.with(|e| e.unsubscribe(id))
+}
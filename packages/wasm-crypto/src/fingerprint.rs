@@ -0,0 +1,257 @@
+//! Public Key Fingerprints
+//!
+//! Canonical, human-comparable renderings of a 32-byte public key
+//! (Ed25519 identity or X25519 HPKE key). Every surface that shows a key
+//! (contact cards, pairing screens, QR labels) should go through here so the
+//! same key always looks the same.
+//!
+//! The fingerprint digest is `SHA-256("holi-fp-v1" || public_key)`. It is
+//! rendered as:
+//! - grouped hex: 16 groups of 4 uppercase hex digits
+//! - z-base32: 52 characters in groups of 4
+//! - emoji: the first 48 bits as 8 emoji from a fixed 64-entry table
+//!
+//! Only the hex and z-base32 forms carry the whole digest. 48 bits can be
+//! matched by a generated key, so the emoji form is a visual aid and never
+//! verifies a key on its own.
+
+use wasm_bindgen::prelude::*;
+
+const PUBLIC_KEY_LEN: usize = 32;
+const GROUP_LEN: usize = 4;
+const EMOJI_COUNT: usize = 8;
+
+const ZBASE32_ALPHABET: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+const EMOJI_TABLE: [&str; 64] = [
+    "🐶", "🐱", "🐭", "🐹", "🐰", "🦊", "🐻", "🐼",
+    "🐨", "🐯", "🦁", "🐮", "🐷", "🐸", "🐵", "🐔",
+    "🐧", "🐦", "🦆", "🦉", "🐺", "🐴", "🦄", "🐝",
+    "🐛", "🦋", "🐌", "🐞", "🐢", "🐍", "🐙", "🦀",
+    "🐬", "🐳", "🦈", "🐊", "🦓", "🦒", "🐘", "🦔",
+    "🌵", "🌲", "🌻", "🌙", "⭐", "🔥", "🌈", "❄️",
+    "🍎", "🍋", "🍉", "🍇", "🍓", "🍒", "🥕", "🌽",
+    "⚽", "🎸", "🚀", "⚓", "🔑", "🎈", "🔔", "💎",
+];
+
+/// Compute the 32-byte fingerprint digest of a public key.
+///
+/// Defined in `holi-qr`, whose signed-QR key ids are prefixes of it.
+pub(crate) fn digest(public_key: &[u8]) -> [u8; 32] {
+    holi_qr::fingerprint_digest(public_key)
+}
+
+fn group(s: &str) -> String {
+    s.as_bytes()
+        .chunks(GROUP_LEN)
+        .map(|c| std::str::from_utf8(c).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Strip separators and whitespace so user-typed fingerprints compare equal.
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != ':')
+        .collect()
+}
+
+pub(crate) fn zbase32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() * 8).div_ceil(5));
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ZBASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ZBASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+pub(crate) fn zbase32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let value = ZBASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c.to_ascii_lowercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn emoji_digest(digest: &[u8; 32]) -> Vec<&'static str> {
+    // 8 emoji * 6 bits = 48 bits = first 6 digest bytes.
+    let mut acc: u64 = 0;
+    for &b in &digest[..6] {
+        acc = (acc << 8) | b as u64;
+    }
+    (0..EMOJI_COUNT)
+        .map(|i| EMOJI_TABLE[((acc >> (42 - 6 * i)) & 0x3f) as usize])
+        .collect()
+}
+
+fn check_public_key(public_key: &[u8]) -> Result<(), JsValue> {
+    if public_key.len() != PUBLIC_KEY_LEN {
+        return Err(JsValue::from_str("Public key must be 32 bytes"));
+    }
+    Ok(())
+}
+
+/// Raw 32-byte fingerprint digest of a public key
+#[wasm_bindgen]
+pub fn fingerprint_bytes(public_key: &[u8]) -> Result<Vec<u8>, JsValue> {
+    check_public_key(public_key)?;
+    Ok(digest(public_key).to_vec())
+}
+
+/// Fingerprint as grouped uppercase hex, e.g. `"1A2B 3C4D ..."`
+#[wasm_bindgen]
+pub fn fingerprint_hex(public_key: &[u8]) -> Result<String, JsValue> {
+    check_public_key(public_key)?;
+    Ok(group(&hex::encode_upper(digest(public_key))))
+}
+
+/// Fingerprint as grouped z-base32, e.g. `"ybnd rfg8 ..."`
+#[wasm_bindgen]
+pub fn fingerprint_zbase32(public_key: &[u8]) -> Result<String, JsValue> {
+    check_public_key(public_key)?;
+    Ok(group(&zbase32_encode(&digest(public_key))))
+}
+
+/// Short emoji digest (8 emoji separated by spaces) for quick visual comparison
+#[wasm_bindgen]
+pub fn fingerprint_emoji(public_key: &[u8]) -> Result<String, JsValue> {
+    check_public_key(public_key)?;
+    Ok(emoji_digest(&digest(public_key)).join(" "))
+}
+
+/// Parse a hex fingerprint (any case, spaces/dashes/colons ignored) back to its 32-byte digest
+#[wasm_bindgen]
+pub fn parse_fingerprint_hex(formatted: &str) -> Result<Vec<u8>, JsValue> {
+    let bytes = hex::decode(normalize(formatted))
+        .map_err(|e| JsValue::from_str(&format!("Invalid hex fingerprint: {}", e)))?;
+    if bytes.len() != 32 {
+        return Err(JsValue::from_str("Fingerprint must be 32 bytes"));
+    }
+    Ok(bytes)
+}
+
+/// Parse a z-base32 fingerprint (spaces/dashes ignored) back to its 32-byte digest
+#[wasm_bindgen]
+pub fn parse_fingerprint_zbase32(formatted: &str) -> Result<Vec<u8>, JsValue> {
+    let bytes = zbase32_decode(&normalize(formatted))
+        .ok_or_else(|| JsValue::from_str("Invalid z-base32 fingerprint"))?;
+    if bytes.len() != 32 {
+        return Err(JsValue::from_str("Fingerprint must be 32 bytes"));
+    }
+    Ok(bytes)
+}
+
+/// How much of a public key's fingerprint a displayed or typed one matched.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FingerprintMatch {
+    None = 0,
+    /// The 8-emoji digest matched: 48 bits, for a quick visual check only.
+    Visual = 1,
+    /// The full hex or z-base32 digest matched.
+    Full = 2,
+}
+
+/// Compare a displayed or typed fingerprint (hex, z-base32 or emoji) with a public key
+#[wasm_bindgen]
+pub fn compare_fingerprint(public_key: &[u8], formatted: &str) -> FingerprintMatch {
+    if public_key.len() != PUBLIC_KEY_LEN {
+        return FingerprintMatch::None;
+    }
+    let expected = digest(public_key);
+    let compact = normalize(formatted);
+
+    let full = match compact.len() {
+        64 => hex::decode(&compact).ok(),
+        52 => zbase32_decode(&compact),
+        _ => None,
+    };
+    match full {
+        Some(bytes) if bytes == expected => FingerprintMatch::Full,
+        Some(_) => FingerprintMatch::None,
+        None if compact == emoji_digest(&expected).concat() => FingerprintMatch::Visual,
+        None => FingerprintMatch::None,
+    }
+}
+
+/// Check a hex or z-base32 fingerprint against a public key. The emoji form
+/// is too short to verify a key; see `compare_fingerprint`.
+#[wasm_bindgen]
+pub fn verify_fingerprint(public_key: &[u8], formatted: &str) -> bool {
+    compare_fingerprint(public_key, formatted) == FingerprintMatch::Full
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zbase32_roundtrip() {
+        let data: Vec<u8> = (0u8..32).collect();
+        let encoded = zbase32_encode(&data);
+        assert_eq!(encoded.len(), 52);
+        assert_eq!(zbase32_decode(&encoded).unwrap(), data);
+        assert_eq!(zbase32_decode(&encoded.to_uppercase()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_zbase32_known_vector() {
+        // Reference value from the z-base-32 spec.
+        assert_eq!(zbase32_encode(&[0xf0, 0xbf, 0xc7]), "6n9hq");
+        assert!(zbase32_decode("0l").is_none());
+    }
+
+    #[test]
+    fn test_formats_are_stable() {
+        let pk = [9u8; 32];
+        let hex = group(&hex::encode_upper(digest(&pk)));
+        assert_eq!(hex.split(' ').count(), 16);
+        assert!(verify_fingerprint(&pk, &hex));
+        assert!(verify_fingerprint(&pk, &hex.to_lowercase().replace(' ', ":")));
+
+        let zb = group(&zbase32_encode(&digest(&pk)));
+        assert_eq!(zb.split(' ').count(), 13);
+        assert!(verify_fingerprint(&pk, &zb));
+
+        let emoji = emoji_digest(&digest(&pk)).join(" ");
+        assert_eq!(emoji_digest(&digest(&pk)).len(), 8);
+        assert_eq!(compare_fingerprint(&pk, &hex), FingerprintMatch::Full);
+        assert_eq!(compare_fingerprint(&pk, &zb), FingerprintMatch::Full);
+        assert_eq!(compare_fingerprint(&pk, &emoji), FingerprintMatch::Visual);
+    }
+
+    #[test]
+    fn test_emoji_digest_does_not_verify() {
+        let pk = [9u8; 32];
+        let emoji = emoji_digest(&digest(&pk)).join(" ");
+        assert!(!verify_fingerprint(&pk, &emoji));
+        assert_eq!(compare_fingerprint(&[8u8; 32], &emoji), FingerprintMatch::None);
+    }
+
+    #[test]
+    fn test_verify_rejects_other_key() {
+        let hex = group(&hex::encode_upper(digest(&[1u8; 32])));
+        assert!(!verify_fingerprint(&[2u8; 32], &hex));
+        assert!(!verify_fingerprint(&[1u8; 31], &hex));
+        assert!(!verify_fingerprint(&[1u8; 32], "not a fingerprint"));
+    }
+}
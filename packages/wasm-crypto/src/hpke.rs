@@ -0,0 +1,135 @@
+//! Hybrid Public-Key Encryption (HPKE) Sealed Boxes
+//!
+//! RFC 9180 base mode with the suite
+//! DHKEM(X25519, HKDF-SHA256) + HKDF-SHA256 + ChaCha20Poly1305.
+//!
+//! Lets a sender encrypt to a recipient's X25519 public key without an
+//! interactive handshake (offline share tokens, wrapping group envelope keys).
+//!
+//! Sealed box layout: `enc (32 bytes) || ciphertext || tag (16 bytes)`.
+//!
+//! The primitive lives in `holi-hpke`; this module adds the JS bindings and
+//! draws ephemeral keys from `HoliRng`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+use crate::rng::random_bytes;
+
+pub use holi_hpke::{HPKE_ENC_LEN, HPKE_TAG_LEN};
+
+fn hpke_error(e: holi_hpke::HpkeError) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+fn parse_public_key(bytes: &[u8]) -> Result<[u8; 32], JsValue> {
+    if bytes.len() != 32 {
+        return Err(JsValue::from_str("Public key must be 32 bytes"));
+    }
+    let mut pk = [0u8; 32];
+    pk.copy_from_slice(bytes);
+    Ok(pk)
+}
+
+/// Encrypt `plaintext` to a recipient's X25519 public key.
+///
+/// `info` binds the application context (e.g. `b"holi.share-token.v1"`) and
+/// must match on open. `aad` is authenticated but not encrypted.
+/// Returns: enc (32 bytes) + ciphertext + tag (16 bytes)
+#[wasm_bindgen]
+pub fn hpke_seal(
+    recipient_public_key: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+    let pk_r = parse_public_key(recipient_public_key)?;
+    let ephemeral_secret: [u8; 32] = random_bytes();
+    holi_hpke::seal(&ephemeral_secret, &pk_r, info, aad, plaintext).map_err(hpke_error)
+}
+
+/// X25519 keypair for receiving HPKE sealed boxes
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HpkeKeyPair {
+    #[wasm_bindgen(skip)]
+    secret_bytes: [u8; 32],
+}
+
+#[wasm_bindgen]
+impl HpkeKeyPair {
+    /// Generate a new random X25519 keypair
+    #[wasm_bindgen(constructor)]
+    pub fn generate() -> Self {
+        HpkeKeyPair {
+            secret_bytes: random_bytes(),
+        }
+    }
+
+    /// Create keypair from a raw 32-byte secret
+    pub fn from_bytes(bytes: &[u8]) -> Result<HpkeKeyPair, JsValue> {
+        if bytes.len() != 32 {
+            return Err(JsValue::from_str("Secret key must be 32 bytes"));
+        }
+        let mut secret_bytes = [0u8; 32];
+        secret_bytes.copy_from_slice(bytes);
+        Ok(HpkeKeyPair { secret_bytes })
+    }
+
+    /// Export the secret key as bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.secret_bytes.to_vec()
+    }
+
+    /// Get the public key as bytes
+    pub fn public_key_bytes(&self) -> Vec<u8> {
+        holi_hpke::public_key(&self.secret_bytes).to_vec()
+    }
+
+    /// Get the public key as hex string
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.public_key_bytes())
+    }
+
+    /// Decrypt a sealed box produced by `hpke_seal` for this keypair.
+    /// Expects: enc (32 bytes) + ciphertext + tag.
+    pub fn open(&self, info: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, JsValue> {
+        holi_hpke::open(&self.secret_bytes, info, aad, sealed).map_err(hpke_error)
+    }
+}
+
+impl fmt::Debug for HpkeKeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HpkeKeyPair")
+         .field("public", &self.public_key_hex())
+         .finish()
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let recipient = HpkeKeyPair::generate();
+        let info = b"holi.test.v1";
+        let sealed = hpke_seal(&recipient.public_key_bytes(), info, b"aad", b"Project Key").unwrap();
+        assert_eq!(sealed.len(), HPKE_ENC_LEN + 11 + HPKE_TAG_LEN);
+
+        let opened = recipient.open(info, b"aad", &sealed).unwrap();
+        assert_eq!(opened, b"Project Key");
+    }
+
+    #[test]
+    fn test_open_wrong_recipient_or_context() {
+        let recipient = HpkeKeyPair::generate();
+        let other = HpkeKeyPair::generate();
+        let sealed = hpke_seal(&recipient.public_key_bytes(), b"info", b"", b"secret").unwrap();
+
+        assert!(other.open(b"info", b"", &sealed).is_err());
+        assert!(recipient.open(b"other-info", b"", &sealed).is_err());
+        assert!(recipient.open(b"info", b"aad", &sealed).is_err());
+    }
+}
@@ -3,8 +3,8 @@
 //! Provides keypair generation, signing, and verification.
 
 use ed25519_dalek::{SigningKey, VerifyingKey, Signer, Verifier, Signature};
-use rand::rngs::OsRng;
 use serde::{Serialize, Deserialize};
+use crate::rng::HoliRng;
 use std::fmt;
 use wasm_bindgen::prelude::*;
 
@@ -21,7 +21,7 @@ impl IdentityKey {
     /// Generate a new random identity keypair
     #[wasm_bindgen(constructor)]
     pub fn generate() -> Self {
-        let mut csprng = OsRng;
+        let mut csprng = HoliRng;
         let signing_key = SigningKey::generate(&mut csprng);
         IdentityKey {
             secret_bytes: signing_key.to_bytes(),
@@ -1,12 +1,18 @@
 //! Holi.tools Cryptographic Primitives
 //! 
-//! Provides Ed25519 signing and ChaCha20-Poly1305 encryption.
+//! Provides Ed25519 signing, ChaCha20-Poly1305 encryption, HPKE sealed boxes
+//! and signed QR codes.
 //! Designed for identity, vault, and P2P communication.
 
 pub mod identity;
 pub mod encryption;
 pub mod pake;
 pub mod vault;
+pub mod hpke;
+pub mod rng;
+pub mod fingerprint;
+pub mod signed_qr;
+pub mod events;
 
 use wasm_bindgen::prelude::*;
 
@@ -21,3 +27,14 @@ pub fn init() {
 pub fn crypto_version() -> String {
     "holi-wasm-crypto v0.1.0".to_string()
 }
+
+/// Which build of this module is running:
+/// `{ package, version, gitHash, features, protocols }`.
+/// `features` includes `test-rng` on builds that must not ship.
+#[wasm_bindgen]
+pub fn crypto_build_info() -> JsValue {
+    holi_wasm_build_info::BuildInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .with_feature("test-rng", cfg!(feature = "test-rng"))
+        .with_protocol("signedQr", holi_qr::SIGNED_QR_VERSION)
+        .to_js()
+}
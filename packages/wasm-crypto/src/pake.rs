@@ -1,37 +1,40 @@
 //! Password-Authenticated Key Exchange (PAKE)
 //!
-//! Current implementation: SPAKE2 over Ed25519Group.
+//! Current implementation: SPAKE2 over Ed25519Group, from `holi-pake`.
 //!
 //! Notes:
 //! - This is meant to be used for establishing a per-session symmetric key
 //!   without ever sending the password or a password-derived key.
 //! - The raw SPAKE2 output is further expanded via HKDF-SHA256 to produce a
 //!   domain-separated 32-byte session key.
+//! - `finish` succeeds with a wrong password too; it only shows when the
+//!   first frame sealed under the key fails to open. These bindings do not
+//!   limit attempts: pair with short PINs through wasm-core's `PairingGuard`
+//!   (`start_a` / `start_b` / `start_symmetric`), which refuses codes that are
+//!   backed off or locked out. Both derive the same key.
 
-use hkdf::Hkdf;
-use sha2::Sha256;
-use spake2::{Ed25519Group, Identity, Password, Spake2};
+use holi_pake::PakeSession;
 use wasm_bindgen::prelude::*;
 
-const HOLI_PAKE_SALT_V1: &[u8] = b"holi.pake.salt.v1";
-const HOLI_PAKE_INFO_SESSION_KEY_V1: &[u8] = b"holi.pake.info.session_key.v1";
+use crate::events::{self, CryptoEvent, PakeRole, PakeState};
+use crate::rng::HoliRng;
 
-fn hkdf_32(shared_key_material: &[u8]) -> Result<[u8; 32], JsValue> {
-    let hk = Hkdf::<Sha256>::new(Some(HOLI_PAKE_SALT_V1), shared_key_material);
-    let mut okm = [0u8; 32];
-    hk.expand(HOLI_PAKE_INFO_SESSION_KEY_V1, &mut okm)
-        .map_err(|_| JsValue::from_str("HKDF expand failed"))?;
-    Ok(okm)
-}
+/// Consume the SPAKE2 state, derive the session key and report the outcome to subscribers.
+fn finish_session(state: &mut Option<PakeSession>, inbound_msg: &[u8], role: PakeRole) -> Result<Vec<u8>, JsValue> {
+    let result = state
+        .take()
+        .ok_or_else(|| JsValue::from_str("SPAKE2 state already consumed"))
+        .and_then(|state| state.finish(inbound_msg).map_err(|e| JsValue::from_str(&e.to_string())));
 
-fn spake_err(e: spake2::Error) -> JsValue {
-    JsValue::from_str(&format!("SPAKE2 failed: {e}"))
+    let outcome = if result.is_ok() { PakeState::Established } else { PakeState::Failed };
+    events::emit(CryptoEvent::PakeState { role, state: outcome });
+    result.map(|key| key.to_vec())
 }
 
 /// SPAKE2 role A (typically: offerer / initiator).
 #[wasm_bindgen]
 pub struct Spake2A {
-    state: Option<Spake2<Ed25519Group>>,
+    state: Option<PakeSession>,
     outbound_msg: Vec<u8>,
 }
 
@@ -43,11 +46,8 @@ impl Spake2A {
     /// For holi, these should be stable per pairing context (e.g. contact IDs).
     #[wasm_bindgen(constructor)]
     pub fn new(password: &[u8], id_a: &[u8], id_b: &[u8]) -> Result<Spake2A, JsValue> {
-        let (state, outbound_msg) = Spake2::<Ed25519Group>::start_a(
-            &Password::new(password),
-            &Identity::new(id_a),
-            &Identity::new(id_b),
-        );
+        let state = PakeSession::start_a(password, id_a, id_b, HoliRng);
+        let outbound_msg = state.message().to_vec();
 
         Ok(Spake2A {
             state: Some(state),
@@ -62,21 +62,14 @@ impl Spake2A {
 
     /// Finish the handshake with the peer's message and derive a 32-byte session key.
     pub fn finish(&mut self, inbound_msg: &[u8]) -> Result<Vec<u8>, JsValue> {
-        let state = self
-            .state
-            .take()
-            .ok_or_else(|| JsValue::from_str("SPAKE2 state already consumed"))?;
-
-        let shared = state.finish(inbound_msg).map_err(spake_err)?;
-        let session_key = hkdf_32(&shared)?;
-        Ok(session_key.to_vec())
+        finish_session(&mut self.state, inbound_msg, PakeRole::A)
     }
 }
 
 /// SPAKE2 role B (typically: answerer / responder).
 #[wasm_bindgen]
 pub struct Spake2B {
-    state: Option<Spake2<Ed25519Group>>,
+    state: Option<PakeSession>,
     outbound_msg: Vec<u8>,
 }
 
@@ -85,11 +78,8 @@ impl Spake2B {
     /// Start SPAKE2 role B.
     #[wasm_bindgen(constructor)]
     pub fn new(password: &[u8], id_a: &[u8], id_b: &[u8]) -> Result<Spake2B, JsValue> {
-        let (state, outbound_msg) = Spake2::<Ed25519Group>::start_b(
-            &Password::new(password),
-            &Identity::new(id_a),
-            &Identity::new(id_b),
-        );
+        let state = PakeSession::start_b(password, id_a, id_b, HoliRng);
+        let outbound_msg = state.message().to_vec();
 
         Ok(Spake2B {
             state: Some(state),
@@ -104,14 +94,7 @@ impl Spake2B {
 
     /// Finish the handshake with the peer's message and derive a 32-byte session key.
     pub fn finish(&mut self, inbound_msg: &[u8]) -> Result<Vec<u8>, JsValue> {
-        let state = self
-            .state
-            .take()
-            .ok_or_else(|| JsValue::from_str("SPAKE2 state already consumed"))?;
-
-        let shared = state.finish(inbound_msg).map_err(spake_err)?;
-        let session_key = hkdf_32(&shared)?;
-        Ok(session_key.to_vec())
+        finish_session(&mut self.state, inbound_msg, PakeRole::B)
     }
 }
 
@@ -120,7 +103,7 @@ impl Spake2B {
 /// Useful when you don't want to pre-assign A/B roles.
 #[wasm_bindgen]
 pub struct Spake2Symmetric {
-    state: Option<Spake2<Ed25519Group>>,
+    state: Option<PakeSession>,
     outbound_msg: Vec<u8>,
 }
 
@@ -128,10 +111,8 @@ pub struct Spake2Symmetric {
 impl Spake2Symmetric {
     #[wasm_bindgen(constructor)]
     pub fn new(password: &[u8], id_s: &[u8]) -> Result<Spake2Symmetric, JsValue> {
-        let (state, outbound_msg) = Spake2::<Ed25519Group>::start_symmetric(
-            &Password::new(password),
-            &Identity::new(id_s),
-        );
+        let state = PakeSession::start_symmetric(password, id_s, HoliRng);
+        let outbound_msg = state.message().to_vec();
 
         Ok(Spake2Symmetric {
             state: Some(state),
@@ -144,14 +125,7 @@ impl Spake2Symmetric {
     }
 
     pub fn finish(&mut self, inbound_msg: &[u8]) -> Result<Vec<u8>, JsValue> {
-        let state = self
-            .state
-            .take()
-            .ok_or_else(|| JsValue::from_str("SPAKE2 state already consumed"))?;
-
-        let shared = state.finish(inbound_msg).map_err(spake_err)?;
-        let session_key = hkdf_32(&shared)?;
-        Ok(session_key.to_vec())
+        finish_session(&mut self.state, inbound_msg, PakeRole::Symmetric)
     }
 }
 
@@ -0,0 +1,37 @@
+//! Injectable Randomness
+//!
+//! Every key and nonce in this crate is drawn through `HoliRng`, from
+//! `holi-rng` so that wasm-p2p and wasm-core draw theirs the same way.
+//! Production builds always read from `OsRng`. Builds with the `test-rng`
+//! feature (and unit tests) can install a seeded ChaCha20 stream so that
+//! envelopes and keys are reproducible against fixed vectors; the
+//! `set_test_rng_seed` / `clear_test_rng` bindings come with it.
+
+pub use holi_rng::{random_bytes, HoliRng};
+#[cfg(any(test, feature = "test-rng"))]
+pub use holi_rng::{reset_test_rng, seed_test_rng};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::EncryptionKey;
+    use crate::identity::IdentityKey;
+
+    #[test]
+    fn test_seeded_keys_and_nonces() {
+        seed_test_rng([1u8; 32]);
+        let key1 = EncryptionKey::generate();
+        let id1 = IdentityKey::generate();
+        let ct1 = key1.encrypt(b"fixed vector").unwrap();
+
+        seed_test_rng([1u8; 32]);
+        let key2 = EncryptionKey::generate();
+        let id2 = IdentityKey::generate();
+        let ct2 = key2.encrypt(b"fixed vector").unwrap();
+        reset_test_rng();
+
+        assert_eq!(key1.to_bytes(), key2.to_bytes());
+        assert_eq!(id1.public_key_bytes(), id2.public_key_bytes());
+        assert_eq!(ct1, ct2);
+    }
+}
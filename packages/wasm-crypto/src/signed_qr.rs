@@ -0,0 +1,128 @@
+//! Signed QR Codes
+//!
+//! Ed25519 signing and offline verification for the signed QR format in
+//! `holi-qr` (`content || signature || key id`, byte mode). The key id is
+//! the first 8 bytes of the signer's fingerprint digest.
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use holi_qr::{signed_qr_key_id as key_id, ErrorCorrectionLevel, QrSigner, KEY_ID_LEN, SIGNATURE_LEN};
+use wasm_bindgen::prelude::*;
+
+use crate::identity::IdentityKey;
+
+const PUBLIC_KEY_LEN: usize = 32;
+
+impl QrSigner for IdentityKey {
+    fn key_id(&self) -> [u8; KEY_ID_LEN] {
+        key_id(&self.public_key_bytes())
+    }
+
+    fn sign(&self, message: &[u8]) -> [u8; SIGNATURE_LEN] {
+        let mut sig = [0u8; SIGNATURE_LEN];
+        sig.copy_from_slice(&IdentityKey::sign(self, message));
+        sig
+    }
+}
+
+/// Verify `payload` against `trusted_keys`, returning the signed content.
+fn verify_payload<'a>(payload: &'a [u8], trusted_keys: &[[u8; PUBLIC_KEY_LEN]]) -> Result<&'a [u8], &'static str> {
+    let signed = holi_qr::split_signed_payload(payload).map_err(|_| "Not a signed QR payload")?;
+    let signature = Signature::from_bytes(&signed.signature);
+    let message = signed.message();
+
+    // Key ids are short, so a collision just means trying the next key.
+    let verified = trusted_keys
+        .iter()
+        .filter(|pk| key_id(pk.as_slice()) == signed.key_id)
+        .filter_map(|pk| VerifyingKey::from_bytes(pk).ok())
+        .any(|vk| vk.verify(&message, &signature).is_ok());
+    if verified {
+        Ok(signed.content)
+    } else {
+        Err("Signature not valid for any trusted key")
+    }
+}
+
+/// Key id embedded in QRs signed by `public_key`
+#[wasm_bindgen]
+pub fn signed_qr_key_id(public_key: &[u8]) -> Result<Vec<u8>, JsValue> {
+    if public_key.len() != PUBLIC_KEY_LEN {
+        return Err(JsValue::from_str("Public key must be 32 bytes"));
+    }
+    Ok(key_id(public_key).to_vec())
+}
+
+/// Signed byte-mode payload for `content`, for callers with their own QR renderer
+#[wasm_bindgen]
+pub fn signed_qr_payload(content: &[u8], identity: &IdentityKey) -> Vec<u8> {
+    holi_qr::encode_signed_payload(content, identity)
+}
+
+/// Generate a signed QR code as SVG
+#[wasm_bindgen]
+pub fn generate_signed_qr(content: &[u8], identity: &IdentityKey) -> Result<String, JsValue> {
+    let qr = holi_qr::generate_signed_qr(content, identity, ErrorCorrectionLevel::Medium)
+        .map_err(|e| JsValue::from_str(&format!("Signed QR generation failed: {}", e)))?;
+    Ok(holi_qr::render_svg(&qr))
+}
+
+/// Verify a scanned signed QR offline.
+///
+/// `decoded_bytes` must be the raw byte-mode payload (e.g. jsQR's
+/// `binaryData`), and `trusted_keys` the concatenated 32-byte public keys to
+/// accept. Returns the signed content.
+#[wasm_bindgen]
+pub fn verify_signed_qr(decoded_bytes: &[u8], trusted_keys: &[u8]) -> Result<Vec<u8>, JsValue> {
+    if !trusted_keys.len().is_multiple_of(PUBLIC_KEY_LEN) {
+        return Err(JsValue::from_str("Trusted keys must be concatenated 32-byte public keys"));
+    }
+    let keys: Vec<[u8; PUBLIC_KEY_LEN]> = trusted_keys
+        .chunks_exact(PUBLIC_KEY_LEN)
+        .map(|k| {
+            let mut key = [0u8; PUBLIC_KEY_LEN];
+            key.copy_from_slice(k);
+            key
+        })
+        .collect();
+    verify_payload(decoded_bytes, &keys)
+        .map(|content| content.to_vec())
+        .map_err(JsValue::from_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public_key(identity: &IdentityKey) -> [u8; PUBLIC_KEY_LEN] {
+        let mut pk = [0u8; PUBLIC_KEY_LEN];
+        pk.copy_from_slice(&identity.public_key_bytes());
+        pk
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let issuer = IdentityKey::generate();
+        let other = IdentityKey::generate();
+        let payload = holi_qr::encode_signed_payload(b"admit one", &issuer);
+
+        let trusted = [public_key(&other), public_key(&issuer)];
+        assert_eq!(verify_payload(&payload, &trusted).unwrap(), b"admit one");
+        assert!(verify_payload(&payload, &[public_key(&other)]).is_err());
+    }
+
+    #[test]
+    fn test_key_id_is_fingerprint_prefix() {
+        let pk = [3u8; PUBLIC_KEY_LEN];
+        assert_eq!(key_id(&pk), crate::fingerprint::digest(&pk)[..KEY_ID_LEN]);
+    }
+
+    #[test]
+    fn test_rejects_tampering() {
+        let issuer = IdentityKey::generate();
+        let trusted = [public_key(&issuer)];
+        let mut payload = holi_qr::encode_signed_payload(b"admit one", &issuer);
+        payload[0] ^= 1;
+        assert!(verify_payload(&payload, &trusted).is_err());
+        assert!(verify_payload(b"short", &trusted).is_err());
+    }
+}
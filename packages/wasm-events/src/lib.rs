@@ -0,0 +1,144 @@
+//! Holi.tools Event Bridge
+//!
+//! One subscription mechanism for every WASM package: JS registers a
+//! callback for an event kind (or `"*"` for all), Rust emits typed events
+//! that arrive as plain objects `{ type: "<kind>", ...fields }`.
+//!
+//! Each package owns a thread-local `EventEmitter` and exposes its own
+//! `subscribe_<pkg>_events` / `unsubscribe_<pkg>_events` bindings around it.
+//! The names are per package because wasm exports share one namespace when
+//! several packages are linked into the same binary.
+
+use std::cell::{Cell, RefCell};
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// Subscribe to every event kind.
+pub const ALL_EVENTS: &str = "*";
+
+/// A typed event that can be delivered to JS.
+///
+/// Implementors usually derive `Serialize` with `#[serde(tag = "type")]`
+/// so the kind shows up as the `type` field on the JS side.
+pub trait Event: Serialize {
+    /// Event kind used for listener filtering, e.g. `"transferProgress"`.
+    fn kind(&self) -> &'static str;
+}
+
+struct Listener {
+    id: u32,
+    kind: String,
+    callback: js_sys::Function,
+}
+
+/// Registry of JS callbacks keyed by subscription id.
+#[derive(Default)]
+pub struct EventEmitter {
+    listeners: RefCell<Vec<Listener>>,
+    next_id: Cell<u32>,
+}
+
+impl EventEmitter {
+    pub const fn new() -> Self {
+        EventEmitter {
+            listeners: RefCell::new(Vec::new()),
+            next_id: Cell::new(1),
+        }
+    }
+
+    /// Register `callback` for `kind`. Returns an id for `unsubscribe`.
+    pub fn subscribe(&self, kind: &str, callback: js_sys::Function) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1).max(1));
+        self.listeners.borrow_mut().push(Listener {
+            id,
+            kind: kind.to_string(),
+            callback,
+        });
+        id
+    }
+
+    /// Remove a subscription. Returns false if `id` was not registered.
+    pub fn unsubscribe(&self, id: u32) -> bool {
+        let mut listeners = self.listeners.borrow_mut();
+        let before = listeners.len();
+        listeners.retain(|l| l.id != id);
+        listeners.len() != before
+    }
+
+    /// Remove every subscription.
+    pub fn clear(&self) {
+        self.listeners.borrow_mut().clear();
+    }
+
+    pub fn listener_count(&self) -> usize {
+        self.listeners.borrow().len()
+    }
+
+    /// Whether anyone is listening for `kind`. Lets callers skip building
+    /// events nobody will receive.
+    pub fn has_listeners(&self, kind: &str) -> bool {
+        self.listeners
+            .borrow()
+            .iter()
+            .any(|l| l.kind == kind || l.kind == ALL_EVENTS)
+    }
+
+    /// Deliver `event` to every matching listener.
+    ///
+    /// Callback exceptions are swallowed so one faulty listener can't break
+    /// the Rust caller or starve the others.
+    pub fn emit<E: Event>(&self, event: &E) {
+        let kind = event.kind();
+        if !self.has_listeners(kind) {
+            return;
+        }
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        let Ok(value) = event.serialize(&serializer) else {
+            return;
+        };
+        // Snapshot callbacks so listeners may (un)subscribe while being called.
+        let callbacks: Vec<js_sys::Function> = self
+            .listeners
+            .borrow()
+            .iter()
+            .filter(|l| l.kind == kind || l.kind == ALL_EVENTS)
+            .map(|l| l.callback.clone())
+            .collect();
+        for callback in callbacks {
+            let _ = callback.call1(&JsValue::NULL, &value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    #[serde(tag = "type", rename_all = "camelCase")]
+    enum TestEvent {
+        Ping,
+    }
+
+    impl Event for TestEvent {
+        fn kind(&self) -> &'static str {
+            "ping"
+        }
+    }
+
+    #[test]
+    fn test_emit_without_listeners_is_noop() {
+        let emitter = EventEmitter::new();
+        assert!(!emitter.has_listeners("ping"));
+        emitter.emit(&TestEvent::Ping);
+        assert_eq!(emitter.listener_count(), 0);
+    }
+
+    #[test]
+    fn test_unsubscribe_unknown_id() {
+        let emitter = EventEmitter::new();
+        assert!(!emitter.unsubscribe(42));
+    }
+}
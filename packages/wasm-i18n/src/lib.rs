@@ -0,0 +1,119 @@
+//! Holi.tools Message Localization
+//!
+//! Each package keeps its own catalog of user-facing errors and warnings as
+//! an enum implementing [`Message`]; this crate holds the active locale and
+//! the `set_locale` / `get_locale` bindings. The locale is per module, so the
+//! app calls `set_locale` on every package it loads.
+
+use std::cell::Cell;
+
+use wasm_bindgen::prelude::*;
+
+/// Supported UI languages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+}
+
+impl Locale {
+    /// Parse a BCP 47 tag (`"es"`, `"es-MX"`, `"en_US"`); only the language is used.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_']).next().unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "en" => Some(Locale::En),
+            "es" => Some(Locale::Es),
+            _ => None,
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+        }
+    }
+}
+
+thread_local! {
+    static LOCALE: Cell<Locale> = const { Cell::new(Locale::En) };
+}
+
+/// A user-facing message with a translation for every [`Locale`].
+pub trait Message {
+    fn text(&self, locale: Locale) -> String;
+}
+
+pub fn locale() -> Locale {
+    LOCALE.with(|l| l.get())
+}
+
+pub fn set_active_locale(locale: Locale) {
+    LOCALE.with(|l| l.set(locale));
+}
+
+/// `msg` in the active locale.
+pub fn localize(msg: &impl Message) -> String {
+    msg.text(locale())
+}
+
+/// `msg` in the active locale, as a JS error value.
+pub fn js_error(msg: &impl Message) -> JsValue {
+    JsValue::from_str(&localize(msg))
+}
+
+/// Select the language of error and warning messages ("en", "es", "es-MX", ...).
+/// Returns false (and keeps the current locale) if the language is unsupported.
+#[wasm_bindgen]
+pub fn set_locale(tag: &str) -> bool {
+    match Locale::parse(tag) {
+        Some(locale) => {
+            set_active_locale(locale);
+            true
+        }
+        None => false,
+    }
+}
+
+#[wasm_bindgen]
+pub fn get_locale() -> String {
+    locale().tag().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Greeting;
+
+    impl Message for Greeting {
+        fn text(&self, locale: Locale) -> String {
+            match locale {
+                Locale::En => "hello".into(),
+                Locale::Es => "hola".into(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(Locale::parse("es"), Some(Locale::Es));
+        assert_eq!(Locale::parse("es-MX"), Some(Locale::Es));
+        assert_eq!(Locale::parse("EN_us"), Some(Locale::En));
+        assert_eq!(Locale::parse("fr"), None);
+        assert_eq!(Locale::parse(""), None);
+    }
+
+    #[test]
+    fn test_set_locale() {
+        assert_eq!(localize(&Greeting), "hello");
+        assert!(set_locale("es-AR"));
+        assert_eq!(localize(&Greeting), "hola");
+        assert_eq!(get_locale(), "es");
+
+        assert!(!set_locale("de"));
+        assert_eq!(locale(), Locale::Es);
+        set_active_locale(Locale::En);
+    }
+}
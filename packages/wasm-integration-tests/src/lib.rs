@@ -0,0 +1,10 @@
+//! Holi.tools WASM Integration Tests
+//!
+//! Exercises the real WASM builds end to end under `wasm-bindgen-test` in
+//! headless browsers:
+//! - QR generate -> verify (`wasm-qr`)
+//! - frame encode -> encrypt -> decrypt -> decode (`wasm-p2p` + `wasm-crypto`)
+//! - SPAKE2 pairing between two `wasm-crypto` instances
+//!
+//! All tests live in `tests/`. Run with
+//! `wasm-pack test --headless --chrome --firefox`.
@@ -0,0 +1,34 @@
+//! Frame encode -> encrypt -> decrypt -> decode through the wasm-p2p bindings,
+//! keyed with material from wasm-crypto.
+
+#![cfg(target_arch = "wasm32")]
+
+use holi_wasm_crypto::encryption::EncryptionKey;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn chat_frame_survives_envelope() {
+    let key = EncryptionKey::generate().to_bytes();
+    let frame = holi_wasm_p2p::encode_chat_text_v1("hola desde el navegador");
+
+    let envelope = holi_wasm_p2p::encrypt_envelope_v1(&key, &frame).unwrap();
+    let inner = holi_wasm_p2p::decrypt_envelope_v1(&key, &envelope).unwrap();
+
+    assert_eq!(inner, frame);
+    assert_eq!(
+        holi_wasm_p2p::decode_chat_text_payload_v1(&inner).unwrap(),
+        "hola desde el navegador"
+    );
+}
+
+#[wasm_bindgen_test]
+fn envelope_rejects_wrong_key() {
+    let key = EncryptionKey::generate().to_bytes();
+    let other = EncryptionKey::generate().to_bytes();
+    let frame = holi_wasm_p2p::encode_file_end_v1("file-1");
+
+    let envelope = holi_wasm_p2p::encrypt_envelope_v1(&key, &frame).unwrap();
+    assert!(holi_wasm_p2p::decrypt_envelope_v1(&other, &envelope).is_err());
+}
@@ -0,0 +1,43 @@
+//! SPAKE2 pairing between two wasm-crypto instances, then a wasm-p2p
+//! envelope under the derived session key.
+
+#![cfg(target_arch = "wasm32")]
+
+use holi_wasm_crypto::pake::{Spake2A, Spake2B, Spake2Symmetric};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const ID_A: &[u8] = b"holi:it:alice";
+const ID_B: &[u8] = b"holi:it:bob";
+
+#[wasm_bindgen_test]
+fn ab_pairing_keys_an_envelope() {
+    let mut a = Spake2A::new(b"482-913", ID_A, ID_B).unwrap();
+    let mut b = Spake2B::new(b"482-913", ID_A, ID_B).unwrap();
+    let key_a = a.finish(&b.message()).unwrap();
+    let key_b = b.finish(&a.message()).unwrap();
+    assert_eq!(key_a, key_b);
+
+    let frame = holi_wasm_p2p::encode_chat_text_v1("paired");
+    let envelope = holi_wasm_p2p::encrypt_envelope_v1(&key_a, &frame).unwrap();
+    assert_eq!(holi_wasm_p2p::decrypt_envelope_v1(&key_b, &envelope).unwrap(), frame);
+}
+
+#[wasm_bindgen_test]
+fn wrong_password_yields_different_keys() {
+    let mut a = Spake2A::new(b"482-913", ID_A, ID_B).unwrap();
+    let mut b = Spake2B::new(b"000-000", ID_A, ID_B).unwrap();
+    let key_a = a.finish(&b.message()).unwrap();
+    let key_b = b.finish(&a.message()).unwrap();
+    assert_ne!(key_a, key_b);
+}
+
+#[wasm_bindgen_test]
+fn symmetric_pairing_agrees() {
+    let mut x = Spake2Symmetric::new(b"shared", b"holi:it:room").unwrap();
+    let mut y = Spake2Symmetric::new(b"shared", b"holi:it:room").unwrap();
+    let key_x = x.finish(&y.message()).unwrap();
+    let key_y = y.finish(&x.message()).unwrap();
+    assert_eq!(key_x, key_y);
+}
@@ -0,0 +1,22 @@
+//! QR generate -> verify through the wasm-qr bindings.
+
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn generated_svg_decodes_to_input() {
+    let text = "https://holi.tools/p/integration";
+    let svg = holi_wasm_qr::generate_qr_svg(text).unwrap();
+    assert_eq!(holi_wasm_qr::verify_qr_svg(&svg).unwrap(), text);
+}
+
+#[wasm_bindgen_test]
+fn styled_svg_decodes_to_input() {
+    let text = "holi styled";
+    let options = r#"{"body_shape":"rounded","eye_frame_shape":"circle","eye_ball_shape":"circle"}"#;
+    let svg = holi_wasm_qr::generate_styled_svg(text, options).unwrap();
+    assert_eq!(holi_wasm_qr::verify_qr_svg(&svg).unwrap(), text);
+}
@@ -0,0 +1,162 @@
+//! Holi.tools Memory Stats
+//!
+//! Shared pieces behind each package's `<pkg>_memory_stats()` binding, so the
+//! app can spot leaks and clear caches under memory pressure on mobile.
+//!
+//! Linear memory size is always available. Allocator figures need the
+//! package to be built with its `alloc-stats` feature, which installs
+//! `CountingAllocator` as the global allocator; otherwise they are `null`.
+//! Only one package per binary may enable it.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// Size of one wasm memory page.
+pub const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static LIVE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// System allocator wrapper that tracks live and peak heap usage.
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn track_alloc(size: usize) {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let now = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(now, Ordering::Relaxed);
+        LIVE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn track_dealloc(size: usize) {
+        ALLOCATED.fetch_sub(size, Ordering::Relaxed);
+        LIVE_ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::track_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::track_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::track_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::track_dealloc(layout.size());
+            Self::track_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Heap usage as seen by `CountingAllocator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllocatorStats {
+    pub allocated_bytes: usize,
+    pub peak_allocated_bytes: usize,
+    pub live_allocations: usize,
+}
+
+/// `None` unless `CountingAllocator` is the global allocator.
+pub fn allocator_stats() -> Option<AllocatorStats> {
+    if !INSTALLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    Some(AllocatorStats {
+        allocated_bytes: ALLOCATED.load(Ordering::Relaxed),
+        peak_allocated_bytes: PEAK.load(Ordering::Relaxed),
+        live_allocations: LIVE_ALLOCATIONS.load(Ordering::Relaxed),
+    })
+}
+
+/// Current size of the module's linear memory (0 off wasm).
+pub fn linear_memory_bytes() -> usize {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0) * WASM_PAGE_SIZE
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+/// Snapshot returned to JS as `{ linearMemoryBytes, allocator, caches }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    pub linear_memory_bytes: usize,
+    pub allocator: Option<AllocatorStats>,
+    /// Live entries per package-owned cache, e.g. `{ "gpuBuffers": 4 }`.
+    pub caches: BTreeMap<&'static str, usize>,
+}
+
+impl MemoryStats {
+    pub fn collect() -> Self {
+        MemoryStats {
+            linear_memory_bytes: linear_memory_bytes(),
+            allocator: allocator_stats(),
+            caches: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_cache(mut self, name: &'static str, live: usize) -> Self {
+        self.caches.insert(name, live);
+        self
+    }
+
+    pub fn to_js(&self) -> JsValue {
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        self.serialize(&serializer).unwrap_or(JsValue::NULL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static ALLOC: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn test_counts_allocations() {
+        let before = allocator_stats().expect("allocator installed");
+        let buffer = vec![0u8; WASM_PAGE_SIZE * 16];
+        let during = allocator_stats().unwrap();
+        assert!(during.peak_allocated_bytes >= buffer.len());
+        assert!(during.allocated_bytes >= buffer.len());
+        drop(buffer);
+        assert!(allocator_stats().unwrap().peak_allocated_bytes >= before.allocated_bytes);
+    }
+
+    #[test]
+    fn test_collect_with_caches() {
+        let stats = MemoryStats::collect().with_cache("buffers", 3);
+        assert_eq!(stats.caches.get("buffers"), Some(&3));
+        assert_eq!(stats.linear_memory_bytes, 0);
+    }
+}
@@ -0,0 +1,188 @@
+//! One frame to a small group ("room") of peers in a single MultiEnvelope.
+//!
+//! The sender seals the frame once per peer with that peer's session;
+//! relays forward the container (or `multi_envelope_entry_v1` copies) and
+//! each recipient opens only its own entry.
+
+use std::collections::BTreeMap;
+
+use wasm_bindgen::prelude::*;
+
+use holi_p2p::frame::{
+	decode_multi_envelope_payload_v1, decode_v1, encode_multi_envelope_v1, FrameType, MultiEnvelopeEntry,
+	MULTI_ENVELOPE_MAX_RECIPIENTS,
+};
+
+use crate::messages::{decode_error, p2p_error, payload_error, P2pMessage};
+use crate::rekey::EnvelopeSession;
+
+fn decode_entries(bytes: &[u8]) -> Result<Vec<MultiEnvelopeEntry>, JsValue> {
+	let (frame, _used) = decode_v1(bytes, 16 * 1024 * 1024).map_err(decode_error)?;
+	if frame.frame_type != FrameType::MultiEnvelope {
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("MultiEnvelope")));
+	}
+	decode_multi_envelope_payload_v1(&frame.payload).map_err(payload_error)
+}
+
+/// The EncryptedEnvelope addressed to `recipient` in a MultiEnvelope, for
+/// relays that forward each peer only its own copy.
+#[wasm_bindgen]
+pub fn multi_envelope_entry_v1(bytes: &[u8], recipient: &str) -> Result<Option<Vec<u8>>, JsValue> {
+	Ok(decode_entries(bytes)?
+		.into_iter()
+		.find(|e| e.recipient == recipient)
+		.map(|e| e.envelope))
+}
+
+/// Peer ids a MultiEnvelope is addressed to.
+#[wasm_bindgen]
+pub fn multi_envelope_recipients_v1(bytes: &[u8]) -> Result<Vec<String>, JsValue> {
+	Ok(decode_entries(bytes)?.into_iter().map(|e| e.recipient).collect())
+}
+
+/// The peers of a room, each with its own `EnvelopeSession`: entries are
+/// sealed and opened like pairwise envelopes, with the same padding and
+/// key rotation. Drive rotation per peer with `rekey_due_peers`,
+/// `start_rekey` and `handle_rekey_frame`, sending what they return to that
+/// peer directly.
+#[wasm_bindgen]
+pub struct BroadcastGroup {
+	self_id: String,
+	/// Padding buckets applied to every peer's session; empty for none.
+	pad_buckets: Vec<u32>,
+	peers: BTreeMap<String, EnvelopeSession>,
+}
+
+impl BroadcastGroup {
+	fn session(&mut self, peer_id: &str) -> Result<&mut EnvelopeSession, JsValue> {
+		self.peers.get_mut(peer_id).ok_or_else(|| p2p_error(P2pMessage::UnknownPeer))
+	}
+}
+
+#[wasm_bindgen]
+impl BroadcastGroup {
+	/// `self_id` is our own peer id, used to find our entry when opening.
+	#[wasm_bindgen(constructor)]
+	pub fn new(self_id: &str) -> BroadcastGroup {
+		BroadcastGroup {
+			self_id: self_id.to_string(),
+			pad_buckets: Vec::new(),
+			peers: BTreeMap::new(),
+		}
+	}
+
+	/// Add `peer_id` with a fresh session on `key_bytes`, replacing any
+	/// session it had. `suite` as for `encrypt_envelope_with_suite_v1`.
+	pub fn set_peer_key(&mut self, peer_id: &str, key_bytes: &[u8], suite: u8) -> Result<(), JsValue> {
+		let mut session = EnvelopeSession::new(key_bytes, suite)?;
+		session.set_padding_buckets(self.pad_buckets.clone());
+		if !self.peers.contains_key(peer_id) && self.peers.len() == MULTI_ENVELOPE_MAX_RECIPIENTS {
+			return Err(p2p_error(P2pMessage::TooManyRecipients));
+		}
+		self.peers.insert(peer_id.to_string(), session);
+		Ok(())
+	}
+
+	/// Returns false if `peer_id` wasn't in the group.
+	pub fn remove_peer(&mut self, peer_id: &str) -> bool {
+		self.peers.remove(peer_id).is_some()
+	}
+
+	pub fn peers(&self) -> Vec<String> {
+		self.peers.keys().cloned().collect()
+	}
+
+	/// Padding for every peer's session, as `EnvelopeSession.set_padding_buckets`.
+	pub fn set_padding_buckets(&mut self, buckets: Vec<u32>) {
+		for session in self.peers.values_mut() {
+			session.set_padding_buckets(buckets.clone());
+		}
+		self.pad_buckets = buckets;
+	}
+
+	/// MultiEnvelope with `inner_frame_bytes` sealed for every peer.
+	pub fn seal(&mut self, inner_frame_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+		let entries = self
+			.peers
+			.iter_mut()
+			.map(|(peer, session)| {
+				Ok(MultiEnvelopeEntry {
+					recipient: peer.clone(),
+					envelope: session.seal(inner_frame_bytes)?,
+				})
+			})
+			.collect::<Result<Vec<_>, JsValue>>()?;
+		Ok(encode_multi_envelope_v1(&entries))
+	}
+
+	/// Open our entry of a MultiEnvelope sent by `sender_id`.
+	pub fn open(&self, sender_id: &str, bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+		let session = self
+			.peers
+			.get(sender_id)
+			.ok_or_else(|| p2p_error(P2pMessage::UnknownPeer))?;
+		let entry = decode_entries(bytes)?
+			.into_iter()
+			.find(|e| e.recipient == self.self_id)
+			.ok_or_else(|| p2p_error(P2pMessage::NotARecipient))?;
+		session.open(&entry.envelope)
+	}
+
+	/// Peers whose session reached a rotation threshold.
+	pub fn rekey_due_peers(&self) -> Vec<String> {
+		self.peers
+			.iter()
+			.filter(|(_, session)| session.rekey_due())
+			.map(|(peer, _)| peer.clone())
+			.collect()
+	}
+
+	/// Sealed RekeyRequest to send to `peer_id`.
+	pub fn start_rekey(&mut self, peer_id: &str) -> Result<Vec<u8>, JsValue> {
+		self.session(peer_id)?.start_rekey()
+	}
+
+	/// `EnvelopeSession.handle_rekey_frame` for the session with `peer_id`.
+	pub fn handle_rekey_frame(
+		&mut self,
+		peer_id: &str,
+		inner_frame_bytes: &[u8],
+	) -> Result<Option<Vec<u8>>, JsValue> {
+		self.session(peer_id)?.handle_rekey_frame(inner_frame_bytes)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A pairwise envelope from `peer_id`, opened with its session in `group`.
+	fn open_pairwise(group: &BroadcastGroup, peer_id: &str, envelope: &[u8]) -> Vec<u8> {
+		group.peers[peer_id].open(envelope).unwrap()
+	}
+
+	#[test]
+	fn peers_open_their_entries_through_their_sessions() {
+		let (ab, ac) = ([1u8; 32], [2u8; 32]);
+		let mut alice = BroadcastGroup::new("alice");
+		alice.set_peer_key("bob", &ab, 1).unwrap();
+		alice.set_peer_key("carol", &ac, 1).unwrap();
+		alice.set_padding_buckets(vec![256]);
+		let mut bob = BroadcastGroup::new("bob");
+		bob.set_peer_key("alice", &ab, 1).unwrap();
+
+		let inner = holi_p2p::frame::encode_chat_text_v1("hola");
+		let sealed = alice.seal(&inner).unwrap();
+		assert_eq!(multi_envelope_recipients_v1(&sealed).unwrap(), ["bob", "carol"]);
+		assert_eq!(bob.open("alice", &sealed).unwrap(), inner);
+
+		// Rotating alice's session with bob keeps the room readable.
+		let request = alice.start_rekey("bob").unwrap();
+		let request = open_pairwise(&bob, "alice", &request);
+		let response = bob.handle_rekey_frame("alice", &request).unwrap().unwrap();
+		let response = open_pairwise(&alice, "bob", &response);
+		assert_eq!(alice.handle_rekey_frame("bob", &response).unwrap(), None);
+		assert_eq!(bob.open("alice", &alice.seal(&inner).unwrap()).unwrap(), inner);
+	}
+
+}
@@ -0,0 +1,292 @@
+//! Burn-after-reading chat sessions; the key schedule is described in
+//! `holi_p2p::ephemeral`.
+
+use wasm_bindgen::prelude::*;
+
+use chacha20poly1305::{aead::Aead, aead::KeyInit, aead::Payload, XChaCha20Poly1305};
+use hkdf::Hkdf;
+use holi_rng::HoliRng;
+use rand::RngCore;
+use sha2::Sha256;
+use zeroize::{Zeroize, Zeroizing};
+
+use holi_p2p::ephemeral::{
+	chain_info, BurnLedger, BurnReport, EphemeralError, ReceiveWindow, DEFAULT_EPHEMERAL_TTL_MS,
+	EPHEMERAL_RATCHET_INFO,
+};
+use holi_p2p::frame::{
+	decode_ephemeral_envelope_payload_v1, decode_v1, ephemeral_associated_data_v1, encode_ephemeral_envelope_v1,
+	EphemeralEnvelope, FrameType, ENVELOPE_NONCE_LEN,
+};
+
+use crate::messages::{decode_error, p2p_error, payload_error, P2pMessage};
+
+type Key = Zeroizing<[u8; 32]>;
+
+/// First chain key of one direction.
+fn first_chain(session_key: &[u8; 32], from_initiator: bool) -> Key {
+	let mut chain = Zeroizing::new([0u8; 32]);
+	Hkdf::<Sha256>::new(None, session_key)
+		.expand(&chain_info(from_initiator), chain.as_mut())
+		.expect("32-byte HKDF output");
+	chain
+}
+
+/// Move `chain` one message forward, returning that message's key. The old
+/// chain key is overwritten.
+fn ratchet(chain: &mut Key) -> Key {
+	let mut okm = Zeroizing::new([0u8; 64]);
+	Hkdf::<Sha256>::from_prk(chain.as_ref())
+		.expect("32-byte PRK")
+		.expand(EPHEMERAL_RATCHET_INFO, okm.as_mut())
+		.expect("64-byte HKDF output");
+	chain.copy_from_slice(&okm[32..]);
+	let mut key = Zeroizing::new([0u8; 32]);
+	key.copy_from_slice(&okm[..32]);
+	key
+}
+
+fn millis(ms: f64) -> u64 {
+	if ms.is_finite() { ms.max(0.0) as u64 } else { 0 }
+}
+
+/// Burn-after-reading chat with one peer.
+///
+/// Every message has its own key from a hash ratchet, and each message's
+/// text and key are wiped a TTL after it was sent or received. Texts stay
+/// in WASM memory: `seal` and `open` return an id, and the UI reads the
+/// text with `text(id)` whenever it renders. Call `sweep` at
+/// `next_expiry()` and drop the ids it returns from the screen;
+/// `burn_report()` says what is left.
+///
+/// Wiping is best effort: strings already handed to JS (the text passed to
+/// `seal`, copies from `text`) are up to the garbage collector, and the
+/// session key passed in is the caller's to drop.
+#[wasm_bindgen]
+pub struct EphemeralChat {
+	ttl_ms: u64,
+	send_chain: Key,
+	send_index: u64,
+	receive_chain: Key,
+	window: ReceiveWindow<Key>,
+	messages: BurnLedger<Zeroizing<String>>,
+	destroyed: bool,
+}
+
+impl EphemeralChat {
+	fn with_key(session_key: &[u8; 32], initiator: bool, ttl_ms: u64) -> Self {
+		EphemeralChat {
+			ttl_ms: match ttl_ms {
+				0 => DEFAULT_EPHEMERAL_TTL_MS,
+				ttl => ttl,
+			},
+			send_chain: first_chain(session_key, initiator),
+			send_index: 0,
+			receive_chain: first_chain(session_key, !initiator),
+			window: ReceiveWindow::new(),
+			messages: BurnLedger::new(),
+			destroyed: false,
+		}
+	}
+
+	fn check_alive(&self) -> Result<(), P2pMessage> {
+		if self.destroyed {
+			return Err(P2pMessage::EphemeralBurned);
+		}
+		Ok(())
+	}
+
+	/// Seal `text` under the next send key and keep it; returns its id.
+	fn seal_envelope(&mut self, text: &str, now_ms: u64) -> Result<(u32, EphemeralEnvelope), P2pMessage> {
+		self.check_alive()?;
+		let key = ratchet(&mut self.send_chain);
+		let index = self.send_index;
+		self.send_index += 1;
+		let mut nonce = [0u8; ENVELOPE_NONCE_LEN];
+		HoliRng.fill_bytes(&mut nonce);
+		let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+			.encrypt(
+				(&nonce).into(),
+				Payload {
+					msg: text.as_bytes(),
+					aad: &ephemeral_associated_data_v1(index, self.ttl_ms),
+				},
+			)
+			.map_err(|_| P2pMessage::EncryptFailed)?;
+		let id = self.messages.insert(Zeroizing::new(text.to_string()), now_ms.saturating_add(self.ttl_ms));
+		Ok((id, EphemeralEnvelope { index, ttl_ms: self.ttl_ms, nonce, ciphertext }))
+	}
+
+	/// Open `envelope` and keep its text; returns its id. The chain moves
+	/// on a copy that is kept only once the message decrypts, so a forged
+	/// frame can't use up the key of the genuine one.
+	fn open_envelope(&mut self, envelope: &EphemeralEnvelope, now_ms: u64) -> Result<u32, P2pMessage> {
+		self.check_alive()?;
+		let expires_at = now_ms.saturating_add(self.ttl_ms.min(envelope.ttl_ms));
+
+		let mut receive_chain = self.receive_chain.clone();
+		let mut window = self.window.clone();
+		let key = window
+			.take(envelope.index, expires_at, || ratchet(&mut receive_chain))
+			.map_err(|e| match e {
+				EphemeralError::KeyGone { .. } => P2pMessage::EphemeralKeyGone,
+				EphemeralError::TooFarAhead { .. } => P2pMessage::EphemeralTooFarAhead,
+			})?;
+		let plaintext = Zeroizing::new(
+			XChaCha20Poly1305::new(key.as_ref().into())
+				.decrypt(
+					(&envelope.nonce).into(),
+					Payload {
+						msg: &envelope.ciphertext,
+						aad: &ephemeral_associated_data_v1(envelope.index, envelope.ttl_ms),
+					},
+				)
+				.map_err(|_| P2pMessage::DecryptFailed)?,
+		);
+		let text = std::str::from_utf8(&plaintext).map_err(|_| P2pMessage::PayloadNotUtf8)?;
+
+		self.receive_chain = receive_chain;
+		self.window = window;
+		Ok(self.messages.insert(Zeroizing::new(text.to_string()), expires_at))
+	}
+
+	/// `{ id, index, expiresAt }` of a message just stored.
+	fn entry(&self, id: u32, index: u64) -> Result<JsValue, JsValue> {
+		let obj = js_sys::Object::new();
+		let set = |name: &str, value: JsValue| js_sys::Reflect::set(&obj, &JsValue::from_str(name), &value);
+		set("id", JsValue::from(id))?;
+		set("index", JsValue::from_f64(index as f64))?;
+		set("expiresAt", self.messages.expires_at(id).map_or(JsValue::NULL, |t| JsValue::from_f64(t as f64)))?;
+		Ok(obj.into())
+	}
+}
+
+#[wasm_bindgen]
+impl EphemeralChat {
+	/// `session_key` is the shared session key; exactly one side passes
+	/// `initiator = true`. Messages are wiped `ttl_ms` after sending or
+	/// receiving (0 for the default of five minutes).
+	#[wasm_bindgen(constructor)]
+	pub fn new(session_key: &[u8], initiator: bool, ttl_ms: f64) -> Result<EphemeralChat, JsValue> {
+		let mut key = crate::parse_key_32(session_key)?;
+		let chat = EphemeralChat::with_key(&key, initiator, millis(ttl_ms));
+		key.zeroize();
+		Ok(chat)
+	}
+
+	#[wasm_bindgen(getter, js_name = ttlMs)]
+	pub fn ttl_ms(&self) -> f64 {
+		self.ttl_ms as f64
+	}
+
+	/// Seal `text` as an EphemeralEnvelope frame and keep it for display.
+	/// Returns `{ id, index, expiresAt, frame }`.
+	pub fn seal(&mut self, text: &str, now_ms: f64) -> Result<JsValue, JsValue> {
+		let (id, envelope) = self.seal_envelope(text, millis(now_ms)).map_err(p2p_error)?;
+		let frame = encode_ephemeral_envelope_v1(&envelope);
+		let entry = self.entry(id, envelope.index)?;
+		js_sys::Reflect::set(&entry, &JsValue::from_str("frame"), &js_sys::Uint8Array::from(&frame[..]))?;
+		Ok(entry)
+	}
+
+	/// Open an EphemeralEnvelope from the peer and keep its text. Returns
+	/// `{ id, index, expiresAt }`; read the text with `text(id)`.
+	pub fn open(&mut self, frame_bytes: &[u8], now_ms: f64) -> Result<JsValue, JsValue> {
+		self.check_alive().map_err(p2p_error)?;
+		let (frame, _used) = decode_v1(frame_bytes, 1024 * 1024).map_err(decode_error)?;
+		if frame.frame_type != FrameType::EphemeralEnvelope {
+			return Err(p2p_error(P2pMessage::UnexpectedFrame("EphemeralEnvelope")));
+		}
+		let envelope = decode_ephemeral_envelope_payload_v1(&frame.payload).map_err(payload_error)?;
+		let id = self.open_envelope(&envelope, millis(now_ms)).map_err(p2p_error)?;
+		self.entry(id, envelope.index)
+	}
+
+	/// Text of message `id`, or `undefined` once it was burned.
+	pub fn text(&self, id: u32) -> Option<String> {
+		self.messages.get(id).map(|text| text.as_str().to_string())
+	}
+
+	/// When the next message expires (ms, same clock as `now_ms`), or
+	/// `undefined` with nothing left.
+	#[wasm_bindgen(js_name = nextExpiry)]
+	pub fn next_expiry(&self) -> Option<f64> {
+		self.messages.next_expiry().map(|t| t as f64)
+	}
+
+	/// Wipe the messages and skipped keys that expired by `now_ms`.
+	/// Returns the ids of the wiped messages.
+	pub fn sweep(&mut self, now_ms: f64) -> Vec<u32> {
+		let now = millis(now_ms);
+		self.window.expire(now);
+		self.messages.sweep(now)
+	}
+
+	/// Wipe everything now, chain keys included; the session can't send or
+	/// receive afterwards. Returns the ids of the wiped messages.
+	#[wasm_bindgen(js_name = burnAll)]
+	pub fn burn_all(&mut self) -> Vec<u32> {
+		self.window.clear();
+		self.send_chain.zeroize();
+		self.receive_chain.zeroize();
+		self.destroyed = true;
+		self.messages.burn_all()
+	}
+
+	/// What the session still holds: `{ liveMessages, burnedMessages,
+	/// skippedKeys, sendIndex, receiveIndex, destroyed,
+	/// historyUnrecoverable }`. `historyUnrecoverable` means no text or key
+	/// of an earlier message is left in WASM memory.
+	#[wasm_bindgen(js_name = burnReport)]
+	pub fn burn_report(&self) -> Result<JsValue, JsValue> {
+		let report = BurnReport {
+			live_messages: self.messages.live(),
+			burned_messages: self.messages.burned(),
+			skipped_keys: self.window.skipped_keys(),
+			send_index: self.send_index,
+			receive_index: self.window.next_index(),
+			destroyed: self.destroyed,
+		};
+		let obj = js_sys::Object::new();
+		let set = |name: &str, value: JsValue| js_sys::Reflect::set(&obj, &JsValue::from_str(name), &value);
+		set("liveMessages", JsValue::from_f64(report.live_messages as f64))?;
+		set("burnedMessages", JsValue::from_f64(report.burned_messages as f64))?;
+		set("skippedKeys", JsValue::from_f64(report.skipped_keys as f64))?;
+		set("sendIndex", JsValue::from_f64(report.send_index as f64))?;
+		set("receiveIndex", JsValue::from_f64(report.receive_index as f64))?;
+		set("destroyed", JsValue::from_bool(report.destroyed))?;
+		set("historyUnrecoverable", JsValue::from_bool(report.history_unrecoverable()))?;
+		Ok(obj.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn forged_frame_does_not_use_up_the_key() {
+		let key = [7u8; 32];
+		let mut alice = EphemeralChat::with_key(&key, true, 60_000);
+		let mut bob = EphemeralChat::with_key(&key, false, 60_000);
+		let (_, genuine) = alice.seal_envelope("hola", 0).unwrap();
+		let (_, next) = alice.seal_envelope("adiós", 0).unwrap();
+		assert_ne!(genuine.nonce, next.nonce);
+
+		// A forgery at the genuine index, and one far enough ahead to skip keys
+		let forgeries = [
+			EphemeralEnvelope { ciphertext: vec![0; genuine.ciphertext.len()], ..genuine.clone() },
+			EphemeralEnvelope { index: 40, ..next.clone() },
+		];
+		for forged in &forgeries {
+			assert!(matches!(bob.open_envelope(forged, 0), Err(P2pMessage::DecryptFailed)));
+		}
+		assert_eq!((bob.window.next_index(), bob.window.skipped_keys()), (0, 0));
+
+		let id = bob.open_envelope(&genuine, 0).unwrap();
+		assert_eq!(bob.text(id).as_deref(), Some("hola"));
+		let id = bob.open_envelope(&next, 0).unwrap();
+		assert_eq!(bob.text(id).as_deref(), Some("adiós"));
+		assert!(matches!(bob.open_envelope(&genuine, 0), Err(P2pMessage::EphemeralKeyGone)));
+	}
+}
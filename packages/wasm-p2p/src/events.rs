@@ -0,0 +1,47 @@
+//! Transfer events delivered to JS subscribers.
+
+use holi_wasm_events::{Event, EventEmitter};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+	static EVENTS: EventEmitter = const { EventEmitter::new() };
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum P2pEvent {
+	/// `TransferLimiter` admitted a FileChunk of transfer `id`.
+	TransferProgress { id: String, chunk_index: u32, bytes: usize },
+	/// `TransferLimiter` saw the FileEnd of transfer `id`.
+	TransferComplete { id: String },
+}
+
+impl Event for P2pEvent {
+	fn kind(&self) -> &'static str {
+		match self {
+			P2pEvent::TransferProgress { .. } => "transferProgress",
+			P2pEvent::TransferComplete { .. } => "transferComplete",
+		}
+	}
+}
+
+pub fn emit(event: P2pEvent) {
+	EVENTS.with(|e| e.emit(&event));
+}
+
+/// Live JS subscriptions, reported by memory stats.
+pub fn listener_count() -> usize {
+	EVENTS.with(|e| e.listener_count())
+}
+
+/// Register `callback` for events of `kind` (`"*"` for all). Returns a subscription id.
+#[wasm_bindgen]
+pub fn subscribe_p2p_events(kind: &str, callback: js_sys::Function) -> u32 {
+	EVENTS.with(|e| e.subscribe(kind, callback))
+}
+
+#[wasm_bindgen]
+pub fn unsubscribe_p2p_events(id: u32) -> bool {
+	EVENTS.with(|e| e.unsubscribe(id))
+}
@@ -0,0 +1,94 @@
+use std::cell::Cell;
+
+use wasm_bindgen::prelude::*;
+
+use holi_p2p::keepalive::{self, ClockOffsetEstimator};
+
+thread_local! {
+	static LIVE_PEER_CLOCKS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// `PeerClock` instances not yet freed from JS.
+pub fn live_peer_clocks() -> usize {
+	LIVE_PEER_CLOCKS.with(|c| c.get())
+}
+
+fn ms(value: f64) -> u64 {
+	if value.is_finite() && value > 0.0 {
+		value as u64
+	} else {
+		0
+	}
+}
+
+#[wasm_bindgen]
+pub fn is_heartbeat_v1(bytes: &[u8]) -> bool {
+	keepalive::is_heartbeat(bytes)
+}
+
+#[wasm_bindgen]
+pub fn encode_heartbeat_ping_v1(now_ms: f64) -> Vec<u8> {
+	keepalive::encode_heartbeat_ping(ms(now_ms)).to_vec()
+}
+
+/// Answer a heartbeat ping. Pass `clock_sync` only if the peer's Hello
+/// advertised it (capability 16): the pong then echoes the ping's timestamp
+/// for clock estimation, which older peers can't read.
+#[wasm_bindgen]
+pub fn encode_heartbeat_pong_v1(ping_bytes: &[u8], now_ms: f64, clock_sync: bool) -> Result<Vec<u8>, JsValue> {
+	keepalive::encode_heartbeat_pong(ping_bytes, ms(now_ms), clock_sync)
+		.ok_or_else(|| crate::messages::p2p_error(crate::messages::P2pMessage::NotHeartbeat))
+}
+
+/// Per-connection clock offset estimate, fed with received pongs.
+#[wasm_bindgen]
+pub struct PeerClock {
+	inner: ClockOffsetEstimator,
+}
+
+impl Default for PeerClock {
+	fn default() -> Self {
+		LIVE_PEER_CLOCKS.with(|c| c.set(c.get() + 1));
+		PeerClock {
+			inner: ClockOffsetEstimator::new(),
+		}
+	}
+}
+
+impl Drop for PeerClock {
+	fn drop(&mut self) {
+		LIVE_PEER_CLOCKS.with(|c| c.set(c.get().saturating_sub(1)));
+	}
+}
+
+#[wasm_bindgen]
+impl PeerClock {
+	#[wasm_bindgen(constructor)]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Record a pong received at `now_ms`. Returns false for legacy pongs without a sample.
+	pub fn record_pong(&mut self, pong_bytes: &[u8], now_ms: f64) -> bool {
+		self.inner.record_pong(pong_bytes, ms(now_ms))
+	}
+
+	/// Peer clock minus local clock in ms, or undefined before the first sample.
+	pub fn estimated_peer_clock_offset_ms(&self) -> Option<f64> {
+		self.inner.offset_ms().map(|o| o as f64)
+	}
+
+	/// Round-trip time of the sample behind the estimate.
+	pub fn rtt_ms(&self) -> Option<f64> {
+		self.inner.rtt_ms().map(|r| r as f64)
+	}
+
+	/// Convert a peer timestamp to local time.
+	pub fn to_local_ms(&self, peer_ms: f64) -> f64 {
+		self.inner.to_local_ms(ms(peer_ms)) as f64
+	}
+
+	pub fn reset(&mut self) {
+		self.inner.reset();
+	}
+}
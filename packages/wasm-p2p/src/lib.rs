@@ -1,13 +1,105 @@
+mod broadcast;
+mod ephemeral;
+mod events;
+mod keepalive;
+mod merkle;
+mod messages;
+mod offload;
+mod outbound;
+mod rekey;
+mod sealed_offer;
+mod transfer_policy;
+mod tuning;
+mod webcrypto;
+
 use wasm_bindgen::prelude::*;
 
+use aes_gcm::Aes256Gcm;
 use chacha20poly1305::{aead::Aead, aead::KeyInit, XChaCha20Poly1305};
+use holi_p2p::frame::CipherSuite;
+use holi_rng::HoliRng;
 use rand::RngCore;
 
+use messages::{decode_error, p2p_error, payload_error, P2pMessage};
+
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOC: holi_wasm_memory::CountingAllocator = holi_wasm_memory::CountingAllocator;
+pub use broadcast::{multi_envelope_entry_v1, multi_envelope_recipients_v1, BroadcastGroup};
+pub use ephemeral::EphemeralChat;
+pub use events::{subscribe_p2p_events, unsubscribe_p2p_events};
+pub use holi_wasm_i18n::{get_locale, set_locale};
+pub use keepalive::{encode_heartbeat_ping_v1, encode_heartbeat_pong_v1, is_heartbeat_v1, PeerClock};
+pub use merkle::{verify_file_chunk, FileHasher, FileMerkleTree};
+pub use offload::{open_slot, seal_slot, CryptoOffload};
+pub use outbound::FrameScheduler;
+pub use rekey::EnvelopeSession;
+pub use sealed_offer::{
+	decode_file_offer_sealed_id_v1, decode_file_reveal_id_v1, encode_file_reveal_v1, open_file_offer_v1,
+	seal_file_offer_v1,
+};
+pub use transfer_policy::TransferLimiter;
+pub use tuning::{calibrate_transfer, TransferTuning};
+pub use webcrypto::{decrypt_envelope_webcrypto_v1, encrypt_envelope_webcrypto_v1};
+
 #[wasm_bindgen]
 pub fn encode_chat_text_v1(text: &str) -> Vec<u8> {
 	holi_p2p::frame::encode_chat_text_v1(text)
 }
 
+/// Canonical bytes to sign (Ed25519, with the sender's identity key) for
+/// `encode_attributed_chat_text_v1`.
+#[wasm_bindgen]
+pub fn chat_text_signing_bytes_v1(
+	text: &str,
+	sender: &[u8],
+	sequence: u64,
+	timestamp: u64,
+) -> Result<Vec<u8>, JsValue> {
+	let sender = sender.try_into().map_err(|_| p2p_error(P2pMessage::SenderKeyLength))?;
+	Ok(holi_p2p::frame::chat_text_signing_bytes(text, sender, sequence, timestamp))
+}
+
+/// Like `chat_text_signing_bytes_v1`, but also covering the recipient's
+/// public key and the channel (room or session id), so the signature doesn't
+/// verify if the message is replayed elsewhere. Use it only with peers whose
+/// Hello sets bit 128.
+#[wasm_bindgen]
+pub fn bound_chat_text_signing_bytes_v1(
+	text: &str,
+	sender: &[u8],
+	recipient: &[u8],
+	channel: &[u8],
+	sequence: u64,
+	timestamp: u64,
+) -> Result<Vec<u8>, JsValue> {
+	let sender = sender.try_into().map_err(|_| p2p_error(P2pMessage::SenderKeyLength))?;
+	let recipient = recipient.try_into().map_err(|_| p2p_error(P2pMessage::RecipientKeyLength))?;
+	Ok(holi_p2p::frame::bound_chat_text_signing_bytes(text, sender, recipient, channel, sequence, timestamp))
+}
+
+/// ChatText carrying the sender's public key, sequence, timestamp and the
+/// signature over `chat_text_signing_bytes_v1`, or if `bound` over
+/// `bound_chat_text_signing_bytes_v1`.
+#[wasm_bindgen]
+pub fn encode_attributed_chat_text_v1(
+	text: &str,
+	sender: &[u8],
+	sequence: u64,
+	timestamp: u64,
+	signature: &[u8],
+	bound: bool,
+) -> Result<Vec<u8>, JsValue> {
+	let attribution = holi_p2p::frame::ChatAttribution {
+		sender: sender.try_into().map_err(|_| p2p_error(P2pMessage::SenderKeyLength))?,
+		sequence,
+		timestamp,
+		signature: signature.try_into().map_err(|_| p2p_error(P2pMessage::SignatureLength))?,
+		bound,
+	};
+	Ok(holi_p2p::frame::encode_attributed_chat_text_v1(text, &attribution))
+}
+
 #[wasm_bindgen]
 pub fn encode_file_offer_v1(id: &str, filename: &str, mime_type: &str, size: u64) -> Vec<u8> {
 	holi_p2p::frame::encode_file_offer_v1(&holi_p2p::frame::FileOffer {
@@ -15,17 +107,78 @@ pub fn encode_file_offer_v1(id: &str, filename: &str, mime_type: &str, size: u64
 		filename: filename.to_string(),
 		mime_type: mime_type.to_string(),
 		size,
+		merkle: None,
+		thumbnail: None,
 	})
 }
 
+/// FileOffer carrying the file's Merkle root (see `FileHasher`).
+#[wasm_bindgen]
+pub fn encode_file_offer_merkle_v1(
+	id: &str,
+	filename: &str,
+	mime_type: &str,
+	size: u64,
+	leaf_size: u32,
+	merkle_root: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+	Ok(holi_p2p::frame::encode_file_offer_v1(&holi_p2p::frame::FileOffer {
+		id: id.to_string(),
+		filename: filename.to_string(),
+		mime_type: mime_type.to_string(),
+		size,
+		merkle: Some(holi_p2p::frame::MerkleRoot {
+			leaf_size,
+			root: merkle::parse_root(merkle_root)?,
+		}),
+		thumbnail: None,
+	}))
+}
+
 #[wasm_bindgen]
 pub fn encode_file_accept_v1(id: &str) -> Vec<u8> {
 	holi_p2p::frame::encode_file_accept_v1(id)
 }
 
+/// FileReject for transfer `id`. `code` is `"too-large"`,
+/// `"unsupported-type"`, `"user-declined"`, `"quota-exceeded"` or `"busy"`
+/// (omitted: unspecified); `detail` the limit behind it (see
+/// `decode_file_reject_v1`). `reason` is optional human text.
+#[wasm_bindgen]
+pub fn encode_file_reject_v1(
+	id: &str,
+	reason: &str,
+	code: Option<String>,
+	detail: Option<f64>,
+) -> Result<Vec<u8>, JsValue> {
+	let code = match code.as_deref() {
+		Some(name) => holi_p2p::frame::RejectCode::from_name(name)
+			.ok_or_else(|| p2p_error(P2pMessage::UnknownRejectCode))?,
+		None => holi_p2p::frame::RejectCode::Unspecified,
+	};
+	Ok(holi_p2p::frame::encode_file_reject_v1(&holi_p2p::frame::FileReject {
+		id: id.to_string(),
+		code,
+		detail: detail.filter(|d| d.is_finite() && *d > 0.0).map_or(0, |d| d as u64),
+		reason: reason.to_string(),
+	}))
+}
+
+/// Goodbye to send right before closing the channel on purpose. `reason`
+/// is `"user-left"`, `"app-closing"`, `"idle"`, `"protocol-violation"` or
+/// `"restarting"` (omitted: unspecified); `resume_ticket` is an optional
+/// opaque token for the peer to present when reconnecting.
 #[wasm_bindgen]
-pub fn encode_file_reject_v1(id: &str, reason: &str) -> Vec<u8> {
-	holi_p2p::frame::encode_file_reject_v1(id, reason)
+pub fn encode_goodbye_v1(reason: Option<String>, resume_ticket: Option<Vec<u8>>) -> Result<Vec<u8>, JsValue> {
+	let reason = match reason.as_deref() {
+		Some(name) => holi_p2p::frame::GoodbyeReason::from_name(name)
+			.ok_or_else(|| p2p_error(P2pMessage::UnknownGoodbyeReason))?,
+		None => holi_p2p::frame::GoodbyeReason::Unspecified,
+	};
+	Ok(holi_p2p::frame::encode_goodbye_v1(&holi_p2p::frame::Goodbye {
+		reason,
+		resume_ticket: resume_ticket.unwrap_or_default(),
+	}))
 }
 
 #[wasm_bindgen]
@@ -33,38 +186,208 @@ pub fn encode_file_chunk_v1(id: &str, chunk_index: u32, chunk_bytes: &[u8]) -> V
 	holi_p2p::frame::encode_file_chunk_v1(id, chunk_index, chunk_bytes)
 }
 
+/// FileChunk with its Merkle proof (concatenated 32-byte hashes from
+/// `FileMerkleTree.proof`). Only send it to peers whose Hello sets bit 32.
+#[wasm_bindgen]
+pub fn encode_file_chunk_with_proof_v1(
+	id: &str,
+	chunk_index: u32,
+	proof: &[u8],
+	chunk_bytes: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+	let proof = merkle::parse_proof(proof)?;
+	holi_p2p::frame::encode_file_chunk_with_proof_v1(id, chunk_index, &proof, chunk_bytes)
+		.map_err(|_| p2p_error(P2pMessage::ProofLength))
+}
+
+/// FileChunk with its data DEFLATE-compressed when that makes it smaller,
+/// and the Merkle proof unless `proof` is empty. Only send it to peers
+/// whose Hello sets bit 64 (plus 32 for a proof), for files
+/// `should_compress_file` accepts.
+#[wasm_bindgen]
+pub fn encode_file_chunk_compressed_v1(
+	id: &str,
+	chunk_index: u32,
+	proof: &[u8],
+	chunk_bytes: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+	let proof = merkle::parse_proof(proof)?;
+	let proof = (!proof.is_empty()).then_some(proof.as_slice());
+	holi_p2p::frame::encode_file_chunk_compressed_v1(id, chunk_index, proof, chunk_bytes)
+		.map_err(|_| p2p_error(P2pMessage::ProofLength))
+}
+
 #[wasm_bindgen]
 pub fn encode_file_end_v1(id: &str) -> Vec<u8> {
 	holi_p2p::frame::encode_file_end_v1(id)
 }
 
+/// Whether a file is worth compressing before sending: false for media,
+/// archives and high-entropy data. `sample` is the start of the file. Chunks
+/// of files that pass go out with `encode_file_chunk_compressed_v1`.
+#[wasm_bindgen]
+pub fn should_compress_file(mime_type: &str, sample: &[u8]) -> bool {
+	holi_p2p::compressibility::should_compress(mime_type, sample)
+}
+
+/// Filename safe to save a received file under.
+#[wasm_bindgen]
+pub fn sanitize_filename(filename: &str) -> String {
+	holi_p2p::sniff::sanitize_filename(filename)
+}
+
+/// Check a received file against its offer before handing it to the
+/// browser. `sample` is the start of the data. Returns `{ filename,
+/// detected, mismatch, active, suspicious, blobType }`: save under
+/// `filename` and create the Blob with `blobType`, which is
+/// `application/octet-stream` when `suspicious`.
+#[wasm_bindgen]
+pub fn check_received_file(filename: &str, mime_type: &str, sample: &[u8]) -> Result<JsValue, JsValue> {
+	let check = holi_p2p::sniff::check_content(filename, mime_type, sample);
+	let obj = js_sys::Object::new();
+	let set = |name: &str, value: JsValue| js_sys::Reflect::set(&obj, &JsValue::from_str(name), &value);
+	set("filename", JsValue::from_str(&holi_p2p::sniff::sanitize_filename(filename)))?;
+	set("detected", check.detected.map_or(JsValue::NULL, JsValue::from_str))?;
+	set("mismatch", JsValue::from_bool(check.mismatch))?;
+	set("active", JsValue::from_bool(check.active))?;
+	set("suspicious", JsValue::from_bool(check.is_suspicious()))?;
+	set("blobType", JsValue::from_str(check.blob_type(mime_type)))?;
+	Ok(obj.into())
+}
+
+/// Memory usage of this module: `{ linearMemoryBytes, allocator, caches }`.
+/// `allocator` is null unless built with the `alloc-stats` feature.
+#[wasm_bindgen]
+pub fn p2p_memory_stats() -> JsValue {
+	holi_wasm_memory::MemoryStats::collect()
+		.with_cache("eventListeners", events::listener_count())
+		.with_cache("peerClocks", keepalive::live_peer_clocks())
+		.to_js()
+}
+
+/// Which build of this module is running:
+/// `{ package, version, gitHash, features, protocols }`.
+#[wasm_bindgen]
+pub fn p2p_build_info() -> JsValue {
+	holi_wasm_build_info::BuildInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+		.with_feature("alloc-stats", cfg!(feature = "alloc-stats"))
+		.with_feature("test-rng", cfg!(feature = "test-rng"))
+		.with_protocol("frame", holi_p2p::frame::VERSION_V1 as u32)
+		.to_js()
+}
+
+/// JSON counters of frames encoded and decoded since load (or the last
+/// `reset_metrics`): `{ encoded, decoded, decodeErrors }`, where `encoded`
+/// and `decoded` list `{ type, name, frames, bytes }` per frame type seen
+/// and `decodeErrors` maps error names like `"bad-magic"` to counts.
+#[wasm_bindgen]
+pub fn metrics_snapshot() -> String {
+	holi_p2p::metrics::Metrics::global().snapshot().to_json()
+}
+
+/// Zero the counters behind `metrics_snapshot`.
+#[wasm_bindgen]
+pub fn reset_metrics() {
+	holi_p2p::metrics::Metrics::global().reset();
+}
+
+/// JSON description of the frame format, for TS parser/doc codegen.
+#[wasm_bindgen]
+pub fn frame_schema_json() -> String {
+	holi_p2p::schema::schema_json()
+}
+
+/// Type byte of the first frame in `bytes`. Only peeks: the frame counts in
+/// `metrics_snapshot` once, when its typed decoder reads it.
 #[wasm_bindgen]
 pub fn decode_frame_type_v1(bytes: &[u8]) -> Result<u8, JsValue> {
-	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
-		.map_err(|e| JsValue::from_str(&format!("decode error: {e:?}")))?;
+	let (frame, _used) = holi_p2p::frame::peek_v1_ref(bytes, 1024 * 1024)
+		.map_err(decode_error)?;
 	Ok(frame.frame_type as u8)
 }
 
+/// `{ frameType, flags, payloadStart, payloadEnd, used }` for the first
+/// frame in `bytes`. Take `bytes.subarray(payloadStart, payloadEnd)` for the
+/// payload instead of copying it out.
 #[wasm_bindgen]
-pub fn decode_chat_text_payload_v1(bytes: &[u8]) -> Result<String, JsValue> {
+pub fn decode_frame_ref_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
+	let (frame, used) = holi_p2p::frame::decode_v1_ref(bytes, 1024 * 1024)
+		.map_err(decode_error)?;
+	let obj = js_sys::Object::new();
+	let fields = [
+		("frameType", frame.frame_type as u8 as f64),
+		("flags", frame.flags as f64),
+		("payloadStart", frame.payload_range.start as f64),
+		("payloadEnd", frame.payload_range.end as f64),
+		("used", used as f64),
+	];
+	for (name, value) in fields {
+		js_sys::Reflect::set(&obj, &JsValue::from_str(name), &JsValue::from_f64(value))?;
+	}
+	Ok(obj.into())
+}
+
+fn decode_chat_text(bytes: &[u8]) -> Result<holi_p2p::frame::ChatText, JsValue> {
 	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
-		.map_err(|e| JsValue::from_str(&format!("decode error: {e:?}")))?;
+		.map_err(decode_error)?;
 	if frame.frame_type != holi_p2p::frame::FrameType::ChatText {
-		return Err(JsValue::from_str("not ChatText"));
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("ChatText")));
+	}
+	holi_p2p::frame::decode_chat_text_frame_v1(&frame).map_err(|e| match e {
+		holi_p2p::frame::DecodeError::InvalidUtf8 => {
+			holi_p2p::metrics::Metrics::global().record_decode_error(&e);
+			p2p_error(P2pMessage::PayloadNotUtf8)
+		}
+		e => payload_error(e),
+	})
+}
+
+/// Text of a ChatText frame, attributed or not.
+#[wasm_bindgen]
+pub fn decode_chat_text_payload_v1(bytes: &[u8]) -> Result<String, JsValue> {
+	Ok(decode_chat_text(bytes)?.text)
+}
+
+/// `{ text }`, plus `sender`, `sequence`, `timestamp` and `signature` when
+/// the message is attributed. The signature is not checked here.
+#[wasm_bindgen]
+pub fn decode_chat_text_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
+	let chat = decode_chat_text(bytes)?;
+	let obj = js_sys::Object::new();
+	js_sys::Reflect::set(&obj, &JsValue::from_str("text"), &JsValue::from_str(&chat.text))?;
+	if let Some(a) = &chat.attribution {
+		let sender = js_sys::Uint8Array::from(a.sender.as_slice());
+		js_sys::Reflect::set(&obj, &JsValue::from_str("sender"), &sender.into())?;
+		// Counters and ms timestamps stay below 2^53.
+		js_sys::Reflect::set(
+			&obj,
+			&JsValue::from_str("sequence"),
+			&JsValue::from_f64(a.sequence as f64),
+		)?;
+		js_sys::Reflect::set(
+			&obj,
+			&JsValue::from_str("timestamp"),
+			&JsValue::from_f64(a.timestamp as f64),
+		)?;
+		let signature = js_sys::Uint8Array::from(a.signature.as_slice());
+		js_sys::Reflect::set(&obj, &JsValue::from_str("signature"), &signature.into())?;
 	}
-	String::from_utf8(frame.payload).map_err(|_| JsValue::from_str("payload not utf-8"))
+	Ok(obj.into())
 }
 
 #[wasm_bindgen]
 pub fn decode_file_offer_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
 	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
-		.map_err(|e| JsValue::from_str(&format!("decode error: {e:?}")))?;
+		.map_err(decode_error)?;
 	if frame.frame_type != holi_p2p::frame::FrameType::FileOffer {
-		return Err(JsValue::from_str("not FileOffer"));
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("FileOffer")));
 	}
 	let offer = holi_p2p::frame::decode_file_offer_payload_v1(&frame.payload)
-		.map_err(|e| JsValue::from_str(&format!("decode payload error: {e:?}")))?;
+		.map_err(payload_error)?;
+	offer_to_js(&offer)
+}
 
+fn offer_to_js(offer: &holi_p2p::frame::FileOffer) -> Result<JsValue, JsValue> {
 	let obj = js_sys::Object::new();
 	js_sys::Reflect::set(&obj, &JsValue::from_str("id"), &JsValue::from_str(&offer.id))?;
 	js_sys::Reflect::set(
@@ -80,29 +403,46 @@ pub fn decode_file_offer_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
 	// JS can't represent all u64 exactly; we assume file sizes are < 2^53.
 	let size_f64 = offer.size as f64;
 	js_sys::Reflect::set(&obj, &JsValue::from_str("size"), &JsValue::from_f64(size_f64))?;
+	if let Some(merkle) = &offer.merkle {
+		js_sys::Reflect::set(
+			&obj,
+			&JsValue::from_str("leafSize"),
+			&JsValue::from_f64(merkle.leaf_size as f64),
+		)?;
+		let root = js_sys::Uint8Array::from(merkle.root.as_slice());
+		js_sys::Reflect::set(&obj, &JsValue::from_str("merkleRoot"), &root.into())?;
+	}
+	// Opened with the session key on the JS side (`open_offer_thumbnail`)
+	if offer.thumbnail.is_some() {
+		js_sys::Reflect::set(&obj, &JsValue::from_str("hasThumbnail"), &JsValue::TRUE)?;
+	}
 	Ok(obj.into())
 }
 
 #[wasm_bindgen]
 pub fn decode_file_accept_id_v1(bytes: &[u8]) -> Result<String, JsValue> {
 	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
-		.map_err(|e| JsValue::from_str(&format!("decode error: {e:?}")))?;
+		.map_err(decode_error)?;
 	if frame.frame_type != holi_p2p::frame::FrameType::FileAccept {
-		return Err(JsValue::from_str("not FileAccept"));
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("FileAccept")));
 	}
 	holi_p2p::frame::decode_file_accept_payload_v1(&frame.payload)
-		.map_err(|e| JsValue::from_str(&format!("decode payload error: {e:?}")))
+		.map_err(payload_error)
 }
 
+/// `{ id, reason, code, detail }`. `code` is one of the `encode_file_reject_v1`
+/// names or `"unspecified"`; `detail` is the size limit in bytes for
+/// too-large, ms until the quota resets for quota-exceeded, the transfer
+/// limit for busy, else 0.
 #[wasm_bindgen]
 pub fn decode_file_reject_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
 	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
-		.map_err(|e| JsValue::from_str(&format!("decode error: {e:?}")))?;
+		.map_err(decode_error)?;
 	if frame.frame_type != holi_p2p::frame::FrameType::FileReject {
-		return Err(JsValue::from_str("not FileReject"));
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("FileReject")));
 	}
 	let rej = holi_p2p::frame::decode_file_reject_payload_v1(&frame.payload)
-		.map_err(|e| JsValue::from_str(&format!("decode payload error: {e:?}")))?;
+		.map_err(payload_error)?;
 
 	let obj = js_sys::Object::new();
 	js_sys::Reflect::set(&obj, &JsValue::from_str("id"), &JsValue::from_str(&rej.id))?;
@@ -111,12 +451,41 @@ pub fn decode_file_reject_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
 		&JsValue::from_str("reason"),
 		&JsValue::from_str(&rej.reason),
 	)?;
+	js_sys::Reflect::set(&obj, &JsValue::from_str("code"), &JsValue::from_str(rej.code.name()))?;
+	js_sys::Reflect::set(&obj, &JsValue::from_str("detail"), &JsValue::from_f64(rej.detail as f64))?;
+	Ok(obj.into())
+}
+
+/// `{ reason, resumeTicket, reconnect }`. `reason` is one of the
+/// `encode_goodbye_v1` names or `"unspecified"`; `resumeTicket` a
+/// `Uint8Array`, or null if none; `reconnect` whether to reconnect once the
+/// channel closes (only when the peer is restarting).
+#[wasm_bindgen]
+pub fn decode_goodbye_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
+	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
+		.map_err(decode_error)?;
+	if frame.frame_type != holi_p2p::frame::FrameType::Goodbye {
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("Goodbye")));
+	}
+	let goodbye = holi_p2p::frame::decode_goodbye_payload_v1(&frame.payload)
+		.map_err(payload_error)?;
+
+	let obj = js_sys::Object::new();
+	js_sys::Reflect::set(&obj, &JsValue::from_str("reason"), &JsValue::from_str(goodbye.reason.name()))?;
+	let ticket = if goodbye.resume_ticket.is_empty() {
+		JsValue::NULL
+	} else {
+		js_sys::Uint8Array::from(goodbye.resume_ticket.as_slice()).into()
+	};
+	js_sys::Reflect::set(&obj, &JsValue::from_str("resumeTicket"), &ticket)?;
+	let end = holi_p2p::session::SessionEnd::PeerLeft(goodbye);
+	js_sys::Reflect::set(&obj, &JsValue::from_str("reconnect"), &JsValue::from_bool(end.should_reconnect()))?;
 	Ok(obj.into())
 }
 
 fn parse_key_32(key_bytes: &[u8]) -> Result<[u8; 32], JsValue> {
 	if key_bytes.len() != 32 {
-		return Err(JsValue::from_str("key must be 32 bytes"));
+		return Err(p2p_error(P2pMessage::KeyLength));
 	}
 	let mut key = [0u8; 32];
 	key.copy_from_slice(key_bytes);
@@ -125,7 +494,7 @@ fn parse_key_32(key_bytes: &[u8]) -> Result<[u8; 32], JsValue> {
 
 fn parse_nonce_24(nonce_bytes: &[u8]) -> Result<[u8; holi_p2p::frame::ENVELOPE_NONCE_LEN], JsValue> {
 	if nonce_bytes.len() != holi_p2p::frame::ENVELOPE_NONCE_LEN {
-		return Err(JsValue::from_str("nonce must be 24 bytes"));
+		return Err(p2p_error(P2pMessage::NonceLength));
 	}
 	let mut nonce = [0u8; holi_p2p::frame::ENVELOPE_NONCE_LEN];
 	nonce.copy_from_slice(nonce_bytes);
@@ -138,11 +507,11 @@ pub fn encrypt_envelope_v1(key_bytes: &[u8], inner_frame_bytes: &[u8]) -> Result
 	let cipher = XChaCha20Poly1305::new((&key).into());
 
 	let mut nonce = [0u8; holi_p2p::frame::ENVELOPE_NONCE_LEN];
-	rand::rngs::OsRng.fill_bytes(&mut nonce);
+	HoliRng.fill_bytes(&mut nonce);
 
 	let ct = cipher
 		.encrypt((&nonce).into(), inner_frame_bytes)
-		.map_err(|_| JsValue::from_str("encrypt failed"))?;
+		.map_err(|_| p2p_error(P2pMessage::EncryptFailed))?;
 
 	Ok(holi_p2p::frame::encode_encrypted_envelope_v1(&nonce, &ct))
 }
@@ -158,38 +527,148 @@ pub fn encrypt_envelope_v1_with_nonce(
 	let cipher = XChaCha20Poly1305::new((&key).into());
 	let ct = cipher
 		.encrypt((&nonce).into(), inner_frame_bytes)
-		.map_err(|_| JsValue::from_str("encrypt failed"))?;
+		.map_err(|_| p2p_error(P2pMessage::EncryptFailed))?;
 	Ok(holi_p2p::frame::encode_encrypted_envelope_v1(&nonce, &ct))
 }
 
+/// Open an EncryptedEnvelope of any cipher suite. The plaintext is returned
+/// whole; only `EnvelopeSession.open` strips length padding.
 #[wasm_bindgen]
 pub fn decrypt_envelope_v1(key_bytes: &[u8], envelope_frame_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
 	let key = parse_key_32(key_bytes)?;
-	let cipher = XChaCha20Poly1305::new((&key).into());
+	open_envelope(&key, &decode_envelope(envelope_frame_bytes)?)
+}
+
+fn open_envelope(key: &[u8; 32], envelope: &holi_p2p::frame::EncryptedEnvelope) -> Result<Vec<u8>, JsValue> {
+	let pt = match envelope.suite {
+		CipherSuite::XChaCha20Poly1305 => XChaCha20Poly1305::new(key.into())
+			.decrypt(envelope.nonce.as_slice().into(), envelope.ciphertext.as_slice()),
+		CipherSuite::Aes256Gcm => Aes256Gcm::new(key.into())
+			.decrypt(envelope.nonce.as_slice().into(), envelope.ciphertext.as_slice()),
+	};
+	pt.map_err(|_| p2p_error(P2pMessage::DecryptFailed))
+}
 
-	let (frame, _used) = holi_p2p::frame::decode_v1(envelope_frame_bytes, 1024 * 1024)
-		.map_err(|e| JsValue::from_str(&format!("decode error: {e:?}")))?;
+fn decode_envelope(bytes: &[u8]) -> Result<holi_p2p::frame::EncryptedEnvelope, JsValue> {
+	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
+		.map_err(decode_error)?;
 	if frame.frame_type != holi_p2p::frame::FrameType::EncryptedEnvelope {
-		return Err(JsValue::from_str("not EncryptedEnvelope"));
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("EncryptedEnvelope")));
 	}
-	let (nonce, ciphertext) = holi_p2p::frame::decode_encrypted_envelope_payload_v1(&frame.payload)
-		.map_err(|e| JsValue::from_str(&format!("decode payload error: {e:?}")))?;
+	holi_p2p::frame::decode_encrypted_envelope_frame_v1(&frame).map_err(payload_error)
+}
+
+fn parse_cipher_suite(suite: u8) -> Result<CipherSuite, JsValue> {
+	CipherSuite::from_u8(suite).ok_or_else(|| p2p_error(P2pMessage::UnknownCipherSuite))
+}
+
+/// Seal with `suite` (1 = XChaCha20-Poly1305, 2 = AES-256-GCM), as chosen
+/// by `negotiate_cipher_suite_v1`. For AES on devices with hardware support,
+/// `encrypt_envelope_webcrypto_v1` is usually faster.
+#[wasm_bindgen]
+pub fn encrypt_envelope_with_suite_v1(
+	suite: u8,
+	key_bytes: &[u8],
+	inner_frame_bytes: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+	seal_envelope(parse_cipher_suite(suite)?, &parse_key_32(key_bytes)?, inner_frame_bytes)
+}
 
-	let pt = cipher
-		.decrypt((&nonce).into(), ciphertext.as_slice())
-		.map_err(|_| JsValue::from_str("decrypt failed"))?;
-	Ok(pt)
+fn seal_envelope(suite: CipherSuite, key: &[u8; 32], inner_frame_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+	let mut nonce = vec![0u8; suite.nonce_len()];
+	HoliRng.fill_bytes(&mut nonce);
+	let ct = match suite {
+		CipherSuite::XChaCha20Poly1305 => XChaCha20Poly1305::new(key.into())
+			.encrypt(nonce.as_slice().into(), inner_frame_bytes),
+		CipherSuite::Aes256Gcm => Aes256Gcm::new(key.into())
+			.encrypt(nonce.as_slice().into(), inner_frame_bytes),
+	}
+	.map_err(|_| p2p_error(P2pMessage::EncryptFailed))?;
+	holi_p2p::frame::encode_encrypted_envelope_with_suite_v1(suite, &nonce, &ct)
+		.map_err(|_| p2p_error(P2pMessage::NonceLength))
+}
+
+/// Hello frame advertising `capabilities`: bit 1 = prefers AES-256-GCM
+/// (set it only where AES is hardware-accelerated), 2 = reads offer
+/// thumbnails, 4 = receives animated QR codes, 8 = decodes fountain-coded
+/// QR frames, 16 = reads clock-sync heartbeat pongs, 32 = reads Merkle proofs
+/// in FileChunk frames, 64 = inflates compressed FileChunk data, 128 =
+/// checks ChatText signatures bound to recipient and channel.
+/// `max_chunk_size` is the largest FileChunk data we accept; 0 is sent as
+/// no limit, since peers reject it.
+#[wasm_bindgen]
+pub fn encode_hello_v1(capabilities: u32, max_chunk_size: Option<u32>) -> Vec<u8> {
+	holi_p2p::frame::encode_hello_v1(&holi_p2p::frame::Hello {
+		capabilities,
+		max_chunk_size: max_chunk_size.filter(|&n| n > 0),
+	})
+}
+
+fn decode_hello(bytes: &[u8]) -> Result<holi_p2p::frame::Hello, JsValue> {
+	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
+		.map_err(decode_error)?;
+	if frame.frame_type != holi_p2p::frame::FrameType::Hello {
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("Hello")));
+	}
+	holi_p2p::frame::decode_hello_payload_v1(&frame.payload).map_err(payload_error)
+}
+
+fn hello_to_js(hello: &holi_p2p::frame::Hello) -> Result<JsValue, JsValue> {
+	let obj = js_sys::Object::new();
+	js_sys::Reflect::set(
+		&obj,
+		&JsValue::from_str("capabilities"),
+		&JsValue::from_f64(hello.capabilities as f64),
+	)?;
+	let max_chunk_size = hello.max_chunk_size.map_or(JsValue::NULL, |n| JsValue::from_f64(n as f64));
+	js_sys::Reflect::set(&obj, &JsValue::from_str("maxChunkSize"), &max_chunk_size)?;
+	Ok(obj.into())
+}
+
+#[wasm_bindgen]
+pub fn decode_hello_capabilities_v1(bytes: &[u8]) -> Result<u32, JsValue> {
+	Ok(decode_hello(bytes)?.capabilities)
+}
+
+/// `{ capabilities, maxChunkSize }` of a Hello; `maxChunkSize` is null if
+/// the peer states no limit.
+#[wasm_bindgen]
+pub fn decode_hello_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
+	hello_to_js(&decode_hello(bytes)?)
+}
+
+/// What this session may use, from our Hello fields and the peer's Hello
+/// frame: `{ capabilities, maxChunkSize }` with the capabilities both sides
+/// advertise and the smaller chunk limit. Don't send anything outside it.
+#[wasm_bindgen]
+pub fn negotiate_hello_v1(
+	local_capabilities: u32,
+	local_max_chunk_size: Option<u32>,
+	remote_hello_bytes: &[u8],
+) -> Result<JsValue, JsValue> {
+	let local = holi_p2p::frame::Hello {
+		capabilities: local_capabilities,
+		max_chunk_size: local_max_chunk_size.filter(|&n| n > 0),
+	};
+	hello_to_js(&local.intersect(&decode_hello(remote_hello_bytes)?))
+}
+
+/// Cipher suite byte for `encrypt_envelope_with_suite_v1`, from our Hello
+/// capabilities and the peer's.
+#[wasm_bindgen]
+pub fn negotiate_cipher_suite_v1(local_capabilities: u32, remote_capabilities: u32) -> u8 {
+	CipherSuite::negotiate(local_capabilities, remote_capabilities) as u8
 }
 
 #[wasm_bindgen]
 pub fn decode_file_chunk_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
 	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
-		.map_err(|e| JsValue::from_str(&format!("decode error: {e:?}")))?;
+		.map_err(decode_error)?;
 	if frame.frame_type != holi_p2p::frame::FrameType::FileChunk {
-		return Err(JsValue::from_str("not FileChunk"));
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("FileChunk")));
 	}
-	let chunk = holi_p2p::frame::decode_file_chunk_payload_v1(&frame.payload)
-		.map_err(|e| JsValue::from_str(&format!("decode payload error: {e:?}")))?;
+	let chunk = holi_p2p::frame::decode_file_chunk_frame_v1(&frame)
+		.map_err(payload_error)?;
 
 	let obj = js_sys::Object::new();
 	js_sys::Reflect::set(&obj, &JsValue::from_str("id"), &JsValue::from_str(&chunk.id))?;
@@ -200,16 +679,89 @@ pub fn decode_file_chunk_v1(bytes: &[u8]) -> Result<JsValue, JsValue> {
 	)?;
 	let data = js_sys::Uint8Array::from(chunk.data.as_slice());
 	js_sys::Reflect::set(&obj, &JsValue::from_str("data"), &data.into())?;
+	if !chunk.proof.is_empty() {
+		let proof = js_sys::Uint8Array::from(chunk.proof.concat().as_slice());
+		js_sys::Reflect::set(&obj, &JsValue::from_str("proof"), &proof.into())?;
+	}
 	Ok(obj.into())
 }
 
 #[wasm_bindgen]
 pub fn decode_file_end_id_v1(bytes: &[u8]) -> Result<String, JsValue> {
 	let (frame, _used) = holi_p2p::frame::decode_v1(bytes, 1024 * 1024)
-		.map_err(|e| JsValue::from_str(&format!("decode error: {e:?}")))?;
+		.map_err(decode_error)?;
 	if frame.frame_type != holi_p2p::frame::FrameType::FileEnd {
-		return Err(JsValue::from_str("not FileEnd"));
+		return Err(p2p_error(P2pMessage::UnexpectedFrame("FileEnd")));
+	}
+	let id = holi_p2p::frame::decode_file_end_payload_v1(&frame.payload)
+		.map_err(payload_error)?;
+	Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn envelope_suites_roundtrip() {
+		let key = [3u8; 32];
+		let inner = encode_chat_text_v1("hola");
+		let aes = CipherSuite::negotiate(
+			holi_p2p::frame::HELLO_CAP_AES_256_GCM,
+			holi_p2p::frame::HELLO_CAP_AES_256_GCM,
+		);
+		for suite in [CipherSuite::XChaCha20Poly1305, aes] {
+			let sealed = encrypt_envelope_with_suite_v1(suite as u8, &key, &inner).unwrap();
+			assert_eq!(decode_envelope(&sealed).unwrap().suite, suite);
+			assert_eq!(decrypt_envelope_v1(&key, &sealed).unwrap(), inner);
+		}
+		assert_eq!(aes, CipherSuite::Aes256Gcm);
+	}
+
+	#[test]
+	fn decrypt_keeps_bytes_after_the_frame() {
+		let key = [3u8; 32];
+		let mut plaintext = encode_chat_text_v1("hola");
+		plaintext.extend_from_slice(b"trailing");
+		let sealed = encrypt_envelope_v1(&key, &plaintext).unwrap();
+		assert_eq!(decrypt_envelope_v1(&key, &sealed).unwrap(), plaintext);
+
+		let mut session = rekey::EnvelopeSession::new(&key, CipherSuite::XChaCha20Poly1305 as u8).unwrap();
+		session.use_default_padding();
+		let padded = session.seal(&encode_chat_text_v1("hola")).unwrap();
+		assert_eq!(decrypt_envelope_v1(&key, &padded).unwrap().len(), 64);
+		assert_eq!(session.open(&padded).unwrap(), encode_chat_text_v1("hola"));
+	}
+
+	#[test]
+	fn peeking_the_type_does_not_count_the_frame() {
+		use holi_p2p::frame::FrameType;
+		use holi_p2p::metrics::Metrics;
+
+		// No other test here decodes FileEnd frames
+		let decoded = || {
+			let snapshot = Metrics::global().snapshot();
+			snapshot.decoded.iter().find(|c| c.frame_type == FrameType::FileEnd).map_or(0, |c| c.frames)
+		};
+		let bytes = encode_file_end_v1("peek");
+		let before = decoded();
+		assert_eq!(decode_frame_type_v1(&bytes).unwrap(), FrameType::FileEnd as u8);
+		holi_p2p::frame::decode_v1(&bytes, 1024).unwrap();
+		assert_eq!(decoded(), before + 1);
+	}
+	#[test]
+	fn seeded_rng_gives_the_same_envelope() {
+		let key = [3u8; 32];
+		let inner = encode_chat_text_v1("hola");
+		holi_rng::seed_test_rng([9u8; 32]);
+		let first = encrypt_envelope_v1(&key, &inner).unwrap();
+		holi_rng::seed_test_rng([9u8; 32]);
+		let second = encrypt_envelope_v1(&key, &inner).unwrap();
+		holi_rng::reset_test_rng();
+		assert_eq!(first, second);
+		assert_eq!(decrypt_envelope_v1(&key, &first).unwrap(), inner);
+
+		let unseeded = encrypt_envelope_v1(&key, &inner).unwrap();
+		assert_ne!(unseeded, first);
 	}
-	holi_p2p::frame::decode_file_end_payload_v1(&frame.payload)
-		.map_err(|e| JsValue::from_str(&format!("decode payload error: {e:?}")))
 }
@@ -0,0 +1,104 @@
+//! Merkle hashing of outgoing files and per-chunk verification of incoming ones.
+//!
+//! The sender hashes the file with `FileHasher` (leaf size = chunk size), puts
+//! the root in the FileOffer and a proof in every FileChunk; the receiver
+//! checks each chunk with `verify_file_chunk` as it arrives.
+
+use wasm_bindgen::prelude::*;
+
+use holi_p2p::merkle::{self, MerkleBuilder, MerkleHash, MerkleTree, MAX_PROOF_LEN, MERKLE_HASH_LEN};
+
+use crate::messages::{p2p_error, P2pMessage};
+
+pub(crate) fn parse_root(bytes: &[u8]) -> Result<MerkleHash, JsValue> {
+	MerkleHash::try_from(bytes).map_err(|_| p2p_error(P2pMessage::MerkleRootLength))
+}
+
+/// Split concatenated 32-byte hashes into a proof.
+pub(crate) fn parse_proof(bytes: &[u8]) -> Result<Vec<MerkleHash>, JsValue> {
+	if !bytes.len().is_multiple_of(MERKLE_HASH_LEN) || bytes.len() / MERKLE_HASH_LEN > MAX_PROOF_LEN {
+		return Err(p2p_error(P2pMessage::ProofLength));
+	}
+	Ok(bytes
+		.chunks_exact(MERKLE_HASH_LEN)
+		.map(|h| MerkleHash::try_from(h).unwrap())
+		.collect())
+}
+
+/// Streaming Merkle hasher for a file being offered.
+#[wasm_bindgen]
+pub struct FileHasher {
+	builder: MerkleBuilder,
+}
+
+#[wasm_bindgen]
+impl FileHasher {
+	#[wasm_bindgen(constructor)]
+	pub fn new(leaf_size: u32) -> FileHasher {
+		FileHasher {
+			builder: MerkleBuilder::new(leaf_size),
+		}
+	}
+
+	/// Feed the next bytes of the file (any slicing).
+	pub fn update(&mut self, data: &[u8]) {
+		self.builder.update(data);
+	}
+
+	pub fn finish(self) -> FileMerkleTree {
+		FileMerkleTree {
+			tree: self.builder.finish(),
+		}
+	}
+}
+
+/// Merkle tree of an outgoing file; hands out per-chunk proofs.
+#[wasm_bindgen]
+pub struct FileMerkleTree {
+	tree: MerkleTree,
+}
+
+#[wasm_bindgen]
+impl FileMerkleTree {
+	/// 32-byte root for the FileOffer.
+	pub fn root(&self) -> Vec<u8> {
+		self.tree.root().to_vec()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn leaf_size(&self) -> u32 {
+		self.tree.leaf_size()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn leaf_count(&self) -> u32 {
+		self.tree.leaf_count() as u32
+	}
+
+	/// Concatenated sibling hashes for chunk `index`, for `encode_file_chunk_with_proof_v1`.
+	pub fn proof(&self, index: u32) -> Option<Vec<u8>> {
+		self.tree.proof(index as usize).map(|p| p.concat())
+	}
+}
+
+/// Check a received chunk against the root from its FileOffer.
+/// `size` and `leaf_size` are the offer's; `proof` is the chunk's.
+#[wasm_bindgen]
+pub fn verify_file_chunk(
+	root: &[u8],
+	size: u64,
+	leaf_size: u32,
+	chunk_index: u32,
+	data: &[u8],
+	proof: &[u8],
+) -> Result<bool, JsValue> {
+	let root = parse_root(root)?;
+	let proof = parse_proof(proof)?;
+	Ok(merkle::verify_leaf(
+		&root,
+		merkle::leaf_count(size, leaf_size),
+		u64::from(chunk_index),
+		data,
+		&proof,
+	))
+}
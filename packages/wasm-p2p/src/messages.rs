@@ -0,0 +1,139 @@
+//! User-facing errors (en/es).
+
+use holi_wasm_i18n::{Locale, Message};
+use wasm_bindgen::JsValue;
+
+#[derive(Debug)]
+pub enum P2pMessage {
+	/// Frame or payload could not be decoded; carries the decoder's detail.
+	Decode(String),
+	/// Frame decoded but is not the expected type.
+	UnexpectedFrame(&'static str),
+	PayloadNotUtf8,
+	KeyLength,
+	NonceLength,
+	EncryptFailed,
+	DecryptFailed,
+	NotHeartbeat,
+	MerkleRootLength,
+	ProofLength,
+	SlotTooSmall,
+	UnknownPriority,
+	UnknownFrameType,
+	OfferIdMismatch,
+	SenderKeyLength,
+	RecipientKeyLength,
+	SignatureLength,
+	UnknownCipherSuite,
+	WebCryptoUnavailable,
+	RekeyRejected,
+	TooManyRecipients,
+	UnknownPeer,
+	NotARecipient,
+	UnknownRejectCode,
+	UnknownGoodbyeReason,
+	EphemeralBurned,
+	EphemeralKeyGone,
+	EphemeralTooFarAhead,
+}
+
+impl Message for P2pMessage {
+	fn text(&self, locale: Locale) -> String {
+		use P2pMessage::*;
+		match (self, locale) {
+			(Decode(d), Locale::En) => format!("Could not read the message: {d}"),
+			(Decode(d), Locale::Es) => format!("No se pudo leer el mensaje: {d}"),
+			(UnexpectedFrame(expected), Locale::En) => format!("Unexpected message type (expected {expected})"),
+			(UnexpectedFrame(expected), Locale::Es) => format!("Tipo de mensaje inesperado (se esperaba {expected})"),
+			(PayloadNotUtf8, Locale::En) => "The message text is not valid UTF-8".into(),
+			(PayloadNotUtf8, Locale::Es) => "El texto del mensaje no es UTF-8 válido".into(),
+			(KeyLength, Locale::En) => "The encryption key must be 32 bytes".into(),
+			(KeyLength, Locale::Es) => "La clave de cifrado debe tener 32 bytes".into(),
+			(NonceLength, Locale::En) => "The nonce must be 24 bytes".into(),
+			(NonceLength, Locale::Es) => "El nonce debe tener 24 bytes".into(),
+			(EncryptFailed, Locale::En) => "Could not encrypt the message".into(),
+			(EncryptFailed, Locale::Es) => "No se pudo cifrar el mensaje".into(),
+			(DecryptFailed, Locale::En) => {
+				"Could not decrypt the message. The key may be wrong or the data was altered".into()
+			}
+			(DecryptFailed, Locale::Es) => {
+				"No se pudo descifrar el mensaje. La clave puede ser incorrecta o los datos fueron alterados".into()
+			}
+			(NotHeartbeat, Locale::En) => "Not a heartbeat ping".into(),
+			(NotHeartbeat, Locale::Es) => "No es un ping de heartbeat".into(),
+			(MerkleRootLength, Locale::En) => "The Merkle root must be 32 bytes".into(),
+			(MerkleRootLength, Locale::Es) => "La raíz Merkle debe tener 32 bytes".into(),
+			(ProofLength, Locale::En) => "The Merkle proof must be up to 32 hashes of 32 bytes".into(),
+			(ProofLength, Locale::Es) => "La prueba Merkle debe tener hasta 32 hashes de 32 bytes".into(),
+			(SlotTooSmall, Locale::En) => "The frame does not fit in the worker slot".into(),
+			(SlotTooSmall, Locale::Es) => "La trama no cabe en el espacio del worker".into(),
+			(UnknownPriority, Locale::En) => "Priority must be 0 (control), 1 (chat) or 2 (bulk)".into(),
+			(UnknownPriority, Locale::Es) => "La prioridad debe ser 0 (control), 1 (chat) o 2 (masivo)".into(),
+			(UnknownFrameType, Locale::En) => "Cannot classify the frame: unknown frame type".into(),
+			(UnknownFrameType, Locale::Es) => "No se puede clasificar la trama: tipo de trama desconocido".into(),
+			(OfferIdMismatch, Locale::En) => "The reveal does not belong to this file offer".into(),
+			(OfferIdMismatch, Locale::Es) => "La revelación no corresponde a esta oferta de archivo".into(),
+			(SenderKeyLength, Locale::En) => "The sender public key must be 32 bytes".into(),
+			(SenderKeyLength, Locale::Es) => "La clave pública del remitente debe tener 32 bytes".into(),
+			(RecipientKeyLength, Locale::En) => "The recipient public key must be 32 bytes".into(),
+			(RecipientKeyLength, Locale::Es) => "La clave pública del destinatario debe tener 32 bytes".into(),
+			(SignatureLength, Locale::En) => "The signature must be 64 bytes".into(),
+			(SignatureLength, Locale::Es) => "La firma debe tener 64 bytes".into(),
+			(UnknownCipherSuite, Locale::En) => "Unknown cipher suite".into(),
+			(UnknownCipherSuite, Locale::Es) => "Conjunto de cifrado desconocido".into(),
+			(WebCryptoUnavailable, Locale::En) => "WebCrypto is not available in this environment".into(),
+			(WebCryptoUnavailable, Locale::Es) => "WebCrypto no está disponible en este entorno".into(),
+			(RekeyRejected, Locale::En) => "The key rotation message does not match this session".into(),
+			(RekeyRejected, Locale::Es) => "El mensaje de rotación de clave no corresponde a esta sesión".into(),
+			(TooManyRecipients, Locale::En) => "A broadcast can reach at most 255 peers".into(),
+			(TooManyRecipients, Locale::Es) => "Una difusión puede llegar como máximo a 255 pares".into(),
+			(UnknownPeer, Locale::En) => "No key for this peer".into(),
+			(UnknownPeer, Locale::Es) => "No hay clave para este par".into(),
+			(NotARecipient, Locale::En) => "The broadcast is not addressed to us".into(),
+			(NotARecipient, Locale::Es) => "La difusión no está dirigida a nosotros".into(),
+			(UnknownRejectCode, Locale::En) => {
+				"Unknown reject code (expected too-large, unsupported-type, user-declined, quota-exceeded or busy)"
+					.into()
+			}
+			(UnknownRejectCode, Locale::Es) => {
+				"Código de rechazo desconocido (se esperaba too-large, unsupported-type, user-declined, quota-exceeded o busy)"
+					.into()
+			}
+			(UnknownGoodbyeReason, Locale::En) => {
+				"Unknown goodbye reason (expected user-left, app-closing, idle, protocol-violation or restarting)"
+					.into()
+			}
+			(UnknownGoodbyeReason, Locale::Es) => {
+				"Motivo de despedida desconocido (se esperaba user-left, app-closing, idle, protocol-violation o restarting)"
+					.into()
+			}
+			(EphemeralBurned, Locale::En) => "This ephemeral chat was burned".into(),
+			(EphemeralBurned, Locale::Es) => "Este chat efímero fue destruido".into(),
+			(EphemeralKeyGone, Locale::En) => {
+				"The message was already read or has expired; its key no longer exists".into()
+			}
+			(EphemeralKeyGone, Locale::Es) => {
+				"El mensaje ya se leyó o caducó; su clave ya no existe".into()
+			}
+			(EphemeralTooFarAhead, Locale::En) => "Too many earlier messages are missing".into(),
+			(EphemeralTooFarAhead, Locale::Es) => "Faltan demasiados mensajes anteriores".into(),
+		}
+	}
+}
+
+/// Localized JS error for `msg`.
+pub fn p2p_error(msg: P2pMessage) -> JsValue {
+	holi_wasm_i18n::js_error(&msg)
+}
+
+/// Localized decode error carrying the decoder's `Debug` detail.
+pub fn decode_error(e: impl std::fmt::Debug) -> JsValue {
+	p2p_error(P2pMessage::Decode(format!("{e:?}")))
+}
+
+/// `decode_error` for a payload decoder's error, counted in
+/// `holi_p2p::metrics` (`decode_v1` already counts header errors).
+pub fn payload_error(e: holi_p2p::frame::DecodeError) -> JsValue {
+	holi_p2p::metrics::Metrics::global().record_decode_error(&e);
+	decode_error(e)
+}
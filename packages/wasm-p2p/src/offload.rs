@@ -0,0 +1,140 @@
+//! Encrypt/decrypt of transfer frames on worker threads.
+//!
+//! The owner keeps a `CryptoOffload` (slot scheduler) and a SharedArrayBuffer
+//! of `buffer_len` bytes; each worker loads this module too and runs
+//! `seal_slot`/`open_slot` on views into that buffer. Frames cross from JS
+//! into wasm and back exactly once; nothing goes through `postMessage`.
+//! Slot layout and states are documented in `holi_p2p::offload`.
+
+use wasm_bindgen::prelude::*;
+
+use holi_p2p::offload::{Job, OffloadScheduler, SlotLayout};
+
+use crate::messages::{p2p_error, P2pMessage};
+
+fn job_to_js(job: Job) -> Result<JsValue, JsValue> {
+	let obj = js_sys::Object::new();
+	js_sys::Reflect::set(&obj, &JsValue::from_str("seq"), &JsValue::from_f64(job.seq as f64))?;
+	js_sys::Reflect::set(&obj, &JsValue::from_str("worker"), &JsValue::from_f64(job.worker as f64))?;
+	js_sys::Reflect::set(&obj, &JsValue::from_str("slot"), &JsValue::from_f64(job.slot as f64))?;
+	Ok(obj.into())
+}
+
+/// Write `out` to the start of `slot`, returning its length.
+fn write_slot(slot: &js_sys::Uint8Array, out: &[u8]) -> Result<u32, JsValue> {
+	if out.len() > slot.length() as usize {
+		return Err(p2p_error(P2pMessage::SlotTooSmall));
+	}
+	slot.subarray(0, out.len() as u32).copy_from(out);
+	Ok(out.len() as u32)
+}
+
+/// Encrypt the `len`-byte frame at the start of `slot` into an
+/// EncryptedEnvelope, in place. `slot` is the slot's data view. Returns the
+/// envelope length.
+#[wasm_bindgen]
+pub fn seal_slot(key_bytes: &[u8], slot: &js_sys::Uint8Array, len: u32) -> Result<u32, JsValue> {
+	let frame = slot.subarray(0, len).to_vec();
+	let envelope = crate::encrypt_envelope_v1(key_bytes, &frame)?;
+	write_slot(slot, &envelope)
+}
+
+/// Decrypt the `len`-byte EncryptedEnvelope at the start of `slot`, in
+/// place. Returns the inner frame length.
+#[wasm_bindgen]
+pub fn open_slot(key_bytes: &[u8], slot: &js_sys::Uint8Array, len: u32) -> Result<u32, JsValue> {
+	let envelope = slot.subarray(0, len).to_vec();
+	let frame = crate::decrypt_envelope_v1(key_bytes, &envelope)?;
+	write_slot(slot, &frame)
+}
+
+/// Slot scheduler for a pool of crypto workers; see `holi_p2p::offload`.
+#[wasm_bindgen]
+pub struct CryptoOffload {
+	inner: OffloadScheduler,
+}
+
+#[wasm_bindgen]
+impl CryptoOffload {
+	/// `workers` x `slots_per_worker` slots, each fitting a sealed FileChunk
+	/// of `chunk_size` data bytes.
+	#[wasm_bindgen(constructor)]
+	pub fn new(workers: u32, slots_per_worker: u32, chunk_size: u32) -> CryptoOffload {
+		CryptoOffload {
+			inner: OffloadScheduler::new(SlotLayout::for_chunk_size(
+				workers as usize,
+				slots_per_worker as usize,
+				chunk_size as usize,
+			)),
+		}
+	}
+
+	fn layout(&self) -> &SlotLayout {
+		self.inner.layout()
+	}
+
+	/// Size of the SharedArrayBuffer to allocate.
+	#[wasm_bindgen(getter)]
+	pub fn buffer_len(&self) -> u32 {
+		self.layout().buffer_len() as u32
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn workers(&self) -> u32 {
+		self.layout().workers as u32
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn slots_per_worker(&self) -> u32 {
+		self.layout().slots_per_worker as u32
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn slot_capacity(&self) -> u32 {
+		self.layout().slot_capacity as u32
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn in_flight(&self) -> u32 {
+		self.inner.in_flight() as u32
+	}
+
+	pub fn doorbell_offset(&self, worker: u32) -> u32 {
+		self.layout().doorbell_offset(worker as usize) as u32
+	}
+
+	pub fn header_offset(&self, slot: u32) -> u32 {
+		self.layout().header_offset(slot as usize) as u32
+	}
+
+	pub fn data_offset(&self, slot: u32) -> u32 {
+		self.layout().data_offset(slot as usize) as u32
+	}
+
+	/// Reserve a slot for the next frame: `{ seq, worker, slot }`, or null
+	/// while every slot is busy.
+	pub fn submit(&mut self) -> Result<JsValue, JsValue> {
+		match self.inner.submit() {
+			Some(job) => job_to_js(job),
+			None => Ok(JsValue::NULL),
+		}
+	}
+
+	/// Mark `slot` finished by its worker.
+	pub fn complete(&mut self, slot: u32) -> bool {
+		self.inner.complete(slot as usize)
+	}
+
+	/// Next finished frame in submission order (`{ seq, worker, slot }`), or
+	/// null. Read the slot before the next `submit`.
+	pub fn next_ready(&mut self) -> Result<JsValue, JsValue> {
+		match self.inner.next_ready() {
+			Some(job) => job_to_js(job),
+			None => Ok(JsValue::NULL),
+		}
+	}
+
+	pub fn reset(&mut self) {
+		self.inner.reset();
+	}
+}
@@ -0,0 +1,75 @@
+//! Outbound frame prioritization for the DataChannel.
+//!
+//! Frames are queued here instead of going straight to `channel.send`; the
+//! send loop pulls `next_batch(budget)` whenever the channel's buffer drains.
+//! Control frames go first, and chat is interleaved with file chunks by
+//! weight; see `holi_p2p::outbound`.
+
+use wasm_bindgen::prelude::*;
+
+use holi_p2p::outbound::{FrameScheduler as Inner, Priority, SchedulerWeights};
+
+use crate::messages::{p2p_error, P2pMessage};
+
+#[wasm_bindgen]
+pub struct FrameScheduler {
+	inner: Inner,
+}
+
+fn priority(value: u8) -> Result<Priority, JsValue> {
+	Priority::from_u8(value).ok_or_else(|| p2p_error(P2pMessage::UnknownPriority))
+}
+
+#[wasm_bindgen]
+impl FrameScheduler {
+	/// `chat_weight` : `bulk_weight` is the byte share of chat vs file
+	/// chunks while both are queued (zero counts as one).
+	#[wasm_bindgen(constructor)]
+	pub fn new(chat_weight: u32, bulk_weight: u32) -> FrameScheduler {
+		FrameScheduler {
+			inner: Inner::with_weights(SchedulerWeights {
+				chat: chat_weight,
+				bulk: bulk_weight,
+			}),
+		}
+	}
+
+	/// Priority class of an encoded plaintext frame: 0 control, 1 chat,
+	/// 2 bulk. Call before sealing it into an EncryptedEnvelope.
+	pub fn classify(frame: &[u8]) -> Result<u8, JsValue> {
+		Priority::for_frame_bytes(frame)
+			.map(|p| p as u8)
+			.ok_or_else(|| p2p_error(P2pMessage::UnknownFrameType))
+	}
+
+	/// Queue a frame (plaintext or sealed) under priority 0/1/2.
+	pub fn enqueue(&mut self, priority_class: u8, frame: Vec<u8>) -> Result<(), JsValue> {
+		self.inner.enqueue(priority(priority_class)?, frame);
+		Ok(())
+	}
+
+	/// Frames to send now, in order: at least one if any are queued, and
+	/// about `bytes_budget` bytes in total.
+	pub fn next_batch(&mut self, bytes_budget: u32) -> js_sys::Array {
+		self.inner
+			.next_batch(bytes_budget as usize)
+			.iter()
+			.map(|frame| js_sys::Uint8Array::from(frame.as_slice()))
+			.collect()
+	}
+
+	/// Bytes queued under one priority.
+	pub fn pending_bytes(&self, priority_class: u8) -> Result<u32, JsValue> {
+		Ok(self.inner.pending_bytes(priority(priority_class)?) as u32)
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn len(&self) -> u32 {
+		self.inner.len() as u32
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn is_empty(&self) -> bool {
+		self.inner.is_empty()
+	}
+}
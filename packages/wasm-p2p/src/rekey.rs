@@ -0,0 +1,205 @@
+//! Envelope sealing with automatic key rotation; the exchange is described
+//! in `holi_p2p::rekey`.
+
+use wasm_bindgen::prelude::*;
+
+use hkdf::Hkdf;
+use holi_rng::HoliRng;
+use rand::RngCore;
+use sha2::Sha256;
+use x25519_dalek::{x25519, X25519_BASEPOINT_BYTES};
+use zeroize::Zeroizing;
+
+use holi_p2p::frame::{
+	decode_rekey_payload_v1, decode_v1, encode_rekey_request_v1, encode_rekey_response_v1, CipherSuite,
+	FrameType, Rekey,
+};
+use holi_p2p::padding::{pad_frame, DEFAULT_PAD_BUCKETS};
+use holi_p2p::rekey::{rekey_info, RekeyPolicy, RekeySchedule, RequestOutcome};
+
+use crate::messages::{decode_error, p2p_error, payload_error, P2pMessage};
+
+/// Drop length padding the peer's session added after the inner frame.
+fn unpadded(mut plaintext: Vec<u8>) -> Vec<u8> {
+	let len = holi_p2p::padding::unpad_frame(&plaintext).len();
+	plaintext.truncate(len);
+	plaintext
+}
+
+/// Envelope keys and X25519 secrets, wiped when dropped or replaced.
+type Key = Zeroizing<[u8; 32]>;
+
+fn fresh_secret() -> Key {
+	let mut secret = Zeroizing::new([0u8; 32]);
+	HoliRng.fill_bytes(secret.as_mut());
+	secret
+}
+
+/// Key for `epoch`: HKDF-SHA256 over the X25519 secret, salted with the old key.
+fn derive_key(
+	old_key: &[u8; 32],
+	secret: &[u8; 32],
+	peer_public: &[u8; 32],
+	epoch: u32,
+) -> Result<Key, JsValue> {
+	let shared = Zeroizing::new(x25519(*secret, *peer_public));
+	if *shared == [0u8; 32] {
+		return Err(p2p_error(P2pMessage::RekeyRejected));
+	}
+	let mut key = Zeroizing::new([0u8; 32]);
+	Hkdf::<Sha256>::new(Some(old_key), shared.as_ref())
+		.expand(&rekey_info(epoch), key.as_mut())
+		.expect("32-byte HKDF output");
+	Ok(key)
+}
+
+/// EncryptedEnvelope sealing for one peer that rotates its key after a
+/// byte or frame threshold, so a long session doesn't encrypt gigabytes
+/// under one key.
+///
+/// Seal everything with `seal`; when `rekey_due()`, send `start_rekey()`.
+/// Pass every opened RekeyRequest/RekeyResponse to `handle_rekey_frame`
+/// and send what it returns. With padding on, frames are padded to bucket
+/// sizes before sealing so message lengths don't show on the wire.
+#[wasm_bindgen]
+pub struct EnvelopeSession {
+	suite: CipherSuite,
+	/// Padding bucket sizes; empty for no padding.
+	pad_buckets: Vec<usize>,
+	key: Key,
+	/// Key of the previous epoch, for frames the peer sealed before switching.
+	previous: Option<Key>,
+	/// Our X25519 secret while our request waits for a response.
+	ephemeral: Option<Key>,
+	schedule: RekeySchedule,
+}
+
+impl EnvelopeSession {
+	fn switch_to(&mut self, key: Key, epoch: u32) {
+		self.previous = Some(std::mem::replace(&mut self.key, key));
+		self.ephemeral = None;
+		self.schedule.complete(epoch);
+	}
+
+	fn on_request(&mut self, request: &Rekey) -> Result<Option<Vec<u8>>, JsValue> {
+		match self.schedule.on_request(request) {
+			Ok(RequestOutcome::Accept) => {}
+			Ok(RequestOutcome::Ignore) => return Ok(None),
+			Err(_) => return Err(p2p_error(P2pMessage::RekeyRejected)),
+		}
+		let secret = fresh_secret();
+		let new_key = derive_key(&self.key, &secret, &request.public_key, request.epoch)?;
+		// The response still goes out under the old key: the peer only
+		// switches once it has read it.
+		let response = self.seal(&encode_rekey_response_v1(&Rekey {
+			epoch: request.epoch,
+			public_key: x25519(*secret, X25519_BASEPOINT_BYTES),
+		}))?;
+		self.switch_to(new_key, request.epoch);
+		Ok(Some(response))
+	}
+
+	fn on_response(&mut self, response: &Rekey) -> Result<(), JsValue> {
+		self.schedule
+			.on_response(response)
+			.map_err(|_| p2p_error(P2pMessage::RekeyRejected))?;
+		let secret = self.ephemeral.as_ref().ok_or_else(|| p2p_error(P2pMessage::RekeyRejected))?;
+		let new_key = derive_key(&self.key, secret, &response.public_key, response.epoch)?;
+		self.switch_to(new_key, response.epoch);
+		Ok(())
+	}
+}
+
+#[wasm_bindgen]
+impl EnvelopeSession {
+	/// `key_bytes` is the session's starting key; `suite` comes from
+	/// `negotiate_cipher_suite_v1`.
+	#[wasm_bindgen(constructor)]
+	pub fn new(key_bytes: &[u8], suite: u8) -> Result<EnvelopeSession, JsValue> {
+		Ok(EnvelopeSession {
+			suite: crate::parse_cipher_suite(suite)?,
+			pad_buckets: Vec::new(),
+			key: Zeroizing::new(crate::parse_key_32(key_bytes)?),
+			previous: None,
+			ephemeral: None,
+			schedule: RekeySchedule::new(RekeyPolicy::default()),
+		})
+	}
+
+	/// Rotate after `max_bytes` sealed plaintext bytes or `max_messages`
+	/// frames, whichever comes first.
+	pub fn set_rekey_thresholds(&mut self, max_bytes: f64, max_messages: f64) {
+		self.schedule.set_policy(RekeyPolicy {
+			max_bytes: max_bytes.max(1.0) as u64,
+			max_messages: max_messages.max(1.0) as u64,
+		});
+	}
+
+	/// Pad sealed frames up to the smallest of `buckets` (bytes) that fits,
+	/// or a multiple of the largest. An empty list turns padding off.
+	pub fn set_padding_buckets(&mut self, buckets: Vec<u32>) {
+		self.pad_buckets = buckets.into_iter().map(|b| b as usize).collect();
+	}
+
+	/// Pad with `holi_p2p::padding::DEFAULT_PAD_BUCKETS` (64 B to 16 KiB).
+	pub fn use_default_padding(&mut self) {
+		self.pad_buckets = DEFAULT_PAD_BUCKETS.to_vec();
+	}
+
+	/// Rotations completed so far.
+	#[wasm_bindgen(getter)]
+	pub fn epoch(&self) -> u32 {
+		self.schedule.epoch()
+	}
+
+	/// Seal an encoded frame under the current key, padded if enabled.
+	pub fn seal(&mut self, inner_frame_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+		let mut plaintext = Zeroizing::new(inner_frame_bytes.to_vec());
+		pad_frame(&mut plaintext, &self.pad_buckets);
+		let envelope = crate::seal_envelope(self.suite, &self.key, &plaintext)?;
+		self.schedule.record_sealed(plaintext.len());
+		Ok(envelope)
+	}
+
+	/// Open an envelope from the peer, returning the inner frame without
+	/// padding.
+	pub fn open(&self, envelope_frame_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+		let envelope = crate::decode_envelope(envelope_frame_bytes)?;
+		crate::open_envelope(&self.key, &envelope)
+			.or_else(|e| match &self.previous {
+				Some(previous) => crate::open_envelope(previous, &envelope),
+				None => Err(e),
+			})
+			.map(unpadded)
+	}
+
+	/// A threshold was reached and no rotation is in flight.
+	pub fn rekey_due(&self) -> bool {
+		self.schedule.due()
+	}
+
+	/// Sealed RekeyRequest to send to the peer.
+	pub fn start_rekey(&mut self) -> Result<Vec<u8>, JsValue> {
+		let secret = fresh_secret();
+		let request = self.schedule.start(x25519(*secret, X25519_BASEPOINT_BYTES));
+		self.ephemeral = Some(secret);
+		self.seal(&encode_rekey_request_v1(&request))
+	}
+
+	/// Act on an opened RekeyRequest or RekeyResponse. Returns a sealed
+	/// RekeyResponse to send back, if any.
+	pub fn handle_rekey_frame(&mut self, inner_frame_bytes: &[u8]) -> Result<Option<Vec<u8>>, JsValue> {
+		let (frame, _used) = decode_v1(inner_frame_bytes, 1024).map_err(decode_error)?;
+		let rekey = match frame.frame_type {
+			FrameType::RekeyRequest | FrameType::RekeyResponse => {
+				decode_rekey_payload_v1(&frame.payload).map_err(payload_error)?
+			}
+			_ => return Err(p2p_error(P2pMessage::UnexpectedFrame("RekeyRequest"))),
+		};
+		if frame.frame_type == FrameType::RekeyRequest {
+			self.on_request(&rekey)
+		} else {
+			self.on_response(&rekey).map(|()| None)
+		}
+	}
+}
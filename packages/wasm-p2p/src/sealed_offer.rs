@@ -0,0 +1,117 @@
+//! File offers whose metadata stays hidden until the receiver accepts.
+//!
+//! Flow: the sender seals a FileOffer (`seal_file_offer_v1`) and sends only
+//! the FileOfferSealed frame, in which just the transfer id is readable. Once
+//! the receiver answers with FileAccept, the sender sends `encode_file_reveal_v1`
+//! with the per-offer key; the receiver opens the offer (`open_file_offer_v1`)
+//! and answers a second time, with FileAccept or FileReject, before any
+//! chunks flow. The sender commits to the metadata up front, so it can't
+//! change what was offered after seeing the accept.
+
+use chacha20poly1305::{aead::Aead, aead::KeyInit, aead::Payload, XChaCha20Poly1305};
+use holi_rng::HoliRng;
+use rand::RngCore;
+use wasm_bindgen::prelude::*;
+
+use holi_p2p::frame::{
+	decode_file_offer_payload_v1, decode_file_offer_sealed_payload_v1, decode_file_reveal_payload_v1,
+	decode_offer_metadata, decode_v1, encode_file_offer_sealed_v1, encode_offer_metadata, Frame, FrameType,
+	SealedFileOffer, ENVELOPE_NONCE_LEN, OFFER_KEY_LEN,
+};
+
+use crate::messages::{decode_error, p2p_error, payload_error, P2pMessage};
+
+fn decode_frame(bytes: &[u8], expected: FrameType, name: &'static str) -> Result<Frame, JsValue> {
+	let (frame, _used) = decode_v1(bytes, 1024 * 1024).map_err(decode_error)?;
+	if frame.frame_type != expected {
+		return Err(p2p_error(P2pMessage::UnexpectedFrame(name)));
+	}
+	Ok(frame)
+}
+
+/// Seal an encoded FileOffer (from `encode_file_offer_v1` or
+/// `encode_file_offer_merkle_v1`). Returns `{ frame, key }`: send `frame`
+/// now and keep `key` for `encode_file_reveal_v1` after the receiver accepts.
+#[wasm_bindgen]
+pub fn seal_file_offer_v1(offer_frame_bytes: &[u8]) -> Result<JsValue, JsValue> {
+	let frame = decode_frame(offer_frame_bytes, FrameType::FileOffer, "FileOffer")?;
+	let offer = decode_file_offer_payload_v1(&frame.payload).map_err(payload_error)?;
+
+	let mut key = [0u8; OFFER_KEY_LEN];
+	HoliRng.fill_bytes(&mut key);
+	let mut nonce = [0u8; ENVELOPE_NONCE_LEN];
+	HoliRng.fill_bytes(&mut nonce);
+
+	let mut metadata = Vec::new();
+	encode_offer_metadata(&offer.metadata(), &mut metadata);
+	let ciphertext = XChaCha20Poly1305::new((&key).into())
+		.encrypt(
+			(&nonce).into(),
+			Payload {
+				msg: &metadata,
+				aad: offer.id.as_bytes(),
+			},
+		)
+		.map_err(|_| p2p_error(P2pMessage::EncryptFailed))?;
+
+	let sealed = encode_file_offer_sealed_v1(&SealedFileOffer {
+		id: offer.id,
+		nonce,
+		ciphertext,
+	});
+	let obj = js_sys::Object::new();
+	let frame = js_sys::Uint8Array::from(sealed.as_slice());
+	js_sys::Reflect::set(&obj, &JsValue::from_str("frame"), &frame.into())?;
+	let key = js_sys::Uint8Array::from(key.as_slice());
+	js_sys::Reflect::set(&obj, &JsValue::from_str("key"), &key.into())?;
+	Ok(obj.into())
+}
+
+/// Transfer id of a FileOfferSealed, the only thing readable before the reveal.
+#[wasm_bindgen]
+pub fn decode_file_offer_sealed_id_v1(bytes: &[u8]) -> Result<String, JsValue> {
+	let frame = decode_frame(bytes, FrameType::FileOfferSealed, "FileOfferSealed")?;
+	let sealed = decode_file_offer_sealed_payload_v1(&frame.payload).map_err(payload_error)?;
+	Ok(sealed.id)
+}
+
+#[wasm_bindgen]
+pub fn encode_file_reveal_v1(id: &str, key_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+	let key = crate::parse_key_32(key_bytes)?;
+	Ok(holi_p2p::frame::encode_file_reveal_v1(&holi_p2p::frame::FileReveal {
+		id: id.to_string(),
+		key,
+	}))
+}
+
+#[wasm_bindgen]
+pub fn decode_file_reveal_id_v1(bytes: &[u8]) -> Result<String, JsValue> {
+	let frame = decode_frame(bytes, FrameType::FileReveal, "FileReveal")?;
+	let reveal = decode_file_reveal_payload_v1(&frame.payload).map_err(payload_error)?;
+	Ok(reveal.id)
+}
+
+/// Open a FileOfferSealed with the FileReveal that followed it. Returns the
+/// same object as `decode_file_offer_v1`.
+#[wasm_bindgen]
+pub fn open_file_offer_v1(sealed_frame_bytes: &[u8], reveal_frame_bytes: &[u8]) -> Result<JsValue, JsValue> {
+	let frame = decode_frame(sealed_frame_bytes, FrameType::FileOfferSealed, "FileOfferSealed")?;
+	let sealed = decode_file_offer_sealed_payload_v1(&frame.payload).map_err(payload_error)?;
+	let frame = decode_frame(reveal_frame_bytes, FrameType::FileReveal, "FileReveal")?;
+	let reveal = decode_file_reveal_payload_v1(&frame.payload).map_err(payload_error)?;
+	if reveal.id != sealed.id {
+		return Err(p2p_error(P2pMessage::OfferIdMismatch));
+	}
+
+	let metadata = XChaCha20Poly1305::new((&reveal.key).into())
+		.decrypt(
+			(&sealed.nonce).into(),
+			Payload {
+				msg: &sealed.ciphertext,
+				aad: sealed.id.as_bytes(),
+			},
+		)
+		.map_err(|_| p2p_error(P2pMessage::DecryptFailed))?;
+	let metadata = decode_offer_metadata(&metadata).map_err(payload_error)?;
+	crate::offer_to_js(&holi_p2p::frame::FileOffer::from_metadata(sealed.id, metadata))
+}
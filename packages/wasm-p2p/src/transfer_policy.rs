@@ -0,0 +1,123 @@
+//! Abuse limits on incoming transfers, enforced where offers and chunks
+//! arrive; see `holi_p2p::transfer_policy`.
+//!
+//! Pass every received FileOffer to `admit_offer`, every FileChunk to
+//! `record_chunk` and every FileEnd to `record_end`. The first two return a
+//! FileReject frame to send back when the transfer is refused, and undefined
+//! when it may go on. Admitted chunks and ends are reported to
+//! `subscribe_p2p_events` listeners as `transferProgress` and
+//! `transferComplete`.
+
+use wasm_bindgen::prelude::*;
+
+use holi_p2p::frame::{decode_file_chunk_frame_v1, decode_file_end_payload_v1, decode_file_offer_payload_v1, decode_v1, FrameType};
+use holi_p2p::transfer_policy::{encode_reject, TransferLimiter as Inner, TransferPolicy};
+
+use crate::events::{self, P2pEvent};
+use crate::messages::{decode_error, p2p_error, payload_error, P2pMessage};
+
+fn limit(value: f64) -> Option<u64> {
+	(value.is_finite() && value > 0.0).then_some(value as u64)
+}
+
+fn ms(value: f64) -> u64 {
+	if value.is_finite() && value > 0.0 {
+		value as u64
+	} else {
+		0
+	}
+}
+
+#[wasm_bindgen]
+pub struct TransferLimiter {
+	inner: Inner,
+}
+
+#[wasm_bindgen]
+impl TransferLimiter {
+	/// Limits in bytes and transfers; 0 is unlimited. The daily quota is per
+	/// peer and counts offered sizes.
+	#[wasm_bindgen(constructor)]
+	pub fn new(max_file_size: f64, max_concurrent: u32, per_peer_daily_bytes: f64) -> TransferLimiter {
+		TransferLimiter {
+			inner: Inner::new(TransferPolicy {
+				max_file_size: limit(max_file_size),
+				max_concurrent: (max_concurrent > 0).then_some(max_concurrent as usize),
+				per_peer_daily_bytes: limit(per_peer_daily_bytes),
+			}),
+		}
+	}
+
+	/// Check a FileOffer from `peer`. Returns the FileReject frame to send,
+	/// or undefined if the transfer is admitted.
+	pub fn admit_offer(
+		&mut self,
+		peer: &str,
+		offer_bytes: &[u8],
+		now_ms: f64,
+	) -> Result<Option<Vec<u8>>, JsValue> {
+		let (frame, _used) = decode_v1(offer_bytes, 1024 * 1024).map_err(decode_error)?;
+		if frame.frame_type != FrameType::FileOffer {
+			return Err(p2p_error(P2pMessage::UnexpectedFrame("FileOffer")));
+		}
+		let offer = decode_file_offer_payload_v1(&frame.payload).map_err(payload_error)?;
+		let refused = self.inner.admit(peer, &offer, ms(now_ms)).err();
+		Ok(refused.map(|reason| encode_reject(&offer.id, &reason)))
+	}
+
+	/// Count a FileChunk from `peer`. Returns the FileReject frame to send,
+	/// or undefined; a refused transfer is already finished.
+	pub fn record_chunk(&mut self, peer: &str, chunk_bytes: &[u8]) -> Result<Option<Vec<u8>>, JsValue> {
+		let (frame, _used) = decode_v1(chunk_bytes, 1024 * 1024).map_err(decode_error)?;
+		if frame.frame_type != FrameType::FileChunk {
+			return Err(p2p_error(P2pMessage::UnexpectedFrame("FileChunk")));
+		}
+		let chunk = decode_file_chunk_frame_v1(&frame).map_err(payload_error)?;
+		match self.inner.record_chunk(peer, &chunk.id, chunk.data.len()) {
+			Ok(()) => {
+				events::emit(P2pEvent::TransferProgress {
+					id: chunk.id,
+					chunk_index: chunk.chunk_index,
+					bytes: chunk.data.len(),
+				});
+				Ok(None)
+			}
+			Err(reason) => {
+				self.inner.finish(peer, &chunk.id);
+				Ok(Some(encode_reject(&chunk.id, &reason)))
+			}
+		}
+	}
+
+	/// A FileEnd from `peer`: finishes the transfer and returns its id.
+	pub fn record_end(&mut self, peer: &str, end_bytes: &[u8]) -> Result<String, JsValue> {
+		let (frame, _used) = decode_v1(end_bytes, 1024 * 1024).map_err(decode_error)?;
+		if frame.frame_type != FrameType::FileEnd {
+			return Err(p2p_error(P2pMessage::UnexpectedFrame("FileEnd")));
+		}
+		let id = decode_file_end_payload_v1(&frame.payload).map_err(payload_error)?;
+		self.inner.finish(peer, &id);
+		events::emit(P2pEvent::TransferComplete { id: id.clone() });
+		Ok(id)
+	}
+
+	/// Transfer `id` from `peer` ended without a FileEnd: a reject either
+	/// way, or a cancel.
+	pub fn finish(&mut self, peer: &str, id: &str) {
+		self.inner.finish(peer, id);
+	}
+
+	/// `peer` disconnected; its running transfers no longer count.
+	pub fn finish_peer(&mut self, peer: &str) {
+		self.inner.finish_peer(peer);
+	}
+
+	pub fn active_count(&self) -> u32 {
+		self.inner.active_count() as u32
+	}
+
+	/// Bytes admitted from `peer` in the current day.
+	pub fn peer_bytes(&self, peer: &str, now_ms: f64) -> f64 {
+		self.inner.peer_bytes(peer, ms(now_ms)) as f64
+	}
+}
@@ -0,0 +1,137 @@
+//! Chunk-size autotuning for file transfers.
+//!
+//! Encrypt+encode cost dominates on low-end phones, so a fixed chunk size is
+//! either too small (per-frame overhead) or too large (long stalls, coarse
+//! progress). `calibrate_transfer` measures the real pipeline on this device.
+
+use wasm_bindgen::prelude::*;
+
+use chacha20poly1305::{aead::Aead, aead::KeyInit, XChaCha20Poly1305};
+use holi_clock::{default_clock, Clock};
+use holi_rng::HoliRng;
+use rand::RngCore;
+use serde::Serialize;
+
+/// Chunk sizes tried, smallest first. 64 KiB is the largest message size
+/// that every browser's DataChannel implementation accepts.
+const CANDIDATE_CHUNK_SIZES: [usize; 5] = [4 * 1024, 8 * 1024, 16 * 1024, 32 * 1024, 64 * 1024];
+/// Time spent measuring each candidate.
+const SAMPLE_BUDGET_MS: u64 = 25;
+/// Smaller chunks within this fraction of the best throughput are preferred.
+const THROUGHPUT_TOLERANCE: f64 = 0.9;
+/// Data in flight should cover roughly this much encrypt time.
+const TARGET_IN_FLIGHT_MS: f64 = 100.0;
+const MIN_WINDOW: u32 = 2;
+/// Keeps `RTCDataChannel.bufferedAmount` under 1 MiB.
+const MAX_IN_FLIGHT_BYTES: usize = 1024 * 1024;
+
+/// Recommended transfer parameters for this device, handed to JS as
+/// `{ chunkSize, window, throughputBytesPerSec }`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferTuning {
+	/// Bytes of file data per FileChunk frame.
+	pub chunk_size: u32,
+	/// Chunks to keep in flight before waiting for the channel to drain.
+	pub window: u32,
+	/// Measured encrypt+encode throughput at `chunk_size`, in bytes per second.
+	pub throughput_bytes_per_sec: f64,
+}
+
+/// Measure bytes per ms of encode + encrypt at `chunk_size`.
+fn measure(cipher: &XChaCha20Poly1305, chunk_size: usize, clock: &dyn Clock) -> f64 {
+	let data = vec![0xA5u8; chunk_size];
+	let mut nonce = [0u8; holi_p2p::frame::ENVELOPE_NONCE_LEN];
+	let start = clock.now_ms();
+	let mut processed = 0usize;
+	let mut index = 0u32;
+	loop {
+		let frame = holi_p2p::frame::encode_file_chunk_v1("calibrate", index, &data);
+		HoliRng.fill_bytes(&mut nonce);
+		if let Ok(ct) = cipher.encrypt((&nonce).into(), frame.as_slice()) {
+			std::hint::black_box(holi_p2p::frame::encode_encrypted_envelope_v1(&nonce, &ct));
+			processed += chunk_size;
+		}
+		index += 1;
+		let elapsed = clock.now_ms().saturating_sub(start);
+		if elapsed >= SAMPLE_BUDGET_MS {
+			return processed as f64 / elapsed as f64;
+		}
+	}
+}
+
+/// Pick chunk size and window from `(chunk_size, bytes_per_ms)` samples.
+fn recommend(samples: &[(usize, f64)]) -> TransferTuning {
+	let best = samples.iter().map(|s| s.1).fold(0.0, f64::max);
+	let (chunk_size, throughput) = samples
+		.iter()
+		.copied()
+		.find(|s| s.1 >= best * THROUGHPUT_TOLERANCE)
+		.unwrap_or((CANDIDATE_CHUNK_SIZES[2], best));
+
+	let wanted = (throughput * TARGET_IN_FLIGHT_MS / chunk_size as f64).ceil() as u32;
+	let max_window = (MAX_IN_FLIGHT_BYTES / chunk_size) as u32;
+	TransferTuning {
+		chunk_size: chunk_size as u32,
+		window: wanted.clamp(MIN_WINDOW, max_window),
+		throughput_bytes_per_sec: throughput * 1000.0,
+	}
+}
+
+/// Benchmark encrypt+encode for several chunk sizes (~125 ms) and return the
+/// recommended `{ chunkSize, window, throughputBytesPerSec }` for file
+/// transfers, as `ChatManagerOptions.transferTuning` takes it.
+#[wasm_bindgen]
+pub fn calibrate_transfer() -> JsValue {
+	let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+	calibrate(&default_clock()).serialize(&serializer).unwrap_or(JsValue::NULL)
+}
+
+/// `calibrate_transfer`, timed by `clock`.
+fn calibrate(clock: &dyn Clock) -> TransferTuning {
+	let mut key = [0u8; 32];
+	HoliRng.fill_bytes(&mut key);
+	let cipher = XChaCha20Poly1305::new((&key).into());
+
+	let samples: Vec<(usize, f64)> = CANDIDATE_CHUNK_SIZES
+		.iter()
+		.map(|&size| (size, measure(&cipher, size, clock)))
+		.collect();
+	recommend(&samples)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn recommends_smallest_chunk_near_best_throughput() {
+		// Throughput in bytes per ms; 16 KiB is within 10% of the best.
+		let samples = [(4096, 200.0), (8192, 400.0), (16384, 920.0), (32768, 1000.0), (65536, 990.0)];
+		let tuning = recommend(&samples);
+		assert_eq!(tuning.chunk_size, 16384);
+		// 920 B/ms * 100 ms / 16 KiB = 5.6 chunks in flight
+		assert_eq!(tuning.window, 6);
+		assert_eq!(tuning.throughput_bytes_per_sec, 920_000.0);
+	}
+
+	#[test]
+	fn clamps_window() {
+		// Slow device: at least MIN_WINDOW chunks in flight.
+		let slow = recommend(&[(4096, 1.0)]);
+		assert_eq!((slow.chunk_size, slow.window), (4096, MIN_WINDOW));
+
+		// Fast device: no more than MAX_IN_FLIGHT_BYTES buffered.
+		let fast = recommend(&[(65536, 1_000_000.0)]);
+		assert_eq!(fast.window as usize, MAX_IN_FLIGHT_BYTES / 65536);
+	}
+
+	#[test]
+	fn serializes_camel_case() {
+		let tuning = recommend(&[(8192, 80.0)]);
+		let json = serde_json::to_value(tuning).unwrap();
+		assert_eq!(json["chunkSize"], 8192);
+		assert_eq!(json["window"], MIN_WINDOW);
+		assert!(json.get("chunk_size").is_none());
+	}
+}
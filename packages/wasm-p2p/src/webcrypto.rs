@@ -0,0 +1,85 @@
+//! AES-256-GCM envelopes through WebCrypto.
+//!
+//! Same wire format as `encrypt_envelope_with_suite_v1` with suite 2, but the
+//! AEAD runs in the browser's SubtleCrypto, which uses AES-NI / ARMv8 crypto
+//! instructions where the device has them. Only worth it once both peers
+//! advertised `HELLO_CAP_AES_256_GCM`; XChaCha20 envelopes are opened in wasm.
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use holi_rng::HoliRng;
+use rand::RngCore;
+
+use holi_p2p::frame::{CipherSuite, ENVELOPE_AES_GCM_NONCE_LEN};
+
+use crate::messages::{p2p_error, P2pMessage};
+
+fn subtle() -> Result<web_sys::SubtleCrypto, JsValue> {
+	let crypto = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("crypto"))?;
+	if crypto.is_undefined() {
+		return Err(p2p_error(P2pMessage::WebCryptoUnavailable));
+	}
+	Ok(crypto.unchecked_into::<web_sys::Crypto>().subtle())
+}
+
+async fn import_key(
+	subtle: &web_sys::SubtleCrypto,
+	key: &[u8],
+	usage: &str,
+) -> Result<web_sys::CryptoKey, JsValue> {
+	let usages = js_sys::Array::of1(&JsValue::from_str(usage));
+	let key_data = js_sys::Uint8Array::from(key);
+	let promise = subtle.import_key_with_str("raw", &key_data, "AES-GCM", false, &usages)?;
+	Ok(JsFuture::from(promise).await?.unchecked_into())
+}
+
+/// Async `encrypt_envelope_with_suite_v1(2, key, frame)` on WebCrypto.
+#[wasm_bindgen]
+pub async fn encrypt_envelope_webcrypto_v1(
+	key_bytes: Vec<u8>,
+	inner_frame_bytes: Vec<u8>,
+) -> Result<js_sys::Uint8Array, JsValue> {
+	let key = crate::parse_key_32(&key_bytes)?;
+	let subtle = subtle()?;
+	let crypto_key = import_key(&subtle, &key, "encrypt").await?;
+
+	let mut nonce = [0u8; ENVELOPE_AES_GCM_NONCE_LEN];
+	HoliRng.fill_bytes(&mut nonce);
+	let params = web_sys::AesGcmParams::new("AES-GCM", &js_sys::Uint8Array::from(nonce.as_slice()));
+	let data = js_sys::Uint8Array::from(inner_frame_bytes.as_slice());
+	let ct = JsFuture::from(subtle.encrypt_with_object_and_buffer_source(&params, &crypto_key, &data)?)
+		.await
+		.map_err(|_| p2p_error(P2pMessage::EncryptFailed))?;
+	let ct = js_sys::Uint8Array::new(&ct).to_vec();
+
+	let envelope =
+		holi_p2p::frame::encode_encrypted_envelope_with_suite_v1(CipherSuite::Aes256Gcm, &nonce, &ct)
+			.map_err(|_| p2p_error(P2pMessage::NonceLength))?;
+	Ok(js_sys::Uint8Array::from(envelope.as_slice()))
+}
+
+/// Async `decrypt_envelope_v1`: AES-256-GCM envelopes open on WebCrypto,
+/// XChaCha20-Poly1305 ones in wasm.
+#[wasm_bindgen]
+pub async fn decrypt_envelope_webcrypto_v1(
+	key_bytes: Vec<u8>,
+	envelope_frame_bytes: Vec<u8>,
+) -> Result<js_sys::Uint8Array, JsValue> {
+	let envelope = crate::decode_envelope(&envelope_frame_bytes)?;
+	if envelope.suite != CipherSuite::Aes256Gcm {
+		let pt = crate::decrypt_envelope_v1(&key_bytes, &envelope_frame_bytes)?;
+		return Ok(js_sys::Uint8Array::from(pt.as_slice()));
+	}
+	let key = crate::parse_key_32(&key_bytes)?;
+	let subtle = subtle()?;
+	let crypto_key = import_key(&subtle, &key, "decrypt").await?;
+
+	let params = web_sys::AesGcmParams::new("AES-GCM", &js_sys::Uint8Array::from(envelope.nonce.as_slice()));
+	let data = js_sys::Uint8Array::from(envelope.ciphertext.as_slice());
+	let pt = JsFuture::from(subtle.decrypt_with_object_and_buffer_source(&params, &crypto_key, &data)?)
+		.await
+		.map_err(|_| p2p_error(P2pMessage::DecryptFailed))?;
+	Ok(js_sys::Uint8Array::new(&pt))
+}
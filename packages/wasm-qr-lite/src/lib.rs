@@ -96,6 +96,21 @@ pub fn init(canvas_id: &str) -> Result<(), JsValue> {
     Ok(())
 }
 
+/// Which build of this module is running:
+/// `{ package, version, gitHash, features, protocols }`, same shape as the
+/// other packages. Built by hand to stay clear of serde.
+#[wasm_bindgen]
+pub fn qr_lite_build_info() -> JsValue {
+    let mut json = String::from(concat!(
+        r#"{"package":""#, env!("CARGO_PKG_NAME"),
+        r#"","version":""#, env!("CARGO_PKG_VERSION"),
+        r#"","gitHash":""#,
+    ));
+    json.push_str(option_env!("HOLI_GIT_HASH").unwrap_or("unknown"));
+    json.push_str(r#"","features":[],"protocols":{}}"#);
+    js_sys::JSON::parse(&json).unwrap_or(JsValue::NULL)
+}
+
 /// Render QR modules as glowing particles
 /// `data`: Flat array of [x, y, r, g, b, scale] per module
 #[wasm_bindgen]
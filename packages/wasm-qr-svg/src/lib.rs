@@ -69,8 +69,21 @@ pub fn get_qr_matrix(text: &str, ecc: &str, mask: i32) -> Vec<u8> {
     data
 }
 
+/// Module path as SVG, finder patterns left out so the caller can overlay
+/// custom eyes.
 #[wasm_bindgen]
 pub fn generate_svg(text: &str, shape: u8, ecc: &str, mask: i32) -> String {
+    render_svg(text, shape, ecc, mask, false)
+}
+
+/// Module path as SVG with every dark module drawn, finder patterns
+/// included, so it scans on its own.
+#[wasm_bindgen]
+pub fn generate_svg_full(text: &str, shape: u8, ecc: &str, mask: i32) -> String {
+    render_svg(text, shape, ecc, mask, true)
+}
+
+fn render_svg(text: &str, shape: u8, ecc: &str, mask: i32, full_matrix: bool) -> String {
     let qr = match create_qr(text, ecc, mask) {
         Some(q) => q,
         None => return String::from("<svg></svg>"),
@@ -89,8 +102,8 @@ pub fn generate_svg(text: &str, shape: u8, ecc: &str, mask: i32) -> String {
 
     for y in 0..size {
         for x in 0..size {
-            // Skip Finder Patterns (7x7 corners)
-            if (x < 7 && y < 7) || (x >= size - 7 && y < 7) || (x < 7 && y >= size - 7) {
+            // Skip Finder Patterns (7x7 corners) unless drawing the full matrix
+            if !full_matrix && ((x < 7 && y < 7) || (x >= size - 7 && y < 7) || (x < 7 && y >= size - 7)) {
                 continue;
             }
 
@@ -175,3 +188,20 @@ fn push_usize(s: &mut String, mut n: usize) {
         s.push(buffer[i] as char);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_matrix_draws_finder_patterns() {
+        let svg = generate_svg("https://holi.tools", 0, "M", -1);
+        let full = generate_svg_full("https://holi.tools", 0, "M", -1);
+        // Top-left corner of the top-left finder is always dark
+        assert!(!svg.contains("M0 0h1v1h-1z"));
+        assert!(full.contains("M0 0h1v1h-1z"));
+        // Three finders of 24 + 9 dark modules each
+        let modules = |s: &str| s.matches("h1v1h-1z").count();
+        assert_eq!(modules(&full), modules(&svg) + 3 * 33);
+    }
+}
@@ -0,0 +1,13 @@
+type CoreModule = typeof import('./pkg-core/holi_wasm_qr.js');
+type FullModule = typeof import('./pkg/holi_wasm_qr.js');
+
+export function instantiateStreaming<T extends { default: (init?: unknown) => Promise<unknown> }>(
+    glue: T,
+    url?: string | URL,
+): Promise<T>;
+
+export function is_core_loaded(): boolean;
+export function is_verify_loaded(): boolean;
+
+export function ensure_core_loaded(wasmUrl?: string | URL): Promise<CoreModule>;
+export function ensure_verify_loaded(wasmUrl?: string | URL): Promise<FullModule>;
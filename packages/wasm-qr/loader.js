@@ -0,0 +1,90 @@
+/**
+ * Lazy loader for @holi/wasm-qr.
+ *
+ * The page starts with the core build (`pkg-core`: generation, styled SVG,
+ * diffs, matrices, STL, layout). Verification, PNG export, image decoding
+ * and artistic mode need resvg, which is most of the binary, so they live in
+ * the full build (`pkg`) and are only fetched by `ensure_verify_loaded()`.
+ *
+ * Both builds are instantiated with `WebAssembly.instantiateStreaming`, so
+ * compilation starts while the bytes are still downloading. Serve `.wasm`
+ * as `application/wasm`; otherwise wasm-bindgen falls back to buffering.
+ *
+ * Important: keep the import paths as string literals so bundlers can split
+ * them into their own chunks.
+ */
+
+let core = null;
+let corePromise = null;
+let full = null;
+let fullPromise = null;
+
+/**
+ * Initialize a wasm-bindgen glue module, streaming its binary from `url`.
+ *
+ * Without `url` the glue fetches the `_bg.wasm` next to it (also streamed).
+ */
+export async function instantiateStreaming(glue, url) {
+    await glue.default(url ? { module_or_path: fetch(url) } : undefined);
+    return glue;
+}
+
+function once(load, set, reset) {
+    return load().then(
+        (mod) => {
+            set(mod);
+            return mod;
+        },
+        (error) => {
+            reset();
+            throw error;
+        },
+    );
+}
+
+export function is_core_loaded() {
+    return core !== null;
+}
+
+export function is_verify_loaded() {
+    return full !== null;
+}
+
+/**
+ * Load the core build exactly once.
+ *
+ * @param {string | URL} [wasmUrl] Where `holi_wasm_qr_bg.wasm` of `pkg-core` is served
+ */
+export function ensure_core_loaded(wasmUrl) {
+    if (core) return Promise.resolve(core);
+    corePromise ??= once(
+        async () => instantiateStreaming(await import('./pkg-core/holi_wasm_qr.js'), wasmUrl),
+        (mod) => (core = mod),
+        () => (corePromise = null),
+    );
+    return corePromise;
+}
+
+/**
+ * Load the build with verify, raster export, image decoding and artistic
+ * mode exactly once.
+ *
+ * Resolves to the module exporting `verify_qr_svg`, `rasterize_qr_svg`,
+ * `decode_qr_image` and `generate_artistic_qr`. If the core build already
+ * has them (see `qr_capabilities`), nothing more is fetched.
+ *
+ * @param {string | URL} [wasmUrl] Where `holi_wasm_qr_bg.wasm` of `pkg` is served
+ */
+export function ensure_verify_loaded(wasmUrl) {
+    if (full) return Promise.resolve(full);
+    if (core && JSON.parse(core.qr_capabilities()).verify) {
+        full = core;
+        return Promise.resolve(full);
+    }
+    fullPromise ??= once(
+        async () => instantiateStreaming(await import('./pkg/holi_wasm_qr.js'), wasmUrl),
+        (mod) => (full = mod),
+        () => (fullPromise = null),
+    );
+    return fullPromise;
+}
@@ -0,0 +1,59 @@
+//! `verify_qr_svg` backend that draws with `OffscreenCanvas` instead of resvg.
+//!
+//! The styled SVG is split into its background and paths, each path is
+//! filled (or stroked) as a `Path2D`, and the pixels go to rxing. Only SVGs from
+//! `generate_styled_svg` are supported.
+
+use holi_qr::{decode_luma, parse_styled_svg, rgba_to_luma, QrError};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{OffscreenCanvas, OffscreenCanvasRenderingContext2d, Path2d};
+
+/// Raster size for verification, as in the resvg path.
+const VERIFY_SIZE: u32 = 800;
+
+fn canvas_error(e: JsValue) -> QrError {
+    QrError::VerificationFailed(format!("Canvas error: {:?}", e))
+}
+
+pub fn verify_svg(svg: &str) -> Result<String, QrError> {
+    let layers = parse_styled_svg(svg)?;
+
+    let canvas = OffscreenCanvas::new(VERIFY_SIZE, VERIFY_SIZE).map_err(canvas_error)?;
+    let ctx: OffscreenCanvasRenderingContext2d = canvas
+        .get_context("2d")
+        .map_err(canvas_error)?
+        .ok_or_else(|| QrError::VerificationFailed("2D canvas context unavailable".into()))?
+        .dyn_into()
+        .map_err(|o| canvas_error(o.into()))?;
+
+    // White background first (important for transparent QRs)
+    let side = VERIFY_SIZE as f64;
+    ctx.set_fill_style_str("#FFFFFF");
+    ctx.fill_rect(0.0, 0.0, side, side);
+
+    let scale = side / layers.size;
+    ctx.scale(scale, scale).map_err(canvas_error)?;
+    if let Some(background) = &layers.background {
+        ctx.set_fill_style_str(background);
+        ctx.fill_rect(0.0, 0.0, layers.size, layers.size);
+    }
+    for layer in &layers.layers {
+        let path = Path2d::new_with_path_string(&layer.path).map_err(canvas_error)?;
+        match &layer.stroke {
+            Some((color, width)) => {
+                ctx.set_stroke_style_str(color);
+                ctx.set_line_width(*width);
+                ctx.stroke_with_path(&path);
+            }
+            None => {
+                ctx.set_fill_style_str(&layer.fill);
+                ctx.fill_with_path_2d(&path);
+            }
+        }
+    }
+
+    let image = ctx
+        .get_image_data(0.0, 0.0, side, side)
+        .map_err(canvas_error)?;
+    decode_luma(rgba_to_luma(&image.data()), VERIFY_SIZE, VERIFY_SIZE)
+}
@@ -2,20 +2,59 @@
 //! 
 //! Lightweight WASM module for generating QR codes as SVG.
 //! Uses fast_qr for high-performance QR generation and holi-qr for styled rendering.
+//!
+//! Verification uses resvg by default. The `canvas` feature (without the
+//! default `resvg`) verifies through `OffscreenCanvas` instead and drops
+//! PNG export and image-file decoding, for a much smaller bundle. With both
+//! enabled, resvg is used.
+//!
+//! Without either feature (`build:core`) the module only generates and
+//! renders. `loader.js` loads that build first and fetches the full one the
+//! first time `ensure_verify_loaded()` is called.
 
+#[cfg(all(feature = "canvas", not(feature = "resvg")))]
+mod canvas;
+mod messages;
+
+use std::cell::RefCell;
+
+use base64::Engine;
 use wasm_bindgen::prelude::*;
 use fast_qr::convert::svg::SvgBuilder;
 use fast_qr::qr::QRBuilder;
 use fast_qr::ECL;
 use serde::{Deserialize, Serialize};
 
+use messages::{qr_error, QrMessage};
+
 // Import from holi-qr core
 use holi_qr::{
-    generate_qr, render_svg_styled, ErrorCorrectionLevel,
-    BodyShape, EyeFrameShape, EyeBallShape, StyledRenderOptions,
-    verify_svg, decode_image
+    generate_qr, generate_qr_eci, Charset, try_render_svg_styled_into, try_render_svg_styled_diff, ErrorCorrectionLevel,
+    BodyShape, EyeFrameShape, EyeBallShape, StyledRenderOptions, SvgPart, EyeCorner, EyeOverride,
+    BackgroundPattern, PatternKind, OutlineStyle, Severity, ValidationIssue,
+    Gradient, GradientKind, GradientStop,
+    decode_luma,
+    classify_payload, PayloadKind, PayloadRisk,
+    print_requirements_at_dpi, PRESETS, ExtrudeOptions, ModuleRole, optimize_payload,
+    check_logo, render_svg_with_logo, Logo, LogoImage,
+    enforce_accessible_theme, AccessibilityPolicy,
+    WifiAuth, WifiCredentials, GeoPoint, SmsMessage, MailTo, Tel, VEvent,
 };
 
+thread_local! {
+    /// Output of `prepare_styled_svg`, read back with `read_svg_chunk` / `write_svg_chunk`.
+    static SVG_BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+    /// Scratch for `generate_styled_svg`, kept so live previews render without
+    /// regrowing a fresh string on every keystroke.
+    static PREVIEW_BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+pub use holi_wasm_i18n::{get_locale, set_locale};
+
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOC: holi_wasm_memory::CountingAllocator = holi_wasm_memory::CountingAllocator;
+
 /// Options for styled QR generation (JSON-serializable for WASM)
 #[derive(Serialize, Deserialize, Default)]
 pub struct QRStyleOptions {
@@ -23,6 +62,10 @@ pub struct QRStyleOptions {
     pub margin: Option<usize>,
     #[serde(default)]
     pub fg_color: Option<String>,
+    /// Replaces `fg_color` on the body and shared eyes, e.g.
+    /// `{"kind": "linear", "angle": 45, "stops": [{"offset": 0, "color": "#1F3A5F"}, {"offset": 1, "color": "#5B00D6"}]}`
+    #[serde(default)]
+    pub fg_gradient: Option<GradientOptions>,
     #[serde(default)]
     pub bg_color: Option<String>,
     #[serde(default)]
@@ -33,6 +76,191 @@ pub struct QRStyleOptions {
     pub eye_ball_shape: Option<String>,
     #[serde(default)]
     pub ecc: Option<String>,
+    /// Per-corner eye overrides, e.g. `{"top_right": {"frame_shape": "circle", "color": "#ff0066"}}`
+    #[serde(default)]
+    pub eyes: Option<EyeOverridesOptions>,
+    /// Decorative layer behind the modules, e.g. `{"kind": "diagonal-lines", "density": 0.3}`
+    #[serde(default)]
+    pub background_pattern: Option<BackgroundPatternOptions>,
+    /// Stroke-only rendering, e.g. `{"stroke_width": 0.15, "contour_only": true}`
+    #[serde(default)]
+    pub outline: Option<OutlineOptions>,
+    /// Keep timing/format/alignment modules readable with thin body shapes
+    /// (default true); see `qr_shape_substitutions`
+    #[serde(default)]
+    pub protect_function_patterns: Option<bool>,
+    /// Image over the center, e.g. `{"svg": "<circle .../>", "size": 0.2}`
+    /// or `{"image_base64": "iVBOR...", "mime_type": "image/png"}`
+    #[serde(default)]
+    pub logo: Option<LogoOptions>,
+    /// Encode Japanese text in Kanji mode for a smaller code (default
+    /// false); needs a build with the `kanji` feature
+    #[serde(default)]
+    pub kanji: Option<bool>,
+    /// Charset to encode the text in, declared by an ECI header:
+    /// `utf-8`, `iso-8859-1` or `shift_jis` (needs the `kanji` feature).
+    /// Unset, no header is written and scanners guess.
+    #[serde(default)]
+    pub charset: Option<String>,
+    /// Reject unknown shape names and ECC levels instead of falling back to
+    /// square / M
+    #[serde(default)]
+    pub strict: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct OutlineOptions {
+    /// In modules (default 0.1)
+    #[serde(default)]
+    pub stroke_width: Option<f64>,
+    /// Outline connected clusters instead of each module
+    #[serde(default)]
+    pub contour_only: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct EyeOverridesOptions {
+    #[serde(default)]
+    pub top_left: Option<EyeOverrideOptions>,
+    #[serde(default)]
+    pub top_right: Option<EyeOverrideOptions>,
+    #[serde(default)]
+    pub bottom_left: Option<EyeOverrideOptions>,
+}
+
+/// Style for one eye; unset fields use the shared eye options
+#[derive(Serialize, Deserialize, Default)]
+pub struct EyeOverrideOptions {
+    #[serde(default)]
+    pub frame_shape: Option<String>,
+    #[serde(default)]
+    pub ball_shape: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    /// As `fg_gradient`; takes precedence over `color`
+    #[serde(default)]
+    pub gradient: Option<GradientOptions>,
+}
+
+impl EyeOverrideOptions {
+    fn to_override(&self) -> EyeOverride {
+        EyeOverride {
+            frame_shape: self.frame_shape.as_deref().map(EyeFrameShape::from_str),
+            ball_shape: self.ball_shape.as_deref().map(EyeBallShape::from_str),
+            color: self.color.clone(),
+            gradient: self.gradient.as_ref().map(GradientOptions::to_gradient),
+        }
+    }
+}
+
+/// Gradient fill for the foreground or one eye
+#[derive(Serialize, Deserialize, Default)]
+pub struct GradientOptions {
+    /// `linear` (default) or `radial`
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub stops: Vec<GradientStopOptions>,
+    /// Linear direction in degrees, clockwise from left-to-right
+    #[serde(default)]
+    pub angle: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct GradientStopOptions {
+    /// 0.0 to 1.0
+    #[serde(default)]
+    pub offset: f64,
+    pub color: String,
+}
+
+impl GradientOptions {
+    fn to_gradient(&self) -> Gradient {
+        Gradient {
+            kind: self.kind.as_deref().map_or(GradientKind::Linear, GradientKind::from_str),
+            stops: self
+                .stops
+                .iter()
+                .map(|s| GradientStop { offset: s.offset, color: s.color.clone() })
+                .collect(),
+            angle: self.angle.unwrap_or(0.0),
+        }
+    }
+}
+
+/// Background pattern; the color is lightened automatically if it would
+/// hurt scanning
+#[derive(Serialize, Deserialize, Default)]
+pub struct BackgroundPatternOptions {
+    /// `dots-grid`, `diagonal-lines` or `noise`
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    /// 0.0 to 1.0
+    #[serde(default)]
+    pub density: Option<f64>,
+}
+
+impl BackgroundPatternOptions {
+    fn to_pattern(&self) -> BackgroundPattern {
+        let default = BackgroundPattern::default();
+        BackgroundPattern {
+            kind: self.kind.as_deref().map_or(default.kind, PatternKind::from_str),
+            color: self.color.clone().unwrap_or(default.color),
+            density: self.density.unwrap_or(default.density),
+        }
+    }
+}
+
+/// Center logo: either `svg` markup or a base64-encoded image file
+#[derive(Serialize, Deserialize, Default)]
+pub struct LogoOptions {
+    /// SVG elements or document, drawn in a `svg_width` x `svg_height` box.
+    /// Embedded as an image, so scripts and links in it stay inert.
+    #[serde(default)]
+    pub svg: Option<String>,
+    /// Default 100
+    #[serde(default)]
+    pub svg_width: Option<f64>,
+    /// Default 100
+    #[serde(default)]
+    pub svg_height: Option<f64>,
+    #[serde(default)]
+    pub image_base64: Option<String>,
+    /// Default `image/png`
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    /// Share of the symbol side, up to 0.4 (default 0.2)
+    #[serde(default)]
+    pub size: Option<f64>,
+    /// Modules kept clear around the image (default 1)
+    #[serde(default)]
+    pub padding: Option<usize>,
+}
+
+impl LogoOptions {
+    fn to_logo(&self) -> Result<Logo, JsValue> {
+        let image = match (&self.svg, &self.image_base64) {
+            (Some(markup), None) => LogoImage::Svg {
+                markup: markup.clone(),
+                width: self.svg_width.unwrap_or(100.0),
+                height: self.svg_height.unwrap_or(100.0),
+            },
+            (None, Some(data)) => LogoImage::Raster {
+                mime_type: self.mime_type.clone().unwrap_or_else(|| "image/png".to_string()),
+                data: base64::engine::general_purpose::STANDARD
+                    .decode(data.trim())
+                    .map_err(|e| qr_error(QrMessage::InvalidOptions(format!("logo.image_base64: {}", e))))?,
+            },
+            _ => {
+                return Err(qr_error(QrMessage::InvalidOptions(
+                    "logo: set exactly one of svg and image_base64".into(),
+                )))
+            }
+        };
+        Ok(Logo { image, size: self.size.unwrap_or(0.2), padding: self.padding.unwrap_or(1) })
+    }
 }
 
 /// Generate a QR code as an SVG string.
@@ -47,7 +275,7 @@ pub fn generate_qr_svg(text: &str) -> Result<String, JsValue> {
     let qrcode = QRBuilder::new(text)
         .ecl(ECL::M) // Medium error correction
         .build()
-        .map_err(|e| JsValue::from_str(&format!("QR generation failed: {:?}", e)))?;
+        .map_err(|e| qr_error(QrMessage::GenerationFailed(format!("{:?}", e))))?;
 
     let svg = SvgBuilder::default()
         .to_str(&qrcode);
@@ -70,13 +298,13 @@ pub fn generate_qr_svg_with_ecl(text: &str, ecl: &str) -> Result<String, JsValue
         "M" => ECL::M, // ~15% recovery
         "Q" => ECL::Q, // ~25% recovery
         "H" => ECL::H, // ~30% recovery
-        _ => return Err(JsValue::from_str("Invalid ECL. Use: L, M, Q, or H")),
+        _ => return Err(qr_error(QrMessage::InvalidEcl)),
     };
 
     let qrcode = QRBuilder::new(text)
         .ecl(error_level)
         .build()
-        .map_err(|e| JsValue::from_str(&format!("QR generation failed: {:?}", e)))?;
+        .map_err(|e| qr_error(QrMessage::GenerationFailed(format!("{:?}", e))))?;
 
     let svg = SvgBuilder::default()
         .to_str(&qrcode);
@@ -94,37 +322,571 @@ pub fn generate_qr_svg_with_ecl(text: &str, ecl: &str) -> Result<String, JsValue
 /// SVG string representation of the styled QR code
 #[wasm_bindgen]
 pub fn generate_styled_svg(text: &str, options_json: &str) -> Result<String, JsValue> {
+    styled_svg(text, options_json)
+}
+
+/// Generate a styled QR code that joins a WiFi network when scanned.
+/// 
+/// # Arguments
+/// * `ssid` - Network name
+/// * `password` - Ignored for open networks
+/// * `auth` - `WPA` (WPA/WPA2), `SAE` (WPA3), `WEP` or `nopass`
+/// * `hidden` - The network doesn't broadcast its SSID
+/// * `options_json` - Style options (as `generate_styled_svg`)
+/// 
+/// # Returns
+/// SVG string of the `WIFI:` payload, escaped for phone scanners
+#[wasm_bindgen]
+pub fn generate_wifi_qr(
+    ssid: &str,
+    password: &str,
+    auth: &str,
+    hidden: bool,
+    options_json: &str,
+) -> Result<String, JsValue> {
+    let credentials = WifiCredentials {
+        ssid: ssid.to_string(),
+        password: password.to_string(),
+        auth: WifiAuth::try_from(auth).map_err(qr_error)?,
+        hidden,
+    };
+    styled_svg(&credentials.to_payload().map_err(qr_error)?, options_json)
+}
+
+/// Structured payload for `qr_payload` / `generate_payload_qr`, selected by
+/// `kind`, e.g. `{"kind": "geo", "latitude": 40.4168, "longitude": -3.7038, "label": "Sol"}`
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PayloadOptions {
+    Wifi {
+        ssid: String,
+        #[serde(default)]
+        password: String,
+        /// `WPA` (default), `SAE`, `WEP` or `nopass`
+        #[serde(default)]
+        auth: Option<String>,
+        #[serde(default)]
+        hidden: bool,
+    },
+    Geo {
+        latitude: f64,
+        longitude: f64,
+        #[serde(default)]
+        altitude: Option<f64>,
+        #[serde(default)]
+        label: Option<String>,
+    },
+    Sms {
+        number: String,
+        #[serde(default)]
+        body: String,
+    },
+    Email {
+        to: Vec<String>,
+        #[serde(default)]
+        cc: Vec<String>,
+        #[serde(default)]
+        subject: String,
+        #[serde(default)]
+        body: String,
+    },
+    Tel {
+        number: String,
+    },
+    /// Times in seconds since the Unix epoch, UTC
+    Event {
+        summary: String,
+        start_unix: i64,
+        #[serde(default)]
+        end_unix: Option<i64>,
+        #[serde(default)]
+        all_day: bool,
+        #[serde(default)]
+        location: String,
+        #[serde(default)]
+        description: String,
+    },
+}
+
+impl PayloadOptions {
+    fn into_payload(self) -> Result<String, JsValue> {
+        let payload = match self {
+            Self::Wifi { ssid, password, auth, hidden } => {
+                let auth = match auth {
+                    Some(auth) => WifiAuth::try_from(auth.as_str()).map_err(qr_error)?,
+                    None => WifiAuth::default(),
+                };
+                WifiCredentials { ssid, password, auth, hidden }.to_payload()
+            }
+            Self::Geo { latitude, longitude, altitude, label } => GeoPoint { latitude, longitude, altitude, label }.to_payload(),
+            Self::Sms { number, body } => SmsMessage { number, body }.to_payload(),
+            Self::Email { to, cc, subject, body } => MailTo { to, cc, subject, body }.to_payload(),
+            Self::Tel { number } => Tel { number }.to_payload(),
+            Self::Event { summary, start_unix, end_unix, all_day, location, description } => {
+                VEvent { summary, start_unix, end_unix, all_day, location, description }.to_payload()
+            }
+        };
+        payload.map_err(qr_error)
+    }
+}
+
+/// Build a payload string for a WiFi login, map pin, text message, email,
+/// phone number or calendar event, escaped for phone scanners.
+/// 
+/// # Arguments
+/// * `payload_json` - JSON object with a `kind` of `wifi`, `geo`, `sms`,
+///   `email`, `tel` or `event` and that kind's fields (see `PayloadOptions`)
+/// 
+/// # Returns
+/// The text to encode
+#[wasm_bindgen]
+pub fn qr_payload(payload_json: &str) -> Result<String, JsValue> {
+    let options: PayloadOptions = serde_json::from_str(payload_json)
+        .map_err(|e| qr_error(QrMessage::InvalidOptions(e.to_string())))?;
+    options.into_payload()
+}
+
+/// Generate a styled QR code for a structured payload.
+/// 
+/// # Arguments
+/// * `payload_json` - Payload (as `qr_payload`)
+/// * `options_json` - Style options (as `generate_styled_svg`)
+/// 
+/// # Returns
+/// SVG string
+#[wasm_bindgen]
+pub fn generate_payload_qr(payload_json: &str, options_json: &str) -> Result<String, JsValue> {
+    styled_svg(&qr_payload(payload_json)?, options_json)
+}
+
+/// Render a styled QR into an internal buffer instead of returning it.
+/// 
+/// Version-40 codes with ornate shapes run to several MB; reading them back in
+/// chunks avoids one large allocation and copy across the boundary.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `options_json` - JSON string with style options (as `generate_styled_svg`)
+/// 
+/// # Returns
+/// Length of the SVG in bytes (UTF-8)
+#[wasm_bindgen]
+pub fn prepare_styled_svg(text: &str, options_json: &str) -> Result<u32, JsValue> {
+    SVG_BUFFER.with(|b| {
+        let mut buf = b.borrow_mut();
+        let rendered = styled_svg_into(text, options_json, &mut buf);
+        if rendered.is_err() {
+            buf.clear();
+        }
+        rendered.map(|()| buf.len() as u32)
+    })
+}
+
+/// Copy up to `len` bytes of the prepared SVG starting at byte `offset`.
+/// 
+/// Chunks split on byte boundaries; decode with `TextDecoder` and `{ stream: true }`.
+/// Returns an empty array past the end.
+#[wasm_bindgen]
+pub fn read_svg_chunk(offset: u32, len: u32) -> Vec<u8> {
+    SVG_BUFFER.with(|b| {
+        let buf = b.borrow();
+        let bytes = buf.as_bytes();
+        let start = (offset as usize).min(bytes.len());
+        let end = start.saturating_add(len as usize).min(bytes.len());
+        bytes[start..end].to_vec()
+    })
+}
+
+/// Write the prepared SVG from byte `offset` into a caller-provided `Uint8Array`.
+/// 
+/// # Returns
+/// Number of bytes written (0 once `offset` reaches the end)
+#[wasm_bindgen]
+pub fn write_svg_chunk(offset: u32, out: &mut [u8]) -> u32 {
+    SVG_BUFFER.with(|b| {
+        let buf = b.borrow();
+        let rest = buf.as_bytes().get(offset as usize..).unwrap_or_default();
+        let n = rest.len().min(out.len());
+        out[..n].copy_from_slice(&rest[..n]);
+        n as u32
+    })
+}
+
+/// Drop the prepared SVG and free its memory.
+#[wasm_bindgen]
+pub fn release_svg_buffer() {
+    SVG_BUFFER.with(|b| *b.borrow_mut() = String::new());
+}
+
+/// Generate a styled QR as a `data:image/svg+xml;base64,...` URI.
+/// 
+/// Ready to assign to an `<img src>`; the encoding happens here instead of in JS.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `options_json` - JSON string with style options (as `generate_styled_svg`)
+#[wasm_bindgen]
+pub fn generate_qr_data_uri(text: &str, options_json: &str) -> Result<String, JsValue> {
+    let svg = styled_svg(text, options_json)?;
+    Ok(data_uri("image/svg+xml", svg.as_bytes()))
+}
+
+/// Generate a styled QR as a `data:image/png;base64,...` URI.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `options_json` - JSON string with style options (as `generate_styled_svg`)
+/// * `width` - Output width in pixels
+#[cfg(feature = "resvg")]
+#[wasm_bindgen]
+pub fn generate_qr_png_data_uri(text: &str, options_json: &str, width: u32) -> Result<String, JsValue> {
+    let svg = styled_svg(text, options_json)?;
+    let png = holi_qr::rasterize_svg(&svg, width)
+        .map_err(qr_error)?;
+    Ok(data_uri("image/png", &png))
+}
+
+/// Generate a styled QR as PNG bytes, `PNG_MODULE_PIXELS` (10) pixels per
+/// module.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `options_json` - JSON string with style options (as `generate_styled_svg`)
+#[cfg(feature = "resvg")]
+#[wasm_bindgen]
+pub fn generate_qr_png(text: &str, options_json: &str) -> Result<Vec<u8>, JsValue> {
+    let (encoding, styled_opts) = styled_options(options_json)?;
+    let qr = encoding.generate(text)?;
+    holi_qr::render_png(&qr, &styled_opts)
+        .map_err(qr_error)
+}
+
+fn data_uri(mime: &str, bytes: &[u8]) -> String {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut uri = String::with_capacity(13 + mime.len() + base64::encoded_len(bytes.len(), true).unwrap_or(0));
+    uri.push_str("data:");
+    uri.push_str(mime);
+    uri.push_str(";base64,");
+    engine.encode_string(bytes, &mut uri);
+    uri
+}
+
+fn styled_svg(text: &str, options_json: &str) -> Result<String, JsValue> {
+    PREVIEW_BUFFER.with(|b| {
+        let mut buf = b.borrow_mut();
+        styled_svg_into(text, options_json, &mut buf)?;
+        // One exact-size copy; the buffer keeps its capacity for the next call
+        Ok(buf.as_str().to_owned())
+    })
+}
+
+fn styled_svg_into(text: &str, options_json: &str, svg: &mut String) -> Result<(), JsValue> {
+    let (encoding, styled_opts) = styled_options(options_json)?;
+    
+    // Generate QR code using holi-qr core
+    let qr = encoding.generate(text)?;
+    
+    // Render styled SVG
+    try_render_svg_styled_into(&qr, &styled_opts, svg)
+        .map_err(qr_error)
+}
+
+/// How the style options say to encode the text
+#[derive(Clone, Copy, PartialEq)]
+struct Encoding {
+    ecl: ErrorCorrectionLevel,
+    kanji: bool,
+    charset: Option<Charset>,
+}
+
+impl Encoding {
+    fn generate(&self, text: &str) -> Result<holi_qr::QrCode, JsValue> {
+        if let Some(charset) = self.charset {
+            generate_qr_eci(text, self.ecl, charset).map_err(qr_error)
+        } else if self.kanji {
+            holi_qr::generate_qr_kanji(text, self.ecl).map_err(qr_error)
+        } else {
+            generate_qr(text, self.ecl).map_err(qr_error)
+        }
+    }
+}
+
+/// Parsed options, rejecting any `validate_options` error.
+fn styled_options(options_json: &str) -> Result<(Encoding, StyledRenderOptions), JsValue> {
+    let (encoding, styled_opts, issues) = parse_styled_options(options_json)?;
+    if let Some(error) = issues.iter().find(|i| i.severity == Severity::Error) {
+        return Err(qr_error(QrMessage::InvalidOptions(format!("{}: {}", error.field, error.message))));
+    }
+    Ok((encoding, styled_opts))
+}
+
+/// Parsed options and everything wrong with them, including names that
+/// fell back to defaults.
+fn parse_styled_options(
+    options_json: &str,
+) -> Result<(Encoding, StyledRenderOptions, Vec<ValidationIssue>), JsValue> {
     // Parse options
     let opts: QRStyleOptions = serde_json::from_str(options_json)
-        .map_err(|e| JsValue::from_str(&format!("Invalid options JSON: {}", e)))?;
+        .map_err(|e| qr_error(QrMessage::InvalidOptions(e.to_string())))?;
     
+    let mut name_issues = Vec::new();
+    let mut check_shape = |field: String, name: &Option<String>| {
+        if let Some(issue) = name.as_deref().and_then(|n| holi_qr::unknown_shape_issue(&field, n)) {
+            name_issues.push(issue);
+        }
+    };
+    check_shape("body_shape".into(), &opts.body_shape);
+    check_shape("eye_frame_shape".into(), &opts.eye_frame_shape);
+    check_shape("eye_ball_shape".into(), &opts.eye_ball_shape);
+    if let Some(eyes) = &opts.eyes {
+        for (corner, eye) in EyeCorner::ALL.iter().zip([&eyes.top_left, &eyes.top_right, &eyes.bottom_left]) {
+            if let Some(eye) = eye {
+                check_shape(format!("eyes.{}.frame_shape", corner.as_str()), &eye.frame_shape);
+                check_shape(format!("eyes.{}.ball_shape", corner.as_str()), &eye.ball_shape);
+            }
+        }
+    }
+
     // Determine ECL
     let ecl = match opts.ecc.as_deref().unwrap_or("M").to_uppercase().as_str() {
         "L" => ErrorCorrectionLevel::Low,
         "M" => ErrorCorrectionLevel::Medium,
         "Q" => ErrorCorrectionLevel::Quartile,
         "H" => ErrorCorrectionLevel::High,
-        _ => ErrorCorrectionLevel::Medium,
+        other => {
+            name_issues.push(ValidationIssue {
+                severity: Severity::Warning,
+                code: "unknown-ecc",
+                field: "ecc".into(),
+                message: format!("Unknown error correction {:?}; using M", other),
+            });
+            ErrorCorrectionLevel::Medium
+        }
     };
     
-    // Generate QR code using holi-qr core
-    let qr = generate_qr(text, ecl)
-        .map_err(|e| JsValue::from_str(&format!("QR generation failed: {:?}", e)))?;
-    
     // Build styled options
     let styled_opts = StyledRenderOptions {
         margin: opts.margin.unwrap_or(4),
         fg_color: opts.fg_color.unwrap_or_else(|| "#000000".to_string()),
+        fg_gradient: opts.fg_gradient.as_ref().map(GradientOptions::to_gradient),
         bg_color: opts.bg_color.unwrap_or_else(|| "#FFFFFF".to_string()),
         body_shape: BodyShape::from_str(opts.body_shape.as_deref().unwrap_or("square")),
         eye_frame_shape: EyeFrameShape::from_str(opts.eye_frame_shape.as_deref().unwrap_or("square")),
         eye_ball_shape: EyeBallShape::from_str(opts.eye_ball_shape.as_deref().unwrap_or("square")),
+        eye_overrides: opts.eyes.map_or_else(Default::default, |eyes| {
+            [&eyes.top_left, &eyes.top_right, &eyes.bottom_left]
+                .map(|eye| eye.as_ref().map(EyeOverrideOptions::to_override).unwrap_or_default())
+        }),
+        background_pattern: opts.background_pattern.as_ref().map(BackgroundPatternOptions::to_pattern),
+        outline: opts.outline.map(|o| OutlineStyle {
+            stroke_width: o.stroke_width.unwrap_or(OutlineStyle::default().stroke_width),
+            contour_only: o.contour_only,
+        }),
+        protect_function_patterns: opts.protect_function_patterns.unwrap_or(true),
+        logo: opts.logo.as_ref().map(LogoOptions::to_logo).transpose()?,
     };
     
-    // Render styled SVG
-    let svg = render_svg_styled(&qr, &styled_opts);
+    // Unknown names only fall back with `strict` off
+    if opts.strict {
+        for issue in &mut name_issues {
+            issue.severity = Severity::Error;
+        }
+    }
+    let mut issues = holi_qr::validate_options(&styled_opts);
+    issues.extend(name_issues);
+    let kanji = opts.kanji.unwrap_or(false);
+    if kanji && cfg!(not(feature = "kanji")) {
+        issues.push(ValidationIssue {
+            severity: Severity::Warning,
+            code: "kanji-unavailable",
+            field: "kanji".into(),
+            message: "Kanji mode not available in this build; using byte mode".into(),
+        });
+    }
+    // An unknown charset is an error even without `strict`: guessing garbles the text
+    let charset = match opts.charset.as_deref().map(Charset::try_from).transpose() {
+        Ok(charset) => charset,
+        Err(e) => {
+            issues.push(ValidationIssue {
+                severity: Severity::Error,
+                code: "unknown-charset",
+                field: "charset".into(),
+                message: messages::detail(e),
+            });
+            None
+        }
+    };
+    if charset.is_some() && kanji {
+        issues.push(ValidationIssue {
+            severity: Severity::Error,
+            code: "charset-with-kanji",
+            field: "charset".into(),
+            message: "Kanji mode has no ECI header; set either kanji or charset".into(),
+        });
+    }
+    if charset == Some(Charset::ShiftJis) && cfg!(not(feature = "kanji")) {
+        issues.push(ValidationIssue {
+            severity: Severity::Error,
+            code: "charset-unavailable",
+            field: "charset".into(),
+            message: "Shift-JIS not available in this build".into(),
+        });
+    }
+    issues.sort_by_key(|issue| issue.severity != Severity::Error);
+    Ok((Encoding { ecl, kanji, charset }, styled_opts, issues))
+}
+
+/// Problems with style options, without rendering.
+/// 
+/// Errors make `generate_styled_svg` and friends reject the options;
+/// warnings (unknown shape names unless `strict`, low contrast, small
+/// margin...) render but may not scan or may not look as intended.
+/// 
+/// # Arguments
+/// * `options_json` - Style options (as `generate_styled_svg`)
+/// 
+/// # Returns
+/// JSON string: `[{ "severity": "warning", "code": "unknown-shape", "field": "body_shape", "message": "..." }]`,
+/// errors first; empty when the options are fine.
+#[wasm_bindgen]
+pub fn validate_styled_options(options_json: &str) -> Result<String, JsValue> {
+    let (_, _, issues) = parse_styled_options(options_json)?;
+    let issues: Vec<_> = issues
+        .iter()
+        .map(|i| serde_json::json!({
+            "severity": match i.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            "code": i.code,
+            "field": i.field,
+            "message": i.message,
+        }))
+        .collect();
+    Ok(serde_json::Value::from(issues).to_string())
+}
+
+/// Adjust style options to an accessibility policy and render them.
+/// 
+/// Light-on-dark themes are turned dark on light, colors move toward
+/// black/white just enough to reach `min_contrast` against the background
+/// and its pattern, a logo the error correction can't cover is shrunk, and
+/// the quiet zone shrinks (not below 4 modules) until modules are
+/// `min_module_px` wide at `display_px`.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `options_json` - Style options (as `generate_styled_svg`); colors must be hex
+/// * `display_px` - Width the code is shown at, quiet zone included, in CSS pixels
+/// * `min_contrast` - WCAG contrast ratio, 1 to 21 (0 for 7, AAA)
+/// * `min_module_px` - Smallest module in CSS pixels (0 for 4)
+/// 
+/// # Returns
+/// JSON string: `{ "options", "adjustments": [{ "field", "from", "to" }], "contrast",
+/// "modulePx", "svg" }`, where `options` is `options_json` with the adjusted
+/// values, to keep as the user's theme.
+#[wasm_bindgen]
+pub fn enforce_accessible_qr_theme(
+    text: &str,
+    options_json: &str,
+    display_px: f64,
+    min_contrast: f64,
+    min_module_px: f64,
+) -> Result<String, JsValue> {
+    let (encoding, styled_opts) = styled_options(options_json)?;
+    let qr = encoding.generate(text)?;
+    let default = AccessibilityPolicy::default();
+    let policy = AccessibilityPolicy {
+        min_contrast: if min_contrast == 0.0 { default.min_contrast } else { min_contrast },
+        min_module_px: if min_module_px == 0.0 { default.min_module_px } else { min_module_px },
+    };
+    let theme = enforce_accessible_theme(&qr, &styled_opts, display_px, &policy).map_err(qr_error)?;
+    let svg = holi_qr::try_render_svg_styled(&qr, &theme.options).map_err(qr_error)?;
+
+    // Same shape as the input, adjusted fields overwritten
+    let mut options: serde_json::Value = serde_json::from_str(options_json)
+        .map_err(|e| qr_error(QrMessage::InvalidOptions(e.to_string())))?;
+    options["fg_color"] = theme.options.fg_color.clone().into();
+    options["bg_color"] = theme.options.bg_color.clone().into();
+    options["margin"] = theme.options.margin.into();
+    let mut set_stops = |path: &str, gradient: &Option<Gradient>| {
+        for (i, stop) in gradient.iter().flat_map(|g| g.stops.iter().enumerate()) {
+            if let Some(color) = options.pointer_mut(&format!("{}/stops/{}/color", path, i)) {
+                *color = stop.color.clone().into();
+            }
+        }
+    };
+    set_stops("/fg_gradient", &theme.options.fg_gradient);
+    for corner in EyeCorner::ALL {
+        set_stops(&format!("/eyes/{}/gradient", corner.as_str()), &theme.options.eye_overrides[corner as usize].gradient);
+    }
+    for corner in EyeCorner::ALL {
+        if let Some(color) = &theme.options.eye_overrides[corner as usize].color {
+            options["eyes"][corner.as_str()]["color"] = color.clone().into();
+        }
+    }
+    if let Some(pattern) = &theme.options.background_pattern {
+        options["background_pattern"]["color"] = pattern.color.clone().into();
+    }
+    if let Some(logo) = &theme.options.logo {
+        options["logo"]["size"] = logo.size.into();
+    }
+    let adjustments: Vec<_> = theme
+        .adjustments
+        .iter()
+        .map(|a| serde_json::json!({ "field": a.field, "from": a.from, "to": a.to }))
+        .collect();
+    Ok(serde_json::json!({
+        "options": options,
+        "adjustments": adjustments,
+        "contrast": theme.contrast,
+        "modulePx": theme.module_px,
+        "svg": svg,
+    }).to_string())
+}
+
+/// Attribute patches that update a `generate_styled_svg(text, prev)` SVG to `next`.
+/// 
+/// For live style editors: apply `element.setAttribute(attribute, value)` to
+/// `svg.children[index]` instead of replacing the whole (possibly multi-MB) SVG.
+/// 
+/// # Arguments
+/// * `text` - The text/URL both SVGs encode
+/// * `prev_options_json` / `next_options_json` - Style options (as `generate_styled_svg`)
+/// 
+/// # Returns
+/// JSON string: `[{ "part": "body", "index": 1, "attribute": "d", "value": "M..." }]`,
+/// or `null` when the structure changed (ECC, Kanji mode, margin, background to/from
+/// transparent, which eyes have their own color, background pattern,
+/// gradients, outline style) and the SVG must be generated again.
+#[wasm_bindgen]
+pub fn diff_styled_svg(text: &str, prev_options_json: &str, next_options_json: &str) -> Result<String, JsValue> {
+    let (prev_encoding, prev) = styled_options(prev_options_json)?;
+    let (next_encoding, next) = styled_options(next_options_json)?;
+    if prev_encoding != next_encoding {
+        return Ok("null".to_string());
+    }
     
-    Ok(svg)
+    let qr = next_encoding.generate(text)?;
+    let Some(patches) = try_render_svg_styled_diff(&qr, &prev, &next).map_err(qr_error)? else {
+        return Ok("null".to_string());
+    };
+    
+    let patches: Vec<_> = patches
+        .into_iter()
+        .map(|p| {
+            let part = match p.part {
+                SvgPart::Background => "background",
+                SvgPart::Body => "body",
+                SvgPart::Finders => "finders",
+                SvgPart::Eye(EyeCorner::TopLeft) => "eye-top-left",
+                SvgPart::Eye(EyeCorner::TopRight) => "eye-top-right",
+                SvgPart::Eye(EyeCorner::BottomLeft) => "eye-bottom-left",
+            };
+            serde_json::json!({ "part": part, "index": p.index, "attribute": p.attribute, "value": p.value })
+        })
+        .collect();
+    Ok(serde_json::Value::from(patches).to_string())
 }
 
 #[wasm_bindgen]
@@ -150,15 +912,13 @@ pub fn generate_matrix(text: &str, ecl: &str) -> Result<QrMatrix, JsValue> {
 /// mask: 0-7 for specific pattern, -1 for auto
 #[wasm_bindgen]
 pub fn generate_matrix_with_mask(text: &str, ecl: &str, mask: i32) -> Result<QrMatrix, JsValue> {
-    use fast_qr::Mask;
-    
     let error_level = match ecl.to_uppercase().as_str() {
         "L" => ECL::L, "M" => ECL::M, "Q" => ECL::Q, "H" => ECL::H,
-        _ => return Err(JsValue::from_str("Invalid ECL")),
+        _ => return Err(qr_error(QrMessage::InvalidEcl)),
     };
 
     // Build QR code with optional mask
-    let qrcode = if mask >= 0 && mask <= 7 {
+    let qrcode = if (0..=7).contains(&mask) {
         let mask_pattern = match mask {
             0 => fast_qr::Mask::Checkerboard,
             1 => fast_qr::Mask::HorizontalLines,
@@ -178,7 +938,7 @@ pub fn generate_matrix_with_mask(text: &str, ecl: &str, mask: i32) -> Result<QrM
         QRBuilder::new(text)
             .ecl(error_level)
             .build()
-    }.map_err(|e| JsValue::from_str(&format!("Gen failed: {:?}", e)))?;
+    }.map_err(|e| qr_error(QrMessage::GenerationFailed(format!("{:?}", e))))?;
 
     // fast_qr stores data as [Module; N]
     // We need to convert to flat Vec<u8> (0/1)
@@ -192,12 +952,83 @@ pub fn generate_matrix_with_mask(text: &str, ecl: &str, mask: i32) -> Result<QrM
     })
 }
 
+/// Generate a QR code SVG no larger than `max_version`.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `ecl` - Error correction level (L, M, Q, H)
+/// * `max_version` - Largest allowed version (1-40; version 1 is 21x21 modules)
+/// 
+/// # Returns
+/// SVG string, or an error saying how many bytes over the limit the text is
+#[wasm_bindgen]
+pub fn generate_qr_svg_constrained(text: &str, ecl: &str, max_version: usize) -> Result<String, JsValue> {
+    let qr = holi_qr::generate_qr_constrained(text, parse_ecl(ecl)?, max_version)
+        .map_err(qr_error)?;
+    Ok(holi_qr::render_svg(&qr))
+}
+
+/// Generate the most robust QR code SVG that fits in `max_version`.
+/// 
+/// Picks the highest error correction level (H, Q, M, then L) that keeps
+/// the code at or below `max_version`.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `max_version` - Largest allowed version (1-40)
+/// 
+/// # Returns
+/// JSON string: `{ "ecl": "Q", "version": 3, "svg": "<svg..." }`
+#[wasm_bindgen]
+pub fn fit_best_ecc(text: &str, max_version: usize) -> Result<String, JsValue> {
+    let qr = holi_qr::fit_best_ecc(text, max_version)
+        .map_err(qr_error)?;
+    Ok(serde_json::json!({
+        "ecl": ecl_name(qr.ecl),
+        "version": (qr.size() - 17) / 4,
+        "svg": holi_qr::render_svg(&qr),
+    }).to_string())
+}
+
 /// Get the version info for this module
 #[wasm_bindgen]
 pub fn qr_version() -> String {
     "holi-wasm-qr v0.4.0 (styled shapes)".to_string()
 }
 
+/// Memory usage of this module.
+/// 
+/// # Returns
+/// `{ linearMemoryBytes, allocator, caches }`; `allocator` is null unless built
+/// with the `alloc-stats` feature. `caches.svgBufferBytes` is the prepared SVG
+/// held for chunked reads; `caches.previewBufferBytes` the scratch reused by
+/// `generate_styled_svg`.
+#[wasm_bindgen]
+pub fn qr_memory_stats() -> JsValue {
+    let svg_buffer = SVG_BUFFER.with(|b| b.borrow().capacity());
+    let preview_buffer = PREVIEW_BUFFER.with(|b| b.borrow().capacity());
+    holi_wasm_memory::MemoryStats::collect()
+        .with_cache("svgBufferBytes", svg_buffer)
+        .with_cache("previewBufferBytes", preview_buffer)
+        .to_js()
+}
+
+/// Which build of this module is running.
+/// 
+/// # Returns
+/// `{ package, version, gitHash, features, protocols }`. `features` tells the
+/// variants apart: `resvg` (default), `canvas`, or neither for the core build;
+/// `kanji` says whether the `kanji` option and the `shift_jis` charset work.
+#[wasm_bindgen]
+pub fn qr_build_info() -> JsValue {
+    holi_wasm_build_info::BuildInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .with_feature("resvg", cfg!(feature = "resvg"))
+        .with_feature("canvas", cfg!(feature = "canvas"))
+        .with_feature("kanji", cfg!(feature = "kanji"))
+        .with_feature("alloc-stats", cfg!(feature = "alloc-stats"))
+        .to_js()
+}
+
 /// Verify that an SVG string contains a scannable QR code.
 /// 
 /// # Arguments
@@ -207,8 +1038,146 @@ pub fn qr_version() -> String {
 /// Result containing the decoded text or an error message.
 #[wasm_bindgen]
 pub fn verify_qr_svg(svg: &str) -> Result<String, JsValue> {
-    verify_svg(svg)
-        .map_err(|e| JsValue::from_str(&format!("Verification failed: {:?}", e)))
+    #[cfg(all(feature = "canvas", not(feature = "resvg")))]
+    let decoded = canvas::verify_svg(svg);
+    #[cfg(not(all(feature = "canvas", not(feature = "resvg"))))]
+    let decoded = holi_qr::verify_svg(svg);
+    decoded.map_err(qr_error)
+}
+
+/// Generate a styled QR with a `logo`, refusing one the code can't survive.
+/// 
+/// Checks the modules hidden by the logo against the error correction, then
+/// decodes the rendered SVG with the `verify_qr_svg` backend (none in the
+/// core build). Use `"ecc": "H"` for larger logos.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `options_json` - Style options (as `generate_styled_svg`)
+/// 
+/// # Returns
+/// SVG string known to scan back to `text`
+#[wasm_bindgen]
+pub fn generate_qr_svg_with_logo(text: &str, options_json: &str) -> Result<String, JsValue> {
+    let (encoding, styled_opts) = styled_options(options_json)?;
+    let qr = encoding.generate(text)?;
+    // With resvg, holi-qr decodes it itself
+    let svg = render_svg_with_logo(&qr, &styled_opts).map_err(qr_error)?;
+    #[cfg(all(feature = "canvas", not(feature = "resvg")))]
+    {
+        let decoded = canvas::verify_svg(&svg).map_err(qr_error)?;
+        if decoded != qr.text {
+            return Err(qr_error(holi_qr::QrError::VerificationFailed(format!(
+                "Decoded {:?} instead of the payload",
+                decoded
+            ))));
+        }
+    }
+    Ok(svg)
+}
+
+/// How much of the code the `logo` in the options hides.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `options_json` - Style options (as `generate_styled_svg`), with a `logo`
+/// 
+/// # Returns
+/// JSON string: `{ "clearedModules", "damagedCodewords", "correctableCodewords",
+/// "coversFunctionPatterns", "fits" }`, or `null` without a logo. `fits` is a
+/// rough bound; `generate_qr_svg_with_logo` also decodes the result.
+#[wasm_bindgen]
+pub fn check_qr_logo(text: &str, options_json: &str) -> Result<String, JsValue> {
+    let (encoding, styled_opts) = styled_options(options_json)?;
+    let Some(logo) = &styled_opts.logo else {
+        return Ok("null".to_string());
+    };
+    let qr = encoding.generate(text)?;
+    let fit = check_logo(&qr, logo);
+    Ok(serde_json::json!({
+        "clearedModules": fit.cleared_modules,
+        "damagedCodewords": fit.damaged_codewords,
+        "correctableCodewords": fit.correctable_codewords,
+        "coversFunctionPatterns": fit.covers_function_patterns,
+        "fits": fit.fits(),
+    }).to_string())
+}
+
+/// Verify many SVGs in one call, e.g. a whole label sheet.
+/// 
+/// # Arguments
+/// * `svgs` - The SVG strings to verify
+/// 
+/// # Returns
+/// JSON string: `{ "results": [{ "ok": true, "text" } | { "ok": false, "error" }],
+/// "stats": { "total", "scannable", "failed" } }`, results in input order and `failed` the
+/// indices that did not scan. Errors are in the current locale (see `set_locale`).
+#[wasm_bindgen]
+pub fn verify_qr_svg_batch(svgs: Vec<String>) -> String {
+    #[cfg(all(feature = "canvas", not(feature = "resvg")))]
+    let results: Vec<holi_qr::VerifyResult> = svgs.iter().map(|svg| canvas::verify_svg(svg)).collect();
+    #[cfg(not(all(feature = "canvas", not(feature = "resvg"))))]
+    let results = holi_qr::verify_batch(&svgs);
+
+    let stats = holi_qr::BatchStats::from_results(&results);
+    let results: Vec<_> = results
+        .into_iter()
+        .map(|r| match r {
+            Ok(text) => serde_json::json!({ "ok": true, "text": text }),
+            Err(e) => serde_json::json!({ "ok": false, "error": holi_wasm_i18n::localize(&QrMessage::from(e)) }),
+        })
+        .collect();
+    serde_json::json!({
+        "results": results,
+        "stats": { "total": stats.total, "scannable": stats.scannable, "failed": stats.failed },
+    }).to_string()
+}
+
+/// Which renderer `verify_qr_svg` uses: `"resvg"`, `"canvas"` or `"none"`.
+#[wasm_bindgen]
+pub fn qr_verify_backend() -> String {
+    if cfg!(feature = "resvg") {
+        "resvg"
+    } else if cfg!(feature = "canvas") {
+        "canvas"
+    } else {
+        "none"
+    }
+    .to_string()
+}
+
+/// Optional subsystems compiled into this build.
+/// 
+/// The core build (`build:core`, no default features) leaves them all out;
+/// `loader.js` checks this before fetching the full build on demand.
+/// 
+/// # Returns
+/// JSON string: `{ "verify": true, "raster": true, "decodeImage": true, "artistic": true }`
+#[wasm_bindgen]
+pub fn qr_capabilities() -> String {
+    let resvg = cfg!(feature = "resvg");
+    serde_json::json!({
+        "verify": resvg || cfg!(feature = "canvas"),
+        "raster": resvg,
+        "decodeImage": resvg,
+        "artistic": resvg,
+    }).to_string()
+}
+
+/// Rasterize an SVG (e.g. from `generate_styled_svg`) to PNG.
+/// 
+/// # Arguments
+/// * `svg` - The SVG string content
+/// * `width` - Output width in pixels; height keeps the aspect ratio
+/// 
+/// # Returns
+/// PNG file bytes or an error message. Either side over 4096 pixels
+/// (`MAX_RASTER_SIZE`) is an error.
+#[cfg(feature = "resvg")]
+#[wasm_bindgen]
+pub fn rasterize_qr_svg(svg: &str, width: u32) -> Result<Vec<u8>, JsValue> {
+    holi_qr::rasterize_svg(svg, width)
+        .map_err(qr_error)
 }
 
 /// Decode a QR code from image bytes (PNG/JPEG).
@@ -218,9 +1187,495 @@ pub fn verify_qr_svg(svg: &str) -> Result<String, JsValue> {
 /// 
 /// # Returns
 /// Result containing the decoded text or an error message.
+#[cfg(feature = "resvg")]
 #[wasm_bindgen]
 pub fn decode_qr_image(image_data: &[u8]) -> Result<String, JsValue> {
-    decode_image(image_data)
-        .map_err(|e| JsValue::from_str(&format!("Decode failed: {:?}", e)))
+    holi_qr::decode_image(image_data)
+        .map_err(|e| qr_error(QrMessage::DecodeFailed(messages::detail(e))))
+}
+
+/// Bytes in a `width` x `height` RGBA image; `None` if that overflows `usize`,
+/// as it can on wasm32
+fn rgba_len(width: u32, height: u32) -> Option<usize> {
+    (width as usize).checked_mul(height as usize)?.checked_mul(4)
+}
+
+/// Decode a QR code from RGBA pixels (e.g. canvas `ImageData.data`).
+/// 
+/// Works with either backend; the canvas build decodes uploaded images this
+/// way after drawing them with `createImageBitmap`.
+/// 
+/// # Arguments
+/// * `rgba` - `width * height * 4` bytes, alpha ignored
+/// * `width` / `height` - Image size in pixels
+/// 
+/// # Returns
+/// Result containing the decoded text or an error message.
+#[wasm_bindgen]
+pub fn decode_qr_rgba(rgba: &[u8], width: u32, height: u32) -> Result<String, JsValue> {
+    if rgba_len(width, height) != Some(rgba.len()) {
+        return Err(qr_error(QrMessage::InvalidArgument(format!(
+            "Expected {} RGBA bytes for {}x{}, got {}",
+            width as u64 * height as u64 * 4,
+            width,
+            height,
+            rgba.len()
+        ))));
+    }
+    decode_luma(holi_qr::rgba_to_luma(rgba), width, height)
+        .map_err(|e| qr_error(QrMessage::DecodeFailed(messages::detail(e))))
+}
+
+/// Decode RGBA pixels like `decode_qr_rgba`, reporting how far decoding got
+/// instead of failing, so the scanner can tell a missing code from a damaged one.
+///
+/// # Arguments
+/// * `rgba` - `width * height * 4` bytes, alpha ignored
+/// * `width` / `height` - Image size in pixels
+///
+/// # Returns
+/// JSON string: `{ "stage", "found", "text", "confidence", "finderPatterns", "points", "version",
+/// "ecl", "mask", "blocks", "failedBlocks", "correctedCodewords", "correctableCodewords" }`, where
+/// `stage` is `"notFound"`, `"formatUnreadable"`, `"dataDamaged"` or `"decoded"`.
+#[wasm_bindgen]
+pub fn diagnose_qr_rgba(rgba: &[u8], width: u32, height: u32) -> Result<String, JsValue> {
+    if rgba_len(width, height) != Some(rgba.len()) {
+        return Err(qr_error(QrMessage::InvalidArgument(format!(
+            "Expected {} RGBA bytes for {}x{}, got {}",
+            width as u64 * height as u64 * 4,
+            width,
+            height,
+            rgba.len()
+        ))));
+    }
+    let d = holi_qr::diagnose_luma(holi_qr::rgba_to_luma(rgba), width, height)
+        .map_err(|e| qr_error(QrMessage::DecodeFailed(messages::detail(e))))?;
+
+    Ok(serde_json::json!({
+        "stage": d.stage.as_str(),
+        "found": d.stage.found(),
+        "confidence": d.confidence(),
+        "text": d.text,
+        "finderPatterns": d.finder_patterns,
+        "points": d.points,
+        "version": d.version,
+        "ecl": d.ecl.map(ecl_name),
+        "mask": d.mask,
+        "blocks": d.blocks,
+        "failedBlocks": d.failed_blocks,
+        "correctedCodewords": d.corrected_codewords,
+        "correctableCodewords": d.correctable_codewords,
+    }).to_string())
+}
+
+/// Check a few consecutive camera frames for focus, lighting and motion.
+///
+/// Meant for the scanner to call every few hundred milliseconds with the
+/// last frames it grabbed, and to show the first hint to the user.
+///
+/// # Arguments
+/// * `rgba` - The frames back to back, oldest first, `width * height * 4` bytes each
+/// * `width` / `height` - Frame size in pixels
+///
+/// # Returns
+/// JSON string: `{ "hints", "brightness", "glare", "textured", "sharpness", "motion", "text",
+/// "coverage" }`, where `hints` lists `"tooDark"`, `"glare"`, `"holdStill"`, `"blurry"`,
+/// `"lowContrast"` or `"tooFar"` most important first, and `text` is the decoded payload (or `null`).
+#[wasm_bindgen]
+pub fn analyze_scan_frames(rgba: &[u8], width: u32, height: u32) -> Result<String, JsValue> {
+    let frame_len = rgba_len(width, height)
+        .filter(|&len| len > 0 && !rgba.is_empty() && rgba.len().is_multiple_of(len));
+    let Some(frame_len) = frame_len else {
+        return Err(qr_error(QrMessage::InvalidArgument(format!(
+            "Expected whole {}x{} RGBA frames, got {} bytes",
+            width,
+            height,
+            rgba.len()
+        ))));
+    };
+    let lumas: Vec<Vec<u8>> = rgba.chunks(frame_len).map(holi_qr::rgba_to_luma).collect();
+    let frames: Vec<&[u8]> = lumas.iter().map(Vec::as_slice).collect();
+    let analysis = holi_qr::analyze_frames(&frames, width, height)
+        .map_err(qr_error)?;
+    let hints: Vec<_> = analysis.hints.iter().map(|h| h.as_str()).collect();
+
+    Ok(serde_json::json!({
+        "hints": hints,
+        "brightness": analysis.stats.brightness,
+        "glare": analysis.stats.glare,
+        "textured": analysis.stats.textured,
+        "sharpness": analysis.stats.sharpness,
+        "motion": analysis.motion,
+        "text": analysis.text,
+        "coverage": analysis.coverage,
+    }).to_string())
+}
+
+/// Options for `generate_artistic_qr` (JSON-serializable for WASM)
+#[derive(Serialize, Deserialize, Default)]
+pub struct ArtisticStyleOptions {
+    /// Error correction (default H: tinting costs contrast)
+    #[serde(default)]
+    pub ecc: Option<String>,
+    #[serde(default)]
+    pub margin: Option<usize>,
+    /// Highest luma (0-255) kept by dark modules
+    #[serde(default)]
+    pub dark_max_luma: Option<u8>,
+    /// Lowest luma (0-255) kept by light modules
+    #[serde(default)]
+    pub light_min_luma: Option<u8>,
 }
 
+/// Generate a QR tinted toward a reference image, verified to scan.
+/// 
+/// Each module takes the colors of the image behind it, darkened or
+/// lightened as needed; if the result doesn't scan, the contrast is raised
+/// and it is rendered again.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `rgba` - Reference image pixels (e.g. canvas `ImageData.data`), `width * height * 4` bytes
+/// * `width` / `height` - Image size in pixels; the central square is used
+/// * `options_json` - `{ "ecc", "margin", "dark_max_luma", "light_min_luma" }`, all optional
+/// 
+/// # Returns
+/// JSON string: `{ "svg", "darkMaxLuma", "lightMinLuma", "attempts" }`
+#[cfg(feature = "resvg")]
+#[wasm_bindgen]
+pub fn generate_artistic_qr(
+    text: &str,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    options_json: &str,
+) -> Result<String, JsValue> {
+    let opts: ArtisticStyleOptions = serde_json::from_str(options_json)
+        .map_err(|e| qr_error(QrMessage::InvalidOptions(e.to_string())))?;
+    let ecl = match opts.ecc.as_deref().unwrap_or("H").to_uppercase().as_str() {
+        "L" => ErrorCorrectionLevel::Low,
+        "M" => ErrorCorrectionLevel::Medium,
+        "Q" => ErrorCorrectionLevel::Quartile,
+        "H" => ErrorCorrectionLevel::High,
+        _ => return Err(qr_error(QrMessage::InvalidEcl)),
+    };
+    let defaults = holi_qr::ArtisticOptions::default();
+    let options = holi_qr::ArtisticOptions {
+        margin: opts.margin.unwrap_or(defaults.margin),
+        dark_max_luma: opts.dark_max_luma.unwrap_or(defaults.dark_max_luma),
+        light_min_luma: opts.light_min_luma.unwrap_or(defaults.light_min_luma),
+        ..defaults
+    };
+
+    let qr = generate_qr(text, ecl)
+        .map_err(qr_error)?;
+    let art = holi_qr::render_artistic(&qr, rgba, width, height, &options)
+        .map_err(qr_error)?;
+    Ok(serde_json::json!({
+        "svg": art.svg,
+        "darkMaxLuma": art.dark_max_luma,
+        "lightMinLuma": art.light_min_luma,
+        "attempts": art.attempts,
+    }).to_string())
+}
+
+
+fn risk_name(risk: PayloadRisk) -> &'static str {
+    match risk {
+        PayloadRisk::DataUri => "dataUri",
+        PayloadRisk::ScriptUri => "scriptUri",
+        PayloadRisk::PunycodeHost => "punycodeHost",
+        PayloadRisk::NonAsciiHost => "nonAsciiHost",
+        PayloadRisk::IpAddressHost => "ipAddressHost",
+        PayloadRisk::CredentialsInUrl => "credentialsInUrl",
+        PayloadRisk::InsecureHttp => "insecureHttp",
+        PayloadRisk::ExecutableDownload => "executableDownload",
+        PayloadRisk::OpenWifi => "openWifi",
+    }
+}
+
+/// Classify decoded QR content and flag risky patterns.
+/// 
+/// # Arguments
+/// * `text` - The decoded QR text
+/// 
+/// # Returns
+/// JSON string: `{ "kind": "url", ..., "risks": ["insecureHttp"], "warnings": [...], "risky": true }`
+/// where `warnings` are the risks as sentences in the current locale (see `set_locale`).
+#[wasm_bindgen]
+pub fn classify_qr_payload(text: &str) -> String {
+    let classification = classify_payload(text);
+    let mut value = match &classification.kind {
+        PayloadKind::Url { scheme, host } => serde_json::json!({ "kind": "url", "scheme": scheme, "host": host }),
+        PayloadKind::Wifi { ssid, security, hidden } => serde_json::json!({ "kind": "wifi", "ssid": ssid, "security": security, "hidden": hidden }),
+        PayloadKind::VCard { name } => serde_json::json!({ "kind": "vcard", "name": name }),
+        PayloadKind::CryptoAddress { currency, address } => serde_json::json!({ "kind": "crypto", "currency": currency, "address": address }),
+        PayloadKind::Email { address } => serde_json::json!({ "kind": "email", "address": address }),
+        PayloadKind::Phone { number } => serde_json::json!({ "kind": "phone", "number": number }),
+        PayloadKind::Text => serde_json::json!({ "kind": "text" }),
+    };
+    let risks: Vec<&str> = classification.risks.iter().map(|r| risk_name(*r)).collect();
+    value["risks"] = serde_json::json!(risks);
+    let warnings: Vec<String> = classification
+        .risks
+        .iter()
+        .map(|r| holi_wasm_i18n::localize(&QrMessage::Risk(*r)))
+        .collect();
+    value["warnings"] = serde_json::json!(warnings);
+    value["risky"] = serde_json::json!(classification.is_risky());
+    value.to_string()
+}
+
+/// Minimum print size and raster resolution for scanning from a distance.
+/// 
+/// # Arguments
+/// * `text` - The text/URL that will be encoded
+/// * `ecl` - Error correction level (L, M, Q, H)
+/// * `scan_distance_m` - Expected scan distance in meters
+/// * `dpi` - Printer resolution (300 is typical)
+/// 
+/// # Returns
+/// JSON string: `{ "modules", "moduleSizeMm", "minSizeMm", "dpi", "pixelsPerModule", "pixelSize" }`
+#[wasm_bindgen]
+pub fn qr_print_requirements(text: &str, ecl: &str, scan_distance_m: f64, dpi: u32) -> Result<String, JsValue> {
+    let level = match ecl.to_uppercase().as_str() {
+        "L" => ErrorCorrectionLevel::Low,
+        "M" => ErrorCorrectionLevel::Medium,
+        "Q" => ErrorCorrectionLevel::Quartile,
+        "H" => ErrorCorrectionLevel::High,
+        _ => return Err(qr_error(QrMessage::InvalidEcl)),
+    };
+    let qr = generate_qr(text, level)
+        .map_err(qr_error)?;
+    let req = print_requirements_at_dpi(&qr, scan_distance_m, dpi)
+        .map_err(qr_error)?;
+
+    Ok(serde_json::json!({
+        "modules": req.modules,
+        "moduleSizeMm": req.module_size_mm,
+        "minSizeMm": req.min_size_mm,
+        "dpi": req.dpi,
+        "pixelsPerModule": req.pixels_per_module,
+        "pixelSize": req.pixel_size,
+    }).to_string())
+}
+
+/// How a payload is encoded, and rewrites that would make the code smaller.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `ecl` - Error correction level (L, M, Q, H)
+/// 
+/// # Returns
+/// JSON string: `{ "segments": [{ "mode", "length", "bits" }], "hints": [{ "kind", "text",
+/// "lossless", "bitsSaved", "modulesSaved", "version" }] }`, where `kind` is `"uppercase"` or
+/// `"stripScheme"` and `lossless` is false when scanners may read `text` differently.
+#[wasm_bindgen]
+pub fn qr_payload_hints(text: &str, ecl: &str) -> Result<String, JsValue> {
+    let ecl = parse_ecl(ecl)?;
+    let qr = generate_qr(text, ecl)
+        .map_err(qr_error)?;
+    let segments: Vec<_> = qr
+        .segments()
+        .iter()
+        .map(|s| serde_json::json!({ "mode": s.mode_name(), "length": s.length, "bits": s.bits }))
+        .collect();
+    let hints: Vec<_> = optimize_payload(text, ecl)
+        .into_iter()
+        .map(|h| serde_json::json!({
+            "kind": h.kind.as_str(),
+            "text": h.text,
+            "lossless": h.lossless,
+            "bitsSaved": h.bits_saved,
+            "modulesSaved": h.modules_saved,
+            "version": h.version,
+        }))
+        .collect();
+
+    Ok(serde_json::json!({ "segments": segments, "hints": hints }).to_string())
+}
+
+/// Role of every module and the raw codewords, for teaching views and debugging.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `ecl` - Error correction level (L, M, Q, H)
+/// 
+/// # Returns
+/// JSON string: `{ "size", "version", "mask", "roles", "codewordIndex", "codewords", "dataCodewords" }`,
+/// where `roles` holds one name per module (row by row, e.g. `"finder"`, `"data"`, `"ecc"`)
+/// and `codewordIndex` the codeword each data/ECC module belongs to (`null` otherwise).
+#[wasm_bindgen]
+pub fn qr_debug_layout(text: &str, ecl: &str) -> Result<String, JsValue> {
+    let qr = generate_qr(text, parse_ecl(ecl)?)
+        .map_err(qr_error)?;
+    let layout = qr.debug_layout();
+    let roles: Vec<_> = layout.roles.iter().map(|r| r.as_str()).collect();
+
+    Ok(serde_json::json!({
+        "size": layout.size,
+        "version": layout.version,
+        "mask": layout.mask,
+        "roles": roles,
+        "codewordIndex": layout.codeword_index,
+        "codewords": layout.codewords,
+        "dataCodewords": layout.data_codewords,
+    }).to_string())
+}
+
+/// Render a QR code with modules colored by role (finder, timing, data, ECC...).
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `ecl` - Error correction level (L, M, Q, H)
+/// 
+/// # Returns
+/// SVG string; colors per role are listed in `qr_debug_legend`
+#[wasm_bindgen]
+pub fn render_qr_layout_svg(text: &str, ecl: &str) -> Result<String, JsValue> {
+    let qr = generate_qr(text, parse_ecl(ecl)?)
+        .map_err(qr_error)?;
+    holi_qr::try_render_svg_layout(&qr, 4).map_err(qr_error)
+}
+
+/// Colors used by `render_qr_layout_svg`.
+/// 
+/// # Returns
+/// JSON string: `[{ "role": "finder", "color": "#C62828" }]`
+#[wasm_bindgen]
+pub fn qr_debug_legend() -> String {
+    let legend: Vec<_> = ModuleRole::ALL
+        .iter()
+        .map(|r| serde_json::json!({ "role": r.as_str(), "color": r.color() }))
+        .collect();
+    serde_json::Value::from(legend).to_string()
+}
+
+/// Function-pattern modules drawn with a fuller shape than `body_shape`
+/// asks for, so the UI can tell the user why their shape isn't used there.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `options_json` - Style options (as `generate_styled_svg`)
+/// 
+/// # Returns
+/// JSON string: `[{ "role": "timing", "requested": "cross", "used": "square", "modules": 9 }]`,
+/// empty when nothing is substituted or `protect_function_patterns` is false.
+#[wasm_bindgen]
+pub fn qr_shape_substitutions(text: &str, options_json: &str) -> Result<String, JsValue> {
+    let (encoding, opts) = styled_options(options_json)?;
+    if !opts.protect_function_patterns {
+        return Ok("[]".to_string());
+    }
+    let qr = encoding.generate(text)?;
+
+    let substitutions: Vec<_> = holi_qr::shape_substitutions(&qr, opts.body_shape)
+        .iter()
+        .map(|s| serde_json::json!({
+            "role": s.role.as_str(),
+            "requested": s.requested.as_str(),
+            "used": s.used.as_str(),
+            "modules": s.modules,
+        }))
+        .collect();
+    Ok(serde_json::Value::from(substitutions).to_string())
+}
+
+fn ecl_name(ecl: ErrorCorrectionLevel) -> &'static str {
+    match ecl {
+        ErrorCorrectionLevel::Low => "L",
+        ErrorCorrectionLevel::Medium => "M",
+        ErrorCorrectionLevel::Quartile => "Q",
+        ErrorCorrectionLevel::High => "H",
+    }
+}
+
+fn parse_ecl(ecl: &str) -> Result<ErrorCorrectionLevel, JsValue> {
+    match ecl.to_uppercase().as_str() {
+        "L" => Ok(ErrorCorrectionLevel::Low),
+        "M" => Ok(ErrorCorrectionLevel::Medium),
+        "Q" => Ok(ErrorCorrectionLevel::Quartile),
+        "H" => Ok(ErrorCorrectionLevel::High),
+        _ => Err(qr_error(QrMessage::InvalidEcl)),
+    }
+}
+
+/// Named style presets for one-click styling.
+/// 
+/// # Returns
+/// JSON string: `[{ "name": "neon", "description": "...", "options": {...} }]`, where
+/// `options` is accepted as-is by `generate_styled_svg`, so a preset can be
+/// tweaked further.
+#[wasm_bindgen]
+pub fn list_presets() -> String {
+    let presets: Vec<_> = PRESETS
+        .iter()
+        .map(|p| {
+            serde_json::json!({
+                "name": p.name,
+                "description": p.description,
+                "options": {
+                    "fg_color": p.fg_color,
+                    "bg_color": p.bg_color,
+                    "body_shape": p.body_shape.as_str(),
+                    "eye_frame_shape": p.eye_frame_shape.as_str(),
+                    "eye_ball_shape": p.eye_ball_shape.as_str(),
+                    "ecc": ecl_name(p.ecl),
+                },
+            })
+        })
+        .collect();
+    serde_json::Value::from(presets).to_string()
+}
+
+/// Generate a styled QR code with a named preset (see `list_presets`).
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `name` - Preset name, e.g. `"pastel-dots"`
+/// 
+/// # Returns
+/// SVG string representation of the styled QR code
+#[wasm_bindgen]
+pub fn render_with_preset(text: &str, name: &str) -> Result<String, JsValue> {
+    if holi_qr::preset(name).is_none() {
+        return Err(qr_error(QrMessage::UnknownPreset(name.to_string())));
+    }
+    holi_qr::render_with_preset(text, name)
+        .map_err(qr_error)
+}
+
+/// Generate a binary STL of the QR code for 3D printing.
+/// 
+/// # Arguments
+/// * `text` - The text/URL to encode
+/// * `ecl` - Error correction level (L, M, Q, H); H is recommended for prints
+/// * `module_size` - Side of one module in mm
+/// * `base_height` - Plate thickness in mm
+/// * `module_height` - Height of the dark modules above the plate in mm
+/// * `margin` - Quiet zone in modules, at most 64
+/// 
+/// # Returns
+/// STL file bytes (save as `.stl`)
+#[wasm_bindgen]
+pub fn generate_qr_stl(
+    text: &str,
+    ecl: &str,
+    module_size: f64,
+    base_height: f64,
+    module_height: f64,
+    margin: usize,
+) -> Result<Vec<u8>, JsValue> {
+    let level = match ecl.to_uppercase().as_str() {
+        "L" => ErrorCorrectionLevel::Low,
+        "M" => ErrorCorrectionLevel::Medium,
+        "Q" => ErrorCorrectionLevel::Quartile,
+        "H" => ErrorCorrectionLevel::High,
+        _ => return Err(qr_error(QrMessage::InvalidEcl)),
+    };
+    let qr = generate_qr(text, level)
+        .map_err(qr_error)?;
+    let options = ExtrudeOptions { module_size, base_height, module_height, margin };
+    holi_qr::render_stl(&qr, &options)
+        .map_err(qr_error)
+}
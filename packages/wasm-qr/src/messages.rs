@@ -0,0 +1,113 @@
+//! User-facing errors and scannability warnings (en/es).
+
+use holi_qr::{PayloadRisk, QrError};
+use holi_wasm_i18n::{Locale, Message};
+use wasm_bindgen::JsValue;
+
+pub enum QrMessage {
+    EmptyInput,
+    InputTooLong { length: usize },
+    ExceedsMaxVersion { max_version: usize, over: usize },
+    GenerationFailed(String),
+    InvalidEcl,
+    InvalidOptions(String),
+    UnknownPreset(String),
+    InvalidArgument(String),
+    NotScannable(String),
+    DecodeFailed(String),
+    RasterizationFailed(String),
+    Risk(PayloadRisk),
+}
+
+impl From<QrError> for QrMessage {
+    fn from(e: QrError) -> Self {
+        match e {
+            QrError::EmptyInput => QrMessage::EmptyInput,
+            QrError::InputTooLong { length } => QrMessage::InputTooLong { length },
+            QrError::ExceedsMaxVersion { max_version, over, .. } => QrMessage::ExceedsMaxVersion { max_version, over },
+            QrError::GenerationFailed(d) => QrMessage::GenerationFailed(d),
+            QrError::InvalidArgument(d) => QrMessage::InvalidArgument(d),
+            QrError::RenderFailed(d) => QrMessage::RasterizationFailed(d),
+            QrError::VerificationFailed(d) => QrMessage::NotScannable(d),
+        }
+    }
+}
+
+impl Message for QrMessage {
+    fn text(&self, locale: Locale) -> String {
+        use QrMessage::*;
+        match (self, locale) {
+            (EmptyInput, Locale::En) => "Input text cannot be empty".into(),
+            (EmptyInput, Locale::Es) => "El texto no puede estar vacío".into(),
+            (InputTooLong { length }, Locale::En) => format!(
+                "Input is too long for a QR code ({length} characters). Shorten it or lower the error correction"
+            ),
+            (InputTooLong { length }, Locale::Es) => format!(
+                "El texto es demasiado largo para un código QR ({length} caracteres). Acórtalo o reduce la corrección de errores"
+            ),
+            (ExceedsMaxVersion { max_version, over }, Locale::En) => format!(
+                "Input is {over} characters too long for a version {max_version} QR code. Shorten it or lower the error correction"
+            ),
+            (ExceedsMaxVersion { max_version, over }, Locale::Es) => format!(
+                "El texto tiene {over} caracteres de más para un código QR de versión {max_version}. Acórtalo o reduce la corrección de errores"
+            ),
+            (GenerationFailed(d), Locale::En) => format!("QR generation failed: {d}"),
+            (GenerationFailed(d), Locale::Es) => format!("No se pudo generar el código QR: {d}"),
+            (InvalidEcl, Locale::En) => "Invalid ECL. Use: L, M, Q, or H".into(),
+            (InvalidEcl, Locale::Es) => "Nivel de corrección no válido. Usa: L, M, Q o H".into(),
+            (InvalidOptions(d), Locale::En) => format!("Invalid options JSON: {d}"),
+            (InvalidOptions(d), Locale::Es) => format!("JSON de opciones no válido: {d}"),
+            (UnknownPreset(name), Locale::En) => format!("Unknown style preset: {name}"),
+            (UnknownPreset(name), Locale::Es) => format!("Estilo predefinido desconocido: {name}"),
+            (InvalidArgument(d), Locale::En) => format!("Invalid argument: {d}"),
+            (InvalidArgument(d), Locale::Es) => format!("Argumento no válido: {d}"),
+            (NotScannable(d), Locale::En) => format!("The QR code is not scannable: {d}"),
+            (NotScannable(d), Locale::Es) => format!("El código QR no se puede escanear: {d}"),
+            (DecodeFailed(d), Locale::En) => format!("No QR code could be read from the image: {d}"),
+            (DecodeFailed(d), Locale::Es) => format!("No se pudo leer ningún código QR de la imagen: {d}"),
+            (RasterizationFailed(d), Locale::En) => format!("Rasterization failed: {d}"),
+            (RasterizationFailed(d), Locale::Es) => format!("No se pudo convertir a imagen: {d}"),
+            (Risk(risk), locale) => risk_text(*risk, locale).into(),
+        }
+    }
+}
+
+fn risk_text(risk: PayloadRisk, locale: Locale) -> &'static str {
+    use PayloadRisk::*;
+    match (risk, locale) {
+        (DataUri, Locale::En) => "Opens embedded content (data: link) instead of a website",
+        (DataUri, Locale::Es) => "Abre contenido incrustado (enlace data:) en lugar de un sitio web",
+        (ScriptUri, Locale::En) => "Runs a script when opened",
+        (ScriptUri, Locale::Es) => "Ejecuta un script al abrirse",
+        (PunycodeHost, Locale::En) => "The domain may imitate a well-known site (punycode)",
+        (PunycodeHost, Locale::Es) => "El dominio podría imitar a un sitio conocido (punycode)",
+        (NonAsciiHost, Locale::En) => "The domain contains unusual characters that may imitate another site",
+        (NonAsciiHost, Locale::Es) => "El dominio contiene caracteres inusuales que podrían imitar a otro sitio",
+        (IpAddressHost, Locale::En) => "Links to a raw IP address instead of a domain",
+        (IpAddressHost, Locale::Es) => "Enlaza a una dirección IP en lugar de un dominio",
+        (CredentialsInUrl, Locale::En) => "The link hides its real destination behind a user name",
+        (CredentialsInUrl, Locale::Es) => "El enlace oculta su destino real tras un nombre de usuario",
+        (InsecureHttp, Locale::En) => "The connection is not encrypted (http)",
+        (InsecureHttp, Locale::Es) => "La conexión no está cifrada (http)",
+        (ExecutableDownload, Locale::En) => "Downloads an app or installer",
+        (ExecutableDownload, Locale::Es) => "Descarga una aplicación o instalador",
+        (OpenWifi, Locale::En) => "The WiFi network has no password",
+        (OpenWifi, Locale::Es) => "La red WiFi no tiene contraseña",
+    }
+}
+
+/// Localized JS error for `msg`.
+pub fn qr_error(msg: impl Into<QrMessage>) -> JsValue {
+    holi_wasm_i18n::js_error(&msg.into())
+}
+
+/// The underlying detail of an error, without the `QrError` prefix.
+pub fn detail(e: QrError) -> String {
+    match e {
+        QrError::GenerationFailed(d)
+        | QrError::InvalidArgument(d)
+        | QrError::RenderFailed(d)
+        | QrError::VerificationFailed(d) => d,
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,119 @@
+//! Color spaces: how instance and gradient colors are read, and a swatch
+//! test pattern for checking rendered colors against the same CSS colors.
+//!
+//! Shading happens in linear light. Input colors are decoded from sRGB
+//! unless set to linear, and the output is sRGB-encoded in the shader when
+//! the swapchain format doesn't do it (`bgra8unorm` on most WebGPU browsers).
+
+use crate::mesh::Instance;
+
+/// Encoding of colors passed in from JS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// CSS-style values (`#808080` is 0.5): decoded to linear before shading
+    #[default]
+    Srgb,
+    /// Already linear, used as-is
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "srgb" => Self::Srgb,
+            "linear" => Self::Linear,
+            _ => return None,
+        })
+    }
+}
+
+/// `[decode input sRGB, encode output sRGB, 0, 0]` uniform.
+pub fn color_uniform(input: ColorSpace, target_format: wgpu::TextureFormat) -> [f32; 4] {
+    let decode = if input == ColorSpace::Srgb { 1.0 } else { 0.0 };
+    let encode = if target_format.is_srgb() { 0.0 } else { 1.0 };
+    [decode, encode, 0.0, 0.0]
+}
+
+/// Canvas clear color for `rgba` given in `input` space, premultiplied as
+/// the canvas expects.
+pub fn clear_color(rgba: [f32; 4], input: ColorSpace, target_format: wgpu::TextureFormat) -> wgpu::Color {
+    let [r, g, b, a] = rgba.map(|c| c.clamp(0.0, 1.0));
+    // sRGB targets encode on write, so they take linear values
+    let to_target = |c: f32| match (input, target_format.is_srgb()) {
+        (ColorSpace::Srgb, true) => srgb_to_linear(c),
+        (ColorSpace::Linear, false) => linear_to_srgb(c),
+        _ => c,
+    };
+    wgpu::Color {
+        r: (to_target(r) * a) as f64,
+        g: (to_target(g) * a) as f64,
+        b: (to_target(b) * a) as f64,
+        a: a as f64,
+    }
+}
+
+/// Test pattern swatches, row-major from the top left: a gray ramp, then
+/// primaries, secondaries and mid-tones.
+pub const TEST_SWATCHES: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x40, 0x40, 0x40],
+    [0x80, 0x80, 0x80],
+    [0xff, 0xff, 0xff],
+    [0xff, 0x00, 0x00],
+    [0x00, 0xff, 0x00],
+    [0x00, 0x00, 0xff],
+    [0xc0, 0xc0, 0xc0],
+    [0xff, 0xff, 0x00],
+    [0x00, 0xff, 0xff],
+    [0xff, 0x00, 0xff],
+    [0x80, 0x00, 0x00],
+    [0xff, 0x80, 0x00],
+    [0x80, 0x00, 0xff],
+    [0x00, 0x80, 0xff],
+    [0x00, 0x80, 0x00],
+];
+
+const PATTERN_COLUMNS: usize = 4;
+/// Distance between swatch centers, in world units.
+const PATTERN_PITCH: f32 = 12.0;
+const SWATCH_SIZE: f32 = 10.0;
+
+/// `TEST_SWATCHES` as CSS hex colors.
+pub fn swatch_css() -> Vec<String> {
+    TEST_SWATCHES.iter().map(|[r, g, b]| format!("#{r:02x}{g:02x}{b:02x}")).collect()
+}
+
+/// Square instances for `TEST_SWATCHES`, centered on the origin, colored in
+/// `input` space the way a caller would pass them.
+pub fn test_pattern_instances(input: ColorSpace) -> Vec<Instance> {
+    let rows = TEST_SWATCHES.len().div_ceil(PATTERN_COLUMNS);
+    TEST_SWATCHES
+        .iter()
+        .enumerate()
+        .map(|(index, rgb)| {
+            let (row, col) = (index / PATTERN_COLUMNS, index % PATTERN_COLUMNS);
+            let color = rgb.map(|c| {
+                let c = c as f32 / 255.0;
+                if input == ColorSpace::Linear { srgb_to_linear(c) } else { c }
+            });
+            Instance {
+                position: [
+                    (col as f32 - (PATTERN_COLUMNS - 1) as f32 * 0.5) * PATTERN_PITCH,
+                    ((rows - 1) as f32 * 0.5 - row as f32) * PATTERN_PITCH,
+                ],
+                scale: SWATCH_SIZE,
+                color,
+                id: index as f32,
+            }
+        })
+        .collect()
+}
+
+/// sRGB transfer function decode, as in the shader.
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
@@ -0,0 +1,86 @@
+//! Renderer events delivered to JS subscribers.
+
+use holi_wasm_events::{Event, EventEmitter};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+    static EVENTS: EventEmitter = const { EventEmitter::new() };
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum RendererEvent {
+    /// Emitted about once per second while frames are rendered; `fps` counts
+    /// rendered frames, so it drops while idle or capped.
+    RendererStats {
+        fps: f32,
+        instances: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Result of a readability guard check of the composite output.
+    /// `margin` (0..1) is how much contrast could still be lost before it stops scanning.
+    Readability {
+        scannable: bool,
+        margin: f32,
+        text: Option<String>,
+    },
+    /// A QR code with new content was decoded from the camera feed.
+    BarcodeScanned { text: String },
+    /// Outcome of every camera frame decode: the text, or why none was found.
+    DecodeResult {
+        text: Option<String>,
+        error: Option<String>,
+    },
+}
+
+impl Event for RendererEvent {
+    fn kind(&self) -> &'static str {
+        match self {
+            RendererEvent::RendererStats { .. } => "rendererStats",
+            RendererEvent::Readability { .. } => "readability",
+            RendererEvent::BarcodeScanned { .. } => "barcodeScanned",
+            RendererEvent::DecodeResult { .. } => "decodeResult",
+        }
+    }
+}
+
+/// An event with the handle of the renderer it came from, delivered as
+/// `{ type, handle, ...fields }`.
+#[derive(Serialize)]
+struct FromRenderer<'a> {
+    handle: u32,
+    #[serde(flatten)]
+    event: &'a RendererEvent,
+}
+
+impl Event for FromRenderer<'_> {
+    fn kind(&self) -> &'static str {
+        self.event.kind()
+    }
+}
+
+/// Deliver `event` from the renderer behind `handle`.
+pub fn emit(handle: u32, event: RendererEvent) {
+    EVENTS.with(|e| e.emit(&FromRenderer { handle, event: &event }));
+}
+
+/// Live JS subscriptions, reported by memory stats.
+pub fn listener_count() -> usize {
+    EVENTS.with(|e| e.listener_count())
+}
+
+/// Register `callback` for events of `kind` (`"*"` for all). Every event
+/// carries the `handle` of the renderer that emitted it. Returns a
+/// subscription id.
+#[wasm_bindgen]
+pub fn subscribe_renderer_events(kind: &str, callback: js_sys::Function) -> u32 {
+    EVENTS.with(|e| e.subscribe(kind, callback))
+}
+
+/// Remove a subscription created by `subscribe_renderer_events`.
+#[wasm_bindgen]
+pub fn unsubscribe_renderer_events(id: u32) -> bool {
+    EVENTS.with(|e| e.unsubscribe(id))
+}
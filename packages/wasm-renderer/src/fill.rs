@@ -0,0 +1,111 @@
+//! Module fill: gradient interpolation and image texture sampling across the QR extent
+
+/// How module colors are interpolated across the QR extent.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Gradient {
+    /// Per-instance colors are used as-is
+    #[default]
+    None,
+    /// Along `angle` (radians, 0 = left to right)
+    Linear { start: [f32; 3], end: [f32; 3], angle: f32 },
+    /// From the center (`start`) out to the corners (`end`)
+    Radial { start: [f32; 3], end: [f32; 3] },
+}
+
+impl Gradient {
+    pub fn parse(kind: &str, start: [f32; 3], end: [f32; 3], angle_deg: f32) -> Option<Self> {
+        Some(match kind.to_lowercase().as_str() {
+            "none" => Self::None,
+            "linear" => Self::Linear { start, end, angle: angle_deg.to_radians() },
+            "radial" => Self::Radial { start, end },
+            _ => return None,
+        })
+    }
+}
+
+/// Fill settings packed into `Uniforms`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Fill {
+    pub gradient: Gradient,
+    pub textured: bool,
+}
+
+impl Fill {
+    /// `[mode, textured, angle, 0]`, `gradient_start` and `gradient_end` uniforms.
+    pub fn uniforms(&self) -> ([f32; 4], [f32; 4], [f32; 4]) {
+        let textured = if self.textured { 1.0 } else { 0.0 };
+        let (mode, start, end, angle) = match self.gradient {
+            Gradient::None => (0.0, [0.0; 3], [0.0; 3], 0.0),
+            Gradient::Linear { start, end, angle } => (1.0, start, end, angle),
+            Gradient::Radial { start, end } => (2.0, start, end, 0.0),
+        };
+        (
+            [mode, textured, angle, 0.0],
+            [start[0], start[1], start[2], 1.0],
+            [end[0], end[1], end[2], 1.0],
+        )
+    }
+}
+
+/// Axis-aligned bounds `[min_x, min_y, max_x, max_y]` covering every module.
+pub fn instance_extent(data: &[crate::mesh::Instance]) -> Option<[f32; 4]> {
+    data.iter().fold(None, |acc, i| {
+        let r = i.scale * 0.5;
+        let b = [i.position[0] - r, i.position[1] - r, i.position[0] + r, i.position[1] + r];
+        Some(match acc {
+            None => b,
+            Some(a) => union_extent(a, b),
+        })
+    })
+}
+
+pub fn union_extent(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])]
+}
+
+/// Upload an RGBA8 image as the fill texture.
+pub fn create_fill_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+) -> wgpu::Texture {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Fill Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        // Images are sRGB encoded; sample them as linear, the space modules are shaded in.
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    texture
+}
+
+/// Placeholder bound while no image is set (the shader skips sampling).
+pub fn create_blank_texture(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+    create_fill_texture(device, queue, &[255, 255, 255, 255], 1, 1)
+}
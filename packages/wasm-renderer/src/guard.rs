@@ -0,0 +1,158 @@
+//! Readability guard: periodically render the composite output off-screen,
+//! read it back and run it through the QR decoder.
+//!
+//! Decoding takes longer than a frame, so the frame callback only takes the
+//! readback (`take_readback`); `check` runs later, outside it, and
+//! `finish` lets the next check start.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::events::RendererEvent;
+
+/// Side of the square off-screen target, in pixels.
+pub const GUARD_SIZE: u32 = 512;
+
+const BYTES_PER_ROW: u32 = GUARD_SIZE * 4;
+
+pub struct ReadabilityGuard {
+    interval_ms: f64,
+    last_check: f64,
+    color: wgpu::Texture,
+    depth: wgpu::Texture,
+    readback: wgpu::Buffer,
+    bgra: bool,
+    in_flight: bool,
+    /// Set by the `map_async` callback: `Some(true)` once the readback is mapped.
+    mapped: Rc<Cell<Option<bool>>>,
+}
+
+impl ReadabilityGuard {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, interval_ms: f64) -> Self {
+        let size = wgpu::Extent3d {
+            width: GUARD_SIZE,
+            height: GUARD_SIZE,
+            depth_or_array_layers: 1,
+        };
+        let color = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Readability Target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // Same format as the surface so the existing pipelines can draw into it
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Readability Depth"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readability Readback"),
+            size: (BYTES_PER_ROW * GUARD_SIZE) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            interval_ms: interval_ms.max(0.0),
+            last_check: f64::NEG_INFINITY,
+            color,
+            depth,
+            readback,
+            bgra: matches!(format, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb),
+            in_flight: false,
+            mapped: Rc::new(Cell::new(None)),
+        }
+    }
+
+    pub fn set_interval(&mut self, interval_ms: f64) {
+        self.interval_ms = interval_ms.max(0.0);
+    }
+
+    /// Whether a new check should be rendered this frame.
+    pub fn is_due(&self, now: f64) -> bool {
+        !self.in_flight && now - self.last_check >= self.interval_ms
+    }
+
+    pub fn views(&self) -> (wgpu::TextureView, wgpu::TextureView) {
+        (
+            self.color.create_view(&wgpu::TextureViewDescriptor::default()),
+            self.depth.create_view(&wgpu::TextureViewDescriptor::default()),
+        )
+    }
+
+    /// Copy the rendered target into the readback buffer.
+    pub fn encode_copy(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.color,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(BYTES_PER_ROW),
+                    rows_per_image: Some(GUARD_SIZE),
+                },
+            },
+            self.color.size(),
+        );
+    }
+
+    /// Start mapping the readback buffer. Call after the copy was submitted.
+    pub fn request_readback(&mut self, now: f64) {
+        self.in_flight = true;
+        self.last_check = now;
+        let mapped = self.mapped.clone();
+        self.readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| mapped.set(Some(result.is_ok())));
+    }
+
+    /// Luma of a finished readback, if any. The check stays in flight until
+    /// `finish`.
+    pub fn take_readback(&mut self) -> Option<Vec<u8>> {
+        let ok = self.mapped.take()?;
+        if !ok {
+            self.in_flight = false;
+            return None;
+        }
+
+        let luma = {
+            let data = self.readback.slice(..).get_mapped_range();
+            let (r, b) = if self.bgra { (2, 0) } else { (0, 2) };
+            data.chunks_exact(4)
+                .map(|p| ((p[r] as u32 * 299 + p[1] as u32 * 587 + p[b] as u32 * 114) / 1000) as u8)
+                .collect::<Vec<u8>>()
+        };
+        self.readback.unmap();
+        Some(luma)
+    }
+
+    /// The check of a readback has been reported.
+    pub fn finish(&mut self) {
+        self.in_flight = false;
+    }
+}
+
+/// Decode a readback from `take_readback`.
+pub fn check(luma: &[u8]) -> RendererEvent {
+    let readability = holi_qr::check_readability(luma, GUARD_SIZE, GUARD_SIZE);
+    RendererEvent::Readability {
+        scannable: readability.is_scannable(),
+        margin: readability.margin,
+        text: readability.text,
+    }
+}
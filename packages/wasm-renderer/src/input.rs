@@ -0,0 +1,188 @@
+//! Demo-mode input: keys and gamepad axes mapped to camera and animation
+//! controls.
+//!
+//! The crate installs no DOM listeners. JS decides which events reach the
+//! renderer and forwards them (`handle_key`, `handle_axis`); this module
+//! keeps what is held and turns it into camera motion each frame.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::layout::Camera;
+
+/// Fitted-content sizes panned per second at zoom 1.
+const PAN_RATE: f32 = 0.5;
+/// Zoom doubles (or halves) in this many seconds.
+const ZOOM_DOUBLING_S: f32 = 1.0;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 16.0;
+/// Animation speed step for `Slower` / `Faster`.
+const SPEED_STEP: f32 = 1.5;
+const MIN_SPEED: f32 = 1.0 / 16.0;
+const MAX_SPEED: f32 = 8.0;
+/// Stick drift below this is ignored.
+const AXIS_DEADZONE: f32 = 0.15;
+
+/// Gamepad axes read, in the standard mapping.
+const AXIS_PAN_X: u32 = 0;
+const AXIS_PAN_Y: u32 = 1;
+const AXIS_ZOOM: u32 = 3;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Control {
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    /// Back to the plain fit and normal speed
+    Reset,
+    /// Animation speed down / up a step
+    Slower,
+    Faster,
+    /// Freeze or resume animations
+    Pause,
+}
+
+impl Control {
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "pan-left" => Self::PanLeft,
+            "pan-right" => Self::PanRight,
+            "pan-up" => Self::PanUp,
+            "pan-down" => Self::PanDown,
+            "zoom-in" => Self::ZoomIn,
+            "zoom-out" => Self::ZoomOut,
+            "reset" => Self::Reset,
+            "slower" => Self::Slower,
+            "faster" => Self::Faster,
+            "pause" => Self::Pause,
+            _ => return None,
+        })
+    }
+}
+
+/// `KeyboardEvent.code` bindings the demo starts with.
+const DEFAULT_BINDINGS: [(&str, Control); 16] = [
+    ("ArrowLeft", Control::PanLeft),
+    ("KeyA", Control::PanLeft),
+    ("ArrowRight", Control::PanRight),
+    ("KeyD", Control::PanRight),
+    ("ArrowUp", Control::PanUp),
+    ("KeyW", Control::PanUp),
+    ("ArrowDown", Control::PanDown),
+    ("KeyS", Control::PanDown),
+    ("Equal", Control::ZoomIn),
+    ("NumpadAdd", Control::ZoomIn),
+    ("Minus", Control::ZoomOut),
+    ("NumpadSubtract", Control::ZoomOut),
+    ("Digit0", Control::Reset),
+    ("BracketLeft", Control::Slower),
+    ("BracketRight", Control::Faster),
+    ("Space", Control::Pause),
+];
+
+pub struct DemoInput {
+    bindings: HashMap<String, Control>,
+    held: HashSet<Control>,
+    /// Gamepad `[pan x, pan y, zoom]` after the deadzone
+    axes: [f32; 3],
+    camera: Camera,
+    speed: f32,
+    paused: bool,
+}
+
+impl Default for DemoInput {
+    fn default() -> Self {
+        Self {
+            bindings: DEFAULT_BINDINGS.iter().map(|(code, c)| (code.to_string(), *c)).collect(),
+            held: HashSet::new(),
+            axes: [0.0; 3],
+            camera: Camera::default(),
+            speed: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl DemoInput {
+    /// Bind `code` to `control`, or unbind it with `None`.
+    pub fn bind(&mut self, code: &str, control: Option<Control>) {
+        match control {
+            Some(control) => self.bindings.insert(code.to_string(), control),
+            None => self.bindings.remove(code),
+        };
+    }
+
+    /// A key went down or up. Returns whether `code` is bound, so the page
+    /// can stop the browser acting on it.
+    pub fn key(&mut self, code: &str, pressed: bool) -> bool {
+        let Some(&control) = self.bindings.get(code) else { return false };
+        if !pressed {
+            self.held.remove(&control);
+            return true;
+        }
+        // Key repeat: held controls are already moving, one-shots fire once
+        if !self.held.insert(control) {
+            return true;
+        }
+        match control {
+            Control::Reset => {
+                self.camera = Camera::default();
+                self.speed = 1.0;
+                self.paused = false;
+            }
+            Control::Slower => self.speed = (self.speed / SPEED_STEP).max(MIN_SPEED),
+            Control::Faster => self.speed = (self.speed * SPEED_STEP).min(MAX_SPEED),
+            Control::Pause => self.paused = !self.paused,
+            _ => {}
+        }
+        true
+    }
+
+    /// Standard-mapping gamepad axis `index` now reads `value` (-1..1):
+    /// left stick pans, right stick vertical zooms. Other axes are ignored.
+    pub fn axis(&mut self, index: u32, value: f32) {
+        let value = if value.abs() < AXIS_DEADZONE { 0.0 } else { value.clamp(-1.0, 1.0) };
+        match index {
+            AXIS_PAN_X => self.axes[0] = value,
+            // Stick down is positive, world up is positive
+            AXIS_PAN_Y => self.axes[1] = -value,
+            AXIS_ZOOM => self.axes[2] = -value,
+            _ => {}
+        }
+    }
+
+    fn held_axis(&self, negative: Control, positive: Control) -> f32 {
+        let held = |c| if self.held.contains(&c) { 1.0 } else { 0.0 };
+        held(positive) - held(negative)
+    }
+
+    /// Move the camera by what is held for `dt_s` seconds. Returns whether
+    /// it moved.
+    pub fn update(&mut self, dt_s: f32) -> bool {
+        let pan = [
+            (self.held_axis(Control::PanLeft, Control::PanRight) + self.axes[0]).clamp(-1.0, 1.0),
+            (self.held_axis(Control::PanDown, Control::PanUp) + self.axes[1]).clamp(-1.0, 1.0),
+        ];
+        let zoom = (self.held_axis(Control::ZoomOut, Control::ZoomIn) + self.axes[2]).clamp(-1.0, 1.0);
+        if pan == [0.0, 0.0] && zoom == 0.0 {
+            return false;
+        }
+        // Pan at the same on-screen speed whatever the zoom
+        let step = PAN_RATE * dt_s / self.camera.zoom;
+        self.camera.pan[0] += pan[0] * step;
+        self.camera.pan[1] += pan[1] * step;
+        self.camera.zoom = (self.camera.zoom * (zoom * dt_s / ZOOM_DOUBLING_S).exp2()).clamp(MIN_ZOOM, MAX_ZOOM);
+        true
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Multiplier for animation time; 0 while paused.
+    pub fn time_scale(&self) -> f64 {
+        if self.paused { 0.0 } else { self.speed as f64 }
+    }
+}
@@ -0,0 +1,118 @@
+//! Viewport layout: where the QR code sits on the canvas.
+//!
+//! The camera is fitted to the drawn modules (plus the quiet zone) rather
+//! than a fixed world size, so any QR version and canvas aspect ratio lay
+//! out the same way. Padding (e.g. safe-area insets) shrinks the area the
+//! code is fitted into; the rest of the canvas still shows the scene.
+
+use crate::math::ortho_view_projection;
+
+/// Quiet zone around the code, in modules (the QR spec minimum).
+pub const QUIET_ZONE_MODULES: f32 = 4.0;
+
+/// World bounds shown when there is nothing to fit, matching the old fixed
+/// camera's height.
+const EMPTY_EXTENT: [f32; 4] = [-30.0, -30.0, 30.0, 30.0];
+
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum FitMode {
+    /// Whole code visible, as large as fits
+    #[default]
+    Contain,
+    /// Code fills the area, cropped on the longer side
+    Cover,
+    /// Each module drawn this many pixels wide, centered
+    ModuleSize(f32),
+}
+
+impl FitMode {
+    /// `"contain"`, `"cover"` or `"module"` (with `module_px` per module).
+    pub fn parse(name: &str, module_px: f32) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "contain" => Self::Contain,
+            "cover" => Self::Cover,
+            "module" | "module-size" if module_px > 0.0 => Self::ModuleSize(module_px),
+            _ => return None,
+        })
+    }
+}
+
+/// Camera moved by hand (demo mode) on top of the fit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    /// View center offset, in widths / heights of the fitted content
+    pub pan: [f32; 2],
+    /// Magnification; 1 is the plain fit
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self { pan: [0.0, 0.0], zoom: 1.0 }
+    }
+}
+
+/// Fit settings, in CSS pixels as set from JS.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Layout {
+    pub fit: FitMode,
+    /// `[top, right, bottom, left]`
+    pub padding: [f32; 4],
+}
+
+impl Layout {
+    /// The same layout in canvas pixels, `pixel_ratio` per CSS pixel.
+    pub fn scaled(&self, pixel_ratio: f32) -> Self {
+        Self {
+            fit: match self.fit {
+                FitMode::ModuleSize(px) => FitMode::ModuleSize(px * pixel_ratio),
+                fit => fit,
+            },
+            padding: self.padding.map(|p| p * pixel_ratio),
+        }
+    }
+
+    /// View-projection placing `extent` (world bounds of the modules, each
+    /// `module` world units wide) on a `width` x `height` canvas. Sizes in
+    /// `self` are canvas pixels here; see `scaled`. `camera` then moves the
+    /// fitted view.
+    pub fn view_projection(
+        &self,
+        width: f32,
+        height: f32,
+        extent: Option<[f32; 4]>,
+        module: f32,
+        camera: &Camera,
+    ) -> [[f32; 4]; 4] {
+        let [min_x, min_y, max_x, max_y] = match extent {
+            Some(e) => {
+                let quiet = QUIET_ZONE_MODULES * module;
+                [e[0] - quiet, e[1] - quiet, e[2] + quiet, e[3] + quiet]
+            }
+            None => EMPTY_EXTENT,
+        };
+        let [top, right, bottom, left] = self.padding;
+        // Area left after padding, at least a pixel so the scale stays finite
+        let area_w = (width - left - right).max(1.0);
+        let area_h = (height - top - bottom).max(1.0);
+        let content_w = (max_x - min_x).max(1e-3);
+        let content_h = (max_y - min_y).max(1e-3);
+
+        // Canvas pixels per world unit
+        let scale = match self.fit {
+            FitMode::Contain => (area_w / content_w).min(area_h / content_h),
+            FitMode::Cover => (area_w / content_w).max(area_h / content_h),
+            FitMode::ModuleSize(px) => px / module.max(1e-3),
+        } * camera.zoom;
+
+        // Content center lands on the center of the padded area
+        let center = [
+            (min_x + max_x) * 0.5 + camera.pan[0] * content_w,
+            (min_y + max_y) * 0.5 + camera.pan[1] * content_h,
+        ];
+        let area_center = [left + area_w * 0.5, top + area_h * 0.5];
+        let world_left = center[0] - area_center[0] / scale;
+        let world_top = center[1] + area_center[1] / scale;
+        ortho_view_projection(world_left, world_left + width / scale, world_top - height / scale, world_top)
+    }
+}
@@ -2,33 +2,320 @@
 //! 
 //! High-performance 3D rendering module using wgpu.
 //! Provides animated mesh rendering with WebGPU/WebGL fallback.
+//!
+//! `start(canvas)` returns a handle; every other call takes it as its first
+//! argument and does nothing once that renderer is stopped.
 
+mod color;
+mod events;
+mod fill;
+mod guard;
+mod input;
+mod layout;
 mod math;
 mod mesh;
+mod morph;
+mod particles;
 mod pipeline;
+mod scanner;
 mod state;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+    sync::Arc,
+};
 use gloo::render::{request_animation_frame, AnimationFrame};
+use holi_clock::default_clock;
 use wasm_bindgen::prelude::*;
-use web_sys::{HtmlCanvasElement, Window};
+use web_sys::{HtmlCanvasElement, HtmlVideoElement, Window};
 
+pub use events::{subscribe_renderer_events, unsubscribe_renderer_events};
 pub use state::State;
 
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOC: holi_wasm_memory::CountingAllocator = holi_wasm_memory::CountingAllocator;
+
+/// One started canvas: its state and the pending animation frame of its loop.
+struct Renderer {
+    state: Rc<RefCell<State>>,
+    raf: Rc<RefCell<Option<AnimationFrame>>>,
+}
+
 thread_local! {
-    static RAF_HANDLE: RefCell<Option<AnimationFrame>> = const { RefCell::new(None) };
-    static RENDERER_STATE: RefCell<Option<Rc<RefCell<State>>>> = const { RefCell::new(None) };
+    static RENDERERS: RefCell<HashMap<u32, Renderer>> = RefCell::new(HashMap::new());
+    static NEXT_HANDLE: Cell<u32> = const { Cell::new(1) };
+}
+
+/// Whether `stop(handle)` hasn't been called for a started renderer.
+#[cfg(target_arch = "wasm32")]
+fn is_running(handle: u32) -> bool {
+    RENDERERS.with(|r| r.borrow().contains_key(&handle))
+}
+
+/// Run `f` on the renderer behind `handle`; `None` if it isn't running.
+fn with_state<R>(handle: u32, f: impl FnOnce(&mut State) -> R) -> Option<R> {
+    // Release the map before calling in, so `f` may reach other renderers.
+    let state = RENDERERS.with(|r| r.borrow().get(&handle).map(|r| r.state.clone()))?;
+    let result = f(&mut state.borrow_mut());
+    Some(result)
 }
 
 /// Update QR Code Instance Data
-/// data: Flat float32 array [x,y,scale,r,g,b, ...]
+/// data: Flat float32 array [x,y,scale,r,g,b,id, ...]
+/// (`id` is reported by `pick`; -1 for modules that can't be picked)
 #[wasm_bindgen]
-pub fn update_qr(data: &[f32]) {
-    RENDERER_STATE.with(|s| {
-        if let Some(state_rc) = &*s.borrow() {
-            state_rc.borrow_mut().update_instances(data);
-        }
-    });
+pub fn update_qr(handle: u32, data: &[f32]) {
+    with_state(handle, |st| st.update_instances(data));
+}
+
+/// Morph the QR code (layer 0) into new instance data over `duration_ms`.
+/// Modules found near their old spot slide there; the rest fade out or in.
+/// A non-positive duration updates at once, like `update_qr`.
+#[wasm_bindgen]
+pub fn morph_to(handle: u32, data: &[f32], duration_ms: f64) {
+    with_state(handle, |st| st.morph_layer_to(0, data, duration_ms, st.animation_time()));
+}
+
+/// Instance id of the module under a point given in CSS pixels from the
+/// canvas' top-left corner, or `undefined` when there is none.
+#[wasm_bindgen]
+pub fn pick(handle: u32, x: f64, y: f64) -> Option<u32> {
+    let ratio = web_sys::window().map_or(1.0, |w| state::pixel_ratio(&w));
+    with_state(handle, |st| st.pick((x * ratio) as f32, (y * ratio) as f32)).flatten()
+}
+
+/// Highlight the modules with instance id `id` (brighter and slightly
+/// larger), e.g. on hover; `undefined` clears the highlight.
+#[wasm_bindgen]
+pub fn set_highlight(handle: u32, id: Option<u32>) {
+    with_state(handle, |st| st.set_highlight(id));
+}
+
+/// Assemble the QR code (layer 0) from particles that fly in from random
+/// points up to `spread` world units from its centre, for `duration_ms`.
+/// Returns false when the GPU has no compute shaders (WebGL) or there is
+/// nothing to assemble; the code is then simply shown.
+#[wasm_bindgen]
+pub fn start_assembly(handle: u32, spread: f32, duration_ms: f64) -> bool {
+    with_state(handle, |st| st.start_assembly(0, spread, duration_ms, st.animation_time())).unwrap_or(false)
+}
+
+/// Stop the assembly and show the finished code.
+#[wasm_bindgen]
+pub fn stop_assembly(handle: u32) {
+    with_state(handle, |st| st.stop_assembly());
+}
+
+/// Configure the assembly forces: spring `stiffness` toward the module,
+/// `damping` (velocity lost per second), sideways `swirl` and `max_speed`
+/// in world units per second. Applies to the running assembly too.
+#[wasm_bindgen]
+pub fn set_assembly_forces(handle: u32, stiffness: f32, damping: f32, swirl: f32, max_speed: f32) {
+    let forces = particles::Forces { stiffness, damping, swirl, max_speed };
+    with_state(handle, |st| st.set_assembly_forces(forces));
+}
+
+/// Update the instances of one module layer (`0..MAX_LAYERS`).
+/// Layer 0 is the same layer `update_qr` writes to.
+#[wasm_bindgen]
+pub fn update_qr_layer(handle: u32, layer: usize, data: &[f32]) -> Result<(), JsValue> {
+    check_layer(layer)?;
+    with_state(handle, |st| st.update_layer_instances(layer, data));
+    Ok(())
+}
+
+/// Select the mesh a layer is drawn with:
+/// "glow" (default for layer 0), "square", "rounded", "circle"/"dots" or "hexagon".
+#[wasm_bindgen]
+pub fn set_layer_shape(handle: u32, layer: usize, shape: &str) -> Result<(), JsValue> {
+    check_layer(layer)?;
+    let mesh = mesh::LayerMesh::parse(shape)
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown layer shape: {shape}")))?;
+    with_state(handle, |st| st.set_layer_mesh(layer, mesh));
+    Ok(())
+}
+
+/// Forward a `keydown` (`pressed`) or `keyup` to the demo controls, by
+/// `KeyboardEvent.code`: arrows/WASD pan, `=`/`-` zoom, `0` resets, `[`/`]`
+/// change animation speed, Space pauses. Returns whether the key is bound,
+/// so the page can `preventDefault()` it.
+#[wasm_bindgen]
+pub fn handle_key(handle: u32, code: &str, pressed: bool) -> bool {
+    with_state(handle, |st| st.handle_key(code, pressed)).unwrap_or(false)
+}
+
+/// Forward a gamepad axis reading (-1..1, standard mapping), e.g. polled
+/// each frame: the left stick pans, the right stick's vertical axis zooms.
+#[wasm_bindgen]
+pub fn handle_axis(handle: u32, index: u32, value: f32) {
+    with_state(handle, |st| st.handle_axis(index, value));
+}
+
+/// Bind a `KeyboardEvent.code` to a demo control ("pan-left", "pan-right",
+/// "pan-up", "pan-down", "zoom-in", "zoom-out", "reset", "slower", "faster",
+/// "pause"), or unbind it with "none".
+#[wasm_bindgen]
+pub fn bind_key(handle: u32, code: &str, control: &str) -> Result<(), JsValue> {
+    let control = match control {
+        "none" => None,
+        name => Some(
+            input::Control::parse(name).ok_or_else(|| JsValue::from_str(&format!("Unknown control: {name}")))?,
+        ),
+    };
+    with_state(handle, |st| st.bind_key(code, control));
+    Ok(())
+}
+
+/// Render at most `fps` frames per second (0 removes the cap). Frames are
+/// only drawn while something animates or changed, whatever the cap.
+#[wasm_bindgen]
+pub fn set_max_fps(handle: u32, fps: f64) {
+    with_state(handle, |st| st.set_max_fps((fps > 0.0).then_some(fps)));
+}
+
+/// How the QR code is fitted into the canvas: "contain" (default, whole
+/// code plus quiet zone visible), "cover" (fills the canvas, cropped) or
+/// "module" (each module `module_px` CSS pixels wide).
+#[wasm_bindgen]
+pub fn set_fit_mode(handle: u32, mode: &str, module_px: f32) -> Result<(), JsValue> {
+    let fit = layout::FitMode::parse(mode, module_px)
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown fit mode: {mode} (module_px {module_px})")))?;
+    with_state(handle, |st| st.set_layout(layout::Layout { fit, ..st.layout() }));
+    Ok(())
+}
+
+/// Keep the code out of the canvas edges, in CSS pixels, e.g. the
+/// `env(safe-area-inset-*)` values or space taken by overlaid UI.
+#[wasm_bindgen]
+pub fn set_viewport_padding(handle: u32, top: f32, right: f32, bottom: f32, left: f32) {
+    let padding = [top, right, bottom, left].map(|p| p.max(0.0));
+    with_state(handle, |st| st.set_layout(layout::Layout { padding, ..st.layout() }));
+}
+
+/// Composite a layer with "normal" (default) or "additive" blending
+/// (colors add up, for glowing particles).
+#[wasm_bindgen]
+pub fn set_layer_blend(handle: u32, layer: usize, mode: &str) -> Result<(), JsValue> {
+    check_layer(layer)?;
+    let blend = pipeline::BlendMode::parse(mode)
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown blend mode: {mode}")))?;
+    with_state(handle, |st| st.set_layer_blend(layer, blend));
+    Ok(())
+}
+
+/// Color the canvas is cleared to, RGBA in 0..1 in the color space set by
+/// `set_color_space`. The default `[0, 0, 0, 0]` lets the page show through.
+#[wasm_bindgen]
+pub fn set_clear_color(handle: u32, rgba: &[f32]) -> Result<(), JsValue> {
+    let rgba: [f32; 4] = rgba
+        .try_into()
+        .map_err(|_| JsValue::from_str(&format!("Expected 4 color components, got {}", rgba.len())))?;
+    with_state(handle, |st| st.set_clear_color(rgba));
+    Ok(())
+}
+
+/// Interpolate module colors across the QR extent.
+/// `kind`: "linear" (along `angle_deg`, 0 = left to right), "radial" or "none";
+/// `start` / `end`: RGB in 0..1.
+#[wasm_bindgen]
+pub fn set_fill_gradient(handle: u32, kind: &str, start: &[f32], end: &[f32], angle_deg: f32) -> Result<(), JsValue> {
+    let start = rgb(start)?;
+    let end = rgb(end)?;
+    let gradient = fill::Gradient::parse(kind, start, end, angle_deg)
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown gradient kind: {kind}")))?;
+    with_state(handle, |st| st.set_gradient(gradient));
+    Ok(())
+}
+
+/// How instance and gradient colors are read: "srgb" (default, the values
+/// CSS uses, so `#808080` is 0.5) or "linear".
+#[wasm_bindgen]
+pub fn set_color_space(handle: u32, space: &str) -> Result<(), JsValue> {
+    let space = color::ColorSpace::parse(space)
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown color space: {space}")))?;
+    with_state(handle, |st| st.set_color_space(space));
+    Ok(())
+}
+
+/// Draw a 4x4 grid of color swatches instead of the QR code, to check
+/// rendered colors against the same colors in CSS (`color_test_swatches`).
+#[wasm_bindgen]
+pub fn set_color_test_pattern(handle: u32, enabled: bool) {
+    with_state(handle, |st| st.set_color_test_pattern(enabled));
+}
+
+/// CSS hex colors of the test pattern swatches, row-major from the top left.
+#[wasm_bindgen]
+pub fn color_test_swatches() -> Vec<String> {
+    color::swatch_css()
+}
+
+/// Sample an RGBA8 image (row-major, `w * h * 4` bytes) inside dark modules,
+/// stretched across the QR extent and blended by its alpha.
+#[wasm_bindgen]
+pub fn set_fill_texture(handle: u32, rgba: &[u8], w: u32, h: u32) -> Result<(), JsValue> {
+    if rgba.len() as u64 != w as u64 * h as u64 * 4 {
+        return Err(JsValue::from_str(&format!(
+            "Expected {} bytes for a {w}x{h} RGBA image, got {}",
+            w as u64 * h as u64 * 4,
+            rgba.len()
+        )));
+    }
+    with_state(handle, |st| st.set_fill_texture(rgba, w, h)).unwrap_or(Ok(()))
+}
+
+#[wasm_bindgen]
+pub fn clear_fill_texture(handle: u32) {
+    with_state(handle, |st| st.clear_fill_texture());
+}
+
+/// Periodically decode the composite output (effects included) and emit
+/// `readability` events: `{ scannable, margin, text }`.
+/// Pass `enabled = false` to stop; checks run at most every `interval_ms`.
+#[wasm_bindgen]
+pub fn set_readability_guard(handle: u32, enabled: bool, interval_ms: f64) {
+    with_state(handle, |st| st.set_readability_guard(enabled.then_some(interval_ms)));
+}
+
+/// Scan QR codes from a playing `<video>` (e.g. a `getUserMedia` stream).
+/// Frames are drawn behind the QR layers, downsampled to grayscale on the GPU
+/// and decoded; `barcodeScanned` events carry each newly seen code.
+#[wasm_bindgen]
+pub fn start_camera_scan(handle: u32, video: HtmlVideoElement) {
+    with_state(handle, |st| st.start_camera_scan(video));
+}
+
+#[wasm_bindgen]
+pub fn stop_camera_scan(handle: u32) {
+    with_state(handle, |st| st.stop_camera_scan());
+}
+
+fn rgb(c: &[f32]) -> Result<[f32; 3], JsValue> {
+    c.try_into()
+        .map_err(|_| JsValue::from_str(&format!("Expected 3 color components, got {}", c.len())))
+}
+
+fn check_layer(layer: usize) -> Result<(), JsValue> {
+    if layer >= state::MAX_LAYERS {
+        return Err(JsValue::from_str(&format!(
+            "Layer {layer} out of range (max {})",
+            state::MAX_LAYERS - 1
+        )));
+    }
+    Ok(())
+}
+
+/// Run `task` from a zero-delay timeout, after the current frame callback
+/// has returned and the frame was presented.
+#[cfg(target_arch = "wasm32")]
+fn defer(window: &Window, task: impl FnOnce() + 'static) {
+    let task = Closure::once_into_js(task);
+    if let Err(e) = window.set_timeout_with_callback(task.unchecked_ref()) {
+        log::warn!("Failed to schedule a decode: {:?}", e);
+    }
 }
 
 /// Start the WebGPU renderer on a canvas element.
@@ -37,51 +324,125 @@ pub fn update_qr(data: &[f32]) {
 /// * `canvas` - The HTML canvas element to render to
 /// 
 /// # Returns
-/// Ok(()) on success, or a JsValue error on failure
+/// The renderer handle on success, or a JsValue error on failure. Several
+/// canvases can run at once, each with its own handle and render loop.
 #[wasm_bindgen]
 #[cfg(target_arch = "wasm32")]
-pub async fn start(canvas: HtmlCanvasElement) -> Result<(), JsValue> {
+pub async fn start(canvas: HtmlCanvasElement) -> Result<u32, JsValue> {
     console_error_panic_hook::set_once();
     
     let window = web_sys::window().ok_or("no global window")?;
-    let state = State::new(&canvas).await?;
+    let state = State::new(&canvas, Arc::new(default_clock())).await?;
     
     let state = Rc::new(RefCell::new(state));
     let canvas = Rc::new(canvas);
 
-    fn schedule(state: Rc<RefCell<State>>, canvas: Rc<HtmlCanvasElement>, window: Rc<Window>) {
-        let handle = request_animation_frame(move |_ts| {
-            let now = js_sys::Date::now();
-            let start_time = state.borrow().start_time();
+    fn schedule(
+        handle: u32,
+        state: Rc<RefCell<State>>,
+        raf: Rc<RefCell<Option<AnimationFrame>>>,
+        canvas: Rc<HtmlCanvasElement>,
+        window: Rc<Window>,
+    ) {
+        let slot = raf.clone();
+        let frame = request_animation_frame(move |_ts| {
+            let (now, start_time) = {
+                let st = state.borrow();
+                (st.now(), st.start_time())
+            };
             let t = ((now - start_time) / 1000.0) as f32;
 
-            {
+            let (stats, readability, scan) = {
                 let mut st = state.borrow_mut();
                 st.resize_if_needed(&window, &canvas);
-                st.render(t);
+                let anim = st.tick(now);
+                // Idle or over the frame cap: keep the last frame on screen
+                let stats = if st.frame_due(now) {
+                    st.advance_morph(anim);
+                    st.advance_particles(anim);
+                    st.render(t, now);
+                    st.record_frame(now)
+                } else {
+                    None
+                };
+                let readability = st.readability_tick(t, now);
+                (stats, readability, st.poll_camera_scan())
+            };
+            // Emit outside the borrow so listeners can call back into the renderer.
+            if let Some(stats) = stats {
+                events::emit(handle, stats);
+            }
+            // Decoding takes longer than a frame; run it after this callback
+            if let Some(luma) = readability {
+                let state = state.clone();
+                defer(&window, move || {
+                    let event = guard::check(&luma);
+                    state.borrow_mut().finish_readability();
+                    events::emit(handle, event);
+                });
+            }
+            if let Some(frame) = scan {
+                let state = state.clone();
+                defer(&window, move || {
+                    let decoded = frame.decode();
+                    let events = state.borrow_mut().finish_camera_scan(decoded);
+                    for event in events {
+                        events::emit(handle, event);
+                    }
+                });
             }
 
-            schedule(state.clone(), canvas.clone(), window.clone());
+            // A listener may have stopped this renderer; don't re-arm its loop then.
+            if is_running(handle) {
+                schedule(handle, state.clone(), raf.clone(), canvas.clone(), window.clone());
+            }
         });
 
-        RAF_HANDLE.with(|h| *h.borrow_mut() = Some(handle));
+        *slot.borrow_mut() = Some(frame);
     }
 
-    schedule(state.clone(), canvas, Rc::new(window));
-    
-    // Store in global for update access
-    RENDERER_STATE.with(|s| *s.borrow_mut() = Some(state));
-    
-    Ok(())
+    let handle = NEXT_HANDLE.with(|n| n.replace(n.get() + 1));
+    let raf = Rc::new(RefCell::new(None));
+    RENDERERS.with(|r| r.borrow_mut().insert(handle, Renderer { state: state.clone(), raf: raf.clone() }));
+    schedule(handle, state, raf, canvas, Rc::new(window));
+    Ok(handle)
 }
 
-/// Stop the renderer and release resources.
+/// Stop the renderer behind `handle` and release its resources.
 #[wasm_bindgen]
 #[cfg(target_arch = "wasm32")]
-pub fn stop() {
-    RAF_HANDLE.with(|h| {
-        *h.borrow_mut() = None;
+pub fn stop(handle: u32) {
+    let renderer = RENDERERS.with(|r| r.borrow_mut().remove(&handle));
+    if let Some(renderer) = renderer {
+        // Cancels the pending frame; its closure holds the only other references
+        renderer.raf.borrow_mut().take();
+    }
+}
+
+/// Memory usage of this module: `{ linearMemoryBytes, allocator, caches }`.
+/// `allocator` is null unless built with the `alloc-stats` feature.
+/// GPU figures are those of the renderer behind `handle`, or summed over
+/// every running renderer when it is omitted.
+#[wasm_bindgen]
+pub fn renderer_memory_stats(handle: Option<u32>) -> JsValue {
+    let (renderers, gpu_buffers, gpu_buffer_bytes, fill_texture_bytes) = RENDERERS.with(|r| {
+        let renderers = r.borrow();
+        renderers
+            .iter()
+            .filter(|(h, _)| handle.is_none_or(|handle| handle == **h))
+            .fold((renderers.len(), 0, 0, 0), |(n, count, bytes, fill), (_, renderer)| {
+                let state = renderer.state.borrow();
+                let (c, b) = state.gpu_buffer_usage();
+                (n, count + c, bytes + b, fill + state.fill_texture_bytes())
+            })
     });
+    holi_wasm_memory::MemoryStats::collect()
+        .with_cache("renderers", renderers)
+        .with_cache("gpuBuffers", gpu_buffers)
+        .with_cache("gpuBufferBytes", gpu_buffer_bytes as usize)
+        .with_cache("fillTextureBytes", fill_texture_bytes as usize)
+        .with_cache("eventListeners", events::listener_count())
+        .to_js()
 }
 
 /// Get the version info for this module
@@ -89,3 +450,12 @@ pub fn stop() {
 pub fn renderer_version() -> String {
     "holi-wasm-renderer v0.1.0".to_string()
 }
+
+/// Which build of this module is running:
+/// `{ package, version, gitHash, features, protocols }`.
+#[wasm_bindgen]
+pub fn renderer_build_info() -> JsValue {
+    holi_wasm_build_info::BuildInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .with_feature("alloc-stats", cfg!(feature = "alloc-stats"))
+        .to_js()
+}
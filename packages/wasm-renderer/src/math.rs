@@ -1,11 +1,13 @@
 //! Math utilities for 3D rendering
 
 /// Subtract two 3D vectors
+#[allow(dead_code)]
 pub fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
     [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
 }
 
 /// Cross product of two 3D vectors
+#[allow(dead_code)]
 pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
     [
         a[1] * b[2] - a[2] * b[1],
@@ -15,6 +17,7 @@ pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
 }
 
 /// Normalize a 3D vector
+#[allow(dead_code)]
 pub fn normalize(v: [f32; 3]) -> [f32; 3] {
     let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
     if len == 0.0 { 
@@ -25,6 +28,7 @@ pub fn normalize(v: [f32; 3]) -> [f32; 3] {
 }
 
 /// Dot product of two 3D vectors
+#[allow(dead_code)]
 pub fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
     a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
 }
@@ -40,19 +44,20 @@ pub fn multiply_matrices(a: [[f32; 4]; 4], b: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
     out
 }
 
-/// Generate a combined view-projection matrix for static top-down camera
-pub fn generate_view_projection(width: f32, height: f32, _time: f32) -> [[f32; 4]; 4] {
-    let aspect = width / height;
-    
-    // ORTHOGRAPHIC PROJECTION (Better for 2D readability)
-    // We want to see roughly -20 to +20 range?
-    // Let's assume QR is ~30x30.
-    // Zoom factor:
-    let zoom = 30.0; 
-    let left = -zoom * aspect;
-    let right = zoom * aspect;
-    let bottom = -zoom;
-    let top = zoom;
+/// World point on the z = 0 plane under normalized device coordinates
+/// `ndc`, for a view-projection without rotation out of that plane.
+pub fn unproject(view_proj: [[f32; 4]; 4], ndc: [f32; 2]) -> [f32; 2] {
+    let [a, b] = [view_proj[0][0], view_proj[1][0]];
+    let [c, d] = [view_proj[0][1], view_proj[1][1]];
+    let x = ndc[0] - view_proj[3][0];
+    let y = ndc[1] - view_proj[3][1];
+    let det = a * d - b * c;
+    [(d * x - b * y) / det, (a * y - c * x) / det]
+}
+
+/// Combined view-projection for a static top-down orthographic camera
+/// showing world rectangle `left..right` x `bottom..top` of the z = 0 plane.
+pub fn ortho_view_projection(left: f32, right: f32, bottom: f32, top: f32) -> [[f32; 4]; 4] {
     let near = 0.1;
     let far = 100.0;
 
@@ -67,28 +72,13 @@ pub fn generate_view_projection(width: f32, height: f32, _time: f32) -> [[f32; 4
         [-(right + left) / r_l, -(top + bottom) / t_b, -near / f_n, 1.0],
     ];
 
-    // Ortho is simple, no view matrix needed if we align world to camera
-    // But let's keep a basic view transform just in case we want to pan
-    // Identity View (Camera at 0,0, looking -Z?) 
-    // Wait, our particles are on XZ plane (y=0).
-    // So we need to look from +Y down, or rotate the particles.
-    // Let's Rotate View: look from +Y down.
-    
-    // View matrix (static front view)
-    // Particles are on XY plane. We look from +Z.
-    
-    // Ortho is already set up.
-    // If we use Identity View, we look down -Z. 
-    // Eye at 0,0,0 looking -Z.
-    // So if particles are at Z=0, and Near=0.1, they might be clipped?
-    // We need to move camera BACK on +Z.
-    
-    // LookAt(eye=[0, 0, 50], center=[0, 0, 0], up=[0, 1, 0])
+    // Particles are on the XY plane; the camera sits at z = 50 looking down -Z,
+    // so the world moves -50 relative to it.
     let view = [
         [1.0, 0.0, 0.0, 0.0],
         [0.0, 1.0, 0.0, 0.0], // Y is Up
         [0.0, 0.0, 1.0, 0.0], // Z is Z
-        [0.0, 0.0, -50.0, 1.0], // Translate World relative to camera (Move camera to +50 => world moves -50)
+        [0.0, 0.0, -50.0, 1.0],
     ];
 
     multiply_matrices(proj, view)
@@ -1,7 +1,18 @@
 //! Mesh generation utilities
 
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
 use wgpu::util::DeviceExt;
 
+/// Instances each layer's buffer can hold.
+pub const MAX_INSTANCES: usize = 10_000;
+
+const CIRCLE_SEGMENTS: usize = 32;
+const CORNER_SEGMENTS: usize = 6;
+/// Corner radius of `RoundedSquare`, relative to the module size.
+const CORNER_RADIUS: f32 = 0.2;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
@@ -38,13 +49,30 @@ pub struct Instance {
     pub position: [f32; 2],
     pub scale: f32,
     pub color: [f32; 3],
+    /// Caller-chosen id reported by `pick` and matched by the highlight;
+    /// a whole number below 2^24, or negative for modules that can't be picked.
+    pub id: f32,
 }
 
 impl Instance {
-    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+    const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
         2 => Float32x2,
         3 => Float32,
-        4 => Float32x3
+        4 => Float32x3,
+        8 => Float32
+    ];
+
+    /// Whether world point `p` falls inside this module's square.
+    pub fn contains(&self, p: [f32; 2]) -> bool {
+        let r = self.scale * 0.5;
+        (p[0] - self.position[0]).abs() <= r && (p[1] - self.position[1]).abs() <= r
+    }
+
+    /// Morph start instances, bound as a second instance buffer.
+    const FROM_ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        5 => Float32x2,
+        6 => Float32,
+        7 => Float32x3
     ];
 
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
@@ -54,6 +82,13 @@ impl Instance {
             attributes: &Self::ATTRIBS,
         }
     }
+
+    pub fn from_desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            attributes: &Self::FROM_ATTRIBS,
+            ..Self::desc()
+        }
+    }
 }
 
 /// Create a single quad mesh (centered at 0,0, radius 0.5)
@@ -89,3 +124,142 @@ pub fn create_quad_mesh(device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer, u
     (vertex_buffer, index_buffer, indices.len() as u32)
 }
 
+
+/// Vertex of the solid shape meshes. Shapes need no UVs, so the layout is
+/// just a 2D position (see `create_shape_pipeline`).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShapeVertex {
+    pub position: [f32; 2],
+}
+
+impl ShapeVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x2];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ShapeVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+/// Per-layer module mesh. Names follow the SVG `BodyShape` options.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum LayerMesh {
+    /// Soft glowing quad (the original particle look)
+    #[default]
+    Glow,
+    Square,
+    RoundedSquare,
+    Circle,
+    Hexagon,
+}
+
+impl LayerMesh {
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "glow" => Self::Glow,
+            "square" => Self::Square,
+            "rounded" => Self::RoundedSquare,
+            "dots" | "circle" => Self::Circle,
+            "hexagon" => Self::Hexagon,
+            _ => return None,
+        })
+    }
+
+    /// Whether this mesh is drawn with the solid shape pipeline.
+    pub fn is_solid(self) -> bool {
+        self != Self::Glow
+    }
+}
+
+/// Triangle fan around the origin through `rim` (closed).
+fn fan(rim: &[[f32; 2]]) -> (Vec<ShapeVertex>, Vec<u16>) {
+    let mut vertices = vec![ShapeVertex { position: [0.0, 0.0] }];
+    vertices.extend(rim.iter().map(|&position| ShapeVertex { position }));
+    let n = rim.len() as u16;
+    let indices = (0..n).flat_map(|i| [0, 1 + i, 1 + (i + 1) % n]).collect();
+    (vertices, indices)
+}
+
+fn polygon(sides: usize, radius: f32, rotation: f32) -> Vec<[f32; 2]> {
+    (0..sides)
+        .map(|i| {
+            let a = rotation + TAU * i as f32 / sides as f32;
+            [radius * a.cos(), radius * a.sin()]
+        })
+        .collect()
+}
+
+fn rounded_square_rim() -> Vec<[f32; 2]> {
+    let inner = 0.5 - CORNER_RADIUS;
+    let corners = [[inner, inner], [-inner, inner], [-inner, -inner], [inner, -inner]];
+    corners
+        .iter()
+        .enumerate()
+        .flat_map(|(c, center)| {
+            (0..=CORNER_SEGMENTS).map(move |i| {
+                let a = c as f32 * FRAC_PI_2 + FRAC_PI_2 * i as f32 / CORNER_SEGMENTS as f32;
+                [center[0] + CORNER_RADIUS * a.cos(), center[1] + CORNER_RADIUS * a.sin()]
+            })
+        })
+        .collect()
+}
+
+/// Geometry of a solid shape, unit-sized and centered at the origin.
+pub fn shape_geometry(shape: LayerMesh) -> (Vec<ShapeVertex>, Vec<u16>) {
+    match shape {
+        LayerMesh::Glow | LayerMesh::Square => fan(&[[0.5, 0.5], [-0.5, 0.5], [-0.5, -0.5], [0.5, -0.5]]),
+        LayerMesh::RoundedSquare => fan(&rounded_square_rim()),
+        LayerMesh::Circle => fan(&polygon(CIRCLE_SEGMENTS, 0.5, 0.0)),
+        // Pointy-top, so neighbouring modules in a row touch on flat sides.
+        LayerMesh::Hexagon => fan(&polygon(6, 0.5, PI / 6.0)),
+    }
+}
+
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+}
+
+/// GPU meshes keyed by shape, created on first use.
+#[derive(Default)]
+pub struct MeshRegistry {
+    meshes: HashMap<LayerMesh, Mesh>,
+}
+
+impl MeshRegistry {
+    pub fn get_or_create(&mut self, device: &wgpu::Device, shape: LayerMesh) -> &Mesh {
+        self.meshes.entry(shape).or_insert_with(|| {
+            if shape == LayerMesh::Glow {
+                let (vertex_buffer, index_buffer, num_indices) = create_quad_mesh(device);
+                return Mesh { vertex_buffer, index_buffer, num_indices };
+            }
+            let (vertices, indices) = shape_geometry(shape);
+            Mesh {
+                vertex_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Shape Vertex Buffer"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+                index_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Shape Index Buffer"),
+                    contents: bytemuck::cast_slice(&indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                }),
+                num_indices: indices.len() as u32,
+            }
+        })
+    }
+
+    pub fn get(&self, shape: LayerMesh) -> Option<&Mesh> {
+        self.meshes.get(&shape)
+    }
+
+    pub fn buffers(&self) -> impl Iterator<Item = &wgpu::Buffer> {
+        self.meshes.values().flat_map(|m| [&m.vertex_buffer, &m.index_buffer])
+    }
+}
@@ -0,0 +1,102 @@
+//! Morph transition between two sets of module instances (old code → new code)
+//!
+//! Modules present in both codes stay put, modules that moved a little slide
+//! to their new spot, and the rest shrink away or grow in. The plan is two
+//! equally long instance lists (`from`, `to`); the vertex shader blends them
+//! by the morph progress uniform.
+
+use std::collections::HashMap;
+
+use crate::mesh::{Instance, MAX_INSTANCES};
+
+/// Modules at most this many module sizes apart are paired and slide.
+const MATCH_RADIUS: f32 = 1.5;
+
+/// Paired start and end instances, same length.
+#[derive(Clone, Debug, Default)]
+pub struct MorphPlan {
+    pub from: Vec<Instance>,
+    pub to: Vec<Instance>,
+}
+
+fn hidden(i: &Instance) -> Instance {
+    Instance { scale: 0.0, ..*i }
+}
+
+fn cell(position: [f32; 2], size: f32) -> (i32, i32) {
+    ((position[0] / size).floor() as i32, (position[1] / size).floor() as i32)
+}
+
+fn distance_sq(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)
+}
+
+/// Pair `old` with `new`: each old module takes the nearest free new module
+/// within `MATCH_RADIUS` module sizes; unpaired old modules shrink to zero
+/// and unpaired new ones grow from zero. Capped at `MAX_INSTANCES` pairs.
+pub fn plan_morph(old: &[Instance], new: &[Instance]) -> MorphPlan {
+    let module = new.iter().chain(old).map(|i| i.scale).find(|s| *s > 0.0).unwrap_or(1.0);
+    let radius = module * MATCH_RADIUS;
+
+    // Spatial hash of new modules, cells one match radius wide
+    let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    for (index, i) in new.iter().enumerate() {
+        grid.entry(cell(i.position, radius)).or_default().push(index);
+    }
+    let mut taken = vec![false; new.len()];
+    let mut plan = MorphPlan::default();
+
+    for o in old {
+        let (cx, cy) = cell(o.position, radius);
+        let nearest = (cx - 1..=cx + 1)
+            .flat_map(|x| (cy - 1..=cy + 1).map(move |y| (x, y)))
+            .filter_map(|c| grid.get(&c))
+            .flatten()
+            .copied()
+            .filter(|&n| !taken[n])
+            .map(|n| (n, distance_sq(o.position, new[n].position)))
+            .filter(|&(_, d)| d <= radius * radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        plan.from.push(*o);
+        match nearest {
+            Some((n, _)) => {
+                taken[n] = true;
+                plan.to.push(new[n]);
+            }
+            None => plan.to.push(hidden(o)),
+        }
+    }
+    for (i, _) in new.iter().zip(&taken).filter(|(_, taken)| !**taken) {
+        plan.from.push(hidden(i));
+        plan.to.push(*i);
+    }
+
+    plan.from.truncate(MAX_INSTANCES);
+    plan.to.truncate(MAX_INSTANCES);
+    plan
+}
+
+/// Smoothstep easing of linear progress `t` in 0..1.
+pub fn ease(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+impl MorphPlan {
+    /// The instances as drawn at eased progress `t`, for starting a new morph
+    /// from the middle of this one.
+    pub fn at(&self, t: f32) -> Vec<Instance> {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        self.from
+            .iter()
+            .zip(&self.to)
+            .map(|(a, b)| Instance {
+                position: [mix(a.position[0], b.position[0]), mix(a.position[1], b.position[1])],
+                scale: mix(a.scale, b.scale),
+                color: [mix(a.color[0], b.color[0]), mix(a.color[1], b.color[1]), mix(a.color[2], b.color[2])],
+                id: b.id,
+            })
+            .filter(|i| i.scale > 0.0)
+            .collect()
+    }
+}
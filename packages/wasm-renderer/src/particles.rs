@@ -0,0 +1,195 @@
+//! Particle assembly: modules fly in from random points and settle into the
+//! QR grid, simulated in a compute pass.
+//!
+//! The pass writes the layer's drawn instances into its own buffer, which
+//! stands in for the layer's instance buffer while the simulation runs, so
+//! the render pipelines are unchanged. WebGL has no compute shaders; there
+//! the assembly is skipped and the code is shown as is.
+
+use wgpu::util::DeviceExt;
+
+use crate::fill::instance_extent;
+use crate::mesh::Instance;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Longest simulation step, so a stalled tab doesn't fling particles away.
+const MAX_STEP_S: f32 = 1.0 / 30.0;
+
+/// Forces acting on each particle, set from JS.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Forces {
+    /// Spring constant pulling a particle to its module.
+    pub stiffness: f32,
+    /// Fraction of velocity lost per second.
+    pub damping: f32,
+    /// Sideways pull relative to the spring, for a spiralling approach.
+    pub swirl: f32,
+    /// Speed limit in world units per second.
+    pub max_speed: f32,
+}
+
+impl Default for Forces {
+    fn default() -> Self {
+        Self { stiffness: 8.0, damping: 3.0, swirl: 2.0, max_speed: 60.0 }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    /// `[dt (s), particle count, stiffness, damping]`
+    sim: [f32; 4],
+    /// `[swirl, max speed, settle radius, 0]`
+    forces: [f32; 4],
+}
+
+/// Whether the adapter can run the simulation.
+pub fn compute_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+}
+
+/// Hash of `(seed, i)` to `0..1`, for scattering start positions.
+fn unit_random(seed: u64, i: u64) -> f32 {
+    let mut z = seed.wrapping_add(i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    ((z ^ (z >> 31)) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// A running assembly of one layer.
+pub struct ParticleSim {
+    pub layer: usize,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+    particles: wgpu::Buffer,
+    goals: wgpu::Buffer,
+    drawn: wgpu::Buffer,
+    count: u32,
+    forces: Forces,
+    settle_radius: f32,
+    start: f64,
+    last: f64,
+    duration: f64,
+}
+
+impl ParticleSim {
+    /// Scatter one particle per instance of `goals` within `spread` world
+    /// units of the code's centre. Caller checks `goals` is not empty.
+    pub fn new(
+        device: &wgpu::Device,
+        layer: usize,
+        goals: &[Instance],
+        spread: f32,
+        forces: Forces,
+        now: f64,
+        duration_ms: f64,
+    ) -> Self {
+        let extent = instance_extent(goals).unwrap_or([0.0; 4]);
+        let center = [(extent[0] + extent[2]) * 0.5, (extent[1] + extent[3]) * 0.5];
+        let seed = now.to_bits();
+        let start: Vec<[f32; 4]> = (0..goals.len() as u64)
+            .map(|i| {
+                let angle = unit_random(seed, 2 * i) * std::f32::consts::TAU;
+                // sqrt for an even spread over the disc
+                let radius = spread * unit_random(seed, 2 * i + 1).sqrt();
+                [center[0] + radius * angle.cos(), center[1] + radius * angle.sin(), 0.0, 0.0]
+            })
+            .collect();
+
+        let storage = |label, contents: &[u8], usage| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE | usage,
+            })
+        };
+        let particles = storage("Particle State", bytemuck::cast_slice(&start), wgpu::BufferUsages::empty());
+        let goals_buffer = storage("Particle Goals", bytemuck::cast_slice(goals), wgpu::BufferUsages::empty());
+        // Starts as the goals, so the first frame before the pass has valid colours
+        let drawn = storage("Particle Instances", bytemuck::cast_slice(goals), wgpu::BufferUsages::VERTEX);
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Params"),
+            size: std::mem::size_of::<Params>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Particle Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("particles.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Particle Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle Bind Group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: particles.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: goals_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: drawn.as_entire_binding() },
+            ],
+        });
+
+        Self {
+            layer,
+            pipeline,
+            bind_group,
+            params,
+            particles,
+            goals: goals_buffer,
+            drawn,
+            count: goals.len() as u32,
+            forces,
+            settle_radius: (spread * 0.25).max(1.0),
+            start: now,
+            last: now,
+            duration: duration_ms,
+        }
+    }
+
+    pub fn set_forces(&mut self, forces: Forces) {
+        self.forces = forces;
+    }
+
+    /// Upload the step from the last call to `now`. Returns false once the
+    /// assembly has run its duration.
+    pub fn step(&mut self, queue: &wgpu::Queue, now: f64) -> bool {
+        let dt = (((now - self.last) / 1000.0) as f32).clamp(0.0, MAX_STEP_S);
+        self.last = now;
+        let f = self.forces;
+        let params = Params {
+            sim: [dt, self.count as f32, f.stiffness, f.damping],
+            forces: [f.swirl, f.max_speed, self.settle_radius, 0.0],
+        };
+        queue.write_buffer(&self.params, 0, bytemuck::cast_slice(&[params]));
+        now - self.start < self.duration
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Particle Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(self.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Instances to draw the layer with while the simulation runs.
+    pub fn instance_buffer(&self) -> &wgpu::Buffer {
+        &self.drawn
+    }
+
+    pub fn buffers(&self) -> impl Iterator<Item = &wgpu::Buffer> {
+        [&self.params, &self.particles, &self.goals, &self.drawn].into_iter()
+    }
+}
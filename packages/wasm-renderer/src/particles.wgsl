@@ -0,0 +1,47 @@
+// Particle assembly: every particle springs toward its QR module
+
+struct Params {
+    sim: vec4<f32>, // x: dt (s), y: particle count, z: stiffness, w: damping
+    forces: vec4<f32>, // x: swirl, y: max speed, z: settle radius
+}
+@group(0) @binding(0) var<uniform> p: Params;
+// xy: position, zw: velocity
+@group(0) @binding(1) var<storage, read_write> particles: array<vec4<f32>>;
+// Target instances, packed [x, y, scale, r, g, b, id]
+@group(0) @binding(2) var<storage, read> goals: array<f32>;
+// Drawn instances, same packing, bound as the layer's instance buffer
+@group(0) @binding(3) var<storage, read_write> drawn: array<f32>;
+
+const STRIDE: u32 = 7u;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= u32(p.sim.y)) {
+        return;
+    }
+    let base = i * STRIDE;
+    let goal = vec2<f32>(goals[base], goals[base + 1u]);
+    let dt = p.sim.x;
+
+    var s = particles[i];
+    let to_goal = goal - s.xy;
+    // Spring toward the module plus a swirl across the spring direction
+    let accel = to_goal * p.sim.z + vec2<f32>(-to_goal.y, to_goal.x) * p.forces.x;
+    var vel = (s.zw + accel * dt) * max(1.0 - p.sim.w * dt, 0.0);
+    let speed = length(vel);
+    if (speed > p.forces.y) {
+        vel *= p.forces.y / speed;
+    }
+    s = vec4<f32>(s.xy + vel * dt, vel);
+    particles[i] = s;
+
+    // Modules grow to full size as their particle arrives
+    let settle = clamp(1.0 - length(goal - s.xy) / max(p.forces.z, 1e-6), 0.25, 1.0);
+    drawn[base] = s.x;
+    drawn[base + 1u] = s.y;
+    drawn[base + 2u] = goals[base + 2u] * settle;
+    for (var k = 3u; k < STRIDE; k++) {
+        drawn[base + k] = goals[base + k];
+    }
+}
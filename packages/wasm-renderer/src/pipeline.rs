@@ -3,6 +3,7 @@
 use crate::mesh::Vertex;
 
 /// WGSL shader for animated wave plane
+#[allow(dead_code)]
 pub const SHADER: &str = r#"
 struct Uniforms {
     view_proj: mat4x4<f32>,
@@ -60,13 +61,104 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 pub struct Uniforms {
     pub view_proj: [[f32; 4]; 4],
     pub time: [f32; 4],
+    /// `[mode (0 none, 1 linear, 2 radial), textured, angle, 0]`
+    pub fill: [f32; 4],
+    pub gradient_start: [f32; 4],
+    pub gradient_end: [f32; 4],
+    /// QR extent in world space: `[min_x, min_y, max_x, max_y]`
+    pub extent: [f32; 4],
+    /// `[eased morph progress (1 when idle), 0, 0, 0]`
+    pub morph: [f32; 4],
+    /// `[highlighted instance id (-1 none), 0, 0, 0]`
+    pub highlight: [f32; 4],
+    /// `[decode input sRGB, encode output sRGB, 0, 0]`, see `color::color_uniform`
+    pub color: [f32; 4],
 }
 
-/// Create the render pipeline
+/// How a layer's modules combine with what is drawn below them.
+///
+/// Both keep the target premultiplied (as the canvas composites it), given
+/// a premultiplied clear color.
+#[repr(usize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Ordinary "over" compositing
+    #[default]
+    Normal = 0,
+    /// Colors add up, for glowing particles
+    Additive = 1,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 2] = [BlendMode::Normal, BlendMode::Additive];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "normal" => Self::Normal,
+            "additive" | "add" => Self::Additive,
+            _ => return None,
+        })
+    }
+
+    fn state(self) -> wgpu::BlendState {
+        match self {
+            Self::Normal => wgpu::BlendState::ALPHA_BLENDING,
+            Self::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendState::ALPHA_BLENDING.alpha,
+            },
+        }
+    }
+}
+
+/// Create the render pipeline (glowing quads)
 pub fn create_pipeline(
     device: &wgpu::Device,
     bind_group_layout: &wgpu::BindGroupLayout,
     format: wgpu::TextureFormat,
+    blend: BlendMode,
+) -> wgpu::RenderPipeline {
+    build_pipeline(
+        device,
+        bind_group_layout,
+        format,
+        blend,
+        "Render Pipeline",
+        ("vs_main", "fs_main"),
+        Vertex::desc(),
+    )
+}
+
+/// Create the pipeline for solid shape meshes (`ShapeVertex` layout)
+pub fn create_shape_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+    blend: BlendMode,
+) -> wgpu::RenderPipeline {
+    build_pipeline(
+        device,
+        bind_group_layout,
+        format,
+        blend,
+        "Shape Pipeline",
+        ("vs_shape", "fs_shape"),
+        crate::mesh::ShapeVertex::desc(),
+    )
+}
+
+fn build_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+    blend: BlendMode,
+    label: &str,
+    (vs_entry, fs_entry): (&str, &str),
+    vertex_layout: wgpu::VertexBufferLayout<'static>,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("Shader"),
@@ -82,23 +174,24 @@ pub fn create_pipeline(
     });
 
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Render Pipeline"),
+        label: Some(label),
         layout: Some(&pipeline_layout),
         vertex: wgpu::VertexState {
             module: &shader,
-            entry_point: Some("vs_main"), // Updated for wgpu 23
+            entry_point: Some(vs_entry), // Updated for wgpu 23
             buffers: &[
-                crate::mesh::Vertex::desc(),
+                vertex_layout,
                 crate::mesh::Instance::desc(),
+                crate::mesh::Instance::from_desc(),
             ],
             compilation_options: wgpu::PipelineCompilationOptions::default(),
         },
         fragment: Some(wgpu::FragmentState {
             module: &shader,
-            entry_point: Some("fs_main"), // Updated for wgpu 23
+            entry_point: Some(fs_entry), // Updated for wgpu 23
             targets: &[Some(wgpu::ColorTargetState {
                 format,
-                blend: Some(wgpu::BlendState::ALPHA_BLENDING), // Enable alpha
+                blend: Some(blend.state()),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
             compilation_options: wgpu::PipelineCompilationOptions::default(),
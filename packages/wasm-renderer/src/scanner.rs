@@ -0,0 +1,424 @@
+//! Camera scanning: video frames are copied straight into a GPU texture,
+//! drawn as the background and downsampled to grayscale on the GPU. Only the
+//! small luma image is read back and handed to the QR decoder.
+//!
+//! The frame callback only takes the readback (`take_frame`); the decode
+//! runs later, outside it, and `finish` reports it and lets the next frame
+//! be scanned.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use web_sys::HtmlVideoElement;
+
+use crate::events::RendererEvent;
+
+/// Width of the grayscale image given to the decoder; height follows the video.
+pub const SCAN_WIDTH: u32 = 640;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VideoUniforms {
+    params: [f32; 4],
+}
+
+/// A grayscale camera frame waiting to be decoded.
+pub struct ScanFrame {
+    luma: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl ScanFrame {
+    pub fn decode(self) -> Result<String, String> {
+        holi_qr::decode_luma(self.luma, self.width, self.height).map_err(|e| e.to_string())
+    }
+}
+
+pub struct CameraScanner {
+    video: HtmlVideoElement,
+    video_texture: Option<wgpu::Texture>,
+    background_uniforms: wgpu::Buffer,
+    scan_uniforms: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    background_bind_group: Option<wgpu::BindGroup>,
+    scan_bind_group: Option<wgpu::BindGroup>,
+    background_pipeline: wgpu::RenderPipeline,
+    grayscale_pipeline: wgpu::RenderPipeline,
+    gray_target: Option<wgpu::Texture>,
+    readback: Option<wgpu::Buffer>,
+    padded_row: u32,
+    in_flight: bool,
+    /// Set by the `map_async` callback: `Some(true)` once the readback is mapped.
+    mapped: Rc<Cell<Option<bool>>>,
+    last_text: Option<String>,
+    srgb_target: bool,
+}
+
+impl CameraScanner {
+    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat, video: HtmlVideoElement) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Video Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("video.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Video Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Video Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let fullscreen_pipeline = |label, fs_entry, format, depth_stencil| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_fullscreen"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(fs_entry),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        // Drawn inside the main pass, so it has to match its depth attachment
+        let background_pipeline = fullscreen_pipeline(
+            "Video Background Pipeline",
+            "fs_video",
+            surface_format,
+            Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+        );
+        let grayscale_pipeline =
+            fullscreen_pipeline("Video Grayscale Pipeline", "fs_grayscale", wgpu::TextureFormat::R8Unorm, None);
+
+        let uniform_buffer = |label| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: std::mem::size_of::<VideoUniforms>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Video Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            video,
+            video_texture: None,
+            background_uniforms: uniform_buffer("Video Background Uniforms"),
+            scan_uniforms: uniform_buffer("Video Scan Uniforms"),
+            bind_group_layout,
+            sampler,
+            background_bind_group: None,
+            scan_bind_group: None,
+            background_pipeline,
+            grayscale_pipeline,
+            gray_target: None,
+            readback: None,
+            padded_row: 0,
+            in_flight: false,
+            mapped: Rc::new(Cell::new(None)),
+            last_text: None,
+            srgb_target: surface_format.is_srgb(),
+        }
+    }
+
+    /// Copy the current video frame into the video texture.
+    /// Returns false while the video has no frame to show yet.
+    pub fn upload_frame(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) -> bool {
+        let (vw, vh) = (self.video.video_width(), self.video.video_height());
+        // readyState 2 = HAVE_CURRENT_DATA
+        if self.video.ready_state() < 2 || vw == 0 || vh == 0 {
+            return false;
+        }
+
+        let size = wgpu::Extent3d {
+            width: vw,
+            height: vh,
+            depth_or_array_layers: 1,
+        };
+        if self.video_texture.as_ref().map(|t| t.size()) != Some(size) {
+            self.resize(device, size);
+        }
+        let Some(texture) = &self.video_texture else { return false };
+
+        queue.copy_external_image_to_texture(
+            &wgpu::ImageCopyExternalImage {
+                source: wgpu::ExternalImageSource::HTMLVideoElement(self.video.clone()),
+                origin: wgpu::Origin2d::ZERO,
+                flip_y: false,
+            },
+            wgpu::ImageCopyTextureTagged {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+                color_space: wgpu::PredefinedColorSpace::Srgb,
+                premultiplied_alpha: false,
+            },
+            size,
+        );
+
+        // Cover fit: crop the video to the canvas aspect ratio
+        let (canvas_aspect, video_aspect) = (width as f32 / height.max(1) as f32, vw as f32 / vh as f32);
+        let scale = if canvas_aspect > video_aspect {
+            [1.0, video_aspect / canvas_aspect]
+        } else {
+            [canvas_aspect / video_aspect, 1.0]
+        };
+        let linearize = if self.srgb_target { 1.0 } else { 0.0 };
+        queue.write_buffer(
+            &self.background_uniforms,
+            0,
+            bytemuck::cast_slice(&[VideoUniforms { params: [scale[0], scale[1], linearize, 0.0] }]),
+        );
+        queue.write_buffer(
+            &self.scan_uniforms,
+            0,
+            bytemuck::cast_slice(&[VideoUniforms { params: [1.0, 1.0, 0.0, 0.0] }]),
+        );
+        true
+    }
+
+    /// (Re)create every size-dependent resource for a `size` video.
+    fn resize(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        let video_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Video Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            // External image copies need RENDER_ATTACHMENT on the destination
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = video_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = |label, uniforms: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniforms.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            })
+        };
+        self.background_bind_group = Some(bind_group("Video Background Bind Group", &self.background_uniforms));
+        self.scan_bind_group = Some(bind_group("Video Scan Bind Group", &self.scan_uniforms));
+        self.video_texture = Some(video_texture);
+
+        let scan_height = ((SCAN_WIDTH as u64 * size.height as u64 / size.width as u64) as u32).max(1);
+        self.gray_target = Some(device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Scan Target"),
+            size: wgpu::Extent3d {
+                width: SCAN_WIDTH,
+                height: scan_height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        }));
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        self.padded_row = SCAN_WIDTH.div_ceil(align) * align;
+        self.readback = Some(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Scan Readback"),
+            size: (self.padded_row * scan_height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        }));
+        // A pending readback refers to the old buffer; its callback must not
+        // touch the new one
+        self.in_flight = false;
+        self.mapped = Rc::new(Cell::new(None));
+    }
+
+    /// Draw the latest frame behind the QR layers.
+    pub fn draw_background(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if let Some(bind_group) = &self.background_bind_group {
+            render_pass.set_pipeline(&self.background_pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    /// Downsample the frame to grayscale and copy it for readback, unless a
+    /// decode is still pending. Returns true if a readback should be requested.
+    pub fn encode_scan(&self, encoder: &mut wgpu::CommandEncoder) -> bool {
+        let (Some(target), Some(readback), Some(bind_group)) = (&self.gray_target, &self.readback, &self.scan_bind_group)
+        else {
+            return false;
+        };
+        if self.in_flight {
+            return false;
+        }
+
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Scan Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.grayscale_pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: target,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_row),
+                    rows_per_image: Some(target.height()),
+                },
+            },
+            target.size(),
+        );
+        true
+    }
+
+    /// Start mapping the readback buffer. Call after the scan copy was submitted.
+    pub fn request_readback(&mut self) {
+        let Some(readback) = &self.readback else { return };
+        self.in_flight = true;
+        let mapped = self.mapped.clone();
+        readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| mapped.set(Some(result.is_ok())));
+    }
+
+    /// The frame of a finished readback, if any. It stays in flight until
+    /// `finish`.
+    pub fn take_frame(&mut self) -> Option<ScanFrame> {
+        let ok = self.mapped.take()?;
+        let (Some(target), Some(readback)) = (&self.gray_target, &self.readback) else {
+            self.in_flight = false;
+            return None;
+        };
+        if !ok {
+            self.in_flight = false;
+            return None;
+        }
+
+        let (width, height) = (target.width(), target.height());
+        let luma = {
+            let data = readback.slice(..).get_mapped_range();
+            data.chunks_exact(self.padded_row as usize)
+                .flat_map(|row| &row[..width as usize])
+                .copied()
+                .collect::<Vec<u8>>()
+        };
+        readback.unmap();
+        Some(ScanFrame { luma, width, height })
+    }
+
+    /// Report the decode of a `take_frame` frame: a `DecodeResult` for it,
+    /// and `BarcodeScanned` when a code with new content comes into view.
+    pub fn finish(&mut self, decoded: Result<String, String>) -> Vec<RendererEvent> {
+        self.in_flight = false;
+        let text = match decoded {
+            Ok(text) => text,
+            Err(error) => return vec![RendererEvent::DecodeResult { text: None, error: Some(error) }],
+        };
+        let mut events = vec![RendererEvent::DecodeResult { text: Some(text.clone()), error: None }];
+        if self.last_text.as_deref() != Some(text.as_str()) {
+            self.last_text = Some(text.clone());
+            events.push(RendererEvent::BarcodeScanned { text });
+        }
+        events
+    }
+}
@@ -4,8 +4,65 @@
 struct Uniforms {
     view_proj: mat4x4<f32>,
     time: vec4<f32>, 
+    fill: vec4<f32>, // x: gradient mode (0 none, 1 linear, 2 radial), y: textured, z: angle
+    gradient_start: vec4<f32>,
+    gradient_end: vec4<f32>,
+    extent: vec4<f32>, // min_x, min_y, max_x, max_y
+    morph: vec4<f32>, // x: eased morph progress, 1 when idle
+    highlight: vec4<f32>, // x: highlighted instance id, -1 for none
+    color: vec4<f32>, // x: decode input colors from sRGB, y: encode output to sRGB
 }
 @group(0) @binding(0) var<uniform> u: Uniforms;
+@group(0) @binding(1) var fill_texture: texture_2d<f32>;
+@group(0) @binding(2) var fill_sampler: sampler;
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((max(c, vec3<f32>(0.0)) + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(max(c, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+// Instance / gradient color to linear light
+fn input_color(c: vec3<f32>) -> vec3<f32> {
+    return select(c, srgb_to_linear(c), u.color.x > 0.5);
+}
+
+// Shaded linear color for the render target: values above 1 (the glow
+// boost) are scaled down as a whole so hues don't shift, then sRGB-encoded
+// unless the target format does that itself
+fn output_color(c: vec3<f32>) -> vec3<f32> {
+    let mapped = c / max(max(c.r, c.g), max(c.b, 1.0));
+    return select(mapped, linear_to_srgb(mapped), u.color.y > 0.5);
+}
+
+// Module color after gradient / texture fill, evaluated across the QR extent
+fn fill_color(base: vec3<f32>, world: vec2<f32>) -> vec3<f32> {
+    let size = max(u.extent.zw - u.extent.xy, vec2<f32>(1e-6, 1e-6));
+    let p = (world - u.extent.xy) / size;
+    var color = base;
+
+    if (u.fill.x > 1.5) {
+        // Radial: center to corners
+        let t = length(p - vec2<f32>(0.5, 0.5)) / 0.70710678;
+        color = mix(input_color(u.gradient_start.rgb), input_color(u.gradient_end.rgb), clamp(t, 0.0, 1.0));
+    } else if (u.fill.x > 0.5) {
+        // Linear: project onto the gradient direction, scaled so corners span 0..1
+        let dir = vec2<f32>(cos(u.fill.z), sin(u.fill.z));
+        let span = abs(dir.x) + abs(dir.y);
+        let t = dot(p - vec2<f32>(0.5, 0.5), dir) / span + 0.5;
+        color = mix(input_color(u.gradient_start.rgb), input_color(u.gradient_end.rgb), clamp(t, 0.0, 1.0));
+    }
+
+    // Sample unconditionally (uniform control flow), blend only when textured
+    let texel = textureSample(fill_texture, fill_sampler, vec2<f32>(p.x, 1.0 - p.y));
+    return mix(color, texel.rgb, texel.a * u.fill.y);
+}
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -16,13 +73,46 @@ struct InstanceInput {
     @location(2) instance_pos: vec2<f32>, 
     @location(3) instance_scale: f32,
     @location(4) instance_color: vec3<f32>,
+    // Morph start; the same buffer as the target when no morph is running
+    @location(5) from_pos: vec2<f32>,
+    @location(6) from_scale: f32,
+    @location(7) from_color: vec3<f32>,
+    @location(8) instance_id: f32,
+};
+
+struct Module {
+    pos: vec2<f32>,
+    scale: f32,
+    color: vec4<f32>,
+    // 1 for the highlighted module, else 0
+    lit: f32,
 };
 
+// Instance blended from its morph start by the progress uniform. Modules
+// that appear or vanish (scale 0 at one end) fade as well as scale.
+fn morphed(i: InstanceInput) -> Module {
+    let t = u.morph.x;
+    var m: Module;
+    m.pos = mix(i.from_pos, i.instance_pos, t);
+    m.scale = mix(i.from_scale, i.instance_scale, t);
+    let alpha = select(1.0, t, i.from_scale == 0.0) * select(1.0, 1.0 - t, i.instance_scale == 0.0);
+    m.color = vec4<f32>(input_color(mix(i.from_color, i.instance_color, t)), alpha);
+    m.lit = select(0.0, 1.0, u.highlight.x >= 0.0 && abs(i.instance_id - u.highlight.x) < 0.5);
+    m.scale *= 1.0 + 0.15 * m.lit;
+    return m;
+}
+
+// Highlighted modules are lightened after the fill, so gradients show it too
+fn highlighted(color: vec3<f32>, lit: f32) -> vec3<f32> {
+    return mix(color, vec3<f32>(1.0, 1.0, 1.0), 0.4 * lit);
+}
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
     @location(1) uv: vec2<f32>,
     @location(2) world_pos: vec3<f32>,
+    @location(3) lit: f32,
 };
 
 @vertex
@@ -31,17 +121,18 @@ fn vs_main(
     instance: InstanceInput,
 ) -> VertexOutput {
     var out: VertexOutput;
+    let m = morphed(instance);
     
     // Scale the quad based on instance data (liquid effect can modulate this)
-    let scaled_pos = model.position * instance.instance_scale;
+    let scaled_pos = model.position * m.scale;
     
     // Translate to instance grid position
     // Presuming grid is XZ or XY. Let's use XY for 2D.
     // model.position is typically centered at 0,0.
     
     let world_pos = vec3<f32>(
-        scaled_pos.x + instance.instance_pos.x,
-        scaled_pos.y + instance.instance_pos.y,
+        scaled_pos.x + m.pos.x,
+        scaled_pos.y + m.pos.y,
         scaled_pos.z // Z usually 0 or used for depth toggling
     );
 
@@ -51,8 +142,9 @@ fn vs_main(
     out.clip_position = u.view_proj * vec4<f32>(world_pos, 1.0);
     
     // Pass color and UV
-    out.color = vec4<f32>(instance.instance_color, 1.0);
+    out.color = m.color;
     out.uv = model.uv;
+    out.lit = m.lit;
     
     return out;
 }
@@ -61,6 +153,8 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base = highlighted(fill_color(in.color.rgb, in.world_pos.xy), in.lit);
+
     // Soft Particle / Metaball look
     // UV is 0..1. Center is 0.5, 0.5
     let center = vec2<f32>(0.5, 0.5);
@@ -77,7 +171,44 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     
     // Dynamic Color modification
     // Use instance color but boost brightness at center for "hot" look
-    let final_color = in.color.rgb * (1.0 + alpha * 1.5);
+    let final_color = base * (1.0 + alpha * 1.5);
     
-    return vec4<f32>(final_color, alpha * in.color.a);
+    return vec4<f32>(output_color(final_color), alpha * in.color.a);
+}
+
+// Solid shape meshes (square, rounded, circle, hexagon)
+
+struct ShapeVertexInput {
+    @location(0) position: vec2<f32>,
+};
+
+struct ShapeVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) world_pos: vec2<f32>,
+    @location(2) lit: f32,
+};
+
+@vertex
+fn vs_shape(
+    model: ShapeVertexInput,
+    instance: InstanceInput,
+) -> ShapeVertexOutput {
+    var out: ShapeVertexOutput;
+    let m = morphed(instance);
+    let world_pos = vec3<f32>(
+        model.position * m.scale + m.pos,
+        0.0
+    );
+    out.clip_position = u.view_proj * vec4<f32>(world_pos, 1.0);
+    out.color = m.color;
+    out.world_pos = world_pos.xy;
+    out.lit = m.lit;
+    return out;
+}
+
+@fragment
+fn fs_shape(in: ShapeVertexOutput) -> @location(0) vec4<f32> {
+    let color = highlighted(fill_color(in.color.rgb, in.world_pos), in.lit);
+    return vec4<f32>(output_color(color), in.color.a);
 }
@@ -1,33 +1,116 @@
 //! Renderer state management
 
+use std::sync::Arc;
+
+use holi_clock::Clock;
 use wasm_bindgen::prelude::*;
 use web_sys::{HtmlCanvasElement, Window};
 
-use crate::math::generate_view_projection;
-use crate::mesh::{create_quad_mesh, Instance};
-use crate::pipeline::{create_pipeline, Uniforms};
+use crate::color::{clear_color, color_uniform, test_pattern_instances, ColorSpace};
+use crate::events::RendererEvent;
+use crate::scanner::{CameraScanner, ScanFrame};
+use crate::guard::{ReadabilityGuard, GUARD_SIZE};
+use crate::fill::{create_blank_texture, create_fill_texture, instance_extent, union_extent, Fill, Gradient};
+use crate::input::{Control, DemoInput};
+use crate::layout::{Camera, Layout};
+use crate::math::unproject;
+use crate::mesh::{Instance, LayerMesh, MeshRegistry, MAX_INSTANCES};
+use crate::morph::{ease, plan_morph, MorphPlan};
+use crate::particles::{compute_supported, Forces, ParticleSim};
+use crate::pipeline::{create_pipeline, create_shape_pipeline, BlendMode, Uniforms};
 use wgpu::util::DeviceExt;
 
+/// Tolerance when pacing frames to `max_fps`, in milliseconds.
+const FRAME_SLACK_MS: f64 = 2.0;
+
+/// Number of independently shaped module layers.
+pub const MAX_LAYERS: usize = 4;
+
+/// One instanced draw: a mesh shape plus its own instance buffer.
+struct Layer {
+    mesh: LayerMesh,
+    instance_buffer: wgpu::Buffer,
+    num_instances: u32,
+    extent: Option<[f32; 4]>,
+    blend: BlendMode,
+    /// CPU copy of the drawn instances, the start of the next morph.
+    instances: Vec<Instance>,
+}
+
+/// A running morph of one layer: its instance buffer holds `plan.to` and
+/// `from_buffer` holds `plan.from`.
+struct Morph {
+    layer: usize,
+    plan: MorphPlan,
+    target: Vec<Instance>,
+    start: f64,
+    duration: f64,
+    /// Eased progress as of the last `advance_morph`.
+    t: f32,
+}
+
+/// Canvas pixels per CSS pixel.
+pub fn pixel_ratio(window: &Window) -> f64 {
+    window.device_pixel_ratio().min(2.0)
+}
+
 pub struct State {
     surface: wgpu::Surface<'static>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
-    render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    instance_buffer: wgpu::Buffer,
+    /// Glow and shape pipelines, indexed by `BlendMode`.
+    render_pipelines: [wgpu::RenderPipeline; 2],
+    shape_pipelines: [wgpu::RenderPipeline; 2],
+    meshes: MeshRegistry,
+    layers: Vec<Layer>,
     uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
+    fill_sampler: wgpu::Sampler,
+    fill_texture: wgpu::Texture,
+    fill: Fill,
+    guard: Option<ReadabilityGuard>,
+    scanner: Option<CameraScanner>,
+    morph: Option<Morph>,
+    /// Morph start instances, created on the first morph.
+    morph_buffer: Option<wgpu::Buffer>,
+    highlight: Option<u32>,
+    /// Whether the device can run compute passes (not on WebGL).
+    compute: bool,
+    particles: Option<ParticleSim>,
+    forces: Forces,
+    color_space: ColorSpace,
+    /// Swatch instances drawn instead of the layers while the color test
+    /// pattern is on.
+    test_pattern: Option<(wgpu::Buffer, u32)>,
+    /// Canvas clear color, RGBA in `color_space`.
+    clear: [f32; 4],
+    /// Fit and padding, in CSS pixels.
+    layout: Layout,
+    /// Canvas pixels per CSS pixel, as of the last resize.
+    pixel_ratio: f32,
+    input: DemoInput,
+    /// Animation clock in ms: wall time scaled by the demo speed.
+    anim_time: f64,
+    last_tick: f64,
+    /// Something changed since the last rendered frame.
+    dirty: bool,
+    max_fps: Option<f64>,
+    last_frame: f64,
+    /// View-projection of the last rendered frame, for picking.
+    view_proj: [[f32; 4]; 4],
     depth_texture: wgpu::Texture,
     depth_view: wgpu::TextureView,
-    num_indices: u32,
-    num_instances: u32,
+    clock: Arc<dyn Clock>,
     start: f64,
+    frames_in_window: u32,
+    stats_window_start: f64,
 }
 
 impl State {
-    pub async fn new(canvas: &HtmlCanvasElement) -> Result<Self, JsValue> {
+    /// Renderer on `canvas`, with wall time read from `clock`.
+    pub async fn new(canvas: &HtmlCanvasElement, clock: Arc<dyn Clock>) -> Result<Self, JsValue> {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
             ..Default::default()
@@ -47,6 +130,12 @@ impl State {
             .await
             .ok_or_else(|| JsValue::from_str("No suitable GPU adapter"))?;
 
+        let compute = compute_supported(&adapter);
+        let required_limits = if compute {
+            wgpu::Limits::downlevel_defaults()
+        } else {
+            wgpu::Limits::downlevel_webgl2_defaults()
+        };
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
@@ -54,7 +143,8 @@ impl State {
                     required_features: wgpu::Features::empty(),
                     // WebGL2 Defaults for compatibility, but respecting wgpu 23.0 restrictions if any
                     // wgpu::Limits::downlevel_webgl2_defaults() is ideal for broad support.
-                    required_limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+                    // Adapters with compute shaders get the storage buffers particles need.
+                    required_limits: required_limits.using_resolution(adapter.limits()),
                     memory_hints: wgpu::MemoryHints::Performance,
                 },
                 None,
@@ -62,17 +152,11 @@ impl State {
             .await
             .map_err(|e| JsValue::from_str(&format!("request_device failed: {e:?}")))?;
 
-        let (vertex_buffer, index_buffer, num_indices) = create_quad_mesh(&device);
-        
-        // Initial Instance Buffer (Empty)
-        // Capacity for 10k instances
-        let instance_data = vec![Instance { position: [0.0,0.0], scale: 0.0, color: [0.0,0.0,0.0] }; 10000];
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance Buffer"),
-            contents: bytemuck::cast_slice(&instance_data),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
-        let num_instances = 0;
+        let mut meshes = MeshRegistry::default();
+        meshes.get_or_create(&device, LayerMesh::default());
+
+        // Layer 0 exists from the start; further layers are created on first update.
+        let layers = vec![Self::create_layer(&device, LayerMesh::default())];
 
         let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Uniform Buffer"),
@@ -83,26 +167,46 @@ impl State {
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Bind Group Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
         });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            }],
+        let fill_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Fill Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
         });
+        let fill_texture = create_blank_texture(&device, &queue);
+        let bind_group = Self::create_bind_group(&device, &bind_group_layout, &uniform_buffer, &fill_texture, &fill_sampler);
 
         let caps = surface.get_capabilities(&adapter);
         let swapchain_format = caps.formats[0];
@@ -126,7 +230,9 @@ impl State {
         });
         let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let render_pipeline = create_pipeline(&device, &bind_group_layout, swapchain_format);
+        let render_pipelines = BlendMode::ALL.map(|b| create_pipeline(&device, &bind_group_layout, swapchain_format, b));
+        let shape_pipelines =
+            BlendMode::ALL.map(|b| create_shape_pipeline(&device, &bind_group_layout, swapchain_format, b));
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -134,51 +240,480 @@ impl State {
             width,
             height,
             present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: caps.alpha_modes[0],
+            // Premultiplied lets the page show through transparent pixels; an
+            // opaque-only surface shows the clear color's RGB instead
+            alpha_mode: if caps.alpha_modes.contains(&wgpu::CompositeAlphaMode::PreMultiplied) {
+                wgpu::CompositeAlphaMode::PreMultiplied
+            } else {
+                caps.alpha_modes[0]
+            },
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
         surface.configure(&device, &config);
 
+        let now = clock.now_ms() as f64;
         Ok(Self {
             surface,
             device,
             queue,
             config,
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            instance_buffer,
+            render_pipelines,
+            shape_pipelines,
+            meshes,
+            layers,
             uniform_buffer,
+            bind_group_layout,
             bind_group,
+            fill_sampler,
+            fill_texture,
+            fill: Fill::default(),
+            guard: None,
+            scanner: None,
+            morph: None,
+            morph_buffer: None,
+            highlight: None,
+            compute,
+            particles: None,
+            forces: Forces::default(),
+            color_space: ColorSpace::default(),
+            test_pattern: None,
+            clear: [0.0; 4],
+            layout: Layout::default(),
+            pixel_ratio: 1.0,
+            dirty: true,
+            max_fps: None,
+            last_frame: f64::NEG_INFINITY,
+            input: DemoInput::default(),
+            anim_time: now,
+            last_tick: now,
+            view_proj: Layout::default().view_projection(width as f32, height as f32, None, 1.0, &Camera::default()),
             depth_texture,
             depth_view,
-            num_indices,
-            num_instances,
-            start: js_sys::Date::now(),
+            clock,
+            start: now,
+            frames_in_window: 0,
+            stats_window_start: now,
+        })
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, label: &str) -> wgpu::Buffer {
+        // Initial Instance Buffer (Empty)
+        let instance_data = vec![Instance { position: [0.0,0.0], scale: 0.0, color: [0.0,0.0,0.0], id: -1.0 }; MAX_INSTANCES];
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(&instance_data),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+
+    fn create_layer(device: &wgpu::Device, mesh: LayerMesh) -> Layer {
+        let instance_buffer = Self::create_instance_buffer(device, "Instance Buffer");
+        Layer {
+            mesh,
+            instance_buffer,
+            num_instances: 0,
+            extent: None,
+            blend: BlendMode::default(),
+            instances: Vec::new(),
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        fill_texture: &wgpu::Texture,
+        fill_sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        let view = fill_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(fill_sampler),
+                },
+            ],
         })
     }
 
+    /// Make sure `layer` (and every layer below it) exists.
+    fn ensure_layer(&mut self, layer: usize) -> &mut Layer {
+        while self.layers.len() <= layer {
+            let mesh = if self.layers.is_empty() { LayerMesh::default() } else { LayerMesh::Square };
+            self.meshes.get_or_create(&self.device, mesh);
+            self.layers.push(Self::create_layer(&self.device, mesh));
+        }
+        &mut self.layers[layer]
+    }
+
     pub fn update_instances(&mut self, data: &[f32]) {
-        // data layout: [x, y, scale, r, g, b] per instance
+        self.update_layer_instances(0, data);
+    }
+
+    /// Replace the instances of `layer`. Caller checks `layer < MAX_LAYERS`.
+    /// Cancels a morph running on the layer.
+    pub fn update_layer_instances(&mut self, layer: usize, data: &[f32]) {
+        // data layout: [x, y, scale, r, g, b, id] per instance
         let instances: &[Instance] = bytemuck::cast_slice(data);
-        self.num_instances = instances.len() as u32;
+        self.set_layer_instances(layer, instances);
+    }
+
+    fn set_layer_instances(&mut self, layer: usize, instances: &[Instance]) {
+        self.dirty = true;
+        if self.morph.as_ref().is_some_and(|m| m.layer == layer) {
+            self.morph = None;
+        }
+        if self.particles.as_ref().is_some_and(|p| p.layer == layer) {
+            self.particles = None;
+        }
+        // Ensure we don't overflow buffer (MAX_INSTANCES capacity)
+        let instances = &instances[..instances.len().min(MAX_INSTANCES)];
+        let target = self.ensure_layer(layer);
+        target.num_instances = instances.len() as u32;
+        target.extent = instance_extent(instances);
+        target.instances = instances.to_vec();
+
+        if !instances.is_empty() {
+            let buffer = &self.layers[layer].instance_buffer;
+            self.queue.write_buffer(buffer, 0, bytemuck::cast_slice(instances));
+        }
+    }
+
+    /// Animate `layer` from what it shows now to `data` over `duration_ms`.
+    ///
+    /// Starts from the current frame of a morph already running, so a
+    /// morph can be retargeted midway. Only one layer morphs at a time; a
+    /// morph on another layer jumps to its target.
+    pub fn morph_layer_to(&mut self, layer: usize, data: &[f32], duration_ms: f64, now: f64) {
+        self.dirty = true;
+        let target: &[Instance] = bytemuck::cast_slice(data);
+        if self.particles.as_ref().is_some_and(|p| p.layer == layer) {
+            self.particles = None;
+        }
+        if duration_ms <= 0.0 {
+            self.set_layer_instances(layer, target);
+            return;
+        }
+        let current = match self.morph.take() {
+            Some(morph) if morph.layer == layer => morph.plan.at(morph.t),
+            Some(morph) => {
+                self.set_layer_instances(morph.layer, &morph.target);
+                self.ensure_layer(layer).instances.clone()
+            }
+            None => self.ensure_layer(layer).instances.clone(),
+        };
+        let target = &target[..target.len().min(MAX_INSTANCES)];
+        let plan = plan_morph(&current, target);
+
+        let from_buffer = self
+            .morph_buffer
+            .get_or_insert_with(|| Self::create_instance_buffer(&self.device, "Morph Instance Buffer"));
+        let drawn = &mut self.layers[layer];
+        drawn.num_instances = plan.to.len() as u32;
+        drawn.extent = match (instance_extent(&plan.from), instance_extent(&plan.to)) {
+            (Some(a), Some(b)) => Some(union_extent(a, b)),
+            (a, b) => a.or(b),
+        };
+        if !plan.to.is_empty() {
+            self.queue.write_buffer(from_buffer, 0, bytemuck::cast_slice(&plan.from));
+            self.queue.write_buffer(&drawn.instance_buffer, 0, bytemuck::cast_slice(&plan.to));
+        }
+        self.morph = Some(Morph { layer, plan, target: target.to_vec(), start: now, duration: duration_ms, t: 0.0 });
+    }
+
+    /// Step the running morph to `now`; the last step settles the layer on
+    /// the target instances.
+    pub fn advance_morph(&mut self, now: f64) {
+        let Some(morph) = self.morph.as_mut() else { return };
+        let progress = ((now - morph.start) / morph.duration) as f32;
+        if progress < 1.0 {
+            morph.t = ease(progress);
+            return;
+        }
+        if let Some(morph) = self.morph.take() {
+            self.set_layer_instances(morph.layer, &morph.target);
+        }
+    }
+
+    /// Fly the modules of `layer` in from random points within `spread` world
+    /// units and settle them over `duration_ms`. Returns false, leaving the
+    /// layer as is, when compute shaders are unavailable or the layer is empty.
+    pub fn start_assembly(&mut self, layer: usize, spread: f32, duration_ms: f64, now: f64) -> bool {
+        if !self.compute {
+            return false;
+        }
+        if let Some(morph) = self.morph.take_if(|m| m.layer == layer) {
+            self.set_layer_instances(layer, &morph.target);
+        }
+        self.ensure_layer(layer);
+        let goals = &self.layers[layer].instances;
+        if goals.is_empty() {
+            return false;
+        }
+        self.particles = Some(ParticleSim::new(&self.device, layer, goals, spread, self.forces, now, duration_ms));
+        true
+    }
+
+    pub fn stop_assembly(&mut self) {
+        self.dirty = true;
+        self.particles = None;
+    }
+
+    /// Forces for the running assembly and later ones.
+    pub fn set_assembly_forces(&mut self, forces: Forces) {
+        self.forces = forces;
+        if let Some(sim) = self.particles.as_mut() {
+            sim.set_forces(forces);
+        }
+    }
+
+    /// Step the running assembly to `now`; once its duration is up the layer
+    /// shows its own instances again.
+    pub fn advance_particles(&mut self, now: f64) {
+        if let Some(sim) = self.particles.as_mut() {
+            if !sim.step(&self.queue, now) {
+                self.particles = None;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Id of the topmost pickable module under canvas pixel `(x, y)`.
+    /// A morphing layer is tested at its target instances.
+    pub fn pick(&self, x: f32, y: f32) -> Option<u32> {
+        let ndc = [2.0 * x / self.config.width as f32 - 1.0, 1.0 - 2.0 * y / self.config.height as f32];
+        let world = unproject(self.view_proj, ndc);
+        self.layers.iter().enumerate().rev().find_map(|(index, layer)| {
+            let instances = match &self.morph {
+                Some(morph) if morph.layer == index => &morph.target,
+                _ => &layer.instances,
+            };
+            instances.iter().rev().find(|i| i.id >= 0.0 && i.contains(world)).map(|i| i.id as u32)
+        })
+    }
+
+    /// Lighten and enlarge the modules with instance id `id`; `None` clears.
+    pub fn set_highlight(&mut self, id: Option<u32>) {
+        self.dirty = true;
+        self.highlight = id;
+    }
+
+    /// Composite `layer` with `blend` from now on.
+    pub fn set_layer_blend(&mut self, layer: usize, blend: BlendMode) {
+        self.dirty = true;
+        self.ensure_layer(layer).blend = blend;
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.dirty = true;
+        self.layout = layout;
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// World bounds of what is drawn and the module size they are measured in.
+    fn content(&self) -> (Option<[f32; 4]>, f32) {
+        if self.test_pattern.is_some() {
+            return (instance_extent(&test_pattern_instances(self.color_space)), 1.0);
+        }
+        let extent = self.layers.iter().filter_map(|l| l.extent).reduce(union_extent);
+        let module = self
+            .layers
+            .iter()
+            .flat_map(|l| &l.instances)
+            .map(|i| i.scale)
+            .find(|s| *s > 0.0)
+            .unwrap_or(1.0);
+        (extent, module)
+    }
 
-        if self.num_instances > 0 {
-             let bytes: &[u8] = bytemuck::cast_slice(instances);
-             // Ensure we don't overflow buffer (10k capacity)
-             let max_bytes = 10000 * std::mem::size_of::<Instance>();
-             let write_len = bytes.len().min(max_bytes);
-             self.queue.write_buffer(&self.instance_buffer, 0, &bytes[..write_len]);
+    /// Color the canvas is cleared to each frame; transparent by default.
+    pub fn set_clear_color(&mut self, rgba: [f32; 4]) {
+        self.dirty = true;
+        self.clear = rgba;
+    }
+
+    /// Draw `layer` with `mesh` from now on.
+    pub fn set_layer_mesh(&mut self, layer: usize, mesh: LayerMesh) {
+        self.dirty = true;
+        self.meshes.get_or_create(&self.device, mesh);
+        self.ensure_layer(layer).mesh = mesh;
+    }
+
+    /// How instance and gradient colors are read from now on.
+    pub fn set_color_space(&mut self, space: ColorSpace) {
+        self.dirty = true;
+        self.color_space = space;
+        if self.test_pattern.is_some() {
+            self.set_color_test_pattern(true);
         }
     }
 
+    /// Draw the color swatches instead of the QR layers, to compare with the
+    /// same colors in CSS.
+    pub fn set_color_test_pattern(&mut self, enabled: bool) {
+        self.dirty = true;
+        if !enabled {
+            self.test_pattern = None;
+            return;
+        }
+        self.meshes.get_or_create(&self.device, LayerMesh::Square);
+        let swatches = test_pattern_instances(self.color_space);
+        let buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Test Pattern Instances"),
+            contents: bytemuck::cast_slice(&swatches),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        self.test_pattern = Some((buffer, swatches.len() as u32));
+    }
+
+    pub fn set_gradient(&mut self, gradient: Gradient) {
+        self.dirty = true;
+        self.fill.gradient = gradient;
+    }
+
+    /// Replace the fill texture. Caller checks `rgba.len() == width * height * 4`.
+    pub fn set_fill_texture(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<(), JsValue> {
+        let max_dim = self.device.limits().max_texture_dimension_2d;
+        if width == 0 || height == 0 || width > max_dim || height > max_dim {
+            return Err(JsValue::from_str(&format!(
+                "Fill texture size {width}x{height} out of range (max {max_dim})"
+            )));
+        }
+        self.fill_texture = create_fill_texture(&self.device, &self.queue, rgba, width, height);
+        self.fill.textured = true;
+        self.rebind_fill_texture();
+        Ok(())
+    }
+
+    pub fn clear_fill_texture(&mut self) {
+        self.fill_texture = create_blank_texture(&self.device, &self.queue);
+        self.fill.textured = false;
+        self.rebind_fill_texture();
+    }
+
+    fn rebind_fill_texture(&mut self) {
+        self.dirty = true;
+        self.bind_group = Self::create_bind_group(
+            &self.device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.fill_texture,
+            &self.fill_sampler,
+        );
+    }
+
+    /// Size of the fill texture in bytes (RGBA8).
+    pub fn fill_texture_bytes(&self) -> u64 {
+        let size = self.fill_texture.size();
+        size.width as u64 * size.height as u64 * 4
+    }
+
+    /// GPU buffers owned by the renderer and their total size in bytes.
+    pub fn gpu_buffer_usage(&self) -> (usize, u64) {
+        let buffers: Vec<&wgpu::Buffer> = self
+            .meshes
+            .buffers()
+            .chain(self.layers.iter().map(|l| &l.instance_buffer))
+            .chain(self.morph_buffer.iter())
+            .chain(self.particles.iter().flat_map(ParticleSim::buffers))
+            .chain(self.test_pattern.iter().map(|(b, _)| b))
+            .chain(std::iter::once(&self.uniform_buffer))
+            .collect();
+        (buffers.len(), buffers.iter().map(|b| b.size()).sum())
+    }
+
+    /// Forward a key event to the demo controls; returns whether it is bound.
+    pub fn handle_key(&mut self, code: &str, pressed: bool) -> bool {
+        let bound = self.input.key(code, pressed);
+        self.dirty |= bound;
+        bound
+    }
+
+    pub fn handle_axis(&mut self, index: u32, value: f32) {
+        self.input.axis(index, value);
+    }
+
+    pub fn bind_key(&mut self, code: &str, control: Option<Control>) {
+        self.input.bind(code, control);
+    }
+
+    /// Advance the camera and the animation clock to wall time `now`.
+    /// Returns the animation time, to pass to `advance_morph` and friends.
+    pub fn tick(&mut self, now: f64) -> f64 {
+        let dt = (now - self.last_tick).max(0.0);
+        self.last_tick = now;
+        if self.input.update((dt / 1000.0) as f32) {
+            self.dirty = true;
+        }
+        self.anim_time += dt * self.input.time_scale();
+        self.anim_time
+    }
+
+    /// Current animation time, for starting animations.
+    pub fn animation_time(&self) -> f64 {
+        self.anim_time
+    }
+
+    /// Render at most `fps` frames per second; `None` follows the display.
+    pub fn set_max_fps(&mut self, fps: Option<f64>) {
+        self.max_fps = fps.filter(|f| *f > 0.0);
+    }
+
+    /// Whether to render a frame at `now`: only while something is animating
+    /// or changed since the last frame, and no faster than `max_fps`.
+    pub fn frame_due(&self, now: f64) -> bool {
+        let animating = self.morph.is_some() || self.particles.is_some() || self.scanner.is_some();
+        if !self.dirty && !animating {
+            return false;
+        }
+        // Animation frame timestamps jitter, so allow a little early
+        self.max_fps.is_none_or(|fps| now - self.last_frame >= 1000.0 / fps - FRAME_SLACK_MS)
+    }
+
+    /// Current wall time in ms, from the renderer's clock.
+    pub fn now(&self) -> f64 {
+        self.clock.now_ms() as f64
+    }
+
     pub fn start_time(&self) -> f64 {
         self.start
     }
 
+    /// Count a rendered frame. Returns a stats event once per elapsed second.
+    pub fn record_frame(&mut self, now: f64) -> Option<RendererEvent> {
+        self.frames_in_window += 1;
+        let elapsed = now - self.stats_window_start;
+        if elapsed < 1000.0 {
+            return None;
+        }
+        let fps = (self.frames_in_window as f64 * 1000.0 / elapsed) as f32;
+        self.frames_in_window = 0;
+        self.stats_window_start = now;
+        Some(RendererEvent::RendererStats {
+            fps,
+            instances: self.layers.iter().map(|l| l.num_instances).sum(),
+            width: self.config.width,
+            height: self.config.height,
+        })
+    }
+
     pub fn resize_if_needed(&mut self, window: &Window, canvas: &HtmlCanvasElement) {
-        let pixel_ratio = window.device_pixel_ratio().min(2.0);
+        let pixel_ratio = pixel_ratio(window);
+        self.pixel_ratio = pixel_ratio as f32;
         let limits = self.device.limits();
         let max_dim = limits.max_texture_dimension_2d;
 
@@ -196,6 +731,7 @@ impl State {
 
         self.config.width = width;
         self.config.height = height;
+        self.dirty = true;
         self.surface.configure(&self.device, &self.config);
 
         self.depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
@@ -215,14 +751,104 @@ impl State {
         self.depth_view = self.depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
     }
 
-    pub fn render(&mut self, time_s: f32) {
-        let view_proj = generate_view_projection(self.config.width as f32, self.config.height as f32, time_s * 0.5);
+    fn uniforms(&self, width: u32, height: u32, layout: &Layout, camera: &Camera, time_s: f32) -> Uniforms {
+        let (content, module) = self.content();
+        let view_proj = layout.view_projection(width as f32, height as f32, content, module, camera);
 
-        let uniforms = Uniforms {
+        // The test pattern shows the swatch colors untouched
+        let fill = if self.test_pattern.is_some() { Fill::default() } else { self.fill };
+        let (fill, gradient_start, gradient_end) = fill.uniforms();
+        let extent = content.unwrap_or([0.0, 0.0, 1.0, 1.0]);
+        Uniforms {
             view_proj,
             time: [time_s, 0.0, 0.0, 0.0],
-        };
+            fill,
+            gradient_start,
+            gradient_end,
+            extent,
+            morph: [self.morph.as_ref().map_or(1.0, |m| m.t), 0.0, 0.0, 0.0],
+            highlight: [self.highlight.map_or(-1.0, |id| id as f32), 0.0, 0.0, 0.0],
+            color: color_uniform(self.color_space, self.config.format),
+        }
+    }
+
+    /// Draw every layer into `view`.
+    fn encode_scene(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        clear: wgpu::Color,
+        camera_background: bool,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        if let Some(scanner) = self.scanner.as_ref().filter(|_| camera_background) {
+            scanner.draw_background(&mut render_pass);
+        }
+
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        if let Some((swatches, count)) = &self.test_pattern {
+            if let Some(mesh) = self.meshes.get(LayerMesh::Square) {
+                render_pass.set_pipeline(&self.shape_pipelines[BlendMode::Normal as usize]);
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_vertex_buffer(1, swatches.slice(..));
+                render_pass.set_vertex_buffer(2, swatches.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(0..mesh.num_indices, 0, 0..*count);
+            }
+            return;
+        }
+        let morphing = self.morph.as_ref().map(|m| m.layer);
+        for (index, layer) in self.layers.iter().enumerate().filter(|(_, l)| l.num_instances > 0) {
+            let instance_buffer = match &self.particles {
+                Some(sim) if sim.layer == index => sim.instance_buffer(),
+                _ => &layer.instance_buffer,
+            };
+            // Without a morph the start instances are the instances themselves
+            let from_buffer = match &self.morph_buffer {
+                Some(buffer) if morphing == Some(index) => buffer,
+                _ => instance_buffer,
+            };
+            let Some(mesh) = self.meshes.get(layer.mesh) else { continue };
+            let pipelines = if layer.mesh.is_solid() { &self.shape_pipelines } else { &self.render_pipelines };
+            let pipeline = &pipelines[layer.blend as usize];
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            render_pass.set_vertex_buffer(2, from_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..mesh.num_indices, 0, 0..layer.num_instances);
+        }
+    }
+
+    pub fn render(&mut self, time_s: f32, now: f64) {
+        self.dirty = false;
+        self.last_frame = now;
+        let layout = self.layout.scaled(self.pixel_ratio);
+        let uniforms = self.uniforms(self.config.width, self.config.height, &layout, self.input.camera(), time_s);
         self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        self.view_proj = uniforms.view_proj;
 
         let frame = match self.surface.get_current_texture() {
             Ok(frame) => frame,
@@ -234,43 +860,105 @@ impl State {
             label: Some("Render Encoder"),
         });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 0.0, // Transparent clear
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+        let has_frame = match self.scanner.as_mut() {
+            Some(scanner) => scanner.upload_frame(&self.device, &self.queue, self.config.width, self.config.height),
+            None => false,
+        };
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..self.num_instances);
+        if let Some(sim) = &self.particles {
+            sim.encode(&mut encoder);
         }
 
+        // Transparent clear
+        let clear = clear_color(self.clear, self.color_space, self.config.format);
+        self.encode_scene(&mut encoder, &view, &self.depth_view, clear, has_frame);
+        let scanning = has_frame && self.scanner.as_ref().is_some_and(|s| s.encode_scan(&mut encoder));
+
         self.queue.submit(std::iter::once(encoder.finish()));
         frame.present();
+
+        if scanning {
+            if let Some(scanner) = self.scanner.as_mut() {
+                scanner.request_readback();
+            }
+        }
+    }
+
+    /// Draw `video` behind the QR layers and decode its frames.
+    pub fn start_camera_scan(&mut self, video: web_sys::HtmlVideoElement) {
+        self.scanner = Some(CameraScanner::new(&self.device, self.config.format, video));
+    }
+
+    pub fn stop_camera_scan(&mut self) {
+        self.dirty = true;
+        self.scanner = None;
+    }
+
+    /// A camera frame read back since the last call, to decode outside the
+    /// frame callback and hand to `finish_camera_scan`.
+    pub fn poll_camera_scan(&mut self) -> Option<ScanFrame> {
+        self.device.poll(wgpu::Maintain::Poll);
+        self.scanner.as_mut().and_then(|s| s.take_frame())
+    }
+
+    /// Events for a decoded camera frame; lets the next one be scanned.
+    pub fn finish_camera_scan(&mut self, decoded: Result<String, String>) -> Vec<RendererEvent> {
+        self.scanner.as_mut().map(|s| s.finish(decoded)).unwrap_or_default()
+    }
+
+    /// Enable (or retune) the readability guard; `None` turns it off.
+    pub fn set_readability_guard(&mut self, interval_ms: Option<f64>) {
+        match (interval_ms, self.guard.as_mut()) {
+            (None, _) => self.guard = None,
+            (Some(interval), Some(guard)) => guard.set_interval(interval),
+            (Some(interval), None) => {
+                self.guard = Some(ReadabilityGuard::new(&self.device, self.config.format, interval));
+            }
+        }
+    }
+
+    /// Take a finished readability readback and start the next one when due.
+    ///
+    /// The composite is drawn over white into an off-screen target, so the
+    /// check sees the effects exactly as a scanner would on a light page.
+    /// The returned luma goes to `guard::check` outside the frame callback,
+    /// then `finish_readability`.
+    pub fn readability_tick(&mut self, time_s: f32, now: f64) -> Option<Vec<u8>> {
+        self.device.poll(wgpu::Maintain::Poll);
+        let luma = self.guard.as_mut().and_then(|g| g.take_readback());
+
+        if self.guard.as_ref().is_some_and(|g| g.is_due(now)) {
+            // Separate submit, so this write doesn't clobber the on-screen uniforms
+            // Whole code with its quiet zone, whatever the on-screen fit
+            let uniforms = self.uniforms(GUARD_SIZE, GUARD_SIZE, &Layout::default(), &Camera::default(), time_s);
+            self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readability Encoder"),
+            });
+            if let Some(guard) = &self.guard {
+                let (view, depth_view) = guard.views();
+                // An opaque clear color hides the page, so the scanner sees it instead
+                let clear = if self.clear[3] >= 1.0 {
+                    clear_color(self.clear, self.color_space, self.config.format)
+                } else {
+                    wgpu::Color::WHITE
+                };
+                self.encode_scene(&mut encoder, &view, &depth_view, clear, false);
+                guard.encode_copy(&mut encoder);
+            }
+            self.queue.submit(std::iter::once(encoder.finish()));
+            if let Some(guard) = self.guard.as_mut() {
+                guard.request_readback(now);
+            }
+        }
+        luma
+    }
+
+    /// A readability check has been reported; lets the next one start.
+    pub fn finish_readability(&mut self) {
+        if let Some(guard) = self.guard.as_mut() {
+            guard.finish();
+        }
     }
 }
@@ -0,0 +1,40 @@
+// Camera frames: full-screen background and grayscale downsample for scanning
+
+struct VideoUniforms {
+    // xy: UV scale around the center (cover fit), z: 1.0 to linearize for an sRGB target
+    params: vec4<f32>,
+}
+@group(0) @binding(0) var<uniform> v: VideoUniforms;
+@group(0) @binding(1) var video_texture: texture_2d<f32>;
+@group(0) @binding(2) var video_sampler: sampler;
+
+struct FullscreenOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// One oversized triangle covering the viewport
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    var out: FullscreenOutput;
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    // Far plane, so the QR layers always draw on top
+    out.clip_position = vec4<f32>(corner * 2.0 - 1.0, 1.0, 1.0);
+    let uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    out.uv = (uv - 0.5) * v.params.xy + 0.5;
+    return out;
+}
+
+@fragment
+fn fs_video(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(video_texture, video_sampler, in.uv).rgb;
+    let linear = pow(color, vec3<f32>(2.2, 2.2, 2.2));
+    return vec4<f32>(mix(color, linear, v.params.z), 1.0);
+}
+
+@fragment
+fn fs_grayscale(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(video_texture, video_sampler, in.uv).rgb;
+    let luma = dot(color, vec3<f32>(0.299, 0.587, 0.114));
+    return vec4<f32>(luma, luma, luma, 1.0);
+}